@@ -62,8 +62,13 @@ export const sign = async(data, privateKey) => {
         dataBuffer
     );
 
-    // ECDSA P-256 signatures from Web Crypto are in ASN.1 DER format
-    // We need to extract the r and s values and concatenate them
+    // Web Crypto already returns ECDSA signatures as r||s, 32 bytes each for P-256, which is
+    // what the backend expects
+    if (signatureBuffer.byteLength === 64) {
+        return new Uint8Array(signatureBuffer);
+    }
+
+    // Anything else is taken as ASN.1 DER, and the r and s values are extracted and concatenated
     
     // Get the raw signature bytes
     const asn1Signature = new Uint8Array(signatureBuffer);
@@ -5,6 +5,14 @@ import { generateKeyPair, sign } from './crypto';
 export function createWebSocketConnection(url) {
     let socket = null;
     let keyPair = null;
+    // Assigned by the server in its challenge; signed envelopes name it as the sender
+    let clientId = null;
+    // Issued once an offer or answer is verified and renewed as it is used; every later message
+    // has to carry it
+    let sessionToken = null;
+    // The server refuses any message whose seq does not go past the last one it took from us
+    let lastSeq = 0;
+    let outgoing = Promise.resolve();
 
     const { subscribe, set } = writable(null);
 
@@ -21,7 +29,7 @@ export function createWebSocketConnection(url) {
 
         await initializeKeyPair();
 
-        socket = new WebSocket(url);
+        socket = new WebSocket(url, 'peer-conference.v1');
 
         socket.onopen = () => {
             connectionStore.setSignalingStatus('Connected');
@@ -44,6 +52,9 @@ export function createWebSocketConnection(url) {
         };
 
         socket.onclose = () => {
+            // Tokens and sequence numbers are bound to the connection they were used on
+            sessionToken = null;
+            lastSeq = 0;
             connectionStore.setSignalingStatus('Disconnected');
             set(null);
         };
@@ -55,72 +66,101 @@ export function createWebSocketConnection(url) {
         }
     }
 
-    const sendSecureOffer = async() => {
-        if(!keyPair) {
-            await initializeKeyPair();
-        }
-
-        // Make sure offer is defined and available
-        if (!offer) {
-            console.error("Offer is undefined in sendSecureOffer!");
-            return;
-        }
+    // Version 2 signatures cover the whole envelope: the signal type, our client id, the target,
+    // the seq and every payload field but the signature itself, the nonce and timestamp included.
+    // The exact text signed goes along as `signed_data`, so it needs no canonical form.
+    const signEnvelope = async(type, fields, seq) => {
+        const envelope = {
+            signal_type: type,
+            sender_id: clientId,
+            target_id: null,
+            seq: seq,
+            payload: fields
+        };
+        const signedData = JSON.stringify(envelope);
+        const signature = await sign(signedData, keyPair.privateKey);
 
-        const offerJSON = JSON.stringify(offer);
-        console.log("Offer being signed:", offerJSON);
-        
-        const signature = await sign(offerJSON, keyPair.privateKey);
-        console.log("Generated signature length:", signature.length);
-        
-        const nonce = crypto.getRandomValues(new Uint8Array(16));
-        
-        console.log("Public key length:", keyPair.publicKey.length);
-        console.log("Public key first bytes:", Array.from(keyPair.publicKey.slice(0, 5)));
-        
-        const securePayload = {
-            offer: offer,
-            public_key: Array.from(keyPair.publicKey),
+        return {
+            ...fields,
             signature: Array.from(signature),
-            nonce: Array.from(nonce)
-        }
-
-        console.log("Sending secure payload:", securePayload);
-        sendMessage('secure-offer', securePayload);
+            signed_data: signedData
+        };
     }
 
-    const sendSecureAnswer = async() => {
+    const sendSecure = async(type, description) => {
         if (!keyPair) {
             await initializeKeyPair();
         }
 
-        const answerJSON = JSON.stringify(answer);
         const nonce = crypto.getRandomValues(new Uint8Array(16));
-        const signature = await sign(answerJSON, keyPair.privateKey);
-
-        const securePayload = {
-            offer: answer,
+        const fields = {
+            offer: description,
             public_key: Array.from(keyPair.publicKey),
-            signature: Array.from(signature),
-            nonce: Array.from(nonce)
+            nonce: Array.from(nonce),
+            // Unix seconds; the server turns away signatures that are stale or from the future
+            timestamp: Math.floor(Date.now() / 1000),
+            signature_version: 2
+        };
+        return queueMessage(type, (seq) => signEnvelope(type, fields, seq));
+    }
+
+    const sendSecureOffer = async() => {
+        // Make sure offer is defined and available
+        if (!offer) {
+            console.error("Offer is undefined in sendSecureOffer!");
+            return;
         }
 
-        sendMessage('secure-answer', securePayload);
+        return sendSecure('secure-offer', offer);
+    }
+
+    const sendSecureAnswer = async() => {
+        return sendSecure('secure-answer', answer);
     }
 
     function sendMessage(type, payload) {
-        if (socket && socket.readyState === WebSocket.OPEN) {
-            const message = JSON.stringify({
-                signal_type: type,
-                payload: JSON.stringify(payload),
-                sender_id: "",
-                timestamp: Date.now()
-            });
-            socket.send(message);
-        }
+        return queueMessage(type, async() => payload);
+    }
+
+    // Messages leave one at a time in the order they were queued, each numbered with the next seq
+    // as it goes, so the server sees every sender's seq strictly increase even while a signed
+    // message is still being signed. `buildPayload` gets the seq the message will carry.
+    function queueMessage(type, buildPayload) {
+        outgoing = outgoing
+            .then(async() => {
+                if (!socket || socket.readyState !== WebSocket.OPEN) return;
+
+                const seq = lastSeq + 1;
+                const payload = await buildPayload(seq);
+                lastSeq = seq;
+                socket.send(JSON.stringify({
+                    signal_type: type,
+                    payload: JSON.stringify(payload),
+                    sender_id: "",
+                    timestamp: Date.now(),
+                    seq: seq,
+                    message_id: crypto.randomUUID(),
+                    ...(sessionToken && { session_token: sessionToken })
+                }));
+            })
+            .catch((error) => console.error(`Failed to send ${type}:`, error));
+        return outgoing;
     }
 
     function handleSignalingMessage(message) {
         switch (message.signal_type) {
+            case 'challenge':
+                handleChallenge(JSON.parse(message.payload));
+                break;
+            case 'authenticated':
+                console.log("Challenge answered; signaling is open");
+                break;
+            case 'session-token':
+                sessionToken = JSON.parse(message.payload).session_token;
+                break;
+            case 'error':
+                handleServerError(JSON.parse(message.payload));
+                break;
             case 'secure-offer':
                 handleSecureOffer(JSON.parse(message.payload));
                 break;
@@ -138,6 +178,30 @@ export function createWebSocketConnection(url) {
         }
     }
 
+    // The server relays nothing until the connection proves it holds its key by signing the
+    // challenge, canonical JSON of the challenge and our client id
+    const handleChallenge = async(payload) => {
+        await initializeKeyPair();
+        clientId = payload.client_id;
+
+        const challengeJSON = JSON.stringify({
+            challenge: payload.challenge,
+            client_id: payload.client_id
+        });
+        const signature = await sign(challengeJSON, keyPair.privateKey);
+
+        sendMessage('challenge-response', {
+            public_key: Array.from(keyPair.publicKey),
+            signature: Array.from(signature)
+        });
+    }
+
+    // The server names what it refused and why, e.g. `missing-seq` or `session-token-required`
+    function handleServerError(payload) {
+        console.error(`Signaling server refused a message (${payload.code}):`, payload.message);
+        connectionStore.setError(payload);
+    }
+
     function handleSecureOffer(payload) {
         connectionStore.setOffer(payload.offer);
         connectionStore.setRemotePublicKey(payload.public_key);
@@ -0,0 +1,88 @@
+pub mod sqlite;
+
+use crate::crypto::normalize_public_key;
+use crate::storage::StoreResult;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+pub use sqlite::SqliteAbuseStore;
+
+// A participant's complaint about another, with what the reporter attached as evidence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbuseReport {
+    pub report_id: String,
+    pub reporter_id: String,
+    pub reporter_identity: String,
+    pub reported_id: String,
+    pub reported_identity: String,
+    pub reported_public_key: Option<Vec<u8>>,
+    pub room_id: Option<String>,
+    pub reason: String,
+    // Kept verbatim; the server does not interpret it
+    pub evidence: Option<serde_json::Value>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BanKind {
+    // `value` is the base64 public key
+    PublicKey,
+    // `value` is an identity as key pinning names it, e.g. `user:alice` or `acme/cert:bob@example.com`
+    Identity,
+}
+
+impl BanKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BanKind::PublicKey => "public-key",
+            BanKind::Identity => "identity",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "public-key" => Some(BanKind::PublicKey),
+            "identity" => Some(BanKind::Identity),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub kind: BanKind,
+    pub value: String,
+    pub reason: Option<String>,
+    pub banned_by: Option<String>,
+    pub created_at: i64,
+}
+
+// Server-wide bans, unlike the per-room ones a host sets when kicking
+pub trait AbuseStore: Send + Sync {
+    fn save_report(&self, report: &AbuseReport) -> StoreResult<()>;
+    // Newest first
+    fn reports(&self, limit: usize) -> StoreResult<Vec<AbuseReport>>;
+    fn ban(&self, ban: &Ban) -> StoreResult<()>;
+    // Returns whether there was a ban to lift
+    fn unban(&self, kind: BanKind, value: &str) -> StoreResult<bool>;
+    fn find_ban(&self, kind: BanKind, value: &str) -> StoreResult<Option<Ban>>;
+    fn bans(&self) -> StoreResult<Vec<Ban>>;
+}
+
+// Keys are banned by their uncompressed point, so the ban holds whichever encoding the key
+// comes back in
+pub fn public_key_value(public_key: &[u8]) -> String {
+    STANDARD.encode(normalize_public_key(public_key))
+}
+
+// The ban on the client's public key, else on its identity
+pub fn find_ban(store: &dyn AbuseStore, identity: &str, public_key: Option<&[u8]>) -> StoreResult<Option<Ban>> {
+    if let Some(public_key) = public_key {
+        if let Some(ban) = store.find_ban(BanKind::PublicKey, &public_key_value(public_key))? {
+            return Ok(Some(ban));
+        }
+    }
+    store.find_ban(BanKind::Identity, identity)
+}
@@ -0,0 +1,319 @@
+use crate::abuse::{AbuseReport, AbuseStore, Ban, BanKind};
+use crate::storage::encryption::{self, StorageCipher};
+use crate::storage::sqlite::add_column_if_missing;
+use crate::storage::StoreResult;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub struct SqliteAbuseStore {
+    conn: Mutex<Connection>,
+    // Seals what reports say and who they name, and bans, when set. Banned values are then
+    // stored as their blind index, which bans are looked up by, and sealed alongside.
+    cipher: Option<Arc<StorageCipher>>,
+}
+
+impl SqliteAbuseStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> StoreResult<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS abuse_reports (
+                report_id TEXT PRIMARY KEY,
+                reporter_id TEXT NOT NULL,
+                reporter_identity TEXT NOT NULL,
+                reported_id TEXT NOT NULL,
+                reported_identity TEXT NOT NULL,
+                reported_public_key BLOB,
+                room_id TEXT,
+                reason TEXT NOT NULL,
+                evidence TEXT,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS bans (
+                kind TEXT NOT NULL,
+                value TEXT NOT NULL,
+                reason TEXT,
+                banned_by TEXT,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (kind, value)
+            )",
+        )?;
+        add_column_if_missing(&conn, "bans", "sealed_value", "TEXT")?;
+
+        Ok(Self { conn: Mutex::new(conn), cipher: None })
+    }
+
+    pub fn with_cipher(mut self, cipher: Arc<StorageCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    // Re-seals reports and bans that are still plaintext or sealed with a retired key, moving
+    // bans to the current key's blind index; returns how many rows were rewritten
+    pub fn rekey(&self) -> StoreResult<usize> {
+        let Some(cipher) = self.cipher.as_deref() else {
+            return Ok(0);
+        };
+        let stale = |columns: &[Option<String>]| columns.iter().flatten().any(|value| cipher.needs_rekey(value));
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+
+        let reports = {
+            let mut statement = conn.prepare(
+                "SELECT report_id, reporter_identity, reported_identity, reported_public_key, reason, evidence FROM abuse_reports",
+            )?;
+            let rows = statement.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<Vec<u8>>>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })?;
+            rows.filter_map(Result::ok)
+                .filter(|(_, reporter, reported, public_key, reason, evidence)| {
+                    let public_key = public_key.as_ref().map(|key| String::from_utf8_lossy(key).into_owned());
+                    stale(&[Some(reporter.clone()), Some(reported.clone()), public_key, Some(reason.clone()), evidence.clone()])
+                })
+                .collect::<Vec<_>>()
+        };
+        for (report_id, reporter, reported, public_key, reason, evidence) in &reports {
+            let field = |column| encryption::field("abuse_reports", column, report_id);
+            let reseal = |column, value: &str| cipher.seal(&cipher.open_stale(value, &field(column))?, &field(column));
+            let public_key = public_key
+                .as_ref()
+                .map(|key| encryption::open_stale_bytes(cipher, key.clone(), &field("reported_public_key")))
+                .transpose()?
+                .map(|key| encryption::seal_bytes(Some(cipher), &key, &field("reported_public_key")))
+                .transpose()?;
+            conn.execute(
+                "UPDATE abuse_reports SET reporter_identity = ?2, reported_identity = ?3, reported_public_key = ?4,
+                    reason = ?5, evidence = ?6
+                 WHERE report_id = ?1",
+                params![
+                    report_id,
+                    reseal("reporter_identity", reporter)?,
+                    reseal("reported_identity", reported)?,
+                    public_key,
+                    reseal("reason", reason)?,
+                    evidence.as_deref().map(|evidence| reseal("evidence", evidence)).transpose()?,
+                ],
+            )?;
+        }
+
+        let bans = {
+            let mut statement = conn.prepare("SELECT kind, value, sealed_value, reason, banned_by, created_at FROM bans")?;
+            let rows = statement.query_map([], |row| Ok((ban_from_row(row)?, row.get::<_, Option<String>>(2)?)))?;
+            rows.filter_map(Result::ok)
+                .filter(|(ban, sealed_value)| {
+                    sealed_value.is_none() || stale(&[sealed_value.clone(), ban.reason.clone(), ban.banned_by.clone()])
+                })
+                .collect::<Vec<_>>()
+        };
+        let rewritten = reports.len() + bans.len();
+        for (mut ban, sealed_value) in bans {
+            let stored = ban.value.clone();
+            let field = |column| encryption::field("bans", column, &format!("{}:{}", ban.kind.as_str(), stored));
+            let open = |column, value: Option<String>| value.map(|value| cipher.open_stale(&value, &field(column))).transpose();
+            // Rows written without a cipher hold the banned value itself
+            let value = open("value", sealed_value)?.unwrap_or_else(|| stored.clone());
+            let (reason, banned_by) = (open("reason", ban.reason.take())?, open("banned_by", ban.banned_by.take())?);
+            conn.execute("DELETE FROM bans WHERE kind = ?1 AND value = ?2", params![ban.kind.as_str(), stored])?;
+            insert_ban(&conn, Some(cipher), &Ban { value, reason, banned_by, ..ban })?;
+        }
+        Ok(rewritten)
+    }
+
+    fn seal_report(&self, report: &AbuseReport) -> StoreResult<SealedReport> {
+        let cipher = self.cipher.as_deref();
+        let field = |column| encryption::field("abuse_reports", column, &report.report_id);
+        let evidence = report.evidence.as_ref().map(serde_json::to_string).transpose()?;
+        Ok(SealedReport {
+            reporter_identity: encryption::seal(cipher, &report.reporter_identity, &field("reporter_identity"))?,
+            reported_identity: encryption::seal(cipher, &report.reported_identity, &field("reported_identity"))?,
+            reported_public_key: report
+                .reported_public_key
+                .as_deref()
+                .map(|key| encryption::seal_bytes(cipher, key, &field("reported_public_key")))
+                .transpose()?,
+            reason: encryption::seal(cipher, &report.reason, &field("reason"))?,
+            evidence: encryption::seal_optional(cipher, evidence.as_deref(), &field("evidence"))?,
+        })
+    }
+
+    // Opens a ban row, whose `value` is the banned value's blind index under a cipher
+    fn open_ban(&self, mut ban: Ban, sealed_value: Option<String>) -> StoreResult<Ban> {
+        let cipher = self.cipher.as_deref();
+        let field = |column| encryption::field("bans", column, &format!("{}:{}", ban.kind.as_str(), ban.value));
+        let (value_field, reason_field, banned_by_field) = (field("value"), field("reason"), field("banned_by"));
+        if let Some(cipher) = cipher {
+            let sealed_value = sealed_value.ok_or("ban is not sealed; it needs a rekey")?;
+            ban.value = cipher.open(&sealed_value, &value_field)?;
+        }
+        ban.reason = encryption::open_optional(cipher, ban.reason, &reason_field)?;
+        ban.banned_by = encryption::open_optional(cipher, ban.banned_by, &banned_by_field)?;
+        Ok(ban)
+    }
+}
+
+// The columns of a report as they are written
+struct SealedReport {
+    reporter_identity: String,
+    reported_identity: String,
+    reported_public_key: Option<Vec<u8>>,
+    reason: String,
+    evidence: Option<String>,
+}
+
+fn ban_from_row(row: &Row) -> rusqlite::Result<Ban> {
+    let kind: String = row.get(0)?;
+    Ok(Ban {
+        kind: BanKind::parse(&kind).unwrap_or(BanKind::Identity),
+        value: row.get(1)?,
+        reason: row.get(3)?,
+        banned_by: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn insert_ban(conn: &Connection, cipher: Option<&StorageCipher>, ban: &Ban) -> StoreResult<()> {
+    let stored = encryption::lookup_value(cipher, &ban.value);
+    let field = |column| encryption::field("bans", column, &format!("{}:{}", ban.kind.as_str(), stored));
+    let sealed_value = cipher.map(|cipher| cipher.seal(&ban.value, &field("value"))).transpose()?;
+    let reason = encryption::seal_optional(cipher, ban.reason.as_deref(), &field("reason"))?;
+    let banned_by = encryption::seal_optional(cipher, ban.banned_by.as_deref(), &field("banned_by"))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO bans (kind, value, sealed_value, reason, banned_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![ban.kind.as_str(), stored, sealed_value, reason, banned_by, ban.created_at],
+    )?;
+    Ok(())
+}
+
+impl AbuseStore for SqliteAbuseStore {
+    fn save_report(&self, report: &AbuseReport) -> StoreResult<()> {
+        let sealed = self.seal_report(report)?;
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO abuse_reports (report_id, reporter_id, reporter_identity, reported_id, reported_identity,
+                reported_public_key, room_id, reason, evidence, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                report.report_id,
+                report.reporter_id,
+                sealed.reporter_identity,
+                report.reported_id,
+                sealed.reported_identity,
+                sealed.reported_public_key,
+                report.room_id,
+                sealed.reason,
+                sealed.evidence,
+                report.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn reports(&self, limit: usize) -> StoreResult<Vec<AbuseReport>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT report_id, reporter_id, reporter_identity, reported_id, reported_identity,
+                reported_public_key, room_id, reason, evidence, created_at
+             FROM abuse_reports ORDER BY created_at DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit.min(i64::MAX as usize) as i64], |row| {
+            Ok((
+                AbuseReport {
+                    report_id: row.get(0)?,
+                    reporter_id: row.get(1)?,
+                    reporter_identity: row.get(2)?,
+                    reported_id: row.get(3)?,
+                    reported_identity: row.get(4)?,
+                    reported_public_key: row.get(5)?,
+                    room_id: row.get(6)?,
+                    reason: row.get(7)?,
+                    evidence: None,
+                    created_at: row.get(9)?,
+                },
+                row.get::<_, Option<String>>(8)?,
+            ))
+        })?;
+
+        let cipher = self.cipher.as_deref();
+        let mut reports = Vec::new();
+        for row in rows {
+            let (mut report, evidence) = row?;
+            let field = |column| encryption::field("abuse_reports", column, &report.report_id);
+            let (reporter_field, reported_field) = (field("reporter_identity"), field("reported_identity"));
+            let (public_key_field, reason_field, evidence_field) = (field("reported_public_key"), field("reason"), field("evidence"));
+            report.reporter_identity = encryption::open(cipher, report.reporter_identity, &reporter_field)?;
+            report.reported_identity = encryption::open(cipher, report.reported_identity, &reported_field)?;
+            report.reported_public_key = report
+                .reported_public_key
+                .map(|key| encryption::open_bytes(cipher, key, &public_key_field))
+                .transpose()?;
+            report.reason = encryption::open(cipher, report.reason, &reason_field)?;
+            report.evidence = encryption::open_optional(cipher, evidence, &evidence_field)?
+                .and_then(|evidence| serde_json::from_str(&evidence).ok());
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    fn ban(&self, ban: &Ban) -> StoreResult<()> {
+        let cipher = self.cipher.as_deref();
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        // A ban still under a retired key's index would otherwise come back at the next rekey
+        for stored in encryption::lookup_values(cipher, &ban.value) {
+            conn.execute("DELETE FROM bans WHERE kind = ?1 AND value = ?2", params![ban.kind.as_str(), stored])?;
+        }
+        insert_ban(&conn, cipher, ban)
+    }
+
+    fn unban(&self, kind: BanKind, value: &str) -> StoreResult<bool> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut removed = 0;
+        for stored in encryption::lookup_values(self.cipher.as_deref(), value) {
+            removed += conn.execute(
+                "DELETE FROM bans WHERE kind = ?1 AND value = ?2",
+                params![kind.as_str(), stored],
+            )?;
+        }
+        Ok(removed > 0)
+    }
+
+    fn find_ban(&self, kind: BanKind, value: &str) -> StoreResult<Option<Ban>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        for stored in encryption::lookup_values(self.cipher.as_deref(), value) {
+            let ban = conn
+                .query_row(
+                    "SELECT kind, value, sealed_value, reason, banned_by, created_at FROM bans WHERE kind = ?1 AND value = ?2",
+                    params![kind.as_str(), stored],
+                    |row| Ok((ban_from_row(row)?, row.get(2)?)),
+                )
+                .optional()?;
+            if let Some((ban, sealed_value)) = ban {
+                return self.open_ban(ban, sealed_value).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn bans(&self) -> StoreResult<Vec<Ban>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare("SELECT kind, value, sealed_value, reason, banned_by, created_at FROM bans ORDER BY created_at")?;
+        let rows = stmt.query_map([], |row| Ok((ban_from_row(row)?, row.get(2)?)))?;
+        rows.map(|row| {
+            let (ban, sealed_value) = row?;
+            self.open_ban(ban, sealed_value)
+        })
+        .collect()
+    }
+}
@@ -0,0 +1,12 @@
+use crate::config;
+use ring::constant_time::verify_slices_are_equal;
+
+// Admin operations are disabled entirely unless an admin token is configured
+pub fn is_admin_token(token: Option<&str>) -> bool {
+    match (config::get_admin_token(), token) {
+        (Some(expected), Some(token)) => {
+            verify_slices_are_equal(expected.as_bytes(), token.as_bytes()).is_ok()
+        }
+        _ => false,
+    }
+}
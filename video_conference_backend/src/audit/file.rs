@@ -0,0 +1,27 @@
+use crate::audit::{AuditEvent, AuditResult, AuditSink};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+// One JSON record per line, appended and flushed as each event happens
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    pub fn open(path: impl AsRef<Path>) -> AuditResult<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn write(&self, event: &AuditEvent) -> AuditResult<()> {
+        let line = serde_json::to_string(event)?;
+        let mut file = self.file.lock().map_err(|_| "audit log lock poisoned")?;
+        writeln!(file, "{}", line)?;
+        file.flush()?;
+        Ok(())
+    }
+}
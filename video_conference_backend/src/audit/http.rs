@@ -0,0 +1,45 @@
+use crate::audit::{AuditEvent, AuditResult, AuditSink};
+use crate::config;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+// Posts each record as JSON to a collector. Delivery happens on a background task so recording
+// never waits on the network; records are logged and dropped if the collector refuses them, or
+// if they find the queue full behind a slow collector.
+pub struct HttpAuditSink {
+    queue: mpsc::Sender<AuditEvent>,
+}
+
+impl HttpAuditSink {
+    // Needs a running tokio runtime for the delivery task
+    pub fn new(url: String) -> AuditResult<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(config::get_audit_http_connect_timeout())
+            .timeout(config::get_audit_http_timeout())
+            .build()?;
+        let (queue, mut pending) = mpsc::channel::<AuditEvent>(config::get_audit_http_queue_size().max(1));
+        tokio::spawn(async move {
+            while let Some(event) = pending.recv().await {
+                let result = client
+                    .post(&url)
+                    .json(&event)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = result {
+                    eprintln!("[ERROR] Failed to deliver audit record {} to {}: {}", event.sequence, url, e);
+                }
+            }
+        });
+        Ok(Self { queue })
+    }
+}
+
+impl AuditSink for HttpAuditSink {
+    fn write(&self, event: &AuditEvent) -> AuditResult<()> {
+        match self.queue.try_send(event.clone()) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("audit delivery queue is full".into()),
+            Err(TrySendError::Closed(_)) => Err("audit delivery task has stopped".into()),
+        }
+    }
+}
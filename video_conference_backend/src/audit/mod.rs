@@ -0,0 +1,139 @@
+pub mod file;
+pub mod http;
+pub mod syslog;
+
+use crate::models::Client;
+use crate::pinning;
+use crate::signaling::correlation;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+pub use file::FileAuditSink;
+pub use http::HttpAuditSink;
+pub use syslog::SyslogAuditSink;
+
+pub type AuditResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+static AUDIT_SINKS: OnceLock<Vec<Arc<dyn AuditSink>>> = OnceLock::new();
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+// Where audit records go. Sinks only ever append; nothing here reads records back.
+pub trait AuditSink: Send + Sync {
+    fn write(&self, event: &AuditEvent) -> AuditResult<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditKind {
+    AuthFailed,
+    VerificationFailed,
+    Replay,
+    KeyRotated,
+    KeyRevoked,
+    Kick,
+    Ban,
+    Unban,
+    AbuseReport,
+    RateLimited,
+    LockedOut,
+    AdminAction,
+}
+
+impl AuditKind {
+    // Failures are warnings; deliberate actions are notices
+    pub fn is_failure(&self) -> bool {
+        matches!(self, AuditKind::AuthFailed | AuditKind::VerificationFailed | AuditKind::Replay | AuditKind::RateLimited | AuditKind::LockedOut)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    // Increases by one per record within a run, so gaps show records that went missing
+    pub sequence: u64,
+    pub timestamp: String,
+    pub kind: AuditKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addr: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    // Of the client message that led to the event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditKind) -> Self {
+        Self {
+            sequence: 0,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            kind,
+            addr: None,
+            client_id: None,
+            identity: None,
+            room_id: None,
+            reason: None,
+            correlation_id: correlation::current(),
+            details: serde_json::Map::new(),
+        }
+    }
+
+    // Who the event is about
+    pub fn client(mut self, client: &Client) -> Self {
+        self.addr = Some(client.address);
+        self.client_id = Some(client.client_id.clone());
+        self.identity = Some(pinning::identity(client));
+        self.room_id = client.room_id.clone();
+        self
+    }
+
+    pub fn addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = Some(addr);
+        self
+    }
+
+    pub fn room(mut self, room_id: &str) -> Self {
+        self.room_id = Some(room_id.to_string());
+        self
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn detail(mut self, key: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.details.insert(key.to_string(), value);
+        }
+        self
+    }
+}
+
+// Set once at startup; events recorded before then, or with no sinks, are dropped
+pub fn install(sinks: Vec<Arc<dyn AuditSink>>) {
+    if AUDIT_SINKS.set(sinks).is_err() {
+        eprintln!("Audit sinks are already installed; keeping the first ones");
+    }
+}
+
+pub fn record(mut event: AuditEvent) {
+    let Some(sinks) = AUDIT_SINKS.get().filter(|sinks| !sinks.is_empty()) else {
+        return;
+    };
+    event.sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    for sink in sinks {
+        if let Err(e) = sink.write(&event) {
+            eprintln!("[ERROR] Failed to write audit record {}: {}", event.sequence, e);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::audit::{AuditEvent, AuditResult, AuditSink};
+use std::net::{ToSocketAddrs, UdpSocket};
+
+// security/authorization messages
+const FACILITY_AUTHPRIV: u8 = 10;
+const SEVERITY_WARNING: u8 = 4;
+const SEVERITY_NOTICE: u8 = 5;
+
+// RFC 5424 messages over UDP, with the JSON record as the message body
+pub struct SyslogAuditSink {
+    socket: UdpSocket,
+    hostname: String,
+}
+
+impl SyslogAuditSink {
+    pub fn connect(addr: impl ToSocketAddrs) -> AuditResult<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        let hostname = std::env::var("HOSTNAME").ok().filter(|name| !name.is_empty()).unwrap_or_else(|| "-".to_string());
+        Ok(Self { socket, hostname })
+    }
+}
+
+impl AuditSink for SyslogAuditSink {
+    fn write(&self, event: &AuditEvent) -> AuditResult<()> {
+        let severity = if event.kind.is_failure() { SEVERITY_WARNING } else { SEVERITY_NOTICE };
+        let priority = FACILITY_AUTHPRIV * 8 + severity;
+        let message_id = serde_json::to_value(event.kind)?;
+        let message = format!(
+            "<{}>1 {} {} peer-conference {} {} - {}",
+            priority,
+            event.timestamp,
+            self.hostname,
+            std::process::id(),
+            message_id.as_str().unwrap_or("-"),
+            serde_json::to_string(event)?
+        );
+        self.socket.send(message.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use crate::auth::webauthn::Credential;
+use crate::storage::sqlite::add_column_if_missing;
+use crate::storage::StoreResult;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use std::sync::Mutex;
+
+// Registered WebAuthn credentials
+pub trait CredentialStore: Send + Sync {
+    fn credential(&self, credential_id: &[u8]) -> StoreResult<Option<Credential>>;
+    fn credentials_for(&self, user_id: &str) -> StoreResult<Vec<Credential>>;
+    fn save_credential(&self, credential: &Credential) -> StoreResult<()>;
+}
+
+pub struct SqliteCredentialStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteCredentialStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> StoreResult<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS webauthn_credentials (
+                credential_id BLOB PRIMARY KEY,
+                user_id TEXT NOT NULL,
+                public_key BLOB NOT NULL,
+                algorithm TEXT NOT NULL,
+                sign_count INTEGER NOT NULL
+            )",
+        )?;
+        add_column_if_missing(&conn, "webauthn_credentials", "attestation", "TEXT NOT NULL DEFAULT '\"none\"'")?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+}
+
+fn credential_from_row(row: &Row) -> rusqlite::Result<Credential> {
+    let algorithm: String = row.get(3)?;
+    let attestation: String = row.get(5)?;
+    // Stored as their JSON form; unknown values fall back to the defaults
+    Ok(Credential {
+        credential_id: row.get(0)?,
+        user_id: row.get(1)?,
+        public_key: row.get(2)?,
+        algorithm: serde_json::from_str(&algorithm).unwrap_or_default(),
+        sign_count: row.get(4)?,
+        attestation: serde_json::from_str(&attestation).unwrap_or_default(),
+    })
+}
+
+impl CredentialStore for SqliteCredentialStore {
+    fn credential(&self, credential_id: &[u8]) -> StoreResult<Option<Credential>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let credential = conn
+            .query_row(
+                "SELECT credential_id, user_id, public_key, algorithm, sign_count, attestation
+                 FROM webauthn_credentials WHERE credential_id = ?1",
+                params![credential_id],
+                credential_from_row,
+            )
+            .optional()?;
+        Ok(credential)
+    }
+
+    fn credentials_for(&self, user_id: &str) -> StoreResult<Vec<Credential>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut statement = conn.prepare(
+            "SELECT credential_id, user_id, public_key, algorithm, sign_count, attestation
+             FROM webauthn_credentials WHERE user_id = ?1",
+        )?;
+        let rows = statement.query_map(params![user_id], credential_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn save_credential(&self, credential: &Credential) -> StoreResult<()> {
+        let algorithm = serde_json::to_string(&credential.algorithm)?;
+        let attestation = serde_json::to_string(&credential.attestation)?;
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO webauthn_credentials
+             (credential_id, user_id, public_key, algorithm, sign_count, attestation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![credential.credential_id, credential.user_id, credential.public_key, algorithm, credential.sign_count, attestation],
+        )?;
+        Ok(())
+    }
+}
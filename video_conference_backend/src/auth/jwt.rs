@@ -0,0 +1,152 @@
+use crate::config;
+use crate::models::Role;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+// Cached key sets are refetched after this long, or sooner when a token names an unknown key
+const JWKS_MAX_AGE: Duration = Duration::from_secs(600);
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(30);
+
+static JWKS_CACHE: Mutex<Option<(Instant, JwkSet)>> = Mutex::new(None);
+static JWKS_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// What the auth service vouches for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    // User id
+    pub sub: String,
+    pub exp: i64,
+    // Role granted in the rooms listed in `rooms`; ignored for tokens good for any room
+    #[serde(default)]
+    pub role: Option<Role>,
+    // Rooms the user may join; unrestricted when absent
+    #[serde(default)]
+    pub rooms: Option<Vec<String>>,
+}
+
+impl Claims {
+    pub fn allows_room(&self, room_id: &str) -> bool {
+        self.rooms.as_ref().is_none_or(|rooms| rooms.iter().any(|room| room == room_id))
+    }
+
+    // The role the token grants in `room_id`, which it only does for rooms it names
+    pub fn role_in(&self, room_id: &str) -> Option<Role> {
+        let named = self.rooms.as_ref().is_some_and(|rooms| rooms.iter().any(|room| room == room_id));
+        self.role.filter(|_| named)
+    }
+}
+
+// Bearer token from the Authorization header, or the `token` query parameter for browsers
+// that cannot set headers on a WebSocket
+pub fn token_from_request(request: &Request) -> Option<String> {
+    let from_header = request.headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    from_header.or_else(|| {
+        request.uri()
+            .query()?
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .map(str::to_string)
+    })
+}
+
+pub async fn validate_token(token: &str) -> Result<Claims, String> {
+    let header = decode_header(token).map_err(|e| format!("Malformed token: {}", e))?;
+    let key = decoding_key(&header).await?;
+
+    let mut validation = Validation::new(header.alg);
+    match config::get_jwt_audience() {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+    if let Some(issuer) = config::get_jwt_issuer() {
+        validation.set_issuer(&[issuer]);
+    }
+
+    decode::<Claims>(token, &key, &validation)
+        .map(|data| data.claims)
+        .map_err(|e| format!("Invalid token: {}", e))
+}
+
+// HMAC tokens use the shared secret; everything else is checked against the JWKS endpoint
+// or, failing that, a configured public key
+async fn decoding_key(header: &Header) -> Result<DecodingKey, String> {
+    if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+        let secret = config::get_jwt_secret().ok_or("HMAC-signed tokens are not accepted")?;
+        return Ok(DecodingKey::from_secret(secret.as_bytes()));
+    }
+
+    if let Some(url) = config::get_jwt_jwks_url() {
+        return jwks_key(&url, header.kid.as_deref()).await;
+    }
+
+    let pem = config::get_jwt_public_key().ok_or("No key is configured for asymmetric tokens")?;
+    let key = match header.alg {
+        Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(pem.as_bytes()),
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(pem.as_bytes()),
+        _ => DecodingKey::from_rsa_pem(pem.as_bytes()),
+    };
+    key.map_err(|e| format!("Configured public key is unusable: {}", e))
+}
+
+// The cache lock is only held to read or replace the set, never across the fetch
+async fn jwks_key(url: &str, kid: Option<&str>) -> Result<DecodingKey, String> {
+    let cached = JWKS_CACHE.lock().map_err(|_| "JWKS cache poisoned")?.clone();
+
+    let age = cached.as_ref().map(|(fetched_at, _)| fetched_at.elapsed());
+    let known = cached.as_ref().is_some_and(|(_, keys)| find_key(keys, kid).is_some());
+    let refresh = match age {
+        None => true,
+        Some(age) => age > JWKS_MAX_AGE || (!known && age > JWKS_MIN_REFRESH),
+    };
+
+    let keys = match cached {
+        Some((_, keys)) if !refresh => keys,
+        _ => {
+            let keys = fetch_jwks(url).await?;
+            *JWKS_CACHE.lock().map_err(|_| "JWKS cache poisoned")? = Some((Instant::now(), keys.clone()));
+            keys
+        }
+    };
+
+    let jwk = find_key(&keys, kid).ok_or("Token was signed with an unknown key")?;
+    DecodingKey::from_jwk(jwk).map_err(|e| format!("Unusable JWK: {}", e))
+}
+
+async fn fetch_jwks(url: &str) -> Result<JwkSet, String> {
+    let client = match JWKS_CLIENT.get() {
+        Some(client) => client,
+        None => {
+            let client = reqwest::Client::builder()
+                .timeout(config::get_jwt_jwks_timeout())
+                .build()
+                .map_err(|e| format!("Failed to build JWKS client: {}", e))?;
+            JWKS_CLIENT.get_or_init(|| client)
+        }
+    };
+    client.get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch JWKS: {}", e))?
+        .json::<JwkSet>()
+        .await
+        .map_err(|e| format!("Failed to parse JWKS: {}", e))
+}
+
+// Tokens without a key id are only accepted when the set holds a single key
+fn find_key<'a>(keys: &'a JwkSet, kid: Option<&str>) -> Option<&'a jsonwebtoken::jwk::Jwk> {
+    match kid {
+        Some(kid) => keys.find(kid),
+        None if keys.keys.len() == 1 => keys.keys.first(),
+        None => None,
+    }
+}
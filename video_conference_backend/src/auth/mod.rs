@@ -0,0 +1,7 @@
+pub mod credentials;
+pub mod jwt;
+pub mod webauthn;
+
+pub use credentials::{CredentialStore, SqliteCredentialStore};
+pub use jwt::{token_from_request, validate_token, Claims};
+pub use webauthn::{Attestation, Ceremony, Credential, PendingCeremony, RelyingParty};
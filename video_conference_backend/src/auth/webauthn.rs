@@ -0,0 +1,271 @@
+use crate::crypto::{self, SignatureAlgorithm, VerificationMode};
+use ciborium::value::Value;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// COSE algorithm identifiers the server accepts
+const COSE_ES256: i64 = -7;
+const COSE_EDDSA: i64 = -8;
+
+// Authenticator data flags
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+// Relying party the browser scopes credentials to
+#[derive(Debug, Clone)]
+pub struct RelyingParty {
+    pub rp_id: String,
+    pub origin: String,
+}
+
+// A registered authenticator key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credential {
+    pub credential_id: Vec<u8>,
+    pub user_id: String,
+    pub public_key: Vec<u8>,
+    pub algorithm: SignatureAlgorithm,
+    pub sign_count: u32,
+    pub attestation: Attestation,
+}
+
+// What the authenticator proved about itself at registration. Certificate chains are not
+// checked against any vendor roots, so they earn no more than `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Attestation {
+    #[default]
+    None,
+    // Signed by the credential key itself: proves possession, not the authenticator's make
+    #[serde(rename = "self")]
+    SelfAttested,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+// Challenge handed out by a `*-begin` message, consumed by the matching finish message
+#[derive(Debug, Clone)]
+pub struct PendingCeremony {
+    pub ceremony: Ceremony,
+    pub challenge: String,
+    pub user_id: String,
+    // Begun without signing in, so it may only create a user that has no credentials yet
+    pub open: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+}
+
+struct AuthenticatorData<'a> {
+    sign_count: u32,
+    // Credential id and COSE key, present on registration
+    attested: Option<(Vec<u8>, Value)>,
+    raw: &'a [u8],
+}
+
+// Checks a `navigator.credentials.create()` response and returns the new credential
+pub fn verify_registration(
+    relying_party: &RelyingParty,
+    pending: &PendingCeremony,
+    client_data_json: &[u8],
+    attestation_object: &[u8]
+) -> Result<Credential, String> {
+    check_client_data(relying_party, pending, "webauthn.create", client_data_json)?;
+
+    let attestation: Value = ciborium::de::from_reader(attestation_object)
+        .map_err(|e| format!("Malformed attestation object: {}", e))?;
+    let format = map_text(&attestation, "fmt").and_then(Value::as_text).ok_or("Attestation has no format")?;
+    let statement = map_text(&attestation, "attStmt").ok_or("Attestation has no statement")?;
+    let auth_data = map_text(&attestation, "authData")
+        .and_then(Value::as_bytes)
+        .ok_or("Attestation has no authenticator data")?;
+
+    let parsed = parse_authenticator_data(relying_party, auth_data)?;
+    let (credential_id, cose_key) = parsed.attested.ok_or("Authenticator data carries no credential")?;
+    let (public_key, algorithm) = cose_public_key(&cose_key)?;
+
+    let client_data_hash = Sha256::digest(client_data_json);
+    let attestation = verify_attestation(format, statement, parsed.raw, &client_data_hash, &public_key, algorithm)?;
+
+    Ok(Credential {
+        credential_id,
+        user_id: pending.user_id.clone(),
+        public_key,
+        algorithm,
+        sign_count: parsed.sign_count,
+        attestation,
+    })
+}
+
+// Checks a `navigator.credentials.get()` response against a stored credential and returns the
+// authenticator's new signature counter
+pub fn verify_assertion(
+    relying_party: &RelyingParty,
+    pending: &PendingCeremony,
+    credential: &Credential,
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8]
+) -> Result<u32, String> {
+    check_client_data(relying_party, pending, "webauthn.get", client_data_json)?;
+    if credential.user_id != pending.user_id {
+        return Err("Credential belongs to a different user".to_string());
+    }
+
+    let parsed = parse_authenticator_data(relying_party, authenticator_data)?;
+    let mut signed = authenticator_data.to_vec();
+    signed.extend_from_slice(&Sha256::digest(client_data_json));
+    verify(credential.algorithm, &signed, signature, &credential.public_key)?;
+
+    // Counters only ever go up; one going backwards means the authenticator was cloned
+    let counting = parsed.sign_count != 0 || credential.sign_count != 0;
+    if counting && parsed.sign_count <= credential.sign_count {
+        return Err("Authenticator signature counter went backwards".to_string());
+    }
+    Ok(parsed.sign_count)
+}
+
+fn check_client_data(
+    relying_party: &RelyingParty,
+    pending: &PendingCeremony,
+    expected_type: &str,
+    client_data_json: &[u8]
+) -> Result<(), String> {
+    let client_data: ClientData = serde_json::from_slice(client_data_json)
+        .map_err(|e| format!("Malformed client data: {}", e))?;
+    if client_data.ceremony_type != expected_type {
+        return Err(format!("Expected a {} response", expected_type));
+    }
+    // The challenge is sent base64url encoded and the browser reports it back the same way
+    if client_data.challenge.trim_end_matches('=') != pending.challenge {
+        return Err("Response answers a different challenge".to_string());
+    }
+    if client_data.origin != relying_party.origin {
+        return Err(format!("Unexpected origin {}", client_data.origin));
+    }
+    Ok(())
+}
+
+fn parse_authenticator_data<'a>(relying_party: &RelyingParty, data: &'a [u8]) -> Result<AuthenticatorData<'a>, String> {
+    if data.len() < 37 {
+        return Err("Authenticator data is too short".to_string());
+    }
+    if data[..32] != Sha256::digest(relying_party.rp_id.as_bytes())[..] {
+        return Err("Authenticator data is for a different relying party".to_string());
+    }
+    let flags = data[32];
+    if flags & FLAG_USER_PRESENT == 0 {
+        return Err("User presence was not confirmed".to_string());
+    }
+    let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+    let attested = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+        // AAGUID (16 bytes), then a length-prefixed credential id, then the COSE key
+        let header = data.get(37..55).ok_or("Attested credential data is truncated")?;
+        let id_length = u16::from_be_bytes([header[16], header[17]]) as usize;
+        let rest = &data[55..];
+        let credential_id = rest.get(..id_length).ok_or("Credential id is truncated")?.to_vec();
+        let cose_key: Value = ciborium::de::from_reader(&rest[id_length..])
+            .map_err(|e| format!("Malformed credential public key: {}", e))?;
+        Some((credential_id, cose_key))
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData { sign_count, attested, raw: data })
+}
+
+// ES256 keys come back as an uncompressed SEC1 point, EdDSA keys as the raw 32 bytes
+fn cose_public_key(key: &Value) -> Result<(Vec<u8>, SignatureAlgorithm), String> {
+    let algorithm = map_int(key, 3).and_then(value_int).ok_or("COSE key has no algorithm")?;
+    let bytes = |label: i64| {
+        map_int(key, label)
+            .and_then(Value::as_bytes)
+            .cloned()
+            .ok_or_else(|| format!("COSE key is missing parameter {}", label))
+    };
+
+    match algorithm {
+        COSE_ES256 => {
+            let mut point = vec![0x04];
+            point.extend(bytes(-2)?);
+            point.extend(bytes(-3)?);
+            Ok((point, SignatureAlgorithm::EcdsaP256))
+        }
+        COSE_EDDSA => Ok((bytes(-2)?, SignatureAlgorithm::Ed25519)),
+        other => Err(format!("Unsupported COSE algorithm {}", other)),
+    }
+}
+
+// `none` carries no statement. `packed` self attestation must be signed by the credential key
+// under the algorithm it declares. A packed `x5c` chain would need vendor roots to mean anything,
+// which the server does not have, so the statement is ignored and the registration recorded as
+// `none`.
+fn verify_attestation(
+    format: &str,
+    statement: &Value,
+    auth_data: &[u8],
+    client_data_hash: &[u8],
+    public_key: &[u8],
+    algorithm: SignatureAlgorithm
+) -> Result<Attestation, String> {
+    match format {
+        "none" => Ok(Attestation::None),
+        "packed" if map_text(statement, "x5c").is_some() => Ok(Attestation::None),
+        "packed" => {
+            let declared = map_text(statement, "alg").and_then(value_int).ok_or("Packed attestation has no algorithm")?;
+            if declared != cose_algorithm(algorithm) {
+                return Err(format!("Attestation algorithm {} does not match the credential key", declared));
+            }
+            let signature = map_text(statement, "sig")
+                .and_then(Value::as_bytes)
+                .ok_or("Packed attestation has no signature")?;
+            let mut signed = auth_data.to_vec();
+            signed.extend_from_slice(client_data_hash);
+            verify(algorithm, &signed, signature, public_key).map(|_| Attestation::SelfAttested)
+        }
+        other => Err(format!("Unsupported attestation format {}", other)),
+    }
+}
+
+fn cose_algorithm(algorithm: SignatureAlgorithm) -> i64 {
+    match algorithm {
+        SignatureAlgorithm::EcdsaP256 => COSE_ES256,
+        SignatureAlgorithm::Ed25519 => COSE_EDDSA,
+    }
+}
+
+// Authenticators sign the message itself and emit DER ECDSA signatures
+fn verify(algorithm: SignatureAlgorithm, message: &[u8], signature: &[u8], public_key: &[u8]) -> Result<(), String> {
+    match algorithm {
+        SignatureAlgorithm::EcdsaP256 => crypto::verify_p256(message, signature, public_key, VerificationMode::Raw),
+        SignatureAlgorithm::Ed25519 => crypto::verify_ed25519(message, signature, public_key),
+    }
+}
+
+fn map_text<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn map_int(map: &Value, key: i64) -> Option<&Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| value_int(k) == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn value_int(value: &Value) -> Option<i64> {
+    value.as_integer().and_then(|integer| i64::try_from(integer).ok())
+}
@@ -0,0 +1,97 @@
+pub mod sqlite;
+
+use crate::config;
+use crate::storage::StoreResult;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub use sqlite::SqliteCallStore;
+
+// How an invite was settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CallOutcome {
+    Ringing,
+    Answered,
+    Rejected,
+    Cancelled,
+    // Rang out on the callee's devices
+    Unanswered,
+    // Timed out with none of the callee's devices connected
+    Missed,
+}
+
+impl CallOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallOutcome::Ringing => "ringing",
+            CallOutcome::Answered => "answered",
+            CallOutcome::Rejected => "rejected",
+            CallOutcome::Cancelled => "cancelled",
+            CallOutcome::Unanswered => "unanswered",
+            CallOutcome::Missed => "missed",
+        }
+    }
+
+    pub fn parse(outcome: &str) -> Option<Self> {
+        match outcome {
+            "ringing" => Some(CallOutcome::Ringing),
+            "answered" => Some(CallOutcome::Answered),
+            "rejected" => Some(CallOutcome::Rejected),
+            "cancelled" => Some(CallOutcome::Cancelled),
+            "unanswered" => Some(CallOutcome::Unanswered),
+            "missed" => Some(CallOutcome::Missed),
+            _ => None,
+        }
+    }
+}
+
+// One invite and the call that came of it. `record_id` is the server's; `call_id` is the one
+// the caller chose. Both ends are named by user id, since only signed-in clients place calls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallRecord {
+    pub record_id: String,
+    pub call_id: String,
+    pub tenant_id: Option<String>,
+    pub caller: String,
+    pub callee: String,
+    pub room_id: Option<String>,
+    pub outcome: CallOutcome,
+    // Unix milliseconds; `answered_at` only for answered calls, `ended_at` once the call is over
+    pub started_at: i64,
+    pub answered_at: Option<i64>,
+    pub ended_at: Option<i64>,
+}
+
+pub trait CallStore: Send + Sync {
+    // Inserts the record, or replaces the one with the same record id
+    fn save(&self, record: &CallRecord) -> StoreResult<()>;
+    // Up to `limit` calls `user_id` placed or received within the tenant, newest first
+    fn history(&self, tenant_id: Option<&str>, user_id: &str, limit: usize) -> StoreResult<Vec<CallRecord>>;
+    // The calls `user_id` missed that it has not been told about yet, oldest first; they count
+    // as told once returned
+    fn take_missed(&self, tenant_id: Option<&str>, user_id: &str) -> StoreResult<Vec<CallRecord>>;
+    // Drops calls started before `cutoff` (unix ms) and all but the newest `keep_missed` missed
+    // calls of each callee; returns how many went
+    fn prune(&self, cutoff: i64, keep_missed: usize) -> StoreResult<usize>;
+}
+
+// Applies the retention policy every sweep interval
+pub async fn run_record_pruner(store: Arc<dyn CallStore>) {
+    let mut interval = tokio::time::interval(config::get_room_sweep_interval());
+
+    loop {
+        interval.tick().await;
+
+        let cutoff = match config::get_call_retention() {
+            Some(retention) => Utc::now().timestamp_millis().saturating_sub(i64::try_from(retention.as_millis()).unwrap_or(i64::MAX)),
+            None => i64::MIN,
+        };
+        match store.prune(cutoff, config::get_missed_calls_max()) {
+            Ok(0) => {}
+            Ok(pruned) => println!("Pruned {} call records", pruned),
+            Err(e) => eprintln!("Failed to prune call records: {}", e),
+        }
+    }
+}
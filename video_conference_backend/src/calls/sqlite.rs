@@ -0,0 +1,137 @@
+use crate::calls::{CallOutcome, CallRecord, CallStore};
+use crate::storage::StoreResult;
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+use std::sync::Mutex;
+
+pub struct SqliteCallStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteCallStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> StoreResult<Self> {
+        // `notified` marks missed calls the callee has been told about; tenant ids are stored
+        // as '' for no tenant so they compare equal
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS call_records (
+                record_id TEXT PRIMARY KEY,
+                call_id TEXT NOT NULL,
+                tenant_id TEXT NOT NULL,
+                caller TEXT NOT NULL,
+                callee TEXT NOT NULL,
+                room_id TEXT,
+                outcome TEXT NOT NULL,
+                started_at INTEGER NOT NULL,
+                answered_at INTEGER,
+                ended_at INTEGER,
+                notified INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS call_records_by_caller ON call_records (tenant_id, caller, started_at);
+            CREATE INDEX IF NOT EXISTS call_records_by_callee ON call_records (tenant_id, callee, started_at)",
+        )?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+}
+
+const COLUMNS: &str = "record_id, call_id, tenant_id, caller, callee, room_id, outcome, started_at, answered_at, ended_at";
+
+fn record_from_row(row: &Row) -> rusqlite::Result<CallRecord> {
+    let tenant_id: String = row.get(2)?;
+    let outcome: String = row.get(6)?;
+    Ok(CallRecord {
+        record_id: row.get(0)?,
+        call_id: row.get(1)?,
+        tenant_id: Some(tenant_id).filter(|tenant_id| !tenant_id.is_empty()),
+        caller: row.get(3)?,
+        callee: row.get(4)?,
+        room_id: row.get(5)?,
+        outcome: CallOutcome::parse(&outcome).unwrap_or(CallOutcome::Ringing),
+        started_at: row.get(7)?,
+        answered_at: row.get(8)?,
+        ended_at: row.get(9)?,
+    })
+}
+
+impl CallStore for SqliteCallStore {
+    fn save(&self, record: &CallRecord) -> StoreResult<()> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            &format!(
+                "INSERT INTO call_records ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                 ON CONFLICT (record_id) DO UPDATE SET outcome = ?7, answered_at = ?9, ended_at = ?10",
+                COLUMNS
+            ),
+            params![
+                record.record_id,
+                record.call_id,
+                record.tenant_id.as_deref().unwrap_or(""),
+                record.caller,
+                record.callee,
+                record.room_id,
+                record.outcome.as_str(),
+                record.started_at,
+                record.answered_at,
+                record.ended_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn history(&self, tenant_id: Option<&str>, user_id: &str, limit: usize) -> StoreResult<Vec<CallRecord>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM call_records WHERE tenant_id = ?1 AND (caller = ?2 OR callee = ?2)
+             ORDER BY started_at DESC, rowid DESC LIMIT ?3",
+            COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![tenant_id.unwrap_or(""), user_id, limit.min(i64::MAX as usize) as i64],
+            record_from_row
+        )?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn take_missed(&self, tenant_id: Option<&str>, user_id: &str) -> StoreResult<Vec<CallRecord>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let missed = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM call_records
+                 WHERE tenant_id = ?1 AND callee = ?2 AND outcome = 'missed' AND notified = 0
+                 ORDER BY started_at, rowid",
+                COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![tenant_id.unwrap_or(""), user_id], record_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        for record in &missed {
+            conn.execute("UPDATE call_records SET notified = 1 WHERE record_id = ?1", params![record.record_id])?;
+        }
+        Ok(missed)
+    }
+
+    fn prune(&self, cutoff: i64, keep_missed: usize) -> StoreResult<usize> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let expired = conn.execute("DELETE FROM call_records WHERE started_at < ?1", params![cutoff])?;
+        let overflow = conn.execute(
+            "DELETE FROM call_records WHERE rowid IN (
+                SELECT rowid FROM (
+                    SELECT rowid, ROW_NUMBER() OVER (
+                        PARTITION BY tenant_id, callee ORDER BY started_at DESC, rowid DESC
+                    ) AS newest
+                    FROM call_records WHERE outcome = 'missed'
+                ) WHERE newest > ?1
+            )",
+            params![keep_missed.min(i64::MAX as usize) as i64],
+        )?;
+        Ok(expired + overflow)
+    }
+}
@@ -0,0 +1,71 @@
+pub mod sqlite;
+
+use crate::config;
+use crate::storage::StoreResult;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub use sqlite::SqliteChatStore;
+
+// A chat message as the room saw it, stamped by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub message_id: String,
+    pub room_id: String,
+    pub client_id: String,
+    // The sender's display name at the time, so the message reads the same after they leave
+    pub display_name: Option<String>,
+    pub text: String,
+    // Unix milliseconds
+    pub sent_at: i64,
+}
+
+// The text as it is relayed: trimmed, 1 to `max_chars` characters, and free of control
+// characters other than line breaks and tabs
+pub fn check_text(text: &str, max_chars: usize) -> Result<&str, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Chat messages must not be blank".to_string());
+    }
+    if text.chars().count() > max_chars {
+        return Err(format!("Chat messages are limited to {} characters", max_chars));
+    }
+    if text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return Err("Chat messages must not contain control characters".to_string());
+    }
+    Ok(text)
+}
+
+// What each room has said, so late joiners can catch up
+pub trait ChatStore: Send + Sync {
+    fn append(&self, message: &ChatMessage) -> StoreResult<()>;
+    // Up to `limit` of the room's messages sent before the message `before` (its latest when
+    // None), oldest first
+    fn history(&self, room_id: &str, before: Option<&str>, limit: usize) -> StoreResult<Vec<ChatMessage>>;
+    // Drops messages sent before `cutoff` (unix ms) and all but the newest `keep` of each
+    // room; returns how many went
+    fn prune(&self, cutoff: i64, keep: usize) -> StoreResult<usize>;
+    // Drops everything the room said, once the room is gone, so a new room under the same id
+    // starts with a clean history
+    fn delete_room(&self, room_id: &str) -> StoreResult<usize>;
+}
+
+// Applies the retention policy every sweep interval
+pub async fn run_history_pruner(store: Arc<dyn ChatStore>) {
+    let mut interval = tokio::time::interval(config::get_room_sweep_interval());
+
+    loop {
+        interval.tick().await;
+
+        let cutoff = match config::get_chat_retention() {
+            Some(retention) => Utc::now().timestamp_millis().saturating_sub(i64::try_from(retention.as_millis()).unwrap_or(i64::MAX)),
+            None => i64::MIN,
+        };
+        match store.prune(cutoff, config::get_chat_history_max_messages()) {
+            Ok(0) => {}
+            Ok(pruned) => println!("Pruned {} chat messages", pruned),
+            Err(e) => eprintln!("Failed to prune chat history: {}", e),
+        }
+    }
+}
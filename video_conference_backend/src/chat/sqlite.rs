@@ -0,0 +1,153 @@
+use crate::chat::{ChatMessage, ChatStore};
+use crate::storage::encryption::{self, StorageCipher};
+use crate::storage::StoreResult;
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub struct SqliteChatStore {
+    conn: Mutex<Connection>,
+    // Seals message text and display names when set
+    cipher: Option<Arc<StorageCipher>>,
+}
+
+impl SqliteChatStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> StoreResult<Self> {
+        // `seq` keeps messages in the order the server relayed them
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS chat_messages (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                message_id TEXT NOT NULL UNIQUE,
+                room_id TEXT NOT NULL,
+                client_id TEXT NOT NULL,
+                display_name TEXT,
+                text TEXT NOT NULL,
+                sent_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS chat_messages_by_room ON chat_messages (room_id, seq)",
+        )?;
+
+        Ok(Self { conn: Mutex::new(conn), cipher: None })
+    }
+
+    pub fn with_cipher(mut self, cipher: Arc<StorageCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    // Re-seals messages that are still plaintext or sealed with a retired key; returns how
+    // many rows were rewritten
+    pub fn rekey(&self) -> StoreResult<usize> {
+        let Some(cipher) = self.cipher.as_deref() else {
+            return Ok(0);
+        };
+
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let stale = {
+            let mut statement = conn.prepare("SELECT message_id, display_name, text FROM chat_messages")?;
+            let rows = statement
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?)))?
+                .filter_map(Result::ok)
+                .filter(|(_, display_name, text)| {
+                    cipher.needs_rekey(text) || display_name.as_deref().is_some_and(|name| cipher.needs_rekey(name))
+                })
+                .collect::<Vec<_>>();
+            rows
+        };
+
+        for (message_id, display_name, text) in &stale {
+            let reseal = |column: &str, value: &str| {
+                let field = encryption::field("chat_messages", column, message_id);
+                cipher.seal(&cipher.open_stale(value, &field)?, &field)
+            };
+            conn.execute(
+                "UPDATE chat_messages SET display_name = ?2, text = ?3 WHERE message_id = ?1",
+                params![
+                    message_id,
+                    display_name.as_deref().map(|name| reseal("display_name", name)).transpose()?,
+                    reseal("text", text)?,
+                ],
+            )?;
+        }
+        Ok(stale.len())
+    }
+
+    fn open_message(&self, mut message: ChatMessage) -> StoreResult<ChatMessage> {
+        let cipher = self.cipher.as_deref();
+        let field = |column| encryption::field("chat_messages", column, &message.message_id);
+        message.display_name = encryption::open_optional(cipher, message.display_name, &field("display_name"))?;
+        message.text = encryption::open(cipher, message.text, &field("text"))?;
+        Ok(message)
+    }
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<ChatMessage> {
+    Ok(ChatMessage {
+        message_id: row.get(0)?,
+        room_id: row.get(1)?,
+        client_id: row.get(2)?,
+        display_name: row.get(3)?,
+        text: row.get(4)?,
+        sent_at: row.get(5)?,
+    })
+}
+
+impl ChatStore for SqliteChatStore {
+    fn append(&self, message: &ChatMessage) -> StoreResult<()> {
+        let cipher = self.cipher.as_deref();
+        let field = |column| encryption::field("chat_messages", column, &message.message_id);
+        let display_name = encryption::seal_optional(cipher, message.display_name.as_deref(), &field("display_name"))?;
+        let text = encryption::seal(cipher, &message.text, &field("text"))?;
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO chat_messages (message_id, room_id, client_id, display_name, text, sent_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![message.message_id, message.room_id, message.client_id, display_name, text, message.sent_at],
+        )?;
+        Ok(())
+    }
+
+    fn history(&self, room_id: &str, before: Option<&str>, limit: usize) -> StoreResult<Vec<ChatMessage>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        // An unknown cursor reads as "before everything" rather than "from the latest", so a
+        // client paging back past pruned messages gets an empty page instead of a repeat
+        let mut stmt = conn.prepare(
+            "SELECT message_id, room_id, client_id, display_name, text, sent_at FROM chat_messages
+             WHERE room_id = ?1 AND (?2 IS NULL OR seq < COALESCE(
+                (SELECT seq FROM chat_messages WHERE message_id = ?2 AND room_id = ?1), 0))
+             ORDER BY seq DESC LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(params![room_id, before, limit.min(i64::MAX as usize) as i64], message_from_row)?;
+        let mut messages = rows.map(|message| self.open_message(message?)).collect::<StoreResult<Vec<_>>>()?;
+        messages.reverse();
+        Ok(messages)
+    }
+
+    fn prune(&self, cutoff: i64, keep: usize) -> StoreResult<usize> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let expired = conn.execute("DELETE FROM chat_messages WHERE sent_at < ?1", params![cutoff])?;
+        let overflow = conn.execute(
+            "DELETE FROM chat_messages WHERE seq IN (
+                SELECT seq FROM (
+                    SELECT seq, ROW_NUMBER() OVER (PARTITION BY room_id ORDER BY seq DESC) AS newest
+                    FROM chat_messages
+                ) WHERE newest > ?1
+            )",
+            params![keep.min(i64::MAX as usize) as i64],
+        )?;
+        Ok(expired + overflow)
+    }
+
+    fn delete_room(&self, room_id: &str) -> StoreResult<usize> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        Ok(conn.execute("DELETE FROM chat_messages WHERE room_id = ?1", params![room_id])?)
+    }
+}
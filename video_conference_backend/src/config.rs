@@ -1,8 +1,814 @@
+use crate::crypto::VerificationMode;
+use crate::models::SignalKind;
+use crate::ratelimit::RateLimit;
+use crate::rooms::HostTransfer;
+use std::collections::HashMap;
 use std::net::{SocketAddr, IpAddr, Ipv4Addr};
+use std::str::FromStr;
+use std::time::Duration;
 
 pub fn get_signaling_server_addr() -> SocketAddr {
-    SocketAddr::new(
+    env_or("SIGNALING_ADDR", SocketAddr::new(
         IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 
         3030
-    )
-}
\ No newline at end of file
+    ))
+}
+
+pub fn get_default_max_participants() -> usize {
+    env_or("MAX_PARTICIPANTS", 8)
+}
+
+// Upper bound on the capacity a client may ask for when a room is created
+pub fn get_max_room_participants() -> usize {
+    env_or("MAX_ROOM_PARTICIPANTS", 50)
+}
+
+pub fn get_room_idle_timeout() -> Duration {
+    Duration::from_secs(env_or("ROOM_IDLE_TIMEOUT_SECS", 3600))
+}
+
+pub fn get_empty_room_ttl() -> Duration {
+    Duration::from_secs(env_or("EMPTY_ROOM_TTL_SECS", 300))
+}
+
+pub fn get_room_sweep_interval() -> Duration {
+    Duration::from_secs(env_or("ROOM_SWEEP_INTERVAL_SECS", 60))
+}
+
+pub fn get_resume_grace_period() -> Duration {
+    Duration::from_secs(env_or("RESUME_GRACE_SECS", 120))
+}
+
+// Verified clients must number every message with an increasing seq, which their signed
+// envelopes cover. Unset, only clients that send one are held to it.
+pub fn get_require_seq() -> bool {
+    env_or("REQUIRE_SEQ", true)
+}
+
+// When set, every message must carry a UUID message_id
+pub fn get_require_message_id() -> bool {
+    env_or("REQUIRE_MESSAGE_ID", true)
+}
+
+// How long, and how many of, a client's message ids are remembered to spot retransmissions
+pub fn get_message_id_ttl() -> Duration {
+    Duration::from_secs(env_or("MESSAGE_ID_TTL_SECS", 60))
+}
+
+pub fn get_message_id_cache_size() -> usize {
+    env_or("MESSAGE_ID_CACHE_SIZE", 512)
+}
+
+// How long a relayed message waits behind a missing earlier one, and how many may wait, before
+// the gap is skipped
+pub fn get_reorder_timeout() -> Duration {
+    Duration::from_millis(env_or("REORDER_TIMEOUT_MS", 250))
+}
+
+pub fn get_reorder_buffer_size() -> usize {
+    env_or("REORDER_BUFFER_SIZE", 32)
+}
+
+// How long a relayed ICE candidate waits for others bound for the same client; 0 sends each
+// on its own
+pub fn get_ice_batch_interval() -> Duration {
+    Duration::from_millis(env_or("ICE_BATCH_INTERVAL_MS", 20))
+}
+
+// Relayed ICE candidates that can be dropped, each relayed unless turned on: those behind an
+// mDNS `.local` name, everything but TURN relay candidates, and those with private, shared
+// (CGNAT), link-local or loopback addresses. Rooms can override each.
+pub fn get_strip_mdns_candidates() -> bool {
+    env_or("ICE_STRIP_MDNS", false)
+}
+
+pub fn get_relay_only() -> bool {
+    env_or("ICE_RELAY_ONLY", false)
+}
+
+pub fn get_drop_private_candidates() -> bool {
+    env_or("ICE_DROP_PRIVATE", false)
+}
+
+// Session tokens are refreshed once past half this lifetime
+pub fn get_session_token_ttl() -> Duration {
+    Duration::from_secs(env_or("SESSION_TOKEN_TTL_SECS", 300))
+}
+
+// When set, verified clients must send their session token with every message that does not
+// carry its own proof; when cleared, only tokens that are sent are checked
+pub fn get_require_session_token() -> bool {
+    env_or("REQUIRE_SESSION_TOKEN", true)
+}
+
+// A renegotiation offer left unanswered this long no longer counts as open
+pub fn get_renegotiation_timeout() -> Duration {
+    Duration::from_secs(env_or("RENEGOTIATION_TIMEOUT_SECS", 30))
+}
+
+pub fn get_admin_token() -> Option<String> {
+    std::env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty())
+}
+
+pub fn get_invite_secret() -> Option<String> {
+    std::env::var("INVITE_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+pub fn get_default_invite_ttl() -> Duration {
+    Duration::from_secs(env_or("INVITE_TTL_SECS", 86400))
+}
+
+pub fn get_max_screen_shares() -> usize {
+    env_or("MAX_SCREEN_SHARES", 1)
+}
+
+pub fn get_chat_max_length() -> usize {
+    env_or("CHAT_MAX_LENGTH", 2000)
+}
+
+// How long peers show a client as typing after its last typing-start; it is relayed again at
+// most every half of this while the client keeps typing
+pub fn get_typing_timeout() -> Duration {
+    Duration::from_secs(env_or("TYPING_TIMEOUT_SECS", 6))
+}
+
+// Largest file peers may offer each other, in bytes; 0 turns file transfers off
+pub fn get_max_file_size() -> u64 {
+    env_or("FILE_MAX_SIZE", 100 * 1024 * 1024)
+}
+
+// MIME types peers may offer each other, e.g. `image/*,application/pdf`; any when unset
+pub fn get_allowed_file_types() -> Vec<String> {
+    env_list("FILE_ALLOWED_TYPES")
+}
+
+// How long a file offer waits for the recipient to accept or reject it
+pub fn get_file_offer_ttl() -> Duration {
+    Duration::from_secs(env_or("FILE_OFFER_TTL_SECS", 120))
+}
+
+// How long a call invite rings on the callee's devices before it times out
+pub fn get_call_ring_timeout() -> Duration {
+    Duration::from_secs(env_or("CALL_RING_TIMEOUT_SECS", 30))
+}
+
+// How long call records are kept; 0 keeps them until they are pushed out some other way
+pub fn get_call_retention() -> Option<Duration> {
+    let secs = env_or("CALL_RETENTION_SECS", 30 * 86400);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+// Newest missed calls kept per callee, so calls to a user who never signs in cannot pile up
+pub fn get_missed_calls_max() -> usize {
+    env_or("MISSED_CALLS_MAX", 50)
+}
+
+// Calls per call-history page unless the client asks for fewer
+pub fn get_call_history_page_size() -> usize {
+    env_or("CALL_HISTORY_PAGE_SIZE", 50)
+}
+
+// Largest whiteboard operation accepted, in bytes of JSON
+pub fn get_whiteboard_max_op_size() -> usize {
+    env_or("WHITEBOARD_MAX_OP_SIZE", 16 * 1024)
+}
+
+// Operations since the last snapshot after which a client is asked to take a new one, and past
+// which further operations are refused until it does
+pub fn get_whiteboard_snapshot_after() -> usize {
+    env_or("WHITEBOARD_SNAPSHOT_AFTER", 500)
+}
+
+pub fn get_whiteboard_max_ops() -> usize {
+    env_or("WHITEBOARD_MAX_OPS", 5000)
+}
+
+// Largest whiteboard snapshot accepted, in bytes of JSON
+pub fn get_whiteboard_max_snapshot_size() -> usize {
+    env_or("WHITEBOARD_MAX_SNAPSHOT_SIZE", 1024 * 1024)
+}
+
+// Operations per `whiteboard-state` page when catching a client up
+pub fn get_whiteboard_page_size() -> usize {
+    env_or("WHITEBOARD_PAGE_SIZE", 100)
+}
+
+// Largest CRDT update or merged document state accepted, in bytes
+pub fn get_document_max_update_size() -> usize {
+    env_or("DOCUMENT_MAX_UPDATE_SIZE", 64 * 1024)
+}
+
+pub fn get_documents_per_room() -> usize {
+    env_or("DOCUMENTS_PER_ROOM", 16)
+}
+
+// Updates since the stored state after which a client is asked to merge them into a new one, and
+// past which further updates are refused until it does
+pub fn get_document_snapshot_after() -> usize {
+    env_or("DOCUMENT_SNAPSHOT_AFTER", 200)
+}
+
+pub fn get_document_max_updates() -> usize {
+    env_or("DOCUMENT_MAX_UPDATES", 2000)
+}
+
+// Bytes a document may hold in its stored state and updates, and all of a room's documents
+// together; updates past either are refused until a snapshot shrinks them
+pub fn get_document_max_bytes() -> usize {
+    env_or("DOCUMENT_MAX_BYTES", 4 * 1024 * 1024)
+}
+
+pub fn get_room_documents_max_bytes() -> usize {
+    env_or("ROOM_DOCUMENTS_MAX_BYTES", 16 * 1024 * 1024)
+}
+
+// Updates per `doc-state` page when catching a client up
+pub fn get_document_page_size() -> usize {
+    env_or("DOCUMENT_PAGE_SIZE", 32)
+}
+
+pub fn get_polls_per_room() -> usize {
+    env_or("POLLS_PER_ROOM", 20)
+}
+
+pub fn get_poll_max_options() -> usize {
+    env_or("POLL_MAX_OPTIONS", 10)
+}
+
+// Longest poll question or option, in characters
+pub fn get_poll_max_length() -> usize {
+    env_or("POLL_MAX_LENGTH", 300)
+}
+
+// Longest Q&A question, in characters
+pub fn get_question_max_length() -> usize {
+    env_or("QUESTION_MAX_LENGTH", 500)
+}
+
+pub fn get_questions_per_room() -> usize {
+    env_or("QUESTIONS_PER_ROOM", 200)
+}
+
+// Reaction names clients may send, e.g. `thumbs-up,clap,heart`
+pub fn get_reactions() -> Vec<String> {
+    let reactions = env_list("REACTIONS");
+    if !reactions.is_empty() {
+        return reactions;
+    }
+    ["thumbs-up", "thumbs-down", "clap", "heart", "laugh", "surprised", "celebrate"]
+        .map(String::from)
+        .to_vec()
+}
+
+// Rooms with at least this many members get reactions as periodic counts instead of one by one
+pub fn get_reaction_aggregate_threshold() -> usize {
+    env_or("REACTION_AGGREGATE_THRESHOLD", 50)
+}
+
+pub fn get_reaction_flush_interval() -> Duration {
+    Duration::from_millis(env_or("REACTION_FLUSH_INTERVAL_MS", 1000))
+}
+
+// How long chat history is kept; 0 keeps it until the per-room cap pushes it out
+pub fn get_chat_retention() -> Option<Duration> {
+    let secs = env_or("CHAT_RETENTION_SECS", 7 * 86400);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+// Newest messages kept per room
+pub fn get_chat_history_max_messages() -> usize {
+    env_or("CHAT_HISTORY_MAX_MESSAGES", 1000)
+}
+
+// Messages sent to a late joiner, and per chat-history page unless the client asks for fewer
+pub fn get_chat_history_page_size() -> usize {
+    env_or("CHAT_HISTORY_PAGE_SIZE", 50)
+}
+
+pub fn get_host_transfer() -> HostTransfer {
+    env_or("HOST_TRANSFER", HostTransfer::CoHostFirst)
+}
+
+pub fn get_signature_mode() -> VerificationMode {
+    env_or("SIGNATURE_MODE", VerificationMode::Raw)
+}
+
+// Verification failures within the window that lock a client and its address out; 0 turns lockouts off
+pub fn get_lockout_max_failures() -> usize {
+    env_or("LOCKOUT_MAX_FAILURES", 5)
+}
+
+pub fn get_lockout_window() -> Duration {
+    Duration::from_secs(env_or("LOCKOUT_WINDOW_SECS", 60))
+}
+
+// The first lockout's length; each further one doubles it, up to the max
+pub fn get_lockout_base() -> Duration {
+    Duration::from_secs(env_or("LOCKOUT_BASE_SECS", 30))
+}
+
+pub fn get_lockout_max() -> Duration {
+    Duration::from_secs(env_or("LOCKOUT_MAX_SECS", 3600))
+}
+
+// Crypto policy: comma-separated signature algorithms (ecdsa-p256, ed25519) and hash functions
+// (sha-256, sha-384, sha-512) clients and certificate chains may use; everything when unset
+pub fn get_crypto_algorithms() -> Vec<String> {
+    env_list("CRYPTO_ALGORITHMS")
+}
+
+pub fn get_crypto_hashes() -> Vec<String> {
+    env_list("CRYPTO_HASHES")
+}
+
+pub fn get_crypto_min_rsa_bits() -> usize {
+    env_or("CRYPTO_MIN_RSA_BITS", 2048)
+}
+
+pub fn get_crypto_min_ec_bits() -> usize {
+    env_or("CRYPTO_MIN_EC_BITS", 256)
+}
+
+pub fn get_crypto_allow_compressed_points() -> bool {
+    env_or("CRYPTO_ALLOW_COMPRESSED_POINTS", true)
+}
+
+// Should stay above the signed max age plus skew, or a nonce could be forgotten while its message is still fresh
+pub fn get_nonce_ttl() -> Duration {
+    Duration::from_secs(env_or("NONCE_TTL_SECS", 300))
+}
+
+// Clients signing with an older scheme than this are turned away; never below the first
+// scheme that signs the nonce
+pub fn get_min_signature_version() -> u32 {
+    env_or("MIN_SIGNATURE_VERSION", 2)
+}
+
+// ICE candidates always need a verified sender; when set they must also be signed
+pub fn get_require_signed_ice() -> bool {
+    env_or("REQUIRE_SIGNED_ICE", false)
+}
+
+// Nothing but the challenge response (or a session resume) is accepted until the client has
+// proved it holds its key by answering; set to false to let clients skip the challenge
+pub fn get_require_challenge() -> bool {
+    env_or("REQUIRE_CHALLENGE", true)
+}
+
+// Tokens are optional unless this is set, but claims in a valid token are always applied
+pub fn get_jwt_required() -> bool {
+    env_or("JWT_REQUIRED", false)
+}
+
+pub fn get_jwt_secret() -> Option<String> {
+    std::env::var("JWT_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+pub fn get_jwt_jwks_url() -> Option<String> {
+    std::env::var("JWT_JWKS_URL").ok().filter(|url| !url.is_empty())
+}
+
+// How long fetching the key set may take, connecting included
+pub fn get_jwt_jwks_timeout() -> Duration {
+    Duration::from_secs(env_or("JWT_JWKS_TIMEOUT_SECS", 5))
+}
+
+// PEM contents of the key at JWT_PUBLIC_KEY_PATH
+pub fn get_jwt_public_key() -> Option<String> {
+    let path = std::env::var("JWT_PUBLIC_KEY_PATH").ok().filter(|path| !path.is_empty())?;
+    std::fs::read_to_string(path).ok()
+}
+
+pub fn get_jwt_audience() -> Option<String> {
+    std::env::var("JWT_AUDIENCE").ok().filter(|audience| !audience.is_empty())
+}
+
+pub fn get_jwt_issuer() -> Option<String> {
+    std::env::var("JWT_ISSUER").ok().filter(|issuer| !issuer.is_empty())
+}
+
+// JSON file of static API keys; setting this or API_KEYS_DB makes a key mandatory to connect
+pub fn get_api_keys_file() -> Option<String> {
+    std::env::var("API_KEYS_FILE").ok().filter(|path| !path.is_empty())
+}
+
+// SQLite database holding API keys
+pub fn get_api_keys_db() -> Option<String> {
+    std::env::var("API_KEYS_DB").ok().filter(|path| !path.is_empty())
+}
+
+// Connections are checked against an in-memory copy of API_KEYS_DB, reloaded this often
+pub fn get_api_keys_refresh_interval() -> Duration {
+    Duration::from_secs(env_or("API_KEYS_REFRESH_SECS", 30))
+}
+
+// wss:// is served directly when both are set
+pub fn get_tls_cert_path() -> Option<String> {
+    std::env::var("TLS_CERT_PATH").ok().filter(|path| !path.is_empty())
+}
+
+pub fn get_tls_key_path() -> Option<String> {
+    std::env::var("TLS_KEY_PATH").ok().filter(|path| !path.is_empty())
+}
+
+// PEM CA bundle that client certificates are verified against; enables mutual TLS
+pub fn get_tls_client_ca_path() -> Option<String> {
+    std::env::var("TLS_CLIENT_CA_PATH").ok().filter(|path| !path.is_empty())
+}
+
+// When false, clients without a certificate are still accepted (with no certificate identity)
+pub fn get_tls_client_cert_required() -> bool {
+    env_or("TLS_CLIENT_CERT_REQUIRED", true)
+}
+
+// PEM CA bundle trusted to issue signing-key certificates; falls back to the TLS client CA
+pub fn get_key_ca_path() -> Option<String> {
+    std::env::var("KEY_CA_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+        .or_else(get_tls_client_ca_path)
+}
+
+// Comma-separated domains to obtain certificates for via ACME; takes effect when no certificate paths are set
+pub fn get_acme_domains() -> Vec<String> {
+    env_list("ACME_DOMAINS")
+}
+
+// Files of CIDR ranges checked when a connection is accepted; reloadable through `reload-ip-filter`
+pub fn get_ip_allowlist_path() -> Option<String> {
+    std::env::var("IP_ALLOWLIST_PATH").ok().filter(|path| !path.is_empty())
+}
+
+pub fn get_ip_denylist_path() -> Option<String> {
+    std::env::var("IP_DENYLIST_PATH").ok().filter(|path| !path.is_empty())
+}
+
+// MaxMind GeoIP2/GeoLite2 Country or City database; country policy is off when unset
+pub fn get_geoip_db_path() -> Option<String> {
+    std::env::var("GEOIP_DB_PATH").ok().filter(|path| !path.is_empty())
+}
+
+// Comma-separated ISO 3166-1 alpha-2 country codes
+pub fn get_geoip_allow_countries() -> Vec<String> {
+    env_list("GEOIP_ALLOW_COUNTRIES")
+}
+
+pub fn get_geoip_deny_countries() -> Vec<String> {
+    env_list("GEOIP_DENY_COUNTRIES")
+}
+
+// Whether addresses the database has no country for get in
+pub fn get_geoip_allow_unknown() -> bool {
+    env_or("GEOIP_ALLOW_UNKNOWN", true)
+}
+
+// Concurrent connections allowed from one address and overall; 0 disables the limit. Connections
+// over either limit are answered with 503 and a Retry-After header before the WebSocket upgrade.
+pub fn get_max_connections_per_ip() -> usize {
+    env_or("MAX_CONNECTIONS_PER_IP", 20)
+}
+
+pub fn get_max_connections() -> usize {
+    env_or("MAX_CONNECTIONS", 10000)
+}
+
+pub fn get_connection_retry_after() -> Duration {
+    Duration::from_secs(env_or("CONNECTION_RETRY_AFTER_SECS", 5))
+}
+
+// How long a connection may take from accept to a finished TLS, WebSocket and Noise handshake
+// while it holds a connection permit
+pub fn get_handshake_timeout() -> Duration {
+    Duration::from_millis(env_or("HANDSHAKE_TIMEOUT_MS", 10_000))
+}
+
+// Largest WebSocket message and single frame tungstenite will buffer; anything bigger closes the
+// connection before it is read into memory. Leave room above MAX_PAYLOAD_SIZE for Noise overhead.
+pub fn get_max_message_size() -> usize {
+    env_or("MAX_MESSAGE_SIZE", 128 * 1024)
+}
+
+pub fn get_max_frame_size() -> usize {
+    env_or("MAX_FRAME_SIZE", 128 * 1024)
+}
+
+// Largest signal (after any decryption) that is parsed; senders of bigger ones are disconnected
+pub fn get_max_payload_size() -> usize {
+    env_or("MAX_PAYLOAD_SIZE", 64 * 1024)
+}
+
+// Largest session description relayed in an offer, answer or renegotiation
+pub fn get_max_sdp_size() -> usize {
+    env_or("MAX_SDP_SIZE", 32 * 1024)
+}
+
+// Upgrades that don't offer the peer-conference.v1 (or Noise) subprotocol are refused with 400
+pub fn get_require_subprotocol() -> bool {
+    env_or("REQUIRE_SUBPROTOCOL", true)
+}
+
+// permessage-deflate is agreed with clients that offer it. Messages shorter than the threshold
+// are sent as they are; the level is zlib's, 0 to 9.
+pub fn get_deflate_enabled() -> bool {
+    env_or("DEFLATE_ENABLED", true)
+}
+
+pub fn get_deflate_threshold() -> usize {
+    env_or("DEFLATE_THRESHOLD", 512)
+}
+
+pub fn get_deflate_level() -> u32 {
+    env_or("DEFLATE_LEVEL", 6u32).min(9)
+}
+
+// Comma-separated origins (e.g. https://meet.example.com) allowed to open a WebSocket; `*` or
+// unset allows any. Requests without an Origin header come from non-browser clients and pass.
+pub fn get_allowed_origins() -> Vec<String> {
+    env_list("ALLOWED_ORIGINS")
+}
+
+pub fn get_acme_contact() -> Option<String> {
+    std::env::var("ACME_CONTACT").ok().filter(|contact| !contact.is_empty())
+}
+
+pub fn get_acme_cache_dir() -> String {
+    env_or("ACME_CACHE_DIR", "acme-cache".to_string())
+}
+
+// Let's Encrypt staging issues untrusted certificates but has far looser rate limits
+pub fn get_acme_production() -> bool {
+    env_or("ACME_PRODUCTION", true)
+}
+
+pub fn get_tls_reload_interval() -> Duration {
+    Duration::from_secs(env_or("TLS_RELOAD_INTERVAL_SECS", 30))
+}
+
+pub fn get_signed_max_age() -> Duration {
+    Duration::from_secs(env_or("SIGNED_MAX_AGE_SECS", 60))
+}
+
+pub fn get_clock_skew() -> Duration {
+    Duration::from_secs(env_or("CLOCK_SKEW_SECS", 5))
+}
+
+pub fn get_room_db_path() -> String {
+    env_or("ROOM_DB_PATH", "rooms.db".to_string())
+}
+
+// Base64 public keys, one per line, that are refused everywhere; also receives keys revoked
+// through `revoke-keys`. Re-read when it changes.
+pub fn get_revoked_keys_path() -> Option<String> {
+    std::env::var("REVOKED_KEYS_PATH").ok().filter(|path| !path.is_empty())
+}
+
+pub fn get_revocation_reload_interval() -> Duration {
+    Duration::from_secs(env_or("REVOCATION_RELOAD_INTERVAL_SECS", 30))
+}
+
+// WebAuthn sign-in is offered when a relying party id (the site's domain) is set; the origin
+// defaults to https://<rp id>
+pub fn get_webauthn_rp_id() -> Option<String> {
+    std::env::var("WEBAUTHN_RP_ID").ok().filter(|rp_id| !rp_id.is_empty())
+}
+
+pub fn get_webauthn_origin() -> Option<String> {
+    std::env::var("WEBAUTHN_ORIGIN").ok().filter(|origin| !origin.is_empty())
+}
+
+// Lets clients without a token register a credential for a user id of their choosing
+pub fn get_webauthn_open_registration() -> bool {
+    env_or("WEBAUTHN_OPEN_REGISTRATION", false)
+}
+
+pub fn get_webauthn_db_path() -> String {
+    env_or("WEBAUTHN_DB_PATH", get_room_db_path())
+}
+
+// Base64 Ed25519 seed the server signs outbound messages with; generated on first start
+pub fn get_server_key_path() -> String {
+    env_or("SERVER_KEY_PATH", "server_identity.key".to_string())
+}
+
+// Blind relay mode: peers may only exchange session descriptions and candidates as sealed
+// messages, so plaintext offers, answers and ICE candidates are refused
+pub fn get_blind_relay() -> bool {
+    env_or("BLIND_RELAY", false)
+}
+
+// Lets native clients that request the Noise subprotocol run a Noise XX handshake over the
+// WebSocket, after which everything on the connection is encrypted
+pub fn get_noise_enabled() -> bool {
+    env_or("NOISE_ENABLED", false)
+}
+
+// Base64 X25519 private and public key, one per line; generated on first start
+pub fn get_noise_key_path() -> String {
+    env_or("NOISE_KEY_PATH", "noise_static.key".to_string())
+}
+
+// Base64 client static keys, one per line, allowed to complete the handshake; any key when unset
+pub fn get_noise_client_keys_path() -> Option<String> {
+    std::env::var("NOISE_CLIENT_KEYS_PATH").ok().filter(|path| !path.is_empty())
+}
+
+// Signals per second and burst size allowed from each connection; a rate of 0 disables the limit
+pub fn get_rate_limit_per_sec() -> f64 {
+    env_or("RATE_LIMIT_PER_SEC", 20.0)
+}
+
+pub fn get_rate_limit_burst() -> f64 {
+    env_or("RATE_LIMIT_BURST", 40.0)
+}
+
+// Shared by every connection from one address, so set above the per-connection limit to leave
+// room for clients behind the same NAT
+pub fn get_ip_rate_limit_per_sec() -> f64 {
+    env_or("IP_RATE_LIMIT_PER_SEC", 50.0)
+}
+
+pub fn get_ip_rate_limit_burst() -> f64 {
+    env_or("IP_RATE_LIMIT_BURST", 100.0)
+}
+
+// Per-connection budgets for individual signal types as comma-separated `type=rate/burst`
+// entries (e.g. `ice-candidate=15/40,chat=2/10`); these override the built-in defaults and are
+// capped at RATE_LIMIT_PER_SEC/RATE_LIMIT_BURST
+pub fn get_signal_rate_limits() -> HashMap<SignalKind, RateLimit> {
+    env_list("SIGNAL_RATE_LIMITS")
+        .iter()
+        .filter_map(|entry| {
+            let (signal_type, limit) = entry.split_once('=')?;
+            let kind = match SignalKind::of(signal_type.trim()) {
+                SignalKind::Unknown => Err(format!("unknown signal type {}", signal_type.trim())),
+                kind => Ok(kind),
+            };
+            match kind.and_then(|kind| Ok((kind, limit.parse()?))) {
+                Ok(limit) => Some(limit),
+                Err(e) => {
+                    eprintln!("Ignoring SIGNAL_RATE_LIMITS entry {}: {}", entry, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// A client with this many refused signals within the window is disconnected
+pub fn get_rate_limit_max_violations() -> u32 {
+    env_or("RATE_LIMIT_MAX_VIOLATIONS", 50)
+}
+
+pub fn get_rate_limit_window() -> Duration {
+    Duration::from_secs(env_or("RATE_LIMIT_WINDOW_SECS", 10))
+}
+
+// Audit records of security events go to every sink configured here: a JSON-lines file, a syslog
+// server (host:port, UDP) and/or an HTTP collector that records are POSTed to
+pub fn get_audit_log_path() -> Option<String> {
+    std::env::var("AUDIT_LOG_PATH").ok().filter(|path| !path.is_empty())
+}
+
+pub fn get_audit_syslog_addr() -> Option<String> {
+    std::env::var("AUDIT_SYSLOG_ADDR").ok().filter(|addr| !addr.is_empty())
+}
+
+pub fn get_audit_http_url() -> Option<String> {
+    std::env::var("AUDIT_HTTP_URL").ok().filter(|url| !url.is_empty())
+}
+
+// Records waiting for the HTTP collector; more are dropped, and logged, until it catches up
+pub fn get_audit_http_queue_size() -> usize {
+    env_or("AUDIT_HTTP_QUEUE_SIZE", 1024)
+}
+
+pub fn get_audit_http_connect_timeout() -> Duration {
+    Duration::from_secs(env_or("AUDIT_HTTP_CONNECT_TIMEOUT_SECS", 5))
+}
+
+// How long delivering one record may take, connecting included
+pub fn get_audit_http_timeout() -> Duration {
+    Duration::from_secs(env_or("AUDIT_HTTP_TIMEOUT_SECS", 10))
+}
+
+// Push gateway that call invites to users with nothing connected are posted to; no pushes if unset
+pub fn get_push_webhook_url() -> Option<String> {
+    std::env::var("PUSH_WEBHOOK_URL").ok().filter(|url| !url.is_empty())
+}
+
+// Pushes waiting for the gateway; more are dropped until it catches up
+pub fn get_push_queue_size() -> usize {
+    env_or("PUSH_QUEUE_SIZE", 256)
+}
+
+pub fn get_push_connect_timeout() -> Duration {
+    Duration::from_secs(env_or("PUSH_CONNECT_TIMEOUT_SECS", 5))
+}
+
+// How long one push may take, connecting included
+pub fn get_push_timeout() -> Duration {
+    Duration::from_secs(env_or("PUSH_TIMEOUT_SECS", 10))
+}
+
+// Secret shared with the TURN server (coturn's static-auth-secret); TURN credentials are only
+// issued when it is set
+pub fn get_turn_secret() -> Option<String> {
+    std::env::var("TURN_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+// Where the TURN secret is read from instead of TURN_SECRET: a file, or a reference for the
+// key provider
+pub fn get_turn_secret_ref() -> Option<String> {
+    std::env::var("TURN_SECRET_REF").ok().filter(|reference| !reference.is_empty())
+}
+
+// The 32-byte key persisted rooms, bans, reports and key pins are encrypted with: a file, or a
+// reference for the key provider. Unset stores them in plaintext.
+pub fn get_storage_key_ref() -> Option<String> {
+    std::env::var("STORAGE_KEY_REF").ok().filter(|reference| !reference.is_empty())
+}
+
+// Earlier storage keys, still accepted for reading; data sealed with them is re-encrypted with
+// the current key at startup, after which they can be removed
+pub fn get_storage_retired_key_refs() -> Vec<String> {
+    env_list("STORAGE_RETIRED_KEY_REFS")
+}
+
+// Comma-separated turn:/turns: URIs handed out with the credentials
+pub fn get_turn_uris() -> Vec<String> {
+    env_list("TURN_URIS")
+}
+
+pub fn get_turn_credential_ttl() -> Duration {
+    Duration::from_secs(env_or("TURN_CREDENTIAL_TTL_SECS", 3600))
+}
+
+// Server keys (TLS key, TURN secret, identity and Noise keys) are read through this backend:
+// file (the default), aws-kms, gcp-kms or pkcs11. With a KMS the key paths point at encrypted
+// blobs; with pkcs11 they are the labels of objects on the token.
+pub fn get_key_provider() -> String {
+    env_or("KEY_PROVIDER", "file".to_string())
+}
+
+pub fn get_gcp_kms_key_name() -> Option<String> {
+    std::env::var("GCP_KMS_KEY_NAME").ok().filter(|name| !name.is_empty())
+}
+
+// Path of the PKCS#11 module, e.g. /usr/lib/softhsm/libsofthsm2.so
+pub fn get_pkcs11_module() -> Option<String> {
+    std::env::var("PKCS11_MODULE").ok().filter(|path| !path.is_empty())
+}
+
+pub fn get_pkcs11_token_label() -> Option<String> {
+    std::env::var("PKCS11_TOKEN_LABEL").ok().filter(|label| !label.is_empty())
+}
+
+pub fn get_pkcs11_pin() -> Option<String> {
+    std::env::var("PKCS11_PIN").ok().filter(|pin| !pin.is_empty())
+}
+
+// Abuse reports and the server-wide ban list live alongside the rooms unless pointed elsewhere
+pub fn get_abuse_db_path() -> String {
+    env_or("ABUSE_DB_PATH", get_room_db_path())
+}
+
+// Chat history lives alongside the rooms unless pointed elsewhere
+pub fn get_chat_db_path() -> String {
+    env_or("CHAT_DB_PATH", get_room_db_path())
+}
+
+// Call records live alongside the rooms unless pointed elsewhere
+pub fn get_call_db_path() -> String {
+    env_or("CALL_DB_PATH", get_room_db_path())
+}
+
+// Pinned public keys live alongside the rooms unless pointed elsewhere
+pub fn get_key_pin_db_path() -> String {
+    env_or("KEY_PIN_DB_PATH", get_room_db_path())
+}
+
+// Verification batches that may run on the blocking pool at once; defaults to one per core
+pub fn get_verify_workers() -> usize {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    env_or("VERIFY_WORKERS", cores)
+}
+
+pub fn get_verify_max_batch() -> usize {
+    env_or("VERIFY_MAX_BATCH", 32)
+}
+
+fn env_list(key: &str) -> Vec<String> {
+    std::env::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
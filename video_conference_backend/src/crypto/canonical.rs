@@ -0,0 +1,315 @@
+use crate::models::message::{SecureConnectionPayload, SignalMessage};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+
+// Newest signing scheme this server understands
+pub const SIGNATURE_VERSION: u32 = 3;
+
+// Oldest scheme whose signature covers the nonce. Offers and answers always carry one, so
+// nothing older is accepted whatever the configured minimum, or a captured offer could be
+// replayed under a fresh nonce.
+pub const NONCE_SIGNATURE_VERSION: u32 = 2;
+
+// RFC 8785 (JCS) serialization: no whitespace, object keys sorted by UTF-16 code units and
+// numbers written the way ECMAScript's Number#toString writes them
+pub fn canonicalize(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
+// The bytes the sender signed. Clients that sign their own serialization can send it as
+// `signed_data`; it must describe the same content that is relayed, otherwise nothing is returned.
+pub fn signed_message(signal: &SignalMessage, payload: &SecureConnectionPayload) -> Option<Vec<u8>> {
+    let expected = signed_value(signal, payload)?;
+    signed_bytes(&expected, payload.signature_version, payload.signed_data.as_deref())
+}
+
+// Version 2 signs the whole envelope: signal type, the server-assigned sender id, the target, the
+// seq when there is one, and every payload field except the signature itself, the nonce and
+// timestamp included, so none of them can be altered in transit. Version 3 signs the same envelope
+// as deterministic CBOR, which leaves nothing to canonicalize. Version 1 signed only the offer and
+// timestamp, leaving the nonce free to change, and no longer verifies at all.
+fn signed_value(signal: &SignalMessage, payload: &SecureConnectionPayload) -> Option<Value> {
+    match payload.signature_version {
+        2 | 3 => envelope_value(signal),
+        _ => None,
+    }
+}
+
+// The bytes a signed ICE candidate covers: the envelope, as version 2 JSON or version 3 CBOR,
+// checked against `signed_data` the same way offers are
+pub fn signed_envelope(signal: &SignalMessage, signature_version: u32, signed_data: Option<&str>) -> Option<Vec<u8>> {
+    let expected = envelope_value(signal)?;
+    signed_bytes(&expected, signature_version, signed_data)
+}
+
+// JSON `signed_data` is accepted in any serialization of the expected value. CBOR has only the
+// one encoding, so `signed_data` (base64) adds nothing but must match it if sent.
+fn signed_bytes(expected: &Value, signature_version: u32, signed_data: Option<&str>) -> Option<Vec<u8>> {
+    if signature_version == 3 {
+        let encoded = canonical_cbor(expected);
+        return match signed_data {
+            Some(signed_data) => (STANDARD.decode(signed_data).ok()? == encoded).then_some(encoded),
+            None => Some(encoded),
+        };
+    }
+    match signed_data {
+        Some(signed_data) => {
+            let parsed: Value = serde_json::from_str(signed_data).ok()?;
+            (parsed == *expected).then(|| signed_data.as_bytes().to_vec())
+        }
+        None => Some(canonicalize(expected).into_bytes()),
+    }
+}
+
+// RFC 8949 section 4.2.1 core deterministic encoding: definite lengths, the shortest form of
+// every integer, length and float, and map entries sorted by the bytes of their encoded keys
+pub fn canonical_cbor(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_cbor(value, &mut out);
+    out
+}
+
+fn envelope_value(signal: &SignalMessage) -> Option<Value> {
+    let mut fields: Value = serde_json::from_str(&signal.payload).ok()?;
+    let fields_map = fields.as_object_mut()?;
+    fields_map.remove("signature");
+    fields_map.remove("signed_data");
+
+    let mut envelope = serde_json::json!({
+        "signal_type": signal.signal_type,
+        "sender_id": signal.sender_id,
+        "target_id": signal.target_id,
+        "payload": fields,
+    });
+    // Only part of the envelope for senders that number their messages
+    if let Some(seq) = signal.seq {
+        envelope["seq"] = seq.into();
+    }
+    Some(envelope)
+}
+
+fn write_cbor(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(flag) => out.push(if *flag { 0xf5 } else { 0xf4 }),
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(uint), _) => write_cbor_head(0, uint, out),
+            (None, Some(int)) => write_cbor_head(1, (-1 - int) as u64, out),
+            (None, None) => write_cbor_float(number.as_f64().unwrap_or(0.0), out),
+        },
+        Value::String(string) => {
+            write_cbor_head(3, string.len() as u64, out);
+            out.extend_from_slice(string.as_bytes());
+        }
+        Value::Array(items) => {
+            write_cbor_head(4, items.len() as u64, out);
+            for item in items {
+                write_cbor(item, out);
+            }
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(Vec<u8>, &Value)> = map
+                .iter()
+                .map(|(key, item)| (canonical_cbor(&Value::String(key.clone())), item))
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            write_cbor_head(5, entries.len() as u64, out);
+            for (key, item) in entries {
+                out.extend_from_slice(&key);
+                write_cbor(item, out);
+            }
+        }
+    }
+}
+
+fn write_cbor_head(major: u8, argument: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(argument as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&argument.to_be_bytes());
+        }
+    }
+}
+
+// The narrowest of half, single and double precision that holds the value exactly
+fn write_cbor_float(float: f64, out: &mut Vec<u8>) {
+    if float.is_nan() {
+        out.extend_from_slice(&[0xf9, 0x7e, 0x00]);
+        return;
+    }
+    let single = float as f32;
+    if single as f64 != float {
+        out.push(0xfb);
+        out.extend_from_slice(&float.to_bits().to_be_bytes());
+        return;
+    }
+    match half_bits(single) {
+        Some(half) => {
+            out.push(0xf9);
+            out.extend_from_slice(&half.to_be_bytes());
+        }
+        None => {
+            out.push(0xfa);
+            out.extend_from_slice(&single.to_bits().to_be_bytes());
+        }
+    }
+}
+
+// The IEEE half precision bits for `single`, when it has an exact one
+fn half_bits(single: f32) -> Option<u16> {
+    let bits = single.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    match exponent {
+        0xff => Some(sign | 0x7c00),
+        0 if mantissa == 0 => Some(sign),
+        // Single precision subnormals are far below the smallest half
+        0 => None,
+        _ => {
+            let exponent = exponent - 127;
+            if (-14..=15).contains(&exponent) {
+                (mantissa & 0x1fff == 0).then(|| sign | (((exponent + 15) as u16) << 10) | (mantissa >> 13) as u16)
+            } else if (-24..-14).contains(&exponent) {
+                let significand = mantissa | 0x80_0000;
+                let shift = -exponent - 1;
+                (significand & ((1 << shift) - 1) == 0).then(|| sign | (significand >> shift) as u16)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(flag) => out.push_str(if *flag { "true" } else { "false" }),
+        Value::Number(number) => write_number(number, out),
+        Value::String(string) => write_string(string, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_value(item, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(string: &str, out: &mut String) {
+    // serde_json already escapes exactly the characters JCS requires, with lowercase hex
+    out.push_str(&Value::String(string.to_string()).to_string());
+}
+
+fn write_number(number: &serde_json::Number, out: &mut String) {
+    const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+    if let Some(int) = number.as_i64() {
+        if int.unsigned_abs() <= MAX_SAFE_INTEGER {
+            out.push_str(&int.to_string());
+            return;
+        }
+    }
+    if let Some(uint) = number.as_u64() {
+        if uint <= MAX_SAFE_INTEGER {
+            out.push_str(&uint.to_string());
+            return;
+        }
+    }
+
+    // Everything else is an IEEE double, exactly as a browser would see it
+    let float = number.as_f64().unwrap_or(0.0);
+    out.push_str(&format_es_number(float));
+}
+
+fn format_es_number(float: f64) -> String {
+    if float == 0.0 {
+        return "0".to_string();
+    }
+
+    // `{:e}` yields the shortest round-tripping digits, e.g. "-1.2345e-7"
+    let (mut digits, mut exponent) = scientific_digits(&format!("{:e}", float.abs()));
+
+    // Halfway between two shortest candidates ECMAScript takes the even one; `{:e}` may not
+    if let Some((even_digits, even_exponent)) = even_tie_break(float.abs(), digits.len()) {
+        digits = even_digits;
+        exponent = even_exponent;
+    }
+
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+    let sign = if float < 0.0 { "-" } else { "" };
+
+    let body = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat((-n) as usize), digits)
+    } else {
+        let exponent = n - 1;
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        let fraction = if k > 1 { format!(".{}", &digits[1..]) } else { String::new() };
+        format!("{}{}e{}{}", &digits[..1], fraction, exponent_sign, exponent.abs())
+    };
+
+    format!("{}{}", sign, body)
+}
+
+// Splits "d.ddde-x" into its significant digits and decimal exponent
+fn scientific_digits(scientific: &str) -> (String, i32) {
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((scientific, "0"));
+    let digits = mantissa.chars().filter(|c| *c != '.').collect();
+    (digits, exponent.parse().unwrap_or(0))
+}
+
+// The rounded-down candidate of length `k` when `float` lies exactly halfway and that candidate is even
+fn even_tie_break(float: f64, k: usize) -> Option<(String, i32)> {
+    // Every double's exact decimal expansion fits in 1100 digits, so this does not round
+    let (exact, exponent) = scientific_digits(&format!("{:.1100e}", float));
+    let exact = exact.as_bytes();
+
+    let halfway = exact.get(k) == Some(&b'5') && exact[k + 1..].iter().all(|digit| *digit == b'0');
+    let even = k > 0 && (exact[k - 1] - b'0').is_multiple_of(2);
+    if !(halfway && even) {
+        return None;
+    }
+
+    let digits = String::from_utf8_lossy(&exact[..k]).trim_end_matches('0').to_string();
+    (!digits.is_empty()).then_some((digits, exponent))
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreshnessError {
+    Missing,
+    Stale,
+    InFuture,
+}
+
+impl FreshnessError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            FreshnessError::Missing => "missing-timestamp",
+            FreshnessError::Stale => "stale-timestamp",
+            FreshnessError::InFuture => "timestamp-in-future",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            FreshnessError::Missing => "Signed offers and answers must carry a signed timestamp",
+            FreshnessError::Stale => "Signed timestamp is too old; sign again",
+            FreshnessError::InFuture => "Signed timestamp is ahead of the server clock",
+        }
+    }
+}
+
+// `signed_at` and `now` are Unix seconds. `skew` widens the window on both sides to absorb clock drift.
+pub fn check_freshness(
+    signed_at: Option<i64>,
+    now: i64,
+    max_age: Duration,
+    skew: Duration
+) -> Result<(), FreshnessError> {
+    let signed_at = signed_at.ok_or(FreshnessError::Missing)?;
+    let skew = i64::try_from(skew.as_secs()).unwrap_or(i64::MAX);
+    let max_age = i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX);
+
+    if signed_at > now.saturating_add(skew) {
+        return Err(FreshnessError::InFuture);
+    }
+    if now.saturating_sub(signed_at) > max_age.saturating_add(skew) {
+        return Err(FreshnessError::Stale);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+pub mod canonical;
+pub mod freshness;
+pub mod nonces;
+pub mod policy;
+pub mod pool;
+pub mod revocation;
+pub mod sequence;
+pub mod server_identity;
+pub mod signature;
+pub mod verifier;
+pub mod x509;
+
+pub use canonical::{canonical_cbor, canonicalize, signed_envelope, signed_message, NONCE_SIGNATURE_VERSION, SIGNATURE_VERSION};
+pub use freshness::{check_freshness, FreshnessError};
+pub use nonces::{NonceCache, NonceError};
+pub use policy::{CryptoPolicy, HashFunction};
+pub use pool::{AsyncVerifier, VerificationPool, VerificationRequest};
+pub use revocation::RevocationList;
+pub use sequence::{check_sequence, SequenceError};
+pub use server_identity::{write_private_key, Ed25519Signer, ServerSigner};
+pub use signature::{
+    check_ed25519, check_p256, normalize_public_key, verify_ed25519, verify_p256, SignatureAlgorithm, VerificationCode, VerificationError,
+    VerificationMode,
+};
+pub use verifier::{DefaultVerifier, PolicyVerifier, SignatureVerifier};
+pub use x509::{TrustAnchors, VerifiedChain};
@@ -0,0 +1,67 @@
+use crate::config;
+use crate::crypto::normalize_public_key;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceError {
+    Missing,
+    Replayed,
+}
+
+impl NonceError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            NonceError::Missing => "missing-nonce",
+            NonceError::Replayed => "replayed-nonce",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            NonceError::Missing => "Secure offers and answers must carry a nonce",
+            NonceError::Replayed => "This nonce has already been used; sign again with a fresh one",
+        }
+    }
+}
+
+// Nonces seen per public key, remembered for `ttl` so a captured offer cannot be replayed
+#[derive(Debug)]
+pub struct NonceCache {
+    seen: HashMap<(Vec<u8>, Vec<u8>), Instant>,
+    ttl: Duration,
+}
+
+impl Default for NonceCache {
+    fn default() -> Self {
+        Self::new(config::get_nonce_ttl())
+    }
+}
+
+impl NonceCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            seen: HashMap::new(),
+            ttl,
+        }
+    }
+
+    // Records the nonce, failing if the same key already used it within the TTL
+    pub fn check(&mut self, public_key: &[u8], nonce: &[u8]) -> Result<(), NonceError> {
+        if nonce.is_empty() {
+            return Err(NonceError::Missing);
+        }
+
+        let now = Instant::now();
+        self.seen.retain(|_, expires_at| *expires_at > now);
+
+        // A nonce replayed under the other encoding of the key is still a replay
+        let key = (normalize_public_key(public_key), nonce.to_vec());
+        if self.seen.contains_key(&key) {
+            return Err(NonceError::Replayed);
+        }
+        self.seen.insert(key, now + self.ttl);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use crate::config;
+use crate::crypto::signature::{SignatureAlgorithm, VerificationCode, VerificationError};
+use std::str::FromStr;
+use x509_parser::oid_registry::OID_SIG_ED25519;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// Algorithms a certificate chain may be signed with, and the hash each one uses
+static CHAIN_ALGORITHMS: &[(&webpki::SignatureAlgorithm, Option<HashFunction>)] = &[
+    (&webpki::ECDSA_P256_SHA256, Some(HashFunction::Sha256)),
+    (&webpki::ECDSA_P256_SHA384, Some(HashFunction::Sha384)),
+    (&webpki::ECDSA_P384_SHA256, Some(HashFunction::Sha256)),
+    (&webpki::ECDSA_P384_SHA384, Some(HashFunction::Sha384)),
+    (&webpki::ED25519, None),
+    (&webpki::RSA_PKCS1_2048_8192_SHA256, Some(HashFunction::Sha256)),
+    (&webpki::RSA_PKCS1_2048_8192_SHA384, Some(HashFunction::Sha384)),
+    (&webpki::RSA_PKCS1_2048_8192_SHA512, Some(HashFunction::Sha512)),
+    (&webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY, Some(HashFunction::Sha256)),
+    (&webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY, Some(HashFunction::Sha384)),
+    (&webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY, Some(HashFunction::Sha512)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashFunction {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashFunction::Sha256 => "sha-256",
+            HashFunction::Sha384 => "sha-384",
+            HashFunction::Sha512 => "sha-512",
+        }
+    }
+}
+
+impl FromStr for HashFunction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "sha-256" | "sha256" => Ok(HashFunction::Sha256),
+            "sha-384" | "sha384" => Ok(HashFunction::Sha384),
+            "sha-512" | "sha512" => Ok(HashFunction::Sha512),
+            other => Err(format!("unknown hash function: {}", other)),
+        }
+    }
+}
+
+// Interop rules every client signature and certificate chain is held to. The defaults accept
+// everything the server can verify.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CryptoPolicy {
+    pub algorithms: Vec<SignatureAlgorithm>,
+    // Applies to ECDSA client signatures (always SHA-256) and to certificate chain signatures
+    pub hashes: Vec<HashFunction>,
+    pub min_rsa_bits: usize,
+    pub min_ec_bits: usize,
+    pub allow_compressed_points: bool,
+}
+
+impl Default for CryptoPolicy {
+    fn default() -> Self {
+        Self {
+            algorithms: vec![SignatureAlgorithm::EcdsaP256, SignatureAlgorithm::Ed25519],
+            hashes: vec![HashFunction::Sha256, HashFunction::Sha384, HashFunction::Sha512],
+            min_rsa_bits: 2048,
+            min_ec_bits: 256,
+            allow_compressed_points: true,
+        }
+    }
+}
+
+impl CryptoPolicy {
+    // Entries that are not understood are reported and left out, so a typo tightens the policy
+    // rather than loosening it
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let algorithms = config::get_crypto_algorithms();
+        let hashes = config::get_crypto_hashes();
+        Self {
+            algorithms: if algorithms.is_empty() { defaults.algorithms } else { parse_list(&algorithms, parse_algorithm) },
+            hashes: if hashes.is_empty() { defaults.hashes } else { parse_list(&hashes, |name| name.parse()) },
+            min_rsa_bits: config::get_crypto_min_rsa_bits(),
+            min_ec_bits: config::get_crypto_min_ec_bits(),
+            allow_compressed_points: config::get_crypto_allow_compressed_points(),
+        }
+    }
+
+    // Whether a client may sign with `algorithm` and `public_key` at all; checked before verifying
+    pub fn check_signature(&self, algorithm: SignatureAlgorithm, public_key: &[u8]) -> Result<(), VerificationError> {
+        let name = algorithm_name(algorithm);
+        if !self.algorithms.contains(&algorithm) {
+            return Err(violation(format!("{} signatures are not accepted by this server", name))
+                .with("algorithm", name)
+                .with("allowed_algorithms", self.algorithms.iter().map(|a| algorithm_name(*a)).collect::<Vec<_>>()));
+        }
+        // P-256 and Ed25519 keys both give 128-bit security
+        if self.min_ec_bits > 256 {
+            return Err(violation(format!("{} keys are smaller than the required {} bits", name, self.min_ec_bits))
+                .with("algorithm", name)
+                .with("min_ec_bits", self.min_ec_bits));
+        }
+        if algorithm == SignatureAlgorithm::EcdsaP256 {
+            if !self.hashes.contains(&HashFunction::Sha256) {
+                return Err(violation("ECDSA signatures use SHA-256, which is not accepted by this server")
+                    .with("hash", HashFunction::Sha256.name()));
+            }
+            if !self.allow_compressed_points && public_key.len() == 33 {
+                return Err(violation("Compressed public keys are not accepted; send the 65-byte uncompressed point")
+                    .with("actual", public_key.len()));
+            }
+        }
+        Ok(())
+    }
+
+    // The chain signature algorithms whose hash is allowed
+    pub fn chain_algorithms(&self) -> Vec<&'static webpki::SignatureAlgorithm> {
+        CHAIN_ALGORITHMS
+            .iter()
+            .filter(|(_, hash)| hash.is_none_or(|hash| self.hashes.contains(&hash)))
+            .map(|(algorithm, _)| *algorithm)
+            .collect()
+    }
+
+    // Holds every certificate in a chain to the minimum key sizes
+    pub fn check_certificate_key(&self, der: &[u8]) -> Result<(), String> {
+        let (_, certificate) = X509Certificate::from_der(der).map_err(|e| format!("Invalid certificate: {}", e))?;
+        let key_info = certificate.public_key();
+        if key_info.algorithm.algorithm == OID_SIG_ED25519 {
+            return match self.min_ec_bits > 256 {
+                true => Err(format!("Ed25519 certificate keys are smaller than the required {} bits", self.min_ec_bits)),
+                false => Ok(()),
+            };
+        }
+        match key_info.parsed() {
+            Ok(x509_parser::public_key::PublicKey::RSA(rsa)) if rsa.key_size() < self.min_rsa_bits => Err(format!(
+                "Certificate for {} has a {}-bit RSA key; at least {} bits are required",
+                certificate.subject(),
+                rsa.key_size(),
+                self.min_rsa_bits
+            )),
+            Ok(x509_parser::public_key::PublicKey::EC(point)) => {
+                if point.key_size() < self.min_ec_bits {
+                    return Err(format!(
+                        "Certificate for {} has a {}-bit EC key; at least {} bits are required",
+                        certificate.subject(),
+                        point.key_size(),
+                        self.min_ec_bits
+                    ));
+                }
+                let compressed = matches!(point.data().first(), Some(0x02 | 0x03));
+                if compressed && !self.allow_compressed_points {
+                    return Err(format!("Certificate for {} has a compressed EC key", certificate.subject()));
+                }
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Unreadable certificate key: {}", e)),
+        }
+    }
+}
+
+fn violation(message: impl Into<String>) -> VerificationError {
+    VerificationError::new(VerificationCode::PolicyViolation, message)
+}
+
+fn algorithm_name(algorithm: SignatureAlgorithm) -> &'static str {
+    match algorithm {
+        SignatureAlgorithm::EcdsaP256 => "ecdsa-p256",
+        SignatureAlgorithm::Ed25519 => "ed25519",
+    }
+}
+
+fn parse_algorithm(name: &str) -> Result<SignatureAlgorithm, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_ascii_lowercase()))
+        .map_err(|_| format!("unknown signature algorithm: {}", name))
+}
+
+fn parse_list<T>(names: &[String], parse: impl Fn(&str) -> Result<T, String>) -> Vec<T> {
+    names
+        .iter()
+        .filter_map(|name| parse(name).map_err(|e| eprintln!("[WARN] Crypto policy: {}", e)).ok())
+        .collect()
+}
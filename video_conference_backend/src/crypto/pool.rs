@@ -0,0 +1,150 @@
+use crate::crypto::signature::{SignatureAlgorithm, VerificationCode, VerificationError};
+use crate::crypto::verifier::SignatureVerifier;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+
+// One signature to check, owned so it can move to a blocking thread
+#[derive(Debug, Clone)]
+pub struct VerificationRequest {
+    pub algorithm: SignatureAlgorithm,
+    pub message: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
+
+impl VerificationRequest {
+    pub fn new(algorithm: SignatureAlgorithm, message: &[u8], signature: &[u8], public_key: &[u8]) -> Self {
+        Self {
+            algorithm,
+            message: message.to_vec(),
+            signature: signature.to_vec(),
+            public_key: public_key.to_vec(),
+        }
+    }
+
+    fn run(&self, verifier: &dyn SignatureVerifier) -> Result<(), VerificationError> {
+        verifier.verify(self.algorithm, &self.message, &self.signature, &self.public_key)
+    }
+}
+
+struct Job {
+    request: VerificationRequest,
+    reply: oneshot::Sender<Result<(), VerificationError>>,
+}
+
+// Hands verifications to a worker that runs them on the blocking pool. Requests that queue up
+// while every worker is busy are taken together and checked in one blocking task, so a burst of
+// offers costs a few thread hand-offs rather than one each.
+#[derive(Clone)]
+pub struct VerificationPool {
+    queue: mpsc::Sender<Job>,
+}
+
+impl VerificationPool {
+    // Must be called from within the runtime; `workers` bounds how many batches run at once
+    pub fn spawn(verifier: Arc<dyn SignatureVerifier>, workers: usize, max_batch: usize) -> Self {
+        let max_batch = max_batch.max(1);
+        let (queue, mut jobs) = mpsc::channel::<Job>(max_batch * workers.max(1) * 4);
+        let workers = Arc::new(Semaphore::new(workers.max(1)));
+
+        tokio::spawn(async move {
+            while let Some(job) = jobs.recv().await {
+                let Ok(permit) = Arc::clone(&workers).acquire_owned().await else {
+                    break;
+                };
+                let mut batch = vec![job];
+                while batch.len() < max_batch {
+                    match jobs.try_recv() {
+                        Ok(job) => batch.push(job),
+                        Err(_) => break,
+                    }
+                }
+
+                let verifier = Arc::clone(&verifier);
+                tokio::task::spawn_blocking(move || {
+                    for job in batch {
+                        // The caller may have gone away; nothing to report to then
+                        let _ = job.reply.send(job.request.run(verifier.as_ref()));
+                    }
+                    drop(permit);
+                });
+            }
+        });
+
+        Self { queue }
+    }
+
+    pub async fn verify(&self, request: VerificationRequest) -> Result<(), VerificationError> {
+        let (reply, result) = oneshot::channel();
+        self.queue
+            .send(Job { request, reply })
+            .await
+            .map_err(|_| stopped())?;
+        result.await.map_err(|_| stopped())?
+    }
+
+    // Checks all of `requests` and returns their results in the same order
+    pub async fn verify_all(&self, requests: Vec<VerificationRequest>) -> Vec<Result<(), VerificationError>> {
+        let mut pending = Vec::with_capacity(requests.len());
+        for request in requests {
+            let (reply, result) = oneshot::channel();
+            match self.queue.send(Job { request, reply }).await {
+                Ok(()) => pending.push(Some(result)),
+                Err(_) => pending.push(None),
+            }
+        }
+
+        let mut results = Vec::with_capacity(pending.len());
+        for result in pending {
+            results.push(match result {
+                Some(result) => result.await.unwrap_or_else(|_| Err(stopped())),
+                None => Err(stopped()),
+            });
+        }
+        results
+    }
+}
+
+// Where verification runs: the server's pool when it has one, otherwise a one-off blocking task
+#[derive(Clone)]
+pub enum AsyncVerifier {
+    Pooled(VerificationPool),
+    Blocking(Arc<dyn SignatureVerifier>),
+}
+
+impl AsyncVerifier {
+    pub async fn verify(&self, request: VerificationRequest) -> Result<(), VerificationError> {
+        match self {
+            AsyncVerifier::Pooled(pool) => pool.verify(request).await,
+            AsyncVerifier::Blocking(verifier) => {
+                let verifier = Arc::clone(verifier);
+                tokio::task::spawn_blocking(move || request.run(verifier.as_ref()))
+                    .await
+                    .map_err(|e| VerificationError::new(VerificationCode::VerifyFailed, e.to_string()))?
+            }
+        }
+    }
+
+    pub async fn verify_all(&self, requests: Vec<VerificationRequest>) -> Vec<Result<(), VerificationError>> {
+        match self {
+            AsyncVerifier::Pooled(pool) => pool.verify_all(requests).await,
+            AsyncVerifier::Blocking(verifier) => {
+                let verifier = Arc::clone(verifier);
+                let count = requests.len();
+                tokio::task::spawn_blocking(move || {
+                    requests.iter().map(|request| request.run(verifier.as_ref())).collect()
+                })
+                .await
+                .unwrap_or_else(|e| {
+                    (0..count)
+                        .map(|_| Err(VerificationError::new(VerificationCode::VerifyFailed, e.to_string())))
+                        .collect()
+                })
+            }
+        }
+    }
+}
+
+fn stopped() -> VerificationError {
+    VerificationError::new(VerificationCode::VerifyFailed, "The signature verification pool has stopped")
+}
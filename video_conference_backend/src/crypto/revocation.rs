@@ -0,0 +1,60 @@
+use crate::crypto::normalize_public_key;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::HashSet;
+use std::path::Path;
+
+pub type RevocationResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Public keys known to be compromised; nothing signed by them is accepted. Keys are held as
+// their uncompressed point, so a revoked key stays revoked in either encoding.
+#[derive(Debug, Clone, Default)]
+pub struct RevocationList {
+    keys: HashSet<Vec<u8>>,
+}
+
+impl RevocationList {
+    pub fn is_revoked(&self, public_key: &[u8]) -> bool {
+        self.keys.contains(&normalize_public_key(public_key))
+    }
+
+    // Returns whether the key was newly revoked
+    pub fn revoke(&mut self, public_key: Vec<u8>) -> bool {
+        self.keys.insert(normalize_public_key(&public_key))
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn from_file(path: &Path) -> RevocationResult<Self> {
+        let keys = load_file(path)?.iter().map(|public_key| normalize_public_key(public_key)).collect();
+        Ok(Self { keys })
+    }
+}
+
+// Base64 public keys, one per line; blank lines and `#` comments are skipped
+pub fn load_file(path: &Path) -> RevocationResult<HashSet<Vec<u8>>> {
+    let contents = std::fs::read_to_string(path)?;
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| STANDARD.decode(line).map_err(|e| format!("invalid key {}: {}", line, e).into()))
+        .collect()
+}
+
+// Appends keys revoked at runtime so they survive a restart
+pub fn append_to_file(path: &Path, public_keys: &[Vec<u8>]) -> RevocationResult<()> {
+    use std::io::Write;
+
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    for public_key in public_keys {
+        writeln!(file, "{}", STANDARD.encode(public_key))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,32 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+    Missing,
+    Stale,
+}
+
+impl SequenceError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SequenceError::Missing => "missing-seq",
+            SequenceError::Stale => "stale-seq",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            SequenceError::Missing => "Every message must carry a seq once sequence numbers are in use",
+            SequenceError::Stale => "seq must be greater than the last one accepted from this client",
+        }
+    }
+}
+
+// The new high-water mark when `seq` is strictly above `last`. A sender that has used sequence
+// numbers once has to keep using them, so it can't fall back to unsequenced replays.
+pub fn check_sequence(last: Option<u64>, seq: Option<u64>, required: bool) -> Result<Option<u64>, SequenceError> {
+    match (last, seq) {
+        (_, None) if required || last.is_some() => Err(SequenceError::Missing),
+        (_, None) => Ok(None),
+        (Some(last), Some(seq)) if seq <= last => Err(SequenceError::Stale),
+        (_, Some(seq)) => Ok(Some(seq)),
+    }
+}
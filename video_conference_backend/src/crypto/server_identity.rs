@@ -0,0 +1,160 @@
+use crate::crypto::{canonicalize, SignatureAlgorithm};
+use crate::models::SignalMessage;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+static SERVER_SIGNER: OnceLock<Arc<dyn ServerSigner>> = OnceLock::new();
+
+// The server's own identity key. Implement this to keep the private key in a KMS or HSM.
+pub trait ServerSigner: Send + Sync {
+    fn algorithm(&self) -> SignatureAlgorithm;
+    fn public_key(&self) -> Vec<u8>;
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+// Ed25519 key kept on local disk as a base64 32-byte seed
+pub struct Ed25519Signer {
+    key: SigningKey,
+}
+
+impl Ed25519Signer {
+    pub fn new(key: SigningKey) -> Self {
+        Self { key }
+    }
+
+    // Generates and saves a key on first start so the identity survives restarts
+    pub fn load_or_generate(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if path.exists() {
+            return Self::from_secret(&std::fs::read(path)?)
+                .map_err(|_| format!("{} does not hold a 32-byte Ed25519 seed", path.display()).into());
+        }
+
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        write_private_key(path, STANDARD.encode(key.to_bytes()).as_bytes())?;
+        println!("Generated server identity key at {}", path.display());
+        Ok(Self::new(key))
+    }
+
+    // A raw 32-byte seed, or the base64 text the key file holds, as a key provider returns it
+    pub fn from_secret(secret: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let seed = match secret.len() {
+            32 => secret.to_vec(),
+            _ => STANDARD.decode(std::str::from_utf8(secret)?.trim())?,
+        };
+        let seed: [u8; 32] = seed.try_into().map_err(|_| "not a 32-byte Ed25519 seed")?;
+        Ok(Self::new(SigningKey::from_bytes(&seed)))
+    }
+}
+
+// Creates a key file only the server's user can read; an existing file is never overwritten
+pub fn write_private_key(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+impl ServerSigner for Ed25519Signer {
+    fn algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Ed25519
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        self.key.verifying_key().to_bytes().to_vec()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, String> {
+        Ok(self.key.sign(message).to_bytes().to_vec())
+    }
+}
+
+// Set once at startup; outbound messages go unsigned until then
+pub fn install(signer: Arc<dyn ServerSigner>) {
+    if SERVER_SIGNER.set(signer).is_err() {
+        eprintln!("Server identity key is already installed; keeping the first one");
+    }
+}
+
+pub fn server_signer() -> Option<&'static Arc<dyn ServerSigner>> {
+    SERVER_SIGNER.get()
+}
+
+// Short id clients can use to recognise the key, e.g. when pinning it
+pub fn key_id(public_key: &[u8]) -> String {
+    Sha256::digest(public_key)[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Canonical JSON of the message without its server signature, which is what the server signs
+pub fn server_signed_message(signal: &SignalMessage) -> Vec<u8> {
+    let mut value = serde_json::to_value(signal).unwrap_or_default();
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("server_signature");
+    }
+    canonicalize(&value).into_bytes()
+}
+
+// Attaches the server's signature to a message about to go out
+pub fn sign_outbound(signal: &mut SignalMessage) {
+    let Some(signer) = server_signer() else {
+        return;
+    };
+    signal.server_signature = None;
+    match signer.sign(&server_signed_message(signal)) {
+        Ok(signature) => signal.server_signature = Some(signature),
+        Err(e) => eprintln!("Failed to sign outbound {}: {}", signal.signal_type, e),
+    }
+}
+
+// What the server signs when it relays a session description other than the one its sender
+// signed: the description as relayed, bound to a digest of the SDP the sender's signature covers
+// and to the signal it travels in, its sender, target and nonce, so the attestation cannot be
+// lifted onto another peer's signal or replayed
+pub fn rewrite_signed_message(signal: &SignalMessage, nonce: &Value, original_sdp: &str, description: &Value) -> Vec<u8> {
+    canonicalize(&serde_json::json!({
+        "description": description,
+        "original_sha256": sdp_digest(original_sdp),
+        "sender_id": signal.sender_id,
+        "target_id": signal.target_id,
+        "nonce": nonce,
+    }))
+    .into_bytes()
+}
+
+// Attributes a rewritten session description to the server. Peers check `signature` with the key
+// from `server-identity` over `rewrite_signed_message` in place of the sender's signature over
+// the SDP; without a server key the rewrite is only marked.
+pub fn attest_rewrite(signal: &SignalMessage, nonce: &Value, original_sdp: &str, description: &Value) -> Value {
+    let mut attestation = serde_json::json!({
+        "by": "server",
+        "original_sha256": sdp_digest(original_sdp),
+    });
+    let Some(signer) = server_signer() else {
+        return attestation;
+    };
+    match signer.sign(&rewrite_signed_message(signal, nonce, original_sdp, description)) {
+        Ok(signature) => {
+            attestation["key_id"] = key_id(&signer.public_key()).into();
+            attestation["algorithm"] = serde_json::to_value(signer.algorithm()).unwrap_or_default();
+            attestation["signature"] = STANDARD.encode(signature).into();
+        }
+        Err(e) => eprintln!("Failed to sign a rewritten session description: {}", e),
+    }
+    attestation
+}
+
+fn sdp_digest(sdp: &str) -> String {
+    Sha256::digest(sdp.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,252 @@
+use crate::crypto::FreshnessError;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::{EncodedPoint, FieldBytes};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+// Key type a client signs with, announced in its secure offer/answer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignatureAlgorithm {
+    #[default]
+    EcdsaP256,
+    Ed25519,
+}
+
+// What exactly the client's ECDSA signature covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationMode {
+    // ECDSA/SHA-256 over the message itself, as produced by WebCrypto, `openssl dgst -sha256 -sign` and
+    // p256's `sign`. A digest handed to a raw ECDSA primitive (PKCS#11 CKM_ECDSA, `openssl pkeyutl -sign`)
+    // yields the same signature, so such clients use this mode too.
+    Raw,
+    // The client hashed the message with SHA-256 itself and sent that digest as the data to sign, e.g.
+    // to WebCrypto after crypto.subtle.digest. The signature is ECDSA/SHA-256 over the digest, not over
+    // the message, so the server verifies the digest it computes as the signed data.
+    Prehashed,
+}
+
+impl VerificationMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VerificationMode::Raw => "raw",
+            VerificationMode::Prehashed => "prehashed",
+        }
+    }
+}
+
+impl FromStr for VerificationMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "raw" => Ok(VerificationMode::Raw),
+            "prehashed" => Ok(VerificationMode::Prehashed),
+            other => Err(format!("unknown signature verification mode: {}", other)),
+        }
+    }
+}
+
+// Machine-readable reason a signed message was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationCode {
+    UnsupportedVersion,
+    DataMismatch,
+    InvalidKeyLength,
+    InvalidKey,
+    InvalidLength,
+    InvalidEncoding,
+    VerifyFailed,
+    PolicyViolation,
+    Freshness(FreshnessError),
+}
+
+impl VerificationCode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerificationCode::UnsupportedVersion => "SIG_UNSUPPORTED_VERSION",
+            VerificationCode::DataMismatch => "SIG_DATA_MISMATCH",
+            VerificationCode::InvalidKeyLength => "SIG_INVALID_KEY_LENGTH",
+            VerificationCode::InvalidKey => "SIG_INVALID_KEY",
+            VerificationCode::InvalidLength => "SIG_INVALID_LENGTH",
+            VerificationCode::InvalidEncoding => "SIG_INVALID_ENCODING",
+            VerificationCode::VerifyFailed => "SIG_VERIFY_FAILED",
+            VerificationCode::PolicyViolation => "SIG_POLICY_VIOLATION",
+            VerificationCode::Freshness(error) => error.code(),
+        }
+    }
+}
+
+// A refused signature, with enough context (lengths, encodings, a digest of the bytes that were
+// checked) for the client to work out what it signed differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+    pub code: VerificationCode,
+    pub message: String,
+    pub context: Map<String, Value>,
+}
+
+impl VerificationError {
+    pub fn new(code: VerificationCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            context: Map::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.context.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code.code()
+    }
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+// Lets verifiers that only have a reason to give report a plain verification failure
+impl From<String> for VerificationError {
+    fn from(message: String) -> Self {
+        Self::new(VerificationCode::VerifyFailed, message)
+    }
+}
+
+// Hex SHA-256 of the bytes the server verified against, to compare with what the client signed
+pub fn message_digest(message: &[u8]) -> String {
+    Sha256::digest(message).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Ed25519 signs the message itself, so the verification mode does not apply
+pub fn verify_ed25519(message: &[u8], signature: &[u8], public_key: &[u8]) -> Result<(), String> {
+    check_ed25519(message, signature, public_key).map_err(|error| error.message)
+}
+
+pub fn check_ed25519(message: &[u8], signature: &[u8], public_key: &[u8]) -> Result<(), VerificationError> {
+    use ed25519_dalek::{Signature, VerifyingKey};
+
+    let public_key: &[u8; 32] = public_key.try_into().map_err(|_| {
+        VerificationError::new(
+            VerificationCode::InvalidKeyLength,
+            format!("Invalid Ed25519 public key length: expected 32 bytes, got {}", public_key.len())
+        )
+        .with("expected", 32)
+        .with("actual", public_key.len())
+    })?;
+    let verifying_key = VerifyingKey::from_bytes(public_key).map_err(|e| {
+        VerificationError::new(VerificationCode::InvalidKey, format!("Invalid Ed25519 public key: {}", e))
+    })?;
+    let signature = Signature::from_slice(signature).map_err(|_| {
+        VerificationError::new(
+            VerificationCode::InvalidLength,
+            format!("Invalid Ed25519 signature length: expected 64 bytes, got {}", signature.len())
+        )
+        .with("expected", 64)
+        .with("actual", signature.len())
+    })?;
+
+    verifying_key.verify_strict(message, &signature).map_err(|e| {
+        VerificationError::new(VerificationCode::VerifyFailed, format!("Signature verification failed: {}", e))
+            .with("algorithm", "ed25519")
+            .with("message_sha256", message_digest(message))
+    })
+}
+
+// A P-256 key as its uncompressed SEC1 point, so the compressed and uncompressed encodings of
+// one key compare equal; anything that is not a P-256 point, like an Ed25519 key, is kept as is
+pub fn normalize_public_key(public_key: &[u8]) -> Vec<u8> {
+    match VerifyingKey::from_sec1_bytes(public_key) {
+        Ok(key) if public_key.len() == 33 || public_key.len() == 65 => key.to_encoded_point(false).as_bytes().to_vec(),
+        _ => public_key.to_vec(),
+    }
+}
+
+// Accepts either raw r||s (64 bytes, as WebCrypto produces) or an ASN.1 DER sequence (OpenSSL and most native libraries)
+pub fn verify_p256(
+    message: &[u8],
+    signature: &[u8],
+    public_key: &[u8],
+    mode: VerificationMode
+) -> Result<(), String> {
+    check_p256(message, signature, public_key, mode).map_err(|error| error.message)
+}
+
+pub fn check_p256(
+    message: &[u8],
+    signature: &[u8],
+    public_key: &[u8],
+    mode: VerificationMode
+) -> Result<(), VerificationError> {
+    // Check public key length - P-256 public keys are uncompressed (65 bytes) or compressed (33 bytes)
+    if public_key.len() != 65 && public_key.len() != 33 {
+        return Err(VerificationError::new(
+            VerificationCode::InvalidKeyLength,
+            format!("Invalid public key length: expected 65 or 33 bytes, got {}", public_key.len())
+        )
+        .with("expected", serde_json::json!([65, 33]))
+        .with("actual", public_key.len()));
+    }
+
+    // Import public key
+    let encoded_point = EncodedPoint::from_bytes(public_key).map_err(|e| {
+        VerificationError::new(VerificationCode::InvalidKey, format!("Failed to parse public key: {}", e))
+    })?;
+    let verifying_key = VerifyingKey::from_encoded_point(&encoded_point).map_err(|e| {
+        VerificationError::new(VerificationCode::InvalidKey, format!("Invalid verifying key: {}", e))
+    })?;
+
+    let (signature, encoding) = if signature.len() == 64 {
+        let signature = Signature::from_scalars(
+            FieldBytes::clone_from_slice(&signature[..32]),
+            FieldBytes::clone_from_slice(&signature[32..])
+        )
+        .map_err(|e| {
+            VerificationError::new(VerificationCode::InvalidEncoding, format!("Failed to parse raw r||s signature: {}", e))
+                .with("encoding", "raw")
+        })?;
+        (signature, "raw")
+    } else if signature.first() == Some(&0x30) {
+        let signature = Signature::from_der(signature).map_err(|e| {
+            VerificationError::new(VerificationCode::InvalidEncoding, format!("Failed to parse DER signature: {}", e))
+                .with("encoding", "der")
+                .with("actual", signature.len())
+        })?;
+        (signature, "der")
+    } else {
+        return Err(VerificationError::new(
+            VerificationCode::InvalidLength,
+            format!(
+                "Unrecognized signature encoding ({} bytes): expected 64-byte raw r||s or an ASN.1 DER sequence",
+                signature.len()
+            )
+        )
+        .with("expected", 64)
+        .with("actual", signature.len()));
+    };
+
+    // `verify` applies ECDSA's own SHA-256 to whatever it is given
+    let result = match mode {
+        VerificationMode::Raw => verifying_key.verify(message, &signature),
+        VerificationMode::Prehashed => verifying_key.verify(&Sha256::digest(message), &signature),
+    };
+
+    result.map_err(|e| {
+        VerificationError::new(VerificationCode::VerifyFailed, format!("Signature verification failed: {}", e))
+            .with("algorithm", "ecdsa-p256")
+            .with("mode", mode.name())
+            .with("encoding", encoding)
+            .with("message_sha256", message_digest(message))
+    })
+}
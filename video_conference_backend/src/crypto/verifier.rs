@@ -0,0 +1,72 @@
+use crate::config;
+use crate::crypto::policy::CryptoPolicy;
+use crate::crypto::signature::{check_ed25519, check_p256, SignatureAlgorithm, VerificationError, VerificationMode};
+use std::sync::Arc;
+
+// Checks a client's signature over the bytes it claims to have signed. Deployments can
+// supply their own, e.g. to back keys with hardware or try other schemes.
+pub trait SignatureVerifier: Send + Sync {
+    fn verify(
+        &self,
+        algorithm: SignatureAlgorithm,
+        message: &[u8],
+        signature: &[u8],
+        public_key: &[u8]
+    ) -> Result<(), VerificationError>;
+}
+
+// P-256 in the given verification mode, plus Ed25519
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultVerifier {
+    pub mode: VerificationMode,
+}
+
+impl DefaultVerifier {
+    pub fn new(mode: VerificationMode) -> Self {
+        Self { mode }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(config::get_signature_mode())
+    }
+}
+
+impl SignatureVerifier for DefaultVerifier {
+    fn verify(
+        &self,
+        algorithm: SignatureAlgorithm,
+        message: &[u8],
+        signature: &[u8],
+        public_key: &[u8]
+    ) -> Result<(), VerificationError> {
+        match algorithm {
+            SignatureAlgorithm::EcdsaP256 => check_p256(message, signature, public_key, self.mode),
+            SignatureAlgorithm::Ed25519 => check_ed25519(message, signature, public_key),
+        }
+    }
+}
+
+// Holds `inner` to a crypto policy, so the policy applies whichever verifier is installed
+pub struct PolicyVerifier {
+    policy: Arc<CryptoPolicy>,
+    inner: Arc<dyn SignatureVerifier>,
+}
+
+impl PolicyVerifier {
+    pub fn new(policy: Arc<CryptoPolicy>, inner: Arc<dyn SignatureVerifier>) -> Self {
+        Self { policy, inner }
+    }
+}
+
+impl SignatureVerifier for PolicyVerifier {
+    fn verify(
+        &self,
+        algorithm: SignatureAlgorithm,
+        message: &[u8],
+        signature: &[u8],
+        public_key: &[u8]
+    ) -> Result<(), VerificationError> {
+        self.policy.check_signature(algorithm, public_key)?;
+        self.inner.verify(algorithm, message, signature, public_key)
+    }
+}
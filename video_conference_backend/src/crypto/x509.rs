@@ -0,0 +1,88 @@
+use crate::crypto::{CryptoPolicy, SignatureAlgorithm};
+use crate::tls::{self, CertIdentity};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::path::Path;
+use std::time::SystemTime;
+use x509_parser::oid_registry::{OID_EC_P256, OID_KEY_TYPE_EC_PUBLIC_KEY, OID_SIG_ED25519};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// DER certificates of the CAs allowed to issue client signing keys
+#[derive(Debug, Clone, Default)]
+pub struct TrustAnchors {
+    certificates: Vec<Vec<u8>>,
+}
+
+// What a validated chain vouches for
+#[derive(Debug, Clone)]
+pub struct VerifiedChain {
+    pub public_key: Vec<u8>,
+    pub algorithm: SignatureAlgorithm,
+    pub identity: CertIdentity,
+}
+
+impl TrustAnchors {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let certificates = rustls_pemfile::certs(&mut std::io::BufReader::new(file))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if certificates.is_empty() {
+            return Err(format!("no CA certificates in {}", path.display()));
+        }
+        Ok(Self { certificates })
+    }
+
+    // `chain` is base64 DER, leaf first; the leaf must carry a P-256 or Ed25519 key usable for
+    // client authentication and chain up to one of the anchors, within `policy`
+    pub fn verify_chain(&self, chain: &[String], policy: &CryptoPolicy, now: SystemTime) -> Result<VerifiedChain, String> {
+        let chain = chain
+            .iter()
+            .map(|certificate| STANDARD.decode(certificate))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Certificate is not valid base64: {}", e))?;
+        let (leaf, intermediates) = chain.split_first().ok_or("Certificate chain is empty")?;
+        for certificate in &chain {
+            policy.check_certificate_key(certificate)?;
+        }
+
+        let anchors = self.certificates
+            .iter()
+            .filter_map(|certificate| webpki::TrustAnchor::try_from_cert_der(certificate).ok())
+            .collect::<Vec<_>>();
+        let intermediates = intermediates.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let time = webpki::Time::try_from(now).map_err(|_| "System clock is before the Unix epoch")?;
+
+        webpki::EndEntityCert::try_from(leaf.as_slice())
+            .and_then(|end_entity| {
+                end_entity.verify_for_usage(
+                    &policy.chain_algorithms(),
+                    &anchors,
+                    &intermediates,
+                    time,
+                    webpki::KeyUsage::client_auth(),
+                    &[]
+                )
+            })
+            .map_err(|e| format!("Certificate chain rejected: {:?}", e))?;
+
+        let (public_key, algorithm) = signing_key(leaf)?;
+        let identity = tls::certificate_identity(leaf).ok_or("Certificate carries no usable subject")?;
+        Ok(VerifiedChain { public_key, algorithm, identity })
+    }
+}
+
+fn signing_key(der: &[u8]) -> Result<(Vec<u8>, SignatureAlgorithm), String> {
+    let (_, certificate) = X509Certificate::from_der(der).map_err(|e| format!("Invalid certificate: {}", e))?;
+    let key_info = certificate.public_key();
+    let key = key_info.subject_public_key.data.to_vec();
+
+    let algorithm = &key_info.algorithm;
+    if algorithm.algorithm == OID_SIG_ED25519 {
+        return Ok((key, SignatureAlgorithm::Ed25519));
+    }
+    let curve = algorithm.parameters.as_ref().and_then(|parameters| parameters.as_oid().ok());
+    if algorithm.algorithm == OID_KEY_TYPE_EC_PUBLIC_KEY && curve == Some(OID_EC_P256) {
+        return Ok((key, SignatureAlgorithm::EcdsaP256));
+    }
+    Err("Certificate key must be P-256 or Ed25519".to_string())
+}
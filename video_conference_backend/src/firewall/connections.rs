@@ -0,0 +1,92 @@
+use crate::config;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionLimit {
+    PerIp,
+    Global,
+}
+
+impl ConnectionLimit {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ConnectionLimit::PerIp => "Too many connections from this address",
+            ConnectionLimit::Global => "Server is at capacity",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+// Open connections overall and per source address; a limit of 0 means no limit
+#[derive(Debug, Default)]
+pub struct ConnectionTracker {
+    max_per_ip: usize,
+    max_total: usize,
+    counts: Mutex<Counts>,
+}
+
+impl ConnectionTracker {
+    pub fn new(max_per_ip: usize, max_total: usize) -> Self {
+        Self {
+            max_per_ip,
+            max_total,
+            counts: Mutex::default(),
+        }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(config::get_max_connections_per_ip(), config::get_max_connections())
+    }
+
+    // The connection counts against the limits until the returned permit is dropped
+    pub fn try_acquire(self: &Arc<Self>, ip: IpAddr) -> Result<ConnectionPermit, ConnectionLimit> {
+        let mut counts = self.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.max_total > 0 && counts.total >= self.max_total {
+            return Err(ConnectionLimit::Global);
+        }
+        let from_ip = counts.per_ip.entry(ip).or_default();
+        if self.max_per_ip > 0 && *from_ip >= self.max_per_ip {
+            return Err(ConnectionLimit::PerIp);
+        }
+        *from_ip += 1;
+        counts.total += 1;
+
+        Ok(ConnectionPermit {
+            tracker: Arc::clone(self),
+            ip,
+        })
+    }
+
+    pub fn active(&self) -> usize {
+        self.counts.lock().map(|counts| counts.total).unwrap_or_default()
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut counts = self.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        counts.total = counts.total.saturating_sub(1);
+        if let Some(from_ip) = counts.per_ip.get_mut(&ip) {
+            *from_ip = from_ip.saturating_sub(1);
+            if *from_ip == 0 {
+                counts.per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
+pub struct ConnectionPermit {
+    tracker: Arc<ConnectionTracker>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.tracker.release(self.ip);
+    }
+}
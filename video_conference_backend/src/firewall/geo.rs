@@ -0,0 +1,122 @@
+use crate::config;
+use crate::firewall::FilterResult;
+use maxminddb::{geoip2, Reader};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// Addresses the database has no country for (private ranges, new allocations) are counted here
+pub const UNKNOWN_REGION: &str = "unknown";
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RegionStats {
+    pub accepted: u64,
+    pub refused: u64,
+    pub active: u64,
+}
+
+// Country-level access policy over a MaxMind GeoIP2/GeoLite2 Country (or City) database.
+// Denied countries always lose; when the allowlist is non-empty only countries on it get in.
+pub struct GeoPolicy {
+    reader: Reader<Vec<u8>>,
+    allow: HashSet<String>,
+    deny: HashSet<String>,
+    // Whether addresses with no country in the database are let in
+    allow_unknown: bool,
+    stats: Mutex<HashMap<String, RegionStats>>,
+}
+
+impl GeoPolicy {
+    pub fn open(path: &Path, allow: Vec<String>, deny: Vec<String>, allow_unknown: bool) -> FilterResult<Self> {
+        let normalize = |codes: Vec<String>| codes.into_iter().map(|code| code.to_ascii_uppercase()).collect();
+        Ok(Self {
+            reader: Reader::open_readfile(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+            allow: normalize(allow),
+            deny: normalize(deny),
+            allow_unknown,
+            stats: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // None when no GeoIP database is configured
+    pub fn from_config() -> FilterResult<Option<Self>> {
+        let Some(path) = config::get_geoip_db_path() else {
+            return Ok(None);
+        };
+        Self::open(
+            Path::new(&path),
+            config::get_geoip_allow_countries(),
+            config::get_geoip_deny_countries(),
+            config::get_geoip_allow_unknown()
+        )
+        .map(Some)
+    }
+
+    // ISO 3166-1 alpha-2 code of the country the address is registered in
+    pub fn country(&self, ip: &IpAddr) -> Option<String> {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(*ip),
+            IpAddr::V4(_) => *ip,
+        };
+        let record: geoip2::Country = self.reader.lookup(ip).ok()?;
+        record
+            .country
+            .or(record.registered_country)
+            .and_then(|country| country.iso_code)
+            .map(str::to_string)
+    }
+
+    pub fn permits(&self, country: Option<&str>) -> bool {
+        match country {
+            Some(country) => !self.deny.contains(country) && (self.allow.is_empty() || self.allow.contains(country)),
+            None => self.allow_unknown,
+        }
+    }
+
+    // Looks the address up, counts the outcome against its region and, when let in, returns a
+    // guard that keeps the connection counted as active. Refusals return the region.
+    pub fn admit(self: &Arc<Self>, ip: &IpAddr) -> Result<RegionGuard, String> {
+        let country = self.country(ip);
+        let permitted = self.permits(country.as_deref());
+        let region = country.unwrap_or_else(|| UNKNOWN_REGION.to_string());
+
+        if let Ok(mut stats) = self.stats.lock() {
+            let entry = stats.entry(region.clone()).or_default();
+            if permitted {
+                entry.accepted += 1;
+                entry.active += 1;
+            } else {
+                entry.refused += 1;
+            }
+        }
+        match permitted {
+            true => Ok(RegionGuard { policy: Arc::clone(self), region }),
+            false => Err(region),
+        }
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, RegionStats> {
+        self.stats
+            .lock()
+            .map(|stats| stats.iter().map(|(region, stats)| (region.clone(), *stats)).collect())
+            .unwrap_or_default()
+    }
+}
+
+// Held for the life of a connection so it counts as active in its region
+pub struct RegionGuard {
+    policy: Arc<GeoPolicy>,
+    pub region: String,
+}
+
+impl Drop for RegionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut stats) = self.policy.stats.lock() {
+            if let Some(entry) = stats.get_mut(&self.region) {
+                entry.active = entry.active.saturating_sub(1);
+            }
+        }
+    }
+}
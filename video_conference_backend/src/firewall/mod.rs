@@ -0,0 +1,73 @@
+pub mod connections;
+pub mod geo;
+
+use crate::config;
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::path::Path;
+
+pub use connections::{ConnectionLimit, ConnectionPermit, ConnectionTracker};
+pub use geo::{GeoPolicy, RegionGuard, RegionStats};
+
+pub type FilterResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Connection-level IP filtering. Denied networks always lose; when the allowlist is non-empty
+// only addresses inside it get through.
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    pub allow: Vec<IpNet>,
+    pub deny: Vec<IpNet>,
+}
+
+impl IpFilter {
+    pub fn new(allow: Vec<IpNet>, deny: Vec<IpNet>) -> Self {
+        Self { allow, deny }
+    }
+
+    // Reads the configured list files; a list without a file is empty
+    pub fn from_config() -> FilterResult<Self> {
+        let allow = match config::get_ip_allowlist_path() {
+            Some(path) => load_list(Path::new(&path))?,
+            None => Vec::new(),
+        };
+        let deny = match config::get_ip_denylist_path() {
+            Some(path) => load_list(Path::new(&path))?,
+            None => Vec::new(),
+        };
+        Ok(Self::new(allow, deny))
+    }
+
+    pub fn permits(&self, ip: &IpAddr) -> bool {
+        // IPv4 clients on a dual-stack socket show up as mapped IPv6 addresses
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(*ip),
+            IpAddr::V4(_) => *ip,
+        };
+
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+}
+
+// CIDR ranges or bare addresses, one per line; blank lines and `#` comments are skipped
+pub fn load_list(path: &Path) -> FilterResult<Vec<IpNet>> {
+    let contents = std::fs::read_to_string(path)?;
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(parse_net)
+        .collect()
+}
+
+pub fn parse_net(entry: &str) -> FilterResult<IpNet> {
+    if let Ok(net) = entry.parse::<IpNet>() {
+        return Ok(net);
+    }
+    entry
+        .parse::<IpAddr>()
+        .map(IpNet::from)
+        .map_err(|_| format!("invalid address or CIDR range: {}", entry).into())
+}
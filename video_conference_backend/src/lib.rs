@@ -1,3 +1,22 @@
+pub mod abuse;
+pub mod admin;
+pub mod audit;
+pub mod auth;
+pub mod calls;
+pub mod chat;
+pub mod crypto;
+pub mod firewall;
 pub mod models;
+pub mod noise;
+pub mod pinning;
+pub mod push;
+pub mod ratelimit;
+pub mod secrets;
 pub mod signaling;
 pub mod config;
+pub mod rooms;
+pub mod sessions;
+pub mod storage;
+pub mod tenants;
+pub mod tls;
+pub mod turn;
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+const MAX_CODECS: usize = 32;
+const MAX_CODEC_NAME_CHARS: usize = 64;
+
+// What a client's media stack supports, declared when it joins so peers can settle on a
+// configuration they share before the first offer
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    // Codec names in order of preference, e.g. `opus` or `video/VP9`
+    pub audio_codecs: Vec<String>,
+    pub video_codecs: Vec<String>,
+    pub simulcast: bool,
+    pub e2ee: bool,
+    pub data_channels: bool,
+}
+
+impl Capabilities {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for codecs in [&self.audio_codecs, &self.video_codecs] {
+            if codecs.len() > MAX_CODECS {
+                return Err("Too many codecs");
+            }
+            for codec in codecs {
+                let codec = codec.trim();
+                if codec.is_empty() {
+                    return Err("Codec names must not be blank");
+                }
+                if codec.chars().count() > MAX_CODEC_NAME_CHARS {
+                    return Err("Codec name is too long");
+                }
+                if codec.chars().any(|c| c.is_control() || c.is_whitespace()) {
+                    return Err("Codec names are single words");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Codec names lowercased and without their MIME type, so `video/VP8` and `vp8` match,
+    // keeping the first mention of each
+    pub fn normalized(mut self) -> Self {
+        self.audio_codecs = normalize_codecs(self.audio_codecs);
+        self.video_codecs = normalize_codecs(self.video_codecs);
+        self
+    }
+
+    // What every one of `members` supports: the codecs they all list, in the first member's
+    // order, and the features they all have. None when nobody has declared anything.
+    pub fn common<'a>(members: impl IntoIterator<Item = &'a Capabilities>) -> Option<Capabilities> {
+        let mut members = members.into_iter();
+        let mut common = members.next()?.clone();
+        for member in members {
+            common.audio_codecs.retain(|codec| member.audio_codecs.contains(codec));
+            common.video_codecs.retain(|codec| member.video_codecs.contains(codec));
+            common.simulcast &= member.simulcast;
+            common.e2ee &= member.e2ee;
+            common.data_channels &= member.data_channels;
+        }
+        Some(common)
+    }
+}
+
+fn normalize_codecs(codecs: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(codecs.len());
+    for codec in codecs {
+        let codec = codec.trim().to_ascii_lowercase();
+        let codec = match codec.split_once('/') {
+            Some(("audio" | "video", name)) => name.to_string(),
+            _ => codec,
+        };
+        if !normalized.contains(&codec) {
+            normalized.push(codec);
+        }
+    }
+    normalized
+}
@@ -1,6 +1,23 @@
+use crate::auth::{Claims, PendingCeremony};
+use crate::crypto::normalize_public_key;
+use crate::models::capabilities::Capabilities;
+use crate::models::encoding::WireEncoding;
+use crate::models::profile::Profile;
+use crate::ratelimit::{FailureTracker, RateLimiter};
+use crate::sessions::SessionToken;
+use crate::signaling::batching::IceBatch;
+use crate::signaling::calls::user_id;
+use crate::signaling::dedup::MessageIdCache;
+use crate::signaling::ordering::ReorderBuffer;
+use crate::tenants::Tenant;
+use crate::tls::CertIdentity;
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
-use tokio::sync::mpsc;
-use tokio_tungstenite::tungstenite::protocol::Message;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{mpsc, Notify};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message};
 
 #[derive(Debug, Clone)]
 pub struct Client {
@@ -9,6 +26,110 @@ pub struct Client {
     pub address: SocketAddr,
     pub public_key: Option<Vec<u8>>,
     pub verified: bool,
+    // Outstanding connect-time challenge and whether it has been answered
+    pub challenge: Option<String>,
+    pub authenticated: bool,
+    // Set once the client presents a valid JWT
+    pub claims: Option<Claims>,
+    // Outstanding WebAuthn ceremony, and the user a completed assertion signed in as
+    pub webauthn: Option<PendingCeremony>,
+    pub webauthn_user: Option<String>,
+    // Tenant of the API key presented at connect time
+    pub tenant: Option<Tenant>,
+    // Verified TLS client certificate, when mutual TLS is on
+    pub certificate: Option<CertIdentity>,
+    // Static key the client proved in a Noise handshake
+    pub noise_key: Option<Vec<u8>>,
+    // Framing settled by the subprotocol; signals are encoded in it as they are queued
+    pub encoding: WireEncoding,
+    // Created with the configured limits on the first signal
+    pub rate_limiter: Option<RateLimiter>,
+    // Signature and challenge failures, for progressive lockout
+    pub verification_failures: FailureTracker,
+    pub room_id: Option<String>,
+    pub resume_token: Option<String>,
+    // Highest seq accepted from this client
+    pub last_seq: Option<u64>,
+    // Recent message ids, so retransmissions are not handled twice
+    pub recent_messages: MessageIdCache,
+    // Relayed messages on their way to this client, per sender; shared by every copy of the client
+    pub reorder: Arc<Mutex<ReorderBuffer>>,
+    // Candidates waiting to go out together, for clients that take them batched
+    pub ice_batch: Arc<Mutex<IceBatch>>,
+    // Settled by the hello exchange; clients that skip it speak version 1 with no extras
+    pub protocol_version: u32,
+    pub features: Vec<String>,
+    // Issued once the client verifies; the previous one is kept until it expires
+    pub session_tokens: Vec<SessionToken>,
+    pub presence: Presence,
+    pub profile: Profile,
+    // Declared when joining a room; None until the client does
+    pub capabilities: Option<Capabilities>,
+    // Role within the current room; reset when the client leaves it
+    pub role: Role,
+    // Set by moderators; clients are expected to keep their microphone off while it holds
+    pub muted_by_host: bool,
+    // When the room was last told this client is typing; None once it has been told it stopped
+    pub typing_since: Option<Instant>,
+    // Notified to make the connection task drop the socket
+    pub shutdown: Arc<Notify>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Host,
+    Moderator,
+    #[default]
+    Participant,
+    Viewer,
+}
+
+impl Role {
+    // Hosts and moderators may use privileged room commands
+    pub fn can_moderate(&self) -> bool {
+        matches!(self, Role::Host | Role::Moderator)
+    }
+
+    // Moderation commands only apply to participants of a lower rank
+    pub fn outranks(&self, other: &Role) -> bool {
+        self.rank() > other.rank()
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Role::Host => 3,
+            Role::Moderator => 2,
+            Role::Participant => 1,
+            Role::Viewer => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Presence {
+    #[default]
+    Active,
+    Away,
+    Speaking,
+    ScreenSharing,
+}
+
+// What other room members get to know about a participant
+#[derive(Debug, Clone, Serialize)]
+pub struct ParticipantInfo {
+    pub client_id: String,
+    pub public_key: Option<Vec<u8>>,
+    pub verified: bool,
+    pub presence: Presence,
+    pub role: Role,
+    pub muted_by_host: bool,
+    // Subject of the client certificate, so peers can tell who is behind a connection
+    pub identity: Option<String>,
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub capabilities: Option<Capabilities>,
 }
 
 impl Client {
@@ -23,6 +144,79 @@ impl Client {
             address,
             public_key: None,
             verified: false,
+            challenge: None,
+            authenticated: false,
+            claims: None,
+            webauthn: None,
+            webauthn_user: None,
+            tenant: None,
+            certificate: None,
+            noise_key: None,
+            encoding: WireEncoding::Json,
+            rate_limiter: None,
+            verification_failures: FailureTracker::default(),
+            room_id: None,
+            resume_token: None,
+            last_seq: None,
+            recent_messages: MessageIdCache::default(),
+            reorder: Arc::new(Mutex::new(ReorderBuffer::default())),
+            ice_batch: Arc::new(Mutex::new(IceBatch::default())),
+            protocol_version: 1,
+            features: Vec::new(),
+            session_tokens: Vec::new(),
+            presence: Presence::default(),
+            profile: Profile::default(),
+            capabilities: None,
+            role: Role::default(),
+            muted_by_host: false,
+            typing_since: None,
+            shutdown: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn tenant_id(&self) -> Option<&str> {
+        self.tenant.as_ref().map(|tenant| tenant.tenant_id.as_str())
+    }
+
+    // Who the client is across connections: the user it is signed in as, else its verified key.
+    // Keys are normalized, so one key counts once whichever encoding it arrives in. None until
+    // the client is verified.
+    pub fn identity(&self) -> Option<Vec<u8>> {
+        if !self.verified {
+            return None;
+        }
+        match user_id(self) {
+            Some(user_id) => Some([b"user:".as_slice(), user_id.as_bytes()].concat()),
+            None => {
+                let public_key = self.public_key.as_deref()?;
+                Some([b"key:".as_slice(), &normalize_public_key(public_key)].concat())
+            }
         }
     }
-}
\ No newline at end of file
+
+    pub fn participant_info(&self) -> ParticipantInfo {
+        ParticipantInfo {
+            client_id: self.client_id.clone(),
+            public_key: self.public_key.clone(),
+            verified: self.verified,
+            presence: self.presence,
+            role: self.role,
+            muted_by_host: self.muted_by_host,
+            identity: self.certificate.as_ref().map(|certificate| certificate.subject.clone()),
+            profile: self.profile.clone(),
+            capabilities: self.capabilities.clone(),
+        }
+    }
+
+    // Sends a close frame after anything already queued, then stops reading from the socket
+    pub async fn disconnect(&self, reason: &str) {
+        let frame = CloseFrame {
+            code: CloseCode::Policy,
+            reason: reason.to_string().into(),
+        };
+        if let Err(e) = self.sender.send(Message::Close(Some(frame))).await {
+            eprintln!("Close error to {}: {}", self.address, e);
+        }
+        self.shutdown.notify_one();
+    }
+}
@@ -0,0 +1,61 @@
+use crate::models::sequenced::{Sequenced, SequencedLog};
+use serde::{Deserialize, Serialize};
+
+// A CRDT update as a client produced it (an automerge change, a yrs update, ...). The server
+// never decodes one; it numbers them per document so clients can tell what they have seen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentUpdate {
+    pub version: u64,
+    pub client_id: String,
+    #[serde(with = "base64_bytes")]
+    pub update: Vec<u8>,
+}
+
+// A document as merged by a client from every update up to `version`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentState {
+    pub version: u64,
+    #[serde(with = "base64_bytes")]
+    pub state: Vec<u8>,
+}
+
+// A named document in a room's shared state: the latest merged state a client handed in and
+// the updates since. Applying the updates on top of the state gives the document as it stands;
+// CRDT merges are idempotent, so an update the state already covers does no harm.
+pub type SharedDocument = SequencedLog<DocumentUpdate, DocumentState>;
+
+impl Sequenced for DocumentUpdate {
+    fn seq(&self) -> u64 {
+        self.version
+    }
+
+    fn size(&self) -> usize {
+        self.update.len()
+    }
+}
+
+impl Sequenced for DocumentState {
+    fn seq(&self) -> u64 {
+        self.version
+    }
+
+    fn size(&self) -> usize {
+        self.state.len()
+    }
+}
+
+// CRDT updates and states travel as standard base64 strings rather than arrays of byte values
+pub mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::models::proto::Envelope;
+use crate::models::SignalMessage;
+use prost::Message as _;
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+pub type EncodingResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// How a connection's signals are framed. JSON travels in text frames; MessagePack and CBOR carry
+// the same message model, field names included, in binary frames, and Protobuf carries it as the
+// numbered fields of proto/signaling.proto.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireEncoding {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+    Protobuf,
+}
+
+impl WireEncoding {
+    pub fn encode(&self, signal: &SignalMessage) -> EncodingResult<Message> {
+        Ok(match self {
+            WireEncoding::Json => Message::Text(serde_json::to_string(signal)?),
+            WireEncoding::MessagePack => Message::Binary(rmp_serde::to_vec_named(signal)?),
+            WireEncoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::ser::into_writer(signal, &mut bytes)?;
+                Message::Binary(bytes)
+            }
+            WireEncoding::Protobuf => Message::Binary(Envelope::from(signal.clone()).encode_to_vec()),
+        })
+    }
+
+    // Frames of the other kind are refused rather than guessed at
+    pub fn decode(&self, frame: &Message) -> EncodingResult<SignalMessage> {
+        match (self, frame) {
+            (WireEncoding::Json, Message::Text(text)) => Ok(serde_json::from_str(text)?),
+            (WireEncoding::MessagePack, Message::Binary(bytes)) => Ok(rmp_serde::from_slice(bytes)?),
+            (WireEncoding::Cbor, Message::Binary(bytes)) => Ok(ciborium::de::from_reader(bytes.as_slice())?),
+            (WireEncoding::Protobuf, Message::Binary(bytes)) => Ok(Envelope::decode(bytes.as_slice())?.into()),
+            (WireEncoding::Json, _) => Err("expected a JSON text frame".into()),
+            (WireEncoding::MessagePack, _) => Err("expected a MessagePack binary frame".into()),
+            (WireEncoding::Cbor, _) => Err("expected a CBOR binary frame".into()),
+            (WireEncoding::Protobuf, _) => Err("expected a Protobuf binary frame".into()),
+        }
+    }
+
+    // Picks the correlation id out of a frame that did not decode as a signal, if it is there
+    pub fn peek_correlation_id(&self, frame: &Message) -> Option<String> {
+        let value: Value = match (self, frame) {
+            (WireEncoding::Json, Message::Text(text)) => serde_json::from_str(text).ok()?,
+            (WireEncoding::MessagePack, Message::Binary(bytes)) => rmp_serde::from_slice(bytes).ok()?,
+            (WireEncoding::Cbor, Message::Binary(bytes)) => ciborium::de::from_reader(bytes.as_slice()).ok()?,
+            // Field 11 can be read even when the rest of the envelope is unusable
+            (WireEncoding::Protobuf, Message::Binary(bytes)) => return CorrelationOnly::decode(bytes.as_slice()).ok()?.correlation_id,
+            _ => return None,
+        };
+        value.get("correlation_id")?.as_str().map(str::to_string)
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct CorrelationOnly {
+    #[prost(string, optional, tag = "11")]
+    correlation_id: Option<String>,
+}
@@ -1,5 +1,14 @@
+use crate::crypto::SignatureAlgorithm;
+use crate::models::capabilities::Capabilities;
+use crate::models::client::{Presence, Role};
+use crate::models::document::base64_bytes;
+use crate::models::question::QuestionAction;
+use crate::models::room::RoomConfig;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
+pub const SERVER_SENDER_ID: &str = "server";
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SignalMessage {
     pub signal_type: String,
@@ -7,12 +16,463 @@ pub struct SignalMessage {
     pub sender_id: String,
     pub timestamp: i64,
     pub signature: Option<Vec<u8>>,
+    pub target_id: Option<String>,
+    // Strictly increasing per sender; signed with the rest of a version 2 envelope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    // Set by the server on relayed messages: counts each sender's messages to this recipient,
+    // which are delivered in that order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay_seq: Option<u64>,
+    // Added by the server to everything it sends, over the rest of the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_signature: Option<Vec<u8>>,
+    // Issued after verification and sent back with later messages; taken off before relaying
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_token: Option<String>,
+    // Chosen by the client and echoed in errors about the message; not signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    // Chosen by the client; acks for a relayed message name it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+}
+
+impl SignalMessage {
+    // Messages originated by the server itself rather than relayed from a peer
+    pub fn server(signal_type: &str, payload: serde_json::Value) -> Self {
+        Self {
+            signal_type: signal_type.to_string(),
+            payload: payload.to_string(),
+            sender_id: SERVER_SENDER_ID.to_string(),
+            timestamp: Utc::now().timestamp(),
+            signature: None,
+            target_id: None,
+            seq: None,
+            relay_seq: None,
+            server_signature: None,
+            session_token: None,
+            correlation_id: None,
+            message_id: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SecureConnectionPayload {
     pub offer: serde_json::Value,
+    // May be left empty when `certificate_chain` supplies the key
+    #[serde(default)]
     pub public_key: Vec<u8>,
     pub signature: Vec<u8>,
     pub nonce: Vec<u8>,
-}
\ No newline at end of file
+    // Unix seconds at signing time; signed together with the offer
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+    // Type of `public_key`; clients that predate this field use P-256
+    #[serde(default)]
+    pub algorithm: SignatureAlgorithm,
+    // Which signing scheme produced `signature`; clients that predate versioning use 1
+    #[serde(default = "default_signature_version")]
+    pub signature_version: u32,
+    // Exact JSON text the signature covers, for clients that cannot produce canonical JSON; with
+    // version 3, the base64 of the CBOR
+    #[serde(default)]
+    pub signed_data: Option<String>,
+    // Base64 DER certificates, leaf first, issued by a configured CA; the leaf's key replaces
+    // `public_key` and its subject becomes the client's certificate identity
+    #[serde(default)]
+    pub certificate_chain: Option<Vec<String>>,
+    // Whether the sender applies end-to-end media encryption
+    #[serde(default)]
+    pub e2ee: bool,
+}
+
+fn default_signature_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinRoomPayload {
+    pub room_id: String,
+    // Only applied when this join creates the room
+    pub max_participants: Option<usize>,
+    pub password: Option<String>,
+    // Grants access in place of the room password
+    pub invite_token: Option<String>,
+    pub capabilities: Option<Capabilities>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateRoomPayload {
+    pub room_id: String,
+    pub password: Option<String>,
+    pub max_participants: Option<usize>,
+    #[serde(default)]
+    pub lobby: bool,
+    #[serde(default)]
+    pub listed: bool,
+    #[serde(default)]
+    pub config: RoomConfig,
+    // Scheduling is restricted to admins
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub admin_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatPayload {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReactionPayload {
+    pub reaction: String,
+}
+
+// What a peer proposes to send over a data channel. The bytes never touch the server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileOfferPayload {
+    // Chosen by the offerer; names the transfer in the reply
+    pub transfer_id: String,
+    pub name: String,
+    pub size: u64,
+    pub mime_type: String,
+    // Hex SHA-256 of the file, for the recipient to check what arrives
+    pub sha256: String,
+    // Label of the data channel the file will be sent on
+    pub label: String,
+}
+
+// Accepts or rejects a file offer
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileResponsePayload {
+    pub transfer_id: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WhiteboardOpPayload {
+    pub op: serde_json::Value,
+}
+
+// Asks for the board as it stands after `since`, for a client that noticed a gap; everything
+// when None
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WhiteboardSyncPayload {
+    pub since: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PollCreatePayload {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub anonymous: bool,
+}
+
+// `option` is an index into the poll's options
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PollVotePayload {
+    pub poll_id: String,
+    pub option: usize,
+}
+
+// Names a poll for poll-close and poll-results
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PollPayload {
+    pub poll_id: String,
+}
+
+// Rings `callee`, a user id, under a call id the caller chose
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CallInvitePayload {
+    pub call_id: String,
+    pub callee: String,
+    pub room_id: Option<String>,
+}
+
+// The newest calls the signed-in user placed or received
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CallHistoryPayload {
+    pub limit: Option<usize>,
+}
+
+// Names a call for call-ringing, call-accept, call-reject, call-cancel and call-hangup
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CallPayload {
+    pub call_id: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuestionAskPayload {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuestionUpvotePayload {
+    pub question_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuestionModeratePayload {
+    pub question_id: String,
+    pub action: QuestionAction,
+}
+
+// An update to the room's document `doc`, in whatever encoding its CRDT library uses, as base64
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocUpdatePayload {
+    pub doc: String,
+    #[serde(with = "base64_bytes")]
+    pub update: Vec<u8>,
+}
+
+// `doc` as merged from every update up to `version`, as base64
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocSnapshotPayload {
+    pub doc: String,
+    pub version: u64,
+    #[serde(with = "base64_bytes")]
+    pub state: Vec<u8>,
+}
+
+// Asks for `doc` as it stands; every document in the room when None
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DocSyncPayload {
+    pub doc: Option<String>,
+}
+
+// A page of the room's chat before `before`, a message id from an earlier page; the latest
+// page when None
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChatHistoryPayload {
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinDecisionPayload {
+    pub client_id: String,
+    pub approved: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LockRoomPayload {
+    #[serde(default = "default_true")]
+    pub locked: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateInvitePayload {
+    pub expires_in_secs: Option<u64>,
+    pub max_uses: Option<u32>,
+    pub role: Option<Role>,
+    // Lets holders past the room's lobby; without it they wait for admission like anyone else
+    #[serde(default)]
+    pub bypass_lobby: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListRoomsPayload {
+    pub admin_token: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RevokeKeysPayload {
+    pub admin_token: Option<String>,
+    pub public_keys: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportPayload {
+    pub client_id: String,
+    pub reason: String,
+    #[serde(default)]
+    pub evidence: Option<serde_json::Value>,
+}
+
+// Bans `public_key` and/or `identity`; naming a connected `client_id` bans its key and identity
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BanPayload {
+    pub admin_token: Option<String>,
+    pub public_key: Option<Vec<u8>>,
+    pub identity: Option<String>,
+    pub client_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UnbanPayload {
+    pub admin_token: Option<String>,
+    pub public_key: Option<Vec<u8>>,
+    pub identity: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AdminListPayload {
+    pub admin_token: Option<String>,
+    pub limit: Option<usize>,
+}
+
+// Lists given here replace the current ones; omitted lists are re-read from their files
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReloadIpFilterPayload {
+    pub admin_token: Option<String>,
+    pub allow: Option<Vec<String>>,
+    pub deny: Option<Vec<String>>,
+}
+
+// A new session description for a connection an earlier verified offer and answer set up
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenegotiatePayload {
+    pub description: serde_json::Value,
+}
+
+// A renegotiation offer with fresh ICE credentials, sent when the sender's network changed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IceRestartPayload {
+    pub description: serde_json::Value,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResumeSessionPayload {
+    pub resume_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PresencePayload {
+    pub status: Presence,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KickPayload {
+    pub client_id: String,
+    // Also block the target's IP and public key from rejoining the room
+    #[serde(default)]
+    pub ban: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MuteRequestPayload {
+    pub client_id: String,
+    #[serde(default = "default_true")]
+    pub muted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MuteAllPayload {
+    #[serde(default = "default_true")]
+    pub muted: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LowerHandPayload {
+    // Moderators may lower someone else's hand; defaults to the sender
+    #[serde(default)]
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleChangePayload {
+    pub client_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeResponsePayload {
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    #[serde(default)]
+    pub algorithm: SignatureAlgorithm,
+}
+
+// `signature` is made with the current key and `proof` with the new one, both over the
+// rotation message, so the rotation is authorised by the old key and the new key is held
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateKeyPayload {
+    pub new_public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub proof: Vec<u8>,
+    pub nonce: Vec<u8>,
+    #[serde(default)]
+    pub algorithm: SignatureAlgorithm,
+}
+
+// Signature fields carried alongside an ICE candidate; the candidate itself is relayed as sent.
+// The signature covers the whole envelope, like a version 2 offer, and is made with the key
+// bound by the sender's verified offer or answer.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CandidateSignature {
+    #[serde(default)]
+    pub signature: Option<Vec<u8>>,
+    #[serde(default)]
+    pub nonce: Option<Vec<u8>>,
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+    #[serde(default)]
+    pub algorithm: SignatureAlgorithm,
+    #[serde(default)]
+    pub signed_data: Option<String>,
+    // 2 signs the envelope as canonical JSON, 3 as deterministic CBOR
+    #[serde(default = "default_candidate_signature_version")]
+    pub signature_version: u32,
+}
+
+fn default_candidate_signature_version() -> u32 {
+    2
+}
+
+// Binary WebAuthn fields travel base64url encoded, as browsers' toJSON() produces them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WebauthnBeginPayload {
+    // Required for sign-in and for open registration; otherwise the token subject is used
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebauthnRegisterPayload {
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebauthnLoginPayload {
+    pub credential_id: String,
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+// `sealed_key` is the sender's media key for `epoch`, sealed to the target's public key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyAnnouncePayload {
+    pub epoch: u64,
+    pub sealed_key: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KeyRequestPayload {
+    // Defaults to the room's current epoch
+    pub epoch: Option<u64>,
+}
+
+// Protocol versions the client speaks, in any order, and the optional features it would use
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HelloPayload {
+    pub versions: Vec<u32>,
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticatePayload {
+    pub token: String,
+}
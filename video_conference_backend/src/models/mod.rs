@@ -1,5 +1,27 @@
+pub mod capabilities;
 pub mod client;
+pub mod document;
+pub mod encoding;
 pub mod message;
+pub mod poll;
+pub mod profile;
+pub mod proto;
+pub mod question;
+pub mod room;
+pub mod schema;
+pub mod sequenced;
+pub mod signal;
+pub mod whiteboard;
 
-pub use client::Client;
-pub use message::SignalMessage;
\ No newline at end of file
+pub use capabilities::Capabilities;
+pub use client::{Client, Presence, Role};
+pub use document::{DocumentState, DocumentUpdate, SharedDocument};
+pub use encoding::WireEncoding;
+pub use message::SignalMessage;
+pub use poll::{Poll, PollResults, PollVote, VoteError};
+pub use profile::Profile;
+pub use question::{Question, QuestionAction, QuestionError, QuestionQueue, QuestionStatus};
+pub use room::{ChatPermission, Room, RoomConfig, WaitingJoin};
+pub use sequenced::{Sequenced, SequencedLog};
+pub use signal::{Payload, Signal, SignalKind};
+pub use whiteboard::{Whiteboard, WhiteboardOp, WhiteboardSnapshot};
@@ -0,0 +1,83 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+// A question put to the room. Each verified identity votes once, however many connections it
+// has; anonymous polls never reveal who chose what, not even to the host.
+#[derive(Debug, Clone)]
+pub struct Poll {
+    pub poll_id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    pub anonymous: bool,
+    pub created_by: String,
+    pub closed: bool,
+    // Votes by the voter's identity, see `Client::identity`
+    votes: HashMap<Vec<u8>, PollVote>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct PollVote {
+    pub client_id: String,
+    pub option: usize,
+}
+
+// How a poll came out, as `poll-results` carries it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PollResults {
+    pub poll_id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    // Votes for each option, in option order
+    pub counts: Vec<u32>,
+    pub total: u32,
+    // Left out of anonymous polls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub votes: Option<Vec<PollVote>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoteError {
+    Closed,
+    AlreadyVoted,
+    UnknownOption,
+}
+
+impl Poll {
+    pub fn new(poll_id: String, question: String, options: Vec<String>, anonymous: bool, created_by: String) -> Self {
+        Self { poll_id, question, options, anonymous, created_by, closed: false, votes: HashMap::new() }
+    }
+
+    pub fn vote(&mut self, voter: &[u8], client_id: &str, option: usize) -> Result<(), VoteError> {
+        if self.closed {
+            return Err(VoteError::Closed);
+        }
+        if option >= self.options.len() {
+            return Err(VoteError::UnknownOption);
+        }
+        if self.votes.contains_key(voter) {
+            return Err(VoteError::AlreadyVoted);
+        }
+        self.votes.insert(voter.to_vec(), PollVote { client_id: client_id.to_string(), option });
+        Ok(())
+    }
+
+    pub fn results(&self) -> PollResults {
+        let mut counts = vec![0u32; self.options.len()];
+        for vote in self.votes.values() {
+            counts[vote.option] += 1;
+        }
+        let votes = (!self.anonymous).then(|| {
+            let mut votes: Vec<PollVote> = self.votes.values().cloned().collect();
+            votes.sort();
+            votes
+        });
+        PollResults {
+            poll_id: self.poll_id.clone(),
+            question: self.question.clone(),
+            options: self.options.clone(),
+            counts,
+            total: self.votes.len() as u32,
+            votes,
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+const MAX_DISPLAY_NAME_CHARS: usize = 64;
+const MAX_AVATAR_URL_LEN: usize = 2048;
+const MAX_METADATA_BYTES: usize = 4096;
+
+// Self-reported participant details shared with the rest of the room
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Profile {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let Some(name) = &self.display_name {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err("Display name must not be blank");
+            }
+            if name.chars().count() > MAX_DISPLAY_NAME_CHARS {
+                return Err("Display name is too long");
+            }
+            if name.chars().any(char::is_control) {
+                return Err("Display name contains control characters");
+            }
+        }
+
+        if let Some(url) = &self.avatar_url {
+            if url.len() > MAX_AVATAR_URL_LEN {
+                return Err("Avatar URL is too long");
+            }
+            if !url.starts_with("https://") {
+                return Err("Avatar URL must use https");
+            }
+        }
+
+        let metadata_size = serde_json::to_vec(&self.metadata).map(|bytes| bytes.len()).unwrap_or(usize::MAX);
+        if metadata_size > MAX_METADATA_BYTES {
+            return Err("Profile metadata is too large");
+        }
+
+        Ok(())
+    }
+
+    pub fn normalized(mut self) -> Self {
+        self.display_name = self.display_name.map(|name| name.trim().to_string());
+        self
+    }
+}
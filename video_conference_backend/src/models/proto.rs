@@ -0,0 +1,70 @@
+use crate::models::SignalMessage;
+
+// proto/signaling.proto's Envelope. Written out by hand so builds need no protoc; keep the tags
+// in step with the .proto file. Only the framing is Protobuf: `payload` is the same JSON text a
+// JSON client would send.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Envelope {
+    #[prost(string, tag = "1")]
+    pub signal_type: String,
+    #[prost(string, tag = "2")]
+    pub payload: String,
+    #[prost(string, tag = "3")]
+    pub sender_id: String,
+    #[prost(int64, tag = "4")]
+    pub timestamp: i64,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub signature: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "6")]
+    pub target_id: Option<String>,
+    #[prost(uint64, optional, tag = "7")]
+    pub seq: Option<u64>,
+    #[prost(uint64, optional, tag = "8")]
+    pub relay_seq: Option<u64>,
+    #[prost(bytes = "vec", optional, tag = "9")]
+    pub server_signature: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "10")]
+    pub session_token: Option<String>,
+    #[prost(string, optional, tag = "11")]
+    pub correlation_id: Option<String>,
+    #[prost(string, optional, tag = "12")]
+    pub message_id: Option<String>,
+}
+
+impl From<SignalMessage> for Envelope {
+    fn from(signal: SignalMessage) -> Self {
+        Self {
+            signal_type: signal.signal_type,
+            payload: signal.payload,
+            sender_id: signal.sender_id,
+            timestamp: signal.timestamp,
+            signature: signal.signature,
+            target_id: signal.target_id,
+            seq: signal.seq,
+            relay_seq: signal.relay_seq,
+            server_signature: signal.server_signature,
+            session_token: signal.session_token,
+            correlation_id: signal.correlation_id,
+            message_id: signal.message_id,
+        }
+    }
+}
+
+impl From<Envelope> for SignalMessage {
+    fn from(envelope: Envelope) -> Self {
+        Self {
+            signal_type: envelope.signal_type,
+            payload: envelope.payload,
+            sender_id: envelope.sender_id,
+            timestamp: envelope.timestamp,
+            signature: envelope.signature,
+            target_id: envelope.target_id,
+            seq: envelope.seq,
+            relay_seq: envelope.relay_seq,
+            server_signature: envelope.server_signature,
+            session_token: envelope.session_token,
+            correlation_id: envelope.correlation_id,
+            message_id: envelope.message_id,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuestionStatus {
+    // Waiting for a moderator; only moderators and the asker see it
+    Pending,
+    Approved,
+    Answered,
+}
+
+// What moderators can do to a question; dismissing one removes it from the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuestionAction {
+    Approve,
+    Answer,
+    Dismiss,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Question {
+    pub question_id: String,
+    pub client_id: String,
+    pub text: String,
+    pub status: QuestionStatus,
+    pub upvotes: usize,
+    pub asked_at: i64,
+    // Identities of the members who upvoted, see `Client::identity`, so each counts once
+    #[serde(skip)]
+    voters: HashSet<Vec<u8>>,
+}
+
+impl Question {
+    // Pending questions show up for moderators, and for whoever asked them
+    pub fn visible_to(&self, viewer: &str, moderator: bool) -> bool {
+        self.status != QuestionStatus::Pending || moderator || self.client_id == viewer
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuestionError {
+    Unknown,
+    // Only approved questions still waiting for an answer take upvotes
+    NotOpen,
+    AlreadyUpvoted,
+}
+
+// A room's questions in the order they were asked
+#[derive(Debug, Clone, Default)]
+pub struct QuestionQueue {
+    questions: Vec<Question>,
+}
+
+impl QuestionQueue {
+    pub fn len(&self) -> usize {
+        self.questions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.questions.is_empty()
+    }
+
+    pub fn get(&self, question_id: &str) -> Option<&Question> {
+        self.questions.iter().find(|question| question.question_id == question_id)
+    }
+
+    pub fn ask(&mut self, question_id: String, client_id: &str, text: &str, asked_at: i64) -> &Question {
+        self.questions.push(Question {
+            question_id,
+            client_id: client_id.to_string(),
+            text: text.to_string(),
+            status: QuestionStatus::Pending,
+            upvotes: 0,
+            asked_at,
+            voters: HashSet::new(),
+        });
+        &self.questions[self.questions.len() - 1]
+    }
+
+    pub fn moderate(&mut self, question_id: &str, action: QuestionAction) -> Result<(), QuestionError> {
+        let index = self.questions
+            .iter()
+            .position(|question| question.question_id == question_id)
+            .ok_or(QuestionError::Unknown)?;
+        match action {
+            QuestionAction::Approve => self.questions[index].status = QuestionStatus::Approved,
+            QuestionAction::Answer => self.questions[index].status = QuestionStatus::Answered,
+            QuestionAction::Dismiss => {
+                self.questions.remove(index);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn upvote(&mut self, question_id: &str, voter: &[u8]) -> Result<(), QuestionError> {
+        let question = self.questions
+            .iter_mut()
+            .find(|question| question.question_id == question_id)
+            .ok_or(QuestionError::Unknown)?;
+        if question.status != QuestionStatus::Approved {
+            return Err(QuestionError::NotOpen);
+        }
+        if !question.voters.insert(voter.to_vec()) {
+            return Err(QuestionError::AlreadyUpvoted);
+        }
+        question.upvotes += 1;
+        Ok(())
+    }
+
+    // The queue as `viewer` sees it: open questions by upvotes, ties in the order they were asked,
+    // then answered ones
+    pub fn ordered(&self, viewer: &str, moderator: bool) -> Vec<&Question> {
+        let mut visible: Vec<&Question> = self.questions
+            .iter()
+            .filter(|question| question.visible_to(viewer, moderator))
+            .collect();
+        visible.sort_by_key(|question| (question.status == QuestionStatus::Answered, std::cmp::Reverse(question.upvotes)));
+        visible
+    }
+}
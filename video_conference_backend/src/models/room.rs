@@ -0,0 +1,230 @@
+use crate::crypto::normalize_public_key;
+use crate::models::client::Role;
+use crate::models::document::SharedDocument;
+use crate::models::poll::Poll;
+use crate::models::question::QuestionQueue;
+use crate::models::whiteboard::Whiteboard;
+use crate::rooms::InviteClaims;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+
+// Per-room policy profile, sent to clients when they join
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomConfig {
+    pub audio_only: bool,
+    pub max_bitrate_kbps: Option<u32>,
+    pub e2ee_required: bool,
+    pub recording_allowed: bool,
+    // Concurrent screen shares allowed; falls back to the server-wide limit
+    pub max_screen_shares: Option<usize>,
+    // ICE candidate filtering; each falls back to the server-wide setting
+    pub strip_mdns_candidates: Option<bool>,
+    pub relay_only: Option<bool>,
+    pub drop_private_candidates: Option<bool>,
+    pub chat: ChatPermission,
+    // Largest file peers may offer each other, in bytes; falls back to the server-wide limit
+    pub max_file_size: Option<u64>,
+    // MIME types peers may offer, e.g. `image/*`; falls back to the server-wide list
+    pub allowed_file_types: Option<Vec<String>>,
+}
+
+// Who may send chat messages in a room
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChatPermission {
+    #[default]
+    Everyone,
+    // Everyone but viewers
+    Participants,
+    // Hosts and moderators only
+    Moderators,
+    Disabled,
+}
+
+impl ChatPermission {
+    pub fn allows(&self, role: Role) -> bool {
+        match self {
+            ChatPermission::Everyone => true,
+            ChatPermission::Participants => role != Role::Viewer,
+            ChatPermission::Moderators => role.can_moderate(),
+            ChatPermission::Disabled => false,
+        }
+    }
+}
+
+// A join held in the lobby, with what it brought along so admission can finish it the same way
+#[derive(Debug, Clone)]
+pub struct WaitingJoin {
+    pub addr: SocketAddr,
+    // The room id as the client named it, which is what JWT room claims refer to
+    pub requested_room_id: String,
+    pub invite: Option<InviteClaims>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Room {
+    pub room_id: String,
+    // Only clients of the same tenant can see or join the room
+    pub tenant_id: Option<String>,
+    pub members: Vec<SocketAddr>,
+    pub max_participants: usize,
+    pub password_hash: Option<String>,
+    // Ephemeral rooms exist only while they have members; rooms created explicitly outlive them
+    pub ephemeral: bool,
+    pub last_activity: Instant,
+    pub host: Option<SocketAddr>,
+    // `pinning::identity` of whoever created the room. It outlives connections and restarts,
+    // and its holder takes the host back whenever it rejoins.
+    pub owner: Option<String>,
+    pub lobby_enabled: bool,
+    // Clients held in the lobby until the host admits them; they are not members yet
+    pub waiting: Vec<WaitingJoin>,
+    pub locked: bool,
+    // Unix timestamps (seconds) bounding a scheduled meeting
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    // Outstanding invite ids and their remaining uses (None means unlimited)
+    pub invites: HashMap<String, Option<u32>>,
+    // Listed rooms show up in `list-rooms` for every client
+    pub listed: bool,
+    pub config: RoomConfig,
+    pub banned_ips: HashSet<IpAddr>,
+    // Normalized with `normalize_public_key`; add through `ban_key`
+    pub banned_keys: HashSet<Vec<u8>>,
+    // Members with a raised hand, in the order they raised it
+    pub raised_hands: Vec<SocketAddr>,
+    // Members currently sharing their screen, in the order they started
+    pub screen_sharers: Vec<SocketAddr>,
+    // Media key epoch, bumped on every membership change once key management is in use. Keys
+    // themselves never pass through in the clear.
+    pub key_epoch: u64,
+    pub key_management: bool,
+    pub whiteboard: Whiteboard,
+    // Collaborative documents by name, e.g. shared notes or an agenda
+    pub documents: HashMap<String, SharedDocument>,
+    // Open and closed polls, oldest first
+    pub polls: Vec<Poll>,
+    pub questions: QuestionQueue,
+}
+
+impl Room {
+    pub fn new(room_id: String, max_participants: usize) -> Self {
+        Self {
+            room_id,
+            tenant_id: None,
+            members: Vec::new(),
+            max_participants: max_participants.max(1),
+            password_hash: None,
+            ephemeral: true,
+            last_activity: Instant::now(),
+            host: None,
+            owner: None,
+            lobby_enabled: false,
+            waiting: Vec::new(),
+            locked: false,
+            starts_at: None,
+            ends_at: None,
+            invites: HashMap::new(),
+            listed: false,
+            config: RoomConfig::default(),
+            banned_ips: HashSet::new(),
+            banned_keys: HashSet::new(),
+            raised_hands: Vec::new(),
+            screen_sharers: Vec::new(),
+            key_epoch: 0,
+            key_management: false,
+            whiteboard: Whiteboard::default(),
+            documents: HashMap::new(),
+            polls: Vec::new(),
+            questions: QuestionQueue::default(),
+        }
+    }
+
+    pub fn contains(&self, addr: &SocketAddr) -> bool {
+        self.members.contains(addr)
+    }
+
+    pub fn add_member(&mut self, addr: SocketAddr) {
+        if !self.contains(&addr) {
+            self.members.push(addr);
+        }
+        self.touch();
+    }
+
+    pub fn remove_member(&mut self, addr: &SocketAddr) {
+        self.members.retain(|member| member != addr);
+        self.lower_hand(addr);
+        self.screen_sharers.retain(|sharer| sharer != addr);
+        self.touch();
+    }
+
+    // Locked rooms still accept their current members, which lets them rejoin idempotently
+    pub fn is_locked_for(&self, addr: &SocketAddr) -> bool {
+        self.locked && !self.contains(addr)
+    }
+
+    pub fn has_started(&self, now: i64) -> bool {
+        self.starts_at.is_none_or(|starts_at| now >= starts_at)
+    }
+
+    pub fn has_ended(&self, now: i64) -> bool {
+        self.ends_at.is_some_and(|ends_at| now >= ends_at)
+    }
+
+    pub fn ban_key(&mut self, public_key: &[u8]) {
+        self.banned_keys.insert(normalize_public_key(public_key));
+    }
+
+    pub fn is_banned(&self, ip: &IpAddr, public_key: Option<&[u8]>) -> bool {
+        self.banned_ips.contains(ip) || public_key.is_some_and(|key| self.banned_keys.contains(&normalize_public_key(key)))
+    }
+
+    // Rooms that require E2EE manage keys from the start; others once a member announces a key
+    pub fn manages_keys(&self) -> bool {
+        self.key_management || self.config.e2ee_required
+    }
+
+    pub fn is_host(&self, addr: &SocketAddr) -> bool {
+        self.host.as_ref() == Some(addr)
+    }
+
+    pub fn add_waiting(&mut self, join: WaitingJoin) {
+        if !self.waiting.iter().any(|waiting| waiting.addr == join.addr) {
+            self.waiting.push(join);
+        }
+    }
+
+    pub fn remove_waiting(&mut self, addr: &SocketAddr) -> Option<WaitingJoin> {
+        let index = self.waiting.iter().position(|waiting| waiting.addr == *addr)?;
+        Some(self.waiting.remove(index))
+    }
+
+    pub fn raise_hand(&mut self, addr: SocketAddr) -> bool {
+        if !self.contains(&addr) || self.raised_hands.contains(&addr) {
+            return false;
+        }
+        self.raised_hands.push(addr);
+        true
+    }
+
+    pub fn lower_hand(&mut self, addr: &SocketAddr) -> bool {
+        let before = self.raised_hands.len();
+        self.raised_hands.retain(|raised| raised != addr);
+        self.raised_hands.len() != before
+    }
+
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.members.len() >= self.max_participants
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
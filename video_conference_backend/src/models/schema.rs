@@ -0,0 +1,327 @@
+use crate::models::{Payload, SignalKind};
+use jsonschema::JSONSchema;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+static SCHEMAS: OnceLock<HashMap<SignalKind, JSONSchema>> = OnceLock::new();
+
+// At most this many violations are reported back for one payload
+const MAX_VIOLATIONS: usize = 10;
+
+// One place a payload does not match its schema; `path` is a JSON pointer into the payload
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    pub kind: SignalKind,
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl SchemaError {
+    pub fn code(&self) -> &'static str {
+        "invalid-payload"
+    }
+
+    pub fn message(&self) -> String {
+        match self.violations.first() {
+            Some(first) if first.path.is_empty() => format!("Invalid {} payload: {}", self.kind.name(), first.message),
+            Some(first) => format!("Invalid {} payload at {}: {}", self.kind.name(), first.path, first.message),
+            None => format!("Invalid {} payload", self.kind.name()),
+        }
+    }
+}
+
+// Checks `payload` against the schema for `kind`. Signal types without one are left to their
+// handlers.
+pub fn validate(kind: SignalKind, payload: &Payload) -> Result<(), SchemaError> {
+    let Some(schema) = schemas().get(&kind) else {
+        return Ok(());
+    };
+    let error = |violations| SchemaError { kind, violations };
+
+    let instance = match payload {
+        Payload::Json(instance) => instance,
+        Payload::Invalid(e) => {
+            return Err(error(vec![SchemaViolation { path: String::new(), message: format!("not valid JSON: {}", e) }]));
+        }
+        Payload::Opaque => return Ok(()),
+    };
+    schema.validate(instance).map_err(|errors| {
+        error(
+            errors
+                .take(MAX_VIOLATIONS)
+                .map(|e| SchemaViolation { path: e.instance_path.to_string(), message: e.to_string() })
+                .collect()
+        )
+    })
+}
+
+fn schemas() -> &'static HashMap<SignalKind, JSONSchema> {
+    SCHEMAS.get_or_init(|| {
+        definitions()
+            .into_iter()
+            .map(|(kind, schema)| {
+                let compiled = JSONSchema::compile(&schema)
+                    .unwrap_or_else(|e| panic!("invalid schema for {:?}: {}", kind, e));
+                (kind, compiled)
+            })
+            .collect()
+    })
+}
+
+// Binary fields travel as arrays of byte values
+fn bytes() -> Value {
+    json!({ "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } })
+}
+
+fn non_empty_bytes() -> Value {
+    json!({ "type": "array", "minItems": 1, "items": { "type": "integer", "minimum": 0, "maximum": 255 } })
+}
+
+// CRDT updates and states travel as standard base64
+fn non_empty_base64() -> Value {
+    json!({ "type": "string", "pattern": "^(?:[A-Za-z0-9+/]{4})*(?:[A-Za-z0-9+/]{2}==|[A-Za-z0-9+/]{3}=)?$", "minLength": 1 })
+}
+
+fn call() -> Value {
+    object(&["call_id"], json!({
+        "call_id": non_empty_string(),
+        "reason": optional(json!({ "type": "string" })),
+    }))
+}
+
+fn doc_name() -> Value {
+    json!({ "type": "string", "pattern": "^[A-Za-z0-9_.-]{1,64}$" })
+}
+
+fn algorithm() -> Value {
+    json!({ "enum": ["ecdsa-p256", "ed25519"] })
+}
+
+fn non_empty_string() -> Value {
+    json!({ "type": "string", "minLength": 1 })
+}
+
+fn optional(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+// An offer or answer: the session description plus the signature over it
+fn secure_connection(sdp_type: &str) -> Value {
+    object(&["offer", "signature", "nonce"], json!({
+        "offer": object(&["type", "sdp"], json!({
+            "type": { "const": sdp_type },
+            // Every session description opens with its version line
+            "sdp": { "type": "string", "pattern": "^v=0\r?\n" },
+        })),
+        "public_key": bytes(),
+        "signature": non_empty_bytes(),
+        "nonce": non_empty_bytes(),
+        "timestamp": optional(json!({ "type": "integer" })),
+        "algorithm": algorithm(),
+        "signature_version": { "type": "integer", "minimum": 1, "maximum": 3 },
+        "signed_data": optional(json!({ "type": "string" })),
+        "certificate_chain": optional(json!({ "type": "array", "minItems": 1, "items": non_empty_string() })),
+        "e2ee": { "type": "boolean" },
+    }))
+}
+
+// What a client's media stack supports; codec names are checked further by the handler
+fn capabilities() -> Value {
+    let codecs = json!({ "type": "array", "items": non_empty_string() });
+    object(&[], json!({
+        "audio_codecs": codecs,
+        "video_codecs": codecs,
+        "simulcast": { "type": "boolean" },
+        "e2ee": { "type": "boolean" },
+        "data_channels": { "type": "boolean" },
+    }))
+}
+
+// A renegotiation carries only the new description; the session token stands in for a signature
+fn renegotiation(sdp_type: &str) -> Value {
+    object(&["description"], json!({
+        "description": object(&["type", "sdp"], json!({
+            "type": { "const": sdp_type },
+            "sdp": { "type": "string", "pattern": "^v=0\r?\n" },
+        })),
+    }))
+}
+
+fn definitions() -> Vec<(SignalKind, Value)> {
+    vec![
+        (SignalKind::SecureOffer, secure_connection("offer")),
+        (SignalKind::SecureAnswer, secure_connection("answer")),
+        (SignalKind::RenegotiateOffer, renegotiation("offer")),
+        (SignalKind::RenegotiateAnswer, renegotiation("answer")),
+        (SignalKind::IceRestart, object(&["description"], json!({
+            "description": object(&["type", "sdp"], json!({
+                "type": { "const": "offer" },
+                "sdp": { "type": "string", "pattern": "^v=0\r?\n" },
+            })),
+            "reason": optional(json!({ "type": "string" })),
+        }))),
+        // An RTCIceCandidateInit; an empty candidate marks the end of candidates
+        (SignalKind::IceCandidate, object(&["candidate"], json!({
+            "candidate": { "type": "string", "pattern": "^(candidate:\\S+ \\d+ \\S+ \\d+ \\S+ \\d+ typ \\S+.*)?$" },
+            "sdpMid": optional(json!({ "type": "string" })),
+            "sdpMLineIndex": optional(json!({ "type": "integer", "minimum": 0, "maximum": 65535 })),
+            "usernameFragment": optional(json!({ "type": "string" })),
+            "signature": optional(non_empty_bytes()),
+            "nonce": optional(non_empty_bytes()),
+            "timestamp": optional(json!({ "type": "integer" })),
+            "algorithm": algorithm(),
+            "signed_data": optional(json!({ "type": "string" })),
+            "signature_version": { "enum": [2, 3] },
+        }))),
+        (SignalKind::Hello, object(&["versions"], json!({
+            "versions": { "type": "array", "minItems": 1, "items": { "type": "integer", "minimum": 1 } },
+            "features": { "type": "array", "items": { "type": "string" } },
+        }))),
+        (SignalKind::Authenticate, object(&["token"], json!({ "token": non_empty_string() }))),
+        (SignalKind::ChallengeResponse, object(&["public_key", "signature"], json!({
+            "public_key": non_empty_bytes(),
+            "signature": non_empty_bytes(),
+            "algorithm": algorithm(),
+        }))),
+        (SignalKind::RotateKey, object(&["new_public_key", "signature", "proof", "nonce"], json!({
+            "new_public_key": non_empty_bytes(),
+            "signature": non_empty_bytes(),
+            "proof": non_empty_bytes(),
+            "nonce": non_empty_bytes(),
+            "algorithm": algorithm(),
+        }))),
+        (SignalKind::ResumeSession, object(&["resume_token"], json!({ "resume_token": non_empty_string() }))),
+        (SignalKind::CreateRoom, object(&["room_id"], json!({
+            "room_id": non_empty_string(),
+            "password": optional(json!({ "type": "string" })),
+            "max_participants": optional(json!({ "type": "integer", "minimum": 1 })),
+            "lobby": { "type": "boolean" },
+            "listed": { "type": "boolean" },
+            "config": { "type": "object" },
+            "starts_at": optional(json!({ "type": "integer" })),
+            "ends_at": optional(json!({ "type": "integer" })),
+            "admin_token": optional(json!({ "type": "string" })),
+        }))),
+        (SignalKind::JoinRoom, object(&["room_id"], json!({
+            "room_id": non_empty_string(),
+            "max_participants": optional(json!({ "type": "integer", "minimum": 1 })),
+            "password": optional(json!({ "type": "string" })),
+            "invite_token": optional(json!({ "type": "string" })),
+            "capabilities": optional(capabilities()),
+        }))),
+        (SignalKind::Capabilities, capabilities()),
+        (SignalKind::Chat, object(&["text"], json!({ "text": non_empty_string() }))),
+        (SignalKind::Reaction, object(&["reaction"], json!({ "reaction": non_empty_string() }))),
+        (SignalKind::FileOffer, object(&["transfer_id", "name", "size", "mime_type", "sha256", "label"], json!({
+            "transfer_id": non_empty_string(),
+            "name": non_empty_string(),
+            "size": { "type": "integer", "minimum": 0 },
+            "mime_type": non_empty_string(),
+            "sha256": { "type": "string", "pattern": "^[0-9a-fA-F]{64}$" },
+            "label": non_empty_string(),
+        }))),
+        (SignalKind::FileAccept, object(&["transfer_id"], json!({ "transfer_id": non_empty_string() }))),
+        (SignalKind::FileReject, object(&["transfer_id"], json!({
+            "transfer_id": non_empty_string(),
+            "reason": optional(json!({ "type": "string" })),
+        }))),
+        (SignalKind::WhiteboardOp, object(&["op"], json!({ "op": { "type": "object" } }))),
+        (SignalKind::WhiteboardSnapshot, object(&["seq", "state"], json!({
+            "seq": { "type": "integer", "minimum": 1 },
+        }))),
+        (SignalKind::WhiteboardSync, object(&[], json!({
+            "since": optional(json!({ "type": "integer", "minimum": 0 })),
+        }))),
+        (SignalKind::DocUpdate, object(&["doc", "update"], json!({
+            "doc": doc_name(),
+            "update": non_empty_base64(),
+        }))),
+        (SignalKind::DocSnapshot, object(&["doc", "version", "state"], json!({
+            "doc": doc_name(),
+            "version": { "type": "integer", "minimum": 1 },
+            "state": non_empty_base64(),
+        }))),
+        (SignalKind::DocSync, object(&[], json!({ "doc": optional(doc_name()) }))),
+        (SignalKind::PollCreate, object(&["question", "options"], json!({
+            "question": non_empty_string(),
+            "options": { "type": "array", "minItems": 2, "items": non_empty_string() },
+            "anonymous": { "type": "boolean" },
+        }))),
+        (SignalKind::PollVote, object(&["poll_id", "option"], json!({
+            "poll_id": non_empty_string(),
+            "option": { "type": "integer", "minimum": 0 },
+        }))),
+        (SignalKind::PollClose, object(&["poll_id"], json!({ "poll_id": non_empty_string() }))),
+        (SignalKind::PollResults, object(&["poll_id"], json!({ "poll_id": non_empty_string() }))),
+        (SignalKind::QuestionAsk, object(&["text"], json!({ "text": non_empty_string() }))),
+        (SignalKind::QuestionUpvote, object(&["question_id"], json!({ "question_id": non_empty_string() }))),
+        (SignalKind::QuestionModerate, object(&["question_id", "action"], json!({
+            "question_id": non_empty_string(),
+            "action": { "enum": ["approve", "answer", "dismiss"] },
+        }))),
+        (SignalKind::CallInvite, object(&["call_id", "callee"], json!({
+            "call_id": non_empty_string(),
+            "callee": non_empty_string(),
+            "room_id": optional(non_empty_string()),
+        }))),
+        (SignalKind::CallRinging, call()),
+        (SignalKind::CallAccept, call()),
+        (SignalKind::CallReject, call()),
+        (SignalKind::CallCancel, call()),
+        (SignalKind::CallHangup, call()),
+        (SignalKind::CallHistory, object(&[], json!({
+            "limit": optional(json!({ "type": "integer", "minimum": 1 })),
+        }))),
+        (SignalKind::ChatHistory, object(&[], json!({
+            "before": optional(non_empty_string()),
+            "limit": optional(json!({ "type": "integer", "minimum": 1 })),
+        }))),
+        (SignalKind::JoinDecision, object(&["client_id", "approved"], json!({
+            "client_id": non_empty_string(),
+            "approved": { "type": "boolean" },
+        }))),
+        (SignalKind::KeyAnnounce, object(&["epoch", "sealed_key"], json!({
+            "epoch": { "type": "integer", "minimum": 0 },
+            "sealed_key": non_empty_string(),
+        }))),
+        (SignalKind::Report, object(&["client_id", "reason"], json!({
+            "client_id": non_empty_string(),
+            "reason": non_empty_string(),
+        }))),
+        (SignalKind::Presence, object(&["status"], json!({
+            "status": { "enum": ["active", "away", "speaking", "screen-sharing"] },
+        }))),
+        (SignalKind::Kick, object(&["client_id"], json!({
+            "client_id": non_empty_string(),
+            "ban": { "type": "boolean" },
+            "reason": optional(json!({ "type": "string" })),
+        }))),
+        (SignalKind::MuteRequest, object(&["client_id"], json!({
+            "client_id": non_empty_string(),
+            "muted": { "type": "boolean" },
+        }))),
+        (SignalKind::Promote, object(&["client_id"], json!({ "client_id": non_empty_string() }))),
+        (SignalKind::Demote, object(&["client_id"], json!({ "client_id": non_empty_string() }))),
+        (SignalKind::WebauthnRegister, object(&["client_data_json", "attestation_object"], json!({
+            "client_data_json": non_empty_string(),
+            "attestation_object": non_empty_string(),
+        }))),
+        (SignalKind::WebauthnLogin, object(&["credential_id", "client_data_json", "authenticator_data", "signature"], json!({
+            "credential_id": non_empty_string(),
+            "client_data_json": non_empty_string(),
+            "authenticator_data": non_empty_string(),
+            "signature": non_empty_string(),
+        }))),
+    ]
+}
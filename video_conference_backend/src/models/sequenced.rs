@@ -0,0 +1,96 @@
+use std::net::SocketAddr;
+
+// An entry in a sequenced log, or a snapshot of the log up to `seq`
+pub trait Sequenced {
+    fn seq(&self) -> u64;
+    // Bytes the entry holds, counted against the log's size limits
+    fn size(&self) -> usize;
+}
+
+// Room state that members change through numbered entries, like whiteboard operations or CRDT
+// updates: the latest snapshot a member handed in and the entries since. The server never
+// interprets either; it numbers entries in the room's order so members apply them alike and a
+// late joiner can catch up from the snapshot.
+#[derive(Debug, Clone)]
+pub struct SequencedLog<E, S> {
+    seq: u64,
+    snapshot: Option<S>,
+    entries: Vec<E>,
+    bytes: usize,
+    // The member asked for the next snapshot; nobody else's is taken
+    snapshot_from: Option<SocketAddr>,
+}
+
+impl<E, S> Default for SequencedLog<E, S> {
+    fn default() -> Self {
+        Self { seq: 0, snapshot: None, entries: Vec::new(), bytes: 0, snapshot_from: None }
+    }
+}
+
+impl<E: Sequenced, S: Sequenced> SequencedLog<E, S> {
+    // Sequence number of the last entry; 0 for a log nobody has written to
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    // Entries not yet folded into a snapshot
+    pub fn pending(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Bytes held by the snapshot and the entries since
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn snapshot(&self) -> Option<&S> {
+        self.snapshot.as_ref()
+    }
+
+    pub fn entries(&self) -> &[E] {
+        &self.entries
+    }
+
+    pub fn snapshot_from(&self) -> Option<SocketAddr> {
+        self.snapshot_from
+    }
+
+    pub fn request_snapshot(&mut self, addr: SocketAddr) {
+        self.snapshot_from = Some(addr);
+    }
+
+    // Appends the entry `entry` builds for the next sequence number
+    pub fn append(&mut self, entry: impl FnOnce(u64) -> E) -> &E {
+        self.seq += 1;
+        let entry = entry(self.seq);
+        self.bytes += entry.size();
+        self.entries.push(entry);
+        &self.entries[self.entries.len() - 1]
+    }
+
+    // Takes `snapshot` in place of the entries it covers; false when it is no newer than the
+    // current one or covers entries that have not happened
+    pub fn compact(&mut self, snapshot: S) -> bool {
+        let base = self.snapshot.as_ref().map_or(0, Sequenced::seq);
+        if snapshot.seq() <= base || snapshot.seq() > self.seq {
+            return false;
+        }
+        self.entries.retain(|entry| entry.seq() > snapshot.seq());
+        self.bytes = snapshot.size() + self.entries.iter().map(Sequenced::size).sum::<usize>();
+        self.snapshot = Some(snapshot);
+        self.snapshot_from = None;
+        true
+    }
+
+    // What a member that has applied everything up to `since` is missing: the entries after it,
+    // or the snapshot and everything after that once `since` predates the snapshot
+    pub fn since(&self, since: u64) -> (Option<&S>, &[E]) {
+        match &self.snapshot {
+            Some(snapshot) if since < snapshot.seq() => (Some(snapshot), &self.entries),
+            _ => {
+                let start = self.entries.partition_point(|entry| entry.seq() <= since);
+                (None, &self.entries[start..])
+            }
+        }
+    }
+}
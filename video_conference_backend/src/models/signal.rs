@@ -0,0 +1,245 @@
+use crate::models::message::*;
+use crate::models::{Capabilities, Profile, SignalMessage, WhiteboardSnapshot};
+use serde::de::value::{MapDeserializer, StrDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, Error as _, IgnoredAny, IntoDeserializer};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use strum::{EnumDiscriminants, IntoStaticStr};
+
+// A client signal as the server reads it: deserialized from the envelope's signal_type, which
+// picks the variant, and its payload, parsed into the type that variant calls for. The envelope
+// stays a `SignalMessage`, since relayed signals are forwarded (and verified) exactly as sent;
+// this is what the server acts on.
+//
+// `SignalKind` is derived from it: what a signal_type names, one variant per `Signal` variant, so
+// everything that looks at a signal before it is dispatched (schemas, rate limits, acks) keys off
+// one enum rather than strings of its own, and a new signal type is added in one place.
+#[derive(Debug, Deserialize, EnumDiscriminants)]
+#[serde(tag = "signal_type", content = "payload", rename_all = "kebab-case")]
+#[strum_discriminants(
+    name(SignalKind),
+    derive(Hash, Deserialize, IntoStaticStr),
+    serde(rename_all = "kebab-case"),
+    strum(serialize_all = "kebab-case")
+)]
+pub enum Signal {
+    #[serde(deserialize_with = "fields")]
+    Hello(HelloPayload),
+    #[serde(deserialize_with = "fields")]
+    Authenticate(AuthenticatePayload),
+    #[serde(deserialize_with = "fields")]
+    WebauthnRegisterBegin(WebauthnBeginPayload),
+    #[serde(deserialize_with = "fields")]
+    WebauthnRegister(WebauthnRegisterPayload),
+    #[serde(deserialize_with = "fields")]
+    WebauthnLoginBegin(WebauthnBeginPayload),
+    #[serde(deserialize_with = "fields")]
+    WebauthnLogin(WebauthnLoginPayload),
+    #[serde(deserialize_with = "fields")]
+    ChallengeResponse(ChallengeResponsePayload),
+    #[serde(deserialize_with = "fields")]
+    KeyAnnounce(KeyAnnouncePayload),
+    #[serde(deserialize_with = "fields")]
+    KeyRequest(KeyRequestPayload),
+    // Opaque to the server
+    #[serde(deserialize_with = "nothing")]
+    Sealed,
+    #[serde(deserialize_with = "fields")]
+    SecureOffer(SecureConnectionPayload),
+    #[serde(deserialize_with = "fields")]
+    SecureAnswer(SecureConnectionPayload),
+    #[serde(deserialize_with = "fields")]
+    IceCandidate(CandidateSignature),
+    #[serde(deserialize_with = "fields")]
+    RenegotiateOffer(RenegotiatePayload),
+    #[serde(deserialize_with = "fields")]
+    RenegotiateAnswer(RenegotiatePayload),
+    #[serde(deserialize_with = "fields")]
+    IceRestart(IceRestartPayload),
+    #[serde(deserialize_with = "fields")]
+    CreateRoom(CreateRoomPayload),
+    #[serde(deserialize_with = "fields")]
+    JoinRoom(JoinRoomPayload),
+    #[serde(deserialize_with = "fields")]
+    JoinDecision(JoinDecisionPayload),
+    #[serde(deserialize_with = "fields")]
+    LockRoom(LockRoomPayload),
+    #[serde(deserialize_with = "fields")]
+    CreateInvite(CreateInvitePayload),
+    #[serde(deserialize_with = "fields")]
+    RotateKey(RotateKeyPayload),
+    #[serde(deserialize_with = "fields")]
+    RevokeKeys(RevokeKeysPayload),
+    #[serde(deserialize_with = "fields")]
+    ReloadIpFilter(ReloadIpFilterPayload),
+    #[serde(deserialize_with = "nothing")]
+    TurnCredentials,
+    #[serde(deserialize_with = "fields")]
+    Report(ReportPayload),
+    #[serde(deserialize_with = "fields")]
+    Ban(BanPayload),
+    #[serde(deserialize_with = "fields")]
+    Unban(UnbanPayload),
+    #[serde(deserialize_with = "fields")]
+    ListBans(AdminListPayload),
+    #[serde(deserialize_with = "fields")]
+    ListReports(AdminListPayload),
+    #[serde(deserialize_with = "fields")]
+    RegionStats(AdminListPayload),
+    #[serde(deserialize_with = "fields")]
+    CompressionStats(AdminListPayload),
+    #[serde(deserialize_with = "fields")]
+    ListRooms(ListRoomsPayload),
+    #[serde(deserialize_with = "fields")]
+    ResumeSession(ResumeSessionPayload),
+    #[serde(deserialize_with = "fields")]
+    Presence(PresencePayload),
+    #[serde(deserialize_with = "fields")]
+    SetProfile(Profile),
+    #[serde(deserialize_with = "fields")]
+    Capabilities(Capabilities),
+    #[serde(deserialize_with = "fields")]
+    Chat(ChatPayload),
+    #[serde(deserialize_with = "fields")]
+    ChatHistory(ChatHistoryPayload),
+    #[serde(deserialize_with = "nothing")]
+    TypingStart,
+    #[serde(deserialize_with = "nothing")]
+    TypingStop,
+    #[serde(deserialize_with = "fields")]
+    Reaction(ReactionPayload),
+    #[serde(deserialize_with = "fields")]
+    FileOffer(FileOfferPayload),
+    #[serde(deserialize_with = "fields")]
+    FileAccept(FileResponsePayload),
+    #[serde(deserialize_with = "fields")]
+    FileReject(FileResponsePayload),
+    #[serde(deserialize_with = "fields")]
+    WhiteboardOp(WhiteboardOpPayload),
+    #[serde(deserialize_with = "fields")]
+    WhiteboardSnapshot(WhiteboardSnapshot),
+    #[serde(deserialize_with = "fields")]
+    WhiteboardSync(WhiteboardSyncPayload),
+    #[serde(deserialize_with = "fields")]
+    DocUpdate(DocUpdatePayload),
+    #[serde(deserialize_with = "fields")]
+    DocSnapshot(DocSnapshotPayload),
+    #[serde(deserialize_with = "fields")]
+    DocSync(DocSyncPayload),
+    #[serde(deserialize_with = "fields")]
+    PollCreate(PollCreatePayload),
+    #[serde(deserialize_with = "fields")]
+    PollVote(PollVotePayload),
+    #[serde(deserialize_with = "fields")]
+    PollClose(PollPayload),
+    #[serde(deserialize_with = "fields")]
+    PollResults(PollPayload),
+    #[serde(deserialize_with = "fields")]
+    QuestionAsk(QuestionAskPayload),
+    #[serde(deserialize_with = "fields")]
+    QuestionUpvote(QuestionUpvotePayload),
+    #[serde(deserialize_with = "fields")]
+    QuestionModerate(QuestionModeratePayload),
+    #[serde(deserialize_with = "nothing")]
+    QuestionSync,
+    #[serde(deserialize_with = "fields")]
+    CallInvite(CallInvitePayload),
+    #[serde(deserialize_with = "fields")]
+    CallRinging(CallPayload),
+    #[serde(deserialize_with = "fields")]
+    CallAccept(CallPayload),
+    #[serde(deserialize_with = "fields")]
+    CallReject(CallPayload),
+    #[serde(deserialize_with = "fields")]
+    CallCancel(CallPayload),
+    #[serde(deserialize_with = "fields")]
+    CallHangup(CallPayload),
+    #[serde(deserialize_with = "fields")]
+    CallHistory(CallHistoryPayload),
+    #[serde(deserialize_with = "nothing")]
+    LeaveRoom,
+    #[serde(deserialize_with = "fields")]
+    Kick(KickPayload),
+    #[serde(deserialize_with = "fields")]
+    MuteRequest(MuteRequestPayload),
+    #[serde(deserialize_with = "fields")]
+    MuteAll(MuteAllPayload),
+    #[serde(deserialize_with = "fields")]
+    Promote(RoleChangePayload),
+    #[serde(deserialize_with = "fields")]
+    Demote(RoleChangePayload),
+    #[serde(deserialize_with = "nothing")]
+    RaiseHand,
+    #[serde(deserialize_with = "fields")]
+    LowerHand(LowerHandPayload),
+    #[serde(deserialize_with = "nothing")]
+    ScreenshareStart,
+    #[serde(deserialize_with = "nothing")]
+    ScreenshareStop,
+    #[serde(other, deserialize_with = "nothing")]
+    #[strum_discriminants(serde(other))]
+    Unknown,
+}
+
+impl Signal {
+    pub fn from_message(signal: &SignalMessage) -> Result<Self, serde::de::value::Error> {
+        let fields = [("signal_type", signal.signal_type.as_str()), ("payload", signal.payload.as_str())];
+        Self::deserialize(MapDeserializer::new(fields.into_iter()))
+    }
+}
+
+// Payloads travel as JSON text. Those that are empty or not a JSON object parse as if they were
+// `{}`, which is enough for signals that take nothing and fails with the missing field for the rest.
+fn fields<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D) -> Result<T, D::Error> {
+    let payload = String::deserialize(deserializer)?;
+    let fields = match serde_json::from_str(&payload) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    T::deserialize(Value::Object(fields)).map_err(D::Error::custom)
+}
+
+// For signals that take nothing, and sealed payloads, which are opaque to the server and never read
+fn nothing<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(), D::Error> {
+    IgnoredAny::deserialize(deserializer).map(|_| ())
+}
+
+impl SignalKind {
+    pub fn of(signal_type: &str) -> Self {
+        let deserializer: StrDeserializer<serde::de::value::Error> = signal_type.into_deserializer();
+        Self::deserialize(deserializer).unwrap_or(SignalKind::Unknown)
+    }
+
+    // The signal_type that names it
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+}
+
+// A signal's payload, read once for the checks that look at it before the signal is dispatched
+#[derive(Debug)]
+pub enum Payload {
+    // Sealed payloads, which are relayed without being read
+    Opaque,
+    Json(Value),
+    Invalid(serde_json::Error),
+}
+
+impl Payload {
+    pub fn read(kind: SignalKind, signal: &SignalMessage) -> Self {
+        if kind == SignalKind::Sealed {
+            return Payload::Opaque;
+        }
+        match serde_json::from_str(&signal.payload) {
+            Ok(value) => Payload::Json(value),
+            Err(e) => Payload::Invalid(e),
+        }
+    }
+
+    pub fn json(&self) -> Option<&Value> {
+        match self {
+            Payload::Json(value) => Some(value),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::models::sequenced::{Sequenced, SequencedLog};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// A drawing operation as the room sequenced it. The server does not interpret `op`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WhiteboardOp {
+    pub seq: u64,
+    pub client_id: String,
+    pub op: Value,
+}
+
+// The board as a client rendered it after applying every operation up to `seq`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WhiteboardSnapshot {
+    pub seq: u64,
+    pub state: Value,
+}
+
+// A room's board: the latest snapshot and the operations since, enough for a late joiner to
+// redraw it
+pub type Whiteboard = SequencedLog<WhiteboardOp, WhiteboardSnapshot>;
+
+impl Sequenced for WhiteboardOp {
+    fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    fn size(&self) -> usize {
+        self.op.to_string().len()
+    }
+}
+
+impl Sequenced for WhiteboardSnapshot {
+    fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    fn size(&self) -> usize {
+        self.state.to_string().len()
+    }
+}
@@ -0,0 +1,173 @@
+use crate::crypto::write_private_key;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use snow::{Builder, TransportState};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::WebSocketStream;
+
+pub type NoiseResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Native clients opt into Noise by requesting this WebSocket subprotocol
+pub const SUBPROTOCOL: &str = "peer-conference.noise";
+
+const PATTERN: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+// Noise messages are capped at 64 KiB including the tag, so longer frames are sent as a run of
+// full-size messages that the receiver cuts apart again at the same boundaries
+const MAX_MESSAGE_LEN: usize = 65535;
+const TAG_LEN: usize = 16;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The server's static X25519 key, plus the client keys allowed to connect (any, when unset)
+pub struct NoiseConfig {
+    private_key: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub allowed_clients: Option<HashSet<Vec<u8>>>,
+}
+
+impl NoiseConfig {
+    // The key file holds the base64 private and public keys on separate lines; it is generated
+    // on first start so clients can pin the server's static key
+    pub fn load_or_generate(path: &Path) -> NoiseResult<Self> {
+        if path.exists() {
+            return Self::parse(&std::fs::read_to_string(path)?)
+                .map_err(|e| format!("{}: {}", path.display(), e).into());
+        }
+
+        let keypair = Builder::new(PATTERN.parse()?).generate_keypair()?;
+        write_private_key(
+            path,
+            format!("{}\n{}\n", STANDARD.encode(&keypair.private), STANDARD.encode(&keypair.public)).as_bytes()
+        )?;
+        println!("Generated Noise static key at {}", path.display());
+        Ok(Self {
+            private_key: keypair.private,
+            public_key: keypair.public,
+            allowed_clients: None,
+        })
+    }
+
+    // The key file's format, also used for keys that come from a key provider
+    pub fn parse(contents: &str) -> NoiseResult<Self> {
+        let mut lines = contents.lines().map(str::trim).filter(|line| !line.is_empty());
+        let (Some(private_key), Some(public_key)) = (lines.next(), lines.next()) else {
+            return Err("does not hold a Noise key pair".into());
+        };
+        Ok(Self {
+            private_key: STANDARD.decode(private_key)?,
+            public_key: STANDARD.decode(public_key)?,
+            allowed_clients: None,
+        })
+    }
+}
+
+// Transport keys for one connection, shared by the reader and the forwarding task
+pub struct NoiseSession {
+    transport: Mutex<TransportState>,
+    // The client's static key, proven during the handshake
+    pub remote_static: Vec<u8>,
+}
+
+impl NoiseSession {
+    pub fn encrypt(&self, plaintext: &[u8]) -> NoiseResult<Vec<u8>> {
+        let mut transport = self.transport.lock().map_err(|_| "Noise session lock poisoned")?;
+        let mut buffer = vec![0u8; MAX_MESSAGE_LEN];
+        let mut frame = Vec::with_capacity(plaintext.len() + TAG_LEN);
+        for chunk in plaintext.chunks(MAX_MESSAGE_LEN - TAG_LEN) {
+            let len = transport.write_message(chunk, &mut buffer)?;
+            frame.extend_from_slice(&buffer[..len]);
+        }
+        Ok(frame)
+    }
+
+    pub fn decrypt(&self, frame: &[u8]) -> NoiseResult<Vec<u8>> {
+        let mut transport = self.transport.lock().map_err(|_| "Noise session lock poisoned")?;
+        let mut buffer = vec![0u8; MAX_MESSAGE_LEN];
+        let mut plaintext = Vec::with_capacity(frame.len());
+        for chunk in frame.chunks(MAX_MESSAGE_LEN) {
+            let len = transport.read_message(chunk, &mut buffer)?;
+            plaintext.extend_from_slice(&buffer[..len]);
+        }
+        Ok(plaintext)
+    }
+
+    // Outgoing text and binary frames go out encrypted; control frames are left alone
+    pub fn seal(&self, message: Message) -> NoiseResult<Message> {
+        match message {
+            Message::Text(text) => Ok(Message::Binary(self.encrypt(text.as_bytes())?)),
+            Message::Binary(data) => Ok(Message::Binary(self.encrypt(&data)?)),
+            message => Ok(message),
+        }
+    }
+
+    // Signals only arrive as encrypted binary frames; anything sent in plaintext is ignored
+    pub fn open(&self, message: Message) -> NoiseResult<Option<String>> {
+        match message {
+            Message::Binary(frame) => Ok(Some(String::from_utf8(self.decrypt(&frame)?)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+// Runs the responder side of Noise XX over binary frames, right after the WebSocket upgrade:
+//   -> e
+//   <- e, ee, s, es
+//   -> s, se
+pub async fn respond<S>(ws: &mut WebSocketStream<S>, config: &NoiseConfig) -> NoiseResult<NoiseSession>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake(ws, config))
+        .await
+        .map_err(|_| "Noise handshake timed out")?
+}
+
+async fn handshake<S>(ws: &mut WebSocketStream<S>, config: &NoiseConfig) -> NoiseResult<NoiseSession>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut noise = Builder::new(PATTERN.parse()?)
+        .local_private_key(&config.private_key)
+        .build_responder()?;
+    let mut buffer = vec![0u8; MAX_MESSAGE_LEN];
+
+    noise.read_message(&next_binary(ws).await?, &mut buffer)?;
+    let len = noise.write_message(&[], &mut buffer)?;
+    ws.send(Message::Binary(buffer[..len].to_vec())).await?;
+    noise.read_message(&next_binary(ws).await?, &mut buffer)?;
+
+    let remote_static = noise
+        .get_remote_static()
+        .ok_or("Noise handshake finished without a client static key")?
+        .to_vec();
+    if let Some(allowed_clients) = &config.allowed_clients {
+        if !allowed_clients.contains(&remote_static) {
+            return Err(format!("Noise client key {} is not allowed", STANDARD.encode(&remote_static)).into());
+        }
+    }
+
+    Ok(NoiseSession {
+        transport: Mutex::new(noise.into_transport_mode()?),
+        remote_static,
+    })
+}
+
+async fn next_binary<S>(ws: &mut WebSocketStream<S>) -> NoiseResult<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Binary(data))) => return Ok(data),
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+            Some(Ok(_)) => return Err("expected a binary Noise handshake message".into()),
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err("connection closed during the Noise handshake".into()),
+        }
+    }
+}
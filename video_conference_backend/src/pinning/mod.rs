@@ -0,0 +1,90 @@
+pub mod sqlite;
+
+use crate::crypto::normalize_public_key;
+use crate::models::Client;
+use crate::storage::StoreResult;
+use sha2::{Digest, Sha256};
+
+pub use sqlite::SqlitePinStore;
+
+// Remembers the first public key each identity presented
+pub trait KeyPinStore: Send + Sync {
+    fn pinned_key(&self, identity: &str) -> StoreResult<Option<Vec<u8>>>;
+    fn pin(&self, identity: &str, public_key: &[u8]) -> StoreResult<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinError {
+    Mismatch,
+    Unavailable,
+}
+
+impl PinError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PinError::Mismatch => "key-pin-mismatch",
+            PinError::Unavailable => "key-pin-unavailable",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            PinError::Mismatch => "This identity is pinned to a different public key; use rotate-key to change it",
+            PinError::Unavailable => "The key pin store could not be consulted",
+        }
+    }
+}
+
+// The most stable name the client has: its token subject, else its certificate subject, else
+// its WebAuthn user, else the fingerprint of its Noise static key or its signing key, which survive
+// a reconnect where the client_id does not. Only a client with none of these goes by its client_id.
+// Scoped by tenant since tenants name their users independently.
+pub fn identity(client: &Client) -> String {
+    let name = match (&client.claims, &client.certificate, &client.webauthn_user) {
+        (Some(claims), _, _) => format!("user:{}", claims.sub),
+        (None, Some(certificate), _) => format!("cert:{}", certificate.subject),
+        (None, None, Some(user_id)) => format!("webauthn:{}", user_id),
+        (None, None, None) => match (&client.noise_key, &client.public_key) {
+            (Some(noise_key), _) => format!("noise:{}", fingerprint(noise_key)),
+            (None, Some(public_key)) => format!("key:{}", fingerprint(&normalize_public_key(public_key))),
+            (None, None) => format!("client:{}", client.client_id),
+        },
+    };
+    match client.tenant_id() {
+        Some(tenant_id) => format!("{}/{}", tenant_id, name),
+        None => name,
+    }
+}
+
+fn fingerprint(key: &[u8]) -> String {
+    Sha256::digest(key).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Pins `public_key` to `identity` on first use and rejects any other key afterwards. Keys are
+// pinned and compared as their uncompressed point, so one key matches in either encoding.
+pub fn check_pin(store: &dyn KeyPinStore, identity: &str, public_key: &[u8]) -> Result<(), PinError> {
+    let pinned = store.pinned_key(identity).map_err(|e| {
+        eprintln!("Key pin lookup failed for {}: {}", identity, e);
+        PinError::Unavailable
+    })?;
+
+    let public_key = normalize_public_key(public_key);
+    match pinned {
+        Some(pinned) if normalize_public_key(&pinned) == public_key => Ok(()),
+        Some(_) => Err(PinError::Mismatch),
+        None => store.pin(identity, &public_key).map_err(|e| {
+            eprintln!("Failed to pin key for {}: {}", identity, e);
+            PinError::Unavailable
+        }),
+    }
+}
+
+// Moves the pin from `old_key` to `new_key`; the identity must currently be pinned to `old_key`
+// (or to nothing)
+pub fn rotate_pin(store: &dyn KeyPinStore, identity: &str, old_key: &[u8], new_key: &[u8]) -> Result<(), PinError> {
+    check_pin(store, identity, old_key)?;
+    store.pin(identity, &normalize_public_key(new_key)).map_err(|e| {
+        eprintln!("Failed to re-pin key for {}: {}", identity, e);
+        PinError::Unavailable
+    })
+}
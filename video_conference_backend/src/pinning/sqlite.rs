@@ -0,0 +1,116 @@
+use crate::pinning::KeyPinStore;
+use crate::storage::encryption::{self, StorageCipher};
+use crate::storage::sqlite::add_column_if_missing;
+use crate::storage::StoreResult;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub struct SqlitePinStore {
+    conn: Mutex<Connection>,
+    // Seals pinned keys when set. Identities are then stored as their blind index, which pins
+    // are looked up by, with the identity itself sealed alongside for `rekey`.
+    cipher: Option<Arc<StorageCipher>>,
+}
+
+impl SqlitePinStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> StoreResult<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS key_pins (
+                identity TEXT PRIMARY KEY,
+                public_key BLOB NOT NULL,
+                pinned_at INTEGER NOT NULL
+            )",
+        )?;
+        add_column_if_missing(&conn, "key_pins", "sealed_identity", "TEXT")?;
+
+        Ok(Self { conn: Mutex::new(conn), cipher: None })
+    }
+
+    pub fn with_cipher(mut self, cipher: Arc<StorageCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    // Re-seals pins that are still plaintext or sealed with a retired key, moving them to the
+    // current key's blind index; returns how many
+    pub fn rekey(&self) -> StoreResult<usize> {
+        let Some(cipher) = self.cipher.as_deref() else {
+            return Ok(0);
+        };
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let stale = {
+            let mut statement = conn.prepare("SELECT identity, sealed_identity, public_key, pinned_at FROM key_pins")?;
+            let rows = statement.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Vec<u8>>(2)?, row.get::<_, i64>(3)?))
+            })?;
+            rows.filter_map(Result::ok)
+                .filter(|(_, sealed_identity, public_key, _)| {
+                    sealed_identity.as_deref().is_none_or(|sealed| cipher.needs_rekey(sealed))
+                        || cipher.needs_rekey(&String::from_utf8_lossy(public_key))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (stored, sealed_identity, public_key, pinned_at) in &stale {
+            // Rows written without a cipher hold the identity itself
+            let identity = match sealed_identity {
+                Some(sealed) => cipher.open_stale(sealed, &encryption::field("key_pins", "identity", stored))?,
+                None => stored.clone(),
+            };
+            let public_key = encryption::open_stale_bytes(cipher, public_key.clone(), &encryption::field("key_pins", "public_key", stored))?;
+            conn.execute("DELETE FROM key_pins WHERE identity = ?1", params![stored])?;
+            insert_pin(&conn, Some(cipher), &identity, &public_key, *pinned_at)?;
+        }
+        Ok(stale.len())
+    }
+}
+
+fn insert_pin(conn: &Connection, cipher: Option<&StorageCipher>, identity: &str, public_key: &[u8], pinned_at: i64) -> StoreResult<()> {
+    let stored = encryption::lookup_value(cipher, identity);
+    let sealed_identity = cipher.map(|cipher| cipher.seal(identity, &encryption::field("key_pins", "identity", &stored))).transpose()?;
+    let public_key = encryption::seal_bytes(cipher, public_key, &encryption::field("key_pins", "public_key", &stored))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO key_pins (identity, sealed_identity, public_key, pinned_at) VALUES (?1, ?2, ?3, ?4)",
+        params![stored, sealed_identity, public_key, pinned_at],
+    )?;
+    Ok(())
+}
+
+impl KeyPinStore for SqlitePinStore {
+    fn pinned_key(&self, identity: &str) -> StoreResult<Option<Vec<u8>>> {
+        let cipher = self.cipher.as_deref();
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        for stored in encryption::lookup_values(cipher, identity) {
+            let key = conn
+                .query_row(
+                    "SELECT public_key FROM key_pins WHERE identity = ?1",
+                    params![stored],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(key) = key {
+                return encryption::open_bytes(cipher, key, &encryption::field("key_pins", "public_key", &stored)).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn pin(&self, identity: &str, public_key: &[u8]) -> StoreResult<()> {
+        let cipher = self.cipher.as_deref();
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        // A pin still under a retired key's index would otherwise come back at the next rekey
+        for stored in encryption::lookup_values(cipher, identity) {
+            conn.execute("DELETE FROM key_pins WHERE identity = ?1", params![stored])?;
+        }
+        insert_pin(&conn, cipher, identity, public_key, Utc::now().timestamp())
+    }
+}
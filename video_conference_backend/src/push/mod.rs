@@ -0,0 +1,39 @@
+pub mod webhook;
+
+use serde::Serialize;
+
+pub use webhook::WebhookNotifier;
+
+pub type PushResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PushEvent {
+    // The callee should be woken to take the call
+    Incoming,
+    // The call was answered, declined, cancelled or rang out; any notification should be withdrawn
+    Withdrawn,
+}
+
+// What a sleeping app needs to wake, connect and take the call: once it signs in as `callee`,
+// the call rings on its connection with everything else it needs
+#[derive(Debug, Clone, Serialize)]
+pub struct CallPush {
+    pub event: PushEvent,
+    pub call_id: String,
+    pub tenant_id: Option<String>,
+    pub callee: String,
+    // User id of the caller; only signed-in callers can ring anyone
+    pub caller: String,
+    pub caller_id: String,
+    pub room_id: Option<String>,
+    // Unix milliseconds after which the call no longer rings
+    pub expires_at: i64,
+}
+
+// Reaches users with nothing connected, through FCM, APNs, web push or a gateway in front of
+// them. Implementations know which devices each user has and must not block: the call is
+// ringing while they run, so delivery belongs on a background task.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, push: &CallPush) -> PushResult<()>;
+}
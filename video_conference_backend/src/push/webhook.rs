@@ -0,0 +1,45 @@
+use crate::config;
+use crate::push::{CallPush, Notifier, PushResult};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+// Posts each push as JSON to a gateway that fans it out to the user's devices. Delivery happens
+// on a background task like the HTTP audit sink's; pushes the gateway refuses are logged and
+// dropped, and so are pushes that find the queue full behind a slow gateway.
+pub struct WebhookNotifier {
+    queue: mpsc::Sender<CallPush>,
+}
+
+impl WebhookNotifier {
+    // Needs a running tokio runtime for the delivery task
+    pub fn new(url: String) -> PushResult<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(config::get_push_connect_timeout())
+            .timeout(config::get_push_timeout())
+            .build()?;
+        let (queue, mut pending) = mpsc::channel::<CallPush>(config::get_push_queue_size().max(1));
+        tokio::spawn(async move {
+            while let Some(push) = pending.recv().await {
+                let result = client
+                    .post(&url)
+                    .json(&push)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = result {
+                    eprintln!("[ERROR] Failed to push call {} to {}: {}", push.call_id, url, e);
+                }
+            }
+        });
+        Ok(Self { queue })
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, push: &CallPush) -> PushResult<()> {
+        match self.queue.try_send(push.clone()) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("push queue is full".into()),
+            Err(TrySendError::Closed(_)) => Err("push delivery task has stopped".into()),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::config;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LockoutPolicy {
+    // Failures within `window` that trip a lockout
+    pub max_failures: usize,
+    pub window: Duration,
+    // The first lockout lasts `base`; each one after doubles, up to `max`
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn from_config() -> Self {
+        Self {
+            max_failures: config::get_lockout_max_failures(),
+            window: config::get_lockout_window(),
+            base: config::get_lockout_base(),
+            max: config::get_lockout_max(),
+        }
+    }
+
+    // Zero failures turns lockouts off
+    pub fn is_disabled(&self) -> bool {
+        self.max_failures == 0
+    }
+
+    fn duration(&self, lockouts: u32) -> Duration {
+        self.base
+            .checked_mul(2u32.saturating_pow(lockouts.saturating_sub(1)))
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+}
+
+// Recent verification failures of one client or address, and how often it has been locked out
+#[derive(Debug, Clone, Default)]
+pub struct FailureTracker {
+    failures: VecDeque<Instant>,
+    lockouts: u32,
+    locked_until: Option<Instant>,
+}
+
+impl FailureTracker {
+    // Counts a failure; returns the lockout it trips, if any
+    pub fn record(&mut self, now: Instant, policy: &LockoutPolicy) -> Option<Duration> {
+        if policy.is_disabled() {
+            return None;
+        }
+        // A clean stretch as long as the longest lockout forgives earlier ones
+        if self.locked_until.is_some_and(|until| now.saturating_duration_since(until) > policy.max) {
+            self.lockouts = 0;
+            self.locked_until = None;
+        }
+
+        while self.failures.front().is_some_and(|failure| now.saturating_duration_since(*failure) > policy.window) {
+            self.failures.pop_front();
+        }
+        self.failures.push_back(now);
+        if self.failures.len() < policy.max_failures {
+            return None;
+        }
+
+        self.failures.clear();
+        self.lockouts += 1;
+        let duration = policy.duration(self.lockouts);
+        self.locked_until = Some(now + duration);
+        Some(duration)
+    }
+
+    pub fn locked_for(&self, now: Instant) -> Option<Duration> {
+        self.locked_until
+            .filter(|until| *until > now)
+            .map(|until| until.saturating_duration_since(now))
+    }
+
+    // Nothing left worth remembering
+    fn is_idle(&self, now: Instant, policy: &LockoutPolicy) -> bool {
+        self.failures.back().is_none_or(|failure| now.saturating_duration_since(*failure) > policy.window)
+            && self.locked_until.is_none_or(|until| now.saturating_duration_since(until) > policy.max)
+    }
+}
+
+// Per-address failure counts. Like address rate limit buckets they outlive the connections, so a
+// locked-out address can't just reconnect; the accept loop checks it without the state lock.
+#[derive(Debug, Default)]
+pub struct LockoutTable {
+    addresses: Mutex<HashMap<IpAddr, FailureTracker>>,
+}
+
+impl LockoutTable {
+    pub fn record(&self, ip: IpAddr, now: Instant, policy: &LockoutPolicy) -> Option<Duration> {
+        let mut addresses = self.addresses.lock().ok()?;
+        addresses.retain(|_, tracker| !tracker.is_idle(now, policy));
+        addresses.entry(ip).or_default().record(now, policy)
+    }
+
+    pub fn locked_for(&self, ip: &IpAddr, now: Instant) -> Option<Duration> {
+        self.addresses.lock().ok()?.get(ip).and_then(|tracker| tracker.locked_for(now))
+    }
+}
@@ -0,0 +1,228 @@
+pub mod lockout;
+
+use crate::config;
+use crate::models::SignalKind;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub use lockout::{FailureTracker, LockoutPolicy, LockoutTable};
+
+// Budgets for signal types whose legitimate rates differ a lot from the rest: candidates arrive in
+// bursts while ICE gathers, offers and answers only when a call is set up or renegotiated. Each is
+// a share of the connection's overall budget, which every signal also draws on.
+const DEFAULT_SIGNAL_LIMITS: &[(SignalKind, f64, f64)] = &[
+    (SignalKind::IceCandidate, 15.0, 40.0),
+    (SignalKind::SecureOffer, 1.0, 5.0),
+    (SignalKind::SecureAnswer, 1.0, 5.0),
+    (SignalKind::Chat, 2.0, 10.0),
+    (SignalKind::ChatHistory, 1.0, 5.0),
+    (SignalKind::TypingStart, 2.0, 5.0),
+    (SignalKind::TypingStop, 2.0, 5.0),
+    (SignalKind::Reaction, 3.0, 10.0),
+    (SignalKind::FileOffer, 1.0, 5.0),
+    (SignalKind::WhiteboardOp, 15.0, 40.0),
+    (SignalKind::DocUpdate, 15.0, 40.0),
+    (SignalKind::PollCreate, 0.2, 3.0),
+    (SignalKind::PollVote, 2.0, 5.0),
+    (SignalKind::QuestionAsk, 0.2, 3.0),
+    (SignalKind::QuestionUpvote, 2.0, 10.0),
+    (SignalKind::QuestionSync, 1.0, 5.0),
+    (SignalKind::CallInvite, 0.5, 5.0),
+    (SignalKind::CallHistory, 1.0, 5.0),
+];
+
+// Refills continuously at `rate` tokens a second up to `burst`; each signal takes one token
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    // Starts full so a client can get through its connection setup in one burst
+    pub fn new(rate: f64, burst: f64, now: Instant) -> Self {
+        Self { rate, burst, tokens: burst, updated: now }
+    }
+
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Whether it would be back at its full burst by `now`
+    pub fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * self.rate >= self.burst
+    }
+
+    // Time until the next token is available
+    pub fn retry_after(&self) -> Duration {
+        Duration::from_secs_f64(((1.0 - self.tokens) / self.rate).max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+impl RateLimit {
+    pub fn per_client() -> Self {
+        Self {
+            rate: config::get_rate_limit_per_sec(),
+            burst: config::get_rate_limit_burst(),
+        }
+    }
+
+    pub fn per_ip() -> Self {
+        Self {
+            rate: config::get_ip_rate_limit_per_sec(),
+            burst: config::get_ip_rate_limit_burst(),
+        }
+    }
+
+    // Defaults from DEFAULT_SIGNAL_LIMITS, with SIGNAL_RATE_LIMITS taking precedence
+    pub fn per_signal_type() -> HashMap<SignalKind, RateLimit> {
+        let mut limits: HashMap<SignalKind, RateLimit> = DEFAULT_SIGNAL_LIMITS
+            .iter()
+            .map(|(kind, rate, burst)| (*kind, RateLimit { rate: *rate, burst: *burst }))
+            .collect();
+        limits.extend(config::get_signal_rate_limits());
+        limits
+    }
+
+    // A rate of zero turns the limit off
+    pub fn is_unlimited(&self) -> bool {
+        self.rate <= 0.0
+    }
+
+    // This limit cut down to fit inside `outer`; an unlimited limit takes `outer` whole
+    pub fn within(self, outer: RateLimit) -> RateLimit {
+        if outer.is_unlimited() {
+            self
+        } else if self.is_unlimited() {
+            outer
+        } else {
+            RateLimit { rate: self.rate.min(outer.rate), burst: self.burst.min(outer.burst) }
+        }
+    }
+}
+
+// `rate/burst`, e.g. `25/100`
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (rate, burst) = value
+            .split_once('/')
+            .ok_or_else(|| format!("expected rate/burst, got {}", value))?;
+        Ok(Self {
+            rate: rate.trim().parse().map_err(|_| format!("invalid rate in {}", value))?,
+            burst: burst.trim().parse().map_err(|_| format!("invalid burst in {}", value))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    // Signals refused so far in the current abuse window
+    pub count: u32,
+    pub retry_after: Duration,
+    // Whether the signal's own type budget ran out rather than the overall one
+    pub per_signal_type: bool,
+}
+
+// A token bucket that also counts how often it has been exceeded, so sustained abuse can be
+// told apart from an occasional burst
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    bucket: TokenBucket,
+    // Signal types with a budget of their own, drawn on before the overall bucket
+    signal_limits: HashMap<SignalKind, RateLimit>,
+    signal_buckets: HashMap<SignalKind, TokenBucket>,
+    violations: u32,
+    window_start: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            limit,
+            bucket: TokenBucket::new(limit.rate, limit.burst, now),
+            signal_limits: HashMap::new(),
+            signal_buckets: HashMap::new(),
+            violations: 0,
+            window_start: now,
+        }
+    }
+
+    // Type budgets above the overall one could never bind, so they are capped at it
+    pub fn with_signal_limits(mut self, signal_limits: HashMap<SignalKind, RateLimit>) -> Self {
+        self.signal_limits = signal_limits
+            .into_iter()
+            .map(|(kind, limit)| (kind, limit.within(self.limit)))
+            .collect();
+        self
+    }
+
+    // A signal has to fit both its type's budget and the overall one. Signals refused by their
+    // type budget don't touch the overall bucket, so a flood of one type can't starve the others.
+    // Violations are forgotten once `window` passes since the first one.
+    pub fn check(&mut self, kind: SignalKind, now: Instant, window: Duration) -> Result<(), Violation> {
+        if let Some(limit) = self.signal_limits.get(&kind).filter(|limit| !limit.is_unlimited()) {
+            let bucket = self.signal_buckets
+                .entry(kind)
+                .or_insert_with(|| TokenBucket::new(limit.rate, limit.burst, now));
+            if !bucket.try_take(now) {
+                let retry_after = bucket.retry_after();
+                return Err(self.violation(now, window, retry_after, true));
+            }
+        }
+
+        if self.limit.is_unlimited() || self.bucket.try_take(now) {
+            return Ok(());
+        }
+        let retry_after = self.bucket.retry_after();
+        Err(self.violation(now, window, retry_after, false))
+    }
+
+    // Every bucket has refilled and no violation is still counted, so a fresh limiter would
+    // behave the same and this one can be forgotten
+    pub fn is_idle(&self, now: Instant, window: Duration) -> bool {
+        self.bucket.is_full(now)
+            && self.signal_buckets.values().all(|bucket| bucket.is_full(now))
+            && (self.violations == 0 || now.saturating_duration_since(self.window_start) > window)
+    }
+
+    fn violation(
+        &mut self,
+        now: Instant,
+        window: Duration,
+        retry_after: Duration,
+        per_signal_type: bool
+    ) -> Violation {
+        if self.violations == 0 || now.saturating_duration_since(self.window_start) > window {
+            self.violations = 0;
+            self.window_start = now;
+        }
+        self.violations += 1;
+        Violation {
+            count: self.violations,
+            retry_after,
+            per_signal_type,
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::models::RoomConfig;
+use crate::signaling::state::SignalingState;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomSummary {
+    pub room_id: String,
+    pub participants: usize,
+    pub max_participants: usize,
+    pub password_protected: bool,
+    pub lobby: bool,
+    pub locked: bool,
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub config: RoomConfig,
+}
+
+// Unlisted rooms and rooms of other tenants are only visible to admins
+pub fn list_rooms(state: &SignalingState, include_unlisted: bool, tenant_id: Option<&str>) -> Vec<RoomSummary> {
+    let mut summaries: Vec<RoomSummary> = state.rooms
+        .values()
+        .filter(|room| include_unlisted || (room.listed && room.tenant_id.as_deref() == tenant_id))
+        .map(|room| RoomSummary {
+            room_id: room.room_id.clone(),
+            participants: room.members.len(),
+            max_participants: room.max_participants,
+            password_protected: room.password_hash.is_some(),
+            lobby: room.lobby_enabled,
+            locked: room.locked,
+            starts_at: room.starts_at,
+            ends_at: room.ends_at,
+            config: room.config.clone(),
+        })
+        .collect();
+
+    summaries.sort_by(|a, b| a.room_id.cmp(&b.room_id));
+    summaries
+}
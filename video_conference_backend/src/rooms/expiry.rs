@@ -0,0 +1,115 @@
+use crate::config;
+use crate::models::{Client, SignalMessage};
+use crate::rooms::forget_room;
+use crate::signaling::handlers::send_signal;
+use crate::signaling::state::{SharedState, SignalingState};
+use chrono::Utc;
+use std::time::{Duration, Instant};
+
+pub async fn run_room_sweeper(state: SharedState) {
+    let mut interval = tokio::time::interval(config::get_room_sweep_interval());
+
+    loop {
+        interval.tick().await;
+
+        let mut state = state.lock().await;
+        let closed = sweep_rooms(
+            &mut state,
+            Instant::now(),
+            config::get_empty_room_ttl(),
+            config::get_room_idle_timeout(),
+        );
+
+        for (room_id, reason, participants) in closed {
+            println!("Closed room {} ({})", room_id, reason);
+            notify_room_closed(&room_id, reason, &participants).await;
+        }
+    }
+}
+
+// Closes a scheduled room once its end time passes
+pub async fn schedule_room_close(state: SharedState, room_id: String, ends_at: i64) {
+    let remaining = ends_at.saturating_sub(Utc::now().timestamp()).max(0) as u64;
+    tokio::time::sleep(Duration::from_secs(remaining)).await;
+
+    let mut state = state.lock().await;
+    let still_scheduled = state.rooms
+        .get(&room_id)
+        .is_some_and(|room| room.ends_at == Some(ends_at));
+
+    if still_scheduled {
+        let participants = close_room(&mut state, &room_id);
+        println!("Closed room {} (ended)", room_id);
+        notify_room_closed(&room_id, "ended", &participants).await;
+    }
+}
+
+// Removes the room and walks its members out as if each had left, returning the clients that
+// were still inside
+pub fn close_room(state: &mut SignalingState, room_id: &str) -> Vec<Client> {
+    let mut participants = Vec::new();
+
+    if let Some(room) = state.rooms.remove(room_id) {
+        forget_room(state, room_id);
+        for addr in &room.members {
+            if state.leave_room(*addr).is_some() {
+                participants.extend(state.clients.get(addr).cloned());
+            }
+        }
+    }
+
+    participants
+}
+
+async fn notify_room_closed(room_id: &str, reason: &str, participants: &[Client]) {
+    let notice = SignalMessage::server("room-closed", serde_json::json!({
+        "room_id": room_id,
+        "reason": reason,
+    }));
+    for client in participants {
+        if let Err(e) = send_signal(client, &notice).await {
+            eprintln!("Failed to notify {} of room closure: {}", client.address, e);
+        }
+    }
+}
+
+// Removes ended rooms, empty rooms past `empty_ttl` and any room idle past `idle_timeout`,
+// returning each closed room id with the reason and the participants that were still inside.
+// Scheduled rooms are never idle before their start time.
+pub fn sweep_rooms(
+    state: &mut SignalingState,
+    now: Instant,
+    empty_ttl: Duration,
+    idle_timeout: Duration
+) -> Vec<(String, &'static str, Vec<Client>)> {
+    let now_ts = Utc::now().timestamp();
+
+    let expired: Vec<(String, &'static str)> = state.rooms
+        .values()
+        .filter(|room| room.has_started(now_ts))
+        .filter_map(|room| {
+            if room.has_ended(now_ts) {
+                return Some((room.room_id.clone(), "ended"));
+            }
+
+            let mut idle = now.saturating_duration_since(room.last_activity);
+            if let Some(starts_at) = room.starts_at {
+                idle = idle.min(Duration::from_secs(now_ts.saturating_sub(starts_at).max(0) as u64));
+            }
+
+            if (room.is_empty() && idle >= empty_ttl) || idle >= idle_timeout {
+                Some((room.room_id.clone(), "idle"))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    expired
+        .into_iter()
+        .map(|(room_id, reason)| {
+            let participants = close_room(state, &room_id);
+            (room_id, reason, participants)
+        })
+        .collect()
+}
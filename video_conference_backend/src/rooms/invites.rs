@@ -0,0 +1,107 @@
+use crate::config;
+use crate::models::Role;
+use crate::rooms::JoinError;
+use crate::signaling::state::SignalingState;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteClaims {
+    pub room_id: String,
+    pub invite_id: String,
+    pub expires_at: i64,
+    // Role granted on join; never `Host`
+    #[serde(default)]
+    pub role: Option<Role>,
+    // Holders skip the lobby; only set when the issuer asked for it
+    #[serde(default)]
+    pub bypass_lobby: bool,
+}
+
+// Without a configured secret a random key is used, so tokens stop verifying after a restart.
+// Remaining uses live on the in-memory room either way; outstanding invites are lost with it.
+fn signing_key() -> &'static hmac::Key {
+    static KEY: OnceLock<hmac::Key> = OnceLock::new();
+    KEY.get_or_init(|| match config::get_invite_secret() {
+        Some(secret) => hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+        None => {
+            let mut secret = [0u8; 32];
+            SystemRandom::new()
+                .fill(&mut secret)
+                .expect("system random source unavailable");
+            hmac::Key::new(hmac::HMAC_SHA256, &secret)
+        }
+    })
+}
+
+// Registers a new invite on the room and returns its token (`claims.signature`, base64url)
+pub fn issue_invite(
+    state: &mut SignalingState,
+    room_id: &str,
+    expires_at: i64,
+    max_uses: Option<u32>,
+    role: Option<Role>,
+    bypass_lobby: bool
+) -> Option<String> {
+    let room = state.rooms.get_mut(room_id)?;
+
+    let claims = InviteClaims {
+        room_id: room_id.to_string(),
+        invite_id: uuid::Uuid::new_v4().to_string(),
+        expires_at,
+        role: role.filter(|role| *role != Role::Host),
+        bypass_lobby,
+    };
+    room.invites.insert(claims.invite_id.clone(), max_uses);
+
+    let encoded = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).ok()?);
+    let signature = hmac::sign(signing_key(), encoded.as_bytes());
+    Some(format!("{}.{}", encoded, URL_SAFE_NO_PAD.encode(signature.as_ref())))
+}
+
+// Validates the token for `room_id` without spending a use, returning its claims.
+// A `Host` role in the claims is dropped rather than granted.
+pub fn check_invite(state: &SignalingState, room_id: &str, token: &str) -> Result<InviteClaims, JoinError> {
+    let mut claims = decode_invite(token).ok_or(JoinError::InvalidInvite)?;
+
+    if claims.room_id != room_id {
+        return Err(JoinError::InvalidInvite);
+    }
+    if claims.expires_at <= Utc::now().timestamp() {
+        return Err(JoinError::InviteExpired);
+    }
+
+    let room = state.rooms.get(room_id).ok_or(JoinError::InvalidInvite)?;
+    if room.invites.get(&claims.invite_id).ok_or(JoinError::InvalidInvite)? == &Some(0) {
+        return Err(JoinError::InviteExhausted);
+    }
+
+    claims.role = claims.role.filter(|role| *role != Role::Host);
+    Ok(claims)
+}
+
+// Spends one use of a checked invite. Called only once the invite has got its holder in.
+pub fn redeem_invite(state: &mut SignalingState, claims: &InviteClaims) {
+    let remaining = state.rooms
+        .get_mut(&claims.room_id)
+        .and_then(|room| room.invites.get_mut(&claims.invite_id));
+
+    if let Some(Some(uses)) = remaining {
+        *uses = uses.saturating_sub(1);
+    }
+}
+
+fn decode_invite(token: &str) -> Option<InviteClaims> {
+    let (encoded, signature) = token.split_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+    hmac::verify(signing_key(), encoded.as_bytes(), &signature).ok()?;
+
+    let claims = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+    serde_json::from_slice(&claims).ok()
+}
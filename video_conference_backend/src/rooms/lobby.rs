@@ -0,0 +1,44 @@
+use crate::models::WaitingJoin;
+use crate::rooms::{moderated_room_mut, moderators, JoinError};
+use crate::signaling::state::SignalingState;
+use std::net::SocketAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LobbyError {
+    NotModerator,
+    NotWaiting,
+}
+
+// Whether a join must be held in the lobby instead of entering the room directly
+pub fn requires_admission(state: &SignalingState, addr: SocketAddr, room_id: &str) -> bool {
+    state.rooms
+        .get(room_id)
+        .map(|room| room.lobby_enabled && !room.is_host(&addr) && !room.contains(&addr))
+        .unwrap_or(false)
+}
+
+// Places the client in the room's lobby and returns the moderators to notify
+pub fn enter_lobby(state: &mut SignalingState, join: WaitingJoin, room_id: &str) -> Vec<SocketAddr> {
+    match state.rooms.get_mut(room_id) {
+        Some(room) => room.add_waiting(join),
+        None => return Vec::new(),
+    }
+    moderators(state, room_id)
+}
+
+// Takes a waiting client out of the moderator's room's lobby. Returns the room, the held join and
+// whether the moderator let it through; entering the room is left to the caller, so an admitted
+// client goes through the same steps as one that joined directly.
+pub fn admit_from_lobby(
+    state: &mut SignalingState,
+    moderator_addr: SocketAddr,
+    client_id: &str,
+    approved: bool
+) -> Result<(String, WaitingJoin, Result<(), JoinError>), LobbyError> {
+    let waiting_addr = state.tenant_client(moderator_addr, client_id).ok_or(LobbyError::NotWaiting)?.address;
+    let room = moderated_room_mut(state, moderator_addr).ok_or(LobbyError::NotModerator)?;
+    let join = room.remove_waiting(&waiting_addr).ok_or(LobbyError::NotWaiting)?;
+
+    let decision = if approved { Ok(()) } else { Err(JoinError::DeniedByHost) };
+    Ok((room.room_id.clone(), join, decision))
+}
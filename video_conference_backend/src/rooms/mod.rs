@@ -0,0 +1,185 @@
+pub mod discovery;
+pub mod expiry;
+pub mod invites;
+pub mod lobby;
+pub mod moderation;
+pub mod ownership;
+pub mod password;
+pub mod persistence;
+pub mod policy;
+
+use crate::models::Room;
+use chrono::Utc;
+use crate::signaling::state::SignalingState;
+use std::net::SocketAddr;
+
+pub use discovery::{list_rooms, RoomSummary};
+pub use expiry::{close_room, run_room_sweeper, schedule_room_close, sweep_rooms};
+pub use invites::{check_invite, issue_invite, redeem_invite, InviteClaims};
+pub use lobby::{admit_from_lobby, enter_lobby, requires_admission, LobbyError};
+pub use moderation::{moderation_target, set_role, ModerationError};
+pub use ownership::{transfer_host, HostTransfer};
+pub use password::{hash_password, verify_password};
+pub use policy::{check_session_description, CandidatePolicy, CandidateRejection, FilePolicy, FileRejection, PolicyViolation};
+pub use persistence::{forget_room, persist_room, restore_rooms};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    RoomFull { max_participants: usize },
+    PasswordRequired,
+    InvalidPassword,
+    DeniedByHost,
+    RoomLocked,
+    NotStarted { starts_at: i64 },
+    InvalidInvite,
+    InviteExpired,
+    InviteExhausted,
+    Banned,
+    NotAllowed,
+}
+
+impl JoinError {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            JoinError::RoomFull { .. } => "room-full",
+            JoinError::PasswordRequired => "password-required",
+            JoinError::InvalidPassword => "invalid-password",
+            JoinError::DeniedByHost => "denied-by-host",
+            JoinError::RoomLocked => "room-locked",
+            JoinError::NotStarted { .. } => "meeting-not-started",
+            JoinError::InvalidInvite => "invalid-invite",
+            JoinError::InviteExpired => "invite-expired",
+            JoinError::InviteExhausted => "invite-exhausted",
+            JoinError::Banned => "banned",
+            JoinError::NotAllowed => "room-not-allowed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateRoomError {
+    AlreadyExists,
+    InvalidSchedule,
+}
+
+// Registers an explicitly created room; its password must already be hashed since
+// hashing is kept out of the state lock
+pub fn create_room(state: &mut SignalingState, mut room: Room) -> Result<(), CreateRoomError> {
+    if state.rooms.contains_key(&room.room_id) {
+        return Err(CreateRoomError::AlreadyExists);
+    }
+
+    let now = Utc::now().timestamp();
+    if let Some(ends_at) = room.ends_at {
+        if ends_at <= now || room.starts_at.is_some_and(|starts_at| starts_at >= ends_at) {
+            return Err(CreateRoomError::InvalidSchedule);
+        }
+    }
+
+    room.ephemeral = false;
+    let room_id = room.room_id.clone();
+    state.rooms.insert(room_id.clone(), room);
+    persist_room(state, &room_id);
+
+    Ok(())
+}
+
+// The room `addr` is currently in, provided its role there allows moderation
+pub fn moderated_room_mut(state: &mut SignalingState, addr: SocketAddr) -> Option<&mut Room> {
+    let client = state.clients.get(&addr)?;
+    if !client.role.can_moderate() {
+        return None;
+    }
+
+    let room_id = client.room_id.clone()?;
+    state.rooms.get_mut(&room_id)
+}
+
+// Connected hosts and moderators of the room
+pub fn moderators(state: &SignalingState, room_id: &str) -> Vec<SocketAddr> {
+    state.rooms
+        .get(room_id)
+        .map(|room| {
+            room.members
+                .iter()
+                .filter(|member| state.clients.get(member).is_some_and(|client| client.role.can_moderate()))
+                .copied()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn check_lock(state: &SignalingState, addr: SocketAddr, room_id: &str) -> Result<(), JoinError> {
+    match state.rooms.get(room_id) {
+        Some(room) if room.is_locked_for(&addr) => Err(JoinError::RoomLocked),
+        _ => Ok(()),
+    }
+}
+
+pub fn check_ban(state: &SignalingState, addr: SocketAddr, room_id: &str) -> Result<(), JoinError> {
+    let public_key = state.clients.get(&addr).and_then(|client| client.public_key.as_deref());
+    match state.rooms.get(room_id) {
+        Some(room) if room.is_banned(&addr.ip(), public_key) => Err(JoinError::Banned),
+        _ => Ok(()),
+    }
+}
+
+// Where a room a client names lives in the registry. Each tenant has a namespace of its own, so
+// two tenants can both have a room "standup"; a tenant's rooms go by "<tenant_id>/<room_id>".
+// Ids already in the tenant's namespace, like those `list-rooms` hands out, are left as they are.
+pub fn scoped_room_id(tenant_id: Option<&str>, room_id: &str) -> String {
+    match tenant_id {
+        Some(tenant_id) if !room_id.strip_prefix(tenant_id).is_some_and(|rest| rest.starts_with('/')) => {
+            format!("{}/{}", tenant_id, room_id)
+        }
+        _ => room_id.to_string(),
+    }
+}
+
+// Rooms of another tenant are off limits, as are tenant rooms to clients without a tenant
+pub fn check_tenant(state: &SignalingState, addr: SocketAddr, room_id: &str) -> Result<(), JoinError> {
+    let tenant_id = state.clients.get(&addr).and_then(|client| client.tenant_id());
+    match state.rooms.get(room_id) {
+        Some(room) if room.tenant_id.as_deref() != tenant_id => Err(JoinError::NotAllowed),
+        _ => Ok(()),
+    }
+}
+
+// Users whose token lists rooms may only join those
+pub fn check_claims(state: &SignalingState, addr: SocketAddr, room_id: &str) -> Result<(), JoinError> {
+    let claims = state.clients.get(&addr).and_then(|client| client.claims.as_ref());
+    match claims {
+        Some(claims) if !claims.allows_room(room_id) => Err(JoinError::NotAllowed),
+        _ => Ok(()),
+    }
+}
+
+pub fn check_schedule(state: &SignalingState, room_id: &str) -> Result<(), JoinError> {
+    let now = Utc::now().timestamp();
+    match state.rooms.get(room_id) {
+        Some(room) if !room.has_started(now) => Err(JoinError::NotStarted {
+            starts_at: room.starts_at.unwrap_or(now),
+        }),
+        _ => Ok(()),
+    }
+}
+
+// The password is verified against the hash the room had before the state lock was let go; this
+// turns the join away if the room's password has changed since, e.g. it was closed and recreated
+pub fn check_password_unchanged(state: &SignalingState, room_id: &str, checked: Option<&str>) -> Result<(), JoinError> {
+    let current = state.rooms.get(room_id).and_then(|room| room.password_hash.as_deref());
+    match (current, checked) {
+        (current, checked) if current == checked => Ok(()),
+        (Some(_), None) => Err(JoinError::PasswordRequired),
+        _ => Err(JoinError::InvalidPassword),
+    }
+}
+
+pub fn check_password(room_password_hash: Option<&str>, password: Option<&str>) -> Result<(), JoinError> {
+    match (room_password_hash, password) {
+        (None, _) => Ok(()),
+        (Some(_), None) => Err(JoinError::PasswordRequired),
+        (Some(hash), Some(password)) if verify_password(password, hash) => Ok(()),
+        (Some(_), Some(_)) => Err(JoinError::InvalidPassword),
+    }
+}
@@ -0,0 +1,74 @@
+use crate::models::Role;
+use crate::signaling::state::SignalingState;
+use std::net::SocketAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationError {
+    NotModerator,
+    NotHost,
+    TargetNotFound,
+    InsufficientRole,
+}
+
+impl ModerationError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ModerationError::NotModerator => "not-moderator",
+            ModerationError::NotHost => "not-host",
+            ModerationError::TargetNotFound => "target-unknown",
+            ModerationError::InsufficientRole => "insufficient-role",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ModerationError::NotModerator => "Only hosts and moderators can do this",
+            ModerationError::NotHost => "Only the host can do this",
+            ModerationError::TargetNotFound => "Target peer is not in your room",
+            ModerationError::InsufficientRole => "Target peer's role is not below yours",
+        }
+    }
+}
+
+// Resolves the room member a moderator wants to act on, checking that the moderator outranks them
+pub fn moderation_target(
+    state: &SignalingState,
+    moderator_addr: SocketAddr,
+    target_id: &str
+) -> Result<SocketAddr, ModerationError> {
+    let moderator = state.clients.get(&moderator_addr).ok_or(ModerationError::NotModerator)?;
+    if !moderator.role.can_moderate() {
+        return Err(ModerationError::NotModerator);
+    }
+
+    let target = state.room_peers(moderator_addr)
+        .into_iter()
+        .find(|client| client.client_id == target_id)
+        .ok_or(ModerationError::TargetNotFound)?;
+
+    if !moderator.role.outranks(&target.role) {
+        return Err(ModerationError::InsufficientRole);
+    }
+
+    Ok(target.address)
+}
+
+// Lets the host move a member between participant and co-host (moderator); the host role itself is never handed out here
+pub fn set_role(
+    state: &mut SignalingState,
+    host_addr: SocketAddr,
+    target_id: &str,
+    role: Role
+) -> Result<SocketAddr, ModerationError> {
+    let host = state.clients.get(&host_addr).ok_or(ModerationError::NotHost)?;
+    if host.role != Role::Host || role == Role::Host {
+        return Err(ModerationError::NotHost);
+    }
+
+    let target_addr = moderation_target(state, host_addr, target_id)?;
+    if let Some(target) = state.clients.get_mut(&target_addr) {
+        target.role = role;
+    }
+
+    Ok(target_addr)
+}
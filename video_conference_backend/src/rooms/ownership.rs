@@ -0,0 +1,70 @@
+use crate::config;
+use crate::models::Role;
+use crate::signaling::state::SignalingState;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+// Who takes over a room whose host has left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostTransfer {
+    // Longest-present co-host, falling back to the longest-present participant
+    CoHostFirst,
+    // Longest-present member regardless of role
+    Oldest,
+    // Leave the room without a host
+    Disabled,
+}
+
+impl FromStr for HostTransfer {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "co-host" => Ok(HostTransfer::CoHostFirst),
+            "oldest" => Ok(HostTransfer::Oldest),
+            "none" => Ok(HostTransfer::Disabled),
+            other => Err(format!("unknown host transfer policy: {}", other)),
+        }
+    }
+}
+
+// Hands the room to another member if its host is no longer in it. Viewers are never
+// promoted; with no candidate left, or transfer disabled, the host slot is cleared so the
+// next joiner takes it. Returns the new host, if any.
+pub fn transfer_host(state: &mut SignalingState, room_id: &str) -> Option<SocketAddr> {
+    let room = state.rooms.get_mut(room_id)?;
+    let host_gone = room.host.is_some_and(|host| !room.contains(&host));
+    if !host_gone {
+        return None;
+    }
+
+    let policy = config::get_host_transfer();
+    if policy == HostTransfer::Disabled {
+        room.host = None;
+        return None;
+    }
+
+    let candidates: Vec<(SocketAddr, Role)> = room.members
+        .iter()
+        .filter_map(|member| state.clients.get(member))
+        .filter(|client| client.role != Role::Viewer)
+        .map(|client| (client.address, client.role))
+        .collect();
+
+    let new_host = match policy {
+        HostTransfer::CoHostFirst => candidates
+            .iter()
+            .find(|(_, role)| *role == Role::Moderator)
+            .or(candidates.first()),
+        _ => candidates.first(),
+    }
+    .map(|(addr, _)| *addr);
+
+    if let Some(room) = state.rooms.get_mut(room_id) {
+        room.host = new_host;
+    }
+    let client = state.clients.get_mut(&new_host?)?;
+    client.role = Role::Host;
+
+    new_host
+}
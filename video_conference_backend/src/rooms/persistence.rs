@@ -0,0 +1,61 @@
+use crate::rooms::schedule_room_close;
+use crate::signaling::state::{SharedState, SignalingState};
+use crate::storage::RoomRecord;
+use chrono::Utc;
+use std::sync::Arc;
+
+// Writes the room definition through to the configured store. Ephemeral rooms are never stored.
+pub fn persist_room(state: &SignalingState, room_id: &str) {
+    let (Some(store), Some(room)) = (&state.room_store, state.rooms.get(room_id)) else {
+        return;
+    };
+
+    if room.ephemeral {
+        return;
+    }
+
+    if let Err(e) = store.save_room(&RoomRecord::from(room)) {
+        eprintln!("[ERROR] Failed to persist room {}: {}", room_id, e);
+    }
+}
+
+// Deletes what was stored for a room that is gone: its definition and its chat history
+pub fn forget_room(state: &SignalingState, room_id: &str) {
+    if let Some(store) = &state.room_store {
+        if let Err(e) = store.delete_room(room_id) {
+            eprintln!("[ERROR] Failed to delete stored room {}: {}", room_id, e);
+        }
+    }
+    if let Some(chat) = &state.chat {
+        if let Err(e) = chat.delete_room(room_id) {
+            eprintln!("[ERROR] Failed to delete chat history of room {}: {}", room_id, e);
+        }
+    }
+}
+
+// Loads stored rooms into the registry at startup, dropping any whose schedule has already ended
+pub async fn restore_rooms(state: SharedState) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut guard = state.lock().await;
+    let store = match &guard.room_store {
+        Some(store) => Arc::clone(store),
+        None => return Ok(0),
+    };
+
+    let now = Utc::now().timestamp();
+    let mut restored = 0;
+
+    for record in store.load_rooms().map_err(|e| e.to_string())? {
+        if record.ends_at.is_some_and(|ends_at| ends_at <= now) {
+            forget_room(&guard, &record.room_id);
+            continue;
+        }
+
+        if let Some(ends_at) = record.ends_at {
+            tokio::spawn(schedule_room_close(Arc::clone(&state), record.room_id.clone(), ends_at));
+        }
+        guard.rooms.insert(record.room_id.clone(), record.into());
+        restored += 1;
+    }
+
+    Ok(restored)
+}
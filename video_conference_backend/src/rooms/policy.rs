@@ -0,0 +1,190 @@
+use crate::config;
+use crate::models::message::SecureConnectionPayload;
+use crate::models::room::RoomConfig;
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    VideoNotAllowed,
+    E2eeRequired,
+}
+
+impl PolicyViolation {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PolicyViolation::VideoNotAllowed => "audio-only-room",
+            PolicyViolation::E2eeRequired => "e2ee-required",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            PolicyViolation::VideoNotAllowed => "This room only allows audio tracks",
+            PolicyViolation::E2eeRequired => "This room requires end-to-end encrypted media",
+        }
+    }
+}
+
+// Checks an offer or answer against the room profile before it is verified and relayed.
+// Bitrate and recording settings are advisory and only distributed to clients.
+pub fn check_session_description(
+    config: &RoomConfig,
+    payload: &SecureConnectionPayload
+) -> Result<(), PolicyViolation> {
+    if config.e2ee_required && !payload.e2ee {
+        return Err(PolicyViolation::E2eeRequired);
+    }
+
+    if config.audio_only {
+        let sdp = payload.offer.get("sdp").and_then(|sdp| sdp.as_str()).unwrap_or("");
+        if sdp.lines().any(|line| line.starts_with("m=video")) {
+            return Err(PolicyViolation::VideoNotAllowed);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateRejection {
+    Mdns,
+    NotRelay,
+    PrivateAddress,
+}
+
+impl CandidateRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CandidateRejection::Mdns => "mdns-candidate",
+            CandidateRejection::NotRelay => "relay-only",
+            CandidateRejection::PrivateAddress => "private-address",
+        }
+    }
+}
+
+// Which relayed ICE candidates a room drops: the server-wide settings with the room's
+// overrides applied
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CandidatePolicy {
+    pub strip_mdns: bool,
+    pub relay_only: bool,
+    pub drop_private: bool,
+}
+
+impl CandidatePolicy {
+    pub fn for_room(config: &RoomConfig) -> Self {
+        Self {
+            strip_mdns: config.strip_mdns_candidates.unwrap_or_else(config::get_strip_mdns_candidates),
+            relay_only: config.relay_only.unwrap_or_else(config::get_relay_only),
+            drop_private: config.drop_private_candidates.unwrap_or_else(config::get_drop_private_candidates),
+        }
+    }
+
+    // Checks one `candidate:` line. The empty end-of-candidates marker and lines too short to
+    // carry an address always pass; the peer's ICE agent rejects the latter itself.
+    pub fn check(&self, candidate: &str) -> Result<(), CandidateRejection> {
+        let candidate = candidate.trim().trim_start_matches("a=");
+        let fields: Vec<&str> = candidate.split_whitespace().collect();
+        let (Some(address), Some(kind)) = (
+            fields.get(4),
+            fields.iter().position(|field| *field == "typ").and_then(|index| fields.get(index + 1))
+        ) else {
+            return Ok(());
+        };
+
+        if self.relay_only && *kind != "relay" {
+            return Err(CandidateRejection::NotRelay);
+        }
+        if self.strip_mdns && address.to_ascii_lowercase().ends_with(".local") {
+            return Err(CandidateRejection::Mdns);
+        }
+        if self.drop_private && address.parse().is_ok_and(|ip| !is_public(ip)) {
+            return Err(CandidateRejection::PrivateAddress);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileRejection {
+    Disabled,
+    TooLarge,
+    TypeNotAllowed,
+}
+
+impl FileRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            FileRejection::Disabled => "file-transfer-disabled",
+            FileRejection::TooLarge => "file-too-large",
+            FileRejection::TypeNotAllowed => "file-type-not-allowed",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            FileRejection::Disabled => "This room does not allow file transfers",
+            FileRejection::TooLarge => "The file is larger than this room allows",
+            FileRejection::TypeNotAllowed => "This room does not allow files of that type",
+        }
+    }
+}
+
+// Which files peers may offer each other in a room: the server-wide settings with the room's
+// overrides applied. The server only sees what the offer claims; the recipient checks the file
+// against the offered size and hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilePolicy {
+    // 0 turns file transfers off
+    pub max_size: u64,
+    // Exact types or `type/*`; empty allows any
+    pub allowed_types: Vec<String>,
+}
+
+impl FilePolicy {
+    pub fn for_room(config: &RoomConfig) -> Self {
+        Self {
+            max_size: config.max_file_size.unwrap_or_else(config::get_max_file_size),
+            allowed_types: config.allowed_file_types.clone().unwrap_or_else(config::get_allowed_file_types),
+        }
+    }
+
+    pub fn check(&self, size: u64, mime_type: &str) -> Result<(), FileRejection> {
+        if self.max_size == 0 {
+            return Err(FileRejection::Disabled);
+        }
+        if size > self.max_size {
+            return Err(FileRejection::TooLarge);
+        }
+        // Parameters such as `; charset=utf-8` do not change the type
+        let mime_type = mime_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        let allowed = self.allowed_types.is_empty() || self.allowed_types.iter().any(|pattern| {
+            let pattern = pattern.to_ascii_lowercase();
+            match pattern.strip_suffix("/*") {
+                Some("*") => true,
+                Some(prefix) => mime_type.split_once('/').is_some_and(|(kind, _)| kind == prefix),
+                None => pattern == mime_type,
+            }
+        });
+        if !allowed {
+            return Err(FileRejection::TypeNotAllowed);
+        }
+        Ok(())
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            // Shared address space behind carrier-grade NAT (100.64.0.0/10)
+            let shared = ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64;
+            !(shared || ip.is_private() || ip.is_link_local() || ip.is_loopback() || ip.is_unspecified())
+        }
+        IpAddr::V6(ip) => {
+            let segment = ip.segments()[0];
+            // Unique local (fc00::/7) and link-local (fe80::/10)
+            let local = segment & 0xfe00 == 0xfc00 || segment & 0xffc0 == 0xfe80;
+            !(local || ip.is_loopback() || ip.is_unspecified())
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::secrets::{read_ciphertext, KeyProvider, SecretResult};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use ring::hmac;
+use sha2::{Digest, Sha256};
+
+const SERVICE: &str = "kms";
+
+// Decrypts key blobs produced by `aws kms encrypt`, using the standard AWS_* credentials.
+// The blob records which KMS key it was encrypted under, so no key id is configured here.
+pub struct AwsKmsProvider {
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl AwsKmsProvider {
+    pub fn from_env() -> SecretResult<Self> {
+        let var = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+        Ok(Self {
+            region: var("AWS_REGION")
+                .or_else(|| var("AWS_DEFAULT_REGION"))
+                .ok_or("KEY_PROVIDER=aws-kms requires AWS_REGION")?,
+            access_key_id: var("AWS_ACCESS_KEY_ID").ok_or("KEY_PROVIDER=aws-kms requires AWS_ACCESS_KEY_ID")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY").ok_or("KEY_PROVIDER=aws-kms requires AWS_SECRET_ACCESS_KEY")?,
+            session_token: var("AWS_SESSION_TOKEN"),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    async fn decrypt(&self, ciphertext: &[u8]) -> SecretResult<Vec<u8>> {
+        let host = format!("kms.{}.amazonaws.com", self.region);
+        let body = serde_json::json!({ "CiphertextBlob": STANDARD.encode(ciphertext) }).to_string();
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host.clone()),
+            ("x-amz-date", amz_date.clone()),
+            ("x-amz-target", "TrentService.Decrypt".to_string()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = self.authorization(&headers, &body, &amz_date);
+
+        let mut request = self.http.post(format!("https://{}/", host)).body(body);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        let response = request.header("authorization", authorization).send().await?;
+        let status = response.status();
+        let reply: serde_json::Value = response.json().await?;
+        if !status.is_success() {
+            let message = reply.get("message").or_else(|| reply.get("Message")).and_then(|m| m.as_str()).unwrap_or("");
+            return Err(format!("KMS Decrypt failed with {}: {}", status, message).into());
+        }
+
+        let plaintext = reply["Plaintext"].as_str().ok_or("KMS Decrypt returned no plaintext")?;
+        Ok(STANDARD.decode(plaintext)?)
+    }
+
+    // Signature Version 4 over the headers in `headers`, which must be sorted by name
+    fn authorization(&self, headers: &[(&str, String)], body: &str, amz_date: &str) -> String {
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, SERVICE);
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            canonical_headers,
+            signed_headers,
+            hex(&Sha256::digest(body.as_bytes()))
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = format!("AWS4{}", self.secret_access_key).into_bytes();
+        for part in [date, self.region.as_str(), SERVICE, "aws4_request"] {
+            key = sign(&key, part.as_bytes());
+        }
+        let signature = hex(&sign(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id, scope, signed_headers, signature
+        )
+    }
+}
+
+impl KeyProvider for AwsKmsProvider {
+    // `reference` is the path of a file holding the ciphertext blob
+    fn load<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, SecretResult<Vec<u8>>> {
+        Box::pin(async move {
+            let ciphertext = read_ciphertext(reference)?;
+            self.decrypt(&ciphertext).await
+        })
+    }
+}
+
+fn sign(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,68 @@
+use crate::secrets::{read_ciphertext, KeyProvider, SecretResult};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_util::future::BoxFuture;
+
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+// Decrypts key blobs produced by `gcloud kms encrypt` with Cloud KMS. The access token comes
+// from GCP_ACCESS_TOKEN when set, otherwise from the instance metadata server.
+pub struct GcpKmsProvider {
+    // projects/<project>/locations/<location>/keyRings/<ring>/cryptoKeys/<key>
+    key_name: String,
+    http: reqwest::Client,
+}
+
+impl GcpKmsProvider {
+    pub fn new(key_name: String) -> Self {
+        Self {
+            key_name,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    async fn access_token(&self) -> SecretResult<String> {
+        if let Some(token) = std::env::var("GCP_ACCESS_TOKEN").ok().filter(|token| !token.is_empty()) {
+            return Ok(token);
+        }
+        let reply: serde_json::Value = self.http
+            .get(METADATA_TOKEN_URL)
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(reply["access_token"].as_str().ok_or("metadata server returned no access token")?.to_string())
+    }
+
+    async fn decrypt(&self, ciphertext: &[u8]) -> SecretResult<Vec<u8>> {
+        let url = format!("https://cloudkms.googleapis.com/v1/{}:decrypt", self.key_name);
+        let response = self.http
+            .post(url)
+            .bearer_auth(self.access_token().await?)
+            .json(&serde_json::json!({ "ciphertext": STANDARD.encode(ciphertext) }))
+            .send()
+            .await?;
+        let status = response.status();
+        let reply: serde_json::Value = response.json().await?;
+        if !status.is_success() {
+            let message = reply["error"]["message"].as_str().unwrap_or("");
+            return Err(format!("Cloud KMS decrypt failed with {}: {}", status, message).into());
+        }
+
+        let plaintext = reply["plaintext"].as_str().ok_or("Cloud KMS decrypt returned no plaintext")?;
+        Ok(STANDARD.decode(plaintext)?)
+    }
+}
+
+impl KeyProvider for GcpKmsProvider {
+    // `reference` is the path of a file holding the ciphertext
+    fn load<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, SecretResult<Vec<u8>>> {
+        Box::pin(async move {
+            let ciphertext = read_ciphertext(reference)?;
+            self.decrypt(&ciphertext).await
+        })
+    }
+}
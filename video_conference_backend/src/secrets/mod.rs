@@ -0,0 +1,50 @@
+pub mod aws;
+pub mod gcp;
+pub mod pkcs11;
+
+use crate::config;
+use futures_util::future::BoxFuture;
+use std::sync::Arc;
+
+pub use aws::AwsKmsProvider;
+pub use gcp::GcpKmsProvider;
+pub use pkcs11::Pkcs11Provider;
+
+pub type SecretResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Where the server's own key material (TLS key, TURN secret, identity, Noise and storage keys) comes
+// from when it must not sit on disk in plaintext. `reference` names the key in the backend: the
+// path of a KMS-encrypted blob, or the label of an HSM object.
+pub trait KeyProvider: Send + Sync {
+    fn load<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, SecretResult<Vec<u8>>>;
+}
+
+// The backend picked by KEY_PROVIDER; None keeps keys in local files
+pub fn from_config() -> SecretResult<Option<Arc<dyn KeyProvider>>> {
+    let provider: Arc<dyn KeyProvider> = match config::get_key_provider().as_str() {
+        "" | "file" => return Ok(None),
+        "aws-kms" => Arc::new(AwsKmsProvider::from_env()?),
+        "gcp-kms" => {
+            let key_name = config::get_gcp_kms_key_name().ok_or("KEY_PROVIDER=gcp-kms requires GCP_KMS_KEY_NAME")?;
+            Arc::new(GcpKmsProvider::new(key_name))
+        }
+        "pkcs11" => {
+            let module = config::get_pkcs11_module().ok_or("KEY_PROVIDER=pkcs11 requires PKCS11_MODULE")?;
+            Arc::new(Pkcs11Provider::new(module, config::get_pkcs11_token_label(), config::get_pkcs11_pin()))
+        }
+        other => return Err(format!("Unknown KEY_PROVIDER {:?}; use file, aws-kms, gcp-kms or pkcs11", other).into()),
+    };
+    Ok(Some(provider))
+}
+
+// KMS ciphertext is kept base64 encoded so it can live in plain text files; raw blobs work too
+pub(crate) fn read_ciphertext(path: &str) -> SecretResult<Vec<u8>> {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    let contents = std::fs::read(path)?;
+    match std::str::from_utf8(&contents).ok().map(str::trim).map(|text| STANDARD.decode(text)) {
+        Some(Ok(decoded)) => Ok(decoded),
+        _ => Ok(contents),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::secrets::{KeyProvider, SecretResult};
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::object::{Attribute, AttributeType};
+use cryptoki::session::UserType;
+use cryptoki::types::AuthPin;
+use futures_util::future::BoxFuture;
+use std::path::PathBuf;
+
+// Reads key material stored as objects on a PKCS#11 token (an HSM, a smartcard or SoftHSM).
+// The objects must be extractable, since the server uses the raw bytes.
+#[derive(Clone)]
+pub struct Pkcs11Provider {
+    module: PathBuf,
+    // The first token present is used when unset
+    token_label: Option<String>,
+    pin: Option<String>,
+}
+
+impl Pkcs11Provider {
+    pub fn new(module: impl Into<PathBuf>, token_label: Option<String>, pin: Option<String>) -> Self {
+        Self {
+            module: module.into(),
+            token_label,
+            pin,
+        }
+    }
+
+    // Blocking; the module is loaded and the session opened for each read, which only
+    // happens at startup
+    fn read_object(&self, label: &str) -> SecretResult<Vec<u8>> {
+        let pkcs11 = Pkcs11::new(&self.module)?;
+        pkcs11.initialize(CInitializeArgs::OsThreads)?;
+
+        let mut slot = None;
+        for candidate in pkcs11.get_slots_with_token()? {
+            let token = pkcs11.get_token_info(candidate)?;
+            if self.token_label.as_deref().is_none_or(|wanted| token.label().trim() == wanted) {
+                slot = Some(candidate);
+                break;
+            }
+        }
+        let slot = slot.ok_or_else(|| match &self.token_label {
+            Some(wanted) => format!("no PKCS#11 token labelled {:?}", wanted),
+            None => "no PKCS#11 token present".to_string(),
+        })?;
+
+        let session = pkcs11.open_ro_session(slot)?;
+        if let Some(pin) = &self.pin {
+            session.login(UserType::User, Some(&AuthPin::new(pin.clone())))?;
+        }
+        let object = session
+            .find_objects(&[Attribute::Label(label.as_bytes().to_vec())])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("no PKCS#11 object labelled {:?}", label))?;
+        let value = session
+            .get_attributes(object, &[AttributeType::Value])?
+            .into_iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Value(value) => Some(value),
+                _ => None,
+            })
+            .ok_or_else(|| format!("PKCS#11 object {:?} has no readable value", label))?;
+        Ok(value)
+    }
+}
+
+impl KeyProvider for Pkcs11Provider {
+    // `reference` is the object's label
+    fn load<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, SecretResult<Vec<u8>>> {
+        Box::pin(async move {
+            let provider = self.clone();
+            let label = reference.to_string();
+            tokio::task::spawn_blocking(move || provider.read_object(&label)).await?
+        })
+    }
+}
@@ -0,0 +1,206 @@
+use crate::auth::Claims;
+use crate::models::{Client, Profile, Role};
+use crate::pinning;
+use crate::signaling::dedup::MessageIdCache;
+use crate::signaling::state::SignalingState;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+// What a verified client leaves behind on disconnect so it can pick up where it left off
+#[derive(Debug, Clone)]
+pub struct SuspendedSession {
+    pub client_id: String,
+    pub address: SocketAddr,
+    pub room_id: Option<String>,
+    pub public_key: Option<Vec<u8>>,
+    pub profile: Profile,
+    pub role: Role,
+    pub claims: Option<Claims>,
+    // As pinning::identity named the client, so bans issued while it is away still reach it
+    pub identity: String,
+    pub tenant_id: Option<String>,
+    pub certificate_fingerprint: Option<String>,
+    // So envelopes sent before the disconnect can't be replayed over the resumed connection
+    pub last_seq: Option<u64>,
+    pub recent_messages: MessageIdCache,
+    pub expires_at: Instant,
+}
+
+// Proof that a connection's offer or answer verified, presented with later messages in place of
+// a fresh signature
+#[derive(Debug, Clone)]
+pub struct SessionToken {
+    pub token: String,
+    pub expires_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionTokenError {
+    Missing,
+    Invalid,
+}
+
+impl SessionTokenError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SessionTokenError::Missing => "session-token-required",
+            SessionTokenError::Invalid => "session-token-invalid",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            SessionTokenError::Missing => "This message must carry the session token issued after verification",
+            SessionTokenError::Invalid => "The session token is unknown or has expired",
+        }
+    }
+}
+
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random source unavailable");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// Issues a resume token for a verified client that doesn't hold one yet
+pub fn issue_resume_token(state: &mut SignalingState, addr: SocketAddr) -> Option<String> {
+    let client = state.clients.get_mut(&addr)?;
+    if !client.verified || client.resume_token.is_some() {
+        return None;
+    }
+
+    let token = random_token();
+    client.resume_token = Some(token.clone());
+    Some(token)
+}
+
+// Issues a fresh session token to a verified client. The token it replaces stays valid until it
+// expires, so messages already in flight with it are not refused.
+pub fn issue_session_token(state: &mut SignalingState, addr: SocketAddr, ttl: Duration) -> Option<SessionToken> {
+    let client = state.clients.get_mut(&addr)?;
+    if !client.verified {
+        return None;
+    }
+
+    let now = Instant::now();
+    let token = SessionToken {
+        token: random_token(),
+        expires_at: now + ttl,
+    };
+    client.session_tokens.retain(|held| held.expires_at > now);
+    if client.session_tokens.len() > 1 {
+        client.session_tokens.remove(0);
+    }
+    client.session_tokens.push(token.clone());
+    Some(token)
+}
+
+// Checks `presented` against the client's live tokens. Ok(true) means the newest one is past
+// half its lifetime and should be replaced.
+pub fn check_session_token(
+    client: &Client,
+    presented: Option<&str>,
+    ttl: Duration
+) -> Result<bool, SessionTokenError> {
+    let presented = presented.ok_or(SessionTokenError::Missing)?;
+    let now = Instant::now();
+    let valid = client.session_tokens.iter().any(|held| {
+        held.expires_at > now
+            && ring::constant_time::verify_slices_are_equal(held.token.as_bytes(), presented.as_bytes()).is_ok()
+    });
+    if !valid {
+        return Err(SessionTokenError::Invalid);
+    }
+    let newest = client.session_tokens.last().map(|held| held.expires_at);
+    Ok(newest.is_some_and(|expires_at| expires_at.saturating_duration_since(now) < ttl / 2))
+}
+
+// Parks the session of a disconnecting client; must run before the client leaves its room
+pub fn suspend_session(state: &mut SignalingState, addr: SocketAddr, grace: Duration) {
+    let now = Instant::now();
+    state.suspended.retain(|_, session| session.expires_at > now);
+
+    let Some(client) = state.clients.get(&addr) else {
+        return;
+    };
+    let Some(token) = client.resume_token.clone() else {
+        return;
+    };
+
+    state.suspended.insert(token, SuspendedSession {
+        client_id: client.client_id.clone(),
+        address: addr,
+        room_id: client.room_id.clone(),
+        public_key: client.public_key.clone(),
+        profile: client.profile.clone(),
+        role: client.role,
+        claims: client.claims.clone(),
+        identity: pinning::identity(client),
+        tenant_id: client.tenant_id().map(str::to_string),
+        certificate_fingerprint: client.certificate.as_ref().map(|certificate| certificate.fingerprint.clone()),
+        last_seq: client.last_seq,
+        recent_messages: client.recent_messages.clone(),
+        expires_at: now + grace,
+    });
+}
+
+// Restores a suspended session onto the connection at `addr`. Room membership is restored
+// even if the room has since been locked or filled, since the client never meant to leave.
+// Returns the restored client_id and a fresh resume token.
+pub fn resume_session(state: &mut SignalingState, addr: SocketAddr, token: &str) -> Option<(String, String)> {
+    // A session can only be picked up with an API key of the tenant it belongs to, and
+    // over the same client certificate it was established with
+    let client = state.clients.get(&addr)?;
+    let tenant_id = client.tenant_id().map(str::to_string);
+    let fingerprint = client.certificate.as_ref().map(|certificate| certificate.fingerprint.clone());
+    let suspended = state.suspended.get(token)?;
+    if suspended.tenant_id != tenant_id || suspended.certificate_fingerprint != fingerprint {
+        return None;
+    }
+
+    let session = state.suspended.remove(token)?;
+    if session.expires_at <= Instant::now() {
+        return None;
+    }
+    let public_key = session.public_key.as_deref();
+    if state.check_ban(addr, public_key).is_some() || state.find_ban(&session.identity, public_key).is_some() {
+        return None;
+    }
+
+    state.leave_room(addr);
+
+    let new_token = random_token();
+    let client = state.clients.get_mut(&addr)?;
+    client.client_id = session.client_id.clone();
+    client.public_key = session.public_key;
+    client.profile = session.profile;
+    client.verified = true;
+    client.authenticated = true;
+    client.claims = session.claims;
+    client.last_seq = client.last_seq.max(session.last_seq);
+    client.recent_messages.merge(session.recent_messages);
+    client.resume_token = Some(new_token.clone());
+    client.session_tokens.clear();
+
+    if let Some(room) = session.room_id.as_ref().and_then(|room_id| state.rooms.get_mut(room_id)) {
+        room.add_member(addr);
+        // A host whose room was handed over while it was away comes back as a co-host
+        let role = if room.is_host(&session.address) {
+            room.host = Some(addr);
+            Role::Host
+        } else if session.role == Role::Host {
+            Role::Moderator
+        } else {
+            session.role
+        };
+        client.room_id = session.room_id;
+        client.role = role;
+    }
+
+    Some((session.client_id, new_token))
+}
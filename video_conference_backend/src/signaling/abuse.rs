@@ -0,0 +1,245 @@
+use crate::abuse::{self, AbuseReport, Ban, BanKind};
+use crate::admin;
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::models::message::{AdminListPayload, BanPayload, ReportPayload, UnbanPayload};
+use crate::models::SignalMessage;
+use crate::pinning;
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::state::{SharedState, SignalingState};
+use chrono::Utc;
+use std::net::SocketAddr;
+
+const MAX_REPORT_REASON_LEN: usize = 1000;
+// Measured as serialized JSON
+const MAX_REPORT_EVIDENCE_LEN: usize = 16 * 1024;
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+// Participants can only report someone they share a room with
+pub async fn handle_report(
+    payload: ReportPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(reporter) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(store) = &state.abuse else {
+        return send_error(reporter, "reports-unavailable", "This server does not take abuse reports", None).await;
+    };
+    let reason = payload.reason.trim();
+    if reason.is_empty() || reason.len() > MAX_REPORT_REASON_LEN {
+        let message = format!("A reason of 1 to {} bytes is required", MAX_REPORT_REASON_LEN);
+        return send_error(reporter, "invalid-report", &message, Some(&payload.client_id)).await;
+    }
+    let evidence_len = payload.evidence.as_ref().map_or(0, |evidence| evidence.to_string().len());
+    if evidence_len > MAX_REPORT_EVIDENCE_LEN {
+        let message = format!("Evidence may be at most {} bytes", MAX_REPORT_EVIDENCE_LEN);
+        return send_error(reporter, "invalid-report", &message, Some(&payload.client_id)).await;
+    }
+    let Some(reported) = state.room_peers(sender_addr).into_iter().find(|peer| peer.client_id == payload.client_id) else {
+        return send_error(reporter, "target-unknown", "You can only report participants in your room", Some(&payload.client_id)).await;
+    };
+
+    let report = AbuseReport {
+        report_id: uuid::Uuid::new_v4().to_string(),
+        reporter_id: reporter.client_id.clone(),
+        reporter_identity: pinning::identity(reporter),
+        reported_id: reported.client_id.clone(),
+        reported_identity: pinning::identity(reported),
+        reported_public_key: reported.public_key.clone(),
+        room_id: reporter.room_id.clone(),
+        reason: reason.to_string(),
+        evidence: payload.evidence,
+        created_at: Utc::now().timestamp(),
+    };
+    store.save_report(&report).map_err(|e| e.to_string())?;
+    audit::record(
+        AuditEvent::new(AuditKind::AbuseReport)
+            .client(reported)
+            .reason(&report.reason)
+            .detail("report_id", &report.report_id)
+            .detail("reporter_id", &report.reporter_id)
+    );
+    println!("{} reported {} ({})", report.reporter_id, report.reported_id, report.report_id);
+
+    let reply = SignalMessage::server("report-received", serde_json::json!({
+        "report_id": report.report_id,
+        "client_id": report.reported_id,
+    }));
+    send_signal(reporter, &reply).await
+}
+
+// Records the bans and disconnects everyone they cover
+pub async fn handle_ban(
+    payload: BanPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr).cloned() else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        audit::record(AuditEvent::new(AuditKind::AuthFailed).client(&sender).detail("method", "admin-token").detail("signal_type", "ban"));
+        return send_error(&sender, "not-admin", "Banning requires an admin token", None).await;
+    }
+    let Some(store) = &state.abuse else {
+        return send_error(&sender, "bans-unavailable", "This server has no ban list", None).await;
+    };
+
+    let mut targets = Vec::new();
+    if let Some(public_key) = &payload.public_key {
+        targets.push((BanKind::PublicKey, abuse::public_key_value(public_key)));
+    }
+    if let Some(identity) = &payload.identity {
+        targets.push((BanKind::Identity, identity.clone()));
+    }
+    if let Some(client_id) = &payload.client_id {
+        let Some(client) = state.addr_of(client_id).and_then(|addr| state.clients.get(&addr)) else {
+            return send_error(&sender, "target-unknown", "No connected client has that id", Some(client_id)).await;
+        };
+        if let Some(public_key) = &client.public_key {
+            targets.push((BanKind::PublicKey, abuse::public_key_value(public_key)));
+        }
+        targets.push((BanKind::Identity, pinning::identity(client)));
+    }
+    if targets.is_empty() {
+        return send_error(&sender, "invalid-ban", "Name a public key, identity or client to ban", None).await;
+    }
+
+    let created_at = Utc::now().timestamp();
+    for (kind, value) in &targets {
+        let ban = Ban {
+            kind: *kind,
+            value: value.clone(),
+            reason: payload.reason.clone(),
+            banned_by: Some(pinning::identity(&sender)),
+            created_at,
+        };
+        store.ban(&ban).map_err(|e| e.to_string())?;
+        let mut event = AuditEvent::new(AuditKind::Ban)
+            .addr(sender_addr)
+            .detail("kind", kind.as_str())
+            .detail("value", value)
+            .detail("banned_by", &ban.banned_by);
+        if let Some(reason) = &ban.reason {
+            event = event.reason(reason);
+        }
+        audit::record(event);
+    }
+    let disconnected = enforce_bans(&mut state).await;
+    println!("{} added {} bans", sender_addr, targets.len());
+
+    let reply = SignalMessage::server("banned", serde_json::json!({
+        "bans": targets.iter().map(|(kind, value)| serde_json::json!({ "kind": kind, "value": value })).collect::<Vec<_>>(),
+        "disconnected": disconnected,
+    }));
+    send_signal(&sender, &reply).await
+}
+
+pub async fn handle_unban(
+    payload: UnbanPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        audit::record(AuditEvent::new(AuditKind::AuthFailed).client(sender).detail("method", "admin-token").detail("signal_type", "unban"));
+        return send_error(sender, "not-admin", "Lifting bans requires an admin token", None).await;
+    }
+    let Some(store) = &state.abuse else {
+        return send_error(sender, "bans-unavailable", "This server has no ban list", None).await;
+    };
+
+    let mut targets = Vec::new();
+    if let Some(public_key) = &payload.public_key {
+        targets.push((BanKind::PublicKey, abuse::public_key_value(public_key)));
+    }
+    if let Some(identity) = &payload.identity {
+        targets.push((BanKind::Identity, identity.clone()));
+    }
+    let mut lifted = 0;
+    for (kind, value) in &targets {
+        if store.unban(*kind, value).map_err(|e| e.to_string())? {
+            lifted += 1;
+            audit::record(
+                AuditEvent::new(AuditKind::Unban)
+                    .addr(sender_addr)
+                    .detail("kind", kind.as_str())
+                    .detail("value", value)
+            );
+        }
+    }
+
+    let reply = SignalMessage::server("unbanned", serde_json::json!({ "lifted": lifted }));
+    send_signal(sender, &reply).await
+}
+
+pub async fn handle_list_bans(
+    payload: AdminListPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        return send_error(sender, "not-admin", "Listing bans requires an admin token", None).await;
+    }
+    let bans = match &state.abuse {
+        Some(store) => store.bans().map_err(|e| e.to_string())?,
+        None => Vec::new(),
+    };
+
+    let reply = SignalMessage::server("bans", serde_json::json!({ "bans": bans }));
+    send_signal(sender, &reply).await
+}
+
+pub async fn handle_list_reports(
+    payload: AdminListPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        return send_error(sender, "not-admin", "Listing reports requires an admin token", None).await;
+    }
+    let limit = payload.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+    let reports = match &state.abuse {
+        Some(store) => store.reports(limit).map_err(|e| e.to_string())?,
+        None => Vec::new(),
+    };
+
+    let reply = SignalMessage::server("reports", serde_json::json!({ "reports": reports }));
+    send_signal(sender, &reply).await
+}
+
+// Disconnects connected clients whose key or identity is now banned and drops suspended
+// sessions holding either; returns how many clients were disconnected
+async fn enforce_bans(state: &mut SignalingState) -> usize {
+    let suspended = std::mem::take(&mut state.suspended);
+    state.suspended = suspended
+        .into_iter()
+        .filter(|(_, session)| state.find_ban(&session.identity, session.public_key.as_deref()).is_none())
+        .collect();
+
+    let mut disconnected = 0;
+    for client in state.clients.values() {
+        if state.check_ban(client.address, None).is_some() {
+            if let Err(e) = send_error(client, "banned", "You have been banned from this server", None).await {
+                eprintln!("Failed to notify {} of ban: {}", client.address, e);
+            }
+            client.disconnect("banned").await;
+            disconnected += 1;
+        }
+    }
+    disconnected
+}
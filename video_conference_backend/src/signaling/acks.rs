@@ -0,0 +1,137 @@
+use crate::models::{Client, SignalKind, SignalMessage};
+use crate::signaling::handlers::send_signal;
+use crate::signaling::state::SharedState;
+use std::cell::RefCell;
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+// Clients list this in their hello to be told what became of each relayed message
+pub const FEATURE: &str = "acks";
+
+// Signals the server passes on to peers, and so can acknowledge
+const RELAYED_SIGNALS: &[SignalKind] = &[
+    SignalKind::SecureOffer,
+    SignalKind::SecureAnswer,
+    SignalKind::IceCandidate,
+    SignalKind::RenegotiateOffer,
+    SignalKind::RenegotiateAnswer,
+    SignalKind::IceRestart,
+    SignalKind::Sealed,
+    SignalKind::KeyAnnounce,
+];
+
+pub fn is_relayed(kind: SignalKind) -> bool {
+    RELAYED_SIGNALS.contains(&kind)
+}
+
+tokio::task_local! {
+    // What became of the relayed message being dispatched
+    static OUTCOME: RefCell<Option<Delivery>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    // Handed straight to the target's connection
+    Delivered,
+    // The target's outbound buffer was full, so the message waited for room behind others
+    Queued,
+    // Not passed on; the reason is the error code the sender was sent, where there was one
+    Failed(String),
+}
+
+impl Delivery {
+    pub fn status(&self) -> &'static str {
+        match self {
+            Delivery::Delivered => "delivered",
+            Delivery::Queued => "queued",
+            Delivery::Failed(_) => "failed",
+        }
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Delivery::Failed(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    // The outcome of sending one message to several peers: delivered if it reached them all
+    // straight away, queued if it reached them all, failed only if it reached none
+    pub fn combine(deliveries: impl IntoIterator<Item = Delivery>) -> Delivery {
+        let mut combined: Option<Delivery> = None;
+        for delivery in deliveries {
+            combined = Some(match (combined, delivery) {
+                (None, delivery) => delivery,
+                (Some(Delivery::Failed(_)), delivery) | (Some(delivery), Delivery::Failed(_)) => delivery,
+                (Some(Delivery::Queued), _) | (_, Delivery::Queued) => Delivery::Queued,
+                (Some(Delivery::Delivered), Delivery::Delivered) => Delivery::Delivered,
+            });
+        }
+        combined.unwrap_or_else(|| Delivery::Failed("no-recipients".to_string()))
+    }
+}
+
+// Runs `future` while recording what it did with the message, for the sender's ack
+pub async fn track<F: Future>(future: F) -> (F::Output, Option<Delivery>) {
+    OUTCOME
+        .scope(RefCell::new(None), async {
+            let output = future.await;
+            (output, OUTCOME.with(|outcome| outcome.borrow_mut().take()))
+        })
+        .await
+}
+
+// Notes what became of the message being dispatched. The first outcome sticks, since a message
+// is turned away (or passed on) once.
+pub fn record(delivery: Delivery) {
+    let _ = OUTCOME.try_with(|outcome| {
+        outcome.borrow_mut().get_or_insert(delivery);
+    });
+}
+
+// Hands an already encoded relayed message to `client` without waiting while its buffer has room
+pub async fn deliver(client: &Client, message: Message) -> Delivery {
+    match client.sender.try_send(message) {
+        Ok(()) => Delivery::Delivered,
+        Err(TrySendError::Full(message)) => match client.sender.send(message).await {
+            Ok(()) => Delivery::Queued,
+            Err(_) => Delivery::Failed("target-disconnected".to_string()),
+        },
+        Err(TrySendError::Closed(_)) => Delivery::Failed("target-disconnected".to_string()),
+    }
+}
+
+// Acknowledges a relayed message that carried a message id, if its sender asked for acks.
+// Messages that were neither passed on nor turned away with an error were dropped.
+pub async fn send_ack(
+    signal: &SignalMessage,
+    outcome: Option<Delivery>,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(message_id) = &signal.message_id else {
+        return Ok(());
+    };
+    if !is_relayed(SignalKind::of(&signal.signal_type)) {
+        return Ok(());
+    }
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(());
+    };
+    if !client.features.iter().any(|feature| feature == FEATURE) {
+        return Ok(());
+    }
+
+    let outcome = outcome.unwrap_or_else(|| Delivery::Failed("dropped".to_string()));
+    let ack = SignalMessage::server("ack", serde_json::json!({
+        "message_id": message_id,
+        "signal_type": signal.signal_type,
+        "target_id": signal.target_id,
+        "status": outcome.status(),
+        "reason": outcome.reason(),
+    }));
+    send_signal(client, &ack).await
+}
@@ -0,0 +1,157 @@
+use crate::admin;
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::firewall::{self, FilterResult, IpFilter};
+use crate::config;
+use crate::crypto::revocation;
+use crate::models::message::{AdminListPayload, ReloadIpFilterPayload, RevokeKeysPayload};
+use crate::models::SignalMessage;
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::{deflate, keys};
+use crate::signaling::state::SharedState;
+use std::net::SocketAddr;
+use std::path::Path;
+
+// Connections accepted, refused and currently open per country, since startup
+pub async fn handle_region_stats(
+    payload: AdminListPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        return send_error(sender, "not-admin", "Region statistics require an admin token", None).await;
+    }
+    let Some(geo) = &state.geo else {
+        return send_error(sender, "geoip-unavailable", "This server has no GeoIP database", None).await;
+    };
+
+    let reply = SignalMessage::server("region-stats", serde_json::json!({ "regions": geo.stats() }));
+    send_signal(sender, &reply).await
+}
+
+// Messages compressed with permessage-deflate in each direction, and the bytes that saved
+pub async fn handle_compression_stats(
+    payload: AdminListPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        return send_error(sender, "not-admin", "Compression statistics require an admin token", None).await;
+    }
+
+    let reply = SignalMessage::server("compression-stats", serde_json::to_value(deflate::stats())?);
+    send_signal(sender, &reply).await
+}
+
+// Swaps in new IP lists and drops connected clients the new lists no longer permit
+pub async fn handle_reload_ip_filter(
+    payload: ReloadIpFilterPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr).cloned() else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        audit::record(AuditEvent::new(AuditKind::AuthFailed).client(&sender).detail("method", "admin-token").detail("signal_type", "reload-ip-filter"));
+        return send_error(&sender, "not-admin", "Reloading the IP filter requires an admin token", None).await;
+    }
+
+    let filter = match build_filter(&payload) {
+        Ok(filter) => filter,
+        Err(e) => return send_error(&sender, "invalid-ip-filter", &e.to_string(), None).await,
+    };
+    let (allowed, denied) = (filter.allow.len(), filter.deny.len());
+    *state.ip_filter.write().map_err(|e| e.to_string())? = filter.clone();
+
+    let mut disconnected = 0;
+    for client in state.clients.values().filter(|client| !filter.permits(&client.address.ip())) {
+        client.disconnect("address not allowed").await;
+        disconnected += 1;
+    }
+    println!("{} reloaded the IP filter ({} allowed, {} denied ranges)", sender_addr, allowed, denied);
+    audit::record(
+        AuditEvent::new(AuditKind::AdminAction)
+            .client(&sender)
+            .reason("reload-ip-filter")
+            .detail("allow", allowed)
+            .detail("deny", denied)
+            .detail("disconnected", disconnected)
+    );
+
+    let reply = SignalMessage::server("ip-filter-reloaded", serde_json::json!({
+        "allow": allowed,
+        "deny": denied,
+        "disconnected": disconnected,
+    }));
+    send_signal(&sender, &reply).await
+}
+
+// Adds keys to the revocation list (and its file, if configured) and disconnects their holders
+pub async fn handle_revoke_keys(
+    payload: RevokeKeysPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr).cloned() else {
+        return Ok(());
+    };
+    if !admin::is_admin_token(payload.admin_token.as_deref()) {
+        audit::record(AuditEvent::new(AuditKind::AuthFailed).client(&sender).detail("method", "admin-token").detail("signal_type", "revoke-keys"));
+        return send_error(&sender, "not-admin", "Revoking keys requires an admin token", None).await;
+    }
+
+    let added: Vec<Vec<u8>> = payload.public_keys
+        .into_iter()
+        .filter(|public_key| !public_key.is_empty())
+        .filter(|public_key| state.revoked.revoke(public_key.clone()))
+        .collect();
+    if let Some(path) = config::get_revoked_keys_path() {
+        if let Err(e) = revocation::append_to_file(Path::new(&path), &added) {
+            eprintln!("Failed to record revoked keys in {}: {}", path, e);
+        }
+    }
+    let disconnected = keys::enforce_revocations(&mut state).await;
+    println!("{} revoked {} keys", sender_addr, added.len());
+    audit::record(
+        AuditEvent::new(AuditKind::KeyRevoked)
+            .client(&sender)
+            .detail("public_keys", &added)
+            .detail("disconnected", disconnected)
+    );
+
+    let reply = SignalMessage::server("keys-revoked", serde_json::json!({
+        "revoked": added.len(),
+        "disconnected": disconnected,
+    }));
+    send_signal(&sender, &reply).await
+}
+
+fn build_filter(payload: &ReloadIpFilterPayload) -> FilterResult<IpFilter> {
+    let current = IpFilter::from_config()?;
+    let parse = |entries: &Vec<String>| {
+        entries
+            .iter()
+            .map(|entry| firewall::parse_net(entry))
+            .collect::<FilterResult<Vec<_>>>()
+    };
+
+    let allow = match &payload.allow {
+        Some(entries) => parse(entries)?,
+        None => current.allow,
+    };
+    let deny = match &payload.deny {
+        Some(entries) => parse(entries)?,
+        None => current.deny,
+    };
+    Ok(IpFilter::new(allow, deny))
+}
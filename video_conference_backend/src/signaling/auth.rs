@@ -0,0 +1,343 @@
+use crate::abuse;
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::auth;
+use crate::config;
+use crate::crypto;
+use crate::models::message::{AuthenticatePayload, ChallengeResponsePayload};
+use crate::models::{SignalKind, SignalMessage};
+use crate::pinning;
+use crate::sessions;
+use crate::signaling::{calls, correlation};
+use crate::signaling::handlers::{advance_sequence, send_error, send_session_token, send_signal};
+use crate::signaling::limits;
+use crate::signaling::state::SharedState;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::net::SocketAddr;
+
+// Signals a client may send before it has authenticated
+const UNAUTHENTICATED_SIGNALS: &[SignalKind] = &[
+    SignalKind::Hello,
+    SignalKind::Authenticate,
+    SignalKind::ChallengeResponse,
+    SignalKind::ResumeSession,
+    SignalKind::WebauthnRegisterBegin,
+    SignalKind::WebauthnRegister,
+    SignalKind::WebauthnLoginBegin,
+    SignalKind::WebauthnLogin,
+];
+
+// Signals that carry their own proof, so they are taken without a session token
+const SELF_AUTHENTICATING_SIGNALS: &[SignalKind] = &[
+    SignalKind::SecureOffer,
+    SignalKind::SecureAnswer,
+    SignalKind::RotateKey,
+];
+
+// Signals that stand in for a verified offer or answer, so they need a session token whatever
+// REQUIRE_SESSION_TOKEN says
+const SESSION_BOUND_SIGNALS: &[SignalKind] = &[
+    SignalKind::RenegotiateOffer,
+    SignalKind::RenegotiateAnswer,
+    SignalKind::IceRestart,
+];
+
+// Signals whose seq is signed, so their handlers check it once the signature has been verified
+const SIGNED_SEQUENCE_SIGNALS: &[SignalKind] = &[
+    SignalKind::SecureOffer,
+    SignalKind::SecureAnswer,
+    SignalKind::IceCandidate,
+];
+
+// The exact bytes a client signs to answer `challenge`
+pub fn challenge_message(challenge: &str, client_id: &str) -> Vec<u8> {
+    crypto::canonicalize(&serde_json::json!({
+        "challenge": challenge,
+        "client_id": client_id,
+    }))
+    .into_bytes()
+}
+
+// First message on every connection, so clients can check the signatures on everything after it
+pub async fn send_server_identity(
+    addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(signer) = crypto::server_identity::server_signer() else {
+        return Ok(());
+    };
+    let public_key = signer.public_key();
+
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(());
+    };
+    let message = SignalMessage::server("server-identity", serde_json::json!({
+        "public_key": STANDARD.encode(&public_key),
+        "key_id": crypto::server_identity::key_id(&public_key),
+        "algorithm": signer.algorithm(),
+    }));
+    send_signal(client, &message).await
+}
+
+// Sent right after the WebSocket handshake
+pub async fn send_challenge(
+    addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get_mut(&addr) else {
+        return Ok(());
+    };
+
+    let challenge = sessions::random_token();
+    client.challenge = Some(challenge.clone());
+
+    let message = SignalMessage::server("challenge", serde_json::json!({
+        "challenge": challenge,
+        "client_id": client.client_id,
+        "required": config::get_require_challenge(),
+    }));
+    send_signal(client, &message).await
+}
+
+// False (after telling the client) when the signal has to wait for the client to answer the
+// challenge or present a token
+pub async fn check_authenticated(
+    kind: SignalKind,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if UNAUTHENTICATED_SIGNALS.contains(&kind) {
+        return Ok(true);
+    }
+
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(false);
+    };
+
+    if config::get_require_challenge() && !client.authenticated {
+        send_error(client, "unauthenticated", "Answer the connection challenge first", None).await?;
+        return Ok(false);
+    }
+    if config::get_jwt_required() && client.claims.is_none() {
+        send_error(client, "unauthenticated", "Present a valid token first", None).await?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+// False (after telling the client) when a verified client's message lacks a live session token,
+// or any client's message carries one that is not its own. Tokens past half their lifetime are
+// replaced as they are used.
+pub async fn check_session_token(
+    signal: &SignalMessage,
+    kind: SignalKind,
+    session_token: Option<&str>,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if UNAUTHENTICATED_SIGNALS.contains(&kind) || SELF_AUTHENTICATING_SIGNALS.contains(&kind) {
+        return Ok(true);
+    }
+
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(false);
+    };
+    let required = SESSION_BOUND_SIGNALS.contains(&kind) || (client.verified && config::get_require_session_token());
+    if session_token.is_none() && !required {
+        return Ok(true);
+    }
+
+    match sessions::check_session_token(client, session_token, config::get_session_token_ttl()) {
+        Ok(false) => Ok(true),
+        Ok(true) => {
+            send_session_token(&mut state, addr).await?;
+            Ok(true)
+        }
+        Err(error) => {
+            audit::record(
+                AuditEvent::new(AuditKind::AuthFailed)
+                    .client(client)
+                    .reason(error.message())
+                    .detail("method", "session-token")
+                    .detail("signal_type", &signal.signal_type)
+            );
+            send_error(client, error.code(), error.message(), None).await?;
+            Ok(false)
+        }
+    }
+}
+
+// Validates a JWT from the handshake or an `authenticate` message and attaches its claims
+pub async fn authenticate_token(
+    token: &str,
+    addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    // JWKS lookups may hit the network, so validation runs outside the lock
+    let result = auth::validate_token(token).await;
+
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(());
+    };
+
+    // Under mutual TLS the token has to name the same user as the certificate
+    let result = result.and_then(|claims| match &client.certificate {
+        Some(certificate) if certificate.subject != claims.sub => Err(format!(
+            "token subject {} does not match certificate subject {}",
+            claims.sub, certificate.subject
+        )),
+        _ => Ok(claims),
+    });
+    // A key bound before the token arrived has to match the one pinned to the token's user
+    let result = result.and_then(|claims| {
+        if let (Some(key_pins), Some(public_key)) = (&state.key_pins, &client.public_key) {
+            let mut user = client.clone();
+            user.claims = Some(claims.clone());
+            pinning::check_pin(key_pins.as_ref(), &pinning::identity(&user), public_key)
+                .map_err(|error| error.message().to_string())?;
+        }
+        Ok(claims)
+    });
+    let result = result.and_then(|claims| match &state.abuse {
+        Some(store) => {
+            let mut user = client.clone();
+            user.claims = Some(claims.clone());
+            match abuse::find_ban(store.as_ref(), &pinning::identity(&user), user.public_key.as_deref()) {
+                Ok(Some(_)) => Err("This user is banned".to_string()),
+                Ok(None) => Ok(claims),
+                Err(e) => {
+                    eprintln!("[ERROR] Ban lookup failed for {}: {}", addr, e);
+                    Ok(claims)
+                }
+            }
+        }
+        None => Ok(claims),
+    });
+
+    let Some(client) = state.clients.get_mut(&addr) else {
+        return Ok(());
+    };
+
+    match result {
+        Ok(claims) => {
+            let reply = SignalMessage::server("token-accepted", serde_json::json!({
+                "user_id": claims.sub,
+                "role": claims.role,
+                "rooms": claims.rooms,
+            }));
+            client.claims = Some(claims);
+            send_signal(client, &reply).await?;
+            calls::signed_in(&mut state, addr).await
+        }
+        Err(reason) => {
+            eprintln!("Rejected token from {}: {}{}", addr, reason, correlation::tag());
+            audit::record(AuditEvent::new(AuditKind::AuthFailed).client(client).reason(&reason).detail("method", "token"));
+            send_error(client, "invalid-token", &reason, None).await?;
+            if config::get_jwt_required() {
+                client.disconnect("invalid token").await;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub async fn handle_authenticate(
+    payload: AuthenticatePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    authenticate_token(&payload.token, sender_addr, state).await
+}
+
+// Binds the connection to the key that signed the challenge; later offers must use the same key
+pub async fn handle_challenge_response(
+    payload: ChallengeResponsePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (verifier, message) = {
+        let mut state = state.lock().await;
+        let verifier = state.async_verifier();
+        let Some(client) = state.clients.get_mut(&sender_addr) else {
+            return Ok(());
+        };
+
+        // Each challenge can be answered once
+        let Some(challenge) = client.challenge.take() else {
+            return send_error(client, "no-challenge", "There is no outstanding challenge for this connection", None).await;
+        };
+        (verifier, challenge_message(&challenge, &client.client_id))
+    };
+
+    // Verified without the state lock so other connections are not held up behind it
+    let verified = verifier
+        .verify(crypto::VerificationRequest::new(payload.algorithm, &message, &payload.signature, &payload.public_key))
+        .await;
+
+    let mut state = state.lock().await;
+    // A connection already bound to a key (by an earlier offer, say) cannot be moved to another one
+    let rebinding = state.clients.get(&sender_addr)
+        .and_then(|client| client.public_key.as_ref())
+        .is_some_and(|bound| *bound != payload.public_key);
+    let checked = if rebinding {
+        Err("This connection is bound to a different public key".to_string())
+    } else {
+        verified.map_err(|error| error.to_string())
+    };
+    // Only a key that proved itself gets pinned
+    let result = checked
+        .and_then(|_| {
+            if state.revoked.is_revoked(&payload.public_key) {
+                return Err("This public key has been revoked".to_string());
+            }
+            if state.check_ban(sender_addr, Some(&payload.public_key)).is_some() {
+                return Err("This key or identity is banned".to_string());
+            }
+            Ok(())
+        })
+        .and_then(|_| {
+            state.check_key_pin(sender_addr, &payload.public_key)
+                .map_err(|error| error.message().to_string())
+        });
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    if let Err(reason) = result {
+        eprintln!("Challenge failed for {}: {}{}", sender_addr, reason, correlation::tag());
+        audit::record(AuditEvent::new(AuditKind::AuthFailed).client(client).reason(&reason).detail("method", "challenge"));
+        send_error(client, "challenge-failed", &reason, None).await?;
+        if config::get_require_challenge() {
+            client.disconnect("challenge failed").await;
+        }
+        limits::record_verification_failure(&mut state, sender_addr).await;
+        return Ok(());
+    }
+
+    client.public_key = Some(payload.public_key);
+    client.authenticated = true;
+
+    let reply = SignalMessage::server("authenticated", serde_json::json!({
+        "client_id": client.client_id,
+    }));
+    send_signal(client, &reply).await
+}
+
+// False (after telling the client) when a message's seq does not move past the last one accepted
+// from its sender. Signed offers, answers and candidates are checked by their handlers instead.
+pub async fn check_sequence(
+    signal: &SignalMessage,
+    kind: SignalKind,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if SIGNED_SEQUENCE_SIGNALS.contains(&kind) {
+        return Ok(true);
+    }
+    advance_sequence(&mut *state.lock().await, addr, signal).await
+}
@@ -0,0 +1,93 @@
+use crate::config;
+use crate::models::{Client, SignalKind, SignalMessage, WireEncoding};
+use crate::signaling::acks::{self, Delivery};
+use crate::signaling::handlers::encode;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+// Clients list this in their hello to take trickled candidates in `ice-candidates` batches
+pub const FEATURE: &str = "ice-batching";
+
+// Relayed candidates waiting to go out to one client together, as they would have been sent
+// on their own
+#[derive(Debug, Default)]
+pub struct IceBatch {
+    candidates: Vec<Value>,
+}
+
+impl IceBatch {
+    // Drops `sender_id`'s waiting candidates and says how many there were
+    pub fn discard_from(&mut self, sender_id: &str) -> usize {
+        let before = self.candidates.len();
+        self.candidates.retain(|candidate| candidate["sender_id"] != sender_id);
+        before - self.candidates.len()
+    }
+
+    // One `ice-candidates` message with everything waiting, oldest first
+    fn take(&mut self, encoding: WireEncoding) -> Option<Result<Message, Box<dyn std::error::Error>>> {
+        if self.candidates.is_empty() {
+            return None;
+        }
+        let candidates = std::mem::take(&mut self.candidates);
+        let batch = SignalMessage::server("ice-candidates", serde_json::json!({ "candidates": candidates }));
+        Some(encode(&batch, encoding))
+    }
+}
+
+pub fn is_candidate(encoding: WireEncoding, message: &Message) -> bool {
+    parse_candidate(encoding, message).is_some()
+}
+
+fn parse_candidate(encoding: WireEncoding, message: &Message) -> Option<Value> {
+    let signal = encoding.decode(message).ok()?;
+    (SignalKind::of(&signal.signal_type) == SignalKind::IceCandidate).then(|| serde_json::to_value(signal).ok()).flatten()
+}
+
+// Hands a relayed message the reorder buffer let out to `client`. For clients that batch, a
+// candidate waits up to ICE_BATCH_INTERVAL_MS to go out with any others; anything else sends
+// what is waiting first, so it is not overtaken.
+pub async fn deliver(client: &Client, message: Message) -> Delivery {
+    let interval = config::get_ice_batch_interval();
+    let batching = !interval.is_zero() && client.features.iter().any(|feature| feature == FEATURE);
+    if !batching {
+        return acks::deliver(client, message).await;
+    }
+
+    if let Some(candidate) = parse_candidate(client.encoding, &message) {
+        let Ok(mut batch) = client.ice_batch.lock() else {
+            return acks::deliver(client, message).await;
+        };
+        batch.candidates.push(candidate);
+        if batch.candidates.len() == 1 {
+            let pending = Arc::clone(&client.ice_batch);
+            let sender = client.sender.clone();
+            let address = client.address;
+            let encoding = client.encoding;
+            tokio::spawn(async move {
+                tokio::time::sleep(interval).await;
+                if let Some(message) = take(&pending, encoding) {
+                    if let Err(e) = sender.send(message).await {
+                        eprintln!("Send error to {}: {}", address, e);
+                    }
+                }
+            });
+        }
+        return Delivery::Queued;
+    }
+
+    if let Some(waiting) = take(&client.ice_batch, client.encoding) {
+        acks::deliver(client, waiting).await;
+    }
+    acks::deliver(client, message).await
+}
+
+fn take(batch: &Mutex<IceBatch>, encoding: WireEncoding) -> Option<Message> {
+    match batch.lock().ok()?.take(encoding)? {
+        Ok(message) => Some(message),
+        Err(e) => {
+            eprintln!("Failed to encode ICE candidate batch: {}", e);
+            None
+        }
+    }
+}
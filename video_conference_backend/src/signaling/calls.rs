@@ -0,0 +1,580 @@
+use crate::calls::{CallOutcome, CallRecord};
+use crate::config;
+use crate::models::message::{CallHistoryPayload, CallInvitePayload, CallPayload};
+use crate::models::{Client, SignalMessage};
+use crate::push::{CallPush, PushEvent};
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::state::{SharedState, SignalingState};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const MAX_CALL_ID_CHARS: usize = 64;
+
+// An invite still ringing on the callee's devices
+#[derive(Debug, Clone)]
+pub struct PendingCall {
+    pub caller: SocketAddr,
+    // Every connection the callee was signed in on when the invite went out
+    pub devices: Vec<SocketAddr>,
+    pub invited_at: Instant,
+    // Whether the callee was pushed for having nothing connected
+    pub pushed: bool,
+    // Names the callee and the room the caller asks it to join, if the call is to happen in one
+    pub record: CallRecord,
+}
+
+// Unanswered invites, keyed by the call id the caller chose, and the direct calls in progress
+#[derive(Debug, Default)]
+pub struct Calls {
+    pending: HashMap<String, PendingCall>,
+    // Both ends of each direct call, each mapped to the call's record and the other end
+    direct: HashMap<SocketAddr, (CallRecord, SocketAddr)>,
+}
+
+impl Calls {
+    // False when the call id is already ringing
+    pub fn invite(&mut self, call_id: &str, call: PendingCall) -> bool {
+        if self.pending.contains_key(call_id) {
+            return false;
+        }
+        self.pending.insert(call_id.to_string(), call);
+        true
+    }
+
+    pub fn get(&self, call_id: &str) -> Option<&PendingCall> {
+        self.pending.get(call_id)
+    }
+
+    pub fn remove(&mut self, call_id: &str) -> Option<PendingCall> {
+        self.pending.remove(call_id)
+    }
+
+    // Takes out the invites that have rung for `ttl` without an answer
+    pub fn expire(&mut self, now: Instant, ttl: Duration) -> Vec<(String, PendingCall)> {
+        let expired: Vec<String> = self.pending
+            .iter()
+            .filter(|(_, call)| now.saturating_duration_since(call.invited_at) >= ttl)
+            .map(|(call_id, _)| call_id.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|call_id| self.pending.remove(&call_id).map(|call| (call_id, call)))
+            .collect()
+    }
+
+    pub fn pair(&mut self, record: CallRecord, a: SocketAddr, b: SocketAddr) {
+        self.direct.insert(a, (record.clone(), b));
+        self.direct.insert(b, (record, a));
+    }
+
+    // The other end of `addr`'s direct call, if it is in one
+    pub fn direct_peer(&self, addr: SocketAddr) -> Option<SocketAddr> {
+        self.direct.get(&addr).map(|(_, peer)| *peer)
+    }
+
+    // Ends `addr`'s direct call, returning its record and the other end
+    pub fn unpair(&mut self, addr: SocketAddr) -> Option<(CallRecord, SocketAddr)> {
+        let (record, peer) = self.direct.remove(&addr)?;
+        self.direct.remove(&peer);
+        Some((record, peer))
+    }
+
+    // Rings `addr`, just signed in as `user`, for the calls still ringing for that user within
+    // the tenant, returning their ids. Calls it placed itself are left alone.
+    pub fn ring_device(&mut self, tenant_id: Option<&str>, user: &str, addr: SocketAddr) -> Vec<String> {
+        self.pending
+            .iter_mut()
+            .filter(|(_, call)| call.record.tenant_id.as_deref() == tenant_id && call.record.callee == user)
+            .filter(|(_, call)| call.caller != addr && !call.devices.contains(&addr))
+            .map(|(call_id, call)| {
+                call.devices.push(addr);
+                call_id.clone()
+            })
+            .collect()
+    }
+
+    // Call ids `addr` placed or is being rung for
+    pub fn involving(&self, addr: SocketAddr) -> Vec<String> {
+        self.pending
+            .iter()
+            .filter(|(_, call)| call.caller == addr || call.devices.contains(&addr))
+            .map(|(call_id, _)| call_id.clone())
+            .collect()
+    }
+}
+
+// The user a client is signed in as, through a token or WebAuthn; calls address users by it
+pub fn user_id(client: &Client) -> Option<&str> {
+    client
+        .claims
+        .as_ref()
+        .map(|claims| claims.sub.as_str())
+        .or(client.webauthn_user.as_deref())
+}
+
+// Rings every connection the callee is signed in on, within the caller's tenant. Only a caller
+// that is signed in and verified may ring anyone, so the callee always knows who is calling. The
+// caller's own connection is never rung, even when it is signed in as the callee. Invites
+// without a room are for direct calls, which the caller cannot place from inside a room. A
+// callee with nothing connected is pushed, when a notifier is set, and rings on each connection
+// it signs in on while the invite lasts; otherwise the invite times out as a missed call.
+pub async fn handle_call_invite(
+    payload: CallInvitePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(caller) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(caller_user) = user_id(caller) else {
+        return send_error(caller, "not-signed-in", "Sign in before placing a call", None).await;
+    };
+    if !caller.verified {
+        return send_error(caller, "unverified-sender", "Only verified clients can place calls", None).await;
+    }
+    if payload.call_id.chars().count() > MAX_CALL_ID_CHARS {
+        let message = format!("Call ids are limited to {} characters", MAX_CALL_ID_CHARS);
+        return send_error(caller, "invalid-call", &message, None).await;
+    }
+    if payload.room_id.is_none() {
+        if let Some((code, message)) = direct_conflict(&state, sender_addr) {
+            return send_error(caller, code, message, None).await;
+        }
+    }
+    let devices: Vec<SocketAddr> = state.clients
+        .values()
+        .filter(|client| client.address != sender_addr && client.tenant_id() == caller.tenant_id())
+        .filter(|client| user_id(client) == Some(payload.callee.as_str()))
+        .map(|client| client.address)
+        .collect();
+
+    let record = CallRecord {
+        record_id: uuid::Uuid::new_v4().to_string(),
+        call_id: payload.call_id.clone(),
+        tenant_id: caller.tenant_id().map(str::to_string),
+        caller: caller_user.to_string(),
+        callee: payload.callee,
+        room_id: payload.room_id,
+        outcome: CallOutcome::Ringing,
+        started_at: Utc::now().timestamp_millis(),
+        answered_at: None,
+        ended_at: None,
+    };
+    let call = PendingCall {
+        caller: sender_addr,
+        devices: devices.clone(),
+        invited_at: Instant::now(),
+        pushed: devices.is_empty() && state.notifier.is_some(),
+        record: record.clone(),
+    };
+    let invite = invite_message(&state, &call);
+    if !state.calls.invite(&payload.call_id, call.clone()) {
+        let Some(caller) = state.clients.get(&sender_addr) else {
+            return Ok(());
+        };
+        return send_error(caller, "invalid-call", "That call id is already in use", None).await;
+    }
+    // Only once the invite stands, so a refused one never wakes the callee
+    if call.pushed {
+        push(&state, &call, PushEvent::Incoming);
+    }
+    save_record(&state, &record);
+    for device in devices.iter().filter_map(|device| state.clients.get(device)) {
+        send_signal(device, &invite).await?;
+    }
+    Ok(())
+}
+
+// A callee device telling the caller it is ringing; the call keeps ringing elsewhere too
+pub async fn handle_call_ringing(
+    payload: CallPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(call) = rung_call(&state, &payload.call_id, sender_addr).await? else {
+        return Ok(());
+    };
+    let (Some(device), Some(caller)) = (state.clients.get(&sender_addr), state.clients.get(&call.caller)) else {
+        return Ok(());
+    };
+    let ringing = SignalMessage::server("call-ringing", serde_json::json!({
+        "call_id": payload.call_id,
+        "client_id": device.client_id,
+    }));
+    send_signal(caller, &ringing).await
+}
+
+// The first device to answer takes the call; the others stop ringing. A device declining
+// declines the call for all of them. An invite without a room becomes a direct call: the two
+// ends are paired, and signals between them are verified and relayed as though they shared a
+// room of their own.
+pub async fn handle_call_answer(
+    payload: CallPayload,
+    accepted: bool,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(call) = rung_call(&state, &payload.call_id, sender_addr).await? else {
+        return Ok(());
+    };
+    if accepted && call.record.room_id.is_none() {
+        let Some(device) = state.clients.get(&sender_addr) else {
+            return Ok(());
+        };
+        if let Some((code, message)) = direct_conflict(&state, sender_addr) {
+            return send_error(device, code, message, None).await;
+        }
+        // The caller may have had another of its direct invites answered, or joined a room
+        if direct_conflict(&state, call.caller).is_some() {
+            return send_error(device, "caller-busy", "The caller is in another call or a room", None).await;
+        }
+    }
+    let Some(mut call) = state.calls.remove(&payload.call_id) else {
+        return Ok(());
+    };
+    withdraw_push(&state, &call);
+    let Some(device) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+
+    let answer = if accepted {
+        SignalMessage::server("call-accept", serde_json::json!({
+            "call_id": payload.call_id,
+            "client_id": device.client_id,
+            "user_id": call.record.callee,
+            "room_id": call.record.room_id,
+            "direct": call.record.room_id.is_none(),
+        }))
+    } else {
+        SignalMessage::server("call-reject", serde_json::json!({
+            "call_id": payload.call_id,
+            "client_id": device.client_id,
+            "reason": payload.reason,
+        }))
+    };
+    if let Some(caller) = state.clients.get(&call.caller) {
+        send_signal(caller, &answer).await?;
+    }
+    // A call in a room ends with the room, which keeps no record of it; a direct call's record
+    // is closed when either end hangs up
+    if accepted {
+        call.record.outcome = CallOutcome::Answered;
+        call.record.answered_at = Some(Utc::now().timestamp_millis());
+        save_record(&state, &call.record);
+        if call.record.room_id.is_none() {
+            state.calls.pair(call.record.clone(), call.caller, sender_addr);
+        }
+    } else {
+        end_record(&state, &mut call.record, CallOutcome::Rejected);
+    }
+    let reason = if accepted { "answered-elsewhere" } else { "rejected-elsewhere" };
+    let others: Vec<SocketAddr> = call.devices.iter().copied().filter(|device| *device != sender_addr).collect();
+    cancel_ringing(&state, &payload.call_id, &others, reason).await
+}
+
+// The caller hanging up before anyone answered
+pub async fn handle_call_cancel(
+    payload: CallPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let placed = state.calls.get(&payload.call_id).is_some_and(|call| call.caller == sender_addr);
+    if !placed {
+        return unknown_call(&state, sender_addr, &payload.call_id).await;
+    }
+    let Some(mut call) = state.calls.remove(&payload.call_id) else {
+        return Ok(());
+    };
+    end_record(&state, &mut call.record, CallOutcome::Cancelled);
+    withdraw_push(&state, &call);
+    cancel_ringing(&state, &payload.call_id, &call.devices, "cancelled").await
+}
+
+// Either end hanging up a direct call ends it for both
+pub async fn handle_call_hangup(
+    payload: CallPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let in_call = state.calls.direct.get(&sender_addr).is_some_and(|(record, _)| record.call_id == payload.call_id);
+    if !in_call {
+        return unknown_call(&state, sender_addr, &payload.call_id).await;
+    }
+    hang_up(&mut state, sender_addr, "hangup").await
+}
+
+// Ends `addr`'s direct call, if it is in one, and tells the other end why
+pub async fn hang_up(
+    state: &mut SignalingState,
+    addr: SocketAddr,
+    reason: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some((mut record, peer)) = state.calls.unpair(addr) else {
+        return Ok(());
+    };
+    record.ended_at = Some(Utc::now().timestamp_millis());
+    save_record(state, &record);
+    let Some(peer) = state.clients.get(&peer) else {
+        return Ok(());
+    };
+    let ended = SignalMessage::server("call-ended", serde_json::json!({
+        "call_id": record.call_id,
+        "reason": reason,
+    }));
+    send_signal(peer, &ended).await
+}
+
+// Stops invites that rang out: the caller hears the call timed out and the devices stop ringing.
+// An invite no device was left ringing for is a missed call.
+pub async fn expire_calls(state: &mut SignalingState) {
+    let expired = state.calls.expire(Instant::now(), config::get_call_ring_timeout());
+    for (call_id, mut call) in expired {
+        let outcome = if call.devices.is_empty() { CallOutcome::Missed } else { CallOutcome::Unanswered };
+        end_record(state, &mut call.record, outcome);
+        withdraw_push(state, &call);
+        if let Some(caller) = state.clients.get(&call.caller) {
+            let timeout = SignalMessage::server("call-timeout", serde_json::json!({ "call_id": call_id }));
+            if let Err(e) = send_signal(caller, &timeout).await {
+                eprintln!("Failed to send call timeout to {}: {}", call.caller, e);
+            }
+        }
+        if let Err(e) = cancel_ringing(state, &call_id, &call.devices, "timeout").await {
+            eprintln!("Failed to cancel call {}: {}", call_id, e);
+        }
+        // The callee may have signed in on a connection the invite never rang
+        if outcome == CallOutcome::Missed {
+            if let Err(e) = send_missed(state, call.record.tenant_id.as_deref(), &call.record.callee).await {
+                eprintln!("Failed to send missed call {}: {}", call_id, e);
+            }
+        }
+    }
+}
+
+pub async fn run_call_timer(state: SharedState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+        expire_calls(&mut *state.lock().await).await;
+    }
+}
+
+// Ends the calls `addr` is part of as it disconnects. A caller leaving cancels its invites; a
+// device leaving stops being rung, and when it was the last one the invite goes on until it
+// times out as missed. A direct call ends for the other side too.
+pub async fn drop_calls(state: &mut SignalingState, addr: SocketAddr) {
+    if let Err(e) = hang_up(state, addr, "disconnected").await {
+        eprintln!("Failed to end direct call of {}: {}", addr, e);
+    }
+    for call_id in state.calls.involving(addr) {
+        let Some(mut call) = state.calls.remove(&call_id) else {
+            continue;
+        };
+        if call.caller == addr {
+            end_record(state, &mut call.record, CallOutcome::Cancelled);
+            withdraw_push(state, &call);
+            if let Err(e) = cancel_ringing(state, &call_id, &call.devices, "cancelled").await {
+                eprintln!("Failed to end call {}: {}", call_id, e);
+            }
+        } else {
+            call.devices.retain(|device| *device != addr);
+            state.calls.invite(&call_id, call);
+        }
+    }
+}
+
+// Pages back through the calls the signed-in user placed or received, newest first
+pub async fn handle_call_history(
+    payload: CallHistoryPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(user) = user_id(client) else {
+        return send_error(client, "not-signed-in", "Sign in to read your call history", None).await;
+    };
+    let Some(store) = &state.call_store else {
+        return send_error(client, "call-history-unavailable", "This server does not keep call history", None).await;
+    };
+
+    let page_size = config::get_call_history_page_size();
+    let limit = payload.limit.unwrap_or(page_size).clamp(1, page_size);
+    match store.history(client.tenant_id(), user, limit) {
+        Ok(calls) => {
+            let history = SignalMessage::server("call-history", serde_json::json!({ "calls": calls }));
+            send_signal(client, &history).await
+        }
+        Err(e) => {
+            eprintln!("Failed to read call history of {}: {}", user, e);
+            send_error(client, "call-history-unavailable", "Call history could not be read", None).await
+        }
+    }
+}
+
+// Rings a client that has just signed in for the calls still ringing for its user, which is how
+// a device woken by a push takes the call, then tells it about the calls it missed while offline
+pub async fn signed_in(
+    state: &mut SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(());
+    };
+    let Some(user) = user_id(client).map(str::to_string) else {
+        return Ok(());
+    };
+    let tenant_id = client.tenant_id().map(str::to_string);
+
+    for call_id in state.calls.ring_device(tenant_id.as_deref(), &user, addr) {
+        let (Some(call), Some(client)) = (state.calls.get(&call_id), state.clients.get(&addr)) else {
+            continue;
+        };
+        send_signal(client, &invite_message(state, call)).await?;
+    }
+    send_missed(state, tenant_id.as_deref(), &user).await
+}
+
+// Sends each of `user_id`'s connections a `missed-call` per call it has not been told about.
+// With none connected the calls wait for its next sign-in.
+async fn send_missed(
+    state: &SignalingState,
+    tenant_id: Option<&str>,
+    user: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(store) = &state.call_store else {
+        return Ok(());
+    };
+    let devices: Vec<&Client> = state.clients
+        .values()
+        .filter(|client| client.tenant_id() == tenant_id && user_id(client) == Some(user))
+        .collect();
+    if devices.is_empty() {
+        return Ok(());
+    }
+    for record in store.take_missed(tenant_id, user).map_err(|e| e.to_string())? {
+        let missed = SignalMessage::server("missed-call", serde_json::to_value(&record)?);
+        for device in &devices {
+            send_signal(device, &missed).await?;
+        }
+    }
+    Ok(())
+}
+
+// The call-invite a device rings with, good for what is left of the ring timeout
+fn invite_message(state: &SignalingState, call: &PendingCall) -> SignalMessage {
+    let caller = state.clients.get(&call.caller);
+    let remaining = config::get_call_ring_timeout().saturating_sub(call.invited_at.elapsed());
+    SignalMessage::server("call-invite", serde_json::json!({
+        "call_id": call.record.call_id,
+        "caller_id": caller.map(|caller| caller.client_id.as_str()),
+        "caller_user": caller.and_then(user_id),
+        "room_id": call.record.room_id,
+        "expires_in_ms": remaining.as_millis() as u64,
+    }))
+}
+
+// Pushes are fire and forget: a notifier that fails costs the callee the call, not the caller
+fn push(state: &SignalingState, call: &PendingCall, event: PushEvent) {
+    let Some(notifier) = &state.notifier else {
+        return;
+    };
+    let push = CallPush {
+        event,
+        call_id: call.record.call_id.clone(),
+        tenant_id: call.record.tenant_id.clone(),
+        callee: call.record.callee.clone(),
+        caller: call.record.caller.clone(),
+        caller_id: state.clients.get(&call.caller).map(|caller| caller.client_id.clone()).unwrap_or_default(),
+        room_id: call.record.room_id.clone(),
+        expires_at: call.record.started_at + config::get_call_ring_timeout().as_millis() as i64,
+    };
+    if let Err(e) = notifier.notify(&push) {
+        eprintln!("Failed to push call {} to {}: {}", push.call_id, push.callee, e);
+    }
+}
+
+// Lets a pushed callee's devices drop the notification once the call stops ringing
+fn withdraw_push(state: &SignalingState, call: &PendingCall) {
+    if call.pushed {
+        push(state, call, PushEvent::Withdrawn);
+    }
+}
+
+// The record has already been acted on, so a storage failure only costs the history
+fn save_record(state: &SignalingState, record: &CallRecord) {
+    if let Some(store) = &state.call_store {
+        if let Err(e) = store.save(record) {
+            eprintln!("Failed to store call record {}: {}", record.record_id, e);
+        }
+    }
+}
+
+fn end_record(state: &SignalingState, record: &mut CallRecord, outcome: CallOutcome) {
+    record.outcome = outcome;
+    record.ended_at = Some(Utc::now().timestamp_millis());
+    save_record(state, record);
+}
+
+// Why `addr` cannot be in a direct call right now: its room's peers would take the place of the
+// other end, and a client is in one direct call at a time
+fn direct_conflict(state: &SignalingState, addr: SocketAddr) -> Option<(&'static str, &'static str)> {
+    if state.client_room(addr).is_some() {
+        return Some(("in-room", "Leave your room before a direct call"));
+    }
+    if state.calls.direct_peer(addr).is_some() {
+        return Some(("in-call", "Hang up your current call first"));
+    }
+    None
+}
+
+async fn cancel_ringing(
+    state: &SignalingState,
+    call_id: &str,
+    devices: &[SocketAddr],
+    reason: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cancel = SignalMessage::server("call-cancel", serde_json::json!({
+        "call_id": call_id,
+        "reason": reason,
+    }));
+    for device in devices.iter().filter_map(|device| state.clients.get(device)) {
+        send_signal(device, &cancel).await?;
+    }
+    Ok(())
+}
+
+// The call `addr` is being rung for; an error goes back to anyone else naming it
+async fn rung_call(
+    state: &SignalingState,
+    call_id: &str,
+    addr: SocketAddr
+) -> Result<Option<PendingCall>, Box<dyn std::error::Error>> {
+    match state.calls.get(call_id) {
+        Some(call) if call.devices.contains(&addr) => Ok(Some(call.clone())),
+        _ => {
+            unknown_call(state, addr, call_id).await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn unknown_call(
+    state: &SignalingState,
+    addr: SocketAddr,
+    call_id: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    match state.clients.get(&addr) {
+        Some(client) => send_error(client, "unknown-call", &format!("No call {} is ringing", call_id), None).await,
+        None => Ok(()),
+    }
+}
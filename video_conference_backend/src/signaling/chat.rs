@@ -0,0 +1,201 @@
+use crate::chat::{self, ChatMessage, ChatStore};
+use crate::config;
+use crate::models::message::{ChatHistoryPayload, ChatPayload};
+use crate::models::SignalMessage;
+use crate::signaling::acks::{self, Delivery};
+use crate::signaling::handlers::{send_error, send_signal, send_to_room};
+use crate::signaling::state::{SharedState, SignalingState};
+use crate::storage::StoreResult;
+use chrono::Utc;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+// Chat goes to everyone in the sender's room, the sender included, as the server stamped it,
+// so every member sees the same id, time and order
+pub async fn handle_chat(
+    payload: ChatPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before chatting", None).await;
+    };
+    if !room.config.chat.allows(client.role) {
+        return send_error(client, "chat-not-allowed", "Your role cannot send chat messages in this room", None).await;
+    }
+    let text = match chat::check_text(&payload.text, config::get_chat_max_length()) {
+        Ok(text) => text,
+        Err(reason) => return send_error(client, "invalid-chat", &reason, None).await,
+    };
+
+    let message = ChatMessage {
+        message_id: uuid::Uuid::new_v4().to_string(),
+        room_id: room.room_id.clone(),
+        client_id: client.client_id.clone(),
+        display_name: client.profile.display_name.clone(),
+        text: text.to_string(),
+        sent_at: Utc::now().timestamp_millis(),
+    };
+    // The message ends the sender's typing; peers clear the indicator when it arrives
+    if let Some(client) = state.clients.get_mut(&sender_addr) {
+        client.typing_since = None;
+    }
+    let chat = SignalMessage::server("chat", serde_json::to_value(&message)?);
+    send_to_room(&state, &message.room_id, &chat).await?;
+
+    // The message has already gone out, so a storage failure only costs late joiners
+    if let Some(store) = &state.chat {
+        if let Err(e) = store.append(&message) {
+            eprintln!("Failed to store chat message {}: {}", message.message_id, e);
+        }
+    }
+    Ok(())
+}
+
+// Typing indicators are coalesced: a client that keeps sending typing-start is relayed once
+// per half timeout, enough to keep its peers' indicators from lapsing, and typing-stop only
+// reaches peers that were told it started. Dropped signals are not errors; the ack says why.
+pub async fn handle_typing_start(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    if !may_type(&state, sender_addr) {
+        return Ok(());
+    }
+    let now = Instant::now();
+    let timeout = config::get_typing_timeout();
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    if client.typing_since.is_some_and(|since| now.duration_since(since) < timeout / 2) {
+        acks::record(Delivery::Failed("coalesced".to_string()));
+        return Ok(());
+    }
+    client.typing_since = Some(now);
+
+    let typing = SignalMessage::server("typing-start", serde_json::json!({
+        "client_id": client.client_id,
+        "expires_in_ms": timeout.as_millis() as u64,
+    }));
+    send_to_peers(&state, sender_addr, &typing).await
+}
+
+pub async fn handle_typing_stop(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    if !may_type(&state, sender_addr) {
+        return Ok(());
+    }
+    let timeout = config::get_typing_timeout();
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    // Peers stopped showing the indicator on their own once it expired
+    if client.typing_since.take().is_none_or(|since| since.elapsed() >= timeout) {
+        acks::record(Delivery::Failed("coalesced".to_string()));
+        return Ok(());
+    }
+
+    let typing = SignalMessage::server("typing-stop", serde_json::json!({
+        "client_id": client.client_id,
+    }));
+    send_to_peers(&state, sender_addr, &typing).await
+}
+
+// Only members whose role may chat have anything to type
+fn may_type(state: &SignalingState, addr: SocketAddr) -> bool {
+    let (Some(client), Some(room)) = (state.clients.get(&addr), state.client_room(addr)) else {
+        acks::record(Delivery::Failed("not-in-room".to_string()));
+        return false;
+    };
+    if !room.config.chat.allows(client.role) {
+        acks::record(Delivery::Failed("chat-not-allowed".to_string()));
+        return false;
+    }
+    true
+}
+
+async fn send_to_peers(
+    state: &SignalingState,
+    sender_addr: SocketAddr,
+    signal: &SignalMessage
+) -> Result<(), Box<dyn std::error::Error>> {
+    for peer in state.room_peers(sender_addr) {
+        send_signal(peer, signal).await?;
+    }
+    Ok(())
+}
+
+// Pages back through the room's chat: each page comes oldest first, and the first message's
+// id is the `before` for the one preceding it
+pub async fn handle_chat_history(
+    payload: ChatHistoryPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before reading its chat", None).await;
+    };
+    let Some(store) = &state.chat else {
+        return send_error(client, "chat-history-unavailable", "This server does not keep chat history", None).await;
+    };
+
+    let page_size = config::get_chat_history_page_size();
+    let limit = payload.limit.unwrap_or(page_size).clamp(1, page_size);
+    match history_page(store.as_ref(), &room.room_id, payload.before.as_deref(), limit) {
+        Ok(history) => send_signal(client, &history).await,
+        Err(e) => {
+            eprintln!("Failed to read chat history of room {}: {}", room.room_id, e);
+            send_error(client, "chat-history-unavailable", "Chat history could not be read", None).await
+        }
+    }
+}
+
+// Catches a newcomer up on what the room said before they arrived
+pub async fn send_recent_history(
+    state: &SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(store), Some(client), Some(room)) = (&state.chat, state.clients.get(&addr), state.client_room(addr)) else {
+        return Ok(());
+    };
+    match history_page(store.as_ref(), &room.room_id, None, config::get_chat_history_page_size()) {
+        Ok(history) => send_signal(client, &history).await,
+        Err(e) => {
+            eprintln!("Failed to read chat history of room {}: {}", room.room_id, e);
+            Ok(())
+        }
+    }
+}
+
+fn history_page(
+    store: &dyn ChatStore,
+    room_id: &str,
+    before: Option<&str>,
+    limit: usize
+) -> StoreResult<SignalMessage> {
+    // One extra tells whether there is an earlier page
+    let mut messages = store.history(room_id, before, limit + 1)?;
+    let has_more = messages.len() > limit;
+    if has_more {
+        messages.remove(0);
+    }
+    Ok(SignalMessage::server("chat-history", serde_json::json!({
+        "room_id": room_id,
+        "before": before,
+        "messages": messages,
+        "has_more": has_more,
+    })))
+}
@@ -0,0 +1,35 @@
+use crate::models::message::SERVER_SENDER_ID;
+use crate::models::SignalMessage;
+use std::future::Future;
+use std::net::SocketAddr;
+
+tokio::task_local! {
+    // The client message being dispatched: who sent it and the correlation id it carried
+    static CURRENT: (SocketAddr, Option<String>);
+}
+
+// Runs `future` on behalf of the message from `addr` carrying `correlation_id`
+pub async fn scope<F: Future>(addr: SocketAddr, correlation_id: Option<String>, future: F) -> F::Output {
+    CURRENT.scope((addr, correlation_id), future).await
+}
+
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|(_, id)| id.clone()).ok().flatten()
+}
+
+// Appended to log lines written while handling a message, so they can be matched to its reply
+pub fn tag() -> String {
+    current().map(|id| format!(" [correlation {}]", id)).unwrap_or_default()
+}
+
+// What the server sends back to the client whose message it is handling echoes that message's
+// correlation id. Relayed messages, and notices to anyone else, are left alone.
+pub fn stamp(recipient: SocketAddr, signal: &mut SignalMessage) {
+    if signal.sender_id != SERVER_SENDER_ID || signal.correlation_id.is_some() {
+        return;
+    }
+    signal.correlation_id = CURRENT
+        .try_with(|(addr, id)| id.clone().filter(|_| *addr == recipient))
+        .ok()
+        .flatten();
+}
@@ -0,0 +1,123 @@
+use crate::config;
+use crate::models::SignalMessage;
+use crate::signaling::acks::{self, Delivery};
+use crate::signaling::correlation;
+use crate::signaling::handlers::send_error;
+use crate::signaling::state::SharedState;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Message ids a client has sent recently, with what became of each. Carried over a resumed
+// session, since reconnecting is exactly when clients retransmit.
+#[derive(Debug, Clone, Default)]
+pub struct MessageIdCache {
+    outcomes: HashMap<String, Option<Delivery>>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl MessageIdCache {
+    // Records `message_id`; false when it was already seen within `ttl`
+    pub fn insert(&mut self, message_id: &str, now: Instant, ttl: Duration, capacity: usize) -> bool {
+        self.expire(now, ttl);
+        if self.outcomes.contains_key(message_id) {
+            return false;
+        }
+        while self.order.len() >= capacity.max(1) {
+            let Some((_, oldest)) = self.order.pop_front() else {
+                break;
+            };
+            self.outcomes.remove(&oldest);
+        }
+        self.outcomes.insert(message_id.to_string(), None);
+        self.order.push_back((now, message_id.to_string()));
+        true
+    }
+
+    pub fn settle(&mut self, message_id: &str, outcome: Option<Delivery>) {
+        if let Some(entry) = self.outcomes.get_mut(message_id) {
+            *entry = outcome;
+        }
+    }
+
+    pub fn outcome(&self, message_id: &str) -> Option<Delivery> {
+        self.outcomes.get(message_id).cloned().flatten()
+    }
+
+    // Takes in what a resumed session's previous connection saw
+    pub fn merge(&mut self, other: MessageIdCache) {
+        for (seen, message_id) in other.order {
+            let Some(outcome) = other.outcomes.get(&message_id) else {
+                continue;
+            };
+            if !self.outcomes.contains_key(&message_id) {
+                self.outcomes.insert(message_id.clone(), outcome.clone());
+                self.order.push_back((seen, message_id));
+            }
+        }
+        self.order.make_contiguous().sort_by_key(|(seen, _)| *seen);
+    }
+
+    fn expire(&mut self, now: Instant, ttl: Duration) {
+        while self.order.front().is_some_and(|(seen, _)| now.saturating_duration_since(*seen) > ttl) {
+            if let Some((_, message_id)) = self.order.pop_front() {
+                self.outcomes.remove(&message_id);
+            }
+        }
+    }
+}
+
+// False when the signal is a retransmission, which is acked with the original's outcome rather
+// than handled again, or when it lacks the UUID message id REQUIRE_MESSAGE_ID asks for
+pub async fn check_message_id(
+    signal: &SignalMessage,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(message_id) = signal.message_id.as_deref() else {
+        if !config::get_require_message_id() {
+            return Ok(true);
+        }
+        let state = state.lock().await;
+        if let Some(client) = state.clients.get(&addr) {
+            send_error(client, "missing-message-id", "Every message needs a message_id", None).await?;
+        }
+        return Ok(false);
+    };
+    if Uuid::parse_str(message_id).is_err() {
+        let state = state.lock().await;
+        if let Some(client) = state.clients.get(&addr) {
+            send_error(client, "invalid-message-id", "message_id must be a UUID", None).await?;
+        }
+        return Ok(false);
+    }
+
+    let outcome = {
+        let mut state = state.lock().await;
+        let Some(client) = state.clients.get_mut(&addr) else {
+            return Ok(false);
+        };
+        let now = Instant::now();
+        if client.recent_messages.insert(message_id, now, config::get_message_id_ttl(), config::get_message_id_cache_size()) {
+            return Ok(true);
+        }
+        client.recent_messages.outcome(message_id)
+    };
+    eprintln!("Ignoring retransmitted {} {} from {}{}", signal.signal_type, message_id, addr, correlation::tag());
+    if let Some(outcome) = outcome {
+        acks::record(outcome);
+    }
+    Ok(false)
+}
+
+// Remembers what became of a message, so a retransmission of it gets the same answer
+pub async fn settle(signal: &SignalMessage, outcome: &Option<Delivery>, addr: SocketAddr, state: &SharedState) {
+    let Some(message_id) = signal.message_id.as_deref() else {
+        return;
+    };
+    let mut state = state.lock().await;
+    if let Some(client) = state.clients.get_mut(&addr) {
+        client.recent_messages.settle(message_id, outcome.clone());
+    }
+}
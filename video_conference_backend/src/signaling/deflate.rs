@@ -0,0 +1,455 @@
+use crate::config;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use serde::Serialize;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+// RFC 7692. tungstenite has no extension support, so DeflateStream sits between it and the
+// socket, inflating client frames before tungstenite parses them and compressing the frames it
+// writes.
+pub const EXTENSION: &str = "permessage-deflate";
+// Each message the server sends is compressed on its own, so no window is kept per connection;
+// clients may keep theirs
+pub const RESPONSE: &str = "permessage-deflate; server_no_context_takeover";
+
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+const FIN: u8 = 0x80;
+const RSV1: u8 = 0x40;
+const MASKED: u8 = 0x80;
+const OPCODE: u8 = 0x0f;
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+// Frames held back for the socket before writes start waiting on it
+const WRITE_BUFFER: usize = 64 * 1024;
+
+static OUTBOUND: Counters = Counters::new();
+static INBOUND: Counters = Counters::new();
+
+struct Counters {
+    messages: AtomicU64,
+    uncompressed: AtomicU64,
+    compressed: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            messages: AtomicU64::new(0),
+            uncompressed: AtomicU64::new(0),
+            compressed: AtomicU64::new(0),
+        }
+    }
+
+    fn add(&self, uncompressed: usize, compressed: usize) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.uncompressed.fetch_add(uncompressed as u64, Ordering::Relaxed);
+        self.compressed.fetch_add(compressed as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> DeflateCounts {
+        let uncompressed_bytes = self.uncompressed.load(Ordering::Relaxed);
+        let compressed_bytes = self.compressed.load(Ordering::Relaxed);
+        DeflateCounts {
+            messages: self.messages.load(Ordering::Relaxed),
+            uncompressed_bytes,
+            compressed_bytes,
+            bytes_saved: uncompressed_bytes.saturating_sub(compressed_bytes),
+        }
+    }
+}
+
+// Compressed messages in one direction since startup
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DeflateCounts {
+    pub messages: u64,
+    pub uncompressed_bytes: u64,
+    pub compressed_bytes: u64,
+    pub bytes_saved: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DeflateStats {
+    pub outbound: DeflateCounts,
+    pub inbound: DeflateCounts,
+}
+
+pub fn stats() -> DeflateStats {
+    DeflateStats {
+        outbound: OUTBOUND.snapshot(),
+        inbound: INBOUND.snapshot(),
+    }
+}
+
+// Whether one of the client's permessage-deflate offers can be taken up. Offers that hold the
+// server to a window under zlib's 32KB, or that carry parameters this server doesn't know, are
+// passed over.
+pub fn accepts_offer(request: &Request) -> bool {
+    request
+        .headers()
+        .get_all("Sec-WebSocket-Extensions")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(acceptable_offer)
+}
+
+fn acceptable_offer(offer: &str) -> bool {
+    let mut parts = offer.split(';').map(str::trim);
+    if !parts.next().is_some_and(|name| name.eq_ignore_ascii_case(EXTENSION)) {
+        return false;
+    }
+    parts.all(|param| {
+        let (name, value) = match param.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (param, None),
+        };
+        match name {
+            "server_no_context_takeover" | "client_no_context_takeover" => value.is_none(),
+            "client_max_window_bits" => value.is_none_or(|bits| bits.parse::<u8>().is_ok_and(|bits| (8..=15).contains(&bits))),
+            "server_max_window_bits" => value == Some("15"),
+            _ => false,
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeflateConfig {
+    pub threshold: usize,
+    pub level: u32,
+    // Nothing inflates past this; it matches tungstenite's own limit
+    pub max_message_size: usize,
+}
+
+impl DeflateConfig {
+    pub fn from_config() -> Self {
+        Self {
+            threshold: config::get_deflate_threshold(),
+            level: config::get_deflate_level(),
+            max_message_size: config::get_max_message_size(),
+        }
+    }
+}
+
+// Passes bytes straight through until `enable` is called once the handshake has agreed on the
+// extension; from then on it works in whole frames
+pub struct DeflateStream<S> {
+    inner: S,
+    codec: Option<Codec>,
+}
+
+impl<S> DeflateStream<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner, codec: None }
+    }
+
+    pub fn enable(&mut self, config: DeflateConfig) {
+        self.codec = Some(Codec::new(config));
+    }
+}
+
+struct Frame {
+    header: u8,
+    mask: Option<[u8; 4]>,
+    // Unmasked
+    payload: Vec<u8>,
+}
+
+// A compressed message whose later fragments are still to come
+struct Fragments {
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    compressed: Vec<u8>,
+}
+
+struct Codec {
+    config: DeflateConfig,
+    level: Compression,
+    // Client messages may build on the window of earlier ones
+    inflater: Decompress,
+    fragments: Option<Fragments>,
+    // Socket bytes short of a whole frame, and rewritten frames for tungstenite to read
+    received: Vec<u8>,
+    readable: Vec<u8>,
+    // Written bytes short of a whole frame, and rewritten frames for the socket
+    written: Vec<u8>,
+    writable: Vec<u8>,
+}
+
+impl Codec {
+    fn new(config: DeflateConfig) -> Self {
+        Self {
+            config,
+            level: Compression::new(config.level),
+            inflater: Decompress::new(false),
+            fragments: None,
+            received: Vec::new(),
+            readable: Vec::new(),
+            written: Vec::new(),
+            writable: Vec::new(),
+        }
+    }
+
+    fn inbound(&mut self, frame: Frame) -> io::Result<()> {
+        let opcode = frame.header & OPCODE;
+        let is_control = opcode & 0x8 != 0;
+        let fin = frame.header & FIN != 0;
+
+        if is_control {
+            write_frame(&mut self.readable, frame.header, frame.mask, &frame.payload);
+            return Ok(());
+        }
+        match (&mut self.fragments, opcode) {
+            (Some(fragments), CONTINUATION) => {
+                if fragments.compressed.len() + frame.payload.len() > self.config.max_message_size {
+                    return Err(invalid_data("compressed message is too large"));
+                }
+                fragments.compressed.extend_from_slice(&frame.payload);
+            }
+            (Some(_), _) => return Err(invalid_data("data frame inside a fragmented message")),
+            (None, TEXT | BINARY) if frame.header & RSV1 != 0 => {
+                self.fragments = Some(Fragments { opcode, mask: frame.mask, compressed: frame.payload });
+            }
+            (None, _) => {
+                write_frame(&mut self.readable, frame.header, frame.mask, &frame.payload);
+                return Ok(());
+            }
+        }
+        if !fin {
+            return Ok(());
+        }
+
+        let Some(fragments) = self.fragments.take() else {
+            return Ok(());
+        };
+        let message = self.inflate(&fragments.compressed)?;
+        INBOUND.add(message.len(), fragments.compressed.len());
+        write_frame(&mut self.readable, FIN | fragments.opcode, fragments.mask, &message);
+        Ok(())
+    }
+
+    fn outbound(&mut self, frame: Frame) {
+        let opcode = frame.header & OPCODE;
+        // tungstenite sends each message as a single frame
+        let whole_message = frame.header & FIN != 0 && matches!(opcode, TEXT | BINARY);
+        if whole_message && frame.payload.len() >= self.config.threshold {
+            match self.deflate(&frame.payload) {
+                Ok(compressed) if compressed.len() < frame.payload.len() => {
+                    OUTBOUND.add(frame.payload.len(), compressed.len());
+                    write_frame(&mut self.writable, frame.header | RSV1, frame.mask, &compressed);
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Compression error: {}", e),
+            }
+        }
+        write_frame(&mut self.writable, frame.header, frame.mask, &frame.payload);
+    }
+
+    fn deflate(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        let mut compressor = Compress::new(self.level, false);
+        let mut compressed = Vec::with_capacity(payload.len() / 2 + 64);
+        loop {
+            let consumed = compressor.total_in() as usize;
+            compressor
+                .compress_vec(&payload[consumed..], &mut compressed, FlushCompress::Sync)
+                .map_err(invalid_data)?;
+            // The flush is done once all input is in and the output did not fill up
+            if compressor.total_in() as usize == payload.len() && compressed.len() < compressed.capacity() {
+                break;
+            }
+            compressed.reserve(compressed.capacity().max(64));
+        }
+        if compressed.ends_with(&TAIL) {
+            compressed.truncate(compressed.len() - TAIL.len());
+        }
+        Ok(compressed)
+    }
+
+    fn inflate(&mut self, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let input = [compressed, &TAIL].concat();
+        let start = self.inflater.total_in();
+        let mut message = Vec::with_capacity((input.len() * 4).min(self.config.max_message_size + 1));
+        loop {
+            let consumed = (self.inflater.total_in() - start) as usize;
+            let produced = message.len();
+            let status = self
+                .inflater
+                .decompress_vec(&input[consumed..], &mut message, FlushDecompress::Sync)
+                .map_err(invalid_data)?;
+            if message.len() > self.config.max_message_size {
+                return Err(invalid_data("inflated message is too large"));
+            }
+            // A final block ends the client's stream; its next message starts a fresh one
+            if status == Status::StreamEnd {
+                self.inflater.reset(false);
+                break;
+            }
+            let now_consumed = (self.inflater.total_in() - start) as usize;
+            if message.len() == message.capacity() {
+                message.reserve(message.capacity().max(64));
+            } else if now_consumed == input.len() {
+                break;
+            } else if now_consumed == consumed && message.len() == produced {
+                return Err(invalid_data("truncated compressed message"));
+            }
+        }
+        Ok(message)
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// Splits the first whole frame off the front of `buffer`
+fn take_frame(buffer: &mut Vec<u8>, max_payload: usize) -> io::Result<Option<Frame>> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+    let masked = buffer[1] & MASKED != 0;
+    let (length, mut offset) = match buffer[1] & 0x7f {
+        126 if buffer.len() >= 4 => (u16::from_be_bytes([buffer[2], buffer[3]]) as u64, 4),
+        127 if buffer.len() >= 10 => {
+            let mut length = [0u8; 8];
+            length.copy_from_slice(&buffer[2..10]);
+            (u64::from_be_bytes(length), 10)
+        }
+        126 | 127 => return Ok(None),
+        length => (length as u64, 2),
+    };
+    if length > max_payload as u64 {
+        return Err(invalid_data("frame is too large"));
+    }
+    let mask = if masked {
+        if buffer.len() < offset + 4 {
+            return Ok(None);
+        }
+        let mut key = [0u8; 4];
+        key.copy_from_slice(&buffer[offset..offset + 4]);
+        offset += 4;
+        Some(key)
+    } else {
+        None
+    };
+    let end = offset + length as usize;
+    if buffer.len() < end {
+        return Ok(None);
+    }
+
+    let mut payload = buffer[offset..end].to_vec();
+    if let Some(key) = mask {
+        apply_mask(&mut payload, key);
+    }
+    let header = buffer[0];
+    buffer.drain(..end);
+    Ok(Some(Frame { header, mask, payload }))
+}
+
+fn write_frame(out: &mut Vec<u8>, header: u8, mask: Option<[u8; 4]>, payload: &[u8]) {
+    out.push(header);
+    let mask_bit = if mask.is_some() { MASKED } else { 0 };
+    match payload.len() {
+        length if length < 126 => out.push(mask_bit | length as u8),
+        length if length <= u16::MAX as usize => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(key) => {
+            out.extend_from_slice(&key);
+            let start = out.len();
+            out.extend_from_slice(payload);
+            apply_mask(&mut out[start..], key);
+        }
+        None => out.extend_from_slice(payload),
+    }
+}
+
+fn apply_mask(bytes: &mut [u8], key: [u8; 4]) {
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte ^= key[i % 4];
+    }
+}
+
+fn poll_drain<S: AsyncWrite + Unpin>(inner: &mut S, pending: &mut Vec<u8>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    while !pending.is_empty() {
+        let written = ready!(Pin::new(&mut *inner).poll_write(cx, pending))?;
+        if written == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+        pending.drain(..written);
+    }
+    Poll::Ready(Ok(()))
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(codec) = this.codec.as_mut() else {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        };
+        loop {
+            if !codec.readable.is_empty() {
+                let length = buf.remaining().min(codec.readable.len());
+                buf.put_slice(&codec.readable[..length]);
+                codec.readable.drain(..length);
+                return Poll::Ready(Ok(()));
+            }
+            if let Some(frame) = take_frame(&mut codec.received, codec.config.max_message_size)? {
+                codec.inbound(frame)?;
+                continue;
+            }
+
+            let mut chunk = [0u8; 8192];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            codec.received.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(codec) = this.codec.as_mut() else {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        };
+        if codec.writable.len() >= WRITE_BUFFER {
+            ready!(poll_drain(&mut this.inner, &mut codec.writable, cx))?;
+        }
+        codec.written.extend_from_slice(buf);
+        while let Some(frame) = take_frame(&mut codec.written, usize::MAX)? {
+            codec.outbound(frame);
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(codec) = this.codec.as_mut() {
+            ready!(poll_drain(&mut this.inner, &mut codec.writable, cx))?;
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(codec) = this.codec.as_mut() {
+            ready!(poll_drain(&mut this.inner, &mut codec.writable, cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
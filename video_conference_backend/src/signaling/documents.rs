@@ -0,0 +1,186 @@
+use crate::config;
+use crate::models::message::{DocSnapshotPayload, DocSyncPayload, DocUpdatePayload};
+use crate::models::{Client, DocumentState, DocumentUpdate, SharedDocument, SignalMessage};
+use crate::signaling::handlers::{send_error, send_to_room};
+use crate::signaling::sequenced::{ask_for_snapshot, check_editor, send_pages};
+use crate::signaling::state::{SharedState, SignalingState};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::net::SocketAddr;
+
+const EDIT: &str = "edit shared documents";
+
+// Stores the update under the document's next version and sends it to every member. The
+// sender gets it back too: it has applied the update already, and merging it again changes
+// nothing, but the version tells it what a snapshot of its copy covers. Once enough updates pile
+// up the sender is asked to merge them into a snapshot.
+pub async fn handle_doc_update(
+    payload: DocUpdatePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(room_id) = check_editor(&state, sender_addr, EDIT).await? else {
+        return Ok(());
+    };
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if payload.update.len() > config::get_document_max_update_size() {
+        return send_error(client, "invalid-doc-update", "Document update is too large", None).await;
+    }
+    let Some(room) = state.rooms.get(&room_id) else {
+        return Ok(());
+    };
+    let room_bytes: usize = room.documents.values().map(SharedDocument::bytes).sum();
+    if room_bytes + payload.update.len() > config::get_room_documents_max_bytes() {
+        let message = "The room's documents need snapshots before they take more updates";
+        return send_error(client, "documents-full", message, None).await;
+    }
+    match room.documents.get(&payload.doc) {
+        None if room.documents.len() >= config::get_documents_per_room() => {
+            let message = format!("The room already has {} documents", room.documents.len());
+            return send_error(client, "too-many-documents", &message, None).await;
+        }
+        Some(document)
+            if document.pending() >= config::get_document_max_updates()
+                || document.bytes() + payload.update.len() > config::get_document_max_bytes() =>
+        {
+            // The member asked for a snapshot may have left, leaving the document stuck
+            ask_for_doc_snapshot(&mut state, &room_id, &payload.doc, sender_addr).await?;
+            if let Some(client) = state.clients.get(&sender_addr) {
+                let message = format!("Document {} needs a snapshot before it takes more updates", payload.doc);
+                send_error(client, "document-full", &message, None).await?;
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    let client_id = client.client_id.clone();
+    let Some(room) = state.rooms.get_mut(&room_id) else {
+        return Ok(());
+    };
+
+    let document = room.documents.entry(payload.doc.clone()).or_default();
+    let update = document.append(|version| DocumentUpdate { version, client_id, update: payload.update });
+    let mut relayed = serde_json::to_value(update)?;
+    relayed["room_id"] = serde_json::json!(room_id);
+    relayed["doc"] = serde_json::json!(payload.doc);
+    send_to_room(&state, &room_id, &SignalMessage::server("doc-update", relayed)).await?;
+    ask_for_doc_snapshot(&mut state, &room_id, &payload.doc, sender_addr).await
+}
+
+async fn ask_for_doc_snapshot(
+    state: &mut SignalingState,
+    room_id: &str,
+    doc: &str,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = |version| SignalMessage::server("doc-snapshot-request", serde_json::json!({
+        "room_id": room_id,
+        "doc": doc,
+        "version": version,
+    }));
+    let snapshot_after = config::get_document_snapshot_after();
+    ask_for_snapshot(state, room_id, addr, snapshot_after, |room| room.documents.get_mut(doc), request).await
+}
+
+// Only the member that was sent `doc-snapshot-request` for the document can hand in a snapshot
+// of it, so nobody else can replace what late joiners are shown
+pub async fn handle_doc_snapshot(
+    payload: DocSnapshotPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(room_id) = check_editor(&state, sender_addr, EDIT).await? else {
+        return Ok(());
+    };
+    let (Some(client), Some(room)) = (state.clients.get(&sender_addr), state.rooms.get(&room_id)) else {
+        return Ok(());
+    };
+    if room.documents.get(&payload.doc).and_then(SharedDocument::snapshot_from) != Some(sender_addr) {
+        let message = format!("Hand in a snapshot of {} only when asked for one", payload.doc);
+        return send_error(client, "snapshot-not-requested", &message, None).await;
+    }
+    if payload.state.len() > config::get_document_max_update_size() {
+        return send_error(client, "invalid-snapshot", "Document snapshot is too large", None).await;
+    }
+    let snapshot = DocumentState { version: payload.version, state: payload.state };
+    let compacted = state.rooms
+        .get_mut(&room_id)
+        .and_then(|room| room.documents.get_mut(&payload.doc))
+        .is_some_and(|document| document.compact(snapshot));
+    if !compacted {
+        if let Some(client) = state.clients.get(&sender_addr) {
+            let message = format!(
+                "A snapshot of {} at {} is older than the stored one or ahead of the document",
+                payload.doc,
+                payload.version
+            );
+            return send_error(client, "invalid-snapshot", &message, None).await;
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_doc_sync(
+    payload: DocSyncPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before syncing its documents", None).await;
+    };
+    match payload.doc {
+        // A document nobody has written to yet syncs as an empty one
+        Some(doc) => {
+            let empty = SharedDocument::default();
+            let document = room.documents.get(&doc).unwrap_or(&empty);
+            send_document(client, &room.room_id, &doc, document).await
+        }
+        None => send_documents(&state, sender_addr).await,
+    }
+}
+
+// Sends a newcomer every document in its room
+pub async fn send_documents(
+    state: &SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(client), Some(room)) = (state.clients.get(&addr), state.client_room(addr)) else {
+        return Ok(());
+    };
+    for (doc, document) in &room.documents {
+        send_document(client, &room.room_id, doc, document).await?;
+    }
+    Ok(())
+}
+
+// The stored state, if any, and the updates to apply on top of it, in `doc-state` pages of at
+// most DOCUMENT_PAGE_SIZE updates. The state comes with the first page, and `complete` marks
+// the last.
+async fn send_document(
+    client: &Client,
+    room_id: &str,
+    doc: &str,
+    document: &SharedDocument
+) -> Result<(), Box<dyn std::error::Error>> {
+    let page = |state: Option<&DocumentState>, updates: &[DocumentUpdate], complete| {
+        SignalMessage::server("doc-state", serde_json::json!({
+            "room_id": room_id,
+            "doc": doc,
+            "version": document.seq(),
+            "state_version": state.map(|state| state.version),
+            "state": state.map(|state| STANDARD.encode(&state.state)),
+            "updates": updates,
+            "complete": complete,
+        }))
+    };
+    send_pages(client, document, 0, config::get_document_page_size(), page).await
+}
@@ -0,0 +1,101 @@
+use crate::models::message::{KeyAnnouncePayload, KeyRequestPayload};
+use crate::models::SignalMessage;
+use crate::signaling::{errors, sdp};
+use crate::signaling::handlers::{relay_to, send_error, send_signal, send_to_room};
+use crate::signaling::state::{SharedState, SignalingState};
+use std::net::SocketAddr;
+
+// Starts a new key epoch after a membership change so departed members cannot decrypt what
+// follows and new members cannot decrypt what came before. Every member is expected to
+// announce a fresh key for the new epoch to each peer.
+pub async fn rekey(
+    state: &mut SignalingState,
+    room_id: &str,
+    reason: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(room) = state.rooms.get_mut(room_id).filter(|room| room.manages_keys()) else {
+        return Ok(());
+    };
+    room.key_epoch += 1;
+
+    let epoch = room.key_epoch;
+    let members: Vec<String> = room.members
+        .iter()
+        .filter_map(|member| state.clients.get(member))
+        .map(|client| client.client_id.clone())
+        .collect();
+
+    let update = SignalMessage::server("rekey", serde_json::json!({
+        "room_id": room_id,
+        "epoch": epoch,
+        "reason": reason,
+        "members": members,
+    }));
+    send_to_room(state, room_id, &update).await
+}
+
+// Forwards a member's sealed media key to one peer. Only the epoch is read; the key stays sealed.
+pub async fn handle_key_announce(
+    signal: &SignalMessage,
+    payload: KeyAnnouncePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(sender) = state.clients.get(&sender_addr).cloned() else {
+        return Ok(());
+    };
+    let Some(target_id) = signal.target_id.as_deref() else {
+        return send_error(&sender, "missing-target", "Key announcements are sealed to one target_id", None).await;
+    };
+    let Some(room) = state.client_room_mut(sender_addr) else {
+        return send_error(&sender, "not-in-room", "Join a room before announcing keys", None).await;
+    };
+
+    room.key_management = true;
+    if payload.epoch != room.key_epoch {
+        let epoch = room.key_epoch;
+        let error = errors::error_signal("stale-epoch", "Announce a key for the current epoch", None, serde_json::json!({
+            "epoch": epoch,
+        }));
+        return send_signal(&sender, &error).await;
+    }
+
+    match state.room_peers(sender_addr).into_iter().find(|peer| peer.client_id == target_id) {
+        Some(target) => relay_to(target, &sdp::sanitize(signal, sender_addr, &state)).await,
+        None => send_error(&sender, "target-unknown", "Target peer is not in your room", Some(target_id)).await,
+    }
+}
+
+// Asks one peer (or, without a target, every peer) to announce its key again, e.g. after a
+// missed announcement
+pub async fn handle_key_request(
+    signal: &SignalMessage,
+    payload: KeyRequestPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let (Some(sender), Some(room)) = (state.clients.get(&sender_addr), state.client_room(sender_addr)) else {
+        return Ok(());
+    };
+
+    let request = SignalMessage::server("key-request", serde_json::json!({
+        "room_id": room.room_id,
+        "client_id": sender.client_id,
+        "epoch": payload.epoch.unwrap_or(room.key_epoch),
+    }));
+    let peers = state.room_peers(sender_addr);
+    match signal.target_id.as_deref() {
+        Some(target_id) => match peers.into_iter().find(|peer| peer.client_id == target_id) {
+            Some(target) => send_signal(target, &request).await,
+            None => send_error(sender, "target-unknown", "Target peer is not in your room", Some(target_id)).await,
+        },
+        None => {
+            for peer in peers {
+                send_signal(peer, &request).await?;
+            }
+            Ok(())
+        }
+    }
+}
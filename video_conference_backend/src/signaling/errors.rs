@@ -0,0 +1,147 @@
+use crate::models::SignalMessage;
+use crate::signaling::acks::{self, Delivery};
+use crate::signaling::correlation;
+use serde_json::Value;
+
+// An `error` signal. `code` is the stable identifier clients match on and `numeric_code` its
+// number; `extra` (an object, or null) adds fields particular to the error. A message turned
+// away with an error is acked as failed for that reason.
+pub fn error_signal(code: &str, message: &str, target_id: Option<&str>, extra: Value) -> SignalMessage {
+    acks::record(Delivery::Failed(code.to_string()));
+    let mut body = serde_json::json!({
+        "code": code,
+        "numeric_code": numeric_code(code),
+        "message": message,
+        "target_id": target_id,
+        "correlation_id": correlation::current(),
+    });
+    if let (Some(body), Value::Object(extra)) = (body.as_object_mut(), extra) {
+        body.extend(extra);
+    }
+    SignalMessage::server("error", body)
+}
+
+// Numbers group codes by what went wrong: 1xxx the message itself, 2xxx authentication,
+// 3xxx signatures and keys, 4xxx rooms and moderation, 5xxx server-side services, 6xxx
+// negotiating peer connections and 9xxx the server. Numbers are never reused once published.
+pub fn numeric_code(code: &str) -> u16 {
+    match code {
+        "malformed-message" => 1000,
+        "invalid-payload" => 1001,
+        "unknown-signal" => 1002,
+        "message-too-large" => 1003,
+        "unsupported-version" => 1004,
+        "blind-relay-only" => 1005,
+        "missing-target" => 1006,
+        "target-unknown" => 1007,
+        "invalid-room-id" => 1008,
+        "invalid-profile" => 1009,
+        "invalid-schedule" => 1010,
+        "invalid-role" => 1011,
+        "missing-message-id" => 1012,
+        "invalid-message-id" => 1013,
+        "invalid-capabilities" => 1014,
+        "invalid-chat" => 1015,
+        "invalid-reaction" => 1016,
+        "invalid-file-offer" => 1017,
+        "unknown-transfer" => 1018,
+        "invalid-whiteboard-op" => 1019,
+        "invalid-snapshot" => 1020,
+        "invalid-doc-update" => 1021,
+        "invalid-poll" => 1022,
+        "unknown-poll" => 1023,
+        "invalid-question" => 1024,
+        "unknown-question" => 1025,
+        "invalid-call" => 1026,
+        "unknown-call" => 1027,
+
+        "unauthenticated" => 2000,
+        "invalid-token" => 2001,
+        "no-challenge" => 2002,
+        "challenge-failed" => 2003,
+        "session-token-required" => 2004,
+        "session-token-invalid" => 2005,
+        "resume-failed" => 2006,
+        "webauthn-unavailable" => 2007,
+        "webauthn-failed" => 2008,
+        "credential-exists" => 2009,
+        "registration-not-allowed" => 2010,
+        "missing-user" => 2011,
+        "locked-out" => 2012,
+        "banned" => 2013,
+        "not-admin" => 2014,
+        "not-signed-in" => 2015,
+
+        "missing-nonce" => 3000,
+        "replayed-nonce" => 3001,
+        "missing-timestamp" => 3002,
+        "stale-timestamp" => 3003,
+        "timestamp-in-future" => 3004,
+        "missing-seq" => 3005,
+        "stale-seq" => 3006,
+        "unverified-sender" => 3007,
+        "unsigned-candidate" => 3008,
+        "key-mismatch" => 3009,
+        "key-revoked" => 3010,
+        "no-key-bound" => 3011,
+        "invalid-certificate" => 3012,
+        "certificate-mismatch" => 3013,
+        "key-pin-mismatch" => 3014,
+        "key-pin-unavailable" => 3015,
+        "stale-epoch" => 3016,
+        "target-unverified" => 3017,
+        "SIG_UNSUPPORTED_VERSION" => 3100,
+        "SIG_DATA_MISMATCH" => 3101,
+        "SIG_INVALID_KEY_LENGTH" => 3102,
+        "SIG_INVALID_KEY" => 3103,
+        "SIG_INVALID_LENGTH" => 3104,
+        "SIG_INVALID_ENCODING" => 3105,
+        "SIG_VERIFY_FAILED" => 3106,
+        "SIG_POLICY_VIOLATION" => 3107,
+
+        "not-in-room" => 4000,
+        "room-exists" => 4001,
+        "not-moderator" => 4002,
+        "not-host" => 4003,
+        "insufficient-role" => 4004,
+        "screenshare-busy" => 4005,
+        "audio-only-room" => 4006,
+        "e2ee-required" => 4007,
+        "chat-not-allowed" => 4008,
+        "file-transfer-disabled" => 4009,
+        "file-too-large" => 4010,
+        "file-type-not-allowed" => 4011,
+        "whiteboard-full" => 4012,
+        "too-many-documents" => 4013,
+        "document-full" => 4014,
+        "too-many-polls" => 4015,
+        "poll-closed" => 4016,
+        "poll-open" => 4017,
+        "already-voted" => 4018,
+        "too-many-questions" => 4019,
+        "question-not-open" => 4020,
+        "already-upvoted" => 4021,
+        "in-room" => 4023,
+        "in-call" => 4024,
+        "snapshot-not-requested" => 4025,
+        "documents-full" => 4026,
+        "caller-busy" => 4027,
+
+        "invalid-report" => 5000,
+        "reports-unavailable" => 5001,
+        "invalid-ban" => 5002,
+        "bans-unavailable" => 5003,
+        "invalid-ip-filter" => 5004,
+        "geoip-unavailable" => 5005,
+        "turn-unavailable" => 5006,
+        "chat-history-unavailable" => 5007,
+        "call-history-unavailable" => 5008,
+
+        "renegotiation-glare" => 6000,
+        "no-renegotiation" => 6001,
+        "invalid-sdp" => 6002,
+        "sdp-too-large" => 6003,
+
+        _ => 9000,
+    }
+}
@@ -0,0 +1,170 @@
+use crate::config;
+use crate::models::message::{FileOfferPayload, FileResponsePayload};
+use crate::models::SignalMessage;
+use crate::rooms::FilePolicy;
+use crate::signaling::handlers::{relay_to, send_error};
+use crate::signaling::sdp;
+use crate::signaling::state::{SharedState, SignalingState};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const MAX_TRANSFER_ID_CHARS: usize = 64;
+const MAX_FILE_NAME_CHARS: usize = 255;
+
+#[derive(Debug)]
+struct PendingOffer {
+    offerer_id: String,
+    recipient_id: String,
+    offered_at: Instant,
+}
+
+// File offers waiting on their recipient, keyed by transfer id
+#[derive(Debug, Default)]
+pub struct FileTransfers {
+    pending: HashMap<String, PendingOffer>,
+}
+
+impl FileTransfers {
+    // False when the transfer id is already waiting on an answer
+    pub fn offer(&mut self, transfer_id: &str, offerer_id: &str, recipient_id: &str, now: Instant, ttl: Duration) -> bool {
+        self.pending.retain(|_, offer| now.saturating_duration_since(offer.offered_at) < ttl);
+        if self.pending.contains_key(transfer_id) {
+            return false;
+        }
+        self.pending.insert(transfer_id.to_string(), PendingOffer {
+            offerer_id: offerer_id.to_string(),
+            recipient_id: recipient_id.to_string(),
+            offered_at: now,
+        });
+        true
+    }
+
+    // Closes the offer `recipient_id` was made and returns who made it; None when there is no
+    // such offer, it expired, or it was made to someone else
+    pub fn answer(&mut self, transfer_id: &str, recipient_id: &str, now: Instant, ttl: Duration) -> Option<String> {
+        let offer = self.pending.get(transfer_id)?;
+        if offer.recipient_id != recipient_id || now.saturating_duration_since(offer.offered_at) >= ttl {
+            return None;
+        }
+        self.pending.remove(transfer_id).map(|offer| offer.offerer_id)
+    }
+
+    pub fn forget(&mut self, client_id: &str) {
+        self.pending.retain(|_, offer| offer.offerer_id != client_id && offer.recipient_id != client_id);
+    }
+}
+
+// Relays a file offer to the peer it names once the room's file policy allows it. The file
+// itself goes over a data channel between the two.
+pub async fn handle_file_offer(
+    signal: &SignalMessage,
+    payload: FileOfferPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(target_id) = find_target(signal, sender_addr, &state).await? else {
+        return Ok(());
+    };
+    let (Some(sender), Some(room)) = (state.clients.get(&sender_addr), state.client_room(sender_addr)) else {
+        return Ok(());
+    };
+    if let Err(reason) = check_offer(&payload) {
+        return send_error(sender, "invalid-file-offer", reason, Some(&target_id)).await;
+    }
+    if let Err(rejection) = FilePolicy::for_room(&room.config).check(payload.size, &payload.mime_type) {
+        return send_error(sender, rejection.code(), rejection.message(), Some(&target_id)).await;
+    }
+
+    let sender_id = sender.client_id.clone();
+    let offered = state.file_transfers.offer(
+        &payload.transfer_id,
+        &sender_id,
+        &target_id,
+        Instant::now(),
+        config::get_file_offer_ttl()
+    );
+    let (Some(sender), Some(target)) = (
+        state.clients.get(&sender_addr),
+        state.tenant_client(sender_addr, &target_id)
+    ) else {
+        return Ok(());
+    };
+    if !offered {
+        return send_error(sender, "invalid-file-offer", "That transfer id is already in use", Some(&target_id)).await;
+    }
+    println!("{} offered {} a {} byte file ({})", sender_id, target_id, payload.size, payload.transfer_id);
+    relay_to(target, &sdp::sanitize(signal, sender_addr, &state)).await
+}
+
+// Relays `file-accept` or `file-reject` back to whoever made the offer
+pub async fn handle_file_response(
+    signal: &SignalMessage,
+    payload: FileResponsePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(sender_id) = state.clients.get(&sender_addr).map(|client| client.client_id.clone()) else {
+        return Ok(());
+    };
+    let offerer_id = state.file_transfers.answer(
+        &payload.transfer_id,
+        &sender_id,
+        Instant::now(),
+        config::get_file_offer_ttl()
+    );
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(offerer) = offerer_id.and_then(|offerer_id| {
+        state.room_peers(sender_addr).into_iter().find(|client| client.client_id == offerer_id)
+    }) else {
+        return send_error(sender, "unknown-transfer", "No file offer with that id is waiting on you", None).await;
+    };
+    relay_to(offerer, &sdp::sanitize(signal, sender_addr, &state)).await
+}
+
+// The verified room peer the offer names; None once the sender has been told why there is none
+async fn find_target(
+    signal: &SignalMessage,
+    sender_addr: SocketAddr,
+    state: &SignalingState
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(None);
+    };
+    let Some(target_id) = signal.target_id.clone() else {
+        send_error(sender, "missing-target", "File offers must name a target_id", None).await?;
+        return Ok(None);
+    };
+    let target = state.room_peers(sender_addr)
+        .into_iter()
+        .find(|client| client.client_id == target_id)
+        .map(|client| client.verified);
+    let (code, message) = match target {
+        Some(true) => return Ok(Some(target_id)),
+        Some(false) => ("target-unverified", "Target peer has not completed verification"),
+        None => ("target-unknown", "Target peer is not in your room"),
+    };
+    send_error(sender, code, message, Some(&target_id)).await?;
+    Ok(None)
+}
+
+fn check_offer(payload: &FileOfferPayload) -> Result<(), &'static str> {
+    if payload.transfer_id.chars().count() > MAX_TRANSFER_ID_CHARS {
+        return Err("Transfer id is too long");
+    }
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_FILE_NAME_CHARS {
+        return Err("File names are 1 to 255 characters");
+    }
+    // A bare name, so the recipient cannot be steered into writing somewhere else
+    if name.chars().any(|c| c.is_control() || c == '/' || c == '\\') || name == "." || name == ".." {
+        return Err("File names must not contain paths or control characters");
+    }
+    Ok(())
+}
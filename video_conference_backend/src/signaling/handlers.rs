@@ -1,149 +1,990 @@
-use crate::models::{Client, SignalMessage};
-use crate::models::message::SecureConnectionPayload;
+use crate::models::{Client, Role, Room, SignalMessage, WaitingJoin, WireEncoding};
+use crate::models::message::{CreateInvitePayload, CreateRoomPayload, JoinDecisionPayload, JoinRoomPayload, ListRoomsPayload, LockRoomPayload, ResumeSessionPayload, SecureConnectionPayload};
+use crate::admin;
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::crypto::{self, AsyncVerifier, NonceError, SequenceError, VerificationCode, VerificationError, VerificationRequest};
+use crate::config;
+use crate::pinning;
+use crate::rooms::{self, CreateRoomError, JoinError, LobbyError};
+use crate::sessions;
+use crate::tls::CertIdentity;
+use crate::signaling::{acks, batching, calls, correlation, errors, limits, roster, sdp};
+use crate::signaling::acks::Delivery;
+use crate::signaling::state::{SharedState, SignalingState};
+use chrono::Utc;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use std::collections::HashMap;
+use std::time::{Instant, SystemTime};
 use tokio_tungstenite::tungstenite::protocol::Message;
-use p256::ecdsa::signature::Verifier;
 
 pub async fn handle_secure_offer(
     signal: &SignalMessage,
+    payload: SecureConnectionPayload,
     sender_addr: SocketAddr,
-    clients: Arc<Mutex<HashMap<SocketAddr, Client>>>
+    state: SharedState
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let payload: SecureConnectionPayload = serde_json::from_str(&signal.payload)?;
-    
-    if !verify_signature(&payload.offer, &payload.signature, &payload.public_key) {
-        eprintln!("Invalid offer signature");
+    if !verify_and_bind(signal, payload, "offer", sender_addr, &state).await? {
         return Ok(());
     }
 
-    {
-        let mut clients_map = clients.lock().await;
-        if let Some(client) = clients_map.get_mut(&sender_addr) {
-            client.public_key = Some(payload.public_key.clone());
-            client.verified = true;
+    let signal = sdp::sanitize(signal, sender_addr, &*state.lock().await);
+    relay_signal(&signal, sender_addr, state).await?;
+    Ok(())
+}
+
+pub async fn handle_secure_answer(
+    signal: &SignalMessage,
+    payload: SecureConnectionPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !verify_and_bind(signal, payload, "answer", sender_addr, &state).await? {
+        return Ok(());
+    }
+
+    let signal = sdp::sanitize(signal, sender_addr, &*state.lock().await);
+    relay_signal(&signal, sender_addr, state).await?;
+    Ok(())
+}
+
+// Runs every check a secure offer or answer (`kind`) must pass, then binds its key to the
+// connection and marks it verified. Returns false, after telling the sender why, on the first
+// check that fails.
+async fn verify_and_bind(
+    signal: &SignalMessage,
+    mut payload: SecureConnectionPayload,
+    kind: &str,
+    sender_addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let certificate = match check_certificate_chain(&mut payload, state).await {
+        Ok(certificate) => certificate,
+        Err(reason) => {
+            eprintln!("Rejected {} from {}: {}{}", kind, sender_addr, reason, correlation::tag());
+            let state = state.lock().await;
+            audit_rejection(&state, sender_addr, signal, "invalid-certificate", &reason);
+            if let Some(client) = state.clients.get(&sender_addr) {
+                send_error(client, "invalid-certificate", &reason, None).await?;
+            }
+            return Ok(false);
         }
+    };
+
+    if !enforce_room_policy(&payload, sender_addr, state).await? {
+        return Ok(false);
+    }
+    
+    let verifier = state.lock().await.async_verifier();
+    if let Err(error) = check_signature(&verifier, signal, &payload).await {
+        eprintln!("Rejected {} from {}: {} ({}){}", kind, sender_addr, error.code(), error, correlation::tag());
+        let mut state = state.lock().await;
+        audit_rejection(&state, sender_addr, signal, error.code(), &error.message);
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_verification_error(client, &error).await?;
+        }
+        limits::record_verification_failure(&mut state, sender_addr).await;
+        return Ok(false);
     }
 
-    broadcast_to_verified_peers(signal, sender_addr, clients).await?;
+    let mut state = state.lock().await;
+    if state.revoked.is_revoked(&payload.public_key) {
+        eprintln!("Rejected {} from {}: key is revoked{}", kind, sender_addr, correlation::tag());
+        audit_rejection(&state, sender_addr, signal, "key-revoked", "This public key has been revoked");
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_error(client, "key-revoked", "This public key has been revoked", None).await?;
+            client.disconnect("key revoked").await;
+        }
+        return Ok(false);
+    }
+    if state.check_ban(sender_addr, Some(&payload.public_key)).is_some() {
+        eprintln!("Rejected {} from {}: banned{}", kind, sender_addr, correlation::tag());
+        audit_rejection(&state, sender_addr, signal, "banned", "This key or identity is banned");
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_error(client, "banned", "You have been banned from this server", None).await?;
+            client.disconnect("banned").await;
+        }
+        return Ok(false);
+    }
+    if let Some(client) = state.clients.get(&sender_addr) {
+        if client.public_key.as_ref().is_some_and(|key| *key != payload.public_key) {
+            eprintln!("Rejected {} from {}: signed with a different key{}", kind, sender_addr, correlation::tag());
+            audit_rejection(&state, sender_addr, signal, "key-mismatch", "This connection is bound to a different public key");
+            send_error(client, "key-mismatch", "This connection is bound to a different public key", None).await?;
+            return Ok(false);
+        }
+    }
+    if let Err(reason) = bind_certificate(&mut state, sender_addr, certificate) {
+        eprintln!("Rejected {} from {}: {}{}", kind, sender_addr, reason, correlation::tag());
+        audit_rejection(&state, sender_addr, signal, "certificate-mismatch", &reason);
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_error(client, "certificate-mismatch", &reason, None).await?;
+        }
+        return Ok(false);
+    }
+    if let Err(error) = state.check_key_pin(sender_addr, &payload.public_key) {
+        eprintln!("Rejected {} from {}: {}{}", kind, sender_addr, error.code(), correlation::tag());
+        audit_rejection(&state, sender_addr, signal, error.code(), error.message());
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_error(client, error.code(), error.message(), None).await?;
+        }
+        return Ok(false);
+    }
+    if let Err(error) = state.nonces.check(&payload.public_key, &payload.nonce) {
+        eprintln!("Rejected {} from {}: {}{}", kind, sender_addr, error.code(), correlation::tag());
+        audit_rejection(&state, sender_addr, signal, error.code(), error.message());
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_error(client, error.code(), error.message(), None).await?;
+        }
+        limits::record_verification_failure(&mut state, sender_addr).await;
+        return Ok(false);
+    }
+    if !advance_sequence(&mut state, sender_addr, signal).await? {
+        return Ok(false);
+    }
+    if let Some(client) = state.clients.get_mut(&sender_addr) {
+        client.public_key = Some(payload.public_key.clone());
+        client.verified = true;
+    }
+    send_resume_token(&mut state, sender_addr).await?;
+    send_session_token(&mut state, sender_addr).await?;
+    Ok(true)
+}
+
+async fn send_resume_token(
+    state: &mut SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(token) = sessions::issue_resume_token(state, addr) {
+        if let Some(client) = state.clients.get(&addr) {
+            let message = SignalMessage::server("resume-token", serde_json::json!({
+                "resume_token": token,
+                "grace_secs": config::get_resume_grace_period().as_secs(),
+            }));
+            send_signal(client, &message).await?;
+        }
+    }
     Ok(())
 }
 
-pub async fn handle_secure_answer(
+// Sent after every verified offer or answer, and when the current token is due for renewal
+pub async fn send_session_token(
+    state: &mut SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ttl = config::get_session_token_ttl();
+    if let Some(token) = sessions::issue_session_token(state, addr, ttl) {
+        if let Some(client) = state.clients.get(&addr) {
+            let message = SignalMessage::server("session-token", serde_json::json!({
+                "session_token": token.token,
+                "expires_in_secs": ttl.as_secs(),
+            }));
+            send_signal(client, &message).await?;
+        }
+    }
+    Ok(())
+}
+
+// Returns the restored client_id so the connection can adopt it for later messages
+pub async fn handle_resume_session(
+    payload: ResumeSessionPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let resumed = sessions::resume_session(&mut state, sender_addr, &payload.resume_token);
+
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(None);
+    };
+
+    match resumed {
+        Some((client_id, resume_token)) => {
+            let reply = SignalMessage::server("session-resumed", serde_json::json!({
+                "client_id": client_id,
+                "room_id": client.room_id,
+                "resume_token": resume_token,
+            }));
+            send_signal(client, &reply).await?;
+            send_session_token(&mut state, sender_addr).await?;
+            roster::announce_join(&mut state, sender_addr).await?;
+            Ok(Some(client_id))
+        }
+        None => {
+            send_error(client, "resume-failed", "Resume token is unknown or its grace period has expired", None).await?;
+            Ok(None)
+        }
+    }
+}
+
+// Returns false (after telling the sender why) when the room bans the sender or its profile forbids this session description
+async fn enforce_room_policy(
+    payload: &SecureConnectionPayload,
+    sender_addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(room) = state.client_room(sender_addr) else {
+        return Ok(true);
+    };
+
+    let violation = if room.is_banned(&sender_addr.ip(), Some(&payload.public_key)) {
+        Some(("banned", "You are banned from this room"))
+    } else {
+        rooms::check_session_description(&room.config, payload)
+            .err()
+            .map(|violation| (violation.code(), violation.message()))
+    };
+
+    match (violation, state.clients.get(&sender_addr)) {
+        (Some((code, message)), Some(client)) => {
+            send_error(client, code, message, None).await?;
+            Ok(false)
+        }
+        (Some(_), None) => Ok(false),
+        (None, _) => Ok(true),
+    }
+}
+
+// Delivers to the addressed peer when `target_id` is set, otherwise to the whole room
+pub async fn relay_signal(
     signal: &SignalMessage,
     sender_addr: SocketAddr,
-    clients: Arc<Mutex<HashMap<SocketAddr, Client>>>
+    state: SharedState
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let payload: SecureConnectionPayload = serde_json::from_str(&signal.payload)?;
-    
-    if !verify_signature(&payload.offer, &payload.signature, &payload.public_key) {
-        eprintln!("Invalid answer signature");
+    match &signal.target_id {
+        Some(target_id) => send_to_target(signal, target_id, sender_addr, state).await,
+        None => broadcast_to_verified_peers(signal, sender_addr, state).await,
+    }
+}
+
+// Blind relay: `payload` is ciphertext sealed to the target's public key and is forwarded
+// byte-for-byte without ever being parsed, so the server never sees the SDP or candidates
+// inside. Only the envelope's `target_id` is used for routing. Both ends need a bound key,
+// since that is what the sender seals to.
+pub async fn relay_sealed(
+    signal: &SignalMessage,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+
+    let Some(sender) = state.clients.get(&sender_addr) else {
         return Ok(());
+    };
+    let Some(target_id) = signal.target_id.as_deref() else {
+        return send_error(sender, "missing-target", "Sealed messages must name a target_id", None).await;
+    };
+    if sender.public_key.is_none() {
+        return send_error(sender, "no-key-bound", "Bind a public key before sending sealed messages", None).await;
     }
 
-    {
-        let mut clients_map = clients.lock().await;
-        if let Some(client) = clients_map.get_mut(&sender_addr) {
-            client.verified = true;
-        }
+    let target = state.room_peers(sender_addr)
+        .into_iter()
+        .find(|client| client.client_id == target_id);
+    let error = match target {
+        Some(client) if client.public_key.is_some() => return relay_to(client, signal).await,
+        Some(_) => ("target-unverified", "Target peer has no public key to seal to"),
+        None => ("target-unknown", "Target peer is not in your room"),
+    };
+    send_error(sender, error.0, error.1, Some(target_id)).await
+}
+
+pub async fn send_to_target(
+    signal: &SignalMessage,
+    target_id: &str,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+
+    let target = state.room_peers(sender_addr)
+        .into_iter()
+        .find(|client| client.client_id == target_id);
+
+    let error = match target {
+        Some(client) if client.verified => return relay_to(client, signal).await,
+        Some(_) => ("target-unverified", "Target peer has not completed verification"),
+        None => ("target-unknown", "Target peer is not in your room"),
+    };
+
+    if let Some(sender) = state.clients.get(&sender_addr) {
+        send_error(sender, error.0, error.1, Some(target_id)).await?;
     }
 
-    broadcast_to_verified_peers(signal, sender_addr, clients).await?;
     Ok(())
 }
 
 pub async fn broadcast_to_verified_peers(
     signal: &SignalMessage,
     sender_addr: SocketAddr,
-    clients: Arc<Mutex<HashMap<SocketAddr, Client>>>
+    state: SharedState
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let clients_map = clients.lock().await;
-    
-    let message = serde_json::to_string(signal)?;
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
     
-    for (addr, client) in clients_map.iter() {
-        if *addr != sender_addr && client.verified {
-            if let Err(e) = client.sender.send(Message::Text(message.clone())).await {
-                eprintln!("Broadcast error to {}: {}", addr, e);
+    let mut deliveries = Vec::new();
+    for client in state.room_peers(sender_addr) {
+        if client.verified {
+            let delivery = deliver_in_order(client, signal).await?;
+            if let Delivery::Failed(reason) = &delivery {
+                eprintln!("Broadcast error to {}: {}", client.address, reason);
             }
+            deliveries.push(delivery);
         }
     }
+    acks::record(Delivery::combine(deliveries));
 
     Ok(())
 }
 
-fn verify_signature(
-    data: &serde_json::Value,
-    signature: &[u8],
-    public_key: &[u8],
-) -> bool {
-    // Check public key length - P-256 public keys are uncompressed (65 bytes) or compressed (33 bytes)
-    if public_key.len() != 65 && public_key.len() != 33 {
-        eprintln!("[ERROR] Invalid public key length: expected 65 or 33 bytes, got {}", public_key.len());
-        return false;
+pub async fn handle_create_room(
+    payload: CreateRoomPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    if payload.room_id.trim().is_empty() {
+        eprintln!("Rejected create-room with empty room id from {}{}", sender_addr, correlation::tag());
+        return reject_empty_room_id(sender_addr, &state).await;
+    }
+    let room_id = tenant_room_id(&payload.room_id, sender_addr, &state).await;
+    let room_id = room_id.as_str();
+
+    let scheduled = payload.starts_at.is_some() || payload.ends_at.is_some();
+    if scheduled && !admin::is_admin_token(payload.admin_token.as_deref()) {
+        let state = state.lock().await;
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_error(client, "not-admin", "Scheduling rooms requires an admin token", None).await?;
+        }
+        return Ok(());
     }
 
-    // Check signature length - P-256 ECDSA signatures are typically 64 bytes (r and s components)
-    if signature.len() != 64 {
-        eprintln!("[ERROR] Secure offer handling error: Uint8Array of valid length expected - got {} bytes", signature.len());
-        return false;
+    // Argon2 is deliberately slow, so hashing runs on the blocking pool rather than the executor
+    let password_hash = match payload.password.clone() {
+        Some(password) => Some(
+            tokio::task::spawn_blocking(move || rooms::hash_password(&password))
+                .await?
+                .map_err(|e| e.to_string())?
+        ),
+        None => None,
+    };
+    let max_participants = requested_capacity(payload.max_participants);
+
+    let mut room = Room::new(room_id.to_string(), max_participants);
+    room.password_hash = password_hash;
+    room.lobby_enabled = payload.lobby;
+    room.listed = payload.listed;
+    room.config = payload.config.clone();
+    room.starts_at = payload.starts_at;
+    room.ends_at = payload.ends_at;
+    // Scheduled rooms are pre-created by an admin; whoever joins first becomes host
+    if !scheduled {
+        room.host = Some(sender_addr);
     }
 
-    // Convert the message to bytes
-    let message = match serde_json::to_vec(data) {
-        Ok(msg) => msg,
-        Err(e) => {
-            eprintln!("[ERROR] Failed to serialize data: {}", e);
-            return false;
+    let mut guard = state.lock().await;
+    if let Some(tenant) = guard.clients.get(&sender_addr).and_then(|client| client.tenant.as_ref()) {
+        room.tenant_id = Some(tenant.tenant_id.clone());
+        room.max_participants = tenant.max_participants(room.max_participants);
+    }
+    if !scheduled {
+        room.owner = guard.clients.get(&sender_addr).map(pinning::identity);
+    }
+    let result = rooms::create_room(&mut guard, room);
+
+    if let (Ok(()), Some(ends_at)) = (result, payload.ends_at) {
+        tokio::spawn(rooms::schedule_room_close(Arc::clone(&state), room_id.to_string(), ends_at));
+    }
+
+    if let Some(client) = guard.clients.get(&sender_addr) {
+        match result {
+            Ok(()) => {
+                let reply = SignalMessage::server("room-created", serde_json::json!({
+                    "room_id": room_id,
+                    "password_protected": payload.password.is_some(),
+                    "lobby": payload.lobby,
+                    "listed": payload.listed,
+                    "starts_at": payload.starts_at,
+                    "ends_at": payload.ends_at,
+                }));
+                send_signal(client, &reply).await?;
+            }
+            Err(CreateRoomError::AlreadyExists) => {
+                send_error(client, "room-exists", "A room with this id already exists", None).await?;
+            }
+            Err(CreateRoomError::InvalidSchedule) => {
+                send_error(client, "invalid-schedule", "Room must end in the future and after it starts", None).await?;
+            }
         }
+    }
+
+    Ok(())
+}
+
+// The capacity a client asked for, held to the server's maximum
+fn requested_capacity(requested: Option<usize>) -> usize {
+    requested
+        .unwrap_or_else(config::get_default_max_participants)
+        .min(config::get_max_room_participants())
+}
+
+// The registry id of the room a client names, in its tenant's namespace
+async fn tenant_room_id(room_id: &str, addr: SocketAddr, state: &SharedState) -> String {
+    let state = state.lock().await;
+    let tenant_id = state.clients.get(&addr).and_then(|client| client.tenant_id());
+    rooms::scoped_room_id(tenant_id, room_id.trim())
+}
+
+async fn reject_empty_room_id(addr: SocketAddr, state: &SharedState) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    match state.clients.get(&addr) {
+        Some(client) => send_error(client, "invalid-room-id", "Room ids must not be empty", None).await,
+        None => Ok(()),
+    }
+}
+
+pub async fn handle_join_room(
+    payload: JoinRoomPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    if payload.room_id.trim().is_empty() {
+        eprintln!("Rejected join-room with empty room id from {}{}", sender_addr, correlation::tag());
+        return reject_empty_room_id(sender_addr, &state).await;
+    }
+    let room_id = tenant_room_id(&payload.room_id, sender_addr, &state).await;
+    let room_id = room_id.as_str();
+
+    let max_participants = requested_capacity(payload.max_participants);
+
+    // An invite token stands in for the password. Argon2 verification is deliberately
+    // slow, so it runs on the blocking pool without holding the state lock, and the hash it was
+    // checked against is compared with the room's again once the lock is back.
+    let invited = payload.invite_token.is_some();
+    let password_hash = {
+        let state = state.lock().await;
+        state.rooms.get(room_id).and_then(|room| room.password_hash.clone())
+    };
+    let password_check = if invited {
+        Ok(())
+    } else {
+        let (hash, password) = (password_hash.clone(), payload.password.clone());
+        tokio::task::spawn_blocking(move || rooms::check_password(hash.as_deref(), password.as_deref())).await?
     };
 
-    // Use p256 crate for verification
-    use p256::ecdsa::{Signature, VerifyingKey};
-    use p256::{EncodedPoint, FieldBytes};
-    
-    // Import public key
-    let encoded_point = match EncodedPoint::from_bytes(public_key) {
-        Ok(point) => point,
-        Err(e) => {
-            eprintln!("[ERROR] Failed to parse public key: {}", e);
-            return false;
+    let mut state = state.lock().await;
+    if let Some(capabilities) = payload.capabilities {
+        if !roster::declare_capabilities(&mut state, sender_addr, capabilities).await? {
+            return Ok(());
+        }
+    }
+    let mut invite = None;
+    let access = password_check
+        .and_then(|_| match invited {
+            true => Ok(()),
+            false => rooms::check_password_unchanged(&state, room_id, password_hash.as_deref()),
+        })
+        .and_then(|_| rooms::check_schedule(&state, room_id))
+        .and_then(|_| rooms::check_lock(&state, sender_addr, room_id))
+        .and_then(|_| rooms::check_tenant(&state, sender_addr, room_id))
+        .and_then(|_| rooms::check_ban(&state, sender_addr, room_id))
+        .and_then(|_| rooms::check_claims(&state, sender_addr, payload.room_id.trim()))
+        .and_then(|_| match payload.invite_token.as_deref() {
+            Some(token) => rooms::check_invite(&state, room_id, token).map(|claims| invite = Some(claims)),
+            None => Ok(()),
+        });
+
+    // An invite stands in for admission only when it was issued to bypass the lobby
+    let bypasses_lobby = invite.as_ref().is_some_and(|claims| claims.bypass_lobby);
+    if access.is_ok() && !bypasses_lobby && rooms::requires_admission(&state, sender_addr, room_id) {
+        let join = WaitingJoin {
+            addr: sender_addr,
+            requested_room_id: payload.room_id.trim().to_string(),
+            invite,
+        };
+        let moderators = rooms::enter_lobby(&mut state, join, room_id);
+
+        if let Some(client) = state.clients.get(&sender_addr) {
+            let reply = SignalMessage::server("lobby-waiting", serde_json::json!({ "room_id": room_id }));
+            send_signal(client, &reply).await?;
+
+            let request = SignalMessage::server("join-request", serde_json::json!({
+                "room_id": room_id,
+                "client_id": client.client_id,
+            }));
+            for moderator in moderators.iter().filter_map(|addr| state.clients.get(addr)) {
+                send_signal(moderator, &request).await?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    enter_room(&mut state, sender_addr, room_id, payload.room_id.trim(), max_participants, access, invite).await
+}
+
+// Joins the room once access is settled and takes care of what comes with it: the role an invite
+// or the auth service grants, leaving the previous room and hanging up a direct call. Direct joins
+// and lobby admissions both finish here; `requested_room_id` is the id as the client named it.
+async fn enter_room(
+    state: &mut SignalingState,
+    addr: SocketAddr,
+    room_id: &str,
+    requested_room_id: &str,
+    max_participants: usize,
+    access: Result<(), JoinError>,
+    invite: Option<rooms::InviteClaims>
+) -> Result<(), Box<dyn std::error::Error>> {
+    let previous_room = state.clients.get(&addr).and_then(|client| client.room_id.clone());
+    let result = access
+        .and_then(|_| state.join_room(addr, room_id, max_participants));
+
+    // A join turned away (full room, say) leaves the invite's uses untouched
+    if let (Ok(()), Some(claims)) = (&result, &invite) {
+        rooms::redeem_invite(state, claims);
+    }
+
+    let invite_role = invite.and_then(|claims| claims.role);
+    if let (Ok(()), Some(role), Some(client)) = (&result, invite_role, state.clients.get_mut(&addr)) {
+        client.role = role;
+    }
+
+    // A role the auth service granted for this room applies unless an invite set one or the joiner
+    // just became host
+    if let (Ok(()), None, Some(client)) = (&result, invite_role, state.clients.get_mut(&addr)) {
+        let claimed_role = client.claims.as_ref().and_then(|claims| claims.role_in(requested_room_id));
+        if let (Some(role), false) = (claimed_role, client.role == Role::Host) {
+            client.role = role;
+        }
+    }
+
+    // Joining another room implicitly leaves the current one
+    if let (Ok(()), Some(previous_room)) = (&result, previous_room) {
+        if previous_room != room_id {
+            if let Some(client_id) = state.clients.get(&addr).map(|client| client.client_id.clone()) {
+                roster::announce_leave(state, &previous_room, &client_id).await?;
+            }
         }
+    }
+
+    // The room's peers take the place of a direct call's other end
+    if result.is_ok() {
+        calls::hang_up(state, addr, "joined-room").await?;
+    }
+
+    send_join_outcome(state, addr, room_id, result).await
+}
+
+pub async fn handle_join_decision(
+    payload: JoinDecisionPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    match rooms::admit_from_lobby(&mut state, sender_addr, &payload.client_id, payload.approved) {
+        Ok((room_id, join, decision)) => {
+            let max_participants = state.rooms.get(&room_id).map_or(1, |room| room.max_participants);
+            enter_room(&mut state, join.addr, &room_id, &join.requested_room_id, max_participants, decision, join.invite).await?;
+        }
+        Err(error) => {
+            let (code, message) = match error {
+                LobbyError::NotModerator => ("not-moderator", "Only hosts and moderators can admit lobby participants"),
+                LobbyError::NotWaiting => ("not-waiting", "Client is not waiting in your room's lobby"),
+            };
+            if let Some(host) = state.clients.get(&sender_addr) {
+                send_error(host, code, message, Some(&payload.client_id)).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_lock_room(
+    payload: LockRoomPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let room_id = match rooms::moderated_room_mut(&mut state, sender_addr) {
+        Some(room) => {
+            room.locked = payload.locked;
+            room.room_id.clone()
+        }
+        None => {
+            if let Some(client) = state.clients.get(&sender_addr) {
+                send_error(client, "not-moderator", "Only hosts and moderators can lock the room", None).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    rooms::persist_room(&state, &room_id);
+
+    let notice = SignalMessage::server("room-lock-changed", serde_json::json!({
+        "room_id": room_id,
+        "locked": payload.locked,
+    }));
+    send_to_room(&state, &room_id, &notice).await
+}
+
+pub async fn handle_create_invite(
+    payload: CreateInvitePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ttl = payload.expires_in_secs
+        .map(std::time::Duration::from_secs)
+        .unwrap_or_else(config::get_default_invite_ttl);
+    // A lifetime too long to add up is held to the last representable time rather than wrapping
+    // around into the past
+    let expires_at = i64::try_from(ttl.as_secs())
+        .ok()
+        .and_then(|secs| Utc::now().timestamp().checked_add(secs))
+        .unwrap_or(i64::MAX);
+
+    let mut state = state.lock().await;
+    let sender_role = state.clients.get(&sender_addr).map(|client| client.role).unwrap_or_default();
+
+    // Only the host may hand out elevated roles
+    if payload.role == Some(Role::Host) || (payload.role == Some(Role::Moderator) && sender_role != Role::Host) {
+        if let Some(client) = state.clients.get(&sender_addr) {
+            send_error(client, "invalid-role", "Invites cannot grant a role at or above your own", None).await?;
+        }
+        return Ok(());
+    }
+
+    let room_id = rooms::moderated_room_mut(&mut state, sender_addr).map(|room| room.room_id.clone());
+    let token = room_id
+        .as_deref()
+        .and_then(|room_id| rooms::issue_invite(&mut state, room_id, expires_at, payload.max_uses, payload.role, payload.bypass_lobby));
+
+    if let Some(client) = state.clients.get(&sender_addr) {
+        match token {
+            Some(token) => {
+                let reply = SignalMessage::server("invite-created", serde_json::json!({
+                    "room_id": room_id,
+                    "token": token,
+                    "expires_at": expires_at,
+                    "max_uses": payload.max_uses,
+                    "role": payload.role,
+                    "bypass_lobby": payload.bypass_lobby,
+                }));
+                send_signal(client, &reply).await?;
+            }
+            None => {
+                send_error(client, "not-moderator", "Only hosts and moderators can create invites", None).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_list_rooms(
+    payload: ListRoomsPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    // An empty payload lists public rooms only
+    let include_unlisted = admin::is_admin_token(payload.admin_token.as_deref());
+
+    let state = state.lock().await;
+    if let Some(client) = state.clients.get(&sender_addr) {
+        let reply = SignalMessage::server("room-list", serde_json::json!({
+            "rooms": rooms::list_rooms(&state, include_unlisted, client.tenant_id()),
+        }));
+        send_signal(client, &reply).await?;
+    }
+
+    Ok(())
+}
+
+async fn send_join_outcome(
+    state: &mut SignalingState,
+    addr: SocketAddr,
+    room_id: &str,
+    outcome: Result<(), JoinError>
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = match state.clients.get(&addr) {
+        Some(client) => client,
+        None => return Ok(()),
+    };
+
+    let reply = match outcome {
+        Ok(()) => SignalMessage::server("room-joined", serde_json::json!({
+            "room_id": room_id,
+            "client_id": client.client_id,
+            "config": state.rooms.get(room_id).map(|room| &room.config),
+        })),
+        Err(error) => return send_signal(client, &join_rejection(room_id, &error)).await,
     };
 
-    let verifying_key = match VerifyingKey::from_encoded_point(&encoded_point) {
-        Ok(key) => key,
-        Err(e) => {
-            eprintln!("[ERROR] Invalid verifying key: {}", e);
-            return false;
+    send_signal(client, &reply).await?;
+    roster::announce_join(state, addr).await
+}
+
+fn join_rejection(room_id: &str, error: &JoinError) -> SignalMessage {
+    match error {
+        JoinError::RoomFull { max_participants } => SignalMessage::server("room-full", serde_json::json!({
+            "room_id": room_id,
+            "max_participants": max_participants,
+        })),
+        JoinError::NotStarted { starts_at } => SignalMessage::server("meeting-not-started", serde_json::json!({
+            "room_id": room_id,
+            "starts_at": starts_at,
+        })),
+        _ => SignalMessage::server("join-rejected", serde_json::json!({
+            "room_id": room_id,
+            "reason": error.reason(),
+        })),
+    }
+}
+
+pub async fn handle_leave_room(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+
+    if let Some(room_id) = state.leave_room(sender_addr) {
+        if let Some(client) = state.clients.get(&sender_addr) {
+            let reply = SignalMessage::server("room-left", serde_json::json!({ "room_id": room_id }));
+            send_signal(client, &reply).await?;
+            let client_id = client.client_id.clone();
+            roster::announce_leave(&mut state, &room_id, &client_id).await?;
         }
+    }
+
+    Ok(())
+}
+
+// Everything leaving the server carries its signature, relayed messages included. Signals are
+// framed for the receiving connection here, straight from the typed message.
+pub fn encode(signal: &SignalMessage, encoding: WireEncoding) -> Result<Message, Box<dyn std::error::Error>> {
+    let mut signal = signal.clone();
+    crypto::server_identity::sign_outbound(&mut signal);
+    encoding.encode(&signal).map_err(|e| e as Box<dyn std::error::Error>)
+}
+
+pub async fn send_signal(
+    client: &Client,
+    signal: &SignalMessage
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut signal = signal.clone();
+    correlation::stamp(client.address, &mut signal);
+    let message = encode(&signal, client.encoding)?;
+    if let Err(e) = client.sender.send(message).await {
+        eprintln!("Send error to {}: {}", client.address, e);
+    }
+    Ok(())
+}
+
+// Passes a peer's message on to `client`, noting the outcome for the sender's ack
+pub async fn relay_to(
+    client: &Client,
+    signal: &SignalMessage
+) -> Result<(), Box<dyn std::error::Error>> {
+    let delivery = deliver_in_order(client, signal).await?;
+    if let Delivery::Failed(reason) = &delivery {
+        eprintln!("Send error to {}: {}", client.address, reason);
+    }
+    acks::record(delivery);
+    Ok(())
+}
+
+// Numbers the message for `client`, unless it was numbered for it as it came in, and hands over
+// whatever its reorder buffer lets out. A message held back behind a gap counts as queued.
+async fn deliver_in_order(client: &Client, signal: &SignalMessage) -> Result<Delivery, Box<dyn std::error::Error>> {
+    let mut relayed = signal.clone();
+    let ready = {
+        let Ok(mut reorder) = client.reorder.lock() else {
+            return Ok(Delivery::Failed("reorder-buffer-unavailable".to_string()));
+        };
+        let reserved = signal.relay_seq.filter(|_| signal.target_id.as_deref() == Some(client.client_id.as_str()));
+        let seq = reserved.unwrap_or_else(|| reorder.assign(&signal.sender_id));
+        relayed.relay_seq = Some(seq);
+        let message = encode(&relayed, client.encoding)?;
+        reorder.push(
+            &signal.sender_id,
+            seq,
+            message,
+            Instant::now(),
+            config::get_reorder_timeout(),
+            config::get_reorder_buffer_size()
+        )
     };
 
-    // Create signature object from raw bytes - use clone_from_slice to get owned values
-    let signature = match Signature::from_scalars(
-        FieldBytes::clone_from_slice(&signature[..32]),
-        FieldBytes::clone_from_slice(&signature[32..])
-    ) {
-        Ok(sig) => sig,
-        Err(e) => {
-            eprintln!("[ERROR] Failed to parse signature: {}", e);
-            return false;
+    let mut deliveries = Vec::new();
+    for message in ready {
+        deliveries.push(batching::deliver(client, message).await);
+    }
+    Ok(match deliveries.is_empty() {
+        true => Delivery::Queued,
+        false => Delivery::combine(deliveries),
+    })
+}
+
+// Server notices go to every member of the room, verified or not
+pub async fn send_to_room(
+    state: &SignalingState,
+    room_id: &str,
+    signal: &SignalMessage
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(room) = state.rooms.get(room_id) {
+        for client in room.members.iter().filter_map(|addr| state.clients.get(addr)) {
+            send_signal(client, signal).await?;
         }
+    }
+    Ok(())
+}
+
+// Offers and answers turned away by a security check go on the audit trail
+pub fn audit_rejection(state: &SignalingState, addr: SocketAddr, signal: &SignalMessage, code: &str, reason: &str) {
+    let replayed = code == NonceError::Replayed.code() || code == SequenceError::Stale.code();
+    let kind = if replayed { AuditKind::Replay } else { AuditKind::VerificationFailed };
+    let event = AuditEvent::new(kind)
+        .addr(addr)
+        .reason(reason)
+        .detail("code", code)
+        .detail("signal_type", &signal.signal_type);
+    audit::record(match state.clients.get(&addr) {
+        Some(client) => event.client(client),
+        None => event,
+    });
+}
+
+// False (after telling the client) when `signal`'s seq does not move past the last one accepted
+// from it; an out-of-order envelope counts as a verification failure
+pub async fn advance_sequence(
+    state: &mut SignalingState,
+    addr: SocketAddr,
+    signal: &SignalMessage
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Err(error) = state.advance_sequence(addr, signal.seq) else {
+        return Ok(true);
     };
+    eprintln!("Rejected {} from {}: {}{}", signal.signal_type, addr, error.code(), correlation::tag());
+    audit_rejection(state, addr, signal, error.code(), error.message());
+    if let Some(client) = state.clients.get(&addr) {
+        send_error(client, error.code(), error.message(), None).await?;
+    }
+    limits::record_verification_failure(state, addr).await;
+    Ok(false)
+}
 
-    // Verify the signature
-    use sha2::{Sha256, Digest};
-    let mut hasher = Sha256::new();
-    hasher.update(&message);
-    let digest = hasher.finalize();
-    
-    match verifying_key.verify(&digest, &signature) {
-        Ok(_) => true,
-        Err(e) => {
-            eprintln!("[ERROR] Signature verification failed: {}", e);
-            false
+pub async fn send_error(
+    client: &Client,
+    code: &str,
+    message: &str,
+    target_id: Option<&str>
+) -> Result<(), Box<dyn std::error::Error>> {
+    send_signal(client, &errors::error_signal(code, message, target_id, serde_json::Value::Null)).await
+}
+
+// An `error` whose context says what the server checked, so a client can find where its signing
+// went wrong instead of guessing
+pub async fn send_verification_error(
+    client: &Client,
+    error: &VerificationError
+) -> Result<(), Box<dyn std::error::Error>> {
+    let error = errors::error_signal(error.code(), &error.message, None, serde_json::json!({
+        "context": error.context,
+    }));
+    send_signal(client, &error).await
+}
+
+// Validates a certificate chain carried in the payload and swaps its key in for `public_key`.
+// The identity is only bound to the client once the signature has been checked.
+async fn check_certificate_chain(
+    payload: &mut SecureConnectionPayload,
+    state: &SharedState
+) -> Result<Option<CertIdentity>, String> {
+    let Some(chain) = &payload.certificate_chain else {
+        return Ok(None);
+    };
+    let (anchors, policy) = {
+        let state = state.lock().await;
+        (state.key_anchors.clone(), Arc::clone(&state.crypto_policy))
+    };
+    let Some(anchors) = anchors else {
+        return Err("Certificate chains are not accepted by this server".to_string());
+    };
+
+    let verified = anchors.verify_chain(chain, &policy, SystemTime::now())?;
+    if !payload.public_key.is_empty() && payload.public_key != verified.public_key {
+        return Err("public_key does not match the certificate".to_string());
+    }
+    payload.public_key = verified.public_key;
+    payload.algorithm = verified.algorithm;
+    Ok(Some(verified.identity))
+}
+
+// A connection keeps one certificate identity, whether it came from mutual TLS or a key certificate
+fn bind_certificate(
+    state: &mut SignalingState,
+    addr: SocketAddr,
+    certificate: Option<CertIdentity>
+) -> Result<(), String> {
+    let (Some(certificate), Some(client)) = (certificate, state.clients.get_mut(&addr)) else {
+        return Ok(());
+    };
+    match &client.certificate {
+        Some(bound) if bound.subject != certificate.subject => Err(format!(
+            "This connection is bound to certificate subject {}",
+            bound.subject
+        )),
+        Some(_) => Ok(()),
+        None => {
+            client.certificate = Some(certificate);
+            Ok(())
         }
     }
-}
\ No newline at end of file
+}
+
+// Returns the error code and message to send back when the signature does not hold up
+async fn check_signature(
+    verifier: &AsyncVerifier,
+    signal: &SignalMessage,
+    payload: &SecureConnectionPayload
+) -> Result<(), VerificationError> {
+    let version = payload.signature_version;
+    let min_version = config::get_min_signature_version().max(crypto::NONCE_SIGNATURE_VERSION);
+    if version < min_version || version > crypto::SIGNATURE_VERSION {
+        return Err(VerificationError::new(
+            VerificationCode::UnsupportedVersion,
+            format!(
+                "Signature version {} is not accepted; use version {} to {}",
+                version,
+                min_version,
+                crypto::SIGNATURE_VERSION
+            )
+        )
+        .with("signature_version", version)
+        .with("min_version", min_version)
+        .with("max_version", crypto::SIGNATURE_VERSION));
+    }
+
+    let message = crypto::signed_message(signal, payload).ok_or_else(|| {
+        VerificationError::new(VerificationCode::DataMismatch, "signed_data does not describe the relayed message")
+            .with("signal_type", signal.signal_type.clone())
+    })?;
+    verifier
+        .verify(VerificationRequest::new(payload.algorithm, &message, &payload.signature, &payload.public_key))
+        .await
+        .map_err(|error| error.with("signature_version", version))?;
+
+    // Only trusted once the signature covering it checks out
+    crypto::check_freshness(
+        payload.timestamp,
+        Utc::now().timestamp(),
+        config::get_signed_max_age(),
+        config::get_clock_skew()
+    )
+    .map_err(|error| {
+        VerificationError::new(VerificationCode::Freshness(error), error.message())
+            .with("timestamp", payload.timestamp)
+            .with("server_time", Utc::now().timestamp())
+    })
+}
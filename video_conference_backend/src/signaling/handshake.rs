@@ -0,0 +1,122 @@
+use crate::models::WireEncoding;
+use crate::noise;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::HashSet;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+// Clients name the signaling protocol version they speak when they upgrade. The Noise
+// subprotocol carries the same protocol inside an encrypted channel, and the MessagePack, CBOR and
+// Protobuf ones frame it in binary instead of JSON text.
+pub const SUBPROTOCOL: &str = "peer-conference.v1";
+pub const MSGPACK_SUBPROTOCOL: &str = "peer-conference.v1.msgpack";
+pub const CBOR_SUBPROTOCOL: &str = "peer-conference.v1.cbor";
+pub const PROTOBUF_SUBPROTOCOL: &str = "peer-conference.v1.proto";
+
+// The subprotocol a handshake settled on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subprotocol {
+    V1,
+    MessagePack,
+    Cbor,
+    Protobuf,
+    Noise,
+}
+
+impl Subprotocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subprotocol::V1 => SUBPROTOCOL,
+            Subprotocol::MessagePack => MSGPACK_SUBPROTOCOL,
+            Subprotocol::Cbor => CBOR_SUBPROTOCOL,
+            Subprotocol::Protobuf => PROTOBUF_SUBPROTOCOL,
+            Subprotocol::Noise => noise::SUBPROTOCOL,
+        }
+    }
+
+    pub fn encoding(&self) -> WireEncoding {
+        match self {
+            Subprotocol::MessagePack => WireEncoding::MessagePack,
+            Subprotocol::Cbor => WireEncoding::Cbor,
+            Subprotocol::Protobuf => WireEncoding::Protobuf,
+            Subprotocol::V1 | Subprotocol::Noise => WireEncoding::Json,
+        }
+    }
+}
+
+// Holds the upgrade request's Sec-WebSocket-* headers to RFC 6455 beyond what tungstenite checks
+// and picks the subprotocol to answer with: Noise when offered and available, then MessagePack,
+// then CBOR, then Protobuf, then v1.
+// Requests that offer neither are turned away when `required`, which also keeps out scanners that
+// speak bare WebSocket.
+#[allow(clippy::result_large_err)]
+pub fn negotiate(request: &Request, noise_available: bool, required: bool) -> Result<Option<Subprotocol>, ErrorResponse> {
+    let key = single_header(request, "Sec-WebSocket-Key")?;
+    if STANDARD.decode(key.trim()).map(|key| key.len()) != Ok(16) {
+        return Err(bad_request("Sec-WebSocket-Key must be 16 base64-encoded bytes"));
+    }
+    if single_header(request, "Sec-WebSocket-Version")?.trim() != "13" {
+        return Err(bad_request("Only WebSocket version 13 is supported"));
+    }
+
+    let offered = offered_subprotocols(request)?;
+    if noise_available && offered.contains(noise::SUBPROTOCOL) {
+        return Ok(Some(Subprotocol::Noise));
+    }
+    if offered.contains(MSGPACK_SUBPROTOCOL) {
+        return Ok(Some(Subprotocol::MessagePack));
+    }
+    if offered.contains(CBOR_SUBPROTOCOL) {
+        return Ok(Some(Subprotocol::Cbor));
+    }
+    if offered.contains(PROTOBUF_SUBPROTOCOL) {
+        return Ok(Some(Subprotocol::Protobuf));
+    }
+    if offered.contains(SUBPROTOCOL) {
+        return Ok(Some(Subprotocol::V1));
+    }
+    if required {
+        eprintln!("Rejected WebSocket upgrade offering subprotocols {:?}", offered);
+        return Err(bad_request(&format!("Request the {} subprotocol", SUBPROTOCOL)));
+    }
+    Ok(None)
+}
+
+#[allow(clippy::result_large_err)]
+fn single_header<'a>(request: &'a Request, name: &str) -> Result<&'a str, ErrorResponse> {
+    let mut values = request.headers().get_all(name).iter();
+    match (values.next(), values.next()) {
+        (Some(value), None) => value.to_str().map_err(|_| bad_request(&format!("{} is not valid text", name))),
+        (None, _) => Err(bad_request(&format!("{} is missing", name))),
+        (Some(_), Some(_)) => Err(bad_request(&format!("{} must be sent once", name))),
+    }
+}
+
+// Every Sec-WebSocket-Protocol entry must be a non-empty HTTP token, offered once
+#[allow(clippy::result_large_err)]
+fn offered_subprotocols(request: &Request) -> Result<HashSet<&str>, ErrorResponse> {
+    let mut offered = HashSet::new();
+    for value in request.headers().get_all("Sec-WebSocket-Protocol") {
+        let value = value.to_str().map_err(|_| bad_request("Sec-WebSocket-Protocol is not valid text"))?;
+        for subprotocol in value.split(',').map(str::trim) {
+            if subprotocol.is_empty() || !subprotocol.bytes().all(is_token_byte) {
+                return Err(bad_request("Sec-WebSocket-Protocol entries must be tokens"));
+            }
+            if !offered.insert(subprotocol) {
+                return Err(bad_request("Sec-WebSocket-Protocol entries must not repeat"));
+            }
+        }
+    }
+    Ok(offered)
+}
+
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+fn bad_request(message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = StatusCode::BAD_REQUEST;
+    response
+}
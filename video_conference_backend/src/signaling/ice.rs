@@ -0,0 +1,126 @@
+use crate::config;
+use crate::crypto::{self, VerificationCode, VerificationError, VerificationRequest};
+use crate::models::message::CandidateSignature;
+use crate::models::{RoomConfig, SignalMessage};
+use crate::rooms::{CandidatePolicy, CandidateRejection};
+use crate::signaling::acks::{self, Delivery};
+use crate::signaling::correlation;
+use crate::signaling::handlers::{advance_sequence, audit_rejection, relay_signal, send_error, send_verification_error};
+use crate::signaling::{limits, sdp};
+use crate::signaling::state::{SharedState, SignalingState};
+use chrono::Utc;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+// Candidates are only taken from a connection whose offer or answer verified, and a signed
+// candidate must verify against the key that offer bound. Without this, any connection in the
+// room could steer a peer's media to an address of its choosing.
+pub async fn handle_ice_candidate(
+    signal: &SignalMessage,
+    signed: CandidateSignature,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let checked = {
+        let state = state.lock().await;
+        let Some(client) = state.clients.get(&sender_addr) else {
+            return Ok(());
+        };
+        let public_key = match &client.public_key {
+            Some(public_key) if client.verified => public_key.clone(),
+            _ => {
+                audit_rejection(&state, sender_addr, signal, "unverified-sender", "ICE candidate before a verified offer or answer");
+                return send_error(client, "unverified-sender", "Send a verified offer or answer before ICE candidates", None).await;
+            }
+        };
+        if signed.signature.is_none() && config::get_require_signed_ice() {
+            audit_rejection(&state, sender_addr, signal, "unsigned-candidate", "ICE candidate without a signature");
+            return send_error(client, "unsigned-candidate", "ICE candidates must be signed", None).await;
+        }
+        signed.signature.is_some().then(|| (state.async_verifier(), public_key))
+    };
+
+    if let Some((verifier, public_key)) = checked {
+        if let Err(error) = check_candidate(signal, &signed, &public_key, &verifier).await {
+            eprintln!("Rejected ICE candidate from {}: {} ({}){}", sender_addr, error.code(), error, correlation::tag());
+            let mut state = state.lock().await;
+            audit_rejection(&state, sender_addr, signal, error.code(), &error.message);
+            if let Some(client) = state.clients.get(&sender_addr) {
+                send_verification_error(client, &error).await?;
+            }
+            limits::record_verification_failure(&mut state, sender_addr).await;
+            return Ok(());
+        }
+
+        let mut state = state.lock().await;
+        let nonce = signed.nonce.as_deref().unwrap_or_default();
+        if let Err(error) = state.nonces.check(&public_key, nonce) {
+            audit_rejection(&state, sender_addr, signal, error.code(), error.message());
+            if let Some(client) = state.clients.get(&sender_addr) {
+                send_error(client, error.code(), error.message(), None).await?;
+            }
+            limits::record_verification_failure(&mut state, sender_addr).await;
+            return Ok(());
+        }
+    }
+    let signal = {
+        let mut state = state.lock().await;
+        if !advance_sequence(&mut state, sender_addr, signal).await? {
+            return Ok(());
+        }
+        // Filtered candidates are dropped without an error; the sender's ack says why
+        if let Some(rejection) = filter_candidate(signal, sender_addr, &state) {
+            println!("Dropped ICE candidate from {}: {}{}", sender_addr, rejection.code(), correlation::tag());
+            acks::record(Delivery::Failed(rejection.code().to_string()));
+            return Ok(());
+        }
+        sdp::sanitize(signal, sender_addr, &state)
+    };
+
+    relay_signal(&signal, sender_addr, Arc::clone(&state)).await
+}
+
+// Applies the sender's room candidate policy, or the server-wide one outside a room
+fn filter_candidate(signal: &SignalMessage, sender_addr: SocketAddr, state: &SignalingState) -> Option<CandidateRejection> {
+    let payload: serde_json::Value = serde_json::from_str(&signal.payload).ok()?;
+    let candidate = payload.get("candidate")?.as_str()?;
+    let policy = match state.client_room(sender_addr) {
+        Some(room) => CandidatePolicy::for_room(&room.config),
+        None => CandidatePolicy::for_room(&RoomConfig::default()),
+    };
+    policy.check(candidate).err()
+}
+
+async fn check_candidate(
+    signal: &SignalMessage,
+    signed: &CandidateSignature,
+    public_key: &[u8],
+    verifier: &crypto::AsyncVerifier
+) -> Result<(), VerificationError> {
+    let (Some(signature), Some(_)) = (&signed.signature, &signed.nonce) else {
+        return Err(VerificationError::new(VerificationCode::DataMismatch, "Signed ICE candidates must carry a nonce"));
+    };
+    if !matches!(signed.signature_version, 2 | 3) {
+        return Err(VerificationError::new(VerificationCode::UnsupportedVersion, "ICE candidates are signed with version 2 or 3")
+            .with("signature_version", signed.signature_version));
+    }
+    let message = crypto::signed_envelope(signal, signed.signature_version, signed.signed_data.as_deref()).ok_or_else(|| {
+        VerificationError::new(VerificationCode::DataMismatch, "signed_data does not describe the relayed candidate")
+            .with("signal_type", signal.signal_type.clone())
+    })?;
+    verifier
+        .verify(VerificationRequest::new(signed.algorithm, &message, signature, public_key))
+        .await?;
+
+    crypto::check_freshness(
+        signed.timestamp,
+        Utc::now().timestamp(),
+        config::get_signed_max_age(),
+        config::get_clock_skew()
+    )
+    .map_err(|error| {
+        VerificationError::new(VerificationCode::Freshness(error), error.message())
+            .with("timestamp", signed.timestamp)
+            .with("server_time", Utc::now().timestamp())
+    })
+}
@@ -0,0 +1,160 @@
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::crypto::{self, VerificationRequest};
+use crate::models::message::RotateKeyPayload;
+use crate::models::SignalMessage;
+use crate::pinning;
+use crate::signaling::correlation;
+use crate::signaling::handlers::{send_error, send_signal, send_to_room, send_verification_error};
+use crate::signaling::limits;
+use crate::signaling::state::{SharedState, SignalingState};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+// The exact bytes both keys sign to rotate from `old_key` to `new_key`
+pub fn rotation_message(client_id: &str, old_key: &[u8], new_key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    crypto::canonicalize(&serde_json::json!({
+        "type": "rotate-key",
+        "client_id": client_id,
+        "old_public_key": STANDARD.encode(old_key),
+        "new_public_key": STANDARD.encode(new_key),
+        "nonce": STANDARD.encode(nonce),
+    }))
+    .into_bytes()
+}
+
+// Replaces the key bound to the connection and pinned to its identity, then tells the room so
+// peers re-verify against the new key
+pub async fn handle_rotate_key(
+    payload: RotateKeyPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (verifier, client, old_key) = {
+        let state = state.lock().await;
+        let Some(client) = state.clients.get(&sender_addr).cloned() else {
+            return Ok(());
+        };
+        let Some(old_key) = client.public_key.clone() else {
+            return send_error(&client, "no-key-bound", "There is no key on this connection to rotate", None).await;
+        };
+
+        // A compromised key must not be able to hand its identity on
+        if state.revoked.is_revoked(&old_key) || state.revoked.is_revoked(&payload.new_public_key) {
+            audit::record(AuditEvent::new(AuditKind::VerificationFailed).client(&client).reason("key rotation with a revoked key"));
+            return send_error(&client, "key-revoked", "This public key has been revoked", None).await;
+        }
+        (state.async_verifier(), client, old_key)
+    };
+
+    // Both signatures are checked together, off the reactor and without the state lock
+    let message = rotation_message(&client.client_id, &old_key, &payload.new_public_key, &payload.nonce);
+    let result = verifier
+        .verify_all(vec![
+            VerificationRequest::new(payload.algorithm, &message, &payload.signature, &old_key),
+            VerificationRequest::new(payload.algorithm, &message, &payload.proof, &payload.new_public_key),
+        ])
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>();
+    if let Err(reason) = result {
+        eprintln!("Rejected key rotation from {}: {}{}", sender_addr, reason, correlation::tag());
+        audit::record(AuditEvent::new(AuditKind::VerificationFailed).client(&client).reason(reason.to_string()).detail("code", reason.code()).detail("signal_type", "rotate-key"));
+        send_verification_error(&client, &reason).await?;
+        limits::record_verification_failure(&mut *state.lock().await, sender_addr).await;
+        return Ok(());
+    }
+
+    let mut state = state.lock().await;
+    // The key may have changed or been revoked while the signatures were being checked
+    if state.clients.get(&sender_addr).and_then(|current| current.public_key.as_ref()) != Some(&old_key) {
+        return send_error(&client, "no-key-bound", "The key on this connection changed during rotation", None).await;
+    }
+    if state.revoked.is_revoked(&old_key) || state.revoked.is_revoked(&payload.new_public_key) {
+        return send_error(&client, "key-revoked", "This public key has been revoked", None).await;
+    }
+    if let Err(error) = state.nonces.check(&old_key, &payload.nonce) {
+        audit::record(AuditEvent::new(AuditKind::Replay).client(&client).reason(error.message()).detail("signal_type", "rotate-key"));
+        return send_error(&client, error.code(), error.message(), None).await;
+    }
+    if let Some(key_pins) = &state.key_pins {
+        let identity = pinning::identity(&client);
+        if let Err(error) = pinning::rotate_pin(key_pins.as_ref(), &identity, &old_key, &payload.new_public_key) {
+            return send_error(&client, error.code(), error.message(), None).await;
+        }
+    }
+
+    if let Some(client) = state.clients.get_mut(&sender_addr) {
+        client.public_key = Some(payload.new_public_key.clone());
+    }
+    println!("{} rotated its public key", sender_addr);
+    audit::record(
+        AuditEvent::new(AuditKind::KeyRotated)
+            .client(&client)
+            .detail("previous_public_key", &old_key)
+            .detail("public_key", &payload.new_public_key)
+    );
+
+    let update = SignalMessage::server("key-rotated", serde_json::json!({
+        "client_id": client.client_id,
+        "public_key": payload.new_public_key,
+        "previous_public_key": old_key,
+        "algorithm": payload.algorithm,
+    }));
+    match &client.room_id {
+        Some(room_id) => send_to_room(&state, room_id, &update).await,
+        None => send_signal(&client, &update).await,
+    }
+}
+
+// Disconnects clients bound to a revoked key and drops suspended sessions holding one.
+// Returns how many clients were disconnected.
+pub async fn enforce_revocations(state: &mut SignalingState) -> usize {
+    let revoked = state.revoked.clone();
+    state.suspended.retain(|_, session| {
+        !session.public_key.as_ref().is_some_and(|key| revoked.is_revoked(key))
+    });
+
+    let mut disconnected = 0;
+    for client in state.clients.values() {
+        if client.public_key.as_ref().is_some_and(|key| revoked.is_revoked(key)) {
+            if let Err(e) = send_error(client, "key-revoked", "This public key has been revoked", None).await {
+                eprintln!("Failed to notify {} of revocation: {}", client.address, e);
+            }
+            client.disconnect("key revoked").await;
+            disconnected += 1;
+        }
+    }
+    disconnected
+}
+
+// Re-reads the revocation file whenever it changes and applies it to connected clients
+pub async fn watch_revocations(state: SharedState, path: PathBuf, interval: Duration) {
+    let modified = |path: &PathBuf| -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    };
+    let mut last_modified = modified(&path);
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        let current = modified(&path);
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        match crypto::RevocationList::from_file(&path) {
+            Ok(revoked) => {
+                let mut state = state.lock().await;
+                state.revoked = revoked;
+                let disconnected = enforce_revocations(&mut state).await;
+                println!("Reloaded {} revoked keys; disconnected {} clients", state.revoked.len(), disconnected);
+            }
+            Err(e) => eprintln!("Failed to reload revocation list: {}", e),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::config;
+use crate::models::{schema, Payload, SignalKind, SignalMessage};
+use crate::ratelimit::{LockoutPolicy, RateLimit, RateLimiter};
+use crate::signaling::acks::{self, Delivery};
+use crate::signaling::correlation;
+use crate::signaling::errors;
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::state::{SharedState, SignalingState};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+// False when the signal is over the budget of the client, of its signal type or of its address. The client is warned
+// on the first refused signal of a window and disconnected if it keeps going.
+pub async fn check_rate_limit(
+    kind: SignalKind,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let now = Instant::now();
+    let window = config::get_rate_limit_window();
+
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get_mut(&addr) else {
+        return Ok(false);
+    };
+    let client_result = client.rate_limiter
+        .get_or_insert_with(|| RateLimiter::new(RateLimit::per_client()).with_signal_limits(RateLimit::per_signal_type()))
+        .check(kind, now, window);
+    // The address budget is only charged once the client's own allows the signal, so a throttled
+    // client can't drain it for its neighbours
+    let (scope, result) = match client_result {
+        Ok(()) => {
+            // Address budgets outlive their connections, so reconnecting doesn't buy a fresh
+            // burst; they are forgotten once refilled, checked whenever a new address turns up
+            if !state.ip_rate_limits.contains_key(&addr.ip()) {
+                state.ip_rate_limits.retain(|_, limiter| !limiter.is_idle(now, window));
+            }
+            ("ip", state.ip_rate_limits
+                .entry(addr.ip())
+                .or_insert_with(|| RateLimiter::new(RateLimit::per_ip()))
+                .check(kind, now, window))
+        }
+        Err(violation) if violation.per_signal_type => ("signal-type", Err(violation)),
+        Err(violation) => ("client", Err(violation)),
+    };
+    let Err(violation) = result else {
+        return Ok(true);
+    };
+    acks::record(Delivery::Failed("rate-limited".to_string()));
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(false);
+    };
+
+    if violation.count >= config::get_rate_limit_max_violations() {
+        eprintln!("Disconnecting {} for exceeding its {} rate limit", addr, scope);
+        audit::record(
+            AuditEvent::new(AuditKind::RateLimited)
+                .client(client)
+                .detail("scope", scope)
+                .detail("signal_type", kind.name())
+                .detail("violations", violation.count)
+        );
+        client.disconnect("rate limit exceeded").await;
+    } else if violation.count == 1 {
+        let warning = SignalMessage::server("rate-limited", serde_json::json!({
+            "scope": scope,
+            "signal_type": kind.name(),
+            "retry_after_ms": violation.retry_after.as_millis() as u64,
+        }));
+        send_signal(client, &warning).await?;
+    }
+    Ok(false)
+}
+
+// False (after telling the client where) when the payload does not match its signal type's
+// schema, so malformed messages are turned away before any signature is checked
+pub async fn check_payload(
+    kind: SignalKind,
+    payload: &Payload,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Err(error) = schema::validate(kind, payload) else {
+        return Ok(true);
+    };
+    eprintln!("Rejected {} from {}: {}{}", kind.name(), addr, error.message(), correlation::tag());
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(false);
+    };
+    let message = errors::error_signal(error.code(), &error.message(), None, serde_json::json!({
+        "context": {
+            "signal_type": kind.name(),
+            "violations": error.violations,
+        },
+    }));
+    send_signal(client, &message).await?;
+    Ok(false)
+}
+
+// Tells the client why before its connection is dropped for an oversized signal
+pub async fn reject_oversized(addr: SocketAddr, len: usize, max_payload_size: usize, state: &SharedState) {
+    eprintln!("Disconnecting {} for a {}-byte message", addr, len);
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return;
+    };
+    let message = format!("Messages are limited to {} bytes", max_payload_size);
+    if let Err(e) = send_error(client, "message-too-large", &message, None).await {
+        eprintln!("Send error to {}: {}", addr, e);
+    }
+    client.disconnect("message too large").await;
+}
+
+// Counts a failed signature or challenge against the client and its address. Once either has
+// failed too often within the window the client is told how long it is locked out and dropped;
+// the address is refused at accept time until the lockout ends.
+pub async fn record_verification_failure(state: &mut SignalingState, addr: SocketAddr) {
+    let now = Instant::now();
+    let policy = LockoutPolicy::from_config();
+    let ip_lockout = state.lockouts.record(addr.ip(), now, &policy);
+    let Some(client) = state.clients.get_mut(&addr) else {
+        return;
+    };
+    let client_lockout = client.verification_failures.record(now, &policy);
+    let (scope, duration) = match (client_lockout, ip_lockout) {
+        (_, Some(duration)) => ("ip", duration),
+        (Some(duration), None) => ("client", duration),
+        (None, None) => return,
+    };
+
+    eprintln!("Locking out {} ({}) for {}s after repeated verification failures", addr, scope, duration.as_secs());
+    audit::record(
+        AuditEvent::new(AuditKind::LockedOut)
+            .client(client)
+            .detail("scope", scope)
+            .detail("duration_secs", duration.as_secs())
+    );
+    let notice = errors::error_signal("locked-out", "Too many failed verifications; try again later", None, serde_json::json!({
+        "retry_after_secs": duration.as_secs(),
+    }));
+    if let Err(e) = send_signal(client, &notice).await {
+        eprintln!("Send error to {}: {}", addr, e);
+    }
+    client.disconnect("locked out").await;
+}
@@ -1,5 +1,39 @@
+pub mod abuse;
+pub mod acks;
+pub mod admin;
+pub mod auth;
+pub mod batching;
+pub mod calls;
+pub mod chat;
+pub mod correlation;
+pub mod dedup;
+pub mod deflate;
+pub mod documents;
+pub mod e2ee;
+pub mod files;
+pub mod errors;
 pub mod handlers;
+pub mod handshake;
+pub mod ice;
+pub mod keys;
+pub mod limits;
+pub mod moderation;
+pub mod ordering;
+pub mod polls;
+pub mod protocol;
+pub mod questions;
+pub mod reactions;
+pub mod renegotiation;
+pub mod roster;
+pub mod screenshare;
+pub mod sdp;
+pub mod sequenced;
 pub mod server;
+pub mod state;
+pub mod turn;
+pub mod webauthn;
+pub mod whiteboard;
 
 pub use handlers::*;
-pub use server::*;
\ No newline at end of file
+pub use server::*;
+pub use state::*;
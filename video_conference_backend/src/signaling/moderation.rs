@@ -0,0 +1,181 @@
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::models::message::{KickPayload, MuteAllPayload, MuteRequestPayload, RoleChangePayload};
+use crate::models::{Role, SignalMessage};
+use crate::rooms;
+use crate::signaling::handlers::{send_error, send_signal, send_to_room};
+use crate::signaling::roster;
+use crate::signaling::state::SharedState;
+use std::net::SocketAddr;
+
+pub async fn handle_kick(
+    payload: KickPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let target_addr = match rooms::moderation_target(&state, sender_addr, &payload.client_id) {
+        Ok(target_addr) => target_addr,
+        Err(error) => {
+            if let Some(client) = state.clients.get(&sender_addr) {
+                send_error(client, error.code(), error.message(), Some(&payload.client_id)).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    let Some(target) = state.clients.get(&target_addr).cloned() else {
+        return Ok(());
+    };
+    let Some(room_id) = target.room_id.clone() else {
+        return Ok(());
+    };
+
+    if payload.ban {
+        if let Some(room) = state.rooms.get_mut(&room_id) {
+            room.banned_ips.insert(target_addr.ip());
+            if let Some(public_key) = &target.public_key {
+                room.ban_key(public_key);
+            }
+        }
+    }
+
+    let notice = SignalMessage::server("kicked", serde_json::json!({
+        "room_id": room_id,
+        "reason": payload.reason,
+        "banned": payload.ban,
+    }));
+    send_signal(&target, &notice).await?;
+
+    state.leave_room(target_addr);
+    roster::announce_leave(&mut state, &room_id, &target.client_id).await?;
+    target.disconnect("kicked").await;
+
+    println!("{} removed {} from room {} (banned: {})", sender_addr, target_addr, room_id, payload.ban);
+    let moderator_id = state.clients.get(&sender_addr).map(|client| client.client_id.clone());
+    let kind = if payload.ban { AuditKind::Ban } else { AuditKind::Kick };
+    let mut event = AuditEvent::new(kind)
+        .client(&target)
+        .room(&room_id)
+        .detail("moderator_id", moderator_id);
+    if let Some(reason) = &payload.reason {
+        event = event.reason(reason);
+    }
+    audit::record(event);
+    Ok(())
+}
+
+pub async fn handle_mute_request(
+    payload: MuteRequestPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let target_addr = match rooms::moderation_target(&state, sender_addr, &payload.client_id) {
+        Ok(target_addr) => target_addr,
+        Err(error) => {
+            if let Some(client) = state.clients.get(&sender_addr) {
+                send_error(client, error.code(), error.message(), Some(&payload.client_id)).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    let moderator_id = state.clients.get(&sender_addr).map(|client| client.client_id.clone());
+    let Some(target) = state.clients.get_mut(&target_addr) else {
+        return Ok(());
+    };
+    target.muted_by_host = payload.muted;
+
+    let request = SignalMessage::server("mute-request", serde_json::json!({
+        "muted": payload.muted,
+        "by": moderator_id,
+    }));
+    send_signal(target, &request).await?;
+
+    let update = SignalMessage::server("mute-state", serde_json::json!({
+        "client_id": target.client_id,
+        "muted_by_host": payload.muted,
+    }));
+    if let Some(room_id) = target.room_id.clone() {
+        send_to_room(&state, &room_id, &update).await?;
+    }
+
+    Ok(())
+}
+
+// Applies to every member the moderator outranks; other moderators and the host are left alone
+pub async fn handle_mute_all(
+    payload: MuteAllPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(moderator) = state.clients.get(&sender_addr).cloned() else {
+        return Ok(());
+    };
+    let Some(room_id) = moderator.room_id.clone().filter(|_| moderator.role.can_moderate()) else {
+        let error = rooms::ModerationError::NotModerator;
+        return send_error(&moderator, error.code(), error.message(), None).await;
+    };
+
+    let targets: Vec<SocketAddr> = state.room_peers(sender_addr)
+        .into_iter()
+        .filter(|peer| moderator.role.outranks(&peer.role))
+        .map(|peer| peer.address)
+        .collect();
+
+    let request = SignalMessage::server("mute-request", serde_json::json!({
+        "muted": payload.muted,
+        "by": moderator.client_id,
+    }));
+    let mut muted_ids = Vec::new();
+    for addr in targets {
+        if let Some(target) = state.clients.get_mut(&addr) {
+            target.muted_by_host = payload.muted;
+            muted_ids.push(target.client_id.clone());
+            send_signal(target, &request).await?;
+        }
+    }
+
+    let update = SignalMessage::server("mute-all", serde_json::json!({
+        "muted": payload.muted,
+        "by": moderator.client_id,
+        "client_ids": muted_ids,
+    }));
+    send_to_room(&state, &room_id, &update).await
+}
+
+// `promote` makes the target a co-host, `demote` turns a co-host back into a participant
+pub async fn handle_role_change(
+    payload: RoleChangePayload,
+    sender_addr: SocketAddr,
+    state: SharedState,
+    role: Role
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let target_addr = match rooms::set_role(&mut state, sender_addr, &payload.client_id, role) {
+        Ok(target_addr) => target_addr,
+        Err(error) => {
+            if let Some(client) = state.clients.get(&sender_addr) {
+                send_error(client, error.code(), error.message(), Some(&payload.client_id)).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    let Some(target) = state.clients.get(&target_addr) else {
+        return Ok(());
+    };
+    let Some(room_id) = target.room_id.clone() else {
+        return Ok(());
+    };
+    let host_id = state.clients.get(&sender_addr).map(|client| client.client_id.clone());
+
+    let update = SignalMessage::server("role-changed", serde_json::json!({
+        "room_id": room_id,
+        "client_id": target.client_id,
+        "role": role,
+        "by": host_id,
+    }));
+    send_to_room(&state, &room_id, &update).await
+}
@@ -0,0 +1,221 @@
+use crate::config;
+use crate::models::{SignalKind, SignalMessage};
+use crate::signaling::{acks, batching};
+use crate::signaling::state::{SharedState, SignalingState};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+// One recipient's view of what each sender has relayed to it. Messages are numbered per sender
+// and handed to the recipient's connection in that order; any that turn up ahead of a gap are
+// held until it fills, the hold has lasted `timeout`, or more than `capacity` are waiting, at
+// which point the gap is skipped.
+#[derive(Debug, Default)]
+pub struct ReorderBuffer {
+    senders: HashMap<String, SenderStream>,
+}
+
+#[derive(Debug, Default)]
+struct SenderStream {
+    next_assigned: u64,
+    next_released: u64,
+    // A number that was given up on is held as None, so it stops being waited for
+    held: BTreeMap<u64, (Instant, Option<Message>)>,
+}
+
+impl SenderStream {
+    // Everything from `next_released` on that is no longer waiting on a gap, in order
+    fn release(&mut self, now: Instant, timeout: Duration, capacity: usize) -> Vec<Message> {
+        let mut ready = Vec::new();
+        loop {
+            while let Some((_, message)) = self.held.remove(&self.next_released) {
+                ready.extend(message);
+                self.next_released += 1;
+            }
+            let Some((&first, (held_since, _))) = self.held.iter().next() else {
+                break;
+            };
+            let overdue = now.saturating_duration_since(*held_since) >= timeout;
+            if !overdue && self.held.len() <= capacity {
+                break;
+            }
+            // The missing messages are given up on
+            self.next_released = first;
+        }
+        ready
+    }
+}
+
+impl ReorderBuffer {
+    // The relay_seq for `sender_id`'s next message to this recipient
+    pub fn assign(&mut self, sender_id: &str) -> u64 {
+        let stream = self.senders.entry(sender_id.to_string()).or_default();
+        let seq = stream.next_assigned;
+        stream.next_assigned += 1;
+        seq
+    }
+
+    // Takes message `seq` from `sender_id` and returns whatever can now go out, in order
+    pub fn push(
+        &mut self,
+        sender_id: &str,
+        seq: u64,
+        message: Message,
+        now: Instant,
+        timeout: Duration,
+        capacity: usize
+    ) -> Vec<Message> {
+        let stream = self.senders.entry(sender_id.to_string()).or_default();
+        if seq < stream.next_released {
+            return Vec::new();
+        }
+        stream.held.insert(seq, (now, Some(message)));
+        stream.release(now, timeout, capacity)
+    }
+
+    // Stops waiting for message `seq` from `sender_id`, which is not coming, and returns whatever
+    // that lets out. Does nothing if it already arrived.
+    pub fn skip(
+        &mut self,
+        sender_id: &str,
+        seq: u64,
+        now: Instant,
+        timeout: Duration,
+        capacity: usize
+    ) -> Vec<Message> {
+        let Some(stream) = self.senders.get_mut(sender_id) else {
+            return Vec::new();
+        };
+        if seq < stream.next_released {
+            return Vec::new();
+        }
+        stream.held.entry(seq).or_insert((now, None));
+        stream.release(now, timeout, capacity)
+    }
+
+    // Whatever has waited `timeout` behind a gap, from every sender
+    pub fn release_overdue(&mut self, now: Instant, timeout: Duration, capacity: usize) -> Vec<Message> {
+        self.senders
+            .values_mut()
+            .flat_map(|stream| stream.release(now, timeout, capacity))
+            .collect()
+    }
+
+    // Stops waiting on `sender_id`'s messages numbered below `until` (all of them when None): drops
+    // the held ones `stale` picks out and returns the rest, in order. Messages numbered from
+    // `until` on are still released in their turn.
+    pub fn flush(
+        &mut self,
+        sender_id: &str,
+        until: Option<u64>,
+        mut stale: impl FnMut(&Message) -> bool
+    ) -> Vec<Message> {
+        let Some(stream) = self.senders.get_mut(sender_id) else {
+            return Vec::new();
+        };
+        let until = until.unwrap_or(stream.next_assigned).max(stream.next_released);
+        let later = stream.held.split_off(&until);
+        let earlier = std::mem::replace(&mut stream.held, later);
+        stream.next_released = until;
+        earlier
+            .into_values()
+            .filter_map(|(_, message)| message)
+            .filter(|message| !stale(message))
+            .collect()
+    }
+
+    // Drops `sender_id`'s stream and returns what it still held, in order. A sender that comes
+    // back is numbered from zero again.
+    pub fn forget(&mut self, sender_id: &str) -> Vec<Message> {
+        self.senders
+            .remove(sender_id)
+            .map(|stream| stream.held.into_values().filter_map(|(_, message)| message).collect())
+            .unwrap_or_default()
+    }
+}
+
+// A relay_seq taken for a message when it came in, before any handling that could let a later one
+// from the same sender overtake it
+#[derive(Debug)]
+pub struct Reservation {
+    recipient: SocketAddr,
+    sender_id: String,
+    seq: u64,
+}
+
+// Numbers a relayed signal addressed to a peer for that peer as it comes off the sender's
+// connection. Any relay_seq the sender put on it is dropped.
+pub async fn reserve(signal: &mut SignalMessage, addr: SocketAddr, state: &SharedState) -> Option<Reservation> {
+    signal.relay_seq = None;
+    if !acks::is_relayed(SignalKind::of(&signal.signal_type)) {
+        return None;
+    }
+    let target_id = signal.target_id.as_deref()?;
+    let state = state.lock().await;
+    let target = state.room_peers(addr).into_iter().find(|client| client.client_id == target_id)?;
+    let seq = target.reorder.lock().ok()?.assign(&signal.sender_id);
+    signal.relay_seq = Some(seq);
+    Some(Reservation { recipient: target.address, sender_id: signal.sender_id.clone(), seq })
+}
+
+// Once the signal has been handled: if it never reached the peer it was numbered for (turned
+// away, say), the peer stops waiting for it
+pub async fn settle(reservation: Option<Reservation>, state: &SharedState) {
+    let Some(reservation) = reservation else {
+        return;
+    };
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&reservation.recipient) else {
+        return;
+    };
+    let released = match client.reorder.lock() {
+        Ok(mut reorder) => reorder.skip(
+            &reservation.sender_id,
+            reservation.seq,
+            Instant::now(),
+            config::get_reorder_timeout(),
+            config::get_reorder_buffer_size()
+        ),
+        Err(_) => Vec::new(),
+    };
+    for message in released {
+        batching::deliver(client, message).await;
+    }
+}
+
+// Hands a disconnecting sender's held messages on and forgets its streams
+pub async fn forget_sender(state: &SignalingState, sender_id: &str) {
+    for client in state.clients.values() {
+        let released = match client.reorder.lock() {
+            Ok(mut reorder) => reorder.forget(sender_id),
+            Err(_) => Vec::new(),
+        };
+        for message in released {
+            batching::deliver(client, message).await;
+        }
+    }
+}
+
+// Lets out messages that have waited REORDER_TIMEOUT_MS behind a gap even when nothing else
+// arrives from their sender
+pub async fn run_reorder_flusher(state: SharedState) {
+    let timeout = config::get_reorder_timeout();
+    // A zero timeout never holds anything, but the interval still needs a period
+    let mut interval = tokio::time::interval(timeout.max(Duration::from_millis(1)));
+
+    loop {
+        interval.tick().await;
+        let state = state.lock().await;
+        let now = Instant::now();
+        for client in state.clients.values() {
+            let released = match client.reorder.lock() {
+                Ok(mut reorder) => reorder.release_overdue(now, timeout, config::get_reorder_buffer_size()),
+                Err(_) => Vec::new(),
+            };
+            for message in released {
+                batching::deliver(client, message).await;
+            }
+        }
+    }
+}
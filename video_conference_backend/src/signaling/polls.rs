@@ -0,0 +1,215 @@
+use crate::config;
+use crate::models::message::{PollCreatePayload, PollPayload, PollVotePayload};
+use crate::models::{Poll, SignalMessage, VoteError};
+use crate::signaling::handlers::{send_error, send_signal, send_to_room};
+use crate::signaling::state::{SharedState, SignalingState};
+use std::net::SocketAddr;
+
+// Hosts and moderators put polls to the room; everyone in it hears about the poll at once
+pub async fn handle_poll_create(
+    payload: PollCreatePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before creating a poll", None).await;
+    };
+    if !client.role.can_moderate() {
+        return send_error(client, "not-moderator", "Only hosts and moderators can create polls", None).await;
+    }
+    if room.polls.len() >= config::get_polls_per_room() {
+        let message = format!("The room already has {} polls", room.polls.len());
+        return send_error(client, "too-many-polls", &message, None).await;
+    }
+    let (question, options) = match check_poll(&payload) {
+        Ok(poll) => poll,
+        Err(reason) => return send_error(client, "invalid-poll", &reason, None).await,
+    };
+
+    let poll = Poll::new(
+        uuid::Uuid::new_v4().to_string(),
+        question,
+        options,
+        payload.anonymous,
+        client.client_id.clone()
+    );
+    let room_id = room.room_id.clone();
+    let created = poll_created(&room_id, &poll);
+    if let Some(room) = state.rooms.get_mut(&room_id) {
+        room.polls.push(poll);
+    }
+    send_to_room(&state, &room_id, &created).await
+}
+
+// Votes are counted against the voter's identity, the user it is signed in as or else its verified
+// key, so reconnecting or joining from a second connection does not earn another one. Nobody sees a tally until the poll closes.
+pub async fn handle_poll_vote(
+    payload: PollVotePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room_id) = client.room_id.clone() else {
+        return send_error(client, "not-in-room", "Join a room before voting", None).await;
+    };
+    let Some(voter) = client.identity() else {
+        return send_error(client, "unverified-sender", "Only verified participants can vote", None).await;
+    };
+    let client_id = client.client_id.clone();
+
+    let outcome = state.rooms
+        .get_mut(&room_id)
+        .and_then(|room| room.polls.iter_mut().find(|poll| poll.poll_id == payload.poll_id))
+        .map(|poll| poll.vote(&voter, &client_id, payload.option));
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    match outcome {
+        Some(Ok(())) => Ok(()),
+        None => send_error(client, "unknown-poll", &format!("No poll {} in this room", payload.poll_id), None).await,
+        Some(Err(VoteError::Closed)) => send_error(client, "poll-closed", "The poll has closed", None).await,
+        Some(Err(VoteError::AlreadyVoted)) => {
+            send_error(client, "already-voted", "You have already voted in this poll", None).await
+        }
+        Some(Err(VoteError::UnknownOption)) => {
+            let message = format!("The poll has no option {}", payload.option);
+            send_error(client, "invalid-poll", &message, None).await
+        }
+    }
+}
+
+// Closing a poll stops the voting and announces the results to the whole room
+pub async fn handle_poll_close(
+    payload: PollPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room_id) = client.room_id.clone() else {
+        return send_error(client, "not-in-room", "Join a room before closing a poll", None).await;
+    };
+    if !client.role.can_moderate() {
+        return send_error(client, "not-moderator", "Only hosts and moderators can close polls", None).await;
+    }
+    let Some(poll) = state.rooms
+        .get_mut(&room_id)
+        .and_then(|room| room.polls.iter_mut().find(|poll| poll.poll_id == payload.poll_id))
+    else {
+        return unknown_poll(&state, sender_addr, &payload.poll_id).await;
+    };
+    if poll.closed {
+        let Some(client) = state.clients.get(&sender_addr) else {
+            return Ok(());
+        };
+        return send_error(client, "poll-closed", "The poll has already closed", None).await;
+    }
+    poll.closed = true;
+    let results = poll_results(&room_id, poll)?;
+    send_to_room(&state, &room_id, &results).await
+}
+
+// The results of a closed poll, for members who were not there when it closed
+pub async fn handle_poll_results(
+    payload: PollPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before asking for poll results", None).await;
+    };
+    let Some(poll) = room.polls.iter().find(|poll| poll.poll_id == payload.poll_id) else {
+        return unknown_poll(&state, sender_addr, &payload.poll_id).await;
+    };
+    if !poll.closed {
+        return send_error(client, "poll-open", "Results are announced when the poll closes", None).await;
+    }
+    send_signal(client, &poll_results(&room.room_id, poll)?).await
+}
+
+// Tells a newcomer about the room's polls: the open ones to vote in and how the closed ones
+// came out
+pub async fn send_polls(
+    state: &SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(client), Some(room)) = (state.clients.get(&addr), state.client_room(addr)) else {
+        return Ok(());
+    };
+    for poll in &room.polls {
+        let signal = if poll.closed {
+            poll_results(&room.room_id, poll)?
+        } else {
+            poll_created(&room.room_id, poll)
+        };
+        send_signal(client, &signal).await?;
+    }
+    Ok(())
+}
+
+// The trimmed question and options; each must fit the length limit and options must differ
+fn check_poll(payload: &PollCreatePayload) -> Result<(String, Vec<String>), String> {
+    let max_length = config::get_poll_max_length();
+    let max_options = config::get_poll_max_options();
+    let check = |text: &str| {
+        let text = text.trim();
+        if text.is_empty() || text.chars().count() > max_length {
+            return Err(format!("Poll questions and options must be 1 to {} characters", max_length));
+        }
+        Ok(text.to_string())
+    };
+
+    let question = check(&payload.question)?;
+    if payload.options.len() < 2 || payload.options.len() > max_options {
+        return Err(format!("Polls take 2 to {} options", max_options));
+    }
+    let options = payload.options.iter().map(|option| check(option)).collect::<Result<Vec<_>, _>>()?;
+    if options.iter().enumerate().any(|(i, option)| options[..i].contains(option)) {
+        return Err("Poll options must differ".to_string());
+    }
+    Ok((question, options))
+}
+
+fn poll_created(room_id: &str, poll: &Poll) -> SignalMessage {
+    SignalMessage::server("poll-created", serde_json::json!({
+        "room_id": room_id,
+        "poll_id": poll.poll_id,
+        "question": poll.question,
+        "options": poll.options,
+        "anonymous": poll.anonymous,
+        "created_by": poll.created_by,
+    }))
+}
+
+fn poll_results(room_id: &str, poll: &Poll) -> Result<SignalMessage, serde_json::Error> {
+    let mut results = serde_json::to_value(poll.results())?;
+    results["room_id"] = serde_json::json!(room_id);
+    Ok(SignalMessage::server("poll-results", results))
+}
+
+async fn unknown_poll(
+    state: &SignalingState,
+    addr: SocketAddr,
+    poll_id: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    match state.clients.get(&addr) {
+        Some(client) => send_error(client, "unknown-poll", &format!("No poll {} in this room", poll_id), None).await,
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,85 @@
+use crate::config;
+use crate::models::message::HelloPayload;
+use crate::models::SignalMessage;
+use crate::signaling::{acks, batching, correlation, errors, renegotiation};
+use crate::signaling::handlers::send_signal;
+use crate::signaling::state::SharedState;
+use std::net::SocketAddr;
+
+// Message format versions this server speaks, newest first. Clients that never say hello are
+// taken to speak the oldest.
+pub const PROTOCOL_VERSIONS: &[u32] = &[1];
+
+// Optional behaviour a client can find out about (and opt into) in its hello
+pub fn server_features() -> Vec<&'static str> {
+    let mut features = vec![
+        "signed-envelopes",
+        "session-tokens",
+        "sequence-numbers",
+        "sealed-relay",
+        "e2ee-keys",
+        "profiles",
+        "moderation",
+        acks::FEATURE,
+        renegotiation::FEATURE,
+        batching::FEATURE,
+    ];
+    if config::get_noise_enabled() {
+        features.push("noise");
+    }
+    if config::get_webauthn_rp_id().is_some() {
+        features.push("webauthn");
+    }
+    features
+}
+
+// The newest version both sides speak
+pub fn negotiate_version(offered: &[u32]) -> Option<u32> {
+    PROTOCOL_VERSIONS.iter().copied().find(|version| offered.contains(version))
+}
+
+// Answers a client's hello with the version the connection will use and the features both sides
+// support. A client newer than the server is moved down to a version it also lists; one that
+// shares no version with the server is told which ones it could use and disconnected.
+pub async fn handle_hello(
+    payload: HelloPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+
+    let Some(version) = negotiate_version(&payload.versions) else {
+        eprintln!("Refusing {}: no common protocol version in {:?}{}", sender_addr, payload.versions, correlation::tag());
+        let refusal = SignalMessage::server("hello-rejected", serde_json::json!({
+            "code": "unsupported-version",
+            "numeric_code": errors::numeric_code("unsupported-version"),
+            "correlation_id": correlation::current(),
+            "message": "The server does not speak any of the offered protocol versions",
+            "offered": payload.versions,
+            "supported": PROTOCOL_VERSIONS,
+        }));
+        send_signal(client, &refusal).await?;
+        client.disconnect("unsupported protocol version").await;
+        return Ok(());
+    };
+
+    let supported = server_features();
+    client.protocol_version = version;
+    client.features = payload
+        .features
+        .into_iter()
+        .filter(|feature| supported.contains(&feature.as_str()))
+        .collect();
+
+    let reply = SignalMessage::server("hello", serde_json::json!({
+        "version": version,
+        "versions": PROTOCOL_VERSIONS,
+        "features": client.features,
+        "server_features": supported,
+        "client_id": client.client_id,
+    }));
+    send_signal(client, &reply).await
+}
@@ -0,0 +1,226 @@
+use crate::config;
+use crate::models::message::{QuestionAskPayload, QuestionModeratePayload, QuestionUpvotePayload};
+use crate::models::{QuestionError, SignalMessage};
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::state::{SharedState, SignalingState};
+use chrono::Utc;
+use std::net::SocketAddr;
+
+// Questions wait for a moderator before the room sees them. Each change goes out as it happens
+// to the members who may see the question: the whole question once it shows up for them, then
+// its count and status as they change, and its id once it is dismissed. Newcomers, and members
+// that ask with question-sync, get the whole queue as they may see it.
+pub async fn handle_question_ask(
+    payload: QuestionAskPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before asking a question", None).await;
+    };
+    if room.questions.len() >= config::get_questions_per_room() {
+        let message = format!("The room already has {} questions", room.questions.len());
+        return send_error(client, "too-many-questions", &message, None).await;
+    }
+    let text = payload.text.trim();
+    let max_length = config::get_question_max_length();
+    if text.is_empty() || text.chars().count() > max_length {
+        let message = format!("Questions must be 1 to {} characters", max_length);
+        return send_error(client, "invalid-question", &message, None).await;
+    }
+    if text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return send_error(client, "invalid-question", "Questions must not contain control characters", None).await;
+    }
+
+    let room_id = room.room_id.clone();
+    let client_id = client.client_id.clone();
+    let text = text.to_string();
+    let question_id = uuid::Uuid::new_v4().to_string();
+    if let Some(room) = state.rooms.get_mut(&room_id) {
+        room.questions.ask(question_id.clone(), &client_id, &text, Utc::now().timestamp_millis());
+    }
+    send_change(&state, &room_id, &question_id, &[]).await
+}
+
+// Each verified identity, the user it is signed in as or else its key, upvotes a question once
+pub async fn handle_question_upvote(
+    payload: QuestionUpvotePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room_id) = client.room_id.clone() else {
+        return send_error(client, "not-in-room", "Join a room before upvoting", None).await;
+    };
+    let Some(voter) = client.identity() else {
+        return send_error(client, "unverified-sender", "Only verified participants can upvote", None).await;
+    };
+
+    let watchers = watchers(&state, &room_id, &payload.question_id);
+    let outcome = state.rooms
+        .get_mut(&room_id)
+        .map_or(Err(QuestionError::Unknown), |room| room.questions.upvote(&payload.question_id, &voter));
+    match outcome {
+        Ok(()) => send_change(&state, &room_id, &payload.question_id, &watchers).await,
+        Err(e) => question_error(&state, sender_addr, &payload.question_id, e).await,
+    }
+}
+
+// Hosts and moderators approve questions into the queue, mark them answered, or dismiss them
+pub async fn handle_question_moderate(
+    payload: QuestionModeratePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room_id) = client.room_id.clone() else {
+        return send_error(client, "not-in-room", "Join a room before moderating questions", None).await;
+    };
+    if !client.role.can_moderate() {
+        return send_error(client, "not-moderator", "Only hosts and moderators can moderate questions", None).await;
+    }
+
+    let watchers = watchers(&state, &room_id, &payload.question_id);
+    let outcome = state.rooms
+        .get_mut(&room_id)
+        .map_or(Err(QuestionError::Unknown), |room| room.questions.moderate(&payload.question_id, payload.action));
+    match outcome {
+        Ok(()) => send_change(&state, &room_id, &payload.question_id, &watchers).await,
+        Err(e) => question_error(&state, sender_addr, &payload.question_id, e).await,
+    }
+}
+
+// The whole queue again, for a member that lost track of the changes
+pub async fn handle_question_sync(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if client.room_id.is_none() {
+        return send_error(client, "not-in-room", "Join a room before asking for its questions", None).await;
+    }
+    send_queue(&state, sender_addr, true).await
+}
+
+// The queue as `addr` may see it. Newcomers to a room without questions are told nothing;
+// `always` sends the empty queue too.
+pub async fn send_queue(
+    state: &SignalingState,
+    addr: SocketAddr,
+    always: bool
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(client), Some(room)) = (state.clients.get(&addr), state.client_room(addr)) else {
+        return Ok(());
+    };
+    if room.questions.is_empty() && !always {
+        return Ok(());
+    }
+    let queue = SignalMessage::server("question-queue", serde_json::json!({
+        "room_id": room.room_id,
+        "questions": room.questions.ordered(&client.client_id, client.role.can_moderate()),
+    }));
+    send_signal(client, &queue).await
+}
+
+// The members of the room that may see the question as it stands
+fn watchers(state: &SignalingState, room_id: &str, question_id: &str) -> Vec<SocketAddr> {
+    let Some(room) = state.rooms.get(room_id) else {
+        return Vec::new();
+    };
+    let Some(question) = room.questions.get(question_id) else {
+        return Vec::new();
+    };
+    room.members
+        .iter()
+        .filter(|addr| {
+            state.clients
+                .get(addr)
+                .is_some_and(|client| question.visible_to(&client.client_id, client.role.can_moderate()))
+        })
+        .copied()
+        .collect()
+}
+
+// Tells each member about a change to the question, given the members that could see it before
+async fn send_change(
+    state: &SignalingState,
+    room_id: &str,
+    question_id: &str,
+    watched_before: &[SocketAddr]
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(room) = state.rooms.get(room_id) else {
+        return Ok(());
+    };
+    let question = room.questions.get(question_id);
+    let shown = question.map(|question| SignalMessage::server("question", serde_json::json!({
+        "room_id": room_id,
+        "question": question,
+    })));
+    let updated = question.map(|question| SignalMessage::server("question-updated", serde_json::json!({
+        "room_id": room_id,
+        "question_id": question_id,
+        "upvotes": question.upvotes,
+        "status": question.status,
+    })));
+    let dismissed = SignalMessage::server("question-dismissed", serde_json::json!({
+        "room_id": room_id,
+        "question_id": question_id,
+    }));
+
+    for addr in &room.members {
+        let Some(client) = state.clients.get(addr) else {
+            continue;
+        };
+        let watched = watched_before.contains(addr);
+        let watches = question.is_some_and(|question| question.visible_to(&client.client_id, client.role.can_moderate()));
+        let signal = match (watched, watches) {
+            (false, true) => shown.as_ref(),
+            (true, true) => updated.as_ref(),
+            (true, false) => Some(&dismissed),
+            (false, false) => None,
+        };
+        if let Some(signal) = signal {
+            send_signal(client, signal).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn question_error(
+    state: &SignalingState,
+    addr: SocketAddr,
+    question_id: &str,
+    error: QuestionError
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(());
+    };
+    match error {
+        QuestionError::Unknown => {
+            let message = format!("No question {} in this room", question_id);
+            send_error(client, "unknown-question", &message, None).await
+        }
+        QuestionError::NotOpen => {
+            send_error(client, "question-not-open", "Only approved, unanswered questions take upvotes", None).await
+        }
+        QuestionError::AlreadyUpvoted => {
+            send_error(client, "already-upvoted", "You have already upvoted this question", None).await
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::config;
+use crate::models::message::ReactionPayload;
+use crate::models::SignalMessage;
+use crate::signaling::handlers::{send_error, send_to_room};
+use crate::signaling::state::{SharedState, SignalingState};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+
+// Reactions waiting to go out as aggregates, per room and reaction
+#[derive(Debug, Default)]
+pub struct ReactionTally {
+    counts: HashMap<String, BTreeMap<String, u32>>,
+}
+
+impl ReactionTally {
+    pub fn add(&mut self, room_id: &str, reaction: &str) {
+        let count = self.counts
+            .entry(room_id.to_string())
+            .or_default()
+            .entry(reaction.to_string())
+            .or_default();
+        *count = count.saturating_add(1);
+    }
+
+    pub fn drain(&mut self) -> Vec<(String, BTreeMap<String, u32>)> {
+        self.counts.drain().collect()
+    }
+}
+
+// Each reaction goes to the whole room as it happens, unless the room is big enough that
+// hundreds of them would swamp clients; those rooms get a count of each reaction instead,
+// every flush interval
+pub async fn handle_reaction(
+    payload: ReactionPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before reacting", None).await;
+    };
+    let reaction = payload.reaction.trim();
+    if !config::get_reactions().iter().any(|allowed| allowed == reaction) {
+        let message = format!("Unknown reaction {}", reaction);
+        return send_error(client, "invalid-reaction", &message, None).await;
+    }
+
+    let room_id = room.room_id.clone();
+    if room.members.len() >= config::get_reaction_aggregate_threshold() {
+        state.reactions.add(&room_id, reaction);
+        return Ok(());
+    }
+    let signal = SignalMessage::server("reaction", serde_json::json!({
+        "room_id": room_id,
+        "client_id": client.client_id,
+        "reaction": reaction,
+    }));
+    send_to_room(&state, &room_id, &signal).await
+}
+
+// Sends every room its aggregated reactions since the last flush
+pub async fn flush_reactions(state: &mut SignalingState) {
+    let interval = config::get_reaction_flush_interval();
+    for (room_id, counts) in state.reactions.drain() {
+        let signal = SignalMessage::server("reactions", serde_json::json!({
+            "room_id": room_id,
+            "counts": counts,
+            "interval_ms": interval.as_millis() as u64,
+        }));
+        if let Err(e) = send_to_room(state, &room_id, &signal).await {
+            eprintln!("Failed to send reactions to room {}: {}", room_id, e);
+        }
+    }
+}
+
+pub async fn run_reaction_flusher(state: SharedState) {
+    let mut interval = tokio::time::interval(config::get_reaction_flush_interval());
+
+    loop {
+        interval.tick().await;
+        flush_reactions(&mut *state.lock().await).await;
+    }
+}
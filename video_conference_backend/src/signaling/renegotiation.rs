@@ -0,0 +1,245 @@
+use crate::config;
+use crate::models::message::IceRestartPayload;
+use crate::models::SignalMessage;
+use crate::signaling::{batching, correlation, sdp};
+use crate::signaling::handlers::{relay_to, send_error, send_signal};
+use crate::signaling::state::{SharedState, SignalingState};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+// Advertised so clients know rosters carry negotiation roles
+pub const FEATURE: &str = "perfect-negotiation";
+
+// Each client's part toward each peer in the perfect-negotiation pattern: on glare the polite
+// side rolls back its own offer and takes the other's, while the impolite side ignores the
+// incoming offer. Derived from the two client ids, so both sides, and a resumed session, always
+// agree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NegotiationRole {
+    Polite,
+    Impolite,
+}
+
+impl NegotiationRole {
+    pub fn between(client_id: &str, peer_id: &str) -> Self {
+        if client_id > peer_id {
+            NegotiationRole::Polite
+        } else {
+            NegotiationRole::Impolite
+        }
+    }
+}
+
+// Open renegotiations, at most one per pair of peers: who offered, and when. An offer from one
+// side while the other's is still open is glare.
+#[derive(Debug, Default)]
+pub struct Renegotiations {
+    open: HashMap<(String, String), (String, Instant)>,
+}
+
+impl Renegotiations {
+    fn key(a: &str, b: &str) -> (String, String) {
+        if a <= b {
+            (a.to_string(), b.to_string())
+        } else {
+            (b.to_string(), a.to_string())
+        }
+    }
+
+    // Opens `offerer`'s renegotiation with `answerer`, replacing any earlier one of its own; false
+    // when `answerer` already has one open
+    pub fn offer(&mut self, offerer: &str, answerer: &str, now: Instant, timeout: Duration) -> bool {
+        let key = Self::key(offerer, answerer);
+        if let Some((open_by, since)) = self.open.get(&key) {
+            if open_by != offerer && now.saturating_duration_since(*since) < timeout {
+                return false;
+            }
+        }
+        self.open.insert(key, (offerer.to_string(), now));
+        true
+    }
+
+    // Opens `offerer`'s renegotiation with `answerer` in place of whatever was open
+    pub fn supersede(&mut self, offerer: &str, answerer: &str, now: Instant) {
+        self.open.insert(Self::key(offerer, answerer), (offerer.to_string(), now));
+    }
+
+    // Closes the renegotiation `offerer` opened with `answerer`; false when none is open
+    pub fn answer(&mut self, answerer: &str, offerer: &str, now: Instant, timeout: Duration) -> bool {
+        let key = Self::key(offerer, answerer);
+        match self.open.get(&key) {
+            Some((open_by, since)) if open_by == offerer && now.saturating_duration_since(*since) < timeout => {
+                self.open.remove(&key);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Whoever offered last to or from `client_id` has nobody left to answer them
+    pub fn forget(&mut self, client_id: &str) {
+        self.open.retain(|(a, b), _| a != client_id && b != client_id);
+    }
+}
+
+// Renegotiation changes the tracks of a connection that a verified offer and answer already set
+// up, so it is taken on the sender's session token rather than a fresh signature
+pub async fn handle_renegotiate_offer(
+    signal: &SignalMessage,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    relay_renegotiation(signal, true, sender_addr, state).await
+}
+
+pub async fn handle_renegotiate_answer(
+    signal: &SignalMessage,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    relay_renegotiation(signal, false, sender_addr, state).await
+}
+
+// An ICE restart opens a renegotiation like `renegotiate-offer` does and is answered with
+// `renegotiate-answer`. Candidates either side still has held back for the other belong to the
+// old network and are dropped; the counterpart is told, and the restart goes out without
+// waiting behind anything.
+pub async fn handle_ice_restart(
+    signal: &SignalMessage,
+    payload: IceRestartPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(target_id) = find_target(signal, sender_addr, &state).await? else {
+        return Ok(());
+    };
+    if !open_renegotiation(signal, true, &target_id, sender_addr, &mut state).await? {
+        return Ok(());
+    }
+    let (Some(sender), Some(target)) = (
+        state.clients.get(&sender_addr),
+        state.tenant_client(sender_addr, &target_id)
+    ) else {
+        return Ok(());
+    };
+
+    // The restart itself was numbered for the target as it came in; only what comes before it
+    // is let out or dropped
+    let mut dropped = 0;
+    for (recipient, from, until) in [(sender, target, None), (target, sender, signal.relay_seq)] {
+        let released = match recipient.reorder.lock() {
+            Ok(mut reorder) => reorder.flush(&from.client_id, until, |message| {
+                let stale = batching::is_candidate(recipient.encoding, message);
+                dropped += stale as usize;
+                stale
+            }),
+            Err(_) => Vec::new(),
+        };
+        if let Ok(mut batch) = recipient.ice_batch.lock() {
+            dropped += batch.discard_from(&from.client_id);
+        }
+        for message in released {
+            batching::deliver(recipient, message).await;
+        }
+    }
+    println!("{} restarted ICE with {} ({} held candidates dropped)", sender_addr, target.address, dropped);
+
+    let notice = SignalMessage::server("ice-restart-started", serde_json::json!({
+        "client_id": sender.client_id,
+        "reason": payload.reason,
+        "dropped_candidates": dropped,
+    }));
+    send_signal(target, &notice).await?;
+    relay_to(target, &sdp::sanitize(signal, sender_addr, &state)).await
+}
+
+// The verified room peer the signal names; None once the sender has been told why there is none
+async fn find_target(
+    signal: &SignalMessage,
+    sender_addr: SocketAddr,
+    state: &SignalingState
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(sender) = state.clients.get(&sender_addr) else {
+        return Ok(None);
+    };
+    let Some(target_id) = signal.target_id.clone() else {
+        send_error(sender, "missing-target", "Renegotiation messages must name a target_id", None).await?;
+        return Ok(None);
+    };
+    let target = state.room_peers(sender_addr)
+        .into_iter()
+        .find(|client| client.client_id == target_id)
+        .map(|client| client.verified);
+    let (code, message) = match target {
+        Some(true) => return Ok(Some(target_id)),
+        Some(false) => ("target-unverified", "Target peer has not completed verification"),
+        None => ("target-unknown", "Target peer is not in your room"),
+    };
+    send_error(sender, code, message, Some(&target_id)).await?;
+    Ok(None)
+}
+
+// Opens or closes the pair's renegotiation; false once the sender has been told it cannot
+async fn open_renegotiation(
+    signal: &SignalMessage,
+    is_offer: bool,
+    target_id: &str,
+    sender_addr: SocketAddr,
+    state: &mut SignalingState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(sender_id) = state.clients.get(&sender_addr).map(|client| client.client_id.clone()) else {
+        return Ok(false);
+    };
+    let now = Instant::now();
+    let timeout = config::get_renegotiation_timeout();
+    let accepted = if is_offer {
+        // On glare the impolite side's offer stands, and the polite side rolls its own back
+        let impolite = NegotiationRole::between(&sender_id, target_id) == NegotiationRole::Impolite;
+        state.renegotiations.offer(&sender_id, target_id, now, timeout) || impolite && {
+            state.renegotiations.supersede(&sender_id, target_id, now);
+            true
+        }
+    } else {
+        state.renegotiations.answer(&sender_id, target_id, now, timeout)
+    };
+    if accepted {
+        return Ok(true);
+    }
+
+    let (code, message) = if is_offer {
+        ("renegotiation-glare", "The target's own renegotiation offer is still open; answer it first")
+    } else {
+        ("no-renegotiation", "The target has no renegotiation offer open with you")
+    };
+    eprintln!("Rejected {} from {} to {}: {}{}", signal.signal_type, sender_addr, target_id, code, correlation::tag());
+    if let Some(sender) = state.clients.get(&sender_addr) {
+        send_error(sender, code, message, Some(target_id)).await?;
+    }
+    Ok(false)
+}
+
+async fn relay_renegotiation(
+    signal: &SignalMessage,
+    is_offer: bool,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(target_id) = find_target(signal, sender_addr, &state).await? else {
+        return Ok(());
+    };
+    if !open_renegotiation(signal, is_offer, &target_id, sender_addr, &mut state).await? {
+        return Ok(());
+    }
+
+    match state.tenant_client(sender_addr, &target_id) {
+        Some(target) => relay_to(target, &sdp::sanitize(signal, sender_addr, &state)).await,
+        None => Ok(()),
+    }
+}
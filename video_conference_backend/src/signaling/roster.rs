@@ -0,0 +1,292 @@
+use crate::models::message::{LowerHandPayload, PresencePayload};
+use crate::models::{Capabilities, Profile, SignalMessage};
+use crate::rooms;
+use crate::signaling::handlers::{send_error, send_signal, send_to_room};
+use crate::signaling::{chat, documents, e2ee, polls, questions, screenshare, whiteboard};
+use crate::signaling::renegotiation::NegotiationRole;
+use crate::signaling::state::{SharedState, SignalingState};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+// Sends the newcomer the verified participants already in its room, what has been said there,
+// what is on the whiteboard, the room's shared documents, polls and questions, and tells them it
+// arrived
+pub async fn announce_join(
+    state: &mut SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(client), Some(room)) = (state.clients.get(&addr), state.client_room(addr)) else {
+        return Ok(());
+    };
+    let room_id = room.room_id.clone();
+
+    let peers = state.room_peers(addr);
+    let participants: Vec<_> = peers
+        .iter()
+        .filter(|peer| peer.verified)
+        .map(|peer| peer.participant_info())
+        .collect();
+    // The newcomer's role toward each participant
+    let negotiation_roles: HashMap<_, _> = peers
+        .iter()
+        .filter(|peer| peer.verified)
+        .map(|peer| (peer.client_id.clone(), NegotiationRole::between(&client.client_id, &peer.client_id)))
+        .collect();
+
+    let roster = SignalMessage::server("roster", serde_json::json!({
+        "room_id": room.room_id,
+        "participants": participants,
+        "negotiation_roles": negotiation_roles,
+        "raised_hands": hand_queue(state, &room.room_id),
+        "screen_sharers": screenshare::sharers(state, &room.room_id),
+        "key_epoch": room.manages_keys().then_some(room.key_epoch),
+        "capabilities": room_capabilities(state, &room.room_id),
+    }));
+    send_signal(client, &roster).await?;
+    chat::send_recent_history(state, addr).await?;
+    whiteboard::send_board(state, addr).await?;
+    documents::send_documents(state, addr).await?;
+    polls::send_polls(state, addr).await?;
+    questions::send_queue(state, addr, false).await?;
+
+    for peer in peers {
+        let joined = SignalMessage::server("peer-joined", serde_json::json!({
+            "room_id": room.room_id,
+            "participant": client.participant_info(),
+            "negotiation_role": NegotiationRole::between(&peer.client_id, &client.client_id),
+            "capabilities": room_capabilities(state, &room.room_id),
+        }));
+        send_signal(peer, &joined).await?;
+    }
+
+    e2ee::rekey(state, &room_id, "join").await
+}
+
+// Tells the remaining members of `room_id` that `client_id` is gone, handing the room
+// to a new host first if it was the host that left
+pub async fn announce_leave(
+    state: &mut SignalingState,
+    room_id: &str,
+    client_id: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    let new_host = rooms::transfer_host(state, room_id);
+    let Some(room) = state.rooms.get(room_id) else {
+        return Ok(());
+    };
+
+    let left = SignalMessage::server("peer-left", serde_json::json!({
+        "room_id": room_id,
+        "client_id": client_id,
+        "capabilities": room_capabilities(state, room_id),
+    }));
+    for peer in room.members.iter().filter_map(|member| state.clients.get(member)) {
+        send_signal(peer, &left).await?;
+    }
+
+    if let Some(host) = new_host.and_then(|addr| state.clients.get(&addr)) {
+        let changed = SignalMessage::server("host-changed", serde_json::json!({
+            "room_id": room_id,
+            "client_id": host.client_id,
+            "previous_host_id": client_id,
+        }));
+        send_to_room(state, room_id, &changed).await?;
+        println!("Room {} handed to {} after its host left", room_id, host.address);
+    }
+
+    e2ee::rekey(state, room_id, "leave").await
+}
+
+pub async fn handle_presence(
+    payload: PresencePayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    client.presence = payload.status;
+    let client_id = client.client_id.clone();
+
+    let update = SignalMessage::server("presence", serde_json::json!({
+        "client_id": client_id,
+        "status": payload.status,
+    }));
+    for peer in state.room_peers(sender_addr) {
+        send_signal(peer, &update).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_set_profile(
+    profile: Profile,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+
+    if let Err(reason) = profile.validate() {
+        return send_error(client, "invalid-profile", reason, None).await;
+    }
+    client.profile = profile.normalized();
+    let participant = client.participant_info();
+
+    let reply = SignalMessage::server("profile-updated", serde_json::json!({
+        "participant": participant,
+    }));
+    send_signal(client, &reply).await?;
+
+    let update = SignalMessage::server("peer-updated", serde_json::json!({
+        "participant": participant,
+    }));
+    for peer in state.room_peers(sender_addr) {
+        send_signal(peer, &update).await?;
+    }
+
+    Ok(())
+}
+
+// Validates and stores what the client declares it supports; false once it has been told why not
+pub async fn declare_capabilities(
+    state: &mut SignalingState,
+    addr: SocketAddr,
+    capabilities: Capabilities
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(client) = state.clients.get_mut(&addr) else {
+        return Ok(false);
+    };
+    if let Err(reason) = capabilities.validate() {
+        send_error(client, "invalid-capabilities", reason, None).await?;
+        return Ok(false);
+    }
+    client.capabilities = Some(capabilities.normalized());
+    Ok(true)
+}
+
+// Declared again after joining, e.g. once a device changes; the room learns the new common set
+pub async fn handle_capabilities(
+    capabilities: Capabilities,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    if !declare_capabilities(&mut state, sender_addr, capabilities).await? {
+        return Ok(());
+    }
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let common = client.room_id.as_deref().and_then(|room_id| room_capabilities(&state, room_id));
+
+    let reply = SignalMessage::server("capabilities-updated", serde_json::json!({
+        "capabilities": client.capabilities,
+        "room_capabilities": common,
+    }));
+    send_signal(client, &reply).await?;
+
+    let update = SignalMessage::server("peer-capabilities", serde_json::json!({
+        "client_id": client.client_id,
+        "capabilities": client.capabilities,
+        "room_capabilities": common,
+    }));
+    for peer in state.room_peers(sender_addr) {
+        send_signal(peer, &update).await?;
+    }
+
+    Ok(())
+}
+
+// What every member of `room_id` that has declared capabilities supports
+pub fn room_capabilities(state: &SignalingState, room_id: &str) -> Option<Capabilities> {
+    let room = state.rooms.get(room_id)?;
+    Capabilities::common(
+        room.members
+            .iter()
+            .filter_map(|member| state.clients.get(member))
+            .filter_map(|client| client.capabilities.as_ref())
+    )
+}
+
+// Client ids with a raised hand in `room_id`, first raised first
+pub fn hand_queue(state: &SignalingState, room_id: &str) -> Vec<String> {
+    state.rooms
+        .get(room_id)
+        .map(|room| {
+            room.raised_hands
+                .iter()
+                .filter_map(|addr| state.clients.get(addr))
+                .map(|client| client.client_id.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn broadcast_hand_queue(
+    state: &SignalingState,
+    room_id: &str
+) -> Result<(), Box<dyn std::error::Error>> {
+    let update = SignalMessage::server("hand-queue", serde_json::json!({
+        "room_id": room_id,
+        "queue": hand_queue(state, room_id),
+    }));
+    send_to_room(state, room_id, &update).await
+}
+
+pub async fn handle_raise_hand(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(room_id) = state.clients.get(&sender_addr).and_then(|client| client.room_id.clone()) else {
+        return Ok(());
+    };
+
+    let raised = state.rooms
+        .get_mut(&room_id)
+        .is_some_and(|room| room.raise_hand(sender_addr));
+    if raised {
+        broadcast_hand_queue(&state, &room_id).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_lower_hand(
+    payload: LowerHandPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room_id) = client.room_id.clone() else {
+        return Ok(());
+    };
+
+    let target_addr = match payload.client_id.as_deref() {
+        Some(target_id) if target_id != client.client_id => {
+            if !client.role.can_moderate() {
+                return send_error(client, "not-moderator", "Only moderators can lower other hands", Some(target_id)).await;
+            }
+            match state.tenant_client(sender_addr, target_id) {
+                Some(target) => target.address,
+                None => return Ok(()),
+            }
+        }
+        _ => sender_addr,
+    };
+
+    let lowered = state.rooms
+        .get_mut(&room_id)
+        .is_some_and(|room| room.lower_hand(&target_addr));
+    if lowered {
+        broadcast_hand_queue(&state, &room_id).await?;
+    }
+
+    Ok(())
+}
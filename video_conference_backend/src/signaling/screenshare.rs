@@ -0,0 +1,89 @@
+use crate::config;
+use crate::models::{Presence, SignalMessage};
+use crate::signaling::handlers::{send_error, send_to_room};
+use crate::signaling::state::{SharedState, SignalingState};
+use std::net::SocketAddr;
+
+// Client ids currently sharing in `room_id`
+pub fn sharers(state: &SignalingState, room_id: &str) -> Vec<String> {
+    state.rooms
+        .get(room_id)
+        .map(|room| {
+            room.screen_sharers
+                .iter()
+                .filter_map(|addr| state.clients.get(addr))
+                .map(|client| client.client_id.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub async fn handle_screenshare_start(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(room) = state.client_room(sender_addr) else {
+        return send_error(client, "not-in-room", "Join a room before sharing your screen", None).await;
+    };
+    if room.screen_sharers.contains(&sender_addr) {
+        return Ok(());
+    }
+
+    let limit = room.config.max_screen_shares.unwrap_or_else(config::get_max_screen_shares);
+    if room.screen_sharers.len() >= limit {
+        let current = sharers(&state, &room.room_id).join(", ");
+        let message = format!("Screen sharing is limited to {} at a time (sharing: {})", limit, current);
+        return send_error(client, "screenshare-busy", &message, None).await;
+    }
+
+    let room_id = room.room_id.clone();
+    if let Some(room) = state.rooms.get_mut(&room_id) {
+        room.screen_sharers.push(sender_addr);
+    }
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    client.presence = Presence::ScreenSharing;
+
+    let started = SignalMessage::server("screenshare-started", serde_json::json!({
+        "room_id": room_id,
+        "client_id": client.client_id,
+    }));
+    send_to_room(&state, &room_id, &started).await
+}
+
+pub async fn handle_screenshare_stop(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(room_id) = state.clients.get(&sender_addr).and_then(|client| client.room_id.clone()) else {
+        return Ok(());
+    };
+
+    let was_sharing = state.rooms.get_mut(&room_id).is_some_and(|room| {
+        let before = room.screen_sharers.len();
+        room.screen_sharers.retain(|sharer| *sharer != sender_addr);
+        room.screen_sharers.len() != before
+    });
+    if !was_sharing {
+        return Ok(());
+    }
+
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    if client.presence == Presence::ScreenSharing {
+        client.presence = Presence::Active;
+    }
+
+    let stopped = SignalMessage::server("screenshare-stopped", serde_json::json!({
+        "room_id": room_id,
+        "client_id": client.client_id,
+    }));
+    send_to_room(&state, &room_id, &stopped).await
+}
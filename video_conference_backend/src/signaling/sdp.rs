@@ -0,0 +1,344 @@
+use crate::config;
+use crate::crypto::server_identity;
+use crate::models::{Payload, Room, RoomConfig, SignalKind, SignalMessage};
+use crate::rooms::CandidatePolicy;
+use crate::signaling::handlers::send_signal;
+use crate::signaling::state::{SharedState, SignalingState};
+use crate::signaling::{correlation, errors};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::net::SocketAddr;
+
+// Where each signal that carries a session description keeps it
+const DESCRIPTION_FIELDS: &[(SignalKind, &str)] = &[
+    (SignalKind::SecureOffer, "offer"),
+    (SignalKind::SecureAnswer, "offer"),
+    (SignalKind::RenegotiateOffer, "description"),
+    (SignalKind::RenegotiateAnswer, "description"),
+    (SignalKind::IceRestart, "description"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SdpError {
+    TooLarge { limit: usize },
+    // `line` counts from 1
+    Malformed { line: usize, reason: String },
+}
+
+impl SdpError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SdpError::TooLarge { .. } => "sdp-too-large",
+            SdpError::Malformed { .. } => "invalid-sdp",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            SdpError::TooLarge { limit } => format!("Session descriptions are limited to {} bytes", limit),
+            SdpError::Malformed { line, reason } => format!("Invalid session description at line {}: {}", line, reason),
+        }
+    }
+
+    fn malformed(line: usize, reason: impl Into<String>) -> Self {
+        SdpError::Malformed { line, reason: reason.into() }
+    }
+}
+
+// Checks the structure RFC 8866 gives a session description: `<type>=<value>` lines, `v=0`
+// first, then the origin, the session name and at least one timing line before the first media
+// section, and well-formed `o=` and `m=` lines. Values themselves are left to the peer.
+pub fn validate(sdp: &str, max_size: usize) -> Result<(), SdpError> {
+    if sdp.len() > max_size {
+        return Err(SdpError::TooLarge { limit: max_size });
+    }
+
+    let mut seen_origin = false;
+    let mut seen_name = false;
+    let mut seen_timing = false;
+    let mut in_media = false;
+    for (index, line) in lines(sdp).enumerate() {
+        let number = index + 1;
+        let (kind, value) = match line.as_bytes() {
+            [kind @ b'a'..=b'z', b'=', ..] => (*kind, &line[2..]),
+            [] => return Err(SdpError::malformed(number, "empty line")),
+            _ => return Err(SdpError::malformed(number, "expected <type>=<value>")),
+        };
+        if value.chars().any(|c| c.is_control() && c != '\t') {
+            return Err(SdpError::malformed(number, "control character in value"));
+        }
+
+        match (number, kind) {
+            (1, b'v') if value == "0" => continue,
+            (1, _) => return Err(SdpError::malformed(number, "must start with v=0")),
+            (2, b'o') => {
+                if value.split(' ').count() != 6 {
+                    return Err(SdpError::malformed(number, "o= takes six fields"));
+                }
+                seen_origin = true;
+            }
+            (2, _) => return Err(SdpError::malformed(number, "o= must follow v=")),
+            (_, b'v' | b'o') => return Err(SdpError::malformed(number, "v= and o= appear only once")),
+            (_, b's') if !in_media => seen_name = true,
+            (_, b't') if !in_media => seen_timing = true,
+            (_, b'm') => {
+                if !(seen_origin && seen_name && seen_timing) {
+                    return Err(SdpError::malformed(number, "media before s= and t="));
+                }
+                check_media(value).map_err(|reason| SdpError::malformed(number, reason))?;
+                in_media = true;
+            }
+            (_, b's' | b't') => return Err(SdpError::malformed(number, "s= and t= belong to the session section")),
+            _ => {}
+        }
+    }
+
+    if !(seen_origin && seen_name && seen_timing) {
+        return Err(SdpError::malformed(lines(sdp).count().max(1), "missing o=, s= or t="));
+    }
+    Ok(())
+}
+
+// `m=<media> <port>[/<count>] <proto> <fmt> ...`
+fn check_media(value: &str) -> Result<(), &'static str> {
+    let fields: Vec<&str> = value.split(' ').collect();
+    if fields.len() < 4 || fields.iter().any(|field| field.is_empty()) {
+        return Err("m= takes a media type, port, protocol and at least one format");
+    }
+    let port = fields[1].split_once('/').map_or(fields[1], |(port, _)| port);
+    if port.parse::<u16>().is_err() {
+        return Err("m= port is not a number");
+    }
+    Ok(())
+}
+
+// Lines without their terminators; the final CRLF does not start another line
+fn lines(sdp: &str) -> impl Iterator<Item = &str> {
+    sdp.strip_suffix('\n')
+        .unwrap_or(sdp)
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+}
+
+// Drops the `a=candidate` lines the room's candidate policy would drop if they were trickled,
+// keeping every other line byte for byte. Returns the description and how many went.
+pub fn strip_candidates(sdp: &str, policy: &CandidatePolicy) -> (String, usize) {
+    let mut removed = 0;
+    let kept: String = sdp
+        .split_inclusive('\n')
+        .filter(|line| {
+            let candidate = line.trim_end();
+            let drop = candidate.starts_with("a=candidate:") && policy.check(candidate).is_err();
+            removed += drop as usize;
+            !drop
+        })
+        .collect();
+    (kept, removed)
+}
+
+// The payload field holding the session description, for signal types that carry one
+fn description_field(kind: SignalKind) -> Option<&'static str> {
+    DESCRIPTION_FIELDS.iter().find(|(described, _)| *described == kind).map(|(_, field)| *field)
+}
+
+// The session description a signal carries, if it is one that does
+pub fn description_sdp(kind: SignalKind, payload: &Value) -> Option<String> {
+    let field = description_field(kind)?;
+    payload.get(field)?.get("sdp")?.as_str().map(str::to_string)
+}
+
+// False (after telling the sender why) when the signal carries a session description that is
+// oversized or malformed, so it is turned away before any signature is checked
+pub async fn check_description(
+    kind: SignalKind,
+    payload: &Payload,
+    addr: SocketAddr,
+    state: &SharedState
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(sdp) = payload.json().and_then(|payload| description_sdp(kind, payload)) else {
+        return Ok(true);
+    };
+    let Err(error) = validate(&sdp, config::get_max_sdp_size()) else {
+        return Ok(true);
+    };
+
+    eprintln!("Rejected {} from {}: {}{}", kind.name(), addr, error.message(), correlation::tag());
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(false);
+    };
+    let line = match &error {
+        SdpError::Malformed { line, .. } => Some(*line),
+        SdpError::TooLarge { .. } => None,
+    };
+    let message = errors::error_signal(error.code(), &error.message(), None, serde_json::json!({
+        "context": {
+            "signal_type": kind.name(),
+            "line": line,
+        },
+    }));
+    send_signal(client, &message).await?;
+    Ok(false)
+}
+
+// What a rewrite hook is told about the description it is handed
+pub struct RewriteContext<'a> {
+    pub signal_type: &'a str,
+    pub sender_id: &'a str,
+    pub target_id: Option<&'a str>,
+    pub room: Option<&'a Room>,
+}
+
+// Lets an embedding application change session descriptions in flight, e.g. to cap bandwidth
+// with `set_bandwidth`, put a codec first with `prefer_codec` or drop header extensions with
+// `remove_extmap`. Returns None to relay the description as it is. A rewrite that does not
+// validate is discarded.
+pub trait SdpRewriter: Send + Sync {
+    fn rewrite(&self, context: &RewriteContext, sdp: &str) -> Option<String>;
+}
+
+// The signal as it is relayed: with the candidates the sender's room does not allow stripped
+// from its description, then whatever the rewrite hook makes of it. A changed description is
+// marked with how many candidates went and attributed to the server, since the sender's
+// signature no longer covers it. Only the server makes those marks; a sender's own are dropped
+// from whatever it sends.
+pub fn sanitize<'a>(signal: &'a SignalMessage, sender_addr: SocketAddr, state: &SignalingState) -> Cow<'a, SignalMessage> {
+    let kind = SignalKind::of(&signal.signal_type);
+    if kind == SignalKind::Sealed {
+        return Cow::Borrowed(signal);
+    }
+    let Ok(mut payload) = serde_json::from_str::<Value>(&signal.payload) else {
+        return Cow::Borrowed(signal);
+    };
+    let forged = payload.as_object_mut().is_some_and(|fields| {
+        let rewritten = fields.remove("rewritten").is_some();
+        let stripped = fields.remove("sdp_stripped").is_some();
+        rewritten || stripped
+    });
+    let unchanged = |payload: Value| match forged {
+        true => Cow::Owned(SignalMessage { payload: payload.to_string(), ..signal.clone() }),
+        false => Cow::Borrowed(signal),
+    };
+    let (Some(field), Some(original)) = (description_field(kind), description_sdp(kind, &payload)) else {
+        return unchanged(payload);
+    };
+    let room = state.client_room(sender_addr);
+    let policy = match room {
+        Some(room) => CandidatePolicy::for_room(&room.config),
+        None => CandidatePolicy::for_room(&RoomConfig::default()),
+    };
+
+    let (mut sdp, removed) = strip_candidates(&original, &policy);
+    if removed > 0 {
+        println!("Stripped {} candidates from the {} of {}{}", removed, signal.signal_type, sender_addr, correlation::tag());
+    }
+    if let Some(rewriter) = &state.sdp_rewriter {
+        let context = RewriteContext {
+            signal_type: &signal.signal_type,
+            sender_id: &signal.sender_id,
+            target_id: signal.target_id.as_deref(),
+            room,
+        };
+        match rewriter.rewrite(&context, &sdp).map(|rewritten| (validate(&rewritten, config::get_max_sdp_size()), rewritten)) {
+            Some((Ok(()), rewritten)) => sdp = rewritten,
+            Some((Err(error), _)) => eprintln!("Discarded the rewrite of {}'s {}: {}", sender_addr, signal.signal_type, error.message()),
+            None => {}
+        }
+    }
+    if sdp == original {
+        return unchanged(payload);
+    }
+
+    payload[field]["sdp"] = Value::String(sdp);
+    if removed > 0 {
+        payload["sdp_stripped"] = removed.into();
+    }
+    let nonce = payload.get("nonce").cloned().unwrap_or_default();
+    payload["rewritten"] = server_identity::attest_rewrite(signal, &nonce, &original, &payload[field]);
+    let mut sanitized = signal.clone();
+    sanitized.payload = payload.to_string();
+    Cow::Owned(sanitized)
+}
+
+// Media sections of a description: the session section first, then one per `m=` line, each
+// with its lines' terminators kept
+fn sections(sdp: &str) -> Vec<Vec<&str>> {
+    let mut sections = vec![Vec::new()];
+    for line in sdp.split_inclusive('\n') {
+        if line.starts_with("m=") {
+            sections.push(Vec::new());
+        }
+        if let Some(section) = sections.last_mut() {
+            section.push(line);
+        }
+    }
+    sections
+}
+
+fn line_ending(sdp: &str) -> &'static str {
+    if sdp.contains("\r\n") || !sdp.contains('\n') { "\r\n" } else { "\n" }
+}
+
+// Caps every media section at `kbps` with a `b=AS` line in place of any it had, placed after
+// the section's `i=` and `c=` lines as RFC 8866 orders them
+pub fn set_bandwidth(sdp: &str, kbps: u32) -> String {
+    let bandwidth = format!("b=AS:{}{}", kbps, line_ending(sdp));
+    let mut out = String::with_capacity(sdp.len() + 64);
+    for (index, section) in sections(sdp).into_iter().enumerate() {
+        let lines: Vec<&str> = section.into_iter().filter(|line| index == 0 || !line.starts_with("b=AS:")).collect();
+        if index == 0 {
+            out.extend(lines);
+            continue;
+        }
+        let at = 1 + lines.iter().skip(1).take_while(|line| line.starts_with("i=") || line.starts_with("c=")).count();
+        out.extend(lines[..at].iter().copied());
+        out.push_str(&bandwidth);
+        out.extend(lines[at..].iter().copied());
+    }
+    out
+}
+
+// Moves the payload types of `codec` (e.g. `VP9`, matched against `a=rtpmap` case-insensitively)
+// to the front of every `media` section's format list, keeping the others in their order
+pub fn prefer_codec(sdp: &str, media: &str, codec: &str) -> String {
+    let mut out = String::with_capacity(sdp.len());
+    for section in sections(sdp) {
+        let Some((first, rest)) = section.split_first().filter(|(first, _)| first.starts_with(&format!("m={} ", media))) else {
+            out.extend(section);
+            continue;
+        };
+        let preferred: Vec<&str> = rest
+            .iter()
+            .filter_map(|line| line.trim_end().strip_prefix("a=rtpmap:"))
+            .filter_map(|map| map.split_once(' '))
+            .filter(|(_, encoding)| encoding.split('/').next().is_some_and(|name| name.eq_ignore_ascii_case(codec)))
+            .map(|(payload_type, _)| payload_type)
+            .collect();
+
+        let line = first.trim_end();
+        let fields: Vec<&str> = line.split(' ').collect();
+        if fields.len() < 4 {
+            out.extend(section);
+            continue;
+        }
+        let (head, formats) = fields.split_at(3);
+        let (mut ordered, others): (Vec<&str>, Vec<&str>) = formats.iter().partition(|format| preferred.contains(format));
+        ordered.extend(others);
+        out.push_str(&head.join(" "));
+        out.push(' ');
+        out.push_str(&ordered.join(" "));
+        out.push_str(&first[line.len()..]);
+        out.extend(rest.iter().copied());
+    }
+    out
+}
+
+// Drops the `a=extmap` lines that map the header extension `uri`
+pub fn remove_extmap(sdp: &str, uri: &str) -> String {
+    sdp.split_inclusive('\n')
+        .filter(|line| {
+            let extmap = line.trim_end().strip_prefix("a=extmap:");
+            extmap.is_none_or(|extmap| extmap.split(' ').nth(1) != Some(uri))
+        })
+        .collect()
+}
@@ -0,0 +1,76 @@
+use crate::models::{Client, Role, Room, Sequenced, SequencedLog, SignalMessage};
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::state::SignalingState;
+use std::net::SocketAddr;
+
+// The sender's room when it may change the room's shared state, e.g. "draw on the whiteboard";
+// viewers follow that state but do not change it
+pub async fn check_editor(
+    state: &SignalingState,
+    addr: SocketAddr,
+    action: &str
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(client) = state.clients.get(&addr) else {
+        return Ok(None);
+    };
+    let Some(room) = state.client_room(addr) else {
+        send_error(client, "not-in-room", &format!("Join a room before you {}", action), None).await?;
+        return Ok(None);
+    };
+    if client.role == Role::Viewer {
+        send_error(client, "insufficient-role", &format!("Viewers cannot {}", action), None).await?;
+        return Ok(None);
+    }
+    Ok(Some(room.room_id.clone()))
+}
+
+// Asks `addr` for a snapshot of the log `log` picks out of the room once `snapshot_after`
+// entries have piled up, unless a member still in the room was asked already. `request` builds
+// the request from the sequence number the snapshot should cover.
+pub async fn ask_for_snapshot<E: Sequenced, S: Sequenced>(
+    state: &mut SignalingState,
+    room_id: &str,
+    addr: SocketAddr,
+    snapshot_after: usize,
+    log: impl FnOnce(&mut Room) -> Option<&mut SequencedLog<E, S>>,
+    request: impl FnOnce(u64) -> SignalMessage
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(room) = state.rooms.get_mut(room_id) else {
+        return Ok(());
+    };
+    let members = room.members.clone();
+    let Some(log) = log(room) else {
+        return Ok(());
+    };
+    let asked = log.snapshot_from().is_some_and(|asked| members.contains(&asked));
+    if asked || log.pending() < snapshot_after {
+        return Ok(());
+    }
+    log.request_snapshot(addr);
+    let request = request(log.seq());
+    match state.clients.get(&addr) {
+        Some(client) => send_signal(client, &request).await,
+        None => Ok(()),
+    }
+}
+
+// Sends `client` what it is missing from `log` after `since`, in pages of at most `page_size`
+// entries. `page` builds each signal from the snapshot, which only the first page carries, the
+// page's entries, and whether it is the last page.
+pub async fn send_pages<E: Sequenced, S: Sequenced>(
+    client: &Client,
+    log: &SequencedLog<E, S>,
+    since: u64,
+    page_size: usize,
+    page: impl Fn(Option<&S>, &[E], bool) -> SignalMessage
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut snapshot, entries) = log.since(since);
+    let mut pages = entries.chunks(page_size.max(1)).peekable();
+    if pages.peek().is_none() {
+        return send_signal(client, &page(snapshot, &[], true)).await;
+    }
+    while let Some(entries) = pages.next() {
+        send_signal(client, &page(snapshot.take(), entries, pages.peek().is_none())).await?;
+    }
+    Ok(())
+}
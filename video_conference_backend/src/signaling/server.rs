@@ -1,51 +1,574 @@
-use crate::models::{Client, SignalMessage};
-use crate::signaling::handlers;
-use std::collections::HashMap;
+use crate::abuse::{AbuseStore, SqliteAbuseStore};
+use crate::calls::{self as call_records, CallStore, SqliteCallStore};
+use crate::chat::{self as chat_history, ChatStore, SqliteChatStore};
+use crate::audit::{self, AuditSink, FileAuditSink, HttpAuditSink, SyslogAuditSink};
+use crate::auth::{token_from_request, CredentialStore, RelyingParty, SqliteCredentialStore};
+use crate::config;
+use crate::crypto::{revocation, server_identity};
+use crate::crypto::{CryptoPolicy, DefaultVerifier, Ed25519Signer, RevocationList, ServerSigner, SignatureVerifier, TrustAnchors, VerificationPool};
+use crate::firewall::{ConnectionLimit, ConnectionPermit, ConnectionTracker, GeoPolicy, IpFilter};
+use crate::models::{Client, Payload, Role, Signal, SignalKind, SignalMessage, WireEncoding};
+use crate::noise::{self, NoiseConfig};
+use crate::secrets::{self, KeyProvider, SecretResult};
+use crate::rooms;
+use crate::sessions;
+use crate::pinning::{KeyPinStore, SqlitePinStore};
+use crate::push::{Notifier, WebhookNotifier};
+use crate::storage::{RoomStore, SqliteRoomStore, StorageCipher};
+use crate::tenants::{self, ApiKeyStore, CachedKeyStore, FileKeyStore, SqliteKeyStore, Tenant};
+use crate::tls::{self, CertIdentity};
+use crate::signaling::{abuse, acks, admin, auth, calls, chat, correlation, dedup, deflate, documents, e2ee, errors, files, handlers, handshake, ice, keys, limits, ordering, polls, protocol, questions, reactions, renegotiation, webauthn, moderation, roster, screenshare, sdp, turn, whiteboard};
+use crate::signaling::deflate::{DeflateConfig, DeflateStream};
+use crate::signaling::handshake::Subprotocol;
+use crate::signaling::state::{SharedState, SignalingState};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use chrono::Utc;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
 use tokio::sync::{mpsc, Mutex};
-use tokio_tungstenite::{accept_async, tungstenite::protocol::Message};
+use tokio_tungstenite::accept_hdr_async_with_config;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::protocol::{Message, WebSocketConfig};
+use tokio_tungstenite::tungstenite::Error as WsError;
 use futures_util::{StreamExt, SinkExt};
 
 pub async fn run_signaling_server(addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-    let listener = TcpListener::bind(&addr).await?;
-    let clients: Arc<Mutex<HashMap<SocketAddr, Client>>> = Arc::new(Mutex::new(HashMap::new()));
+    SignalingServer::builder().addr(addr).build().run().await
+}
 
-    println!("Secure WebRTC signaling server listening on: {}", addr);
+pub struct SignalingServer {
+    addr: SocketAddr,
+    room_store: Option<Arc<dyn RoomStore>>,
+    key_pins: Option<Arc<dyn KeyPinStore>>,
+    abuse: Option<Arc<dyn AbuseStore>>,
+    chat: Option<Arc<dyn ChatStore>>,
+    call_store: Option<Arc<dyn CallStore>>,
+    notifier: Option<Arc<dyn Notifier>>,
+    credentials: Option<Arc<dyn CredentialStore>>,
+    server_signer: Option<Arc<dyn ServerSigner>>,
+    audit_sinks: Vec<Arc<dyn AuditSink>>,
+    verifier: Arc<dyn SignatureVerifier>,
+    api_keys: Option<Arc<dyn ApiKeyStore>>,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    crypto_policy: Option<CryptoPolicy>,
+    tls: Option<(PathBuf, PathBuf)>,
+    client_ca: Option<PathBuf>,
+}
 
-    while let Ok((stream, addr)) = listener.accept().await {
-        let clients = Arc::clone(&clients);
-        
-        tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream, addr, clients).await {
-                eprintln!("Connection error for {}: {}", addr, e);
-            }
+// Defaults: configured listen address, SQLite room and key pin stores at the configured paths, DefaultVerifier,
+// API keys from the configured file or database (none, so no key needed, if neither is set),
+// and TLS from the configured certificate, key and client CA paths (plain ws:// if unset)
+#[derive(Default)]
+pub struct SignalingServerBuilder {
+    addr: Option<SocketAddr>,
+    room_store: Option<Arc<dyn RoomStore>>,
+    key_pins: Option<Arc<dyn KeyPinStore>>,
+    abuse: Option<Arc<dyn AbuseStore>>,
+    chat: Option<Arc<dyn ChatStore>>,
+    call_store: Option<Arc<dyn CallStore>>,
+    notifier: Option<Arc<dyn Notifier>>,
+    credentials: Option<Arc<dyn CredentialStore>>,
+    server_signer: Option<Arc<dyn ServerSigner>>,
+    audit_sinks: Vec<Arc<dyn AuditSink>>,
+    verifier: Option<Arc<dyn SignatureVerifier>>,
+    api_keys: Option<Arc<dyn ApiKeyStore>>,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    crypto_policy: Option<CryptoPolicy>,
+    tls: Option<(PathBuf, PathBuf)>,
+    client_ca: Option<PathBuf>,
+}
+
+impl SignalingServerBuilder {
+    pub fn addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = Some(addr);
+        self
+    }
+
+    pub fn room_store(mut self, room_store: Arc<dyn RoomStore>) -> Self {
+        self.room_store = Some(room_store);
+        self
+    }
+
+    pub fn key_pin_store(mut self, key_pins: Arc<dyn KeyPinStore>) -> Self {
+        self.key_pins = Some(key_pins);
+        self
+    }
+
+    // Abuse reports and the server-wide ban list
+    pub fn abuse_store(mut self, abuse: Arc<dyn AbuseStore>) -> Self {
+        self.abuse = Some(abuse);
+        self
+    }
+
+    // Chat history for late joiners
+    pub fn chat_store(mut self, chat: Arc<dyn ChatStore>) -> Self {
+        self.chat = Some(chat);
+        self
+    }
+
+    // Call history and missed calls not yet delivered
+    pub fn call_store(mut self, call_store: Arc<dyn CallStore>) -> Self {
+        self.call_store = Some(call_store);
+        self
+    }
+
+    // Wakes callees with nothing connected; defaults to a webhook at the configured push URL
+    pub fn notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    // Where WebAuthn credentials are kept; only used when a relying party is configured
+    pub fn credential_store(mut self, credentials: Arc<dyn CredentialStore>) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    // Signs everything the server sends; defaults to an Ed25519 key at the configured path
+    pub fn server_signer(mut self, server_signer: Arc<dyn ServerSigner>) -> Self {
+        self.server_signer = Some(server_signer);
+        self
+    }
+
+    // Adds a destination for audit records; the configured sinks are used only if none are added
+    pub fn audit_sink(mut self, audit_sink: Arc<dyn AuditSink>) -> Self {
+        self.audit_sinks.push(audit_sink);
+        self
+    }
+
+    pub fn verifier(mut self, verifier: Arc<dyn SignatureVerifier>) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    // Which algorithms, hashes and key sizes clients may use; read from the config when unset
+    pub fn crypto_policy(mut self, crypto_policy: CryptoPolicy) -> Self {
+        self.crypto_policy = Some(crypto_policy);
+        self
+    }
+
+    pub fn api_key_store(mut self, api_keys: Arc<dyn ApiKeyStore>) -> Self {
+        self.api_keys = Some(api_keys);
+        self
+    }
+
+    // Where the TLS key, TURN secret, storage keys and server identity and Noise keys are read from; the
+    // configured KEY_PROVIDER is used when unset
+    pub fn key_provider(mut self, key_provider: Arc<dyn KeyProvider>) -> Self {
+        self.key_provider = Some(key_provider);
+        self
+    }
+
+    // PEM certificate chain and private key; both files are watched and reloaded on change
+    pub fn tls(mut self, cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        self.tls = Some((cert_path.into(), key_path.into()));
+        self
+    }
+
+    // Requires clients to present a certificate issued by this CA; only applies with `tls`
+    pub fn client_ca(mut self, ca_path: impl Into<PathBuf>) -> Self {
+        self.client_ca = Some(ca_path.into());
+        self
+    }
+
+    pub fn build(self) -> SignalingServer {
+        let tls = self.tls.or_else(|| match (config::get_tls_cert_path(), config::get_tls_key_path()) {
+            (Some(cert_path), Some(key_path)) => Some((cert_path.into(), key_path.into())),
+            _ => None,
         });
+
+        SignalingServer {
+            tls,
+            client_ca: self.client_ca.or_else(|| config::get_tls_client_ca_path().map(PathBuf::from)),
+            addr: self.addr.unwrap_or_else(config::get_signaling_server_addr),
+            room_store: self.room_store,
+            key_pins: self.key_pins,
+            abuse: self.abuse,
+            chat: self.chat,
+            call_store: self.call_store,
+            notifier: self.notifier,
+            credentials: self.credentials,
+            server_signer: self.server_signer,
+            audit_sinks: self.audit_sinks,
+            verifier: self.verifier.unwrap_or_else(|| Arc::new(DefaultVerifier::from_config())),
+            api_keys: self.api_keys,
+            key_provider: self.key_provider,
+            crypto_policy: self.crypto_policy,
+        }
     }
+}
 
-    Ok(())
+impl SignalingServer {
+    pub fn builder() -> SignalingServerBuilder {
+        SignalingServerBuilder::default()
+    }
+
+    pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        let key_provider = match self.key_provider {
+            Some(key_provider) => Some(key_provider),
+            None => secrets::from_config().map_err(|e| e.to_string())?,
+        };
+        let cipher = load_storage_cipher(key_provider.as_deref()).await.map_err(|e| e.to_string())?;
+        let room_store = match self.room_store {
+            Some(room_store) => room_store,
+            None => {
+                let mut store = SqliteRoomStore::open(config::get_room_db_path()).map_err(|e| e.to_string())?;
+                if let Some(cipher) = &cipher {
+                    store = store.with_cipher(Arc::clone(cipher));
+                    report_rekeyed("room", store.rekey().map_err(|e| e.to_string())?);
+                }
+                Arc::new(store)
+            }
+        };
+        let api_keys: Option<Arc<dyn ApiKeyStore>> = match (self.api_keys, config::get_api_keys_file(), config::get_api_keys_db()) {
+            (Some(api_keys), _, _) => Some(api_keys),
+            (None, Some(path), _) => Some(Arc::new(FileKeyStore::open(path).map_err(|e| e.to_string())?)),
+            (None, None, Some(path)) => {
+                let store = Arc::new(SqliteKeyStore::open(path).map_err(|e| e.to_string())?);
+                let cache = Arc::new(CachedKeyStore::load(store).map_err(|e| e.to_string())?);
+                tokio::spawn(tenants::run_refresher(Arc::clone(&cache)));
+                Some(cache)
+            }
+            (None, None, None) => None,
+        };
+        let key_pins = match self.key_pins {
+            Some(key_pins) => key_pins,
+            None => {
+                let mut store = SqlitePinStore::open(config::get_key_pin_db_path()).map_err(|e| e.to_string())?;
+                if let Some(cipher) = &cipher {
+                    store = store.with_cipher(Arc::clone(cipher));
+                    report_rekeyed("key pin", store.rekey().map_err(|e| e.to_string())?);
+                }
+                Arc::new(store)
+            }
+        };
+        let abuse: Arc<dyn AbuseStore> = match self.abuse {
+            Some(abuse) => abuse,
+            None => {
+                let mut store = SqliteAbuseStore::open(config::get_abuse_db_path()).map_err(|e| e.to_string())?;
+                if let Some(cipher) = &cipher {
+                    store = store.with_cipher(Arc::clone(cipher));
+                    report_rekeyed("abuse", store.rekey().map_err(|e| e.to_string())?);
+                }
+                Arc::new(store)
+            }
+        };
+        let chat: Arc<dyn ChatStore> = match self.chat {
+            Some(chat) => chat,
+            None => {
+                let mut store = SqliteChatStore::open(config::get_chat_db_path()).map_err(|e| e.to_string())?;
+                if let Some(cipher) = &cipher {
+                    store = store.with_cipher(Arc::clone(cipher));
+                    report_rekeyed("chat", store.rekey().map_err(|e| e.to_string())?);
+                }
+                Arc::new(store)
+            }
+        };
+        let call_store: Arc<dyn CallStore> = match self.call_store {
+            Some(call_store) => call_store,
+            None => Arc::new(SqliteCallStore::open(config::get_call_db_path()).map_err(|e| e.to_string())?),
+        };
+        let server_signer: Arc<dyn ServerSigner> = match (self.server_signer, &key_provider) {
+            (Some(server_signer), _) => server_signer,
+            (None, Some(key_provider)) => {
+                let seed = key_provider.load(&config::get_server_key_path()).await.map_err(|e| e.to_string())?;
+                Arc::new(Ed25519Signer::from_secret(&seed).map_err(|e| e.to_string())?)
+            }
+            (None, None) => Arc::new(
+                Ed25519Signer::load_or_generate(Path::new(&config::get_server_key_path())).map_err(|e| e.to_string())?
+            ),
+        };
+        server_identity::install(server_signer);
+        let mut audit_sinks = self.audit_sinks;
+        if audit_sinks.is_empty() {
+            if let Some(path) = config::get_audit_log_path() {
+                audit_sinks.push(Arc::new(FileAuditSink::open(path).map_err(|e| e.to_string())?));
+            }
+            if let Some(addr) = config::get_audit_syslog_addr() {
+                audit_sinks.push(Arc::new(SyslogAuditSink::connect(addr).map_err(|e| e.to_string())?));
+            }
+            if let Some(url) = config::get_audit_http_url() {
+                audit_sinks.push(Arc::new(HttpAuditSink::new(url).map_err(|e| e.to_string())?));
+            }
+        }
+        audit::install(audit_sinks);
+        let mut state = SignalingState::with_room_store(room_store);
+        state.key_pins = Some(key_pins);
+        state.abuse = Some(abuse);
+        state.chat = Some(Arc::clone(&chat));
+        state.call_store = Some(Arc::clone(&call_store));
+        state.notifier = match (self.notifier, config::get_push_webhook_url()) {
+            (Some(notifier), _) => Some(notifier),
+            (None, Some(url)) => Some(Arc::new(WebhookNotifier::new(url).map_err(|e| e.to_string())?) as Arc<dyn Notifier>),
+            (None, None) => None,
+        };
+        state.verifier = Some(self.verifier);
+        state.crypto_policy = Arc::new(self.crypto_policy.unwrap_or_else(CryptoPolicy::from_config));
+        state.verification_pool = Some(VerificationPool::spawn(
+            state.verifier(),
+            config::get_verify_workers(),
+            config::get_verify_max_batch()
+        ));
+        state.turn_secret = match (&key_provider, config::get_turn_secret_ref()) {
+            (Some(key_provider), Some(reference)) => Some(key_provider.load(&reference).await.map_err(|e| e.to_string())?),
+            (None, Some(path)) => Some(std::fs::read_to_string(path)?.trim_end().as_bytes().to_vec()),
+            (_, None) => config::get_turn_secret().map(String::into_bytes),
+        };
+        state.api_keys = api_keys;
+        let ip_filter = IpFilter::from_config().map_err(|e| e.to_string())?;
+        *state.ip_filter.write().map_err(|e| e.to_string())? = ip_filter;
+        let ip_filter = Arc::clone(&state.ip_filter);
+        state.geo = GeoPolicy::from_config().map_err(|e| e.to_string())?.map(Arc::new);
+        let geo = state.geo.clone();
+        let lockouts = Arc::clone(&state.lockouts);
+        if let Some(rp_id) = config::get_webauthn_rp_id() {
+            let origin = config::get_webauthn_origin().unwrap_or_else(|| format!("https://{}", rp_id));
+            let credentials = match self.credentials {
+                Some(credentials) => credentials,
+                None => Arc::new(SqliteCredentialStore::open(config::get_webauthn_db_path()).map_err(|e| e.to_string())?),
+            };
+            state.relying_party = Some(RelyingParty { rp_id, origin });
+            state.credentials = Some(credentials);
+        }
+        if let Some(path) = config::get_key_ca_path() {
+            state.key_anchors = Some(TrustAnchors::load(Path::new(&path))?);
+        }
+        if let Some(path) = config::get_revoked_keys_path() {
+            if Path::new(&path).exists() {
+                state.revoked = RevocationList::from_file(Path::new(&path)).map_err(|e| e.to_string())?;
+            }
+        }
+        if config::get_noise_enabled() {
+            let mut noise = match &key_provider {
+                Some(key_provider) => {
+                    let contents = key_provider.load(&config::get_noise_key_path()).await.map_err(|e| e.to_string())?;
+                    NoiseConfig::parse(&String::from_utf8(contents)?).map_err(|e| e.to_string())?
+                }
+                None => NoiseConfig::load_or_generate(Path::new(&config::get_noise_key_path())).map_err(|e| e.to_string())?,
+            };
+            if let Some(path) = config::get_noise_client_keys_path() {
+                noise.allowed_clients = Some(revocation::load_file(Path::new(&path)).map_err(|e| e.to_string())?);
+            }
+            state.noise = Some(Arc::new(noise));
+        }
+        let state: SharedState = Arc::new(Mutex::new(state));
+
+        let restored = rooms::restore_rooms(Arc::clone(&state)).await?;
+        println!("Restored {} stored rooms", restored);
+
+        let acme_domains = config::get_acme_domains();
+        let acceptor = match self.tls {
+            None if !acme_domains.is_empty() => Some(tls::acme::start(
+                acme_domains,
+                config::get_acme_contact(),
+                config::get_acme_cache_dir().into(),
+                config::get_acme_production()
+            )),
+            Some((cert_path, key_path)) => {
+                let key = match &key_provider {
+                    Some(key_provider) => {
+                        let key_pem = key_provider.load(&key_path.to_string_lossy()).await.map_err(|e| e.to_string())?;
+                        tls::certified_key(&cert_path, &key_pem).map_err(|e| e.to_string())?
+                    }
+                    None => tls::load_certified_key(&cert_path, &key_path).map_err(|e| e.to_string())?,
+                };
+                let resolver = Arc::new(tls::ReloadingCertResolver::new(key));
+                let client_roots = match &self.client_ca {
+                    Some(ca_path) => Some(tls::load_client_roots(ca_path).map_err(|e| e.to_string())?),
+                    None => None,
+                };
+                // A provider-held key is only read at startup
+                if key_provider.is_none() {
+                    tokio::spawn(tls::watch_certificates(
+                        Arc::clone(&resolver),
+                        cert_path,
+                        key_path,
+                        config::get_tls_reload_interval()
+                    ));
+                }
+                Some(tls::acceptor(resolver, client_roots, config::get_tls_client_cert_required()))
+            }
+            None => None,
+        };
+
+        let scheme = if acceptor.is_some() { "wss" } else { "ws" };
+        println!("Secure WebRTC signaling server listening on: {}://{}", scheme, self.addr);
+
+        tokio::spawn(rooms::run_room_sweeper(Arc::clone(&state)));
+        tokio::spawn(chat_history::run_history_pruner(chat));
+        tokio::spawn(call_records::run_record_pruner(call_store));
+        tokio::spawn(reactions::run_reaction_flusher(Arc::clone(&state)));
+        tokio::spawn(ordering::run_reorder_flusher(Arc::clone(&state)));
+        tokio::spawn(calls::run_call_timer(Arc::clone(&state)));
+        if let Some(path) = config::get_revoked_keys_path() {
+            tokio::spawn(keys::watch_revocations(
+                Arc::clone(&state),
+                path.into(),
+                config::get_revocation_reload_interval()
+            ));
+        }
+
+        let connections = Arc::new(ConnectionTracker::from_config());
+        while let Ok((stream, addr)) = listener.accept().await {
+            if !ip_filter.read().is_ok_and(|filter| filter.permits(&addr.ip())) {
+                eprintln!("Refused connection from {}", addr);
+                continue;
+            }
+            if let Some(remaining) = lockouts.locked_for(&addr.ip(), Instant::now()) {
+                eprintln!("Refused connection from {}: locked out for another {}s", addr, remaining.as_secs());
+                continue;
+            }
+            let region = match geo.as_ref().map(|geo| geo.admit(&addr.ip())) {
+                Some(Err(region)) => {
+                    eprintln!("Refused connection from {} (region {})", addr, region);
+                    continue;
+                }
+                Some(Ok(region)) => Some(region),
+                None => None,
+            };
+            // Connections over a limit still get as far as the upgrade, so they can be told to back off
+            let permit = connections.try_acquire(addr.ip());
+            let deadline = tokio::time::Instant::now() + config::get_handshake_timeout();
+            let state = Arc::clone(&state);
+            let acceptor = acceptor.clone();
+
+            tokio::spawn(async move {
+                let result = match acceptor {
+                    Some(acceptor) => match tokio::time::timeout_at(deadline, acceptor.accept(stream)).await {
+                        Ok(Ok(Some(stream))) => {
+                            let identity = tls::peer_identity(stream.get_ref().1.peer_certificates());
+                            handle_connection(stream, addr, identity, permit, deadline, state).await
+                        }
+                        Ok(Ok(None)) => Ok(()),
+                        Ok(Err(e)) => Err(e.into()),
+                        Err(_) => Err("TLS handshake timed out".into()),
+                    },
+                    None => handle_connection(stream, addr, None, permit, deadline, state).await,
+                };
+                if let Err(e) = result {
+                    eprintln!("Connection error for {}: {}", addr, e);
+                }
+                // Counts the connection as active in its region until now
+                drop(region);
+            });
+        }
+
+        Ok(())
+    }
 }
 
-async fn handle_connection(
-    stream: tokio::net::TcpStream,
+async fn handle_connection<S>(
+    stream: S,
     addr: SocketAddr,
-    clients: Arc<Mutex<HashMap<SocketAddr, Client>>>
-) -> Result<(), Box<dyn std::error::Error>> {
-    let ws_stream = accept_async(stream).await?;
+    certificate: Option<CertIdentity>,
+    // Held for the life of the connection
+    permit: Result<ConnectionPermit, ConnectionLimit>,
+    // When the WebSocket and Noise handshakes have to be done by
+    handshake_deadline: tokio::time::Instant,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (api_keys, noise_config) = {
+        let state = state.lock().await;
+        (state.api_keys.clone(), state.noise.clone())
+    };
+    let allowed_origins = config::get_allowed_origins();
+    let require_subprotocol = config::get_require_subprotocol();
+    let max_payload_size = config::get_max_payload_size();
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(config::get_max_message_size()),
+        max_frame_size: Some(config::get_max_frame_size()),
+        ..WebSocketConfig::default()
+    };
+    let mut handshake_token = None;
+    let mut tenant = None;
+    let mut wants_noise = false;
+    let mut encoding = WireEncoding::Json;
+    let mut compress = false;
+    let deflate_enabled = config::get_deflate_enabled();
+    // The callback's error type is fixed by tungstenite
+    #[allow(clippy::result_large_err)]
+    let upgrade = accept_hdr_async_with_config(DeflateStream::new(stream), |request: &Request, mut response: Response| {
+        if let Err(limit) = &permit {
+            return Err(overloaded(*limit));
+        }
+        check_origin(&allowed_origins, request)?;
+        handshake_token = token_from_request(request);
+        if let Some(api_keys) = &api_keys {
+            tenant = Some(authorize_tenant(api_keys.as_ref(), request)?);
+        }
+        if let Some(subprotocol) = handshake::negotiate(request, noise_config.is_some(), require_subprotocol)? {
+            response
+                .headers_mut()
+                .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(subprotocol.name()));
+            wants_noise = subprotocol == Subprotocol::Noise;
+            encoding = subprotocol.encoding();
+        }
+        // Noise frames are ciphertext, which does not compress
+        if deflate_enabled && !wants_noise && deflate::accepts_offer(request) {
+            response
+                .headers_mut()
+                .insert("Sec-WebSocket-Extensions", HeaderValue::from_static(deflate::RESPONSE));
+            compress = true;
+        }
+        Ok(response)
+    }, Some(ws_config));
+    let mut ws_stream = tokio::time::timeout_at(handshake_deadline, upgrade)
+        .await
+        .map_err(|_| "WebSocket handshake timed out")??;
+    if compress {
+        ws_stream.get_mut().enable(DeflateConfig::from_config());
+    }
+    let noise = match noise_config.filter(|_| wants_noise) {
+        Some(noise_config) => Some(Arc::new(
+            tokio::time::timeout_at(handshake_deadline, noise::respond(&mut ws_stream, &noise_config))
+                .await
+                .map_err(|_| "Noise handshake timed out")?
+                .map_err(|e| e.to_string())?
+        )),
+        None => None,
+    };
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
     let (tx, mut rx) = mpsc::channel(100);
     
-    let client_id = uuid::Uuid::new_v4().to_string();
+    let mut client_id = uuid::Uuid::new_v4().to_string();
+    let mut client = Client::new(tx, client_id.clone(), addr);
+    client.tenant = tenant;
+    client.certificate = certificate;
+    client.noise_key = noise.as_ref().map(|noise| noise.remote_static.clone());
+    client.encoding = encoding;
+    let shutdown = Arc::clone(&client.shutdown);
     {
-        let mut clients_map = clients.lock().await;
-        clients_map.insert(addr, Client::new(tx, client_id.clone(), addr));
+        let mut state = state.lock().await;
+        state.clients.insert(addr, client);
+    }
+    auth::send_server_identity(addr, Arc::clone(&state)).await?;
+    auth::send_challenge(addr, Arc::clone(&state)).await?;
+    if let Some(token) = handshake_token {
+        auth::authenticate_token(&token, addr, Arc::clone(&state)).await?;
     }
 
-    let clients_clone = Arc::clone(&clients);
-    let forward_task = tokio::spawn(async move {
+    let state_clone = Arc::clone(&state);
+    let outbound_noise = noise.clone();
+    let mut forward_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
+            let msg = match &outbound_noise {
+                Some(noise) => match noise.seal(msg) {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        eprintln!("Noise encryption error: {}", e);
+                        break;
+                    }
+                },
+                None => msg,
+            };
             if let Err(e) = ws_sender.send(msg).await {
                 eprintln!("Forward error: {}", e);
                 break;
@@ -53,35 +576,498 @@ async fn handle_connection(
         }
     });
 
-    while let Some(Ok(message)) = ws_receiver.next().await {
-        if let Message::Text(text) = message {
-            if let Ok(mut signal) = serde_json::from_str::<SignalMessage>(&text) {
-                signal.sender_id = client_id.clone();
-                signal.timestamp = Utc::now().timestamp();
+    loop {
+        let message = tokio::select! {
+            message = ws_receiver.next() => message,
+            _ = shutdown.notified() => break,
+        };
+        let message = match message {
+            Some(Ok(message)) => message,
+            Some(Err(WsError::Capacity(e))) => {
+                eprintln!("Dropping {}: {}", addr, e);
+                break;
+            }
+            _ => break,
+        };
 
-                match signal.signal_type.as_str() {
-                    "secure-offer" => {
-                        handlers::handle_secure_offer(&signal, addr, Arc::clone(&clients_clone)).await?;
-                    }
-                    "secure-answer" => {
-                        handlers::handle_secure_answer(&signal, addr, Arc::clone(&clients_clone)).await?;
-                    }
-                    "ice-candidate" => {
-                        handlers::broadcast_to_verified_peers(&signal, addr, Arc::clone(&clients_clone)).await?;
-                    }
-                    _ => eprintln!("Unknown signal type: {}", signal.signal_type),
+        let frame = match (&noise, message) {
+            (Some(noise), message) => match noise.open(message) {
+                Ok(Some(text)) => Message::Text(text),
+                Ok(None) => continue,
+                // A frame that fails to decrypt leaves the two sides' nonces out of step for good
+                Err(e) => {
+                    eprintln!("Noise decryption error for {}: {}", addr, e);
+                    break;
                 }
+            },
+            (None, frame @ (Message::Text(_) | Message::Binary(_))) => frame,
+            (None, _) => continue,
+        };
+        if frame.len() > max_payload_size {
+            limits::reject_oversized(addr, frame.len(), max_payload_size, &state_clone).await;
+            break;
+        }
+        let mut signal = match encoding.decode(&frame) {
+            Ok(signal) => signal,
+            Err(e) => {
+                reject_malformed(encoding.peek_correlation_id(&frame), &e.to_string(), addr, &state_clone).await;
+                continue;
             }
+        };
+        signal.sender_id = client_id.clone();
+        signal.timestamp = Utc::now().timestamp();
+        // Never relayed, so peers cannot replay it
+        let session_token = signal.session_token.take();
+        let reservation = ordering::reserve(&mut signal, addr, &state_clone).await;
+
+        let correlation_id = signal.correlation_id.clone();
+        let handled = correlation::scope(addr, correlation_id, async {
+            let (dispatched, outcome) = acks::track(
+                dispatch_signal(&signal, session_token.as_deref(), addr, &mut client_id, Arc::clone(&state_clone))
+            ).await;
+            let dispatched = match dispatched.map_err(|e| e.to_string()) {
+                Ok(()) => {
+                    dedup::settle(&signal, &outcome, addr, &state_clone).await;
+                    acks::send_ack(&signal, outcome, addr, &state_clone).await.map_err(|e| e.to_string())
+                }
+                Err(e) => Err(e),
+            };
+            let Err(e) = dispatched else {
+                return true;
+            };
+            eprintln!("Connection error for {}: {}{}", addr, e, correlation::tag());
+            let state = state_clone.lock().await;
+            if let Some(client) = state.clients.get(&addr) {
+                let error = errors::error_signal("internal-error", "The server could not handle this message", None, serde_json::Value::Null);
+                let _ = handlers::send_signal(client, &error).await;
+            }
+            false
+        }).await;
+        ordering::settle(reservation, &state_clone).await;
+        if !handled {
+            break;
         }
     }
 
-    // Cleanup
-    forward_task.abort();
-    cleanup_client(addr, clients).await;
+    // Cleanup drops the client's sender, so the forward task ends once queued frames
+    // (such as a kick notice and close frame) are flushed
+    cleanup_client(addr, state).await;
+    if tokio::time::timeout(Duration::from_secs(1), &mut forward_task).await.is_err() {
+        forward_task.abort();
+    }
     Ok(())
 }
 
-async fn cleanup_client(addr: SocketAddr, clients: Arc<Mutex<HashMap<SocketAddr, Client>>>) {
-    let mut clients_map = clients.lock().await;
-    clients_map.remove(&addr);
+// 503 with Retry-After, which load balancers take as a signal to send clients elsewhere
+fn overloaded(limit: ConnectionLimit) -> ErrorResponse {
+    eprintln!("Refused WebSocket upgrade: {}", limit.message());
+    let mut response = ErrorResponse::new(Some(limit.message().to_string()));
+    *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    response.headers_mut().insert(
+        "Retry-After",
+        HeaderValue::from(config::get_connection_retry_after().as_secs())
+    );
+    response
+}
+
+// Turns browsers on pages outside the allowlist away with 403
+#[allow(clippy::result_large_err)]
+fn check_origin(allowed_origins: &[String], request: &Request) -> Result<(), ErrorResponse> {
+    let Some(origin) = request.headers().get("Origin") else {
+        return Ok(());
+    };
+    if allowed_origins.is_empty() || allowed_origins.iter().any(|allowed| allowed == "*") {
+        return Ok(());
+    }
+
+    let origin = origin.to_str().unwrap_or_default().trim_end_matches('/');
+    if allowed_origins.iter().any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin)) {
+        return Ok(());
+    }
+
+    eprintln!("Rejected WebSocket upgrade from origin {}", origin);
+    let mut response = ErrorResponse::new(Some("Origin not allowed".to_string()));
+    *response.status_mut() = StatusCode::FORBIDDEN;
+    Err(response)
+}
+
+// The storage key and any retired ones, read from the key provider or from files
+async fn load_storage_cipher(key_provider: Option<&dyn KeyProvider>) -> SecretResult<Option<Arc<StorageCipher>>> {
+    let Some(reference) = config::get_storage_key_ref() else {
+        return Ok(None);
+    };
+    let load = |reference: String| async move {
+        match key_provider {
+            Some(key_provider) => key_provider.load(&reference).await,
+            None => Ok(std::fs::read(&reference)?),
+        }
+    };
+
+    let current = load(reference).await?;
+    let mut retired = Vec::new();
+    for reference in config::get_storage_retired_key_refs() {
+        retired.push(load(reference).await?);
+    }
+    let cipher = StorageCipher::new(&current, &retired)?;
+    println!("Persisted state is encrypted with storage key {}", cipher.key_id());
+    Ok(Some(Arc::new(cipher)))
+}
+
+fn report_rekeyed(store: &str, count: usize) {
+    if count > 0 {
+        println!("Re-encrypted {} {} store rows with the current storage key", count, store);
+    }
+}
+
+// Turns the handshake away with 401 unless it carries a known API key
+#[allow(clippy::result_large_err)]
+fn authorize_tenant(api_keys: &dyn ApiKeyStore, request: &Request) -> Result<Tenant, ErrorResponse> {
+    let tenant = tenants::api_key_from_request(request)
+        .and_then(|api_key| match api_keys.lookup(&api_key) {
+            Ok(tenant) => tenant,
+            Err(e) => {
+                eprintln!("API key lookup failed: {}", e);
+                None
+            }
+        });
+
+    tenant.ok_or_else(|| {
+        let mut response = ErrorResponse::new(Some("A valid API key is required".to_string()));
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        response
+    })
+}
+
+// `client_id` is updated in place when the connection resumes an earlier session
+pub async fn dispatch_signal(
+    signal: &SignalMessage,
+    session_token: Option<&str>,
+    addr: SocketAddr,
+    client_id: &mut String,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let kind = SignalKind::of(&signal.signal_type);
+    if !limits::check_rate_limit(kind, addr, &state).await? {
+        return Ok(());
+    }
+    // Read once, for the checks below
+    let payload = Payload::read(kind, signal);
+    if !limits::check_payload(kind, &payload, addr, &state).await? {
+        return Ok(());
+    }
+    if !sdp::check_description(kind, &payload, addr, &state).await? {
+        return Ok(());
+    }
+    if !auth::check_authenticated(kind, addr, &state).await? {
+        return Ok(());
+    }
+    if !auth::check_session_token(signal, kind, session_token, addr, &state).await? {
+        return Ok(());
+    }
+    if !dedup::check_message_id(signal, addr, &state).await? {
+        return Ok(());
+    }
+    if !auth::check_sequence(signal, kind, addr, &state).await? {
+        return Ok(());
+    }
+
+    let typed = match Signal::from_message(signal) {
+        Ok(typed) => typed,
+        Err(e) => {
+            let state = state.lock().await;
+            if let Some(client) = state.clients.get(&addr) {
+                let message = format!("Invalid {} payload: {}", signal.signal_type, e);
+                handlers::send_error(client, "invalid-payload", &message, None).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    match typed {
+        Signal::Hello(payload) => {
+            protocol::handle_hello(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::Authenticate(payload) => {
+            auth::handle_authenticate(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::WebauthnRegisterBegin(payload) => {
+            webauthn::handle_register_begin(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::WebauthnRegister(payload) => {
+            webauthn::handle_register(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::WebauthnLoginBegin(payload) => {
+            webauthn::handle_login_begin(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::WebauthnLogin(payload) => {
+            webauthn::handle_login(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ChallengeResponse(payload) => {
+            auth::handle_challenge_response(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::KeyAnnounce(payload) => {
+            e2ee::handle_key_announce(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::KeyRequest(payload) => {
+            e2ee::handle_key_request(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::Sealed => {
+            handlers::relay_sealed(signal, addr, Arc::clone(&state)).await?;
+        }
+        Signal::SecureOffer(_)
+        | Signal::SecureAnswer(_)
+        | Signal::IceCandidate(_)
+        | Signal::RenegotiateOffer(_)
+        | Signal::RenegotiateAnswer(_)
+        | Signal::IceRestart(_) if config::get_blind_relay() => {
+            let state = state.lock().await;
+            if let Some(client) = state.clients.get(&addr) {
+                handlers::send_error(client, "blind-relay-only", "This server only relays sealed messages between peers", None).await?;
+            }
+        }
+        Signal::SecureOffer(payload) => {
+            handlers::handle_secure_offer(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::SecureAnswer(payload) => {
+            handlers::handle_secure_answer(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::IceCandidate(payload) => {
+            ice::handle_ice_candidate(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::RenegotiateOffer(_) => {
+            renegotiation::handle_renegotiate_offer(signal, addr, Arc::clone(&state)).await?;
+        }
+        Signal::RenegotiateAnswer(_) => {
+            renegotiation::handle_renegotiate_answer(signal, addr, Arc::clone(&state)).await?;
+        }
+        Signal::IceRestart(payload) => {
+            renegotiation::handle_ice_restart(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CreateRoom(payload) => {
+            handlers::handle_create_room(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::JoinRoom(payload) => {
+            handlers::handle_join_room(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::JoinDecision(payload) => {
+            handlers::handle_join_decision(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::LockRoom(payload) => {
+            handlers::handle_lock_room(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CreateInvite(payload) => {
+            handlers::handle_create_invite(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::RotateKey(payload) => {
+            keys::handle_rotate_key(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::RevokeKeys(payload) => {
+            admin::handle_revoke_keys(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ReloadIpFilter(payload) => {
+            admin::handle_reload_ip_filter(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::TurnCredentials => {
+            turn::handle_turn_credentials(addr, Arc::clone(&state)).await?;
+        }
+        Signal::Report(payload) => {
+            abuse::handle_report(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::Ban(payload) => {
+            abuse::handle_ban(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::Unban(payload) => {
+            abuse::handle_unban(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ListBans(payload) => {
+            abuse::handle_list_bans(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ListReports(payload) => {
+            abuse::handle_list_reports(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::RegionStats(payload) => {
+            admin::handle_region_stats(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CompressionStats(payload) => {
+            admin::handle_compression_stats(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ListRooms(payload) => {
+            handlers::handle_list_rooms(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ResumeSession(payload) => {
+            if let Some(resumed_id) = handlers::handle_resume_session(payload, addr, Arc::clone(&state)).await? {
+                *client_id = resumed_id;
+            }
+        }
+        Signal::Presence(payload) => {
+            roster::handle_presence(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::SetProfile(profile) => {
+            roster::handle_set_profile(profile, addr, Arc::clone(&state)).await?;
+        }
+        Signal::Capabilities(capabilities) => {
+            roster::handle_capabilities(capabilities, addr, Arc::clone(&state)).await?;
+        }
+        Signal::Chat(payload) => {
+            chat::handle_chat(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ChatHistory(payload) => {
+            chat::handle_chat_history(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::TypingStart => {
+            chat::handle_typing_start(addr, Arc::clone(&state)).await?;
+        }
+        Signal::TypingStop => {
+            chat::handle_typing_stop(addr, Arc::clone(&state)).await?;
+        }
+        Signal::Reaction(payload) => {
+            reactions::handle_reaction(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::FileOffer(payload) => {
+            files::handle_file_offer(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::FileAccept(payload) => {
+            files::handle_file_response(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::FileReject(payload) => {
+            files::handle_file_response(signal, payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::WhiteboardOp(payload) => {
+            whiteboard::handle_whiteboard_op(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::WhiteboardSnapshot(snapshot) => {
+            whiteboard::handle_whiteboard_snapshot(snapshot, addr, Arc::clone(&state)).await?;
+        }
+        Signal::WhiteboardSync(payload) => {
+            whiteboard::handle_whiteboard_sync(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::DocUpdate(payload) => {
+            documents::handle_doc_update(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::DocSnapshot(payload) => {
+            documents::handle_doc_snapshot(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::DocSync(payload) => {
+            documents::handle_doc_sync(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::PollCreate(payload) => {
+            polls::handle_poll_create(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::PollVote(payload) => {
+            polls::handle_poll_vote(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::PollClose(payload) => {
+            polls::handle_poll_close(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::PollResults(payload) => {
+            polls::handle_poll_results(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::QuestionAsk(payload) => {
+            questions::handle_question_ask(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::QuestionUpvote(payload) => {
+            questions::handle_question_upvote(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::QuestionModerate(payload) => {
+            questions::handle_question_moderate(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::QuestionSync => {
+            questions::handle_question_sync(addr, Arc::clone(&state)).await?;
+        }
+        Signal::CallInvite(payload) => {
+            calls::handle_call_invite(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CallRinging(payload) => {
+            calls::handle_call_ringing(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CallAccept(payload) => {
+            calls::handle_call_answer(payload, true, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CallReject(payload) => {
+            calls::handle_call_answer(payload, false, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CallCancel(payload) => {
+            calls::handle_call_cancel(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CallHangup(payload) => {
+            calls::handle_call_hangup(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::CallHistory(payload) => {
+            calls::handle_call_history(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::LeaveRoom => {
+            handlers::handle_leave_room(addr, Arc::clone(&state)).await?;
+        }
+        Signal::Kick(payload) => {
+            moderation::handle_kick(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::MuteRequest(payload) => {
+            moderation::handle_mute_request(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::MuteAll(payload) => {
+            moderation::handle_mute_all(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::Promote(payload) => {
+            moderation::handle_role_change(payload, addr, Arc::clone(&state), Role::Moderator).await?;
+        }
+        Signal::Demote(payload) => {
+            moderation::handle_role_change(payload, addr, Arc::clone(&state), Role::Participant).await?;
+        }
+        Signal::RaiseHand => {
+            roster::handle_raise_hand(addr, Arc::clone(&state)).await?;
+        }
+        Signal::LowerHand(payload) => {
+            roster::handle_lower_hand(payload, addr, Arc::clone(&state)).await?;
+        }
+        Signal::ScreenshareStart => {
+            screenshare::handle_screenshare_start(addr, Arc::clone(&state)).await?;
+        }
+        Signal::ScreenshareStop => {
+            screenshare::handle_screenshare_stop(addr, Arc::clone(&state)).await?;
+        }
+        Signal::Unknown => {
+            eprintln!("Unknown signal type: {}", signal.signal_type);
+            let state = state.lock().await;
+            if let Some(client) = state.clients.get(&addr) {
+                let message = format!("Unknown signal type {}", signal.signal_type);
+                handlers::send_error(client, "unknown-signal", &message, None).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Frames that are not a signal at all still get an error, pointing back to the frame's
+// correlation id when one can be picked out of it
+async fn reject_malformed(correlation_id: Option<String>, error: &str, addr: SocketAddr, state: &SharedState) {
+    eprintln!("Rejected malformed message from {}: {}", addr, error);
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&addr) else {
+        return;
+    };
+    let message = format!("Not a valid signal: {}", error);
+    correlation::scope(addr, correlation_id, async {
+        let error = errors::error_signal("malformed-message", &message, None, serde_json::Value::Null);
+        if let Err(e) = handlers::send_signal(client, &error).await {
+            eprintln!("Send error to {}: {}", addr, e);
+        }
+    }).await;
+}
+
+async fn cleanup_client(addr: SocketAddr, state: SharedState) {
+    let mut state = state.lock().await;
+    sessions::suspend_session(&mut state, addr, config::get_resume_grace_period());
+    calls::drop_calls(&mut state, addr).await;
+
+    let room_id = state.clients.get(&addr).and_then(|client| client.room_id.clone());
+    let client = state.remove_client(addr);
+    if let Some(client) = &client {
+        ordering::forget_sender(&state, &client.client_id).await;
+    }
+    if let (Some(client), Some(room_id)) = (client, room_id) {
+        if let Err(e) = roster::announce_leave(&mut state, &room_id, &client.client_id).await {
+            eprintln!("Failed to announce departure of {}: {}", addr, e);
+        }
+    }
 }
\ No newline at end of file
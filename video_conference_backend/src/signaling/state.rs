@@ -0,0 +1,296 @@
+use crate::abuse::{self, AbuseStore, Ban};
+use crate::calls::CallStore;
+use crate::chat::ChatStore;
+use crate::auth::{CredentialStore, RelyingParty};
+use crate::config;
+use crate::crypto::{check_sequence, AsyncVerifier, CryptoPolicy, DefaultVerifier, PolicyVerifier, NonceCache, RevocationList, SequenceError, SignatureVerifier, TrustAnchors, VerificationPool};
+use crate::firewall::{GeoPolicy, IpFilter};
+use crate::noise::NoiseConfig;
+use crate::pinning::{self, KeyPinStore, PinError};
+use crate::push::Notifier;
+use crate::models::{Client, Presence, Role, Room};
+use crate::rooms::{self, JoinError};
+use crate::sessions::SuspendedSession;
+use crate::signaling::calls::Calls;
+use crate::signaling::files::FileTransfers;
+use crate::signaling::reactions::ReactionTally;
+use crate::signaling::renegotiation::Renegotiations;
+use crate::signaling::sdp::SdpRewriter;
+use crate::storage::RoomStore;
+use crate::tenants::ApiKeyStore;
+use crate::ratelimit::{LockoutTable, RateLimiter};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use tokio::sync::Mutex;
+
+#[derive(Default)]
+pub struct SignalingState {
+    pub clients: HashMap<SocketAddr, Client>,
+    pub rooms: HashMap<String, Room>,
+    pub room_store: Option<Arc<dyn RoomStore>>,
+    // Falls back to the default P-256/Ed25519 verifier when unset
+    pub verifier: Option<Arc<dyn SignatureVerifier>>,
+    // Every client signature and certificate chain is held to it; the defaults accept everything
+    // the server can verify
+    pub crypto_policy: Arc<CryptoPolicy>,
+    // Runs verifications off the reactor in batches; each check gets its own blocking task when
+    // unset. Spawn it with `verifier()` so the crypto policy holds there too.
+    pub verification_pool: Option<VerificationPool>,
+    pub nonces: NonceCache,
+    pub revoked: RevocationList,
+    // Issuers of certificate chains in secure offers; chains are refused when unset
+    pub key_anchors: Option<TrustAnchors>,
+    // API keys are required to connect when set
+    pub api_keys: Option<Arc<dyn ApiKeyStore>>,
+    // Shared with the accept loop, which checks it without taking the state lock
+    pub ip_filter: Arc<RwLock<IpFilter>>,
+    // Country policy and per-region connection counts; shared with the accept loop like the IP filter
+    pub geo: Option<Arc<GeoPolicy>>,
+    // WebAuthn sign-in is available when both are set
+    pub relying_party: Option<RelyingParty>,
+    pub credentials: Option<Arc<dyn CredentialStore>>,
+    // Shared with the TURN server; TURN credentials are not issued when unset
+    pub turn_secret: Option<Vec<u8>>,
+    // Noise handshakes are refused when unset
+    pub noise: Option<Arc<NoiseConfig>>,
+    // Abuse reports and server-wide bans; reports are refused and bans not enforced when unset
+    pub abuse: Option<Arc<dyn AbuseStore>>,
+    // Chat is relayed but not kept, and late joiners get no history, when unset
+    pub chat: Option<Arc<dyn ChatStore>>,
+    // Calls are not recorded, and missed calls not delivered, when unset
+    pub call_store: Option<Arc<dyn CallStore>>,
+    // Callees with nothing connected are not pushed when unset
+    pub notifier: Option<Arc<dyn Notifier>>,
+    // Trust-on-first-use key pinning is skipped when unset
+    pub key_pins: Option<Arc<dyn KeyPinStore>>,
+    // Shared by all connections from an address; kept past the last one until refilled
+    pub ip_rate_limits: HashMap<IpAddr, RateLimiter>,
+    // Verification failures per address; checked by the accept loop, so shared like the IP filter
+    pub lockouts: Arc<LockoutTable>,
+    // Keyed by resume token
+    pub suspended: HashMap<String, SuspendedSession>,
+    pub renegotiations: Renegotiations,
+    pub file_transfers: FileTransfers,
+    // Call invites still ringing
+    pub calls: Calls,
+    // Reactions in large rooms, sent out as counts by the flusher
+    pub reactions: ReactionTally,
+    // Lets the embedding application change session descriptions before they are relayed
+    pub sdp_rewriter: Option<Arc<dyn SdpRewriter>>,
+}
+
+pub type SharedState = Arc<Mutex<SignalingState>>;
+
+impl SignalingState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_room_store(room_store: Arc<dyn RoomStore>) -> Self {
+        Self {
+            room_store: Some(room_store),
+            ..Self::default()
+        }
+    }
+
+    // The installed verifier, held to the crypto policy
+    pub fn verifier(&self) -> Arc<dyn SignatureVerifier> {
+        let inner = self.verifier
+            .clone()
+            .unwrap_or_else(|| Arc::new(DefaultVerifier::from_config()));
+        Arc::new(PolicyVerifier::new(Arc::clone(&self.crypto_policy), inner))
+    }
+
+    // Signature checks are CPU-bound, so handlers await them here instead of blocking the reactor
+    pub fn async_verifier(&self) -> AsyncVerifier {
+        match &self.verification_pool {
+            Some(pool) => AsyncVerifier::Pooled(pool.clone()),
+            None => AsyncVerifier::Blocking(self.verifier()),
+        }
+    }
+
+    // Checks `public_key` against the key pinned to the client's identity, pinning it if none is.
+    // The identity is taken as it will be once `public_key` is bound to the connection.
+    pub fn check_key_pin(&self, addr: SocketAddr, public_key: &[u8]) -> Result<(), PinError> {
+        match (&self.key_pins, self.clients.get(&addr)) {
+            (Some(key_pins), Some(client)) => {
+                let mut bound = client.clone();
+                bound.public_key = Some(public_key.to_vec());
+                pinning::check_pin(key_pins.as_ref(), &pinning::identity(&bound), public_key)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Accepts `seq` as the client's new high-water mark if it is strictly above the last one
+    pub fn advance_sequence(&mut self, addr: SocketAddr, seq: Option<u64>) -> Result<(), SequenceError> {
+        let Some(client) = self.clients.get_mut(&addr) else {
+            return Ok(());
+        };
+        let required = client.verified && config::get_require_seq();
+        if let Some(seq) = check_sequence(client.last_seq, seq, required)? {
+            client.last_seq = Some(seq);
+        }
+        Ok(())
+    }
+
+    // The server-wide ban, if any, on the client's identity or on `public_key`
+    pub fn check_ban(&self, addr: SocketAddr, public_key: Option<&[u8]>) -> Option<Ban> {
+        let client = self.clients.get(&addr)?;
+        self.find_ban(&pinning::identity(client), public_key.or(client.public_key.as_deref()))
+    }
+
+    // The server-wide ban, if any, on `identity` or on `public_key`
+    pub fn find_ban(&self, identity: &str, public_key: Option<&[u8]>) -> Option<Ban> {
+        let store = self.abuse.as_ref()?;
+        abuse::find_ban(store.as_ref(), identity, public_key).unwrap_or_else(|e| {
+            eprintln!("[ERROR] Ban lookup failed for {}: {}", identity, e);
+            None
+        })
+    }
+
+    pub fn join_room(
+        &mut self,
+        addr: SocketAddr,
+        room_id: &str,
+        max_participants: usize
+    ) -> Result<(), JoinError> {
+        if let Some(room) = self.rooms.get(room_id) {
+            if room.is_locked_for(&addr) {
+                return Err(JoinError::RoomLocked);
+            }
+            if !room.contains(&addr) && room.is_full() {
+                return Err(JoinError::RoomFull { max_participants: room.max_participants });
+            }
+        }
+
+        self.leave_room(addr);
+
+        // Rooms created by joining belong to the joiner's tenant and take on its room profile
+        let tenant = self.clients.get(&addr).and_then(|client| client.tenant.clone());
+        let identity = self.clients.get(&addr).map(pinning::identity);
+        let room = self.rooms
+            .entry(room_id.to_string())
+            .or_insert_with(|| {
+                let mut room = Room::new(room_id.to_string(), max_participants);
+                if let Some(tenant) = tenant {
+                    room.max_participants = tenant.max_participants(room.max_participants);
+                    room.tenant_id = Some(tenant.tenant_id);
+                    room.config = tenant.config.room_config;
+                }
+                room
+            });
+        room.add_member(addr);
+        room.remove_waiting(&addr);
+        // The owner takes the host back from whoever held it meanwhile, who stays on as co-host
+        let displaced = match room.host {
+            Some(host) if host != addr && identity.is_some() && room.owner == identity => {
+                room.host = Some(addr);
+                Some(host)
+            }
+            Some(_) => None,
+            None => {
+                room.host = Some(addr);
+                None
+            }
+        };
+        let role = if room.is_host(&addr) { Role::Host } else { Role::Participant };
+
+        if let Some(previous) = displaced.and_then(|host| self.clients.get_mut(&host)) {
+            previous.role = Role::Moderator;
+        }
+
+        if let Some(client) = self.clients.get_mut(&addr) {
+            client.room_id = Some(room_id.to_string());
+            client.role = role;
+        }
+
+        Ok(())
+    }
+
+    // Returns the room the client was in, if any. Ephemeral rooms are dropped once their last member leaves.
+    pub fn leave_room(&mut self, addr: SocketAddr) -> Option<String> {
+        let client = self.clients.get_mut(&addr)?;
+        let room_id = client.room_id.take()?;
+        let client_id = client.client_id.clone();
+        client.role = Role::default();
+        client.muted_by_host = false;
+        client.typing_since = None;
+        if client.presence == Presence::ScreenSharing {
+            client.presence = Presence::Active;
+        }
+
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.remove_member(&addr);
+            if room.ephemeral && room.is_empty() {
+                self.rooms.remove(&room_id);
+                rooms::forget_room(self, &room_id);
+            }
+        }
+        self.renegotiations.forget(&client_id);
+        self.file_transfers.forget(&client_id);
+
+        Some(room_id)
+    }
+
+    // The client named `client_id` among those of `addr`'s tenant; clients of other tenants are
+    // never found by id
+    pub fn tenant_client(&self, addr: SocketAddr, client_id: &str) -> Option<&Client> {
+        let tenant_id = self.clients.get(&addr)?.tenant_id();
+        self.clients
+            .values()
+            .find(|client| client.client_id == client_id && client.tenant_id() == tenant_id)
+    }
+
+    pub fn addr_of(&self, client_id: &str) -> Option<SocketAddr> {
+        self.clients
+            .values()
+            .find(|client| client.client_id == client_id)
+            .map(|client| client.address)
+    }
+
+    pub fn touch_room(&mut self, addr: SocketAddr) {
+        let room_id = self.clients.get(&addr).and_then(|client| client.room_id.as_ref());
+        if let Some(room) = room_id.and_then(|room_id| self.rooms.get_mut(room_id)) {
+            room.touch();
+        }
+    }
+
+    pub fn client_room(&self, addr: SocketAddr) -> Option<&Room> {
+        self.clients
+            .get(&addr)
+            .and_then(|client| client.room_id.as_ref())
+            .and_then(|room_id| self.rooms.get(room_id))
+    }
+
+    pub fn client_room_mut(&mut self, addr: SocketAddr) -> Option<&mut Room> {
+        let room_id = self.clients.get(&addr)?.room_id.clone()?;
+        self.rooms.get_mut(&room_id)
+    }
+
+    // Other clients sharing a room with `addr`, or the other end of its direct call when it is not
+    // in a room; empty when it is in neither
+    pub fn room_peers(&self, addr: SocketAddr) -> Vec<&Client> {
+        match self.client_room(addr) {
+            Some(room) => room.members
+                .iter()
+                .filter(|member| **member != addr)
+                .filter_map(|member| self.clients.get(member))
+                .collect(),
+            None => self.calls
+                .direct_peer(addr)
+                .and_then(|peer| self.clients.get(&peer))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    pub fn remove_client(&mut self, addr: SocketAddr) -> Option<Client> {
+        self.leave_room(addr);
+        for room in self.rooms.values_mut() {
+            room.remove_waiting(&addr);
+        }
+        self.clients.remove(&addr)
+    }
+}
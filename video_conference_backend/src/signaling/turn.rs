@@ -0,0 +1,36 @@
+use crate::config;
+use crate::models::SignalMessage;
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::state::SharedState;
+use crate::turn;
+use chrono::Utc;
+use std::net::SocketAddr;
+
+// Credentials are tied to the connection's client_id, so each session gets its own and they
+// can be told apart in the TURN server's logs
+pub async fn handle_turn_credentials(
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(secret) = &state.turn_secret else {
+        return send_error(client, "turn-unavailable", "This server does not issue TURN credentials", None).await;
+    };
+    // Relay capacity is only handed to clients that have proved their key
+    if !client.verified {
+        return send_error(client, "unverified-sender", "Verify your key before asking for TURN credentials", None).await;
+    }
+
+    let credentials = turn::issue_credentials(
+        secret,
+        &client.client_id,
+        config::get_turn_credential_ttl(),
+        Utc::now().timestamp(),
+        config::get_turn_uris()
+    );
+    let reply = SignalMessage::server("turn-credentials", serde_json::to_value(&credentials)?);
+    send_signal(client, &reply).await
+}
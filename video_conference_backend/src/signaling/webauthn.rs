@@ -0,0 +1,232 @@
+use crate::audit::{self, AuditEvent, AuditKind};
+use crate::auth::webauthn::{self, Ceremony, PendingCeremony};
+use crate::config;
+use crate::models::message::{WebauthnBeginPayload, WebauthnLoginPayload, WebauthnRegisterPayload};
+use crate::models::{Client, SignalMessage};
+use crate::sessions;
+use crate::signaling::{calls, correlation};
+use crate::signaling::handlers::{send_error, send_signal};
+use crate::signaling::state::SharedState;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use std::net::SocketAddr;
+
+// Algorithms offered to the browser in preference order: ES256, EdDSA
+const COSE_ALGORITHMS: &[i64] = &[-7, -8];
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|e| format!("{} is not valid base64url: {}", field, e))
+}
+
+async fn unavailable(client: &Client) -> Result<(), Box<dyn std::error::Error>> {
+    send_error(client, "webauthn-unavailable", "WebAuthn is not configured on this server", None).await
+}
+
+// Starts registering an authenticator for the signed-in user (or, with open registration, the
+// requested user id)
+pub async fn handle_register_begin(
+    payload: WebauthnBeginPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let (Some(relying_party), Some(credentials)) = (state.relying_party.clone(), state.credentials.clone()) else {
+        return match state.clients.get(&sender_addr) {
+            Some(client) => unavailable(client).await,
+            None => Ok(()),
+        };
+    };
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+
+    let signed_in = client.claims.as_ref().map(|claims| claims.sub.clone()).or_else(|| client.webauthn_user.clone());
+    let open = signed_in.is_none();
+    let user_id = match (signed_in, payload.user_id) {
+        (Some(user_id), _) => user_id,
+        (None, Some(user_id)) if config::get_webauthn_open_registration() && !user_id.is_empty() => user_id,
+        _ => {
+            return send_error(client, "registration-not-allowed", "Sign in before registering an authenticator", None).await;
+        }
+    };
+
+    let stored = credentials.credentials_for(&user_id).map_err(|e| e.to_string())?;
+    // Open registration only creates new users; adding an authenticator to an existing one
+    // takes signing in as them
+    if open && !stored.is_empty() {
+        return send_error(client, "registration-not-allowed", "Sign in before registering an authenticator", None).await;
+    }
+    let existing: Vec<String> = stored
+        .iter()
+        .map(|credential| URL_SAFE_NO_PAD.encode(&credential.credential_id))
+        .collect();
+    let challenge = sessions::random_token();
+    client.webauthn = Some(PendingCeremony {
+        ceremony: Ceremony::Registration,
+        challenge: challenge.clone(),
+        user_id: user_id.clone(),
+        open,
+    });
+
+    let options = SignalMessage::server("webauthn-register-options", serde_json::json!({
+        "challenge": challenge,
+        "rp_id": relying_party.rp_id,
+        "user_id": user_id,
+        "algorithms": COSE_ALGORITHMS,
+        "exclude_credentials": existing,
+    }));
+    send_signal(client, &options).await
+}
+
+pub async fn handle_register(
+    payload: WebauthnRegisterPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let (Some(relying_party), Some(credentials)) = (state.relying_party.clone(), state.credentials.clone()) else {
+        return Ok(());
+    };
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(pending) = client.webauthn.take().filter(|pending| pending.ceremony == Ceremony::Registration) else {
+        return send_error(client, "no-challenge", "Start with webauthn-register-begin", None).await;
+    };
+
+    let result = decode("client_data_json", &payload.client_data_json)
+        .and_then(|client_data| Ok((client_data, decode("attestation_object", &payload.attestation_object)?)))
+        .and_then(|(client_data, attestation)| {
+            webauthn::verify_registration(&relying_party, &pending, &client_data, &attestation)
+        });
+    let credential = match result {
+        Ok(credential) => credential,
+        Err(reason) => {
+            eprintln!("WebAuthn registration failed for {}: {}{}", sender_addr, reason, correlation::tag());
+            audit::record(AuditEvent::new(AuditKind::AuthFailed).client(client).reason(&reason).detail("method", "webauthn-registration"));
+            return send_error(client, "webauthn-failed", &reason, None).await;
+        }
+    };
+
+    if credentials.credential(&credential.credential_id).map_err(|e| e.to_string())?.is_some() {
+        return send_error(client, "credential-exists", "This authenticator is already registered", None).await;
+    }
+    // Another connection may have claimed the user since this ceremony began
+    if pending.open && !credentials.credentials_for(&credential.user_id).map_err(|e| e.to_string())?.is_empty() {
+        return send_error(client, "registration-not-allowed", "Sign in before registering an authenticator", None).await;
+    }
+    credentials.save_credential(&credential).map_err(|e| e.to_string())?;
+    println!("{} registered a WebAuthn credential for {}", sender_addr, credential.user_id);
+
+    let reply = SignalMessage::server("webauthn-registered", serde_json::json!({
+        "user_id": credential.user_id,
+        "credential_id": URL_SAFE_NO_PAD.encode(&credential.credential_id),
+        "attestation": credential.attestation,
+    }));
+    send_signal(client, &reply).await
+}
+
+// Issues a sign-in challenge; unknown users get one too, so responses don't reveal who is registered
+pub async fn handle_login_begin(
+    payload: WebauthnBeginPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let (Some(relying_party), Some(credentials)) = (state.relying_party.clone(), state.credentials.clone()) else {
+        return match state.clients.get(&sender_addr) {
+            Some(client) => unavailable(client).await,
+            None => Ok(()),
+        };
+    };
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(user_id) = payload.user_id.filter(|user_id| !user_id.is_empty()) else {
+        return send_error(client, "missing-user", "Name the user to sign in as", None).await;
+    };
+
+    let allowed: Vec<String> = credentials
+        .credentials_for(&user_id)
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|credential| URL_SAFE_NO_PAD.encode(&credential.credential_id))
+        .collect();
+    let challenge = sessions::random_token();
+    client.webauthn = Some(PendingCeremony {
+        ceremony: Ceremony::Authentication,
+        challenge: challenge.clone(),
+        user_id,
+        open: false,
+    });
+
+    let options = SignalMessage::server("webauthn-login-options", serde_json::json!({
+        "challenge": challenge,
+        "rp_id": relying_party.rp_id,
+        "allow_credentials": allowed,
+    }));
+    send_signal(client, &options).await
+}
+
+// A valid assertion authenticates the connection as the credential's user
+pub async fn handle_login(
+    payload: WebauthnLoginPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let (Some(relying_party), Some(credentials)) = (state.relying_party.clone(), state.credentials.clone()) else {
+        return Ok(());
+    };
+    let Some(client) = state.clients.get_mut(&sender_addr) else {
+        return Ok(());
+    };
+    let Some(pending) = client.webauthn.take().filter(|pending| pending.ceremony == Ceremony::Authentication) else {
+        return send_error(client, "no-challenge", "Start with webauthn-login-begin", None).await;
+    };
+
+    let result = (|| {
+        let credential_id = decode("credential_id", &payload.credential_id)?;
+        let client_data = decode("client_data_json", &payload.client_data_json)?;
+        let authenticator_data = decode("authenticator_data", &payload.authenticator_data)?;
+        let signature = decode("signature", &payload.signature)?;
+
+        let mut credential = credentials
+            .credential(&credential_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("Unknown credential")?;
+        credential.sign_count = webauthn::verify_assertion(
+            &relying_party,
+            &pending,
+            &credential,
+            &client_data,
+            &authenticator_data,
+            &signature
+        )?;
+        credentials.save_credential(&credential).map_err(|e| e.to_string())?;
+        Ok::<_, String>(credential)
+    })();
+
+    let credential = match result {
+        Ok(credential) => credential,
+        Err(reason) => {
+            eprintln!("WebAuthn sign-in failed for {}: {}{}", sender_addr, reason, correlation::tag());
+            audit::record(AuditEvent::new(AuditKind::AuthFailed).client(client).reason(&reason).detail("method", "webauthn"));
+            send_error(client, "webauthn-failed", &reason, None).await?;
+            if config::get_require_challenge() {
+                client.disconnect("authentication failed").await;
+            }
+            return Ok(());
+        }
+    };
+
+    client.authenticated = true;
+    client.webauthn_user = Some(credential.user_id.clone());
+    let reply = SignalMessage::server("webauthn-authenticated", serde_json::json!({
+        "user_id": credential.user_id,
+    }));
+    send_signal(client, &reply).await?;
+    calls::signed_in(&mut state, sender_addr).await
+}
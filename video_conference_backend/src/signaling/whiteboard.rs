@@ -0,0 +1,144 @@
+use crate::config;
+use crate::models::message::{WhiteboardOpPayload, WhiteboardSyncPayload};
+use crate::models::{SignalMessage, WhiteboardOp, WhiteboardSnapshot};
+use crate::signaling::handlers::{send_error, send_to_room};
+use crate::signaling::sequenced::{ask_for_snapshot, check_editor, send_pages};
+use crate::signaling::state::{SharedState, SignalingState};
+use std::net::SocketAddr;
+
+const DRAW: &str = "draw on the whiteboard";
+
+// Numbers the operation in the room's order and sends it to every member, the sender included,
+// so all of them apply operations in the same order. Once enough operations pile up the sender
+// is asked for a snapshot to fold them into.
+pub async fn handle_whiteboard_op(
+    payload: WhiteboardOpPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    state.touch_room(sender_addr);
+    let Some(room_id) = check_editor(&state, sender_addr, DRAW).await? else {
+        return Ok(());
+    };
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if serde_json::to_vec(&payload.op)?.len() > config::get_whiteboard_max_op_size() {
+        return send_error(client, "invalid-whiteboard-op", "Whiteboard operation is too large", None).await;
+    }
+    if state.rooms.get(&room_id).is_some_and(|room| room.whiteboard.pending() >= config::get_whiteboard_max_ops()) {
+        // The member asked for a snapshot may have left, leaving the board stuck
+        ask_for_board_snapshot(&mut state, &room_id, sender_addr).await?;
+        if let Some(client) = state.clients.get(&sender_addr) {
+            let message = "The whiteboard needs a snapshot before it takes more operations";
+            send_error(client, "whiteboard-full", message, None).await?;
+        }
+        return Ok(());
+    }
+    let client_id = client.client_id.clone();
+    let Some(room) = state.rooms.get_mut(&room_id) else {
+        return Ok(());
+    };
+
+    let op = room.whiteboard.append(|seq| WhiteboardOp { seq, client_id, op: payload.op });
+    let mut relayed = serde_json::to_value(op)?;
+    relayed["room_id"] = serde_json::json!(room_id);
+    send_to_room(&state, &room_id, &SignalMessage::server("whiteboard-op", relayed)).await?;
+    ask_for_board_snapshot(&mut state, &room_id, sender_addr).await
+}
+
+async fn ask_for_board_snapshot(
+    state: &mut SignalingState,
+    room_id: &str,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = |seq| SignalMessage::server("whiteboard-snapshot-request", serde_json::json!({
+        "room_id": room_id,
+        "seq": seq,
+    }));
+    let snapshot_after = config::get_whiteboard_snapshot_after();
+    ask_for_snapshot(state, room_id, addr, snapshot_after, |room| Some(&mut room.whiteboard), request).await
+}
+
+// Only the member that was sent `whiteboard-snapshot-request` can hand in a snapshot, so nobody
+// else can replace the board that late joiners are shown
+pub async fn handle_whiteboard_snapshot(
+    snapshot: WhiteboardSnapshot,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().await;
+    let Some(room_id) = check_editor(&state, sender_addr, DRAW).await? else {
+        return Ok(());
+    };
+    let (Some(client), Some(room)) = (state.clients.get(&sender_addr), state.rooms.get(&room_id)) else {
+        return Ok(());
+    };
+    if room.whiteboard.snapshot_from() != Some(sender_addr) {
+        let message = "Hand in a whiteboard snapshot only when asked for one";
+        return send_error(client, "snapshot-not-requested", message, None).await;
+    }
+    if serde_json::to_vec(&snapshot.state)?.len() > config::get_whiteboard_max_snapshot_size() {
+        return send_error(client, "invalid-snapshot", "Whiteboard snapshot is too large", None).await;
+    }
+    let seq = snapshot.seq;
+    let compacted = state.rooms
+        .get_mut(&room_id)
+        .is_some_and(|room| room.whiteboard.compact(snapshot));
+    if !compacted {
+        if let Some(client) = state.clients.get(&sender_addr) {
+            let message = format!("A snapshot at {} is older than the current one or ahead of the board", seq);
+            return send_error(client, "invalid-snapshot", &message, None).await;
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_whiteboard_sync(
+    payload: WhiteboardSyncPayload,
+    sender_addr: SocketAddr,
+    state: SharedState
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = state.lock().await;
+    let Some(client) = state.clients.get(&sender_addr) else {
+        return Ok(());
+    };
+    if state.client_room(sender_addr).is_none() {
+        return send_error(client, "not-in-room", "Join a room before syncing its whiteboard", None).await;
+    }
+    send_board_since(&state, sender_addr, payload.since.unwrap_or(0)).await
+}
+
+// Catches a newcomer up on the board, if anyone has drawn on it
+pub async fn send_board(
+    state: &SignalingState,
+    addr: SocketAddr
+) -> Result<(), Box<dyn std::error::Error>> {
+    if state.client_room(addr).is_some_and(|room| room.whiteboard.seq() > 0) {
+        send_board_since(state, addr, 0).await?;
+    }
+    Ok(())
+}
+
+// Sends what the client is missing in `whiteboard-state` pages of at most WHITEBOARD_PAGE_SIZE
+// operations. The snapshot, if any, comes with the first page, and `complete` marks the last.
+async fn send_board_since(
+    state: &SignalingState,
+    addr: SocketAddr,
+    since: u64
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(client), Some(room)) = (state.clients.get(&addr), state.client_room(addr)) else {
+        return Ok(());
+    };
+    let page = |snapshot: Option<&WhiteboardSnapshot>, ops: &[WhiteboardOp], complete| {
+        SignalMessage::server("whiteboard-state", serde_json::json!({
+            "room_id": room.room_id,
+            "seq": room.whiteboard.seq(),
+            "snapshot": snapshot,
+            "ops": ops,
+            "complete": complete,
+        }))
+    };
+    send_pages(client, &room.whiteboard, since, config::get_whiteboard_page_size(), page).await
+}
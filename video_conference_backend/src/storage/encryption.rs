@@ -0,0 +1,224 @@
+use crate::storage::StoreResult;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// Encrypted values are stored as `enc2:<key id>:<base64 nonce and ciphertext>`, sealed with the
+// key id and the value's field (table, column and row) as associated data, so a value copied into
+// another row or column no longer opens. `enc1:` values, sealed over the key id alone, and
+// plaintext written before encryption was turned on are only read by `rekey`.
+const PREFIX: &str = "enc2:";
+const LEGACY_PREFIX: &str = "enc1:";
+
+// AES-256-GCM over individual columns of the persisted stores. New values are sealed with the
+// current key; values sealed with a retired key still open, and `rekey` passes on the stores
+// move them to the current one so the retired key can then be dropped. Columns that rows are
+// looked up by hold a keyed blind index of the value instead.
+pub struct StorageCipher {
+    key_id: String,
+    key: LessSafeKey,
+    index_key: hmac::Key,
+    retired: HashMap<String, LessSafeKey>,
+    retired_index_keys: Vec<hmac::Key>,
+    rng: SystemRandom,
+}
+
+// Names the field a value is sealed for: a column of one row, by the row's stored key
+pub fn field(table: &str, column: &str, row: &str) -> String {
+    format!("{}.{}/{}", table, column, row)
+}
+
+impl StorageCipher {
+    // Keys are 32 raw bytes or their base64 text
+    pub fn new(current: &[u8], retired: &[Vec<u8>]) -> StoreResult<Self> {
+        let (key_id, key, index_key) = parse_key(current)?;
+        let mut retired_keys = HashMap::new();
+        let mut retired_index_keys = Vec::new();
+        for secret in retired {
+            let (key_id, key, index_key) = parse_key(secret)?;
+            retired_keys.insert(key_id, key);
+            retired_index_keys.push(index_key);
+        }
+        Ok(Self { key_id, key, index_key, retired: retired_keys, retired_index_keys, rng: SystemRandom::new() })
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    pub fn seal(&self, plaintext: &str, field: &str) -> StoreResult<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| "no randomness for a storage nonce")?;
+        let mut sealed = plaintext.as_bytes().to_vec();
+        let aad = associated_data(&self.key_id, Some(field));
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut sealed)
+            .map_err(|_| "storage encryption failed")?;
+
+        let mut encoded = nonce.to_vec();
+        encoded.extend_from_slice(&sealed);
+        Ok(format!("{}{}:{}", PREFIX, self.key_id, STANDARD.encode(encoded)))
+    }
+
+    // Opens a value sealed for `field`; anything else stored under a cipher is refused
+    pub fn open(&self, stored: &str, field: &str) -> StoreResult<String> {
+        match stored.strip_prefix(PREFIX) {
+            Some(sealed) => self.open_sealed(sealed, Some(field)),
+            None => Err(format!("{} is not sealed with the current scheme; it needs a rekey", field).into()),
+        }
+    }
+
+    // Opens a value however it was written, for `rekey` to seal it again
+    pub fn open_stale(&self, stored: &str, field: &str) -> StoreResult<String> {
+        if let Some(sealed) = stored.strip_prefix(PREFIX) {
+            return self.open_sealed(sealed, Some(field));
+        }
+        match stored.strip_prefix(LEGACY_PREFIX) {
+            Some(sealed) => self.open_sealed(sealed, None),
+            None => Ok(stored.to_string()),
+        }
+    }
+
+    fn open_sealed(&self, sealed: &str, field: Option<&str>) -> StoreResult<String> {
+        let (key_id, encoded) = sealed.split_once(':').ok_or("malformed encrypted value")?;
+        let key = match key_id == self.key_id {
+            true => &self.key,
+            false => self.retired.get(key_id).ok_or_else(|| format!("no storage key with id {}", key_id))?,
+        };
+
+        let mut sealed = STANDARD.decode(encoded)?;
+        if sealed.len() < NONCE_LEN {
+            return Err("malformed encrypted value".into());
+        }
+        let ciphertext = sealed.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed).map_err(|_| "malformed encrypted value")?;
+        let mut plaintext = ciphertext;
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(associated_data(key_id, field)), &mut plaintext)
+            .map_err(|_| format!("value sealed with storage key {} does not decrypt", key_id))?;
+        Ok(String::from_utf8(plaintext.to_vec())?)
+    }
+
+    // Whether `stored` is plaintext, sealed under the old scheme or sealed with a retired key
+    pub fn needs_rekey(&self, stored: &str) -> bool {
+        !stored.starts_with(&format!("{}{}:", PREFIX, self.key_id))
+    }
+
+    // The value stored in place of `value` in a column rows are looked up by
+    pub fn blind_index(&self, value: &str) -> String {
+        blind_index(&self.index_key, value)
+    }
+
+    // `value`'s blind index under the current key, then under each retired one, for lookups of
+    // rows `rekey` has not moved yet
+    pub fn blind_indexes(&self, value: &str) -> Vec<String> {
+        std::iter::once(&self.index_key)
+            .chain(&self.retired_index_keys)
+            .map(|key| blind_index(key, value))
+            .collect()
+    }
+}
+
+// Column helpers for stores that may or may not have a cipher. Without one, values are written
+// as plaintext and encrypted ones can't be read; with one, plaintext is refused.
+pub fn seal(cipher: Option<&StorageCipher>, value: &str, field: &str) -> StoreResult<String> {
+    match cipher {
+        Some(cipher) => cipher.seal(value, field),
+        None => Ok(value.to_string()),
+    }
+}
+
+pub fn open(cipher: Option<&StorageCipher>, stored: String, field: &str) -> StoreResult<String> {
+    match cipher {
+        Some(cipher) => cipher.open(&stored, field),
+        None if is_sealed(stored.as_bytes()) => Err("encrypted value in storage but no STORAGE_KEY_REF is configured".into()),
+        None => Ok(stored),
+    }
+}
+
+pub fn seal_optional(cipher: Option<&StorageCipher>, value: Option<&str>, field: &str) -> StoreResult<Option<String>> {
+    value.map(|value| seal(cipher, value, field)).transpose()
+}
+
+pub fn open_optional(cipher: Option<&StorageCipher>, stored: Option<String>, field: &str) -> StoreResult<Option<String>> {
+    stored.map(|stored| open(cipher, stored, field)).transpose()
+}
+
+// Binary columns are sealed as the base64 of their bytes
+pub fn seal_bytes(cipher: Option<&StorageCipher>, value: &[u8], field: &str) -> StoreResult<Vec<u8>> {
+    match cipher {
+        Some(cipher) => Ok(cipher.seal(&STANDARD.encode(value), field)?.into_bytes()),
+        None => Ok(value.to_vec()),
+    }
+}
+
+pub fn open_bytes(cipher: Option<&StorageCipher>, stored: Vec<u8>, field: &str) -> StoreResult<Vec<u8>> {
+    if cipher.is_none() && !is_sealed(&stored) {
+        return Ok(stored);
+    }
+    let encoded = open(cipher, String::from_utf8(stored)?, field)?;
+    Ok(STANDARD.decode(encoded)?)
+}
+
+// A binary column however it was written, for `rekey`
+pub fn open_stale_bytes(cipher: &StorageCipher, stored: Vec<u8>, field: &str) -> StoreResult<Vec<u8>> {
+    if !is_sealed(&stored) {
+        return Ok(stored);
+    }
+    let encoded = cipher.open_stale(&String::from_utf8(stored)?, field)?;
+    Ok(STANDARD.decode(encoded)?)
+}
+
+// The value of a column rows are looked up by: a blind index under a cipher, else `value` itself
+pub fn lookup_value(cipher: Option<&StorageCipher>, value: &str) -> String {
+    match cipher {
+        Some(cipher) => cipher.blind_index(value),
+        None => value.to_string(),
+    }
+}
+
+// Every stored form `value` may be found under
+pub fn lookup_values(cipher: Option<&StorageCipher>, value: &str) -> Vec<String> {
+    match cipher {
+        Some(cipher) => cipher.blind_indexes(value),
+        None => vec![value.to_string()],
+    }
+}
+
+fn is_sealed(stored: &[u8]) -> bool {
+    stored.starts_with(PREFIX.as_bytes()) || stored.starts_with(LEGACY_PREFIX.as_bytes())
+}
+
+fn associated_data(key_id: &str, field: Option<&str>) -> Vec<u8> {
+    let mut aad = key_id.as_bytes().to_vec();
+    if let Some(field) = field {
+        aad.push(0);
+        aad.extend_from_slice(field.as_bytes());
+    }
+    aad
+}
+
+fn blind_index(key: &hmac::Key, value: &str) -> String {
+    STANDARD.encode(hmac::sign(key, value.as_bytes()))
+}
+
+fn parse_key(secret: &[u8]) -> StoreResult<(String, LessSafeKey, hmac::Key)> {
+    let key = match secret.len() {
+        32 => secret.to_vec(),
+        _ => STANDARD.decode(std::str::from_utf8(secret)?.trim())?,
+    };
+    if key.len() != 32 {
+        return Err("storage keys must be 32 bytes".into());
+    }
+    // Identifies the key without revealing it, so values record which key sealed them
+    let key_id = Sha256::digest(&key)[..4].iter().map(|byte| format!("{:02x}", byte)).collect();
+    // Blind indexes get a key of their own, derived so it says nothing about the sealing key
+    let index_key = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), b"storage blind index");
+    let index_key = hmac::Key::new(hmac::HMAC_SHA256, index_key.as_ref());
+    let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| "invalid storage key")?;
+    Ok((key_id, LessSafeKey::new(key), index_key))
+}
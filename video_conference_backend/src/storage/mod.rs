@@ -0,0 +1,67 @@
+pub mod encryption;
+pub mod sqlite;
+
+use crate::models::{Room, RoomConfig};
+use serde::{Deserialize, Serialize};
+
+pub use encryption::StorageCipher;
+pub use sqlite::SqliteRoomStore;
+
+pub type StoreResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// The durable part of a room definition; membership and activity are runtime-only
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomRecord {
+    pub room_id: String,
+    pub tenant_id: Option<String>,
+    pub owner: Option<String>,
+    pub max_participants: usize,
+    pub password_hash: Option<String>,
+    pub lobby_enabled: bool,
+    pub locked: bool,
+    pub listed: bool,
+    pub config: RoomConfig,
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+}
+
+pub trait RoomStore: Send + Sync {
+    fn load_rooms(&self) -> StoreResult<Vec<RoomRecord>>;
+    fn save_room(&self, record: &RoomRecord) -> StoreResult<()>;
+    fn delete_room(&self, room_id: &str) -> StoreResult<()>;
+}
+
+impl From<&Room> for RoomRecord {
+    fn from(room: &Room) -> Self {
+        Self {
+            room_id: room.room_id.clone(),
+            tenant_id: room.tenant_id.clone(),
+            owner: room.owner.clone(),
+            max_participants: room.max_participants,
+            password_hash: room.password_hash.clone(),
+            lobby_enabled: room.lobby_enabled,
+            locked: room.locked,
+            listed: room.listed,
+            config: room.config.clone(),
+            starts_at: room.starts_at,
+            ends_at: room.ends_at,
+        }
+    }
+}
+
+impl From<RoomRecord> for Room {
+    fn from(record: RoomRecord) -> Self {
+        let mut room = Room::new(record.room_id, record.max_participants);
+        room.tenant_id = record.tenant_id;
+        room.owner = record.owner;
+        room.password_hash = record.password_hash;
+        room.lobby_enabled = record.lobby_enabled;
+        room.locked = record.locked;
+        room.listed = record.listed;
+        room.config = record.config;
+        room.starts_at = record.starts_at;
+        room.ends_at = record.ends_at;
+        room.ephemeral = false;
+        room
+    }
+}
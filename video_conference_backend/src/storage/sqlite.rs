@@ -0,0 +1,164 @@
+use crate::storage::encryption::{self, StorageCipher};
+use crate::storage::{RoomRecord, RoomStore, StoreResult};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub struct SqliteRoomStore {
+    conn: Mutex<Connection>,
+    // Seals password hashes and room configs when set
+    cipher: Option<Arc<StorageCipher>>,
+}
+
+impl SqliteRoomStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> StoreResult<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS rooms (
+                room_id TEXT PRIMARY KEY,
+                max_participants INTEGER NOT NULL,
+                password_hash TEXT,
+                lobby_enabled INTEGER NOT NULL,
+                locked INTEGER NOT NULL,
+                starts_at INTEGER,
+                ends_at INTEGER
+            )",
+        )?;
+        add_column_if_missing(&conn, "rooms", "listed", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "rooms", "config", "TEXT")?;
+        add_column_if_missing(&conn, "rooms", "tenant_id", "TEXT")?;
+        add_column_if_missing(&conn, "rooms", "owner", "TEXT")?;
+
+        Ok(Self { conn: Mutex::new(conn), cipher: None })
+    }
+
+    pub fn with_cipher(mut self, cipher: Arc<StorageCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    // Re-seals rows that are still plaintext or sealed with a retired key; returns how many
+    pub fn rekey(&self) -> StoreResult<usize> {
+        let Some(cipher) = self.cipher.as_deref() else {
+            return Ok(0);
+        };
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let stale = {
+            let mut statement = conn.prepare("SELECT room_id, password_hash, config FROM rooms")?;
+            let rows = statement.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?))
+            })?;
+            rows.filter_map(Result::ok)
+                .filter(|(_, password_hash, config)| [password_hash, config].into_iter().flatten().any(|value| cipher.needs_rekey(value)))
+                .collect::<Vec<_>>()
+        };
+        for (room_id, password_hash, config) in &stale {
+            let reseal = |column: &str, value: &Option<String>| -> StoreResult<Option<String>> {
+                let field = encryption::field("rooms", column, room_id);
+                value.as_deref().map(|value| cipher.seal(&cipher.open_stale(value, &field)?, &field)).transpose()
+            };
+            conn.execute(
+                "UPDATE rooms SET password_hash = ?2, config = ?3 WHERE room_id = ?1",
+                params![room_id, reseal("password_hash", password_hash)?, reseal("config", config)?],
+            )?;
+        }
+        Ok(stale.len())
+    }
+}
+
+// Columns added after the initial schema; older databases pick them up on open
+pub(crate) fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> StoreResult<()> {
+    let mut statement = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = statement
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
+    Ok(())
+}
+
+impl RoomStore for SqliteRoomStore {
+    fn load_rooms(&self) -> StoreResult<Vec<RoomRecord>> {
+        let cipher = self.cipher.as_deref();
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut statement = conn.prepare(
+            "SELECT room_id, max_participants, password_hash, lobby_enabled, locked, starts_at, ends_at, listed, config, tenant_id, owner
+             FROM rooms",
+        )?;
+
+        let rows = statement.query_map([], |row| {
+            Ok((
+                RoomRecord {
+                    room_id: row.get(0)?,
+                    max_participants: row.get::<_, i64>(1)? as usize,
+                    password_hash: None,
+                    lobby_enabled: row.get(3)?,
+                    locked: row.get(4)?,
+                    starts_at: row.get(5)?,
+                    ends_at: row.get(6)?,
+                    listed: row.get(7)?,
+                    config: Default::default(),
+                    tenant_id: row.get(9)?,
+                    owner: row.get(10)?,
+                },
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(8)?,
+            ))
+        })?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            let (mut record, password_hash, config) = row?;
+            let field = |column| encryption::field("rooms", column, &record.room_id);
+            record.password_hash = encryption::open_optional(cipher, password_hash, &field("password_hash"))?;
+            record.config = encryption::open_optional(cipher, config, &field("config"))?
+                .and_then(|config| serde_json::from_str(&config).ok())
+                .unwrap_or_default();
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    fn save_room(&self, record: &RoomRecord) -> StoreResult<()> {
+        let cipher = self.cipher.as_deref();
+        let field = |column| encryption::field("rooms", column, &record.room_id);
+        let password_hash = encryption::seal_optional(cipher, record.password_hash.as_deref(), &field("password_hash"))?;
+        let config = encryption::seal(cipher, &serde_json::to_string(&record.config)?, &field("config"))?;
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO rooms
+             (room_id, max_participants, password_hash, lobby_enabled, locked, starts_at, ends_at, listed, config, tenant_id, owner)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                record.room_id,
+                record.max_participants as i64,
+                password_hash,
+                record.lobby_enabled,
+                record.locked,
+                record.starts_at,
+                record.ends_at,
+                record.listed,
+                config,
+                record.tenant_id,
+                record.owner,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_room(&self, room_id: &str) -> StoreResult<()> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM rooms WHERE room_id = ?1", params![room_id])?;
+        Ok(())
+    }
+}
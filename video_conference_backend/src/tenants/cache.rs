@@ -0,0 +1,52 @@
+use crate::config;
+use crate::storage::StoreResult;
+use crate::tenants::{hash_api_key, ApiKeyStore, SqliteKeyStore, Tenant};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// Answers lookups from memory, since the handshake that asks for them runs on the executor and
+// must not wait on the database. `run_refresher` reloads the keys off the executor every
+// interval, so added and revoked keys take effect within it.
+pub struct CachedKeyStore {
+    store: Arc<SqliteKeyStore>,
+    // Tenants by key hash
+    tenants: RwLock<HashMap<String, Tenant>>,
+}
+
+impl CachedKeyStore {
+    pub fn load(store: Arc<SqliteKeyStore>) -> StoreResult<Self> {
+        let tenants = RwLock::new(store.tenants()?);
+        Ok(Self { store, tenants })
+    }
+
+    pub fn refresh(&self) -> StoreResult<()> {
+        let tenants = self.store.tenants()?;
+        *self.tenants.write().map_err(|e| e.to_string())? = tenants;
+        Ok(())
+    }
+}
+
+impl ApiKeyStore for CachedKeyStore {
+    fn lookup(&self, api_key: &str) -> StoreResult<Option<Tenant>> {
+        let tenants = self.tenants.read().map_err(|e| e.to_string())?;
+        Ok(tenants.get(&hash_api_key(api_key)).cloned())
+    }
+}
+
+pub async fn run_refresher(cache: Arc<CachedKeyStore>) {
+    let mut interval = tokio::time::interval(config::get_api_keys_refresh_interval().max(Duration::from_secs(1)));
+    // The first tick is immediate, and the keys were loaded a moment ago
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let cache = Arc::clone(&cache);
+        match tokio::task::spawn_blocking(move || cache.refresh()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Failed to reload API keys: {}", e),
+            Err(e) => eprintln!("API key reload task failed: {}", e),
+        }
+    }
+}
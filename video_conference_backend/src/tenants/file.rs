@@ -0,0 +1,47 @@
+use crate::storage::StoreResult;
+use crate::tenants::{hash_api_key, ApiKeyStore, Tenant, TenantConfig};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+// One entry of the key file: either the key itself or its SHA-256 hex digest
+#[derive(Debug, Deserialize)]
+struct KeyEntry {
+    api_key: Option<String>,
+    api_key_sha256: Option<String>,
+    tenant_id: String,
+    #[serde(default)]
+    config: TenantConfig,
+}
+
+// Static keys read once from a JSON array at startup
+pub struct FileKeyStore {
+    tenants: HashMap<String, Tenant>,
+}
+
+impl FileKeyStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        let entries: Vec<KeyEntry> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        let mut tenants = HashMap::new();
+        for entry in entries {
+            let hash = match (entry.api_key, entry.api_key_sha256) {
+                (_, Some(hash)) => hash.to_lowercase(),
+                (Some(api_key), None) => hash_api_key(&api_key),
+                (None, None) => return Err(format!("key entry for tenant {} has no key", entry.tenant_id).into()),
+            };
+            tenants.insert(hash, Tenant {
+                tenant_id: entry.tenant_id,
+                config: entry.config,
+            });
+        }
+
+        Ok(Self { tenants })
+    }
+}
+
+impl ApiKeyStore for FileKeyStore {
+    fn lookup(&self, api_key: &str) -> StoreResult<Option<Tenant>> {
+        Ok(self.tenants.get(&hash_api_key(api_key)).cloned())
+    }
+}
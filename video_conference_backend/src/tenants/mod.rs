@@ -0,0 +1,66 @@
+pub mod cache;
+pub mod file;
+pub mod sqlite;
+
+use crate::models::RoomConfig;
+use crate::storage::StoreResult;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+pub use cache::{run_refresher, CachedKeyStore};
+pub use file::FileKeyStore;
+pub use sqlite::SqliteKeyStore;
+
+// Settings applied to every client and ephemeral room of a tenant
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TenantConfig {
+    // Caps the size of rooms the tenant's clients create
+    pub max_participants: Option<usize>,
+    // Profile given to rooms created implicitly by joining
+    pub room_config: RoomConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tenant {
+    pub tenant_id: String,
+    #[serde(default)]
+    pub config: TenantConfig,
+}
+
+impl Tenant {
+    // Room size for a tenant client asking for `requested` participants
+    pub fn max_participants(&self, requested: usize) -> usize {
+        self.config.max_participants.map_or(requested, |max| requested.min(max))
+    }
+}
+
+// Resolves the API key a client presents at connect time to its tenant
+pub trait ApiKeyStore: Send + Sync {
+    fn lookup(&self, api_key: &str) -> StoreResult<Option<Tenant>>;
+}
+
+// Keys are only ever stored and compared as SHA-256 hex digests
+pub fn hash_api_key(api_key: &str) -> String {
+    Sha256::digest(api_key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// `X-Api-Key` header, or the `api_key` query parameter for browsers
+pub fn api_key_from_request(request: &Request) -> Option<String> {
+    let from_header = request.headers()
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok())
+        .map(|key| key.trim().to_string());
+
+    from_header.or_else(|| {
+        request.uri()
+            .query()?
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("api_key="))
+            .map(str::to_string)
+    })
+}
@@ -0,0 +1,81 @@
+use crate::storage::StoreResult;
+use crate::tenants::{hash_api_key, ApiKeyStore, Tenant};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub struct SqliteKeyStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteKeyStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> StoreResult<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS api_keys (
+                key_hash TEXT PRIMARY KEY,
+                tenant_id TEXT NOT NULL,
+                config TEXT
+            )",
+        )?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    pub fn add_key(&self, api_key: &str, tenant: &Tenant) -> StoreResult<()> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO api_keys (key_hash, tenant_id, config) VALUES (?1, ?2, ?3)",
+            params![hash_api_key(api_key), tenant.tenant_id, serde_json::to_string(&tenant.config)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn revoke_key(&self, api_key: &str) -> StoreResult<()> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM api_keys WHERE key_hash = ?1", params![hash_api_key(api_key)])?;
+        Ok(())
+    }
+
+    // Every tenant by key hash, for `CachedKeyStore`
+    pub fn tenants(&self) -> StoreResult<HashMap<String, Tenant>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare("SELECT key_hash, tenant_id, config FROM api_keys")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, tenant_from_row(row.get(1)?, row.get(2)?)))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+fn tenant_from_row(tenant_id: String, config: Option<String>) -> Tenant {
+    Tenant {
+        tenant_id,
+        config: config
+            .and_then(|config| serde_json::from_str(&config).ok())
+            .unwrap_or_default(),
+    }
+}
+
+impl ApiKeyStore for SqliteKeyStore {
+    fn lookup(&self, api_key: &str) -> StoreResult<Option<Tenant>> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let row = conn
+            .query_row(
+                "SELECT tenant_id, config FROM api_keys WHERE key_hash = ?1",
+                params![hash_api_key(api_key)],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .optional()?;
+
+        Ok(row.map(|(tenant_id, config)| tenant_from_row(tenant_id, config)))
+    }
+}
@@ -0,0 +1,33 @@
+use crate::tls::Acceptor;
+use futures_util::StreamExt;
+use rustls_acme::caches::DirCache;
+use rustls_acme::AcmeConfig;
+use std::path::PathBuf;
+
+// Obtains and renews certificates for `domains` from Let's Encrypt using the TLS-ALPN-01
+// challenge, which is answered on the signaling port itself, so that port must be reachable
+// as 443 from the internet. Certificates and the account key are cached in `cache_dir`.
+pub fn start(domains: Vec<String>, contact: Option<String>, cache_dir: PathBuf, production: bool) -> Acceptor {
+    let mut state = AcmeConfig::new(domains)
+        .contact(contact.into_iter().map(|email| format!("mailto:{}", email)))
+        .cache(DirCache::new(cache_dir))
+        .directory_lets_encrypt(production)
+        .state();
+
+    let acceptor = Acceptor::Acme {
+        challenge: state.challenge_rustls_config(),
+        default: state.default_rustls_config(),
+    };
+
+    // Driving the state is what orders, renews and installs certificates
+    tokio::spawn(async move {
+        while let Some(event) = state.next().await {
+            match event {
+                Ok(event) => println!("ACME: {:?}", event),
+                Err(e) => eprintln!("ACME error: {:?}", e),
+            }
+        }
+    });
+
+    acceptor
+}
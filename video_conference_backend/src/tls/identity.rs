@@ -0,0 +1,58 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio_rustls::rustls::Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// Who a client certificate says the peer is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CertIdentity {
+    // First email, DNS or URI subject alternative name, else the subject common name
+    pub subject: String,
+    // Subject organization (O), when the issuing CA sets one
+    pub organization: Option<String>,
+    // SHA-256 of the DER certificate, hex encoded
+    pub fingerprint: String,
+}
+
+// Identity of the leaf certificate presented during the TLS handshake, if any
+pub fn peer_identity(certificates: Option<&[Certificate]>) -> Option<CertIdentity> {
+    certificate_identity(&certificates?.first()?.0)
+}
+
+pub fn certificate_identity(der: &[u8]) -> Option<CertIdentity> {
+    let (_, certificate) = X509Certificate::from_der(der).ok()?;
+
+    let alternative_name = certificate
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .and_then(|extension| {
+            extension.value.general_names.iter().find_map(|name| match name {
+                GeneralName::RFC822Name(name) | GeneralName::DNSName(name) | GeneralName::URI(name) => {
+                    Some(name.to_string())
+                }
+                _ => None,
+            })
+        });
+    let common_name = || {
+        certificate
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|name| name.as_str().ok())
+            .map(str::to_string)
+    };
+    let organization = certificate
+        .subject()
+        .iter_organization()
+        .next()
+        .and_then(|name| name.as_str().ok())
+        .map(str::to_string);
+
+    Some(CertIdentity {
+        subject: alternative_name.or_else(common_name)?,
+        organization,
+        fingerprint: Sha256::digest(der).iter().map(|byte| format!("{:02x}", byte)).collect(),
+    })
+}
@@ -0,0 +1,201 @@
+pub mod acme;
+pub mod identity;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::server::{
+    Acceptor as ClientHelloAcceptor, AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient,
+    ClientHello, ResolvesServerCert,
+};
+use tokio_rustls::rustls::sign::{self, CertifiedKey};
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig, SignatureScheme};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::{LazyConfigAcceptor, TlsAcceptor};
+
+pub use identity::{certificate_identity, peer_identity, CertIdentity};
+
+pub type TlsResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Clone)]
+pub enum Acceptor {
+    // Certificate files on disk
+    Static(TlsAcceptor),
+    // Certificates managed by ACME; challenge connections get their own config
+    Acme {
+        challenge: Arc<ServerConfig>,
+        default: Arc<ServerConfig>,
+    },
+}
+
+impl Acceptor {
+    // None for ACME validation connections, which are done once their handshake completes
+    pub async fn accept(&self, stream: TcpStream) -> std::io::Result<Option<TlsStream<TcpStream>>> {
+        match self {
+            Acceptor::Static(acceptor) => acceptor.accept(stream).await.map(Some),
+            Acceptor::Acme { challenge, default } => {
+                let start = LazyConfigAcceptor::new(ClientHelloAcceptor::default(), stream).await?;
+                if rustls_acme::is_tls_alpn_challenge(&start.client_hello()) {
+                    start.into_stream(Arc::clone(challenge)).await?;
+                    return Ok(None);
+                }
+                start.into_stream(Arc::clone(default)).await.map(Some)
+            }
+        }
+    }
+}
+
+// Serves whatever certificate was loaded last, so renewals take effect without a restart
+pub struct ReloadingCertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadingCertResolver {
+    pub fn new(key: CertifiedKey) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(key)),
+        }
+    }
+
+    pub fn replace(&self, key: CertifiedKey) {
+        if let Ok(mut current) = self.current.write() {
+            *current = Arc::new(key);
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.current.read().ok().map(|current| Arc::clone(&current))
+    }
+}
+
+// PEM certificate chain and private key (PKCS#8, PKCS#1 or SEC1)
+pub fn load_certified_key(cert_path: &Path, key_path: &Path) -> TlsResult<CertifiedKey> {
+    let key = std::fs::read(key_path)?;
+    certified_key(cert_path, &key).map_err(|e| format!("{}: {}", key_path.display(), e).into())
+}
+
+// As `load_certified_key`, with the PEM private key already in hand, e.g. from a key provider
+pub fn certified_key(cert_path: &Path, key_pem: &[u8]) -> TlsResult<CertifiedKey> {
+    let chain: Vec<Certificate> = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if chain.is_empty() {
+        return Err(format!("no certificates in {}", cert_path.display()).into());
+    }
+
+    let key = rustls_pemfile::read_all(&mut BufReader::new(key_pem))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or("no private key found")?;
+
+    let signing_key = sign::any_supported_type(&key).map_err(|e| e.to_string())?;
+    check_key_matches(&chain[0], signing_key.as_ref())?;
+    Ok(CertifiedKey::new(chain, signing_key))
+}
+
+// Signature schemes tried when proving the key belongs to the certificate, with the algorithm
+// that checks each against the certificate's public key
+static KEY_MATCH_SCHEMES: &[(SignatureScheme, &webpki::SignatureAlgorithm)] = &[
+    (SignatureScheme::ECDSA_NISTP256_SHA256, &webpki::ECDSA_P256_SHA256),
+    (SignatureScheme::ECDSA_NISTP384_SHA384, &webpki::ECDSA_P384_SHA384),
+    (SignatureScheme::ED25519, &webpki::ED25519),
+    (SignatureScheme::RSA_PKCS1_SHA256, &webpki::RSA_PKCS1_2048_8192_SHA256),
+];
+
+// Signs a probe with `key` and verifies it against the leaf certificate, so a key left over from
+// the previous pair is refused instead of served with a certificate it cannot speak for
+fn check_key_matches(leaf: &Certificate, key: &dyn sign::SigningKey) -> TlsResult<()> {
+    const PROBE: &[u8] = b"certificate and private key pair check";
+
+    let schemes: Vec<SignatureScheme> = KEY_MATCH_SCHEMES.iter().map(|(scheme, _)| *scheme).collect();
+    let signer = key.choose_scheme(&schemes).ok_or("unsupported private key type")?;
+    let algorithm = KEY_MATCH_SCHEMES
+        .iter()
+        .find(|(scheme, _)| *scheme == signer.scheme())
+        .map(|(_, algorithm)| *algorithm)
+        .ok_or("unsupported private key type")?;
+    let signature = signer.sign(PROBE).map_err(|e| e.to_string())?;
+
+    webpki::EndEntityCert::try_from(leaf.0.as_slice())
+        .map_err(|e| format!("unreadable certificate: {:?}", e))?
+        .verify_signature(algorithm, PROBE, &signature)
+        .map_err(|_| "private key does not match the certificate".into())
+}
+
+// Trust anchors for client certificates, from a PEM bundle
+pub fn load_client_roots(ca_path: &Path) -> TlsResult<RootCertStore> {
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(File::open(ca_path)?))?;
+    let mut roots = RootCertStore::empty();
+    let (added, _) = roots.add_parsable_certificates(&certificates);
+    if added == 0 {
+        return Err(format!("no usable CA certificates in {}", ca_path.display()).into());
+    }
+    Ok(roots)
+}
+
+// With `client_roots`, clients are asked for a certificate chaining to one of them; without
+// `require_client_cert` those that send none are still let in
+pub fn acceptor(
+    resolver: Arc<ReloadingCertResolver>,
+    client_roots: Option<RootCertStore>,
+    require_client_cert: bool
+) -> Acceptor {
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let config = match client_roots {
+        Some(roots) if require_client_cert => builder
+            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed()),
+        Some(roots) => builder
+            .with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed()),
+        None => builder.with_no_client_auth(),
+    }
+    .with_cert_resolver(resolver);
+    Acceptor::Static(TlsAcceptor::from(Arc::new(config)))
+}
+
+// Polls the certificate files and swaps in the new pair whenever either changes. A pair that
+// fails to load or whose key does not match its certificate (e.g. caught halfway through a
+// renewal) is skipped, and the old one kept, until the next change.
+pub async fn watch_certificates(
+    resolver: Arc<ReloadingCertResolver>,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    interval: Duration
+) {
+    let mut last_modified = modified(&cert_path, &key_path);
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        let current = modified(&cert_path, &key_path);
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        match load_certified_key(&cert_path, &key_path) {
+            Ok(key) => {
+                resolver.replace(key);
+                println!("Reloaded TLS certificate from {}", cert_path.display());
+            }
+            Err(e) => eprintln!("Failed to reload TLS certificate: {}", e),
+        }
+    }
+}
+
+fn modified(cert_path: &Path, key_path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(cert_path).and_then(|meta| meta.modified()).ok()?;
+    let key = std::fs::metadata(key_path).and_then(|meta| meta.modified()).ok()?;
+    Some((cert, key))
+}
@@ -0,0 +1,38 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::hmac;
+use serde::Serialize;
+use std::time::Duration;
+
+// Short-lived relay credentials in the form WebRTC's RTCIceServer takes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TurnCredentials {
+    pub username: String,
+    pub credential: String,
+    // Seconds the credentials stay valid
+    pub ttl: u64,
+    pub uris: Vec<String>,
+}
+
+// coturn's `use-auth-secret` scheme (the TURN REST API draft): the username is the expiry time
+// and the user joined by a colon, and the password is the base64 HMAC-SHA1 of the username under
+// the secret shared with the TURN server. The TURN server checks both without calling back here.
+pub fn issue_credentials(
+    secret: &[u8],
+    user: &str,
+    ttl: Duration,
+    now: i64,
+    uris: Vec<String>
+) -> TurnCredentials {
+    let expires_at = now.saturating_add(i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX));
+    let username = format!("{}:{}", expires_at, user);
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let credential = STANDARD.encode(hmac::sign(&key, username.as_bytes()).as_ref());
+
+    TurnCredentials {
+        username,
+        credential,
+        ttl: ttl.as_secs(),
+        uris,
+    }
+}
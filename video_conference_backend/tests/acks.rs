@@ -0,0 +1,91 @@
+mod common;
+
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::acks::{self, Delivery};
+use video_conference_backend::signaling::{dispatch_signal, SharedState, SignalingState};
+use common::{add_member, drain, payloads, session_token, signal};
+
+fn failed(reason: &str) -> Delivery {
+    Delivery::Failed(reason.to_string())
+}
+
+fn sealed_to(target_id: &str, message_id: Option<&str>) -> SignalMessage {
+    let mut message = signal(SignalKind::Sealed, json!("opaque"));
+    message.sender_id = "client-1".to_string();
+    message.target_id = Some(target_id.to_string());
+    message.message_id = message_id.map(str::to_string);
+    message
+}
+
+// Sealed messages only pass between clients with bound keys
+fn bind_keys(state: &mut SignalingState) {
+    for (addr, client) in state.clients.iter_mut() {
+        client.public_key = Some(vec![addr.port() as u8; 65]);
+    }
+}
+
+// Dispatches `message` the way the connection loop does, acknowledging it afterwards
+async fn dispatch(message: &SignalMessage, addr: SocketAddr, state: &SharedState) {
+    let mut client_id = "client-1".to_string();
+    let token = session_token(&mut *state.lock().await, addr);
+    let (dispatched, outcome) = acks::track(dispatch_signal(message, Some(&token), addr, &mut client_id, Arc::clone(state))).await;
+    dispatched.unwrap();
+    acks::send_ack(message, outcome, addr, state).await.unwrap();
+}
+
+#[test]
+fn a_message_to_several_peers_fails_only_if_none_got_it() {
+    assert_eq!(Delivery::combine([Delivery::Delivered, Delivery::Delivered]), Delivery::Delivered);
+    assert_eq!(Delivery::combine([Delivery::Delivered, Delivery::Queued]), Delivery::Queued);
+    assert_eq!(Delivery::combine([failed("target-disconnected"), Delivery::Delivered]), Delivery::Delivered);
+    assert_eq!(Delivery::combine([failed("a"), failed("b")]).status(), "failed");
+    assert_eq!(Delivery::combine([]).reason(), Some("no-recipients"));
+}
+
+#[tokio::test]
+async fn senders_that_ask_for_acks_learn_what_became_of_each_message() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut target_rx) = add_member(&mut inner, 2, "alpha");
+    bind_keys(&mut inner);
+    inner.clients.get_mut(&sender).unwrap().features = vec![acks::FEATURE.to_string()];
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut sender_rx);
+    drain(&mut target_rx);
+
+    dispatch(&sealed_to("client-2", Some("1b4e28ba-2fa1-11d2-883f-0016d3cca427")), sender, &state).await;
+    assert_eq!(payloads(&mut target_rx, "sealed").len(), 1);
+    let ack = payloads(&mut sender_rx, "ack").remove(0);
+    assert_eq!(ack, json!({
+        "message_id": "1b4e28ba-2fa1-11d2-883f-0016d3cca427",
+        "signal_type": "sealed",
+        "target_id": "client-2",
+        "status": "delivered",
+        "reason": null,
+    }));
+
+    dispatch(&sealed_to("client-9", Some("6fa459ea-ee8a-3ca4-894e-db77e160355e")), sender, &state).await;
+    let ack = payloads(&mut sender_rx, "ack").remove(0);
+    assert_eq!((ack["status"].as_str(), ack["reason"].as_str()), (Some("failed"), Some("target-unknown")));
+
+    // Without a message id there is nothing to acknowledge
+    dispatch(&sealed_to("client-2", None), sender, &state).await;
+    assert!(payloads(&mut sender_rx, "ack").is_empty());
+}
+
+#[tokio::test]
+async fn acks_are_only_sent_to_clients_that_asked() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, _target_rx) = add_member(&mut inner, 2, "alpha");
+    bind_keys(&mut inner);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut sender_rx);
+
+    dispatch(&sealed_to("client-2", Some("1b4e28ba-2fa1-11d2-883f-0016d3cca427")), sender, &state).await;
+    assert!(payloads(&mut sender_rx, "ack").is_empty());
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Mutex as AsyncMutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::audit::{self, AuditEvent, AuditKind, AuditResult, AuditSink, FileAuditSink, HttpAuditSink};
+use video_conference_backend::signaling::admin::handle_revoke_keys;
+use video_conference_backend::signaling::{correlation, SharedState, SignalingState};
+use common::{add_client, parsed, signal};
+
+#[derive(Default)]
+struct MemorySink {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl AuditSink for MemorySink {
+    fn write(&self, event: &AuditEvent) -> AuditResult<()> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+// Sinks are installed once per process, so everything is checked in one test
+#[tokio::test]
+async fn security_events_reach_every_sink_in_sequence() {
+    std::env::set_var("ADMIN_TOKEN", "audit-admin");
+    std::env::remove_var("REVOKED_KEYS_PATH");
+    let path = std::env::temp_dir().join(format!("audit-test-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let memory = Arc::new(MemorySink::default());
+    audit::install(vec![memory.clone(), Arc::new(FileAuditSink::open(&path).unwrap())]);
+
+    let mut inner = SignalingState::new();
+    let (admin, _admin_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(AsyncMutex::new(inner));
+
+    let guess = signal(SignalKind::RevokeKeys, json!({ "admin_token": "guess", "public_keys": [[1, 2, 3]] }));
+    handle_revoke_keys(parsed(&guess), admin, Arc::clone(&state)).await.unwrap();
+    let revoke = signal(SignalKind::RevokeKeys, json!({ "admin_token": "audit-admin", "public_keys": [[1, 2, 3]] }));
+    let handled = handle_revoke_keys(parsed(&revoke), admin, Arc::clone(&state));
+    correlation::scope(admin, Some("req-9".to_string()), handled).await.unwrap();
+
+    let events = memory.events.lock().unwrap().clone();
+    assert_eq!(events.iter().map(|event| event.kind).collect::<Vec<_>>(), [AuditKind::AuthFailed, AuditKind::KeyRevoked]);
+    assert!(events[0].kind.is_failure() && !events[1].kind.is_failure());
+    assert_eq!(events[1].sequence, events[0].sequence + 1);
+    assert_eq!(events[0].client_id.as_deref(), Some("client-1"));
+    assert_eq!(events[0].details["method"], "admin-token");
+    assert_eq!((events[0].correlation_id.as_deref(), events[1].correlation_id.as_deref()), (None, Some("req-9")));
+
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["kind"], "auth-failed");
+    assert_eq!(lines[1]["kind"], "key-revoked");
+    assert!(lines[0].get("correlation_id").is_none());
+    assert_eq!(lines[1]["correlation_id"], "req-9");
+    assert_eq!(lines[1]["details"]["disconnected"], 0);
+}
+
+#[tokio::test]
+async fn a_stalled_collector_drops_records_instead_of_queueing_them() {
+    std::env::set_var("AUDIT_HTTP_QUEUE_SIZE", "1");
+    // Accepts connections and never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/audit", listener.local_addr().unwrap());
+    let sink = HttpAuditSink::new(url).unwrap();
+
+    let results: Vec<_> = (0..3).map(|_| sink.write(&AuditEvent::new(AuditKind::AuthFailed))).collect();
+    let error = results.into_iter().find_map(Result::err).unwrap();
+    assert_eq!(error.to_string(), "audit delivery queue is full");
+    drop(listener);
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use p256::ecdsa::SigningKey;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::abuse::{public_key_value, AbuseStore, Ban, BanKind, SqliteAbuseStore};
+use video_conference_backend::{pinning, sessions};
+use video_conference_backend::signaling::{abuse, SharedState, SignalingState};
+use common::{add_client, add_member, drain, parsed, payloads, signal};
+
+const ADMIN_TOKEN: &str = "bans-admin";
+
+// A verified client with a key of its own and a resume token to come back with
+fn add_keyed_client(state: &mut SignalingState, port: u16) -> (SocketAddr, mpsc::Receiver<Message>) {
+    let (addr, rx) = add_client(state, port);
+    let client = state.clients.get_mut(&addr).unwrap();
+    client.public_key = Some(vec![port as u8; 65]);
+    client.resume_token = Some(format!("resume-{}", port));
+    (addr, rx)
+}
+
+fn with_bans() -> (SignalingState, Arc<SqliteAbuseStore>) {
+    let store = Arc::new(SqliteAbuseStore::open_in_memory().unwrap());
+    let mut state = SignalingState::new();
+    state.abuse = Some(Arc::clone(&store) as Arc<dyn AbuseStore>);
+    (state, store)
+}
+
+fn ban(kind: BanKind, value: &str) -> Ban {
+    Ban { kind, value: value.to_string(), reason: None, banned_by: None, created_at: 0 }
+}
+
+#[tokio::test]
+async fn participants_can_only_report_someone_in_their_room() {
+    let (mut inner, store) = with_bans();
+    let (reporter, mut reporter_rx) = add_member(&mut inner, 1, "alpha");
+    add_member(&mut inner, 2, "alpha");
+    add_member(&mut inner, 3, "beta");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let report = |client_id: &str| signal(SignalKind::Report, json!({ "client_id": client_id, "reason": "spamming the chat" }));
+    abuse::handle_report(parsed(&report("client-3")), reporter, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut reporter_rx, "error")[0]["code"], "target-unknown");
+
+    abuse::handle_report(parsed(&report("client-2")), reporter, Arc::clone(&state)).await.unwrap();
+    let received = payloads(&mut reporter_rx, "report-received").remove(0);
+    assert_eq!(received["client_id"], "client-2");
+
+    let reports = store.reports(10).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].report_id, received["report_id"]);
+    assert_eq!(reports[0].reported_identity, "client:client-2");
+}
+
+#[tokio::test]
+async fn reports_with_oversized_evidence_are_refused() {
+    let (mut inner, store) = with_bans();
+    let (reporter, mut reporter_rx) = add_member(&mut inner, 1, "alpha");
+    add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let report = signal(SignalKind::Report, json!({
+        "client_id": "client-2",
+        "reason": "spamming the chat",
+        "evidence": { "messages": ["spam".repeat(5000)] },
+    }));
+    abuse::handle_report(parsed(&report), reporter, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut reporter_rx, "error")[0]["code"], "invalid-report");
+    assert!(store.reports(10).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn banning_a_client_disconnects_it_and_keeps_it_out() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let (mut inner, _store) = with_bans();
+    let (admin, mut admin_rx) = add_client(&mut inner, 21);
+    let (target, mut target_rx) = add_keyed_client(&mut inner, 22);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let ban_target = |admin_token: &str| signal(SignalKind::Ban, json!({ "admin_token": admin_token, "client_id": "client-22" }));
+    abuse::handle_ban(parsed(&ban_target("wrong")), admin, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut admin_rx, "error")[0]["code"], "not-admin");
+    assert!(drain(&mut target_rx).is_empty());
+
+    abuse::handle_ban(parsed(&ban_target(ADMIN_TOKEN)), admin, Arc::clone(&state)).await.unwrap();
+    let banned = payloads(&mut admin_rx, "banned").remove(0);
+    assert_eq!(banned["bans"].as_array().unwrap().len(), 2);
+    assert_eq!(banned["disconnected"], 1);
+    assert_eq!(payloads(&mut target_rx, "error")[0]["code"], "banned");
+
+    let state = state.lock().await;
+    assert!(state.check_ban(target, None).is_some());
+    assert!(state.check_ban(admin, None).is_none());
+}
+
+#[test]
+fn a_ban_issued_while_a_session_is_suspended_stops_it_resuming() {
+    let (mut state, store) = with_bans();
+    let (away, _away_rx) = add_keyed_client(&mut state, 1);
+    let identity = pinning::identity(&state.clients[&away]);
+    sessions::suspend_session(&mut state, away, Duration::from_secs(60));
+    state.clients.remove(&away);
+    let (back, _back_rx) = add_keyed_client(&mut state, 2);
+
+    store.ban(&ban(BanKind::Identity, &identity)).unwrap();
+    assert!(sessions::resume_session(&mut state, back, "resume-1").is_none());
+    assert_eq!(state.clients[&back].client_id, "client-2");
+}
+
+#[tokio::test]
+async fn banning_drops_the_suspended_sessions_it_covers() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let (mut inner, _store) = with_bans();
+    let (admin, _admin_rx) = add_client(&mut inner, 11);
+    let mut identities = Vec::new();
+    for port in [12, 13] {
+        let (addr, _rx) = add_keyed_client(&mut inner, port);
+        identities.push(pinning::identity(&inner.clients[&addr]));
+        sessions::suspend_session(&mut inner, addr, Duration::from_secs(60));
+        inner.clients.remove(&addr);
+    }
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let ban = signal(SignalKind::Ban, json!({ "admin_token": ADMIN_TOKEN, "identity": identities[0] }));
+    abuse::handle_ban(parsed(&ban), admin, Arc::clone(&state)).await.unwrap();
+    let state = state.lock().await;
+    assert!(!state.suspended.contains_key("resume-12"));
+    assert!(state.suspended.contains_key("resume-13"));
+}
+
+#[test]
+fn key_bans_hold_in_either_encoding() {
+    let (mut state, store) = with_bans();
+    let key = *SigningKey::from_slice(&[7; 32]).unwrap().verifying_key();
+    let (uncompressed, compressed) = (key.to_encoded_point(false), key.to_encoded_point(true));
+
+    store.ban(&ban(BanKind::PublicKey, &public_key_value(compressed.as_bytes()))).unwrap();
+    assert!(state.find_ban("client:client-9", Some(uncompressed.as_bytes())).is_some());
+
+    let (member, _member_rx) = add_member(&mut state, 1, "alpha");
+    let room = state.rooms.get_mut("alpha").unwrap();
+    room.ban_key(uncompressed.as_bytes());
+    assert!(room.is_banned(&"10.0.0.1".parse().unwrap(), Some(compressed.as_bytes())));
+    assert!(!room.is_banned(&member.ip(), Some(&[1; 32])));
+}
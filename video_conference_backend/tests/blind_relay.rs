@@ -0,0 +1,141 @@
+mod common;
+
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::{Payload, Signal, SignalKind, SignalMessage};
+use video_conference_backend::signaling::{dispatch_signal, relay_sealed, SharedState, SignalingState};
+use common::{add_member, received, session_token, signal};
+
+// Adds a member of `room_id` whose public key is known, so sealed payloads can be addressed to it
+fn add_keyed_member(state: &mut SignalingState, port: u16, room_id: &str) -> (SocketAddr, mpsc::Receiver<Message>) {
+    let (addr, rx) = add_member(state, port, room_id);
+    state.clients.get_mut(&addr).unwrap().public_key = Some(vec![port as u8; 65]);
+    (addr, rx)
+}
+
+fn sealed(payload: &str, target_id: Option<&str>) -> SignalMessage {
+    SignalMessage {
+        payload: payload.to_string(),
+        sender_id: "client-1".to_string(),
+        target_id: target_id.map(str::to_string),
+        ..signal(SignalKind::Sealed, json!(null))
+    }
+}
+
+// Anything that tried to parse these would fail, so delivery proves the relay left them alone
+const OPAQUE_PAYLOADS: &[&str] = &[
+    "q2V5LWN5cGhlcnRleHQ+/=={{not json",
+    "\u{0}\u{1}\u{7f}binary-ish",
+    "",
+    "{\"offer\":{\"sdp\":\"v=0\"},\"public_key\":\"bogus\",\"signature\":[]}",
+];
+
+#[test]
+fn sealed_payloads_are_never_read() {
+    for payload in OPAQUE_PAYLOADS {
+        let message = sealed(payload, Some("client-2"));
+        let kind = SignalKind::of(&message.signal_type);
+        assert_eq!(kind, SignalKind::Sealed);
+        let read = Payload::read(kind, &message);
+        assert!(matches!(read, Payload::Opaque));
+        assert!(matches!(Signal::from_message(&message), Ok(Signal::Sealed)));
+    }
+    assert_eq!(SignalKind::of("secure-offer"), SignalKind::SecureOffer);
+    assert_eq!(SignalKind::of("no-such-signal"), SignalKind::Unknown);
+}
+
+#[tokio::test]
+async fn sealed_payload_reaches_target_verbatim() {
+    for payload in OPAQUE_PAYLOADS {
+        let mut inner = SignalingState::new();
+        let (sender, mut sender_rx) = add_keyed_member(&mut inner, 1, "alpha");
+        let (_, mut target_rx) = add_keyed_member(&mut inner, 2, "alpha");
+        let state: SharedState = Arc::new(Mutex::new(inner));
+
+        relay_sealed(&sealed(payload, Some("client-2")), sender, Arc::clone(&state)).await.unwrap();
+
+        let delivered = received(&mut target_rx).unwrap();
+        assert_eq!(delivered.signal_type, "sealed");
+        assert_eq!(delivered.payload, *payload);
+        assert!(sender_rx.try_recv().is_err());
+    }
+}
+
+#[tokio::test]
+async fn sealed_messages_pass_the_server_checks_unread() {
+    for payload in OPAQUE_PAYLOADS {
+        let mut inner = SignalingState::new();
+        let (sender, mut sender_rx) = add_keyed_member(&mut inner, 1, "alpha");
+        let token = session_token(&mut inner, sender);
+        let (_, mut target_rx) = add_keyed_member(&mut inner, 2, "alpha");
+        let state: SharedState = Arc::new(Mutex::new(inner));
+
+        let message = sealed(payload, Some("client-2"));
+        let mut client_id = "client-1".to_string();
+        dispatch_signal(&message, Some(&token), sender, &mut client_id, Arc::clone(&state)).await.unwrap();
+
+        let delivered = received(&mut target_rx).unwrap();
+        assert_eq!(delivered.signal_type, "sealed");
+        assert_eq!(delivered.payload, *payload);
+        assert!(sender_rx.try_recv().is_err());
+    }
+}
+
+#[tokio::test]
+async fn sealed_payload_goes_only_to_its_target() {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_keyed_member(&mut inner, 1, "alpha");
+    let (_, mut target_rx) = add_keyed_member(&mut inner, 2, "alpha");
+    let (_, mut bystander_rx) = add_keyed_member(&mut inner, 3, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    relay_sealed(&sealed(OPAQUE_PAYLOADS[0], Some("client-2")), sender, Arc::clone(&state)).await.unwrap();
+
+    assert!(target_rx.try_recv().is_ok());
+    assert!(bystander_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn sealed_payload_does_not_cross_rooms() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_keyed_member(&mut inner, 1, "alpha");
+    let (_, mut other_room_rx) = add_keyed_member(&mut inner, 2, "beta");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    relay_sealed(&sealed(OPAQUE_PAYLOADS[0], Some("client-2")), sender, Arc::clone(&state)).await.unwrap();
+
+    assert!(other_room_rx.try_recv().is_err());
+    let error = received(&mut sender_rx).unwrap();
+    assert_eq!(error.signal_type, "error");
+    assert!(error.payload.contains("target-unknown"));
+}
+
+#[tokio::test]
+async fn sealed_payload_needs_a_target() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_keyed_member(&mut inner, 1, "alpha");
+    let (_, mut peer_rx) = add_keyed_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    relay_sealed(&sealed(OPAQUE_PAYLOADS[0], None), sender, Arc::clone(&state)).await.unwrap();
+
+    assert!(peer_rx.try_recv().is_err());
+    assert!(received(&mut sender_rx).unwrap().payload.contains("missing-target"));
+}
+
+#[tokio::test]
+async fn sealed_payload_needs_a_target_key() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_keyed_member(&mut inner, 1, "alpha");
+    let (target, mut target_rx) = add_keyed_member(&mut inner, 2, "alpha");
+    inner.clients.get_mut(&target).unwrap().public_key = None;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    relay_sealed(&sealed(OPAQUE_PAYLOADS[0], Some("client-2")), sender, Arc::clone(&state)).await.unwrap();
+
+    assert!(target_rx.try_recv().is_err());
+    assert!(received(&mut sender_rx).unwrap().payload.contains("target-unverified"));
+}
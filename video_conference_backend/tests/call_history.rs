@@ -0,0 +1,112 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::calls::{CallOutcome, CallRecord, CallStore, SqliteCallStore};
+use video_conference_backend::models::message::CallHistoryPayload;
+use video_conference_backend::signaling::{calls, SharedState, SignalingState};
+use common::{add_client, call, invite, payloads, sign_in};
+
+fn record(n: i64, callee: &str, outcome: CallOutcome) -> CallRecord {
+    CallRecord {
+        record_id: format!("record-{}", n),
+        call_id: format!("call-{}", n),
+        tenant_id: None,
+        caller: "alice".to_string(),
+        callee: callee.to_string(),
+        room_id: None,
+        outcome,
+        started_at: n,
+        answered_at: None,
+        ended_at: Some(n),
+    }
+}
+
+#[test]
+fn retention_prunes_old_calls_and_caps_missed_calls_per_callee() {
+    let store = SqliteCallStore::open_in_memory().unwrap();
+    for n in 1..=5 {
+        store.save(&record(n, "bob", CallOutcome::Missed)).unwrap();
+        store.save(&record(10 + n, "carol", CallOutcome::Missed)).unwrap();
+    }
+    store.save(&record(21, "bob", CallOutcome::Answered)).unwrap();
+
+    // Older than 2 goes, then all but each callee's newest 2 missed calls
+    assert_eq!(store.prune(2, 2).unwrap(), 1 + 2 + 3);
+    let missed: Vec<_> = store.take_missed(None, "bob").unwrap().into_iter().map(|record| record.started_at).collect();
+    assert_eq!(missed, [4, 5]);
+    assert_eq!(store.take_missed(None, "carol").unwrap().len(), 2);
+    assert_eq!(store.history(None, "bob", 10).unwrap()[0].outcome, CallOutcome::Answered);
+}
+
+#[tokio::test]
+async fn a_call_to_an_offline_callee_is_missed_and_delivered_on_sign_in() {
+    std::env::set_var("CALL_RING_TIMEOUT_SECS", "0");
+    let store: Arc<dyn CallStore> = Arc::new(SqliteCallStore::open_in_memory().unwrap());
+    let mut inner = SignalingState::new();
+    inner.call_store = Some(Arc::clone(&store));
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (callee, mut callee_rx) = add_client(&mut inner, 2);
+    sign_in(&mut inner, caller, "alice");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut caller_rx, "error").is_empty());
+    calls::expire_calls(&mut *state.lock().await).await;
+    assert_eq!(payloads(&mut caller_rx, "call-timeout")[0]["call_id"], "call-1");
+
+    let history = store.history(None, "alice", 10).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].callee, "bob");
+    assert!(history[0].ended_at.is_some());
+
+    let mut inner = state.lock().await;
+    sign_in(&mut inner, callee, "bob");
+    calls::signed_in(&mut inner, callee).await.unwrap();
+    let missed = payloads(&mut callee_rx, "missed-call");
+    assert_eq!(missed.len(), 1);
+    assert_eq!(missed[0]["caller"], "alice");
+    assert_eq!(missed[0]["outcome"], "missed");
+
+    calls::signed_in(&mut inner, callee).await.unwrap();
+    assert!(payloads(&mut callee_rx, "missed-call").is_empty());
+}
+
+#[tokio::test]
+async fn answered_and_rejected_calls_are_recorded_for_both_ends() {
+    let store: Arc<dyn CallStore> = Arc::new(SqliteCallStore::open_in_memory().unwrap());
+    let mut inner = SignalingState::new();
+    inner.call_store = Some(Arc::clone(&store));
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (callee, mut callee_rx) = add_client(&mut inner, 2);
+    let (stranger, mut stranger_rx) = add_client(&mut inner, 3);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, callee, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-1"), true, callee, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_hangup(call("call-1"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_invite(invite("call-2", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-2"), false, callee, Arc::clone(&state)).await.unwrap();
+
+    calls::handle_call_history(CallHistoryPayload::default(), callee, Arc::clone(&state)).await.unwrap();
+    let history = payloads(&mut callee_rx, "call-history").pop().unwrap();
+    let calls = history["calls"].as_array().unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0]["call_id"], "call-2");
+    assert_eq!(calls[0]["outcome"], "rejected");
+    assert_eq!(calls[1]["outcome"], "answered");
+    assert!(calls[1]["answered_at"].is_i64());
+    assert!(calls[1]["ended_at"].is_i64());
+
+    calls::handle_call_history(CallHistoryPayload { limit: Some(1) }, caller, Arc::clone(&state)).await.unwrap();
+    let history = payloads(&mut caller_rx, "call-history").pop().unwrap();
+    assert_eq!(history["calls"].as_array().unwrap().len(), 1);
+    calls::handle_call_history(CallHistoryPayload::default(), stranger, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut stranger_rx, "error")[0]["code"], "not-signed-in");
+
+    state.lock().await.call_store = None;
+    calls::handle_call_history(CallHistoryPayload::default(), callee, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut callee_rx, "error")[0]["code"], "call-history-unavailable");
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::message::CallPayload;
+use video_conference_backend::signaling::{calls, SharedState, SignalingState};
+use common::{add_client, call, invite, payloads, sign_in};
+
+#[tokio::test]
+async fn every_device_rings_and_the_first_answer_cancels_the_rest() {
+    let mut inner = SignalingState::new();
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (phone, mut phone_rx) = add_client(&mut inner, 2);
+    let (laptop, mut laptop_rx) = add_client(&mut inner, 3);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, phone, "bob");
+    sign_in(&mut inner, laptop, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    for rx in [&mut phone_rx, &mut laptop_rx] {
+        let rung = payloads(rx, "call-invite");
+        assert_eq!(rung[0]["caller_user"], "alice");
+        assert_eq!(rung[0]["caller_id"], "client-1");
+    }
+
+    calls::handle_call_ringing(call("call-1"), laptop, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "call-ringing")[0]["client_id"], "client-3");
+
+    calls::handle_call_answer(call("call-1"), true, phone, Arc::clone(&state)).await.unwrap();
+    let accepted = payloads(&mut caller_rx, "call-accept");
+    assert_eq!(accepted[0]["client_id"], "client-2");
+    assert_eq!(accepted[0]["user_id"], "bob");
+    assert_eq!(payloads(&mut laptop_rx, "call-cancel")[0]["reason"], "answered-elsewhere");
+    assert!(payloads(&mut phone_rx, "call-cancel").is_empty());
+
+    calls::handle_call_answer(call("call-1"), true, laptop, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut laptop_rx, "error")[0]["code"], "unknown-call");
+}
+
+#[tokio::test]
+async fn rejections_and_cancellations() {
+    let mut inner = SignalingState::new();
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (phone, mut phone_rx) = add_client(&mut inner, 2);
+    let (laptop, mut laptop_rx) = add_client(&mut inner, 3);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, phone, "bob");
+    sign_in(&mut inner, laptop, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-2", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    let busy = CallPayload { reason: Some("busy".to_string()), ..call("call-2") };
+    calls::handle_call_answer(busy, false, laptop, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "call-reject")[0]["reason"], "busy");
+    assert_eq!(payloads(&mut phone_rx, "call-cancel")[0]["reason"], "rejected-elsewhere");
+
+    calls::handle_call_invite(invite("call-3", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_cancel(call("call-3"), phone, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut phone_rx, "error")[0]["code"], "unknown-call");
+    calls::handle_call_cancel(call("call-3"), caller, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut laptop_rx, "call-cancel").last().unwrap()["reason"], "cancelled");
+}
+
+#[tokio::test]
+async fn unanswered_invites_time_out() {
+    std::env::set_var("CALL_RING_TIMEOUT_SECS", "0");
+    let mut inner = SignalingState::new();
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (phone, mut phone_rx) = add_client(&mut inner, 2);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, phone, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::expire_calls(&mut *state.lock().await).await;
+    assert_eq!(payloads(&mut caller_rx, "call-timeout")[0]["call_id"], "call-1");
+    assert_eq!(payloads(&mut phone_rx, "call-cancel")[0]["reason"], "timeout");
+}
+
+#[tokio::test]
+async fn only_signed_in_verified_callers_ring_anyone() {
+    let mut inner = SignalingState::new();
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (phone, mut phone_rx) = add_client(&mut inner, 2);
+    sign_in(&mut inner, phone, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "error")[0]["code"], "not-signed-in");
+
+    let mut inner = state.lock().await;
+    sign_in(&mut inner, caller, "alice");
+    inner.clients.get_mut(&caller).unwrap().verified = false;
+    drop(inner);
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "error")[0]["code"], "unverified-sender");
+    assert!(payloads(&mut phone_rx, "call-invite").is_empty());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::message::CandidateSignature;
+use video_conference_backend::models::{RoomConfig, SignalKind, SignalMessage};
+use video_conference_backend::rooms::{CandidatePolicy, CandidateRejection};
+use video_conference_backend::signaling::acks::{self, Delivery};
+use video_conference_backend::signaling::ice;
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_member, signal};
+
+const POLICY: CandidatePolicy = CandidatePolicy { strip_mdns: true, relay_only: false, drop_private: true };
+
+// Adds a member of "alpha" with a known public key
+fn add_keyed_member(state: &mut SignalingState, port: u16) -> (SocketAddr, mpsc::Receiver<Message>) {
+    let (addr, rx) = add_member(state, port, "alpha");
+    state.clients.get_mut(&addr).unwrap().public_key = Some(vec![4, 1, 2]);
+    (addr, rx)
+}
+
+fn candidate(line: &str) -> SignalMessage {
+    let mut message = signal(SignalKind::IceCandidate, serde_json::json!({ "candidate": line, "sdpMid": "0", "sdpMLineIndex": 0 }));
+    message.sender_id = "client-1".to_string();
+    message
+}
+
+#[test]
+fn candidates_are_checked_against_the_policy() {
+    assert_eq!(POLICY.check("candidate:1 1 udp 2122260223 4b1a6f3e-77c2.local 54400 typ host"), Err(CandidateRejection::Mdns));
+    assert_eq!(POLICY.check("candidate:2 1 udp 2122260223 192.168.1.20 54400 typ host"), Err(CandidateRejection::PrivateAddress));
+    assert_eq!(POLICY.check("a=candidate:3 1 udp 2122260223 169.254.10.1 54400 typ host"), Err(CandidateRejection::PrivateAddress));
+    assert_eq!(POLICY.check("candidate:4 1 udp 2122260223 fe80::1 54400 typ host"), Err(CandidateRejection::PrivateAddress));
+    assert_eq!(POLICY.check("candidate:5 1 udp 2122260223 fd00::7 54400 typ host"), Err(CandidateRejection::PrivateAddress));
+    assert_eq!(POLICY.check("candidate:8 1 udp 2122260223 100.72.3.4 54400 typ host"), Err(CandidateRejection::PrivateAddress));
+    assert!(POLICY.check("candidate:9 1 udp 2122260223 100.128.3.4 54400 typ host").is_ok());
+    assert!(POLICY.check("candidate:6 1 udp 1686052607 198.51.100.7 61000 typ srflx raddr 192.168.1.20 rport 54400").is_ok());
+    assert!(POLICY.check("candidate:7 1 udp 2122260223 2001:db8::7 54400 typ host").is_ok());
+    // The end-of-candidates marker always goes through
+    assert!(POLICY.check("").is_ok());
+
+    let relay_only = CandidatePolicy { relay_only: true, ..CandidatePolicy::default() };
+    assert_eq!(relay_only.check("candidate:6 1 udp 1686052607 198.51.100.7 61000 typ srflx"), Err(CandidateRejection::NotRelay));
+    assert!(relay_only.check("candidate:8 1 udp 41885439 203.0.113.9 3478 typ relay raddr 198.51.100.7 rport 61000").is_ok());
+}
+
+#[test]
+fn rooms_override_the_server_defaults() {
+    assert_eq!(CandidatePolicy::for_room(&RoomConfig::default()), CandidatePolicy::default());
+
+    let config = RoomConfig { relay_only: Some(true), strip_mdns_candidates: Some(true), ..RoomConfig::default() };
+    assert_eq!(
+        CandidatePolicy::for_room(&config),
+        CandidatePolicy { strip_mdns: true, relay_only: true, drop_private: false }
+    );
+}
+
+#[tokio::test]
+async fn relay_only_rooms_drop_other_candidates() {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_keyed_member(&mut inner, 1);
+    let (_, mut peer_rx) = add_keyed_member(&mut inner, 2);
+    inner.rooms.get_mut("alpha").unwrap().config.relay_only = Some(true);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let host = candidate("candidate:1 1 udp 2122260223 198.51.100.7 54400 typ host");
+    let (result, outcome) = acks::track(ice::handle_ice_candidate(&host, CandidateSignature::default(), sender, Arc::clone(&state))).await;
+    result.unwrap();
+    assert_eq!(outcome, Some(Delivery::Failed("relay-only".to_string())));
+    assert!(peer_rx.try_recv().is_err());
+
+    let relay = candidate("candidate:2 1 udp 41885439 203.0.113.9 3478 typ relay");
+    ice::handle_ice_candidate(&relay, CandidateSignature::default(), sender, Arc::clone(&state)).await.unwrap();
+    let Message::Text(text) = peer_rx.try_recv().unwrap() else {
+        panic!("expected a text frame");
+    };
+    let relayed: SignalMessage = serde_json::from_str(&text).unwrap();
+    assert_eq!(relayed.payload, relay.payload);
+}
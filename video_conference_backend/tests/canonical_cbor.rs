@@ -0,0 +1,171 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use serde_json::{json, Value};
+use video_conference_backend::crypto::{canonical_cbor, signed_envelope, verify_ed25519};
+use video_conference_backend::models::{SignalKind, SignalMessage};
+
+fn hex(value: &str) -> Vec<u8> {
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+// RFC 8949 appendix A, restricted to what JSON can express
+#[test]
+fn encodes_rfc_8949_examples() {
+    let examples: &[(&str, &str)] = &[
+        ("0", "00"),
+        ("23", "17"),
+        ("24", "1818"),
+        ("1000", "1903e8"),
+        ("1000000", "1a000f4240"),
+        ("1000000000000", "1b000000e8d4a51000"),
+        ("18446744073709551615", "1bffffffffffffffff"),
+        ("-1", "20"),
+        ("-1000", "3903e7"),
+        ("0.0", "f90000"),
+        ("-0.0", "f98000"),
+        ("1.5", "f93e00"),
+        ("65504.0", "f97bff"),
+        ("100000.0", "fa47c35000"),
+        ("5.9604644775390625e-8", "f90001"),
+        ("1.1", "fb3ff199999999999a"),
+        ("-4.1", "fbc010666666666666"),
+        ("false", "f4"),
+        ("null", "f6"),
+        (r#""""#, "60"),
+        (r#""IETF""#, "6449455446"),
+        (r#""ü""#, "62c3bc"),
+        ("[1,[2,3],[4,5]]", "8301820203820405"),
+        (r#"{"a":1,"b":[2,3]}"#, "a26161016162820203"),
+        (r#"["a",{"b":"c"}]"#, "826161a161626163"),
+    ];
+    for (json, expected) in examples {
+        let value: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(canonical_cbor(&value), hex(expected), "{}", json);
+    }
+}
+
+#[test]
+fn map_keys_sort_by_encoded_bytes() {
+    // Shorter keys encode to smaller heads, so "z" comes before "aa"
+    let value = json!({ "aa": 2, "z": 1, "b": 3 });
+    assert_eq!(canonical_cbor(&value), hex("a3616203617a0162616102"));
+}
+
+// The RFC 8785 key set: bytewise order of the encoded keys puts shorter UTF-8 first, so unlike
+// canonical JSON the emoji sorts last
+#[test]
+fn map_keys_sort_by_length_then_utf8_bytes() {
+    let input = r#"{
+        "\u20ac": 5,
+        "\r": 1,
+        "\ufb33": 7,
+        "1": 2,
+        "\ud83d\ude00": 6,
+        "\u0080": 3,
+        "\u00f6": 4
+    }"#;
+    let value: Value = serde_json::from_str(input).unwrap();
+    let expected = "a7610d0161310262c2800362c3b60463e282ac0563efacb30764f09f988006";
+    assert_eq!(canonical_cbor(&value), hex(expected));
+}
+
+// Integers stay integers, however large, and only floats are narrowed
+#[test]
+fn numbers_use_the_shortest_exact_form() {
+    let vectors: &[(&str, &str)] = &[
+        ("1.0", "f93c00"),
+        // Canonical JSON writes 0, but the sign survives here
+        ("-0", "f98000"),
+        ("0.00006103515625", "f90400"),
+        ("65505.0", "fa477fe100"),
+        // Read to the exact double, or the signed bytes would be a neighbour of what the client sent
+        ("3.4028234663852886e38", "fa7f7fffff"),
+        ("5e-324", "fb0000000000000001"),
+        ("1e300", "fb7e37e43c8800759c"),
+        ("9007199254740993", "1b0020000000000001"),
+        ("-9223372036854775808", "3b7fffffffffffffff"),
+        // Past 64 bits JSON has only the nearest double
+        ("-9223372036854775809", "fadf000000"),
+        ("18446744073709551616", "fa5f800000"),
+    ];
+    for (json, expected) in vectors {
+        let value: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(canonical_cbor(&value), hex(expected), "{}", json);
+    }
+}
+
+#[test]
+fn strings_are_raw_utf8_with_a_length_head() {
+    let vectors: &[(&str, &str)] = &[
+        (r#""\u0000\"\\/""#, "6400225c2f"),
+        (r#""\ud83d\ude00""#, "64f09f9880"),
+        // No normalization: the precomposed and decomposed forms differ
+        (r#""\u00e9""#, "62c3a9"),
+        (r#""e\u0301""#, "6365cc81"),
+    ];
+    for (json, expected) in vectors {
+        let value: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(canonical_cbor(&value), hex(expected), "{}", json);
+    }
+
+    for (len, head) in [(23, "77"), (24, "7818"), (255, "78ff"), (256, "790100")] {
+        let encoded = canonical_cbor(&json!("x".repeat(len)));
+        assert_eq!(encoded[..head.len() / 2], hex(head), "length {}", len);
+        assert_eq!(encoded.len(), head.len() / 2 + len);
+    }
+}
+
+#[test]
+fn field_order_does_not_change_the_encoding() {
+    let a: Value = serde_json::from_str(r#"{"payload":{"x":1,"y":[1,2]},"signal_type":"ice-candidate"}"#).unwrap();
+    let b: Value = serde_json::from_str(r#"{"signal_type":"ice-candidate","payload":{"y":[1,2],"x":1}}"#).unwrap();
+    assert_eq!(canonical_cbor(&a), canonical_cbor(&b));
+}
+
+fn candidate(payload: Value) -> SignalMessage {
+    let mut signal = SignalMessage::server(SignalKind::IceCandidate.name(), payload);
+    signal.sender_id = "client-1".to_string();
+    signal.target_id = Some("client-2".to_string());
+    signal
+}
+
+#[test]
+fn version_3_candidate_signature_covers_the_cbor_envelope() {
+    let key = SigningKey::from_bytes(&[7u8; 32]);
+    let public_key = key.verifying_key().to_bytes();
+    let unsigned = json!({
+        "candidate": "candidate:1 1 udp 2122260223 192.0.2.1 54400 typ host",
+        "sdpMid": "0",
+        "sdpMLineIndex": 0,
+        "nonce": [1, 2, 3],
+        "algorithm": "ed25519",
+        "signature_version": 3,
+    });
+    let envelope = json!({
+        "signal_type": "ice-candidate",
+        "sender_id": "client-1",
+        "target_id": "client-2",
+        "payload": unsigned,
+    });
+    let signature = key.sign(&canonical_cbor(&envelope)).to_bytes().to_vec();
+
+    let mut payload = unsigned.clone();
+    payload["signature"] = json!(signature);
+    let signal = candidate(payload);
+
+    let message = signed_envelope(&signal, 3, None).unwrap();
+    assert!(verify_ed25519(&message, &signature, &public_key).is_ok());
+
+    // signed_data may restate the bytes, but only exactly
+    let restated = STANDARD.encode(&message);
+    assert_eq!(signed_envelope(&signal, 3, Some(&restated)), Some(message.clone()));
+    assert_eq!(signed_envelope(&signal, 3, Some(&STANDARD.encode(b"other"))), None);
+
+    // The same envelope under version 2 is JSON, which this signature does not cover
+    let json = signed_envelope(&signal, 2, None).unwrap();
+    assert!(verify_ed25519(&json, &signature, &public_key).is_err());
+}
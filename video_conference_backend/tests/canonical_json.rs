@@ -0,0 +1,118 @@
+use serde_json::{json, Number, Value};
+use video_conference_backend::crypto::{canonicalize, signed_message};
+use video_conference_backend::models::message::SecureConnectionPayload;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+
+fn canonical(json: &str) -> String {
+    canonicalize(&serde_json::from_str(json).unwrap())
+}
+
+fn double(bits: u64) -> String {
+    canonicalize(&Value::Number(Number::from_f64(f64::from_bits(bits)).unwrap()))
+}
+
+// RFC 8785 section 3.2.3: the emoji's surrogate pair sorts before U+FB33 even though its code point is higher
+#[test]
+fn object_keys_sort_by_utf16_code_units() {
+    let input = r#"{
+        "\u20ac": "Euro Sign",
+        "\r": "Carriage Return",
+        "\ufb33": "Hebrew Letter Dalet With Dagesh",
+        "1": "One",
+        "\ud83d\ude00": "Emoji: Grinning Face",
+        "\u0080": "Control",
+        "\u00f6": "Latin Small Letter O With Diaeresis"
+    }"#;
+    let expected = concat!(
+        "{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",",
+        "\"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\"\u{20ac}\":\"Euro Sign\",",
+        "\"\u{1f600}\":\"Emoji: Grinning Face\",\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}",
+    );
+    assert_eq!(canonical(input), expected);
+}
+
+// RFC 8785 appendix B, plus the literals a client is most likely to send
+#[test]
+fn numbers_serialize_like_ecmascript() {
+    let vectors = [
+        (0x0000000000000000, "0"),
+        (0x8000000000000000, "0"),
+        (0x0000000000000001, "5e-324"),
+        (0x8000000000000001, "-5e-324"),
+        (0x7fefffffffffffff, "1.7976931348623157e+308"),
+        (0xffefffffffffffff, "-1.7976931348623157e+308"),
+        (0x4340000000000000, "9007199254740992"),
+        (0xc340000000000000, "-9007199254740992"),
+        (0x4430000000000000, "295147905179352830000"),
+        (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+        (0x44b52d02c7e14af6, "1e+23"),
+        (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+        (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+        (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+        (0x444b1ae4d6e2ef50, "1e+21"),
+        (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+        (0x3eb0c6f7a0b5ed8d, "0.000001"),
+        (0x41b3de4355555553, "333333333.3333332"),
+        (0x41b3de4355555554, "333333333.33333325"),
+        (0x41b3de4355555555, "333333333.3333333"),
+        (0x41b3de4355555556, "333333333.3333334"),
+        (0x41b3de4355555557, "333333333.33333343"),
+        (0xbecbf647612f3696, "-0.0000033333333333333333"),
+        (0x43143ff3c1cb0959, "1424953923781206.2"),
+    ];
+    for (bits, expected) in vectors {
+        assert_eq!(double(bits), expected, "bits {:#018x}", bits);
+    }
+
+    assert_eq!(canonical("[1e21, -0, 5e-324, 1.0, 100, -7]"), "[1e+21,0,5e-324,1,100,-7]");
+    // Integers beyond 2^53 are read as the nearest double, as a browser would
+    assert_eq!(canonical("9007199254740993"), "9007199254740992");
+    assert_eq!(canonical("-9007199254740993"), "-9007199254740992");
+    assert_eq!(canonical("18446744073709551615"), "18446744073709552000");
+}
+
+#[test]
+fn strings_escape_only_what_json_requires() {
+    let input = r#""\u0000\u0008\u0009\u000a\u000c\u000d\u001f\u0022\u005c\u002f\u007f\u00e9\ud83d\ude00""#;
+    let expected = "\"\\u0000\\b\\t\\n\\f\\r\\u001f\\\"\\\\/\u{7f}\u{e9}\u{1f600}\"";
+    assert_eq!(canonical(input), expected);
+}
+
+#[test]
+fn nested_structures_lose_all_whitespace() {
+    let input = r#"{ "b": [ true, null, { "d": 1, "c": "x" } ], "a": { } }"#;
+    assert_eq!(canonical(input), r#"{"a":{},"b":[true,null,{"c":"x","d":1}]}"#);
+}
+
+#[test]
+fn signed_data_must_describe_the_relayed_offer() {
+    let fields = json!({
+        "offer": { "type": "offer", "sdp": "v=0" },
+        "public_key": [],
+        "signature": [],
+        "nonce": [1],
+        "signature_version": 2,
+    });
+    let signal = SignalMessage::server(SignalKind::SecureOffer.name(), fields.clone());
+    let mut payload: SecureConnectionPayload = serde_json::from_value(fields).unwrap();
+    let envelope = json!({
+        "signal_type": "secure-offer",
+        "sender_id": signal.sender_id,
+        "target_id": null,
+        "payload": { "offer": { "type": "offer", "sdp": "v=0" }, "public_key": [], "nonce": [1], "signature_version": 2 },
+    });
+    assert_eq!(signed_message(&signal, &payload).unwrap(), canonicalize(&envelope).into_bytes());
+
+    payload.signed_data = Some(serde_json::to_string_pretty(&envelope).unwrap());
+    assert_eq!(signed_message(&signal, &payload).unwrap(), payload.signed_data.as_ref().unwrap().as_bytes());
+
+    // The nonce is part of what is signed
+    let mut renonced = envelope.clone();
+    renonced["payload"]["nonce"] = json!([2]);
+    payload.signed_data = Some(renonced.to_string());
+    assert!(signed_message(&signal, &payload).is_none());
+
+    payload.signed_data = None;
+    payload.signature_version = 1;
+    assert!(signed_message(&signal, &payload).is_none());
+}
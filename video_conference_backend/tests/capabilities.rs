@@ -0,0 +1,90 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::message::JoinRoomPayload;
+use video_conference_backend::models::Capabilities;
+use video_conference_backend::signaling::{handle_join_room, roster, SharedState, SignalingState};
+use common::{add_client, payloads};
+
+fn join(capabilities: Option<Capabilities>) -> JoinRoomPayload {
+    JoinRoomPayload {
+        room_id: "alpha".to_string(),
+        max_participants: None,
+        password: None,
+        invite_token: None,
+        capabilities,
+    }
+}
+
+fn capabilities(audio: &[&str], video: &[&str], simulcast: bool) -> Capabilities {
+    Capabilities {
+        audio_codecs: audio.iter().map(|codec| codec.to_string()).collect(),
+        video_codecs: video.iter().map(|codec| codec.to_string()).collect(),
+        simulcast,
+        e2ee: true,
+        data_channels: true,
+    }
+}
+
+#[test]
+fn the_room_shares_what_every_member_supports() {
+    let first = capabilities(&["opus", "PCMU"], &["video/VP9", "video/VP8", "H264", "vp9"], true).normalized();
+    assert_eq!(first.video_codecs, ["vp9", "vp8", "h264"]);
+
+    let second = capabilities(&["pcmu", "opus"], &["vp8", "vp9"], false).normalized();
+    let common = Capabilities::common([&first, &second]).unwrap();
+    assert_eq!(common.audio_codecs, ["opus", "pcmu"]);
+    assert_eq!(common.video_codecs, ["vp9", "vp8"]);
+    assert!(!common.simulcast);
+    assert!(common.e2ee && common.data_channels);
+
+    assert_eq!(Capabilities::common([]), None);
+    assert!(capabilities(&["opus red"], &[], false).validate().is_err());
+}
+
+#[tokio::test]
+async fn capabilities_declared_at_join_reach_the_roster() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let (b, mut b_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_join_room(join(Some(capabilities(&["opus"], &["vp8", "vp9"], true))), a, Arc::clone(&state)).await.unwrap();
+    let roster = payloads(&mut a_rx, "roster");
+    assert_eq!(roster[0]["capabilities"]["video_codecs"], serde_json::json!(["vp8", "vp9"]));
+
+    handle_join_room(join(Some(capabilities(&["opus"], &["vp9"], false))), b, Arc::clone(&state)).await.unwrap();
+    let roster = payloads(&mut b_rx, "roster");
+    assert_eq!(roster[0]["capabilities"]["video_codecs"], serde_json::json!(["vp9"]));
+    assert_eq!(roster[0]["capabilities"]["simulcast"], false);
+    assert_eq!(roster[0]["participants"][0]["capabilities"]["simulcast"], true);
+
+    let joined = payloads(&mut a_rx, "peer-joined");
+    assert_eq!(joined[0]["participant"]["capabilities"]["video_codecs"], serde_json::json!(["vp9"]));
+    assert_eq!(joined[0]["capabilities"]["video_codecs"], serde_json::json!(["vp9"]));
+}
+
+#[tokio::test]
+async fn later_declarations_update_the_room() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let (b, mut b_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    handle_join_room(join(Some(capabilities(&["opus"], &["vp8"], true))), a, Arc::clone(&state)).await.unwrap();
+    handle_join_room(join(None), b, Arc::clone(&state)).await.unwrap();
+    // Drop what a was told about b arriving
+    payloads(&mut a_rx, "");
+
+    roster::handle_capabilities(capabilities(&["opus"], &["vp8", "av1"], true), b, Arc::clone(&state)).await.unwrap();
+    let reply = payloads(&mut b_rx, "capabilities-updated");
+    assert_eq!(reply[0]["room_capabilities"]["video_codecs"], serde_json::json!(["vp8"]));
+    let update = payloads(&mut a_rx, "peer-capabilities");
+    assert_eq!(update[0]["client_id"], "client-2");
+    assert_eq!(update[0]["capabilities"]["video_codecs"], serde_json::json!(["vp8", "av1"]));
+
+    roster::handle_capabilities(capabilities(&[""], &[], true), b, Arc::clone(&state)).await.unwrap();
+    let error = payloads(&mut b_rx, "error");
+    assert_eq!(error[0]["code"], "invalid-capabilities");
+    assert!(payloads(&mut a_rx, "peer-capabilities").is_empty());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::auth::{check_authenticated, challenge_message, handle_challenge_response, send_challenge};
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_client, drain, parsed, payloads, signal};
+
+// Issues a challenge to `addr` and answers it with `signing_key`
+async fn answer_challenge(
+    signing_key: &SigningKey,
+    addr: SocketAddr,
+    rx: &mut mpsc::Receiver<Message>,
+    state: &SharedState
+) {
+    send_challenge(addr, Arc::clone(state)).await.unwrap();
+    let challenge = payloads(rx, "challenge").remove(0);
+    let message = challenge_message(challenge["challenge"].as_str().unwrap(), challenge["client_id"].as_str().unwrap());
+    let signature: Signature = signing_key.sign(&message);
+
+    let response = signal(SignalKind::ChallengeResponse, json!({
+        "public_key": signing_key.verifying_key().to_encoded_point(false).as_bytes(),
+        "signature": signature.to_bytes().to_vec(),
+    }));
+    handle_challenge_response(parsed(&response), addr, Arc::clone(state)).await.unwrap();
+}
+
+#[tokio::test]
+async fn answering_the_challenge_binds_the_connection_to_the_key() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    answer_challenge(&signing_key, addr, &mut rx, &state).await;
+    assert_eq!(payloads(&mut rx, "authenticated")[0]["client_id"], "client-1");
+
+    let state = state.lock().await;
+    let client = &state.clients[&addr];
+    assert!(client.authenticated);
+    assert_eq!(client.public_key.as_deref(), Some(signing_key.verifying_key().to_encoded_point(false).as_bytes()));
+}
+
+#[tokio::test]
+async fn a_bound_connection_cannot_be_rebound_to_another_key() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let first = SigningKey::random(&mut OsRng);
+    answer_challenge(&first, addr, &mut rx, &state).await;
+    assert_eq!(payloads(&mut rx, "authenticated").len(), 1);
+
+    answer_challenge(&SigningKey::random(&mut OsRng), addr, &mut rx, &state).await;
+    let signals = drain(&mut rx);
+    assert!(signals.iter().all(|(kind, _)| kind != "authenticated"));
+    assert!(signals.iter().any(|(kind, payload)| kind == "error" && payload["code"] == "challenge-failed"));
+
+    let state = state.lock().await;
+    assert_eq!(state.clients[&addr].public_key.as_deref(), Some(first.verifying_key().to_encoded_point(false).as_bytes()));
+}
+
+#[tokio::test]
+async fn a_wrong_signature_fails_the_challenge() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    inner.clients.get_mut(&addr).unwrap().authenticated = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    send_challenge(addr, Arc::clone(&state)).await.unwrap();
+    let signing_key = SigningKey::random(&mut OsRng);
+    let signature: Signature = signing_key.sign(b"not the challenge");
+    let response = signal(SignalKind::ChallengeResponse, json!({
+        "public_key": signing_key.verifying_key().to_encoded_point(false).as_bytes(),
+        "signature": signature.to_bytes().to_vec(),
+    }));
+    handle_challenge_response(parsed(&response), addr, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "challenge-failed");
+    let state = state.lock().await;
+    assert!(!state.clients[&addr].authenticated);
+    assert!(state.clients[&addr].public_key.is_none());
+}
+
+#[tokio::test]
+async fn nothing_but_the_answer_is_accepted_until_the_challenge_is_answered() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    inner.clients.get_mut(&addr).unwrap().authenticated = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    assert!(!check_authenticated(SignalKind::Presence, addr, &state).await.unwrap());
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "unauthenticated");
+    assert!(check_authenticated(SignalKind::ChallengeResponse, addr, &state).await.unwrap());
+
+    answer_challenge(&SigningKey::random(&mut OsRng), addr, &mut rx, &state).await;
+    assert!(check_authenticated(SignalKind::Presence, addr, &state).await.unwrap());
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::chat::{self, ChatMessage};
+use video_conference_backend::models::message::ChatPayload;
+use video_conference_backend::models::{ChatPermission, Role, SignalMessage};
+use video_conference_backend::signaling::{chat as chat_signal, SharedState, SignalingState};
+use common::{add_member, received};
+
+fn chat(text: &str) -> ChatPayload {
+    ChatPayload { text: text.to_string() }
+}
+
+fn error_code(signal: &SignalMessage) -> String {
+    assert_eq!(signal.signal_type, "error");
+    let payload: serde_json::Value = serde_json::from_str(&signal.payload).unwrap();
+    payload["code"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn chat_text_is_trimmed_and_bounded() {
+    assert_eq!(chat::check_text("  hello\nthere \t", 20), Ok("hello\nthere"));
+    assert!(chat::check_text(" \n ", 20).is_err());
+    assert!(chat::check_text("ééééé", 4).is_err());
+    assert!(chat::check_text("ring\u{7}", 20).is_err());
+}
+
+#[tokio::test]
+async fn chat_reaches_everyone_in_the_room_and_no_one_else() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let (_, mut other_rx) = add_member(&mut inner, 3, "beta");
+    inner.clients.get_mut(&a).unwrap().profile.display_name = Some("Ada".to_string());
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    chat_signal::handle_chat(chat(" hi all "), a, Arc::clone(&state)).await.unwrap();
+
+    let echoed = received(&mut a_rx).unwrap();
+    let relayed = received(&mut b_rx).unwrap();
+    assert_eq!(relayed.signal_type, "chat");
+    assert_eq!(echoed.payload, relayed.payload);
+    let message: ChatMessage = serde_json::from_str(&relayed.payload).unwrap();
+    assert_eq!(message.text, "hi all");
+    assert_eq!(message.client_id, "client-1");
+    assert_eq!(message.display_name.as_deref(), Some("Ada"));
+    assert_eq!(message.room_id, "alpha");
+    assert!(other_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn rooms_decide_who_may_chat() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (viewer, mut viewer_rx) = add_member(&mut inner, 2, "alpha");
+    inner.clients.get_mut(&viewer).unwrap().role = Role::Viewer;
+    inner.rooms.get_mut("alpha").unwrap().config.chat = ChatPermission::Participants;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    chat_signal::handle_chat(chat("can you hear me?"), viewer, Arc::clone(&state)).await.unwrap();
+    assert_eq!(error_code(&received(&mut viewer_rx).unwrap()), "chat-not-allowed");
+    assert!(host_rx.try_recv().is_err());
+
+    chat_signal::handle_chat(chat("welcome"), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut viewer_rx).unwrap().signal_type, "chat");
+
+    state.lock().await.rooms.get_mut("alpha").unwrap().config.chat = ChatPermission::Disabled;
+    // The host's own copy of its message
+    assert_eq!(received(&mut host_rx).unwrap().signal_type, "chat");
+    chat_signal::handle_chat(chat("hello?"), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(error_code(&received(&mut host_rx).unwrap()), "chat-not-allowed");
+}
+
+#[tokio::test]
+async fn typing_is_coalesced_and_ends_with_the_message() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    chat_signal::handle_typing_start(a, Arc::clone(&state)).await.unwrap();
+    chat_signal::handle_typing_start(a, Arc::clone(&state)).await.unwrap();
+    let typing = received(&mut b_rx).unwrap();
+    assert_eq!(typing.signal_type, "typing-start");
+    let payload: serde_json::Value = serde_json::from_str(&typing.payload).unwrap();
+    assert_eq!(payload["client_id"], "client-1");
+    assert!(b_rx.try_recv().is_err());
+    assert!(a_rx.try_recv().is_err());
+
+    chat_signal::handle_typing_stop(a, Arc::clone(&state)).await.unwrap();
+    chat_signal::handle_typing_stop(a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut b_rx).unwrap().signal_type, "typing-stop");
+    assert!(b_rx.try_recv().is_err());
+
+    // Sending the message stands in for typing-stop
+    chat_signal::handle_typing_start(a, Arc::clone(&state)).await.unwrap();
+    chat_signal::handle_chat(chat("done"), a, Arc::clone(&state)).await.unwrap();
+    chat_signal::handle_typing_stop(a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut b_rx).unwrap().signal_type, "typing-start");
+    assert_eq!(received(&mut b_rx).unwrap().signal_type, "chat");
+    assert!(b_rx.try_recv().is_err());
+}
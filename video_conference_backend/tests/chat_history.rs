@@ -0,0 +1,122 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::chat::{ChatMessage, ChatStore, SqliteChatStore};
+use video_conference_backend::rooms;
+use video_conference_backend::models::message::{ChatHistoryPayload, ChatPayload, JoinRoomPayload};
+use video_conference_backend::signaling::{chat, handle_join_room, SharedState, SignalingState};
+use common::{add_client, payloads};
+
+fn message(room_id: &str, n: i64) -> ChatMessage {
+    ChatMessage {
+        message_id: format!("{}-{}", room_id, n),
+        room_id: room_id.to_string(),
+        client_id: "client-1".to_string(),
+        display_name: None,
+        text: format!("message {}", n),
+        sent_at: n,
+    }
+}
+
+fn ids(messages: &[ChatMessage]) -> Vec<&str> {
+    messages.iter().map(|message| message.message_id.as_str()).collect()
+}
+
+fn join() -> JoinRoomPayload {
+    JoinRoomPayload {
+        room_id: "alpha".to_string(),
+        max_participants: None,
+        password: None,
+        invite_token: None,
+        capabilities: None,
+    }
+}
+
+#[test]
+fn history_pages_back_and_retention_prunes() {
+    let store = SqliteChatStore::open_in_memory().unwrap();
+    for n in 1..=5 {
+        store.append(&message("alpha", n)).unwrap();
+        store.append(&message("beta", n)).unwrap();
+    }
+
+    assert_eq!(ids(&store.history("alpha", None, 2).unwrap()), ["alpha-4", "alpha-5"]);
+    assert_eq!(ids(&store.history("alpha", Some("alpha-4"), 2).unwrap()), ["alpha-2", "alpha-3"]);
+    assert_eq!(ids(&store.history("alpha", Some("alpha-1"), 2).unwrap()), Vec::<&str>::new());
+    // Another room's message is no cursor here
+    assert!(store.history("alpha", Some("beta-3"), 2).unwrap().is_empty());
+
+    // Older than 2 goes, then all but each room's newest 2
+    assert_eq!(store.prune(2, 2).unwrap(), 2 + 4);
+    assert_eq!(ids(&store.history("alpha", None, 10).unwrap()), ["alpha-4", "alpha-5"]);
+    assert_eq!(ids(&store.history("beta", None, 10).unwrap()), ["beta-4", "beta-5"]);
+}
+
+#[tokio::test]
+async fn late_joiners_catch_up_and_page_back() {
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_client(&mut inner, 1);
+    let (b, mut b_rx) = add_client(&mut inner, 2);
+    let store = Arc::new(SqliteChatStore::open_in_memory().unwrap());
+    for n in 1..=60 {
+        store.append(&message("alpha", n)).unwrap();
+    }
+    inner.chat = Some(store);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    chat::handle_chat(ChatPayload { text: "anyone here?".to_string() }, a, Arc::clone(&state)).await.unwrap();
+
+    handle_join_room(join(), b, Arc::clone(&state)).await.unwrap();
+    let history = payloads(&mut b_rx, "chat-history");
+    let messages = history[0]["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 50);
+    assert_eq!(messages[0]["message_id"], "alpha-12");
+    assert_eq!(messages[49]["text"], "anyone here?");
+    assert_eq!(history[0]["has_more"], true);
+
+    let before = Some("alpha-12".to_string());
+    chat::handle_chat_history(ChatHistoryPayload { before, limit: Some(20) }, b, Arc::clone(&state)).await.unwrap();
+    let page = payloads(&mut b_rx, "chat-history");
+    let messages = page[0]["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 11);
+    assert_eq!(messages[0]["message_id"], "alpha-1");
+    assert_eq!(page[0]["has_more"], false);
+}
+
+#[tokio::test]
+async fn history_needs_a_room_and_a_store() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    chat::handle_chat_history(ChatHistoryPayload::default(), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "not-in-room");
+
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut a_rx, "chat-history").is_empty());
+    chat::handle_chat_history(ChatHistoryPayload::default(), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "chat-history-unavailable");
+}
+
+#[tokio::test]
+async fn a_new_room_under_a_closed_rooms_id_starts_without_its_history() {
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_client(&mut inner, 1);
+    let (b, mut b_rx) = add_client(&mut inner, 2);
+    let store = Arc::new(SqliteChatStore::open_in_memory().unwrap());
+    store.append(&message("beta", 1)).unwrap();
+    inner.chat = Some(store.clone());
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    chat::handle_chat(ChatPayload { text: "secret plans".to_string() }, a, Arc::clone(&state)).await.unwrap();
+    rooms::close_room(&mut *state.lock().await, "alpha");
+    assert!(store.history("alpha", None, 10).unwrap().is_empty());
+
+    handle_join_room(join(), b, Arc::clone(&state)).await.unwrap();
+    let history = payloads(&mut b_rx, "chat-history");
+    assert!(history[0]["messages"].as_array().unwrap().is_empty());
+    assert_eq!(store.history("beta", None, 10).unwrap().len(), 1);
+}
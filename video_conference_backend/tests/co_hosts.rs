@@ -0,0 +1,41 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Role, SignalKind};
+use video_conference_backend::signaling::{moderation, SharedState, SignalingState};
+use common::{add_member, parsed, payloads, signal};
+
+#[tokio::test]
+async fn the_host_promotes_and_demotes_co_hosts() {
+    let mut inner = SignalingState::new();
+    let (host, _host_rx) = add_member(&mut inner, 1, "alpha");
+    let (member, mut member_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let target = json!({ "client_id": "client-2" });
+
+    moderation::handle_role_change(parsed(&signal(SignalKind::Promote, target.clone())), host, Arc::clone(&state), Role::Moderator).await.unwrap();
+    let update = payloads(&mut member_rx, "role-changed").remove(0);
+    assert_eq!((update["role"].as_str(), update["by"].as_str()), (Some("moderator"), Some("client-1")));
+    assert_eq!(state.lock().await.clients[&member].role, Role::Moderator);
+
+    moderation::handle_role_change(parsed(&signal(SignalKind::Demote, target)), host, Arc::clone(&state), Role::Participant).await.unwrap();
+    assert_eq!(payloads(&mut member_rx, "role-changed")[0]["role"], "participant");
+    assert_eq!(state.lock().await.clients[&member].role, Role::Participant);
+}
+
+#[tokio::test]
+async fn co_hosts_cannot_change_roles() {
+    let mut inner = SignalingState::new();
+    let (_host, _host_rx) = add_member(&mut inner, 1, "alpha");
+    let (co_host, mut co_host_rx) = add_member(&mut inner, 2, "alpha");
+    let (member, _member_rx) = add_member(&mut inner, 3, "alpha");
+    inner.clients.get_mut(&co_host).unwrap().role = Role::Moderator;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let promote = signal(SignalKind::Promote, json!({ "client_id": "client-3" }));
+    moderation::handle_role_change(parsed(&promote), co_host, Arc::clone(&state), Role::Moderator).await.unwrap();
+    assert_eq!(payloads(&mut co_host_rx, "error")[0]["code"], "not-host");
+    assert_eq!(state.lock().await.clients[&member].role, Role::Participant);
+}
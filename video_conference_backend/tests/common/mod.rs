@@ -0,0 +1,164 @@
+// Fixtures shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use uuid::Uuid;
+use video_conference_backend::{config, crypto};
+use video_conference_backend::models::message::{CallInvitePayload, CallPayload};
+use video_conference_backend::models::{Client, SignalKind, SignalMessage};
+use video_conference_backend::sessions;
+use video_conference_backend::signaling::handshake::SUBPROTOCOL;
+use video_conference_backend::signaling::{SignalingServer, SignalingState};
+use video_conference_backend::storage::SqliteRoomStore;
+
+// Adds a verified client on 127.0.0.1:`port`, named client-`port`, that has answered the challenge
+pub fn add_client(state: &mut SignalingState, port: u16) -> (SocketAddr, mpsc::Receiver<Message>) {
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+    let (tx, rx) = mpsc::channel(16);
+    let mut client = Client::new(tx, format!("client-{}", port), addr);
+    client.verified = true;
+    client.authenticated = true;
+    state.clients.insert(addr, client);
+    (addr, rx)
+}
+
+// Adds a verified client that has joined `room_id`
+pub fn add_member(state: &mut SignalingState, port: u16, room_id: &str) -> (SocketAddr, mpsc::Receiver<Message>) {
+    let (addr, rx) = add_client(state, port);
+    state.join_room(addr, room_id, 8).unwrap();
+    (addr, rx)
+}
+
+// Issues `addr` a session token to present with the signals it dispatches
+pub fn session_token(state: &mut SignalingState, addr: SocketAddr) -> String {
+    sessions::issue_session_token(state, addr, config::get_session_token_ttl()).unwrap().token
+}
+
+// Signs `addr` in as `user_id`, as a WebAuthn login would
+pub fn sign_in(state: &mut SignalingState, addr: SocketAddr, user_id: &str) {
+    state.clients.get_mut(&addr).unwrap().webauthn_user = Some(user_id.to_string());
+}
+
+// A direct call to the user `callee`
+pub fn invite(call_id: &str, callee: &str) -> CallInvitePayload {
+    CallInvitePayload { call_id: call_id.to_string(), callee: callee.to_string(), room_id: None }
+}
+
+pub fn call(call_id: &str) -> CallPayload {
+    CallPayload { call_id: call_id.to_string(), reason: None }
+}
+
+// A signal as a client would send it, under a fresh message id and numbered after every signal
+// built before it
+pub fn signal(kind: SignalKind, payload: serde_json::Value) -> SignalMessage {
+    static SEQ: AtomicU64 = AtomicU64::new(1);
+    let mut message = SignalMessage::server(kind.name(), payload);
+    message.message_id = Some(Uuid::new_v4().to_string());
+    message.seq = Some(SEQ.fetch_add(1, Ordering::Relaxed));
+    message
+}
+
+// The next signal waiting in `rx`, if any
+pub fn received(rx: &mut mpsc::Receiver<Message>) -> Option<SignalMessage> {
+    match rx.try_recv().ok()? {
+        Message::Text(text) => serde_json::from_str(&text).ok(),
+        _ => None,
+    }
+}
+
+// The type and payload of the next signal waiting in `rx`. A payload that is not JSON reads as null.
+pub fn received_payload(rx: &mut mpsc::Receiver<Message>) -> Option<(String, serde_json::Value)> {
+    let signal = received(rx)?;
+    Some((signal.signal_type, serde_json::from_str(&signal.payload).unwrap_or_default()))
+}
+
+// The type and payload of every signal waiting in `rx`
+pub fn drain(rx: &mut mpsc::Receiver<Message>) -> Vec<(String, serde_json::Value)> {
+    std::iter::from_fn(|| received_payload(rx)).collect()
+}
+
+// Payloads of every message of `signal_type` waiting in `rx`
+pub fn payloads(rx: &mut mpsc::Receiver<Message>, signal_type: &str) -> Vec<serde_json::Value> {
+    drain(rx)
+        .into_iter()
+        .filter(|(kind, _)| kind == signal_type)
+        .map(|(_, payload)| payload)
+        .collect()
+}
+
+// A signal's payload parsed into the type its handler takes, as dispatch hands it over
+pub fn parsed<T: DeserializeOwned>(signal: &SignalMessage) -> T {
+    serde_json::from_str(&signal.payload).unwrap()
+}
+
+// A secure-offer or secure-answer for `offer`, timestamped now and signed as version 2 over its
+// envelope. `sign` receives the canonical bytes the server verifies.
+pub fn secure_offer(
+    offer: serde_json::Value,
+    public_key: &[u8],
+    sign: impl FnOnce(&[u8]) -> Vec<u8>
+) -> SignalMessage {
+    let kind = if offer["type"] == "answer" { SignalKind::SecureAnswer } else { SignalKind::SecureOffer };
+    let mut message = signal(kind, unsigned_offer(offer, public_key));
+    sign_message(&mut message, sign);
+    message
+}
+
+// The fields of a secure-offer for `offer`, timestamped now, without the signature
+pub fn unsigned_offer(offer: serde_json::Value, public_key: &[u8]) -> serde_json::Value {
+    json!({
+        "offer": offer,
+        "timestamp": Utc::now().timestamp(),
+        "public_key": public_key,
+        "nonce": rand::random::<[u8; 16]>(),
+        "signature_version": 2,
+    })
+}
+
+// Signs `message` over its whole envelope, as its payload's signature_version says, replacing
+// any signature it carried
+pub fn sign_message(message: &mut SignalMessage, sign: impl FnOnce(&[u8]) -> Vec<u8>) {
+    let mut fields: serde_json::Value = serde_json::from_str(&message.payload).unwrap();
+    fields.as_object_mut().unwrap().remove("signature");
+    message.payload = fields.to_string();
+    let version = fields["signature_version"].as_u64().unwrap_or(2) as u32;
+    let signed = crypto::signed_envelope(message, version, None).unwrap();
+    fields["signature"] = json!(sign(&signed));
+    message.payload = fields.to_string();
+}
+
+// A WebSocket upgrade request for a server at `addr`, offering the signaling subprotocol
+pub fn upgrade_request(addr: SocketAddr) -> Request {
+    let mut request = format!("ws://{}", addr).into_client_request().unwrap();
+    request.headers_mut().insert("Sec-WebSocket-Protocol", SUBPROTOCOL.parse().unwrap());
+    request
+}
+
+// Starts a server with an in-memory room store on a free port and waits until it accepts
+// connections
+pub async fn start_server() -> SocketAddr {
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let server = SignalingServer::builder()
+        .addr(addr)
+        .room_store(Arc::new(SqliteRoomStore::open_in_memory().unwrap()))
+        .build();
+    tokio::spawn(async move { server.run().await.map_err(|e| e.to_string()) });
+
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            return addr;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("signaling server did not start");
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Error;
+use video_conference_backend::firewall::{ConnectionLimit, ConnectionTracker};
+use common::{start_server, upgrade_request};
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn permits_count_against_both_limits_until_dropped() {
+    let tracker = Arc::new(ConnectionTracker::new(2, 3));
+    let first = tracker.try_acquire(ip("192.0.2.1")).unwrap();
+    let _second = tracker.try_acquire(ip("192.0.2.1")).unwrap();
+    assert_eq!(tracker.try_acquire(ip("192.0.2.1")).err(), Some(ConnectionLimit::PerIp));
+
+    let _third = tracker.try_acquire(ip("192.0.2.2")).unwrap();
+    assert_eq!(tracker.try_acquire(ip("192.0.2.3")).err(), Some(ConnectionLimit::Global));
+    assert_eq!(tracker.active(), 3);
+
+    drop(first);
+    assert_eq!(tracker.active(), 2);
+    assert!(tracker.try_acquire(ip("192.0.2.1")).is_ok());
+
+    let unlimited = Arc::new(ConnectionTracker::new(0, 0));
+    let permits: Vec<_> = (0..100).map(|_| unlimited.try_acquire(ip("192.0.2.1")).unwrap()).collect();
+    assert_eq!(unlimited.active(), permits.len());
+}
+
+#[tokio::test]
+async fn connections_over_the_address_limit_get_503_with_retry_after() {
+    std::env::set_var("MAX_CONNECTIONS_PER_IP", "1");
+    std::env::set_var("CONNECTION_RETRY_AFTER_SECS", "7");
+    let addr = start_server().await;
+
+    // The startup probe holds the address's only slot until the server notices it closed
+    let mut open = None;
+    for _ in 0..50 {
+        if let Ok((socket, _)) = connect_async(upgrade_request(addr)).await {
+            open = Some(socket);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(open.is_some(), "no connection was upgraded");
+
+    let Err(Error::Http(response)) = connect_async(upgrade_request(addr)).await else {
+        panic!("second connection was upgraded");
+    };
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["Retry-After"], "7");
+}
+
+#[tokio::test]
+async fn connections_that_never_finish_the_handshake_give_up_their_slot() {
+    std::env::set_var("MAX_CONNECTIONS_PER_IP", "1");
+    std::env::set_var("HANDSHAKE_TIMEOUT_MS", "200");
+    let addr = start_server().await;
+    // Lets the startup probe's slot go
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let _idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(connect_async(upgrade_request(addr)).await.is_err());
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(connect_async(upgrade_request(addr)).await.is_ok());
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::roster::handle_raise_hand;
+use video_conference_backend::signaling::{correlation, relay_sealed, SharedState, SignalingState};
+use common::{add_member, drain, received, signal};
+
+#[tokio::test]
+async fn replies_to_the_sender_echo_its_correlation_id() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut other_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut sender_rx);
+    drain(&mut other_rx);
+
+    correlation::scope(sender, Some("req-3".to_string()), handle_raise_hand(sender, Arc::clone(&state))).await.unwrap();
+    assert_eq!(received(&mut sender_rx).unwrap().correlation_id.as_deref(), Some("req-3"));
+    // The rest of the room gets the same notice without it
+    assert_eq!(received(&mut other_rx).unwrap().correlation_id, None);
+}
+
+#[tokio::test]
+async fn relayed_messages_keep_the_senders_own_fields() {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (target, mut target_rx) = add_member(&mut inner, 2, "alpha");
+    for (addr, client) in inner.clients.iter_mut() {
+        client.public_key = Some(vec![addr.port() as u8; 65]);
+    }
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut target_rx);
+
+    let mut sealed = signal(SignalKind::Sealed, json!("opaque"));
+    sealed.sender_id = "client-1".to_string();
+    sealed.target_id = Some("client-2".to_string());
+    // Even while handling a message from the target, a relay is not a reply to it
+    correlation::scope(target, Some("req-4".to_string()), relay_sealed(&sealed, sender, Arc::clone(&state))).await.unwrap();
+    assert_eq!(received(&mut target_rx).unwrap().correlation_id, None);
+}
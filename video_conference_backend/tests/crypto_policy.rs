@@ -0,0 +1,82 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::crypto::{CryptoPolicy, HashFunction};
+use video_conference_backend::crypto::SignatureAlgorithm;
+use video_conference_backend::signaling::{handle_secure_offer, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, secure_offer};
+
+fn p256_key(compress: bool) -> Vec<u8> {
+    SigningKey::random(&mut OsRng).verifying_key().to_encoded_point(compress).as_bytes().to_vec()
+}
+
+#[test]
+fn the_default_policy_accepts_every_supported_key() {
+    let policy = CryptoPolicy::default();
+    assert!(policy.check_signature(SignatureAlgorithm::EcdsaP256, &p256_key(false)).is_ok());
+    assert!(policy.check_signature(SignatureAlgorithm::EcdsaP256, &p256_key(true)).is_ok());
+    assert!(policy.check_signature(SignatureAlgorithm::Ed25519, &[1; 32]).is_ok());
+    assert_eq!(policy.chain_algorithms().len(), 11);
+}
+
+#[test]
+fn restricted_policies_report_what_they_refused() {
+    let policy = CryptoPolicy {
+        algorithms: vec![SignatureAlgorithm::EcdsaP256],
+        hashes: vec![HashFunction::Sha384],
+        ..CryptoPolicy::default()
+    };
+    let error = policy.check_signature(SignatureAlgorithm::Ed25519, &[1; 32]).unwrap_err();
+    assert_eq!(error.code(), "SIG_POLICY_VIOLATION");
+    assert_eq!(error.context["allowed_algorithms"], json!(["ecdsa-p256"]));
+
+    // ECDSA client signatures always hash with SHA-256
+    let error = policy.check_signature(SignatureAlgorithm::EcdsaP256, &p256_key(false)).unwrap_err();
+    assert_eq!(error.context["hash"], "sha-256");
+    // Ed25519 chain signatures have no separate hash, so they stay allowed
+    assert_eq!(policy.chain_algorithms().len(), 5);
+
+    let uncompressed_only = CryptoPolicy { allow_compressed_points: false, ..CryptoPolicy::default() };
+    assert!(uncompressed_only.check_signature(SignatureAlgorithm::EcdsaP256, &p256_key(false)).is_ok());
+    let error = uncompressed_only.check_signature(SignatureAlgorithm::EcdsaP256, &p256_key(true)).unwrap_err();
+    assert_eq!(error.context["actual"], 33);
+}
+
+#[test]
+fn unknown_policy_entries_narrow_the_policy() {
+    std::env::set_var("CRYPTO_ALGORITHMS", "ed25519,rsa-1024");
+    std::env::set_var("CRYPTO_HASHES", "sha-512,md5");
+    let policy = CryptoPolicy::from_config();
+    std::env::remove_var("CRYPTO_ALGORITHMS");
+    std::env::remove_var("CRYPTO_HASHES");
+
+    assert_eq!(policy.algorithms, [SignatureAlgorithm::Ed25519]);
+    assert_eq!(policy.hashes, [HashFunction::Sha512]);
+}
+
+#[tokio::test]
+async fn offers_signed_outside_the_servers_policy_are_rejected() {
+    let mut inner = SignalingState::new();
+    inner.crypto_policy = Arc::new(CryptoPolicy { algorithms: vec![SignatureAlgorithm::Ed25519], ..CryptoPolicy::default() });
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let message = secure_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes(), |message| {
+        let signature: Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    });
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "SIG_POLICY_VIOLATION");
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::models::message::JoinRoomPayload;
+use video_conference_backend::signaling::{calls, handle_join_room, send_to_target, SharedState, SignalingState};
+use common::{add_client, call, invite, payloads, sign_in, signal};
+
+#[tokio::test]
+async fn an_accepted_direct_call_pairs_both_ends_until_one_hangs_up() {
+    let mut inner = SignalingState::new();
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (callee, mut callee_rx) = add_client(&mut inner, 2);
+    let (stranger, _stranger_rx) = add_client(&mut inner, 3);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, callee, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-1"), true, callee, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "call-accept")[0]["direct"], true);
+
+    let offer = signal(SignalKind::SecureOffer, json!({ "sdp": "v=0" }));
+    send_to_target(&offer, "client-2", caller, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut callee_rx, "secure-offer").len(), 1);
+    send_to_target(&offer, "client-1", stranger, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut caller_rx, "secure-offer").is_empty());
+
+    calls::handle_call_hangup(call("call-1"), callee, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "call-ended")[0]["reason"], "hangup");
+    send_to_target(&offer, "client-2", caller, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "error")[0]["code"], "target-unknown");
+}
+
+#[tokio::test]
+async fn joining_a_room_ends_a_direct_call_and_room_members_cannot_take_one() {
+    let mut inner = SignalingState::new();
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (callee, mut callee_rx) = add_client(&mut inner, 2);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, callee, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-1"), true, callee, Arc::clone(&state)).await.unwrap();
+    let join = JoinRoomPayload {
+        room_id: "alpha".to_string(),
+        max_participants: None,
+        password: None,
+        invite_token: None,
+        capabilities: None,
+    };
+    handle_join_room(join, callee, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "call-ended")[0]["reason"], "joined-room");
+
+    calls::handle_call_invite(invite("call-2", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-2"), true, callee, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut callee_rx, "error")[0]["code"], "in-room");
+    assert!(payloads(&mut caller_rx, "call-accept").is_empty());
+}
+
+#[tokio::test]
+async fn a_caller_is_paired_with_only_the_first_direct_invite_answered() {
+    let mut inner = SignalingState::new();
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (bob, _bob_rx) = add_client(&mut inner, 2);
+    let (carol, mut carol_rx) = add_client(&mut inner, 3);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, bob, "bob");
+    sign_in(&mut inner, carol, "carol");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_invite(invite("call-2", "carol"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-1"), true, bob, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-2"), true, carol, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(payloads(&mut carol_rx, "error")[0]["code"], "caller-busy");
+    let accepted = payloads(&mut caller_rx, "call-accept");
+    assert_eq!(accepted.len(), 1);
+    assert_eq!(accepted[0]["call_id"], "call-1");
+    let inner = state.lock().await;
+    assert_eq!(inner.calls.direct_peer(caller), Some(bob));
+    assert_eq!(inner.calls.direct_peer(carol), None);
+}
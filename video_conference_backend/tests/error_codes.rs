@@ -0,0 +1,86 @@
+mod common;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::correlation;
+use video_conference_backend::signaling::errors::{self, numeric_code};
+use video_conference_backend::signaling::{dispatch_signal, SharedState, SignalingState};
+use common::{add_client, payloads, session_token, signal, start_server, upgrade_request};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// The payload of the next error the server sends
+async fn next_error(ws: &mut Socket) -> serde_json::Value {
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), ws.next()).await.expect("an error arrived") {
+            Some(Ok(Message::Text(text))) => {
+                let signal: SignalMessage = serde_json::from_str(&text).unwrap();
+                if signal.signal_type == "error" {
+                    return serde_json::from_str(&signal.payload).unwrap();
+                }
+            }
+            Some(Ok(_)) => {}
+            other => panic!("connection ended: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn codes_map_to_stable_numbers() {
+    assert_eq!(numeric_code("invalid-payload"), 1001);
+    assert_eq!(numeric_code("session-token-invalid"), 2005);
+    assert_eq!(numeric_code("SIG_VERIFY_FAILED"), 3106);
+    assert_eq!(numeric_code("not-in-room"), 4000);
+    assert_eq!(numeric_code("no-such-code"), 9000);
+
+    let error = errors::error_signal("banned", "Go away", Some("client-2"), json!({ "retry_after_secs": 5 }));
+    let body: serde_json::Value = serde_json::from_str(&error.payload).unwrap();
+    assert_eq!(body, json!({
+        "code": "banned",
+        "numeric_code": 2013,
+        "message": "Go away",
+        "target_id": "client-2",
+        "correlation_id": null,
+        "retry_after_secs": 5,
+    }));
+}
+
+#[tokio::test]
+async fn errors_point_back_to_the_message_that_caused_them() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let token = session_token(&mut inner, addr);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let mut client_id = "client-1".to_string();
+
+    let unknown = signal(SignalKind::Unknown, json!({}));
+    let dispatch = dispatch_signal(&unknown, Some(&token), addr, &mut client_id, Arc::clone(&state));
+    correlation::scope(addr, Some("req-7".to_string()), dispatch).await.unwrap();
+
+    let error = payloads(&mut rx, "error").remove(0);
+    assert_eq!((error["code"].as_str(), error["numeric_code"].as_u64()), (Some("unknown-signal"), Some(1002)));
+    assert_eq!(error["correlation_id"], "req-7");
+}
+
+#[tokio::test]
+async fn frames_that_are_not_signals_get_a_malformed_message_error() {
+    let addr = start_server().await;
+    let (mut ws, _) = connect_async(upgrade_request(addr)).await.unwrap();
+
+    ws.send(Message::Text(json!({ "correlation_id": "req-1", "payload": 5 }).to_string())).await.unwrap();
+    let error = next_error(&mut ws).await;
+    assert_eq!((error["code"].as_str(), error["numeric_code"].as_u64()), (Some("malformed-message"), Some(1000)));
+    assert_eq!(error["correlation_id"], "req-1");
+
+    ws.send(Message::Text("not json".to_string())).await.unwrap();
+    let error = next_error(&mut ws).await;
+    assert_eq!(error["code"], "malformed-message");
+    assert!(error["correlation_id"].is_null());
+}
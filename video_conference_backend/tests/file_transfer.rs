@@ -0,0 +1,113 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::message::{FileOfferPayload, FileResponsePayload};
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::rooms::{FilePolicy, FileRejection};
+use video_conference_backend::signaling::{files, SharedState, SignalingState};
+use common::{add_member, received_payload};
+
+const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+fn offer(name: &str, size: u64, mime_type: &str) -> FileOfferPayload {
+    FileOfferPayload {
+        transfer_id: "t-1".to_string(),
+        name: name.to_string(),
+        size,
+        mime_type: mime_type.to_string(),
+        sha256: SHA256.to_string(),
+        label: "file-t-1".to_string(),
+    }
+}
+
+fn signal(kind: SignalKind, sender: &str, target: &str, payload: serde_json::Value) -> SignalMessage {
+    SignalMessage {
+        signal_type: kind.name().to_string(),
+        payload: payload.to_string(),
+        sender_id: sender.to_string(),
+        timestamp: 0,
+        signature: None,
+        target_id: Some(target.to_string()),
+        seq: None,
+        relay_seq: None,
+        server_signature: None,
+        session_token: None,
+        correlation_id: None,
+        message_id: None,
+    }
+}
+
+fn offer_signal(payload: &FileOfferPayload) -> SignalMessage {
+    signal(SignalKind::FileOffer, "client-1", "client-2", serde_json::to_value(payload).unwrap())
+}
+
+fn accept() -> (SignalMessage, FileResponsePayload) {
+    let payload = FileResponsePayload { transfer_id: "t-1".to_string(), reason: None };
+    (signal(SignalKind::FileAccept, "client-2", "client-1", serde_json::to_value(&payload).unwrap()), payload)
+}
+
+#[test]
+fn file_policy_checks_size_and_type() {
+    let policy = FilePolicy { max_size: 1000, allowed_types: vec!["image/*".to_string(), "application/pdf".to_string()] };
+    assert_eq!(policy.check(1000, "image/PNG"), Ok(()));
+    assert_eq!(policy.check(10, "application/pdf; version=1.7"), Ok(()));
+    assert_eq!(policy.check(1001, "image/png"), Err(FileRejection::TooLarge));
+    assert_eq!(policy.check(10, "imagex/png"), Err(FileRejection::TypeNotAllowed));
+    assert_eq!(policy.check(10, "application/zip"), Err(FileRejection::TypeNotAllowed));
+
+    let disabled = FilePolicy { max_size: 0, allowed_types: Vec::new() };
+    assert_eq!(disabled.check(0, "text/plain"), Err(FileRejection::Disabled));
+}
+
+#[tokio::test]
+async fn offers_reach_the_recipient_and_answers_the_offerer() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (b, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let (c, mut c_rx) = add_member(&mut inner, 3, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let payload = offer("notes.txt", 42, "text/plain");
+    files::handle_file_offer(&offer_signal(&payload), payload, a, Arc::clone(&state)).await.unwrap();
+    let (signal_type, relayed) = received_payload(&mut b_rx).unwrap();
+    assert_eq!(signal_type, "file-offer");
+    assert_eq!(relayed["sha256"], SHA256);
+    assert_eq!(relayed["label"], "file-t-1");
+
+    // Only the recipient can answer
+    let (answer, response) = accept();
+    files::handle_file_response(&answer, response, c, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received_payload(&mut c_rx).unwrap().1["code"], "unknown-transfer");
+    assert!(a_rx.try_recv().is_err());
+
+    let (answer, response) = accept();
+    files::handle_file_response(&answer, response, b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received_payload(&mut a_rx).unwrap().0, "file-accept");
+
+    // The offer is closed once answered
+    let (answer, response) = accept();
+    files::handle_file_response(&answer, response, b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received_payload(&mut b_rx).unwrap().1["code"], "unknown-transfer");
+}
+
+#[tokio::test]
+async fn rooms_enforce_their_file_policy() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let config = &mut inner.rooms.get_mut("alpha").unwrap().config;
+    config.max_file_size = Some(1024);
+    config.allowed_file_types = Some(vec!["image/*".to_string()]);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for (payload, code) in [
+        (offer("big.png", 4096, "image/png"), "file-too-large"),
+        (offer("run.exe", 10, "application/x-msdownload"), "file-type-not-allowed"),
+        (offer("../../.bashrc", 10, "image/png"), "invalid-file-offer"),
+    ] {
+        files::handle_file_offer(&offer_signal(&payload), payload, a, Arc::clone(&state)).await.unwrap();
+        assert_eq!(received_payload(&mut a_rx).unwrap().1["code"], code);
+    }
+    assert!(b_rx.try_recv().is_err());
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use chrono::Utc;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use video_conference_backend::crypto::{check_freshness, FreshnessError};
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::{handle_secure_offer, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, sign_message, signal, unsigned_offer};
+
+// A secure-offer signed at `timestamp`
+fn offer_signed_at(timestamp: i64) -> SignalMessage {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let mut payload = unsigned_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes());
+    payload["timestamp"] = json!(timestamp);
+    let mut message = signal(SignalKind::SecureOffer, payload);
+    sign_message(&mut message, |message| {
+        let signature: Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    });
+    message
+}
+
+#[test]
+fn timestamps_must_fall_inside_the_window_widened_by_skew() {
+    let max_age = Duration::from_secs(60);
+    let skew = Duration::from_secs(5);
+    let now = 1_700_000_000;
+
+    assert_eq!(check_freshness(None, now, max_age, skew), Err(FreshnessError::Missing));
+    assert_eq!(check_freshness(Some(now), now, max_age, skew), Ok(()));
+    assert_eq!(check_freshness(Some(now - 65), now, max_age, skew), Ok(()));
+    assert_eq!(check_freshness(Some(now - 66), now, max_age, skew), Err(FreshnessError::Stale));
+    assert_eq!(check_freshness(Some(now + 5), now, max_age, skew), Ok(()));
+    assert_eq!(check_freshness(Some(now + 6), now, max_age, skew), Err(FreshnessError::InFuture));
+}
+
+#[test]
+fn timestamps_at_the_ends_of_the_clock_are_refused_without_overflowing() {
+    let now = 1_700_000_000;
+
+    assert_eq!(check_freshness(Some(i64::MIN), now, Duration::from_secs(60), Duration::from_secs(5)), Err(FreshnessError::Stale));
+    assert_eq!(check_freshness(Some(i64::MAX), now, Duration::MAX, Duration::from_secs(5)), Err(FreshnessError::InFuture));
+}
+
+#[tokio::test]
+async fn stale_or_altered_timestamps_are_refused() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let now = Utc::now().timestamp();
+    let message = offer_signed_at(now);
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut guest_rx, "error").is_empty());
+    assert_eq!(payloads(&mut host_rx, "secure-offer").len(), 1);
+
+    let stale = offer_signed_at(now - 3600);
+    // Moving the timestamp forward breaks the signature that covers it
+    let mut altered = stale.clone();
+    let mut payload: serde_json::Value = parsed(&altered);
+    payload["timestamp"] = json!(now);
+    altered.payload = payload.to_string();
+    for (message, code) in [(stale, "stale-timestamp"), (altered, "SIG_VERIFY_FAILED")] {
+        handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], code);
+    }
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
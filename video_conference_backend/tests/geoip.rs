@@ -0,0 +1,73 @@
+mod common;
+
+use serde_json::json;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::firewall::GeoPolicy;
+use video_conference_backend::signaling::{admin, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+// 192.0.2.0/24 is in DE and 198.51.100.0/24 in US; nothing else has a country
+fn policy(allow: &[&str], deny: &[&str], allow_unknown: bool) -> GeoPolicy {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/geoip-country.mmdb");
+    let codes = |codes: &[&str]| codes.iter().map(|code| code.to_string()).collect();
+    GeoPolicy::open(&path, codes(allow), codes(deny), allow_unknown).unwrap()
+}
+
+fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+#[test]
+fn addresses_resolve_to_their_country() {
+    let policy = policy(&[], &[], true);
+    assert_eq!(policy.country(&ip("192.0.2.10")).as_deref(), Some("DE"));
+    assert_eq!(policy.country(&ip("::ffff:198.51.100.7")).as_deref(), Some("US"));
+    assert_eq!(policy.country(&ip("203.0.113.1")), None);
+}
+
+#[test]
+fn denied_countries_lose_to_the_allowlist() {
+    let policy = policy(&["de", "us"], &["US"], false);
+    assert!(policy.permits(Some("DE")));
+    assert!(!policy.permits(Some("US")));
+    assert!(!policy.permits(Some("FR")));
+    assert!(!policy.permits(None));
+}
+
+#[test]
+fn regions_count_refusals_and_open_connections() {
+    let policy = Arc::new(policy(&[], &["US"], true));
+    let german = policy.admit(&ip("192.0.2.10")).unwrap();
+    let unknown = policy.admit(&ip("203.0.113.1")).unwrap();
+    assert_eq!(unknown.region, "unknown");
+    assert_eq!(policy.admit(&ip("198.51.100.7")).err().as_deref(), Some("US"));
+
+    drop(german);
+    let stats = policy.stats();
+    assert_eq!((stats["DE"].accepted, stats["DE"].active), (1, 0));
+    assert_eq!((stats["unknown"].accepted, stats["unknown"].active), (1, 1));
+    assert_eq!((stats["US"].accepted, stats["US"].refused), (0, 1));
+}
+
+#[tokio::test]
+async fn region_stats_are_for_admins() {
+    std::env::set_var("ADMIN_TOKEN", "geoip-admin");
+    let geo = Arc::new(policy(&[], &[], true));
+    let _guard = geo.admit(&ip("192.0.2.10")).unwrap();
+    let mut inner = SignalingState::new();
+    inner.geo = Some(geo);
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let stats = |admin_token: &str| signal(SignalKind::RegionStats, json!({ "admin_token": admin_token }));
+    admin::handle_region_stats(parsed(&stats("wrong")), addr, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "not-admin");
+
+    admin::handle_region_stats(parsed(&stats("geoip-admin")), addr, Arc::clone(&state)).await.unwrap();
+    let regions = payloads(&mut rx, "region-stats").remove(0)["regions"].clone();
+    assert_eq!(regions["DE"], json!({ "accepted": 1, "refused": 0, "active": 1 }));
+}
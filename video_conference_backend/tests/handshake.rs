@@ -0,0 +1,78 @@
+mod common;
+
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Error;
+use video_conference_backend::noise;
+use video_conference_backend::signaling::handshake::{negotiate, Subprotocol, SUBPROTOCOL};
+use common::{start_server, upgrade_request};
+
+// An upgrade request with a valid key and version plus the given extra headers
+fn request(headers: &[(&str, &str)]) -> Request {
+    let mut builder = Request::builder()
+        .uri("/")
+        .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+        .header("Sec-WebSocket-Version", "13");
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+    builder.body(()).unwrap()
+}
+
+fn rejection(request: &Request) -> String {
+    let response = negotiate(request, true, true).unwrap_err();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    response.body().clone().unwrap()
+}
+
+#[test]
+fn noise_is_preferred_over_v1_when_available() {
+    let both = request(&[("Sec-WebSocket-Protocol", &format!("{}, {}", SUBPROTOCOL, noise::SUBPROTOCOL))]);
+    assert_eq!(negotiate(&both, true, true).unwrap(), Some(Subprotocol::Noise));
+    assert_eq!(negotiate(&both, false, true).unwrap(), Some(Subprotocol::V1));
+    assert_eq!(Subprotocol::V1.name(), "peer-conference.v1");
+
+    // Without the requirement, clients that name no subprotocol still get in
+    assert_eq!(negotiate(&request(&[]), false, false).unwrap(), None);
+}
+
+#[test]
+fn malformed_websocket_headers_are_bad_requests() {
+    assert!(rejection(&request(&[])).contains("peer-conference.v1"));
+    assert!(rejection(&request(&[("Sec-WebSocket-Protocol", "chat")])).contains("peer-conference.v1"));
+    assert!(rejection(&request(&[("Sec-WebSocket-Protocol", "peer-conference.v1, ")])).contains("tokens"));
+    assert!(rejection(&request(&[("Sec-WebSocket-Protocol", "peer-conference.v1, peer-conference.v1")])).contains("repeat"));
+    assert!(rejection(&request(&[("Sec-WebSocket-Version", "13")])).contains("sent once"));
+
+    let short_key = Request::builder()
+        .header("Sec-WebSocket-Key", "c2hvcnQ=")
+        .header("Sec-WebSocket-Version", "13")
+        .header("Sec-WebSocket-Protocol", SUBPROTOCOL)
+        .body(())
+        .unwrap();
+    assert!(rejection(&short_key).contains("16 base64-encoded bytes"));
+
+    let old_version = Request::builder()
+        .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+        .header("Sec-WebSocket-Version", "8")
+        .header("Sec-WebSocket-Protocol", SUBPROTOCOL)
+        .body(())
+        .unwrap();
+    assert!(rejection(&old_version).contains("version 13"));
+}
+
+#[tokio::test]
+async fn the_server_answers_with_the_negotiated_subprotocol() {
+    let addr = start_server().await;
+
+    let (_, response) = connect_async(upgrade_request(addr)).await.unwrap();
+    assert_eq!(response.headers()["Sec-WebSocket-Protocol"], SUBPROTOCOL);
+
+    let bare = format!("ws://{}", addr).into_client_request().unwrap();
+    let Err(Error::Http(response)) = connect_async(bare).await else {
+        panic!("upgrade without a subprotocol was accepted");
+    };
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
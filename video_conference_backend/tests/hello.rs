@@ -0,0 +1,52 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::protocol::{self, negotiate_version, PROTOCOL_VERSIONS};
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_client, parsed, payloads, received_payload, signal};
+
+#[test]
+fn the_newest_shared_version_wins() {
+    assert_eq!(PROTOCOL_VERSIONS, [1]);
+    assert_eq!(negotiate_version(&[3, 2, 1]), Some(1));
+    assert_eq!(negotiate_version(&[2]), None);
+    assert_eq!(negotiate_version(&[]), None);
+}
+
+#[tokio::test]
+async fn hello_settles_the_version_and_shared_features() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let hello = signal(SignalKind::Hello, json!({ "versions": [2, 1], "features": ["sealed-relay", "telepathy"] }));
+    protocol::handle_hello(parsed(&hello), addr, Arc::clone(&state)).await.unwrap();
+
+    let reply = payloads(&mut rx, "hello").remove(0);
+    assert_eq!(reply["version"], 1);
+    assert_eq!(reply["features"], json!(["sealed-relay"]));
+    assert_eq!(reply["client_id"], "client-1");
+    assert!(reply["server_features"].as_array().unwrap().contains(&json!("session-tokens")));
+    let client = &state.lock().await.clients[&addr];
+    assert_eq!((client.protocol_version, client.features.clone()), (1, vec!["sealed-relay".to_string()]));
+}
+
+#[tokio::test]
+async fn clients_with_no_common_version_are_turned_away() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let hello = signal(SignalKind::Hello, json!({ "versions": [7, 8] }));
+    protocol::handle_hello(parsed(&hello), addr, Arc::clone(&state)).await.unwrap();
+
+    let (signal_type, refusal) = received_payload(&mut rx).unwrap();
+    assert_eq!(signal_type, "hello-rejected");
+    assert_eq!(refusal["code"], "unsupported-version");
+    assert_eq!(refusal["supported"], json!([1]));
+    assert!(matches!(rx.try_recv(), Ok(Message::Close(_))));
+}
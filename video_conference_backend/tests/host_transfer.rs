@@ -0,0 +1,35 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::Role;
+use video_conference_backend::signaling::{handle_leave_room, SharedState, SignalingState};
+use common::{add_client, add_member, payloads};
+
+// HOST_TRANSFER is read on every transfer, so both policies are exercised in one test
+#[tokio::test]
+async fn a_departing_host_hands_over_to_a_co_host_or_leaves_the_slot_open() {
+    std::env::remove_var("HOST_TRANSFER");
+    let mut inner = SignalingState::new();
+    let (host, _host_rx) = add_member(&mut inner, 1, "alpha");
+    let (_participant, mut participant_rx) = add_member(&mut inner, 2, "alpha");
+    let (co_host, _co_host_rx) = add_member(&mut inner, 3, "alpha");
+    inner.clients.get_mut(&co_host).unwrap().role = Role::Moderator;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_leave_room(host, Arc::clone(&state)).await.unwrap();
+    let changed = payloads(&mut participant_rx, "host-changed").remove(0);
+    assert_eq!((changed["client_id"].as_str(), changed["previous_host_id"].as_str()), (Some("client-3"), Some("client-1")));
+    assert!(state.lock().await.rooms["alpha"].is_host(&co_host));
+    assert_eq!(state.lock().await.clients[&co_host].role, Role::Host);
+
+    std::env::set_var("HOST_TRANSFER", "none");
+    handle_leave_room(co_host, Arc::clone(&state)).await.unwrap();
+    std::env::remove_var("HOST_TRANSFER");
+    assert!(payloads(&mut participant_rx, "host-changed").is_empty());
+    assert!(state.lock().await.rooms["alpha"].host.is_none());
+
+    let (newcomer, _newcomer_rx) = add_client(&mut *state.lock().await, 4);
+    state.lock().await.join_room(newcomer, "alpha", 8).unwrap();
+    assert_eq!(state.lock().await.clients[&newcomer].role, Role::Host);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::{batching, broadcast_to_verified_peers, SharedState, SignalingState};
+use common::{add_member, received, signal};
+
+// Adds a member of "alpha" that announced `features` when it connected
+fn add_member_with(state: &mut SignalingState, port: u16, features: &[&str]) -> (SocketAddr, mpsc::Receiver<Message>) {
+    let (addr, rx) = add_member(state, port, "alpha");
+    state.clients.get_mut(&addr).unwrap().features = features.iter().map(|feature| feature.to_string()).collect();
+    (addr, rx)
+}
+
+fn from_sender(kind: SignalKind, index: u32) -> SignalMessage {
+    let line = format!("candidate:{} 1 udp 1 192.0.2.1 5000 typ host", index);
+    SignalMessage { sender_id: "client-1".to_string(), ..signal(kind, json!({ "candidate": line })) }
+}
+
+fn batched(signal: &SignalMessage) -> Vec<SignalMessage> {
+    assert_eq!(signal.signal_type, "ice-candidates");
+    let payload: serde_json::Value = serde_json::from_str(&signal.payload).unwrap();
+    serde_json::from_value(payload["candidates"].clone()).unwrap()
+}
+
+#[tokio::test]
+async fn candidates_are_sent_together_to_clients_that_batch() {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_member_with(&mut inner, 1, &[]);
+    let (_, mut batching_rx) = add_member_with(&mut inner, 2, &[batching::FEATURE]);
+    let (_, mut plain_rx) = add_member_with(&mut inner, 3, &[]);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for index in 0..3 {
+        broadcast_to_verified_peers(&from_sender(SignalKind::IceCandidate, index), sender, Arc::clone(&state)).await.unwrap();
+    }
+
+    for index in 0..3 {
+        let candidate = received(&mut plain_rx).unwrap();
+        assert_eq!(candidate.signal_type, "ice-candidate");
+        assert_eq!(candidate.relay_seq, Some(index));
+    }
+    assert!(batching_rx.try_recv().is_err());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let candidates = batched(&received(&mut batching_rx).unwrap());
+    assert_eq!(candidates.iter().map(|candidate| candidate.relay_seq).collect::<Vec<_>>(), vec![Some(0), Some(1), Some(2)]);
+    assert!(candidates.iter().all(|candidate| candidate.sender_id == "client-1"));
+    assert!(batching_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn other_messages_do_not_overtake_waiting_candidates() {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_member_with(&mut inner, 1, &[]);
+    let (_, mut batching_rx) = add_member_with(&mut inner, 2, &[batching::FEATURE]);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    broadcast_to_verified_peers(&from_sender(SignalKind::IceCandidate, 0), sender, Arc::clone(&state)).await.unwrap();
+    broadcast_to_verified_peers(&from_sender(SignalKind::SecureOffer, 1), sender, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(batched(&received(&mut batching_rx).unwrap()).len(), 1);
+    assert_eq!(received(&mut batching_rx).unwrap().signal_type, "secure-offer");
+
+    // The timer finds nothing left to send
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(batching_rx.try_recv().is_err());
+}
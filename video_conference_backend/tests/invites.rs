@@ -0,0 +1,131 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{
+    handle_create_invite, handle_create_room, handle_join_room, SharedState, SignalingState,
+};
+use common::{add_client, parsed, payloads, signal};
+
+#[tokio::test]
+async fn invites_stand_in_for_the_password_until_their_uses_run_out() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let (late, mut late_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "password": "hunter2" }));
+    handle_create_room(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "password": "hunter2" }));
+    handle_join_room(parsed(&join), host, Arc::clone(&state)).await.unwrap();
+    handle_create_invite(parsed(&signal(SignalKind::CreateInvite, json!({ "max_uses": 1 }))), host, Arc::clone(&state)).await.unwrap();
+    let token = payloads(&mut host_rx, "invite-created")[0]["token"].as_str().unwrap().to_string();
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "invite_token": token }));
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-joined").len(), 1);
+
+    handle_join_room(parsed(&join), late, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut late_rx, "join-rejected")[0]["reason"], "invite-exhausted");
+}
+
+#[tokio::test]
+async fn forged_invites_and_invites_for_other_rooms_are_refused() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (other_host, _other_rx) = add_client(&mut inner, 2);
+    let (guest, mut guest_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for (room_id, addr) in [("alpha", host), ("beta", other_host)] {
+        let create = signal(SignalKind::CreateRoom, json!({ "room_id": room_id, "password": "hunter2" }));
+        handle_create_room(parsed(&create), addr, Arc::clone(&state)).await.unwrap();
+        let join = signal(SignalKind::JoinRoom, json!({ "room_id": room_id, "password": "hunter2" }));
+        handle_join_room(parsed(&join), addr, Arc::clone(&state)).await.unwrap();
+    }
+    handle_create_invite(parsed(&signal(SignalKind::CreateInvite, json!({}))), host, Arc::clone(&state)).await.unwrap();
+    let token = payloads(&mut host_rx, "invite-created")[0]["token"].as_str().unwrap().to_string();
+    let (claims, _) = token.split_once('.').unwrap();
+
+    for (room_id, token) in [("beta", token.clone()), ("alpha", format!("{}.forged", claims))] {
+        let join = signal(SignalKind::JoinRoom, json!({ "room_id": room_id, "invite_token": token }));
+        handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut guest_rx, "join-rejected")[0]["reason"], "invalid-invite");
+    }
+    assert!(state.lock().await.clients[&guest].room_id.is_none());
+}
+
+#[tokio::test]
+async fn a_join_turned_away_does_not_spend_the_invite() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (first, _first_rx) = add_client(&mut inner, 2);
+    let (guest, mut guest_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "max_participants": 2 }));
+    handle_create_room(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), host, Arc::clone(&state)).await.unwrap();
+    handle_create_invite(parsed(&signal(SignalKind::CreateInvite, json!({ "max_uses": 1 }))), host, Arc::clone(&state)).await.unwrap();
+    let token = payloads(&mut host_rx, "invite-created")[0]["token"].as_str().unwrap().to_string();
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), first, Arc::clone(&state)).await.unwrap();
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "invite_token": token }));
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-full")[0]["max_participants"], 2);
+
+    state.lock().await.leave_room(first);
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-joined").len(), 1);
+}
+
+#[tokio::test]
+async fn an_invite_asked_to_outlive_the_clock_lasts_as_long_as_it_can() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), host, Arc::clone(&state)).await.unwrap();
+    for expires_in_secs in [u64::MAX, i64::MAX as u64] {
+        let create = signal(SignalKind::CreateInvite, json!({ "expires_in_secs": expires_in_secs }));
+        handle_create_invite(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+        let invite = payloads(&mut host_rx, "invite-created").remove(0);
+        assert_eq!(invite["expires_at"], i64::MAX);
+
+        let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "invite_token": invite["token"] }));
+        handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut guest_rx, "room-joined").len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn invitees_wait_in_the_lobby_unless_their_invite_lets_them_past_it() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let (vip, mut vip_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "lobby": true }));
+    handle_create_room(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), host, Arc::clone(&state)).await.unwrap();
+    let mut tokens = Vec::new();
+    for bypass_lobby in [false, true] {
+        let create_invite = signal(SignalKind::CreateInvite, json!({ "bypass_lobby": bypass_lobby }));
+        handle_create_invite(parsed(&create_invite), host, Arc::clone(&state)).await.unwrap();
+        tokens.push(payloads(&mut host_rx, "invite-created")[0]["token"].as_str().unwrap().to_string());
+    }
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "invite_token": tokens[0] }));
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "lobby-waiting").len(), 1);
+    assert!(state.lock().await.clients[&guest].room_id.is_none());
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "invite_token": tokens[1] }));
+    handle_join_room(parsed(&join), vip, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut vip_rx, "room-joined").len(), 1);
+}
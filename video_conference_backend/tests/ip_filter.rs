@@ -0,0 +1,74 @@
+mod common;
+
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::firewall::{self, IpFilter};
+use video_conference_backend::models::{Client, SignalKind};
+use video_conference_backend::signaling::admin::handle_reload_ip_filter;
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+const ADMIN_TOKEN: &str = "ip-filter-admin";
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn denied_ranges_win_and_a_non_empty_allowlist_admits_only_its_ranges() {
+    let open = IpFilter::default();
+    assert!(open.permits(&ip("203.0.113.9")));
+
+    let filter = IpFilter::new(
+        vec![firewall::parse_net("10.0.0.0/8").unwrap(), firewall::parse_net("192.0.2.1").unwrap()],
+        vec![firewall::parse_net("10.6.0.0/16").unwrap()],
+    );
+    assert!(filter.permits(&ip("10.1.2.3")));
+    assert!(filter.permits(&ip("192.0.2.1")));
+    assert!(filter.permits(&ip("::ffff:10.1.2.3")));
+    assert!(!filter.permits(&ip("10.6.2.3")));
+    assert!(!filter.permits(&ip("203.0.113.9")));
+
+    assert!(firewall::parse_net("10.0.0.0/33").is_err());
+}
+
+#[test]
+fn list_files_skip_comments_and_blank_lines() {
+    let path = std::env::temp_dir().join(format!("ip-filter-test-{}.txt", std::process::id()));
+    std::fs::write(&path, "# office\n198.51.100.0/24\n\n2001:db8::/32  # v6 range\n").unwrap();
+    assert_eq!(firewall::load_list(&path).unwrap().len(), 2);
+
+    std::fs::write(&path, "198.51.100.0/24\nnot-an-address\n").unwrap();
+    assert!(firewall::load_list(&path).is_err());
+}
+
+#[tokio::test]
+async fn an_admin_reload_disconnects_clients_the_new_lists_refuse() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let mut inner = SignalingState::new();
+    let (admin, mut admin_rx) = add_client(&mut inner, 1);
+    let outsider: SocketAddr = "203.0.113.9:4000".parse().unwrap();
+    let (tx, mut outsider_rx) = mpsc::channel(16);
+    inner.clients.insert(outsider, Client::new(tx, "outsider".to_string(), outsider));
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let guess = signal(SignalKind::ReloadIpFilter, json!({ "admin_token": "guess", "deny": ["203.0.113.0/24"] }));
+    handle_reload_ip_filter(parsed(&guess), admin, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut admin_rx, "error")[0]["code"], "not-admin");
+
+    let invalid = signal(SignalKind::ReloadIpFilter, json!({ "admin_token": ADMIN_TOKEN, "deny": ["203.0.113.0/40"] }));
+    handle_reload_ip_filter(parsed(&invalid), admin, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut admin_rx, "error")[0]["code"], "invalid-ip-filter");
+    assert!(outsider_rx.try_recv().is_err());
+
+    let reload = signal(SignalKind::ReloadIpFilter, json!({ "admin_token": ADMIN_TOKEN, "deny": ["203.0.113.0/24"] }));
+    handle_reload_ip_filter(parsed(&reload), admin, Arc::clone(&state)).await.unwrap();
+    let reloaded = payloads(&mut admin_rx, "ip-filter-reloaded").remove(0);
+    assert_eq!(reloaded["deny"], 1);
+    assert_eq!(reloaded["disconnected"], 1);
+    assert!(matches!(outsider_rx.try_recv(), Ok(Message::Close(Some(_)))));
+    assert!(!state.lock().await.ip_filter.read().unwrap().permits(&ip("203.0.113.77")));
+}
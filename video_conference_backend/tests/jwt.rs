@@ -0,0 +1,82 @@
+mod common;
+
+use chrono::Utc;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Role, SignalKind};
+use video_conference_backend::signaling::auth::handle_authenticate;
+use video_conference_backend::signaling::{handle_join_room, SharedState, SignalingState};
+use common::{add_client, add_member, parsed, payloads, signal};
+
+const SECRET: &str = "jwt-test-secret";
+
+fn token(secret: &str, rooms: &[&str]) -> String {
+    encode_claims(secret, json!({
+        "sub": "user-7",
+        "exp": Utc::now().timestamp() + 600,
+        "role": "moderator",
+        "rooms": rooms,
+    }))
+}
+
+fn encode_claims(secret: &str, claims: serde_json::Value) -> String {
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+}
+
+#[tokio::test]
+async fn token_claims_grant_a_role_and_limit_the_rooms_a_user_may_join() {
+    std::env::set_var("JWT_SECRET", SECRET);
+    let mut inner = SignalingState::new();
+    add_member(&mut inner, 1, "alpha");
+    let (member, mut member_rx) = add_client(&mut inner, 2);
+    let (outsider, mut outsider_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for (addr, rx, rooms) in [(member, &mut member_rx, ["alpha"]), (outsider, &mut outsider_rx, ["beta"])] {
+        let authenticate = signal(SignalKind::Authenticate, json!({ "token": token(SECRET, &rooms) }));
+        handle_authenticate(parsed(&authenticate), addr, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(rx, "token-accepted")[0]["user_id"], "user-7");
+    }
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }));
+    handle_join_room(parsed(&join), member, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut member_rx, "room-joined").len(), 1);
+    assert_eq!(state.lock().await.clients[&member].role, Role::Moderator);
+
+    handle_join_room(parsed(&join), outsider, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut outsider_rx, "join-rejected")[0]["reason"], "room-not-allowed");
+    assert!(state.lock().await.clients[&outsider].room_id.is_none());
+}
+
+#[tokio::test]
+async fn tokens_signed_with_another_key_are_rejected() {
+    std::env::set_var("JWT_SECRET", SECRET);
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let authenticate = signal(SignalKind::Authenticate, json!({ "token": token("some-other-secret", &["alpha"]) }));
+    handle_authenticate(parsed(&authenticate), addr, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "invalid-token");
+    assert!(state.lock().await.clients[&addr].claims.is_none());
+}
+
+#[tokio::test]
+async fn a_role_is_only_granted_in_the_rooms_the_token_names() {
+    std::env::set_var("JWT_SECRET", SECRET);
+    let mut inner = SignalingState::new();
+    add_member(&mut inner, 1, "alpha");
+    let (addr, mut rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let unscoped = encode_claims(SECRET, json!({ "sub": "user-7", "exp": Utc::now().timestamp() + 600, "role": "host" }));
+    handle_authenticate(parsed(&signal(SignalKind::Authenticate, json!({ "token": unscoped }))), addr, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "token-accepted").len(), 1);
+
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), addr, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "room-joined").len(), 1);
+    assert_eq!(state.lock().await.clients[&addr].role, Role::Participant);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::auth::Claims;
+use video_conference_backend::pinning::{self, check_pin, PinError, SqlitePinStore};
+use video_conference_backend::signaling::auth::{challenge_message, handle_challenge_response, send_challenge};
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_client, drain, parsed, payloads, signal};
+
+fn claims(sub: &str) -> Claims {
+    Claims { sub: sub.to_string(), exp: i64::MAX, role: None, rooms: None }
+}
+
+async fn answer_challenge(signing_key: &SigningKey, addr: SocketAddr, rx: &mut mpsc::Receiver<Message>, state: &SharedState) {
+    send_challenge(addr, Arc::clone(state)).await.unwrap();
+    let challenge = payloads(rx, "challenge").remove(0);
+    let message = challenge_message(challenge["challenge"].as_str().unwrap(), challenge["client_id"].as_str().unwrap());
+    let signature: Signature = signing_key.sign(&message);
+
+    let response = signal(SignalKind::ChallengeResponse, json!({
+        "public_key": signing_key.verifying_key().to_encoded_point(false).as_bytes(),
+        "signature": signature.to_bytes().to_vec(),
+    }));
+    handle_challenge_response(parsed(&response), addr, Arc::clone(state)).await.unwrap();
+}
+
+#[test]
+fn the_first_key_is_pinned_and_others_rejected() {
+    let store = SqlitePinStore::open_in_memory().unwrap();
+
+    assert_eq!(check_pin(&store, "user:alice", b"key-1"), Ok(()));
+    assert_eq!(check_pin(&store, "user:alice", b"key-1"), Ok(()));
+    assert_eq!(check_pin(&store, "user:alice", b"key-2"), Err(PinError::Mismatch));
+    assert_eq!(check_pin(&store, "user:bob", b"key-2"), Ok(()));
+}
+
+#[test]
+fn a_key_matches_its_pin_in_either_encoding() {
+    let store = SqlitePinStore::open_in_memory().unwrap();
+    let old_key = *SigningKey::random(&mut OsRng).verifying_key();
+    let new_key = *SigningKey::random(&mut OsRng).verifying_key();
+    let encoded = |key: &p256::ecdsa::VerifyingKey, compress| key.to_encoded_point(compress).as_bytes().to_vec();
+
+    assert_eq!(check_pin(&store, "user:alice", &encoded(&old_key, true)), Ok(()));
+    assert_eq!(check_pin(&store, "user:alice", &encoded(&old_key, false)), Ok(()));
+    assert_eq!(pinning::rotate_pin(&store, "user:alice", &encoded(&old_key, true), &encoded(&new_key, true)), Ok(()));
+    assert_eq!(check_pin(&store, "user:alice", &encoded(&new_key, false)), Ok(()));
+    assert_eq!(check_pin(&store, "user:alice", &encoded(&old_key, false)), Err(PinError::Mismatch));
+}
+
+#[tokio::test]
+async fn a_user_reconnecting_with_another_key_fails_the_challenge() {
+    let mut inner = SignalingState::new();
+    inner.key_pins = Some(Arc::new(SqlitePinStore::open_in_memory().unwrap()));
+    let (first, mut first_rx) = add_client(&mut inner, 1);
+    let (second, mut second_rx) = add_client(&mut inner, 2);
+    for addr in [first, second] {
+        inner.clients.get_mut(&addr).unwrap().claims = Some(claims("alice"));
+    }
+    assert_eq!(pinning::identity(&inner.clients[&first]), "user:alice");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    answer_challenge(&SigningKey::random(&mut OsRng), first, &mut first_rx, &state).await;
+    assert_eq!(payloads(&mut first_rx, "authenticated").len(), 1);
+
+    answer_challenge(&SigningKey::random(&mut OsRng), second, &mut second_rx, &state).await;
+    let signals = drain(&mut second_rx);
+    assert!(signals.iter().all(|(kind, _)| kind != "authenticated"));
+    let error = &signals.iter().find(|(kind, _)| kind == "error").unwrap().1;
+    assert_eq!(error["code"], "challenge-failed");
+    assert_eq!(error["message"], PinError::Mismatch.message());
+    assert!(state.lock().await.clients[&second].public_key.is_none());
+}
+
+#[tokio::test]
+async fn a_reconnecting_client_is_held_to_the_pin_of_its_noise_key() {
+    let mut inner = SignalingState::new();
+    inner.key_pins = Some(Arc::new(SqlitePinStore::open_in_memory().unwrap()));
+    let (first, mut first_rx) = add_client(&mut inner, 1);
+    let (second, mut second_rx) = add_client(&mut inner, 2);
+    for addr in [first, second] {
+        inner.clients.get_mut(&addr).unwrap().noise_key = Some(vec![7; 32]);
+    }
+    // Both connections name the same static key, whatever their client_ids
+    assert_eq!(pinning::identity(&inner.clients[&first]), pinning::identity(&inner.clients[&second]));
+    assert!(pinning::identity(&inner.clients[&first]).starts_with("noise:"));
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    answer_challenge(&SigningKey::random(&mut OsRng), first, &mut first_rx, &state).await;
+    assert_eq!(payloads(&mut first_rx, "authenticated").len(), 1);
+
+    answer_challenge(&SigningKey::random(&mut OsRng), second, &mut second_rx, &state).await;
+    assert_eq!(payloads(&mut second_rx, "error")[0]["message"], PinError::Mismatch.message());
+}
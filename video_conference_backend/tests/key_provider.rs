@@ -0,0 +1,106 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::crypto::{Ed25519Signer, ServerSigner};
+use video_conference_backend::models::SignalMessage;
+use video_conference_backend::secrets::{self, KeyProvider, SecretResult};
+use video_conference_backend::signaling::SignalingServer;
+use video_conference_backend::storage::SqliteRoomStore;
+use common::upgrade_request;
+
+const SEED: [u8; 32] = [9; 32];
+
+// Stands in for a KMS or HSM: hands out whatever it was given, by reference
+struct MapProvider(HashMap<String, Vec<u8>>);
+
+impl KeyProvider for MapProvider {
+    fn load<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, SecretResult<Vec<u8>>> {
+        Box::pin(async move {
+            self.0.get(reference).cloned().ok_or_else(|| format!("no key named {}", reference).into())
+        })
+    }
+}
+
+fn server(provider: MapProvider) -> (std::net::SocketAddr, SignalingServer) {
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let server = SignalingServer::builder()
+        .addr(addr)
+        .room_store(Arc::new(SqliteRoomStore::open_in_memory().unwrap()))
+        .key_provider(Arc::new(provider))
+        .build();
+    (addr, server)
+}
+
+#[test]
+fn identity_seeds_are_read_raw_or_as_key_file_text() {
+    let raw = Ed25519Signer::from_secret(&SEED).unwrap();
+    let text = Ed25519Signer::from_secret(format!("{}\n", STANDARD.encode(SEED)).as_bytes()).unwrap();
+    assert_eq!(raw.public_key(), text.public_key());
+
+    assert!(Ed25519Signer::from_secret(&[9; 16]).is_err());
+}
+
+#[test]
+fn a_generated_identity_key_is_readable_only_by_its_owner() {
+    let path = std::env::temp_dir().join(format!("identity-test-{}.key", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let generated = Ed25519Signer::load_or_generate(&path).unwrap();
+    let reloaded = Ed25519Signer::load_or_generate(&path).unwrap();
+    assert_eq!(generated.public_key(), reloaded.public_key());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn unknown_key_providers_are_refused() {
+    std::env::set_var("KEY_PROVIDER", "file");
+    assert!(secrets::from_config().unwrap().is_none());
+
+    std::env::set_var("KEY_PROVIDER", "vault");
+    let error = secrets::from_config().err().unwrap();
+    assert!(error.to_string().contains("Unknown KEY_PROVIDER"), "{}", error);
+    std::env::remove_var("KEY_PROVIDER");
+}
+
+#[tokio::test]
+async fn the_server_identity_is_loaded_from_the_key_provider() {
+    std::env::set_var("SERVER_KEY_PATH", "kms/server-identity");
+
+    // Without the key the server refuses to start rather than generating one
+    let (_, missing) = server(MapProvider(HashMap::new()));
+    let error = missing.run().await.err().unwrap();
+    assert!(error.to_string().contains("kms/server-identity"), "{}", error);
+
+    let keys = HashMap::from([("kms/server-identity".to_string(), SEED.to_vec())]);
+    let (addr, server) = server(MapProvider(keys));
+    tokio::spawn(async move { server.run().await.map_err(|e| e.to_string()) });
+    for _ in 0..50 {
+        if TcpStream::connect(addr).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let (mut ws, _) = connect_async(upgrade_request(addr)).await.unwrap();
+    let Some(Ok(Message::Text(text))) = ws.next().await else {
+        panic!("expected the server identity");
+    };
+    let identity: SignalMessage = serde_json::from_str(&text).unwrap();
+    assert_eq!(identity.signal_type, "server-identity");
+    let payload: serde_json::Value = serde_json::from_str(&identity.payload).unwrap();
+    let expected = Ed25519Signer::from_secret(&SEED).unwrap().public_key();
+    assert_eq!(payload["public_key"], STANDARD.encode(expected));
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::pinning::{check_pin, PinError, SqlitePinStore};
+use video_conference_backend::signaling::keys::{handle_rotate_key, rotation_message};
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, sign_in, signal};
+
+fn public_key(signing_key: &SigningKey) -> Vec<u8> {
+    signing_key.verifying_key().to_encoded_point(false).as_bytes().to_vec()
+}
+
+// A rotate-key from `old` to `new`, with the proof made by `prover`
+fn rotation(old: &SigningKey, new: &SigningKey, prover: &SigningKey, nonce: &[u8]) -> serde_json::Value {
+    let message = rotation_message("client-2", &public_key(old), &public_key(new), nonce);
+    let signature: Signature = old.sign(&message);
+    let proof: Signature = prover.sign(&message);
+    json!({
+        "new_public_key": public_key(new),
+        "signature": signature.to_bytes().to_vec(),
+        "proof": proof.to_bytes().to_vec(),
+        "nonce": nonce,
+    })
+}
+
+#[tokio::test]
+async fn rotation_needs_both_keys_and_moves_the_pin() {
+    let pins = Arc::new(SqlitePinStore::open_in_memory().unwrap());
+    let mut inner = SignalingState::new();
+    inner.key_pins = Some(pins.clone());
+    let (_, mut peer_rx) = add_member(&mut inner, 1, "alpha");
+    let (addr, mut rx) = add_member(&mut inner, 2, "alpha");
+    let old = SigningKey::random(&mut OsRng);
+    let new = SigningKey::random(&mut OsRng);
+    inner.clients.get_mut(&addr).unwrap().public_key = Some(public_key(&old));
+    sign_in(&mut inner, addr, "alice");
+    check_pin(pins.as_ref(), "webauthn:alice", &public_key(&old)).unwrap();
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut peer_rx);
+
+    let unproven = rotation(&old, &new, &SigningKey::random(&mut OsRng), b"nonce-1");
+    handle_rotate_key(parsed(&signal(SignalKind::RotateKey, unproven)), addr, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "SIG_VERIFY_FAILED");
+    assert_eq!(state.lock().await.clients[&addr].public_key, Some(public_key(&old)));
+
+    let rotate = rotation(&old, &new, &new, b"nonce-2");
+    handle_rotate_key(parsed(&signal(SignalKind::RotateKey, rotate)), addr, Arc::clone(&state)).await.unwrap();
+    let rotated = payloads(&mut peer_rx, "key-rotated").remove(0);
+    assert_eq!(rotated["client_id"], "client-2");
+    assert_eq!(rotated["public_key"], json!(public_key(&new)));
+    assert_eq!(state.lock().await.clients[&addr].public_key, Some(public_key(&new)));
+
+    assert_eq!(check_pin(pins.as_ref(), "webauthn:alice", &public_key(&new)), Ok(()));
+    assert_eq!(check_pin(pins.as_ref(), "webauthn:alice", &public_key(&old)), Err(PinError::Mismatch));
+}
+
+#[tokio::test]
+async fn a_connection_without_a_key_has_nothing_to_rotate() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_member(&mut inner, 1, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let key = SigningKey::random(&mut OsRng);
+    handle_rotate_key(parsed(&signal(SignalKind::RotateKey, rotation(&key, &key, &key, b"nonce"))), addr, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "no-key-bound");
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::{Role, SignalKind};
+use video_conference_backend::signaling::{handle_join_room, moderation, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, received_payload, signal};
+
+#[tokio::test]
+async fn kicked_members_are_told_disconnected_and_kept_out_when_banned() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (target, mut target_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let kick = signal(SignalKind::Kick, json!({ "client_id": "client-2", "ban": true, "reason": "spam" }));
+    moderation::handle_kick(parsed(&kick), host, Arc::clone(&state)).await.unwrap();
+
+    let (kind, notice) = received_payload(&mut target_rx).unwrap();
+    assert_eq!((kind.as_str(), notice["banned"].as_bool()), ("kicked", Some(true)));
+    assert!(matches!(target_rx.try_recv(), Ok(Message::Close(Some(_)))));
+    assert_eq!(payloads(&mut host_rx, "peer-left")[0]["client_id"], "client-2");
+
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), target, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut target_rx, "join-rejected")[0]["reason"], "banned");
+}
+
+#[tokio::test]
+async fn only_higher_ranked_members_can_kick() {
+    let mut inner = SignalingState::new();
+    add_member(&mut inner, 1, "alpha");
+    let (participant, mut participant_rx) = add_member(&mut inner, 2, "alpha");
+    let (_other, mut other_rx) = add_member(&mut inner, 3, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let kick = signal(SignalKind::Kick, json!({ "client_id": "client-3" }));
+    moderation::handle_kick(parsed(&kick), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut participant_rx, "error")[0]["code"], "not-moderator");
+
+    state.lock().await.clients.get_mut(&participant).unwrap().role = Role::Moderator;
+    let kick_host = signal(SignalKind::Kick, json!({ "client_id": "client-1" }));
+    moderation::handle_kick(parsed(&kick_host), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut participant_rx, "error")[0]["code"], "insufficient-role");
+
+    assert!(drain(&mut other_rx).is_empty());
+    assert_eq!(state.lock().await.rooms["alpha"].members.len(), 3);
+}
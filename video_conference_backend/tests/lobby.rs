@@ -0,0 +1,107 @@
+mod common;
+
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::{Role, SignalKind};
+use video_conference_backend::signaling::{
+    calls, handle_create_invite, handle_create_room, handle_join_decision, handle_join_room, SharedState, SignalingState
+};
+use common::{add_client, call, invite, parsed, payloads, sign_in, signal};
+
+// A lobby room hosted by client-1, with clients 2 and 3 connected but outside it
+async fn lobby_room() -> (SharedState, Vec<(SocketAddr, mpsc::Receiver<Message>)>) {
+    let mut inner = SignalingState::new();
+    let clients = (1..=3).map(|port| add_client(&mut inner, port)).collect::<Vec<_>>();
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let host = clients[0].0;
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "lobby": true }))), host, Arc::clone(&state)).await.unwrap();
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), host, Arc::clone(&state)).await.unwrap();
+    (state, clients)
+}
+
+#[tokio::test]
+async fn the_host_admits_or_turns_away_waiting_joiners() {
+    let (state, mut clients) = lobby_room().await;
+    let (host, guest, other) = (clients[0].0, clients[1].0, clients[2].0);
+    assert_eq!(payloads(&mut clients[0].1, "room-joined").len(), 1);
+
+    for addr in [guest, other] {
+        handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), addr, Arc::clone(&state)).await.unwrap();
+    }
+    assert_eq!(payloads(&mut clients[1].1, "lobby-waiting").len(), 1);
+    let requests = payloads(&mut clients[0].1, "join-request");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["client_id"], "client-2");
+    assert!(state.lock().await.clients[&guest].room_id.is_none());
+
+    let admit = signal(SignalKind::JoinDecision, json!({ "client_id": "client-2", "approved": true }));
+    handle_join_decision(parsed(&admit), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "room-joined").len(), 1);
+    let deny = signal(SignalKind::JoinDecision, json!({ "client_id": "client-3", "approved": false }));
+    handle_join_decision(parsed(&deny), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[2].1, "join-rejected")[0]["reason"], "denied-by-host");
+
+    let state = state.lock().await;
+    assert_eq!(state.clients[&guest].room_id.as_deref(), Some("alpha"));
+    assert!(state.clients[&other].room_id.is_none());
+}
+
+#[tokio::test]
+async fn only_moderators_decide() {
+    let (state, mut clients) = lobby_room().await;
+    let guest = clients[1].0;
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), guest, Arc::clone(&state)).await.unwrap();
+
+    let admit = signal(SignalKind::JoinDecision, json!({ "client_id": "client-2", "approved": true }));
+    handle_join_decision(parsed(&admit), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "error")[0]["code"], "not-moderator");
+    handle_join_decision(parsed(&signal(SignalKind::JoinDecision, json!({ "client_id": "client-3", "approved": true }))), clients[0].0, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[0].1, "error")[0]["code"], "not-waiting");
+    assert!(state.lock().await.clients[&guest].room_id.is_none());
+}
+
+#[tokio::test]
+async fn an_admitted_joiner_takes_its_invite_role_and_leaves_its_old_room() {
+    let (state, mut clients) = lobby_room().await;
+    let (host, guest, peer) = (clients[0].0, clients[1].0, clients[2].0);
+    for addr in [guest, peer] {
+        handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "beta" }))), addr, Arc::clone(&state)).await.unwrap();
+    }
+    let create_invite = signal(SignalKind::CreateInvite, json!({ "role": "moderator" }));
+    handle_create_invite(parsed(&create_invite), host, Arc::clone(&state)).await.unwrap();
+    let token = payloads(&mut clients[0].1, "invite-created")[0]["token"].as_str().unwrap().to_string();
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "invite_token": token }));
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "lobby-waiting").len(), 1);
+    assert!(payloads(&mut clients[2].1, "peer-left").is_empty());
+
+    let admit = signal(SignalKind::JoinDecision, json!({ "client_id": "client-2", "approved": true }));
+    handle_join_decision(parsed(&admit), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "room-joined").len(), 1);
+    assert_eq!(payloads(&mut clients[2].1, "peer-left")[0]["client_id"], "client-2");
+    assert_eq!(state.lock().await.clients[&guest].role, Role::Moderator);
+}
+
+#[tokio::test]
+async fn an_admitted_joiner_hangs_up_its_direct_call() {
+    let (state, mut clients) = lobby_room().await;
+    let (host, guest, caller) = (clients[0].0, clients[1].0, clients[2].0);
+    {
+        let mut inner = state.lock().await;
+        sign_in(&mut inner, caller, "alice");
+        sign_in(&mut inner, guest, "bob");
+    }
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_answer(call("call-1"), true, guest, Arc::clone(&state)).await.unwrap();
+
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), guest, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut clients[2].1, "call-ended").is_empty());
+
+    let admit = signal(SignalKind::JoinDecision, json!({ "client_id": "client-2", "approved": true }));
+    handle_join_decision(parsed(&admit), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[2].1, "call-ended")[0]["reason"], "joined-room");
+}
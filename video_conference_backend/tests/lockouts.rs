@@ -0,0 +1,100 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::ratelimit::{FailureTracker, LockoutPolicy, LockoutTable};
+use video_conference_backend::signaling::{handle_secure_offer, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, secure_offer};
+
+fn policy() -> LockoutPolicy {
+    LockoutPolicy {
+        max_failures: 3,
+        window: Duration::from_secs(60),
+        base: Duration::from_secs(30),
+        max: Duration::from_secs(100),
+    }
+}
+
+#[test]
+fn each_lockout_doubles_up_to_the_max() {
+    let policy = policy();
+    let mut tracker = FailureTracker::default();
+    let start = Instant::now();
+
+    let lockouts: Vec<_> = (0..12u64)
+        .filter_map(|i| tracker.record(start + Duration::from_secs(i), &policy))
+        .collect();
+    assert_eq!(lockouts, [30, 60, 100, 100].map(Duration::from_secs));
+    assert_eq!(tracker.locked_for(start + Duration::from_secs(11)), Some(Duration::from_secs(100)));
+
+    // Failures spread wider than the window never add up to a lockout
+    let mut patient = FailureTracker::default();
+    for i in 0..6u64 {
+        assert_eq!(patient.record(start + Duration::from_secs(i * 61), &policy), None);
+    }
+
+    let off = LockoutPolicy { max_failures: 0, ..policy };
+    assert!((0..10).all(|_| tracker.record(start, &off).is_none()));
+}
+
+#[test]
+fn addresses_are_locked_out_independently() {
+    let table = LockoutTable::default();
+    let now = Instant::now();
+    let (bad, good) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+    for _ in 0..2 {
+        assert_eq!(table.record(bad, now, &policy()), None);
+    }
+    assert_eq!(table.record(bad, now, &policy()), Some(Duration::from_secs(30)));
+    assert!(table.locked_for(&bad, now).is_some());
+    assert_eq!(table.locked_for(&good, now), None);
+}
+
+#[tokio::test]
+async fn repeated_bad_signatures_lock_the_sender_out() {
+    std::env::set_var("LOCKOUT_MAX_FAILURES", "3");
+    std::env::set_var("LOCKOUT_BASE_SECS", "45");
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let forger = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    for _ in 0..3 {
+        let message = secure_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes(), |message| {
+            let signature: Signature = forger.sign(message);
+            signature.to_bytes().to_vec()
+        });
+        handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    }
+
+    let mut messages = Vec::new();
+    while let Ok(message) = guest_rx.try_recv() {
+        messages.push(message);
+    }
+    assert!(matches!(messages.last(), Some(Message::Close(Some(_)))));
+    let errors: Vec<serde_json::Value> = messages
+        .iter()
+        .filter_map(|message| match message {
+            Message::Text(text) => serde_json::from_str::<serde_json::Value>(text).ok(),
+            _ => None,
+        })
+        .map(|signal| serde_json::from_str(signal["payload"].as_str().unwrap()).unwrap())
+        .collect();
+    let codes: Vec<_> = errors.iter().map(|error| error["code"].as_str().unwrap()).collect();
+    assert_eq!(codes, ["SIG_VERIFY_FAILED", "SIG_VERIFY_FAILED", "SIG_VERIFY_FAILED", "locked-out"]);
+    assert_eq!(errors[3]["retry_after_secs"], 45);
+
+    assert!(state.lock().await.lockouts.locked_for(&guest.ip(), Instant::now()).is_some());
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
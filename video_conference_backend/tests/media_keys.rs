@@ -0,0 +1,44 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::e2ee::handle_key_announce;
+use video_conference_backend::signaling::{roster, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, signal};
+
+fn announce(epoch: u64, target_id: &str) -> SignalMessage {
+    let mut announce = signal(SignalKind::KeyAnnounce, json!({ "epoch": epoch, "sealed_key": "c2VhbGVkLWtleQ" }));
+    announce.target_id = Some(target_id.to_string());
+    announce
+}
+
+#[tokio::test]
+async fn sealed_keys_reach_their_target_and_membership_changes_start_a_new_epoch() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let message = announce(0, "client-2");
+    handle_key_announce(&message, parsed(&message), host, Arc::clone(&state)).await.unwrap();
+    let relayed = payloads(&mut guest_rx, "key-announce").remove(0);
+    assert_eq!(relayed["sealed_key"], "c2VhbGVkLWtleQ");
+    assert!(state.lock().await.rooms["alpha"].manages_keys());
+
+    let (latecomer, mut latecomer_rx) = add_member(&mut *state.lock().await, 3, "alpha");
+    roster::announce_join(&mut *state.lock().await, latecomer).await.unwrap();
+    let rekey = payloads(&mut guest_rx, "rekey").remove(0);
+    assert_eq!(rekey["epoch"], 1);
+    assert_eq!(rekey["reason"], "join");
+    assert_eq!(payloads(&mut latecomer_rx, "roster")[0]["key_epoch"], 0);
+    drain(&mut host_rx);
+
+    let message = announce(0, "client-2");
+    handle_key_announce(&message, parsed(&message), host, Arc::clone(&state)).await.unwrap();
+    let error = payloads(&mut host_rx, "error").remove(0);
+    assert_eq!(error["code"], "stale-epoch");
+    assert_eq!(error["epoch"], 1);
+    assert!(payloads(&mut guest_rx, "key-announce").is_empty());
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::acks::{self, Delivery};
+use video_conference_backend::signaling::dedup::{self, MessageIdCache};
+use video_conference_backend::signaling::{dispatch_signal, SharedState, SignalingState};
+use common::{add_member, drain, payloads, session_token, signal};
+
+const TTL: Duration = Duration::from_secs(60);
+
+// Dispatches, acknowledges and settles `message` the way the connection loop does
+async fn dispatch(message: &SignalMessage, addr: SocketAddr, state: &SharedState) {
+    let mut client_id = "client-1".to_string();
+    let token = session_token(&mut *state.lock().await, addr);
+    let (dispatched, outcome) = acks::track(dispatch_signal(message, Some(&token), addr, &mut client_id, Arc::clone(state))).await;
+    dispatched.unwrap();
+    acks::send_ack(message, outcome.clone(), addr, state).await.unwrap();
+    dedup::settle(message, &outcome, addr, state).await;
+}
+
+#[test]
+fn ids_are_remembered_for_the_ttl_and_up_to_the_capacity() {
+    let mut cache = MessageIdCache::default();
+    let start = Instant::now();
+    assert!(cache.insert("a", start, TTL, 2));
+    assert!(!cache.insert("a", start + Duration::from_secs(59), TTL, 2));
+    assert!(cache.insert("a", start + Duration::from_secs(61), TTL, 2));
+
+    assert!(cache.insert("b", start + Duration::from_secs(61), TTL, 2));
+    assert!(cache.insert("c", start + Duration::from_secs(61), TTL, 2));
+    // "a" was pushed out to make room for "c"
+    assert!(cache.insert("a", start + Duration::from_secs(62), TTL, 2));
+
+    cache.settle("c", Some(Delivery::Queued));
+    assert_eq!(cache.outcome("c"), Some(Delivery::Queued));
+    let mut resumed = MessageIdCache::default();
+    resumed.merge(cache);
+    assert_eq!(resumed.outcome("c"), Some(Delivery::Queued));
+    assert!(!resumed.insert("c", start + Duration::from_secs(62), TTL, 2));
+}
+
+#[tokio::test]
+async fn message_ids_must_be_uuids() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_member(&mut inner, 1, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut rx);
+
+    let mut unnamed = signal(SignalKind::RaiseHand, json!({}));
+    unnamed.message_id = None;
+    assert!(!dedup::check_message_id(&unnamed, addr, &state).await.unwrap());
+    let mut misnamed = signal(SignalKind::RaiseHand, json!({}));
+    misnamed.message_id = Some("message-1".to_string());
+    assert!(!dedup::check_message_id(&misnamed, addr, &state).await.unwrap());
+
+    let codes: Vec<_> = payloads(&mut rx, "error").iter().map(|error| error["code"].clone()).collect();
+    assert_eq!(codes, ["missing-message-id", "invalid-message-id"]);
+    assert!(dedup::check_message_id(&signal(SignalKind::RaiseHand, json!({})), addr, &state).await.unwrap());
+}
+
+#[tokio::test]
+async fn retransmissions_get_the_first_ack_and_are_not_relayed_again() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut target_rx) = add_member(&mut inner, 2, "alpha");
+    for (addr, client) in inner.clients.iter_mut() {
+        client.public_key = Some(vec![addr.port() as u8; 65]);
+    }
+    inner.clients.get_mut(&sender).unwrap().features = vec![acks::FEATURE.to_string()];
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut sender_rx);
+    drain(&mut target_rx);
+
+    let mut sealed = signal(SignalKind::Sealed, json!("opaque"));
+    sealed.target_id = Some("client-2".to_string());
+    dispatch(&sealed, sender, &state).await;
+    dispatch(&sealed, sender, &state).await;
+
+    assert_eq!(payloads(&mut target_rx, "sealed").len(), 1);
+    let acks = payloads(&mut sender_rx, "ack");
+    assert_eq!(acks.len(), 2);
+    assert_eq!(acks[0], acks[1]);
+    assert_eq!(acks[1]["status"], "delivered");
+}
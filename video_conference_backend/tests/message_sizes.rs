@@ -0,0 +1,48 @@
+mod common;
+
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use video_conference_backend::models::SignalMessage;
+use common::{start_server, upgrade_request};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Signals received until the server closes the connection
+async fn signals_until_closed(ws: &mut Socket) -> Vec<SignalMessage> {
+    let mut signals = Vec::new();
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), ws.next()).await.expect("connection stayed open") {
+            Some(Ok(Message::Text(text))) => signals.push(serde_json::from_str(&text).unwrap()),
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return signals,
+            Some(Ok(_)) => {}
+        }
+    }
+}
+
+fn chat_of_len(len: usize) -> Message {
+    let text = "x".repeat(len);
+    Message::Text(serde_json::json!({ "signal_type": "chat", "payload": text }).to_string())
+}
+
+#[tokio::test]
+async fn oversized_signals_are_refused_and_the_sender_disconnected() {
+    std::env::set_var("MAX_PAYLOAD_SIZE", "1024");
+    std::env::set_var("MAX_MESSAGE_SIZE", "4096");
+    std::env::set_var("MAX_FRAME_SIZE", "4096");
+    let addr = start_server().await;
+
+    let (mut ws, _) = connect_async(upgrade_request(addr)).await.unwrap();
+    ws.send(chat_of_len(2000)).await.unwrap();
+    let signals = signals_until_closed(&mut ws).await;
+    let error = signals.iter().find(|signal| signal.signal_type == "error").expect("an error before the close");
+    assert!(error.payload.contains("message-too-large"));
+
+    // Too big to buffer at all: dropped without reading it
+    let (mut ws, _) = connect_async(upgrade_request(addr)).await.unwrap();
+    let _ = ws.send(chat_of_len(10_000)).await;
+    let signals = signals_until_closed(&mut ws).await;
+    assert!(signals.iter().all(|signal| signal.signal_type != "error"));
+}
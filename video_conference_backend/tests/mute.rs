@@ -0,0 +1,49 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Role, SignalKind};
+use video_conference_backend::signaling::{moderation, SharedState, SignalingState};
+use common::{add_member, parsed, payloads, signal};
+
+#[tokio::test]
+async fn moderators_mute_one_member_or_everyone_below_them() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (moderator, mut moderator_rx) = add_member(&mut inner, 2, "alpha");
+    let (participant, mut participant_rx) = add_member(&mut inner, 3, "alpha");
+    inner.clients.get_mut(&moderator).unwrap().role = Role::Moderator;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let mute = signal(SignalKind::MuteRequest, json!({ "client_id": "client-3" }));
+    moderation::handle_mute_request(parsed(&mute), moderator, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut participant_rx, "mute-request")[0]["by"], "client-2");
+    assert_eq!(payloads(&mut host_rx, "mute-state")[0]["muted_by_host"], true);
+    assert!(state.lock().await.clients[&participant].muted_by_host);
+
+    moderation::handle_mute_all(parsed(&signal(SignalKind::MuteAll, json!({}))), moderator, Arc::clone(&state)).await.unwrap();
+    let update = payloads(&mut host_rx, "mute-all").remove(0);
+    assert_eq!(update["client_ids"], json!(["client-3"]), "the host outranks the moderator");
+
+    moderation::handle_mute_all(parsed(&signal(SignalKind::MuteAll, json!({ "muted": false }))), host, Arc::clone(&state)).await.unwrap();
+    let update = payloads(&mut moderator_rx, "mute-all").pop().unwrap();
+    assert_eq!(update["client_ids"].as_array().unwrap().len(), 2);
+    assert!(!state.lock().await.clients[&participant].muted_by_host);
+}
+
+#[tokio::test]
+async fn participants_cannot_mute_anyone() {
+    let mut inner = SignalingState::new();
+    let (_host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (participant, mut participant_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let mute = signal(SignalKind::MuteRequest, json!({ "client_id": "client-1" }));
+    moderation::handle_mute_request(parsed(&mute), participant, Arc::clone(&state)).await.unwrap();
+    moderation::handle_mute_all(parsed(&signal(SignalKind::MuteAll, json!({}))), participant, Arc::clone(&state)).await.unwrap();
+
+    let errors = payloads(&mut participant_rx, "error");
+    assert!(errors.iter().all(|error| error["code"] == "not-moderator") && errors.len() == 2);
+    assert!(payloads(&mut host_rx, "mute-request").is_empty());
+}
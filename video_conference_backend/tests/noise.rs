@@ -0,0 +1,92 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use snow::{Builder, Keypair, TransportState};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use video_conference_backend::models::SignalMessage;
+use video_conference_backend::noise::{NoiseConfig, SUBPROTOCOL};
+use common::start_server;
+
+const PATTERN: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("noise-test-{}-{}", std::process::id(), name))
+}
+
+fn keypair() -> Keypair {
+    Builder::new(PATTERN.parse().unwrap()).generate_keypair().unwrap()
+}
+
+async fn next_binary(ws: &mut Socket) -> Option<Vec<u8>> {
+    match tokio::time::timeout(Duration::from_secs(5), ws.next()).await.ok()?? {
+        Ok(Message::Binary(data)) => Some(data),
+        _ => None,
+    }
+}
+
+// Connects with the Noise subprotocol and runs the initiator side of XX with `keys`
+async fn connect(addr: SocketAddr, keys: &Keypair) -> (Socket, TransportState) {
+    let mut request = format!("ws://{}", addr).into_client_request().unwrap();
+    request.headers_mut().insert("Sec-WebSocket-Protocol", SUBPROTOCOL.parse().unwrap());
+    let (mut ws, response) = connect_async(request).await.unwrap();
+    assert_eq!(response.headers()["Sec-WebSocket-Protocol"], SUBPROTOCOL);
+
+    let mut noise = Builder::new(PATTERN.parse().unwrap())
+        .local_private_key(&keys.private)
+        .build_initiator()
+        .unwrap();
+    let mut buffer = vec![0u8; 65535];
+    let len = noise.write_message(&[], &mut buffer).unwrap();
+    ws.send(Message::Binary(buffer[..len].to_vec())).await.unwrap();
+    noise.read_message(&next_binary(&mut ws).await.unwrap(), &mut buffer).unwrap();
+    let len = noise.write_message(&[], &mut buffer).unwrap();
+    ws.send(Message::Binary(buffer[..len].to_vec())).await.unwrap();
+
+    (ws, noise.into_transport_mode().unwrap())
+}
+
+#[test]
+fn the_static_key_is_generated_once_and_reloaded() {
+    let path = temp_path("static.key");
+    let _ = std::fs::remove_file(&path);
+    let generated = NoiseConfig::load_or_generate(&path).unwrap();
+    let reloaded = NoiseConfig::load_or_generate(&path).unwrap();
+    assert_eq!(generated.public_key, reloaded.public_key);
+    assert_eq!(generated.public_key.len(), 32);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn allowed_clients_get_encrypted_signals_and_others_are_dropped() {
+    let allowed = keypair();
+    let keys_path = temp_path("clients.txt");
+    std::fs::write(&keys_path, format!("{}\n", STANDARD.encode(&allowed.public))).unwrap();
+    std::env::set_var("NOISE_ENABLED", "true");
+    std::env::set_var("NOISE_KEY_PATH", temp_path("server.key"));
+    std::env::set_var("NOISE_CLIENT_KEYS_PATH", &keys_path);
+    let addr = start_server().await;
+
+    let (mut ws, mut transport) = connect(addr, &allowed).await;
+    let frame = next_binary(&mut ws).await.expect("an encrypted greeting");
+    let mut buffer = vec![0u8; 65535];
+    let len = transport.read_message(&frame, &mut buffer).unwrap();
+    let greeting: SignalMessage = serde_json::from_slice(&buffer[..len]).unwrap();
+    assert_eq!(greeting.signal_type, "server-identity");
+
+    let (mut ws, _) = connect(addr, &keypair()).await;
+    assert_eq!(next_binary(&mut ws).await, None);
+}
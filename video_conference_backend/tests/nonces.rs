@@ -0,0 +1,85 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::crypto::{self, NonceCache, NonceError};
+use video_conference_backend::signaling::{handle_secure_offer, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, secure_offer, signal};
+
+#[test]
+fn a_nonce_is_accepted_once_per_key_until_it_expires() {
+    let mut cache = NonceCache::new(Duration::from_millis(50));
+
+    assert_eq!(cache.check(b"key-a", b""), Err(NonceError::Missing));
+    assert_eq!(cache.check(b"key-a", b"nonce"), Ok(()));
+    assert_eq!(cache.check(b"key-a", b"nonce"), Err(NonceError::Replayed));
+    assert_eq!(cache.check(b"key-b", b"nonce"), Ok(()));
+
+    std::thread::sleep(Duration::from_millis(80));
+    assert_eq!(cache.check(b"key-a", b"nonce"), Ok(()));
+}
+
+#[tokio::test]
+async fn a_replayed_offer_is_refused_and_not_relayed() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let message = secure_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes(), |message| {
+        let signature: Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    });
+
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut host_rx, "secure-offer").len(), 1);
+    assert!(payloads(&mut guest_rx, "error").is_empty());
+
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "replayed-nonce");
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
+
+#[tokio::test]
+async fn a_captured_offer_cannot_be_replayed_under_a_fresh_nonce() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let sign = |message: &[u8]| {
+        let signature: Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    };
+
+    // Version 1 signed only the offer and timestamp, so the nonce could be swapped freely
+    let mut version_one = json!({ "offer": { "type": "offer", "sdp": "v=0\r\n" }, "timestamp": chrono::Utc::now().timestamp() });
+    version_one["signature"] = json!(sign(crypto::canonicalize(&version_one).as_bytes()));
+    version_one["public_key"] = json!(public_key.as_bytes());
+    version_one["signature_version"] = json!(1);
+    let version_one = signal(SignalKind::SecureOffer, version_one);
+    let version_two = secure_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes(), sign);
+
+    for (mut message, code) in [(version_one, "SIG_UNSUPPORTED_VERSION"), (version_two, "SIG_VERIFY_FAILED")] {
+        let mut payload: serde_json::Value = parsed(&message);
+        payload["nonce"] = json!(rand::random::<[u8; 16]>());
+        message.payload = payload.to_string();
+        handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], code);
+    }
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::signaling::{handle_secure_offer, SharedState, SignalingState};
+use video_conference_backend::models::SignalMessage;
+use common::{add_member, drain, parsed, payloads, secure_offer};
+
+// A secure-offer signed by `signing_key`, with the signature encoded by `encode`
+fn offer(signing_key: &SigningKey, encode: impl Fn(&Signature) -> Vec<u8>) -> SignalMessage {
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    secure_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes(), |message| {
+        encode(&signing_key.sign(message))
+    })
+}
+
+#[tokio::test]
+async fn der_signatures_are_relayed_and_bad_signatures_reported_to_the_sender() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let message = offer(&signing_key, |signature| signature.to_der().as_bytes().to_vec());
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut guest_rx, "error").is_empty());
+    assert_eq!(payloads(&mut host_rx, "secure-offer").len(), 1);
+
+    let message = offer(&signing_key, |signature| signature.to_bytes()[..40].to_vec());
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    let other_key = SigningKey::random(&mut OsRng);
+    let mut message = offer(&other_key, |signature| signature.to_bytes().to_vec());
+    let mut forged: serde_json::Value = parsed(&message);
+    forged["public_key"] = json!(signing_key.verifying_key().to_encoded_point(false).as_bytes());
+    message.payload = forged.to_string();
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+
+    let errors = payloads(&mut guest_rx, "error");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0]["code"], "SIG_INVALID_LENGTH");
+    assert_eq!(errors[1]["code"], "SIG_VERIFY_FAILED");
+    assert!(errors[0]["message"].as_str().unwrap().contains("Unrecognized signature encoding"));
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use std::net::SocketAddr;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Error;
+use common::{start_server, upgrade_request};
+
+// HTTP status of the upgrade response for a handshake from `origin`
+async fn upgrade_status(addr: SocketAddr, origin: Option<&str>) -> StatusCode {
+    let mut request = upgrade_request(addr);
+    if let Some(origin) = origin {
+        request.headers_mut().insert("Origin", origin.parse().unwrap());
+    }
+
+    match connect_async(request).await {
+        Ok((_, response)) => response.status(),
+        Err(Error::Http(response)) => response.status(),
+        Err(e) => panic!("handshake failed: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn only_allowlisted_origins_may_upgrade() {
+    std::env::set_var("ALLOWED_ORIGINS", "https://meet.example.com/, https://app.example.com");
+    let addr = start_server().await;
+
+    assert_eq!(upgrade_status(addr, Some("https://meet.example.com")).await, StatusCode::SWITCHING_PROTOCOLS);
+    assert_eq!(upgrade_status(addr, Some("HTTPS://APP.EXAMPLE.COM")).await, StatusCode::SWITCHING_PROTOCOLS);
+    assert_eq!(upgrade_status(addr, None).await, StatusCode::SWITCHING_PROTOCOLS);
+
+    assert_eq!(upgrade_status(addr, Some("https://evil.example.com")).await, StatusCode::FORBIDDEN);
+}
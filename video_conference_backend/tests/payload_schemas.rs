@@ -0,0 +1,60 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::schema::{self, SchemaError};
+use video_conference_backend::models::{Payload, SignalKind};
+use video_conference_backend::signaling::{limits, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, secure_offer, signal};
+
+// Validates `payload` the way dispatch does, reading it once for its kind
+fn validate(kind: SignalKind, payload: &str) -> Result<(), SchemaError> {
+    let mut message = signal(kind, json!({}));
+    message.payload = payload.to_string();
+    schema::validate(kind, &Payload::read(kind, &message))
+}
+
+#[test]
+fn well_formed_payloads_pass() {
+    let offer = secure_offer(json!({ "type": "offer", "sdp": "v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\n" }), &[4; 65], |_| vec![1; 64]);
+    assert_eq!(validate(SignalKind::SecureOffer, &offer.payload), Ok(()));
+    assert_eq!(validate(SignalKind::Presence, r#"{"status":"away"}"#), Ok(()));
+    // Signal types without a schema are left to their handlers
+    assert_eq!(validate(SignalKind::Unknown, "not json at all"), Ok(()));
+}
+
+#[test]
+fn violations_point_at_the_offending_field() {
+    let mut offer: serde_json::Value = parsed(&secure_offer(json!({ "type": "answer", "sdp": "o=- 1 1 IN IP4 0.0.0.0" }), &[4; 65], |_| vec![1; 64]));
+    offer["nonce"] = json!([]);
+    let error = validate(SignalKind::SecureOffer, &offer.to_string()).unwrap_err();
+    let mut paths: Vec<_> = error.violations.iter().map(|violation| violation.path.as_str()).collect();
+    paths.sort();
+    assert_eq!(paths, ["/nonce", "/offer/sdp", "/offer/type"]);
+
+    let error = validate(SignalKind::Presence, r#"{"status":"asleep"}"#).unwrap_err();
+    assert_eq!(error.code(), "invalid-payload");
+    assert!(error.message().starts_with("Invalid presence payload at /status"), "{}", error.message());
+
+    let error = validate(SignalKind::Presence, "{").unwrap_err();
+    assert!(error.message().contains("not valid JSON"), "{}", error.message());
+}
+
+#[tokio::test]
+async fn malformed_payloads_are_reported_before_any_handler_runs() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let valid = signal(SignalKind::Kick, json!({ "client_id": "client-2" }));
+    assert!(limits::check_payload(SignalKind::Kick, &Payload::read(SignalKind::Kick, &valid), addr, &state).await.unwrap());
+    assert!(payloads(&mut rx, "error").is_empty());
+
+    let invalid = signal(SignalKind::Kick, json!({ "client_id": "", "ban": "yes" }));
+    assert!(!limits::check_payload(SignalKind::Kick, &Payload::read(SignalKind::Kick, &invalid), addr, &state).await.unwrap());
+    let error = payloads(&mut rx, "error").remove(0);
+    assert_eq!(error["code"], "invalid-payload");
+    assert_eq!(error["context"]["signal_type"], "kick");
+    assert_eq!(error["context"]["violations"].as_array().unwrap().len(), 2);
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use tokio_tungstenite::tungstenite::protocol::{Message, Role};
+use tokio_tungstenite::WebSocketStream;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::deflate::{self, DeflateConfig, DeflateStream};
+use video_conference_backend::signaling::{dispatch_signal, SharedState, SignalingState};
+use common::{add_client, payloads, session_token, signal};
+
+const CONFIG: DeflateConfig = DeflateConfig { threshold: 64, level: 6, max_message_size: 64 * 1024 };
+const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+async fn server() -> (WebSocketStream<DeflateStream<DuplexStream>>, DuplexStream) {
+    let (client, server) = tokio::io::duplex(256 * 1024);
+    let mut stream = DeflateStream::new(server);
+    stream.enable(CONFIG);
+    (WebSocketStream::from_raw_socket(stream, Role::Server, None).await, client)
+}
+
+fn sdp() -> String {
+    (0..40).map(|i| format!("a=candidate:{} 1 udp 2122260223 192.0.2.1 54400 typ host\r\n", i)).collect()
+}
+
+fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut compressor = Compress::new(Compression::default(), false);
+    let mut out = Vec::with_capacity(bytes.len() + 64);
+    compressor.compress_vec(bytes, &mut out, FlushCompress::Sync).unwrap();
+    out.truncate(out.len() - 4);
+    out
+}
+
+fn inflate(bytes: &[u8]) -> Vec<u8> {
+    let input = [bytes, &[0x00, 0x00, 0xff, 0xff]].concat();
+    let mut out = Vec::with_capacity(64 * 1024);
+    Decompress::new(false).decompress_vec(&input, &mut out, FlushDecompress::Sync).unwrap();
+    out
+}
+
+fn masked_frame(header: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![header];
+    if payload.len() < 126 {
+        frame.push(0x80 | payload.len() as u8);
+    } else {
+        frame.push(0x80 | 126);
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    }
+    frame.extend_from_slice(&MASK);
+    frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ MASK[i % 4]));
+    frame
+}
+
+async fn read_frame(client: &mut DuplexStream) -> (u8, Vec<u8>) {
+    let mut head = [0u8; 2];
+    client.read_exact(&mut head).await.unwrap();
+    let length = match head[1] & 0x7f {
+        126 => client.read_u16().await.unwrap() as usize,
+        127 => client.read_u64().await.unwrap() as usize,
+        length => length as usize,
+    };
+    let mut payload = vec![0u8; length];
+    client.read_exact(&mut payload).await.unwrap();
+    (head[0], payload)
+}
+
+#[tokio::test]
+async fn large_messages_are_sent_compressed() {
+    let (mut server, mut client) = server().await;
+    let text = sdp();
+
+    server.send(Message::Text(text.clone())).await.unwrap();
+    let (header, payload) = read_frame(&mut client).await;
+
+    assert_eq!(header, 0x80 | 0x40 | 0x1);
+    assert!(payload.len() < text.len() / 4);
+    assert_eq!(inflate(&payload), text.as_bytes());
+}
+
+#[tokio::test]
+async fn messages_under_the_threshold_are_sent_as_they_are() {
+    let (mut server, mut client) = server().await;
+
+    server.send(Message::Text("{\"signal_type\":\"ack\"}".to_string())).await.unwrap();
+    let (header, payload) = read_frame(&mut client).await;
+
+    assert_eq!(header, 0x80 | 0x1);
+    assert_eq!(payload, b"{\"signal_type\":\"ack\"}");
+}
+
+#[tokio::test]
+async fn compressed_client_messages_are_inflated() {
+    let (mut server, mut client) = server().await;
+    let text = sdp();
+    let compressed = compress(text.as_bytes());
+
+    client.write_all(&masked_frame(0x80 | 0x40 | 0x1, &compressed)).await.unwrap();
+    assert_eq!(server.next().await.unwrap().unwrap(), Message::Text(text.clone()));
+
+    // Fragmented, with a ping between the fragments and an uncompressed message after
+    let (first, rest) = compressed.split_at(compressed.len() / 2);
+    client.write_all(&masked_frame(0x40 | 0x1, first)).await.unwrap();
+    client.write_all(&masked_frame(0x80 | 0x9, b"ping")).await.unwrap();
+    client.write_all(&masked_frame(0x80, rest)).await.unwrap();
+    client.write_all(&masked_frame(0x80 | 0x1, b"plain")).await.unwrap();
+
+    assert_eq!(server.next().await.unwrap().unwrap(), Message::Ping(b"ping".to_vec()));
+    assert_eq!(server.next().await.unwrap().unwrap(), Message::Text(text));
+    assert_eq!(server.next().await.unwrap().unwrap(), Message::Text("plain".to_string()));
+}
+
+#[tokio::test]
+async fn oversized_inflation_is_refused() {
+    let (mut server, mut client) = server().await;
+    let bomb = compress(&vec![b'a'; 256 * 1024]);
+
+    client.write_all(&masked_frame(0x80 | 0x40 | 0x2, &bomb)).await.unwrap();
+    assert!(server.next().await.unwrap().is_err());
+}
+
+#[test]
+fn offers_are_accepted_only_when_the_server_can_honour_them() {
+    let offer = |extensions: &str| {
+        let request = Request::builder().header("Sec-WebSocket-Extensions", extensions).body(()).unwrap();
+        deflate::accepts_offer(&request)
+    };
+
+    assert!(offer("permessage-deflate; client_max_window_bits"));
+    assert!(offer("permessage-deflate; server_no_context_takeover; client_max_window_bits=10"));
+    assert!(offer("permessage-deflate; server_max_window_bits=10, permessage-deflate"));
+    assert!(!offer("permessage-deflate; server_max_window_bits=10"));
+    assert!(!offer("permessage-deflate; x-webkit-extra"));
+    assert!(!offer("x-webkit-deflate-frame"));
+}
+
+#[tokio::test]
+async fn compression_stats_are_for_admins() {
+    std::env::set_var("ADMIN_TOKEN", "deflate-admin");
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let token = session_token(&mut inner, addr);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let mut client_id = "client-1".to_string();
+
+    let guess = signal(SignalKind::CompressionStats, json!({ "admin_token": "guess" }));
+    dispatch_signal(&guess, Some(&token), addr, &mut client_id, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "not-admin");
+
+    let stats = signal(SignalKind::CompressionStats, json!({ "admin_token": "deflate-admin" }));
+    dispatch_signal(&stats, Some(&token), addr, &mut client_id, Arc::clone(&state)).await.unwrap();
+    let reply = payloads(&mut rx, "compression-stats").remove(0);
+    assert!(reply["outbound"]["bytes_saved"].is_u64() && reply["inbound"]["messages"].is_u64());
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use p256::ecdsa::SigningKey;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::Message;
+use video_conference_backend::models::message::{JoinRoomPayload, PollCreatePayload, PollPayload, PollVotePayload};
+use video_conference_backend::models::Role;
+use video_conference_backend::signaling::{handle_join_room, polls, SharedState, SignalingState};
+use common::{add_client, payloads, sign_in};
+
+fn join() -> JoinRoomPayload {
+    JoinRoomPayload {
+        room_id: "alpha".to_string(),
+        max_participants: None,
+        password: None,
+        invite_token: None,
+        capabilities: None,
+    }
+}
+
+fn create(anonymous: bool) -> PollCreatePayload {
+    PollCreatePayload {
+        question: " Lunch? ".to_string(),
+        options: vec!["Pizza".to_string(), "Salad".to_string()],
+        anonymous,
+    }
+}
+
+fn vote(poll_id: &str, option: usize) -> PollVotePayload {
+    PollVotePayload { poll_id: poll_id.to_string(), option }
+}
+
+// A room with a host on port 1 and a participant on port 2, each with its own key
+async fn room() -> (SharedState, Vec<(SocketAddr, mpsc::Receiver<Message>)>) {
+    let mut inner = SignalingState::new();
+    let mut clients = Vec::new();
+    for port in 1..=2 {
+        let (addr, rx) = add_client(&mut inner, port);
+        inner.clients.get_mut(&addr).unwrap().public_key = Some(vec![port as u8; 32]);
+        clients.push((addr, rx));
+    }
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    for (addr, _) in &clients {
+        handle_join_room(join(), *addr, Arc::clone(&state)).await.unwrap();
+    }
+    state.lock().await.clients.get_mut(&clients[0].0).unwrap().role = Role::Host;
+    (state, clients)
+}
+
+#[tokio::test]
+async fn each_identity_votes_once_and_results_go_out_on_close() {
+    let (state, mut clients) = room().await;
+    let (host, participant) = (clients[0].0, clients[1].0);
+
+    polls::handle_poll_create(create(false), host, Arc::clone(&state)).await.unwrap();
+    let created = payloads(&mut clients[1].1, "poll-created");
+    assert_eq!(created[0]["question"], "Lunch?");
+    let poll_id = created[0]["poll_id"].as_str().unwrap().to_string();
+
+    polls::handle_poll_vote(vote(&poll_id, 1), participant, Arc::clone(&state)).await.unwrap();
+    polls::handle_poll_vote(vote(&poll_id, 0), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "error")[0]["code"], "already-voted");
+    polls::handle_poll_vote(vote(&poll_id, 0), host, Arc::clone(&state)).await.unwrap();
+
+    polls::handle_poll_results(PollPayload { poll_id: poll_id.clone() }, participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "error")[0]["code"], "poll-open");
+
+    polls::handle_poll_close(PollPayload { poll_id: poll_id.clone() }, host, Arc::clone(&state)).await.unwrap();
+    let results = payloads(&mut clients[1].1, "poll-results");
+    assert_eq!(results[0]["counts"], json!([1, 1]));
+    assert_eq!(results[0]["total"], 2);
+    assert_eq!(results[0]["votes"][1], json!({ "client_id": "client-2", "option": 1 }));
+
+    polls::handle_poll_vote(vote(&poll_id, 0), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "error")[0]["code"], "poll-closed");
+}
+
+#[tokio::test]
+async fn anonymous_results_leave_out_who_voted() {
+    let (state, mut clients) = room().await;
+    let (host, participant) = (clients[0].0, clients[1].0);
+    polls::handle_poll_create(create(true), host, Arc::clone(&state)).await.unwrap();
+    let poll_id = payloads(&mut clients[0].1, "poll-created")[0]["poll_id"].as_str().unwrap().to_string();
+
+    polls::handle_poll_vote(vote(&poll_id, 0), participant, Arc::clone(&state)).await.unwrap();
+    polls::handle_poll_close(PollPayload { poll_id }, host, Arc::clone(&state)).await.unwrap();
+    let results = payloads(&mut clients[0].1, "poll-results");
+    assert_eq!(results[0]["counts"], json!([1, 0]));
+    assert!(results[0].get("votes").is_none());
+}
+
+#[tokio::test]
+async fn only_moderators_create_polls_and_unverified_clients_cannot_vote() {
+    let (state, mut clients) = room().await;
+    let (host, participant) = (clients[0].0, clients[1].0);
+
+    polls::handle_poll_create(create(false), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "error")[0]["code"], "not-moderator");
+
+    let duplicate = PollCreatePayload { options: vec!["Pizza".to_string(), "Pizza ".to_string()], ..create(false) };
+    polls::handle_poll_create(duplicate, host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[0].1, "error")[0]["code"], "invalid-poll");
+
+    polls::handle_poll_create(create(false), host, Arc::clone(&state)).await.unwrap();
+    let poll_id = payloads(&mut clients[1].1, "poll-created")[0]["poll_id"].as_str().unwrap().to_string();
+    state.lock().await.clients.get_mut(&participant).unwrap().verified = false;
+    polls::handle_poll_vote(vote(&poll_id, 0), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[1].1, "error")[0]["code"], "unverified-sender");
+}
+
+#[tokio::test]
+async fn a_key_in_either_encoding_or_a_signed_in_user_votes_once() {
+    let (state, mut clients) = room().await;
+    let host = clients[0].0;
+    polls::handle_poll_create(create(false), host, Arc::clone(&state)).await.unwrap();
+    let poll_id = payloads(&mut clients[0].1, "poll-created")[0]["poll_id"].as_str().unwrap().to_string();
+
+    let key = *SigningKey::from_slice(&[7; 32]).unwrap().verifying_key();
+    let mut inner = state.lock().await;
+    let (uncompressed, mut uncompressed_rx) = add_client(&mut inner, 3);
+    let (compressed, mut compressed_rx) = add_client(&mut inner, 4);
+    inner.clients.get_mut(&uncompressed).unwrap().public_key = Some(key.to_encoded_point(false).as_bytes().to_vec());
+    inner.clients.get_mut(&compressed).unwrap().public_key = Some(key.to_encoded_point(true).as_bytes().to_vec());
+    let (laptop, mut laptop_rx) = add_client(&mut inner, 5);
+    let (phone, mut phone_rx) = add_client(&mut inner, 6);
+    inner.clients.get_mut(&laptop).unwrap().public_key = Some(vec![5; 32]);
+    inner.clients.get_mut(&phone).unwrap().public_key = Some(vec![6; 32]);
+    sign_in(&mut inner, laptop, "alice");
+    sign_in(&mut inner, phone, "alice");
+    drop(inner);
+    for addr in [uncompressed, compressed, laptop, phone] {
+        handle_join_room(join(), addr, Arc::clone(&state)).await.unwrap();
+    }
+
+    polls::handle_poll_vote(vote(&poll_id, 0), uncompressed, Arc::clone(&state)).await.unwrap();
+    polls::handle_poll_vote(vote(&poll_id, 1), compressed, Arc::clone(&state)).await.unwrap();
+    polls::handle_poll_vote(vote(&poll_id, 0), laptop, Arc::clone(&state)).await.unwrap();
+    polls::handle_poll_vote(vote(&poll_id, 1), phone, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut uncompressed_rx, "error").is_empty());
+    assert_eq!(payloads(&mut compressed_rx, "error")[0]["code"], "already-voted");
+    assert!(payloads(&mut laptop_rx, "error").is_empty());
+    assert_eq!(payloads(&mut phone_rx, "error")[0]["code"], "already-voted");
+
+    polls::handle_poll_close(PollPayload { poll_id }, host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut clients[0].1, "poll-results")[0]["counts"], json!([2, 0]));
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Presence, SignalKind};
+use video_conference_backend::signaling::{dispatch_signal, roster, SharedState, SignalingState};
+use common::{add_client, add_member, parsed, payloads, signal};
+
+#[tokio::test]
+async fn presence_changes_reach_the_rest_of_the_room() {
+    let mut inner = SignalingState::new();
+    let (speaker, mut speaker_rx) = add_member(&mut inner, 1, "alpha");
+    let (_listener, mut listener_rx) = add_member(&mut inner, 2, "alpha");
+    let (_outsider, mut outsider_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let update = signal(SignalKind::Presence, json!({ "status": "screen-sharing" }));
+    roster::handle_presence(parsed(&update), speaker, Arc::clone(&state)).await.unwrap();
+
+    let heard = payloads(&mut listener_rx, "presence");
+    assert_eq!(heard[0]["client_id"], "client-1");
+    assert_eq!(heard[0]["status"], "screen-sharing");
+    assert!(payloads(&mut speaker_rx, "presence").is_empty());
+    assert!(payloads(&mut outsider_rx, "presence").is_empty());
+    assert_eq!(state.lock().await.clients[&speaker].participant_info().presence, Presence::ScreenSharing);
+}
+
+#[tokio::test]
+async fn unknown_statuses_are_refused() {
+    let mut inner = SignalingState::new();
+    let (speaker, mut speaker_rx) = add_member(&mut inner, 1, "alpha");
+    let (_listener, mut listener_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let update = signal(SignalKind::Presence, json!({ "status": "dancing" }));
+    let mut client_id = "client-1".to_string();
+    dispatch_signal(&update, None, speaker, &mut client_id, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut speaker_rx, "error")[0]["code"], "invalid-payload");
+    assert!(payloads(&mut listener_rx, "presence").is_empty());
+    assert_eq!(state.lock().await.clients[&speaker].presence, Presence::Active);
+}
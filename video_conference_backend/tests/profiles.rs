@@ -0,0 +1,48 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{roster, SharedState, SignalingState};
+use common::{add_member, parsed, payloads, signal};
+
+#[tokio::test]
+async fn profiles_are_trimmed_and_shared_with_the_room() {
+    let mut inner = SignalingState::new();
+    let (alice, mut alice_rx) = add_member(&mut inner, 1, "alpha");
+    let (_bob, mut bob_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let profile = signal(SignalKind::SetProfile, json!({
+        "display_name": "  Alice  ",
+        "avatar_url": "https://example.com/alice.png",
+        "metadata": { "team": "blue" },
+    }));
+    roster::handle_set_profile(parsed(&profile), alice, Arc::clone(&state)).await.unwrap();
+
+    let own = payloads(&mut alice_rx, "profile-updated").remove(0);
+    assert_eq!(own["participant"]["display_name"], "Alice");
+    let seen = payloads(&mut bob_rx, "peer-updated").remove(0);
+    assert_eq!(seen["participant"]["client_id"], "client-1");
+    assert_eq!(seen["participant"]["metadata"]["team"], "blue");
+}
+
+#[tokio::test]
+async fn invalid_profiles_are_refused_and_not_shared() {
+    let mut inner = SignalingState::new();
+    let (alice, mut alice_rx) = add_member(&mut inner, 1, "alpha");
+    let (_bob, mut bob_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for profile in [
+        json!({ "display_name": "   " }),
+        json!({ "avatar_url": "http://example.com/alice.png" }),
+        json!({ "metadata": { "blob": "x".repeat(5000) } }),
+    ] {
+        roster::handle_set_profile(parsed(&signal(SignalKind::SetProfile, profile)), alice, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut alice_rx, "error")[0]["code"], "invalid-profile");
+    }
+    assert!(payloads(&mut bob_rx, "peer-updated").is_empty());
+    assert!(state.lock().await.clients[&alice].profile.display_name.is_none());
+}
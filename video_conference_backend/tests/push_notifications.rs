@@ -0,0 +1,93 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::push::{CallPush, Notifier, PushEvent, PushResult};
+use video_conference_backend::signaling::{calls, SharedState, SignalingState};
+use common::{add_client, call, invite, payloads, sign_in};
+
+#[derive(Default)]
+struct RecordingNotifier {
+    pushes: std::sync::Mutex<Vec<CallPush>>,
+}
+
+impl Notifier for RecordingNotifier {
+    fn notify(&self, push: &CallPush) -> PushResult<()> {
+        self.pushes.lock().unwrap().push(push.clone());
+        Ok(())
+    }
+}
+
+impl RecordingNotifier {
+    fn events(&self) -> Vec<(PushEvent, String)> {
+        self.pushes.lock().unwrap().iter().map(|push| (push.event, push.call_id.clone())).collect()
+    }
+}
+
+#[tokio::test]
+async fn an_offline_callee_is_pushed_and_rings_once_it_signs_in() {
+    let notifier = Arc::new(RecordingNotifier::default());
+    let mut inner = SignalingState::new();
+    inner.notifier = Some(notifier.clone());
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    let (phone, mut phone_rx) = add_client(&mut inner, 2);
+    sign_in(&mut inner, caller, "alice");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    let pushed = notifier.pushes.lock().unwrap()[0].clone();
+    assert_eq!(pushed.event, PushEvent::Incoming);
+    assert_eq!(pushed.callee, "bob");
+    assert_eq!(pushed.caller, "alice");
+    assert_eq!(pushed.caller_id, "client-1");
+
+    let mut inner = state.lock().await;
+    sign_in(&mut inner, phone, "bob");
+    calls::signed_in(&mut inner, phone).await.unwrap();
+    drop(inner);
+    let rung = payloads(&mut phone_rx, "call-invite");
+    assert_eq!(rung[0]["call_id"], "call-1");
+    assert_eq!(rung[0]["caller_user"], "alice");
+    assert!(rung[0]["expires_in_ms"].as_u64().unwrap() > 0);
+
+    calls::handle_call_answer(call("call-1"), true, phone, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut caller_rx, "call-accept")[0]["client_id"], "client-2");
+    assert_eq!(notifier.events(), [(PushEvent::Incoming, "call-1".to_string()), (PushEvent::Withdrawn, "call-1".to_string())]);
+}
+
+#[tokio::test]
+async fn connected_callees_are_not_pushed_and_cancelled_pushes_are_withdrawn() {
+    let notifier = Arc::new(RecordingNotifier::default());
+    let mut inner = SignalingState::new();
+    inner.notifier = Some(notifier.clone());
+    let (caller, _caller_rx) = add_client(&mut inner, 1);
+    let (phone, _phone_rx) = add_client(&mut inner, 2);
+    sign_in(&mut inner, caller, "alice");
+    sign_in(&mut inner, phone, "bob");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_cancel(call("call-1"), caller, Arc::clone(&state)).await.unwrap();
+    assert!(notifier.events().is_empty());
+
+    calls::handle_call_invite(invite("call-2", "carol"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_cancel(call("call-2"), caller, Arc::clone(&state)).await.unwrap();
+    assert_eq!(notifier.events(), [(PushEvent::Incoming, "call-2".to_string()), (PushEvent::Withdrawn, "call-2".to_string())]);
+}
+
+#[tokio::test]
+async fn an_invite_refused_for_a_taken_call_id_is_not_pushed() {
+    let notifier = Arc::new(RecordingNotifier::default());
+    let mut inner = SignalingState::new();
+    inner.notifier = Some(notifier.clone());
+    let (caller, mut caller_rx) = add_client(&mut inner, 1);
+    sign_in(&mut inner, caller, "alice");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    calls::handle_call_invite(invite("call-1", "bob"), caller, Arc::clone(&state)).await.unwrap();
+    calls::handle_call_invite(invite("call-1", "carol"), caller, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(payloads(&mut caller_rx, "error")[0]["code"], "invalid-call");
+    let callees: Vec<_> = notifier.pushes.lock().unwrap().iter().map(|push| push.callee.clone()).collect();
+    assert_eq!(callees, ["bob"]);
+}
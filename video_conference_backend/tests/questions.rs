@@ -0,0 +1,149 @@
+mod common;
+
+use p256::ecdsa::SigningKey;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::message::{JoinRoomPayload, QuestionAskPayload, QuestionModeratePayload, QuestionUpvotePayload};
+use video_conference_backend::models::{QuestionAction, Role};
+use video_conference_backend::signaling::{handle_join_room, questions, SharedState, SignalingState};
+use common::{add_client, drain, payloads, sign_in};
+
+fn join() -> JoinRoomPayload {
+    JoinRoomPayload {
+        room_id: "alpha".to_string(),
+        max_participants: None,
+        password: None,
+        invite_token: None,
+        capabilities: None,
+    }
+}
+
+fn ask(text: &str) -> QuestionAskPayload {
+    QuestionAskPayload { text: text.to_string() }
+}
+
+fn moderate(question_id: &str, action: QuestionAction) -> QuestionModeratePayload {
+    QuestionModeratePayload { question_id: question_id.to_string(), action }
+}
+
+fn upvote(question_id: &str) -> QuestionUpvotePayload {
+    QuestionUpvotePayload { question_id: question_id.to_string() }
+}
+
+fn texts(queue: &Value) -> Vec<&str> {
+    queue["questions"].as_array().unwrap().iter().map(|question| question["text"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn approved_questions_are_ordered_by_upvotes_for_everyone() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (asker, mut asker_rx) = add_client(&mut inner, 2);
+    let (other, mut other_rx) = add_client(&mut inner, 3);
+    let (late, mut late_rx) = add_client(&mut inner, 4);
+    for (port, addr) in [host, asker, other, late].into_iter().enumerate() {
+        inner.clients.get_mut(&addr).unwrap().public_key = Some(vec![port as u8; 32]);
+    }
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    for addr in [host, asker, other] {
+        handle_join_room(join(), addr, Arc::clone(&state)).await.unwrap();
+    }
+    state.lock().await.clients.get_mut(&host).unwrap().role = Role::Host;
+
+    questions::handle_question_ask(ask("First?"), asker, Arc::clone(&state)).await.unwrap();
+    questions::handle_question_ask(ask("Second?"), asker, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut other_rx, "question").is_empty());
+    assert_eq!(payloads(&mut asker_rx, "question").len(), 2);
+    let ids: Vec<String> = payloads(&mut host_rx, "question")
+        .iter()
+        .map(|shown| shown["question"]["question_id"].as_str().unwrap().to_string())
+        .collect();
+
+    questions::handle_question_upvote(upvote(&ids[1]), other, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut other_rx, "error")[0]["code"], "question-not-open");
+    for id in &ids {
+        questions::handle_question_moderate(moderate(id, QuestionAction::Approve), host, Arc::clone(&state)).await.unwrap();
+    }
+    // The room sees approved questions whole; the asker already had them
+    let shown = payloads(&mut other_rx, "question");
+    assert_eq!(shown[0]["question"]["text"], "First?");
+    assert_eq!(shown[1]["question"]["status"], "approved");
+    assert_eq!(payloads(&mut asker_rx, "question-updated")[1]["status"], "approved");
+
+    questions::handle_question_upvote(upvote(&ids[1]), other, Arc::clone(&state)).await.unwrap();
+    questions::handle_question_upvote(upvote(&ids[1]), other, Arc::clone(&state)).await.unwrap();
+    let signals = drain(&mut other_rx);
+    assert_eq!(signals[0].0, "question-updated");
+    assert_eq!(signals[0].1, json!({ "room_id": "alpha", "question_id": ids[1], "upvotes": 1, "status": "approved" }));
+    assert_eq!(signals[1].1["code"], "already-upvoted");
+
+    handle_join_room(join(), late, Arc::clone(&state)).await.unwrap();
+    let queue = payloads(&mut late_rx, "question-queue").pop().unwrap();
+    assert_eq!(texts(&queue), ["Second?", "First?"]);
+    assert_eq!(queue["questions"][0]["upvotes"], 1);
+
+    questions::handle_question_moderate(moderate(&ids[1], QuestionAction::Answer), host, Arc::clone(&state)).await.unwrap();
+    questions::handle_question_moderate(moderate(&ids[0], QuestionAction::Dismiss), host, Arc::clone(&state)).await.unwrap();
+    let signals = drain(&mut late_rx);
+    assert_eq!(signals[0].0, "question-updated");
+    assert_eq!(signals[0].1["status"], "answered");
+    assert_eq!(signals[1], ("question-dismissed".to_string(), json!({ "room_id": "alpha", "question_id": ids[0] })));
+
+    questions::handle_question_sync(late, Arc::clone(&state)).await.unwrap();
+    let queue = payloads(&mut late_rx, "question-queue").pop().unwrap();
+    assert_eq!(texts(&queue), ["Second?"]);
+    assert_eq!(queue["questions"][0]["status"], "answered");
+}
+
+#[tokio::test]
+async fn a_key_in_either_encoding_or_a_signed_in_user_upvotes_once() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let key = *SigningKey::from_slice(&[7; 32]).unwrap().verifying_key();
+    let (uncompressed, mut uncompressed_rx) = add_client(&mut inner, 2);
+    let (compressed, mut compressed_rx) = add_client(&mut inner, 3);
+    inner.clients.get_mut(&uncompressed).unwrap().public_key = Some(key.to_encoded_point(false).as_bytes().to_vec());
+    inner.clients.get_mut(&compressed).unwrap().public_key = Some(key.to_encoded_point(true).as_bytes().to_vec());
+    let (laptop, mut laptop_rx) = add_client(&mut inner, 4);
+    let (phone, mut phone_rx) = add_client(&mut inner, 5);
+    inner.clients.get_mut(&laptop).unwrap().public_key = Some(vec![4; 32]);
+    inner.clients.get_mut(&phone).unwrap().public_key = Some(vec![5; 32]);
+    sign_in(&mut inner, laptop, "alice");
+    sign_in(&mut inner, phone, "alice");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    for addr in [host, uncompressed, compressed, laptop, phone] {
+        handle_join_room(join(), addr, Arc::clone(&state)).await.unwrap();
+    }
+    state.lock().await.clients.get_mut(&host).unwrap().role = Role::Host;
+
+    questions::handle_question_ask(ask("Why?"), host, Arc::clone(&state)).await.unwrap();
+    let id = payloads(&mut host_rx, "question")[0]["question"]["question_id"].as_str().unwrap().to_string();
+    questions::handle_question_moderate(moderate(&id, QuestionAction::Approve), host, Arc::clone(&state)).await.unwrap();
+
+    for addr in [uncompressed, compressed, laptop, phone] {
+        questions::handle_question_upvote(upvote(&id), addr, Arc::clone(&state)).await.unwrap();
+    }
+    assert!(payloads(&mut uncompressed_rx, "error").is_empty());
+    assert_eq!(payloads(&mut compressed_rx, "error")[0]["code"], "already-upvoted");
+    assert!(payloads(&mut laptop_rx, "error").is_empty());
+    assert_eq!(payloads(&mut phone_rx, "error")[0]["code"], "already-upvoted");
+    assert_eq!(payloads(&mut host_rx, "question-updated").last().unwrap()["upvotes"], 2);
+}
+
+#[tokio::test]
+async fn only_moderators_moderate_and_blank_questions_are_refused() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    state.lock().await.clients.get_mut(&a).unwrap().role = Role::Participant;
+
+    questions::handle_question_ask(ask("   "), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "invalid-question");
+
+    questions::handle_question_ask(ask("Why?"), a, Arc::clone(&state)).await.unwrap();
+    let id = payloads(&mut a_rx, "question")[0]["question"]["question_id"].as_str().unwrap().to_string();
+    questions::handle_question_moderate(moderate(&id, QuestionAction::Approve), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "not-moderator");
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{roster, SharedState, SignalingState};
+use common::{add_member, parsed, payloads, signal};
+
+#[tokio::test]
+async fn hands_queue_in_the_order_they_were_raised() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (first, _first_rx) = add_member(&mut inner, 2, "alpha");
+    let (second, _second_rx) = add_member(&mut inner, 3, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for addr in [second, first, second] {
+        roster::handle_raise_hand(addr, Arc::clone(&state)).await.unwrap();
+    }
+    let updates = payloads(&mut host_rx, "hand-queue");
+    assert_eq!(updates.len(), 2, "raising an already raised hand changes nothing");
+    assert_eq!(updates[1]["queue"], json!(["client-3", "client-2"]));
+
+    let lower = signal(SignalKind::LowerHand, json!({ "client_id": "client-3" }));
+    roster::handle_lower_hand(parsed(&lower), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut host_rx, "hand-queue")[0]["queue"], json!(["client-2"]));
+
+    state.lock().await.leave_room(first);
+    assert_eq!(roster::hand_queue(&*state.lock().await, "alpha"), Vec::<String>::new());
+}
+
+#[tokio::test]
+async fn only_moderators_lower_other_hands() {
+    let mut inner = SignalingState::new();
+    let (_host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (raiser, _raiser_rx) = add_member(&mut inner, 2, "alpha");
+    let (other, mut other_rx) = add_member(&mut inner, 3, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    roster::handle_raise_hand(raiser, Arc::clone(&state)).await.unwrap();
+    let lower = signal(SignalKind::LowerHand, json!({ "client_id": "client-2" }));
+    roster::handle_lower_hand(parsed(&lower), other, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(payloads(&mut other_rx, "error")[0]["code"], "not-moderator");
+    assert_eq!(payloads(&mut host_rx, "hand-queue").len(), 1);
+    assert_eq!(roster::hand_queue(&*state.lock().await, "alpha"), vec!["client-2".to_string()]);
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::ratelimit::{RateLimit, RateLimiter, TokenBucket};
+use video_conference_backend::signaling::limits::check_rate_limit;
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_client, payloads};
+
+#[test]
+fn buckets_allow_a_burst_then_refill_at_the_rate() {
+    let start = Instant::now();
+    let mut bucket = TokenBucket::new(2.0, 3.0, start);
+    assert!((0..3).all(|_| bucket.try_take(start)));
+    assert!(!bucket.try_take(start));
+    assert!(bucket.retry_after() <= Duration::from_millis(500));
+
+    assert!(bucket.try_take(start + Duration::from_millis(500)));
+    assert!(!bucket.try_take(start + Duration::from_millis(500)));
+}
+
+#[test]
+fn violations_are_counted_within_a_window_and_a_zero_rate_is_unlimited() {
+    let window = Duration::from_secs(10);
+    let mut limiter = RateLimiter::new(RateLimit { rate: 0.001, burst: 1.0 });
+    let now = Instant::now();
+    assert_eq!(limiter.check(SignalKind::Presence, now, window), Ok(()));
+    assert_eq!(limiter.check(SignalKind::Presence, now, window).unwrap_err().count, 1);
+    assert_eq!(limiter.check(SignalKind::Presence, now, window).unwrap_err().count, 2);
+    assert_eq!(limiter.check(SignalKind::Presence, now + window * 2, window).unwrap_err().count, 1);
+
+    let mut unlimited = RateLimiter::new(RateLimit { rate: 0.0, burst: 0.0 });
+    assert!((0..1000).all(|_| unlimited.check(SignalKind::Presence, now, window).is_ok()));
+}
+
+#[test]
+fn a_limiter_is_idle_only_once_refilled_and_its_violations_have_expired() {
+    let window = Duration::from_secs(10);
+    let chat = HashMap::from([(SignalKind::Chat, RateLimit { rate: 1.0, burst: 1.0 })]);
+    let mut limiter = RateLimiter::new(RateLimit { rate: 1.0, burst: 2.0 }).with_signal_limits(chat);
+    let now = Instant::now();
+    assert!(limiter.is_idle(now, window));
+
+    assert_eq!(limiter.check(SignalKind::Chat, now, window), Ok(()));
+    assert!(limiter.check(SignalKind::Chat, now, window).is_err());
+    assert!(!limiter.is_idle(now + Duration::from_millis(500), window));
+    assert!(!limiter.is_idle(now + Duration::from_secs(5), window));
+    assert!(limiter.is_idle(now + window + Duration::from_secs(1), window));
+}
+
+#[test]
+fn signal_types_with_a_budget_run_out_without_draining_the_overall_one() {
+    assert_eq!("25/100".parse(), Ok(RateLimit { rate: 25.0, burst: 100.0 }));
+    assert!("25".parse::<RateLimit>().is_err());
+    assert!("fast/100".parse::<RateLimit>().is_err());
+
+    let window = Duration::from_secs(10);
+    let chat = HashMap::from([(SignalKind::Chat, RateLimit { rate: 0.001, burst: 1.0 })]);
+    let mut limiter = RateLimiter::new(RateLimit { rate: 0.001, burst: 2.0 }).with_signal_limits(chat);
+    let now = Instant::now();
+    assert_eq!(limiter.check(SignalKind::Chat, now, window), Ok(()));
+    let violation = limiter.check(SignalKind::Chat, now, window).unwrap_err();
+    assert!(violation.per_signal_type);
+    assert_eq!(limiter.check(SignalKind::Presence, now, window), Ok(()));
+    assert!(!limiter.check(SignalKind::Presence, now, window).unwrap_err().per_signal_type);
+}
+
+#[test]
+fn signal_type_budgets_never_exceed_the_overall_one() {
+    let overall = RateLimit { rate: 0.001, burst: 3.0 };
+    assert_eq!(RateLimit { rate: 25.0, burst: 100.0 }.within(overall), overall);
+    assert_eq!(RateLimit { rate: 0.0, burst: 0.0 }.within(overall), overall);
+    assert_eq!(RateLimit { rate: 0.0005, burst: 1.0 }.within(overall), RateLimit { rate: 0.0005, burst: 1.0 });
+
+    // The built-in budgets fit inside the default 20/40 a connection gets
+    let connection = RateLimit { rate: 20.0, burst: 40.0 };
+    for (kind, limit) in RateLimit::per_signal_type() {
+        assert_eq!(limit.within(connection), limit, "{:?}", kind);
+    }
+
+    let window = Duration::from_secs(10);
+    let ice = HashMap::from([(SignalKind::IceCandidate, RateLimit { rate: 25.0, burst: 100.0 })]);
+    let mut limiter = RateLimiter::new(overall).with_signal_limits(ice);
+    let now = Instant::now();
+    for _ in 0..3 {
+        assert_eq!(limiter.check(SignalKind::IceCandidate, now, window), Ok(()));
+    }
+    assert!(limiter.check(SignalKind::IceCandidate, now, window).unwrap_err().per_signal_type);
+}
+
+#[tokio::test]
+async fn a_client_over_a_budget_is_warned_once_then_disconnected() {
+    std::env::set_var("RATE_LIMIT_PER_SEC", "0.001");
+    std::env::set_var("RATE_LIMIT_BURST", "2");
+    std::env::set_var("RATE_LIMIT_MAX_VIOLATIONS", "3");
+    std::env::set_var("SIGNAL_RATE_LIMITS", "chat=0.001/1");
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let (chatty, mut chatty_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    assert!(check_rate_limit(SignalKind::Chat, chatty, &state).await.unwrap());
+    assert!(!check_rate_limit(SignalKind::Chat, chatty, &state).await.unwrap());
+    assert_eq!(payloads(&mut chatty_rx, "rate-limited")[0]["scope"], "signal-type");
+
+    let mut allowed = Vec::new();
+    for _ in 0..4 {
+        allowed.push(check_rate_limit(SignalKind::Presence, addr, &state).await.unwrap());
+    }
+    assert_eq!(allowed, [true, true, false, false]);
+    let warnings = payloads(&mut rx, "rate-limited");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["scope"], "client");
+    assert_eq!(warnings[0]["signal_type"], "presence");
+
+    assert!(!check_rate_limit(SignalKind::Presence, addr, &state).await.unwrap());
+    assert!(matches!(rx.try_recv(), Ok(Message::Close(Some(_)))));
+}
+
+#[tokio::test]
+async fn reconnecting_from_the_same_address_does_not_refill_its_budget() {
+    let mut inner = SignalingState::new();
+    let (first, _first_rx) = add_client(&mut inner, 1);
+    let stale: std::net::IpAddr = "10.0.0.1".parse().unwrap();
+    inner.ip_rate_limits.insert(stale, RateLimiter::new(RateLimit { rate: 1.0, burst: 1.0 }));
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    // A new address turning up forgets the ones that have refilled
+    assert!(check_rate_limit(SignalKind::Presence, first, &state).await.unwrap());
+    assert!(!state.lock().await.ip_rate_limits.contains_key(&stale));
+
+    let drained = RateLimiter::new(RateLimit { rate: 0.001, burst: 1.0 });
+    state.lock().await.ip_rate_limits.insert(first.ip(), drained);
+    assert!(check_rate_limit(SignalKind::Presence, first, &state).await.unwrap());
+    state.lock().await.remove_client(first);
+    assert!(state.lock().await.ip_rate_limits.contains_key(&first.ip()));
+
+    let (second, mut second_rx) = add_client(&mut *state.lock().await, 2);
+    assert!(!check_rate_limit(SignalKind::Presence, second, &state).await.unwrap());
+    assert_eq!(payloads(&mut second_rx, "rate-limited")[0]["scope"], "ip");
+}
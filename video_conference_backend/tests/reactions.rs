@@ -0,0 +1,56 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::message::ReactionPayload;
+use video_conference_backend::models::Room;
+use video_conference_backend::signaling::{reactions, SharedState, SignalingState};
+use common::{add_member, received_payload};
+
+fn reaction(name: &str) -> ReactionPayload {
+    ReactionPayload { reaction: name.to_string() }
+}
+
+#[tokio::test]
+async fn small_rooms_see_each_reaction() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    reactions::handle_reaction(reaction("clap"), a, Arc::clone(&state)).await.unwrap();
+    let (signal_type, payload) = received_payload(&mut b_rx).unwrap();
+    assert_eq!(signal_type, "reaction");
+    assert_eq!(payload["client_id"], "client-1");
+    assert_eq!(payload["reaction"], "clap");
+    assert_eq!(received_payload(&mut a_rx).unwrap().0, "reaction");
+
+    reactions::handle_reaction(reaction("<script>"), a, Arc::clone(&state)).await.unwrap();
+    let (signal_type, payload) = received_payload(&mut a_rx).unwrap();
+    assert_eq!(signal_type, "error");
+    assert_eq!(payload["code"], "invalid-reaction");
+    assert!(b_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn large_rooms_get_periodic_counts() {
+    let mut inner = SignalingState::new();
+    inner.rooms.insert("alpha".to_string(), Room::new("alpha".to_string(), 100));
+    let mut members: Vec<_> = (1..=50).map(|port| add_member(&mut inner, port, "alpha")).collect();
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for (addr, _) in &members[..3] {
+        reactions::handle_reaction(reaction("clap"), *addr, Arc::clone(&state)).await.unwrap();
+    }
+    reactions::handle_reaction(reaction("heart"), members[3].0, Arc::clone(&state)).await.unwrap();
+    assert!(members.iter_mut().all(|(_, rx)| rx.try_recv().is_err()));
+
+    reactions::flush_reactions(&mut *state.lock().await).await;
+    let (signal_type, payload) = received_payload(&mut members[49].1).unwrap();
+    assert_eq!(signal_type, "reactions");
+    assert_eq!(payload["counts"], serde_json::json!({ "clap": 3, "heart": 1 }));
+
+    // Nothing new, nothing sent
+    reactions::flush_reactions(&mut *state.lock().await).await;
+    assert!(members[49].1.try_recv().is_err());
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::message::IceRestartPayload;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::acks::Delivery;
+use video_conference_backend::signaling::{batching, ordering};
+use video_conference_backend::signaling::renegotiation::{self, NegotiationRole, Renegotiations};
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_member, received, signal};
+
+fn renegotiate(kind: SignalKind, from: u16, to: u16) -> SignalMessage {
+    let sdp_type = if kind == SignalKind::RenegotiateOffer { "offer" } else { "answer" };
+    SignalMessage {
+        sender_id: format!("client-{}", from),
+        target_id: Some(format!("client-{}", to)),
+        ..signal(kind, json!({ "description": { "type": sdp_type, "sdp": "v=0\r\n" } }))
+    }
+}
+
+fn error_code(signal: &SignalMessage) -> String {
+    assert_eq!(signal.signal_type, "error");
+    let payload: serde_json::Value = serde_json::from_str(&signal.payload).unwrap();
+    payload["code"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn answers_close_the_open_offer() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (b, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    renegotiation::handle_renegotiate_offer(&renegotiate(SignalKind::RenegotiateOffer, 1, 2), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut b_rx).unwrap().signal_type, "renegotiate-offer");
+
+    renegotiation::handle_renegotiate_answer(&renegotiate(SignalKind::RenegotiateAnswer, 2, 1), b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut a_rx).unwrap().signal_type, "renegotiate-answer");
+
+    renegotiation::handle_renegotiate_answer(&renegotiate(SignalKind::RenegotiateAnswer, 2, 1), b, Arc::clone(&state)).await.unwrap();
+    assert!(a_rx.try_recv().is_err());
+    assert_eq!(error_code(&received(&mut b_rx).unwrap()), "no-renegotiation");
+}
+
+#[tokio::test]
+async fn crossing_offers_are_glare() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (b, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    renegotiation::handle_renegotiate_offer(&renegotiate(SignalKind::RenegotiateOffer, 1, 2), a, Arc::clone(&state)).await.unwrap();
+    assert!(received(&mut b_rx).is_some());
+
+    renegotiation::handle_renegotiate_offer(&renegotiate(SignalKind::RenegotiateOffer, 2, 1), b, Arc::clone(&state)).await.unwrap();
+    assert!(a_rx.try_recv().is_err());
+    let error = received(&mut b_rx).unwrap();
+    assert_eq!(error_code(&error), "renegotiation-glare");
+
+    // The first offer stands and can still be answered
+    renegotiation::handle_renegotiate_answer(&renegotiate(SignalKind::RenegotiateAnswer, 2, 1), b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut a_rx).unwrap().signal_type, "renegotiate-answer");
+}
+
+#[tokio::test]
+async fn the_impolite_side_wins_glare() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let (b, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    assert_eq!(NegotiationRole::between("client-2", "client-1"), NegotiationRole::Polite);
+    assert_eq!(NegotiationRole::between("client-1", "client-2"), NegotiationRole::Impolite);
+
+    // The polite side offers first, then the impolite side's offer crosses it
+    renegotiation::handle_renegotiate_offer(&renegotiate(SignalKind::RenegotiateOffer, 2, 1), b, Arc::clone(&state)).await.unwrap();
+    assert!(received(&mut a_rx).is_some());
+    renegotiation::handle_renegotiate_offer(&renegotiate(SignalKind::RenegotiateOffer, 1, 2), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut b_rx).unwrap().signal_type, "renegotiate-offer");
+
+    // The polite side rolled back, so only its answer is taken now
+    renegotiation::handle_renegotiate_answer(&renegotiate(SignalKind::RenegotiateAnswer, 1, 2), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(error_code(&received(&mut a_rx).unwrap()), "no-renegotiation");
+    renegotiation::handle_renegotiate_answer(&renegotiate(SignalKind::RenegotiateAnswer, 2, 1), b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(received(&mut a_rx).unwrap().signal_type, "renegotiate-answer");
+}
+
+#[tokio::test]
+async fn ice_restart_drops_held_candidates_and_goes_out_first() {
+    std::env::set_var("ICE_BATCH_INTERVAL_MS", "60000");
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_member(&mut inner, 1, "alpha");
+    let (b, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    // A candidate from a to b waiting to go out with others
+    let recipient = inner.clients.get_mut(&b).unwrap();
+    recipient.features = vec![batching::FEATURE.to_string()];
+    let candidate = json!({ "signal_type": "ice-candidate", "payload": "{}", "sender_id": "client-1", "timestamp": 0, "signature": null, "target_id": null });
+    assert_eq!(batching::deliver(recipient, Message::Text(candidate.to_string())).await, Delivery::Queued);
+    {
+        // And one held behind a message that never arrived
+        let mut reorder = recipient.reorder.lock().unwrap();
+        reorder.assign("client-1");
+        let seq = reorder.assign("client-1");
+        let held = reorder.push("client-1", seq, Message::Text(candidate.to_string()), Instant::now(), Duration::from_secs(60), 32);
+        assert!(held.is_empty());
+    }
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    // Numbered for b as it came in, behind both
+    let mut restart = renegotiate(SignalKind::RenegotiateOffer, 1, 2);
+    restart.signal_type = SignalKind::IceRestart.name().to_string();
+    assert!(ordering::reserve(&mut restart, a, &state).await.is_some());
+    let payload = IceRestartPayload { description: json!({ "type": "offer", "sdp": "v=0\r\n" }), reason: Some("network-change".to_string()) };
+    renegotiation::handle_ice_restart(&restart, payload, a, Arc::clone(&state)).await.unwrap();
+
+    let notice = received(&mut b_rx).unwrap();
+    assert_eq!(notice.signal_type, "ice-restart-started");
+    let notice: serde_json::Value = serde_json::from_str(&notice.payload).unwrap();
+    assert_eq!(notice["client_id"], "client-1");
+    assert_eq!(notice["dropped_candidates"], 2);
+
+    let relayed = received(&mut b_rx).unwrap();
+    assert_eq!(relayed.signal_type, "ice-restart");
+    assert_eq!(relayed.relay_seq, Some(2));
+    assert!(b_rx.try_recv().is_err());
+
+    // It is answered like any other renegotiation
+    renegotiation::handle_renegotiate_answer(&renegotiate(SignalKind::RenegotiateAnswer, 2, 1), b, Arc::clone(&state)).await.unwrap();
+    assert!(b_rx.try_recv().is_err());
+}
+
+#[test]
+fn open_offers_lapse_and_are_forgotten_with_their_peer() {
+    let timeout = Duration::from_secs(30);
+    let start = Instant::now();
+    let mut open = Renegotiations::default();
+
+    assert!(open.offer("a", "b", start, timeout));
+    assert!(!open.offer("b", "a", start + Duration::from_secs(1), timeout));
+    // A lapsed offer no longer blocks the other side
+    assert!(open.offer("b", "a", start + timeout, timeout));
+    assert!(!open.answer("b", "a", start + timeout, timeout));
+
+    // b's offer to a goes when a does
+    open.forget("a");
+    assert!(open.offer("a", "b", start + timeout, timeout));
+}
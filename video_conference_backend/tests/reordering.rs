@@ -0,0 +1,124 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::ordering::{self, ReorderBuffer};
+use video_conference_backend::signaling::{relay_sealed, SharedState, SignalingState};
+use common::{add_member, drain, received, signal};
+
+const TIMEOUT: Duration = Duration::from_millis(250);
+
+fn text(body: &str) -> Message {
+    Message::Text(body.to_string())
+}
+
+#[test]
+fn messages_wait_behind_a_gap_until_it_fills() {
+    let mut buffer = ReorderBuffer::default();
+    let now = Instant::now();
+    assert_eq!((buffer.assign("alice"), buffer.assign("alice"), buffer.assign("bob")), (0, 1, 0));
+
+    assert!(buffer.push("alice", 1, text("second"), now, TIMEOUT, 8).is_empty());
+    assert_eq!(buffer.push("bob", 0, text("bob"), now, TIMEOUT, 8), [text("bob")]);
+    assert_eq!(buffer.push("alice", 0, text("first"), now, TIMEOUT, 8), [text("first"), text("second")]);
+    // Anything already released is late and dropped
+    assert!(buffer.push("alice", 0, text("again"), now, TIMEOUT, 8).is_empty());
+}
+
+#[test]
+fn gaps_are_skipped_when_given_up_on_after_the_timeout_or_once_the_buffer_fills() {
+    let mut buffer = ReorderBuffer::default();
+    let start = Instant::now();
+    (0..4).for_each(|_| { buffer.assign("alice"); });
+    assert!(buffer.push("alice", 1, text("1"), start, TIMEOUT, 8).is_empty());
+    assert_eq!(buffer.skip("alice", 0, start, TIMEOUT, 8), [text("1")]);
+    assert!(buffer.skip("alice", 1, start, TIMEOUT, 8).is_empty());
+
+    assert!(buffer.push("alice", 3, text("3"), start, TIMEOUT, 8).is_empty());
+    assert!(buffer.release_overdue(start + TIMEOUT / 2, TIMEOUT, 8).is_empty());
+    assert_eq!(buffer.release_overdue(start + TIMEOUT, TIMEOUT, 8), [text("3")]);
+
+    let mut small = ReorderBuffer::default();
+    assert!(small.push("alice", 2, text("2"), start, TIMEOUT, 1).is_empty());
+    assert_eq!(small.push("alice", 3, text("3"), start, TIMEOUT, 1), [text("2"), text("3")]);
+}
+
+#[test]
+fn flushing_lets_out_what_came_before_and_forgetting_drops_the_sender() {
+    let mut buffer = ReorderBuffer::default();
+    let now = Instant::now();
+    (0..4).for_each(|_| { buffer.assign("alice"); });
+    for (seq, body) in [(1, "candidate"), (2, "offer")] {
+        assert!(buffer.push("alice", seq, text(body), now, TIMEOUT, 8).is_empty());
+    }
+    assert_eq!(buffer.flush("alice", Some(3), |message| *message == text("candidate")), [text("offer")]);
+    assert_eq!(buffer.push("alice", 3, text("restart"), now, TIMEOUT, 8), [text("restart")]);
+
+    (0..2).for_each(|_| { buffer.assign("alice"); });
+    assert!(buffer.push("alice", 5, text("last"), now, TIMEOUT, 8).is_empty());
+    assert_eq!(buffer.forget("alice"), [text("last")]);
+    assert_eq!(buffer.assign("alice"), 0);
+}
+
+// Two members of room alpha with keys, so sealed messages can pass between them
+async fn sealed_pair() -> (SharedState, std::net::SocketAddr, tokio::sync::mpsc::Receiver<Message>) {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut target_rx) = add_member(&mut inner, 2, "alpha");
+    for (addr, client) in inner.clients.iter_mut() {
+        client.public_key = Some(vec![addr.port() as u8; 65]);
+    }
+    drain(&mut target_rx);
+    (Arc::new(Mutex::new(inner)), sender, target_rx)
+}
+
+fn sealed(text: &str) -> video_conference_backend::models::SignalMessage {
+    let mut sealed = signal(SignalKind::Sealed, json!(text));
+    sealed.sender_id = "client-1".to_string();
+    sealed.target_id = Some("client-2".to_string());
+    sealed
+}
+
+#[tokio::test]
+async fn relayed_messages_are_numbered_per_sender_and_recipient() {
+    let (state, sender, mut target_rx) = sealed_pair().await;
+    for _ in 0..2 {
+        relay_sealed(&sealed("opaque"), sender, Arc::clone(&state)).await.unwrap();
+    }
+    let seqs: Vec<_> = std::iter::from_fn(|| received(&mut target_rx)).map(|relayed| relayed.relay_seq).collect();
+    assert_eq!(seqs, [Some(0), Some(1)]);
+}
+
+#[tokio::test]
+async fn a_message_numbered_as_it_came_in_is_not_overtaken_by_a_later_one() {
+    let (state, sender, mut target_rx) = sealed_pair().await;
+
+    let mut first = sealed("first");
+    first.relay_seq = Some(41);
+    let reservation = ordering::reserve(&mut first, sender, &state).await;
+    assert_eq!(first.relay_seq, Some(0));
+
+    relay_sealed(&sealed("second"), sender, Arc::clone(&state)).await.unwrap();
+    assert!(received(&mut target_rx).is_none());
+
+    relay_sealed(&first, sender, Arc::clone(&state)).await.unwrap();
+    ordering::settle(reservation, &state).await;
+    let relayed: Vec<_> = std::iter::from_fn(|| received(&mut target_rx)).map(|relayed| (relayed.relay_seq, relayed.payload)).collect();
+    assert_eq!(relayed, [(Some(0), json!("first").to_string()), (Some(1), json!("second").to_string())]);
+}
+
+#[tokio::test]
+async fn a_numbered_message_that_is_never_relayed_stops_holding_up_the_rest() {
+    let (state, sender, mut target_rx) = sealed_pair().await;
+
+    let reservation = ordering::reserve(&mut sealed("turned away"), sender, &state).await;
+    relay_sealed(&sealed("next"), sender, Arc::clone(&state)).await.unwrap();
+    assert!(received(&mut target_rx).is_none());
+
+    ordering::settle(reservation, &state).await;
+    assert_eq!(received(&mut target_rx).unwrap().relay_seq, Some(1));
+}
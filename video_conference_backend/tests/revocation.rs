@@ -0,0 +1,66 @@
+mod common;
+
+use p256::ecdsa::SigningKey;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::crypto::{revocation, RevocationList};
+use video_conference_backend::signaling::admin::handle_revoke_keys;
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+const ADMIN_TOKEN: &str = "revocation-admin";
+
+#[test]
+fn revocation_files_skip_comments_and_round_trip_appended_keys() {
+    let path = std::env::temp_dir().join(format!("revoked-keys-test-{}.txt", std::process::id()));
+    std::fs::write(&path, "# leaked laptop\nAQID\n\n").unwrap();
+    revocation::append_to_file(&path, &[vec![4, 5, 6]]).unwrap();
+
+    let revoked = RevocationList::from_file(&path).unwrap();
+    assert_eq!(revoked.len(), 2);
+    assert!(revoked.is_revoked(&[1, 2, 3]));
+    assert!(revoked.is_revoked(&[4, 5, 6]));
+    assert!(!revoked.is_revoked(&[7, 8, 9]));
+
+    std::fs::write(&path, "not base64!\n").unwrap();
+    assert!(RevocationList::from_file(&path).is_err());
+}
+
+#[tokio::test]
+async fn revoking_a_key_disconnects_the_client_holding_it() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    std::env::remove_var("REVOKED_KEYS_PATH");
+    let mut inner = SignalingState::new();
+    let (admin, mut admin_rx) = add_client(&mut inner, 1);
+    let (holder, mut holder_rx) = add_client(&mut inner, 2);
+    inner.clients.get_mut(&holder).unwrap().public_key = Some(vec![1, 2, 3]);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let guess = signal(SignalKind::RevokeKeys, json!({ "admin_token": "guess", "public_keys": [[1, 2, 3]] }));
+    handle_revoke_keys(parsed(&guess), admin, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut admin_rx, "error")[0]["code"], "not-admin");
+    assert!(holder_rx.try_recv().is_err());
+
+    let revoke = signal(SignalKind::RevokeKeys, json!({ "admin_token": ADMIN_TOKEN, "public_keys": [[1, 2, 3]] }));
+    handle_revoke_keys(parsed(&revoke), admin, Arc::clone(&state)).await.unwrap();
+    let revoked = payloads(&mut admin_rx, "keys-revoked").remove(0);
+    assert_eq!(revoked["revoked"], 1);
+    assert_eq!(revoked["disconnected"], 1);
+    assert!(state.lock().await.revoked.is_revoked(&[1, 2, 3]));
+
+    let Ok(Message::Text(error)) = holder_rx.try_recv() else { panic!("expected an error before the close") };
+    assert!(error.contains("key-revoked"));
+    assert!(matches!(holder_rx.try_recv(), Ok(Message::Close(Some(_)))));
+}
+
+#[test]
+fn a_revoked_key_stays_revoked_in_either_encoding() {
+    let key = *SigningKey::from_slice(&[7; 32]).unwrap().verifying_key();
+    let mut revoked = RevocationList::default();
+    assert!(revoked.revoke(key.to_encoded_point(true).as_bytes().to_vec()));
+    assert!(!revoked.revoke(key.to_encoded_point(false).as_bytes().to_vec()));
+    assert!(revoked.is_revoked(key.to_encoded_point(false).as_bytes()));
+}
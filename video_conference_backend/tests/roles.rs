@@ -0,0 +1,48 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Role, SignalKind};
+use video_conference_backend::signaling::{
+    handle_create_invite, handle_join_room, handle_lock_room, SharedState, SignalingState,
+};
+use common::{add_client, add_member, parsed, payloads, signal};
+
+#[tokio::test]
+async fn a_moderator_invite_grants_moderation() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    assert_eq!(state.lock().await.clients[&host].role, Role::Host);
+
+    let invite = signal(SignalKind::CreateInvite, json!({ "role": "moderator" }));
+    handle_create_invite(parsed(&invite), host, Arc::clone(&state)).await.unwrap();
+    let token = payloads(&mut host_rx, "invite-created")[0]["token"].clone();
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "invite_token": token }));
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(state.lock().await.clients[&guest].role, Role::Moderator);
+
+    handle_lock_room(parsed(&signal(SignalKind::LockRoom, json!({ "locked": true }))), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-lock-changed")[0]["locked"], true);
+}
+
+#[tokio::test]
+async fn participants_cannot_moderate_or_hand_out_roles_above_their_own() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (participant, mut participant_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    assert_eq!(state.lock().await.clients[&participant].role, Role::Participant);
+
+    handle_lock_room(parsed(&signal(SignalKind::LockRoom, json!({ "locked": true }))), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut participant_rx, "error")[0]["code"], "not-moderator");
+    handle_create_invite(parsed(&signal(SignalKind::CreateInvite, json!({}))), participant, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut participant_rx, "error")[0]["code"], "not-moderator");
+
+    handle_create_invite(parsed(&signal(SignalKind::CreateInvite, json!({ "role": "host" }))), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut host_rx, "error")[0]["code"], "invalid-role");
+    assert!(!state.lock().await.rooms["alpha"].locked);
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::{
+    handle_create_room, handle_join_room, handle_secure_offer, SharedState, SignalingState,
+};
+use common::{add_client, drain, parsed, payloads, sign_message, signal, unsigned_offer};
+
+// A secure-offer carrying `sdp`, signed the way clients sign offers
+fn offer(sdp: &str, e2ee: bool) -> SignalMessage {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let mut payload = unsigned_offer(json!({ "type": "offer", "sdp": sdp }), public_key.as_bytes());
+    payload["e2ee"] = json!(e2ee);
+    let mut message = signal(SignalKind::SecureOffer, payload);
+    sign_message(&mut message, |message| {
+        let signature: Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    });
+    message
+}
+
+#[tokio::test]
+async fn offers_must_fit_the_room_profile_before_they_are_relayed() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let config = json!({ "audio_only": true, "e2ee_required": true, "max_bitrate_kbps": 64 });
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "config": config }))), host, Arc::clone(&state)).await.unwrap();
+    for addr in [host, guest] {
+        handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), addr, Arc::clone(&state)).await.unwrap();
+    }
+    assert_eq!(payloads(&mut guest_rx, "room-joined")[0]["config"]["max_bitrate_kbps"], 64);
+    drain(&mut host_rx);
+
+    let rejected = [
+        (offer("v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\n", false), "e2ee-required"),
+        (offer("v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\n", true), "audio-only-room"),
+    ];
+    for (message, code) in rejected {
+        handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], code);
+    }
+    assert!(drain(&mut host_rx).is_empty());
+
+    let message = offer("v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\n", true);
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut guest_rx, "error").is_empty());
+    assert_eq!(payloads(&mut host_rx, "secure-offer").len(), 1);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{handle_create_room, handle_list_rooms, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+const ADMIN_TOKEN: &str = "room-discovery-admin";
+
+#[tokio::test]
+async fn listed_rooms_are_public_and_unlisted_ones_need_an_admin() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let mut inner = SignalingState::new();
+    let (host, _host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({ "room_id": "public", "listed": true, "password": "hunter2" }));
+    handle_create_room(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "private" }))), host, Arc::clone(&state)).await.unwrap();
+
+    handle_list_rooms(parsed(&signal(SignalKind::ListRooms, json!({}))), guest, Arc::clone(&state)).await.unwrap();
+    let listing = payloads(&mut guest_rx, "room-list").remove(0);
+    assert_eq!(listing["rooms"].as_array().unwrap().len(), 1);
+    assert_eq!(listing["rooms"][0]["room_id"], "public");
+    assert_eq!(listing["rooms"][0]["password_protected"], true);
+
+    let guess = signal(SignalKind::ListRooms, json!({ "admin_token": "guess" }));
+    handle_list_rooms(parsed(&guess), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-list")[0]["rooms"].as_array().unwrap().len(), 1);
+
+    let admin = signal(SignalKind::ListRooms, json!({ "admin_token": ADMIN_TOKEN }));
+    handle_list_rooms(parsed(&admin), guest, Arc::clone(&state)).await.unwrap();
+    let listing = payloads(&mut guest_rx, "room-list").remove(0);
+    assert_eq!(listing["rooms"][0]["room_id"], "private");
+    assert_eq!(listing["rooms"][1]["room_id"], "public");
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use std::time::{Duration, Instant};
+use video_conference_backend::models::Room;
+use video_conference_backend::rooms::{create_room, sweep_rooms};
+use video_conference_backend::signaling::SignalingState;
+use common::add_member;
+
+const EMPTY_TTL: Duration = Duration::from_secs(300);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+#[test]
+fn idle_rooms_are_closed_and_their_members_walked_out() {
+    let mut state = SignalingState::new();
+    let (idle, _idle_rx) = add_member(&mut state, 1, "idle");
+    let (busy, _busy_rx) = add_member(&mut state, 2, "busy");
+
+    let later = Instant::now() + IDLE_TIMEOUT;
+    state.rooms.get_mut("busy").unwrap().last_activity = later;
+    let closed = sweep_rooms(&mut state, later, EMPTY_TTL, IDLE_TIMEOUT);
+
+    assert_eq!(closed.len(), 1);
+    assert_eq!((closed[0].0.as_str(), closed[0].1), ("idle", "idle"));
+    assert_eq!(closed[0].2[0].client_id, "client-1");
+    assert!(!state.rooms.contains_key("idle"));
+    assert!(state.clients[&idle].room_id.is_none());
+    assert_eq!(state.clients[&busy].room_id.as_deref(), Some("busy"));
+}
+
+#[test]
+fn empty_rooms_are_kept_until_their_ttl_runs_out() {
+    let mut state = SignalingState::new();
+    create_room(&mut state, Room::new("alpha".to_string(), 8)).unwrap();
+
+    let now = Instant::now();
+    assert!(sweep_rooms(&mut state, now, EMPTY_TTL, IDLE_TIMEOUT).is_empty());
+    assert!(state.rooms.contains_key("alpha"));
+
+    let closed = sweep_rooms(&mut state, now + EMPTY_TTL, EMPTY_TTL, IDLE_TIMEOUT);
+    assert_eq!(closed[0].0, "alpha");
+    assert!(closed[0].2.is_empty() && state.rooms.is_empty());
+}
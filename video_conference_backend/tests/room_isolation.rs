@@ -0,0 +1,65 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalMessage;
+use video_conference_backend::signaling::{broadcast_to_verified_peers, SharedState, SignalingState};
+use common::add_member;
+
+fn ice_candidate() -> SignalMessage {
+    SignalMessage {
+        signal_type: "ice-candidate".to_string(),
+        payload: "{}".to_string(),
+        sender_id: "client-1".to_string(),
+        timestamp: 0,
+        signature: None,
+        target_id: None,
+        seq: None,
+        relay_seq: None,
+        server_signature: None,
+        session_token: None,
+        correlation_id: None,
+        message_id: None,
+    }
+}
+
+#[tokio::test]
+async fn broadcast_stays_within_sender_room() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut same_room_rx) = add_member(&mut inner, 2, "alpha");
+    let (_, mut other_room_rx) = add_member(&mut inner, 3, "beta");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    broadcast_to_verified_peers(&ice_candidate(), sender, Arc::clone(&state)).await.unwrap();
+
+    assert!(same_room_rx.try_recv().is_ok());
+    assert!(other_room_rx.try_recv().is_err());
+    assert!(sender_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn broadcast_skips_unverified_room_members() {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (peer, mut peer_rx) = add_member(&mut inner, 2, "alpha");
+    inner.clients.get_mut(&peer).unwrap().verified = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    broadcast_to_verified_peers(&ice_candidate(), sender, Arc::clone(&state)).await.unwrap();
+
+    assert!(peer_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn broadcast_without_room_reaches_nobody() {
+    let mut inner = SignalingState::new();
+    let (sender, _sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut peer_rx) = add_member(&mut inner, 2, "alpha");
+    inner.leave_room(sender);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    broadcast_to_verified_peers(&ice_candidate(), sender, Arc::clone(&state)).await.unwrap();
+
+    assert!(peer_rx.try_recv().is_err());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{handle_create_room, handle_join_room, handle_lock_room, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+#[tokio::test]
+async fn a_locked_room_turns_away_newcomers_until_unlocked() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }));
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "alpha" }))), host, Arc::clone(&state)).await.unwrap();
+    handle_join_room(parsed(&join), host, Arc::clone(&state)).await.unwrap();
+
+    handle_lock_room(parsed(&signal(SignalKind::LockRoom, json!({}))), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut host_rx, "room-lock-changed")[0]["locked"], true);
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "join-rejected")[0]["reason"], "room-locked");
+
+    // The host is already inside, so rejoining is still allowed
+    handle_join_room(parsed(&join), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut host_rx, "room-joined").len(), 1);
+
+    handle_lock_room(parsed(&signal(SignalKind::LockRoom, json!({ "locked": false }))), host, Arc::clone(&state)).await.unwrap();
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-joined").len(), 1);
+}
+
+#[tokio::test]
+async fn only_the_host_can_lock() {
+    let mut inner = SignalingState::new();
+    let (host, _host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }));
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "alpha" }))), host, Arc::clone(&state)).await.unwrap();
+    handle_join_room(parsed(&join), host, Arc::clone(&state)).await.unwrap();
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+
+    handle_lock_room(parsed(&signal(SignalKind::LockRoom, json!({}))), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "not-moderator");
+    assert!(!state.lock().await.rooms["alpha"].locked);
+}
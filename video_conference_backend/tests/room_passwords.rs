@@ -0,0 +1,62 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::rooms::{self, JoinError};
+use video_conference_backend::signaling::{handle_create_room, handle_join_room, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+#[tokio::test]
+async fn protected_rooms_admit_only_the_right_password() {
+    let mut inner = SignalingState::new();
+    let (host, mut host_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "password": "hunter2" }));
+    handle_create_room(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut host_rx, "room-created")[0]["password_protected"], true);
+    let stored = state.lock().await.rooms["alpha"].password_hash.clone().unwrap();
+    assert!(!stored.contains("hunter2"));
+
+    for (password, reason) in [(None, "password-required"), (Some("hunter3"), "invalid-password")] {
+        let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "password": password }));
+        handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut guest_rx, "join-rejected")[0]["reason"], reason);
+    }
+    assert!(state.lock().await.clients[&guest].room_id.is_none());
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "password": "hunter2" }));
+    handle_join_room(parsed(&join), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-joined").len(), 1);
+}
+
+#[tokio::test]
+async fn creating_an_existing_room_is_refused() {
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_client(&mut inner, 1);
+    let (b, mut b_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "alpha" }))), a, Arc::clone(&state)).await.unwrap();
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "password": "x" }))), b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut b_rx, "error")[0]["code"], "room-exists");
+    assert!(state.lock().await.rooms["alpha"].password_hash.is_none());
+}
+
+#[test]
+fn a_password_checked_against_a_replaced_room_does_not_let_the_joiner_in() {
+    let mut state = SignalingState::new();
+    let (host, _host_rx) = add_client(&mut state, 1);
+    state.join_room(host, "alpha", 8).unwrap();
+    let open = state.rooms["alpha"].password_hash.clone();
+    assert_eq!(rooms::check_password_unchanged(&state, "alpha", open.as_deref()), Ok(()));
+
+    // The room was closed and reopened with a password while the joiner's check ran
+    state.rooms.get_mut("alpha").unwrap().password_hash = Some(rooms::hash_password("hunter2").unwrap());
+    assert_eq!(rooms::check_password_unchanged(&state, "alpha", open.as_deref()), Err(JoinError::PasswordRequired));
+    let old = rooms::hash_password("hunter2").unwrap();
+    assert_eq!(rooms::check_password_unchanged(&state, "alpha", Some(&old)), Err(JoinError::InvalidPassword));
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use chrono::Utc;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Role, Room, SignalKind};
+use video_conference_backend::rooms;
+use video_conference_backend::signaling::{handle_create_room, SharedState, SignalingState};
+use video_conference_backend::storage::{RoomRecord, RoomStore, SqliteRoomStore};
+use common::{add_client, parsed, sign_in, signal};
+
+#[tokio::test]
+async fn created_rooms_are_stored_and_restored_on_startup() {
+    let store: Arc<dyn RoomStore> = Arc::new(SqliteRoomStore::open_in_memory().unwrap());
+    let mut inner = SignalingState::with_room_store(Arc::clone(&store));
+    let (host, _host_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "password": "hunter2", "lobby": true }));
+    handle_create_room(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+    state.lock().await.join_room(host, "scratch", 8).unwrap();
+
+    let stored = store.load_rooms().unwrap();
+    assert_eq!(stored.len(), 1, "rooms created by joining are ephemeral and never stored");
+    assert!(stored[0].lobby_enabled);
+
+    let restarted: SharedState = Arc::new(Mutex::new(SignalingState::with_room_store(Arc::clone(&store))));
+    assert_eq!(rooms::restore_rooms(Arc::clone(&restarted)).await.unwrap(), 1);
+    let restarted = restarted.lock().await;
+    let room = &restarted.rooms["alpha"];
+    assert!(!room.ephemeral && room.members.is_empty() && room.host.is_none());
+    assert!(rooms::verify_password("hunter2", room.password_hash.as_deref().unwrap()));
+}
+
+#[tokio::test]
+async fn rooms_whose_schedule_has_ended_are_dropped_on_restore() {
+    let store: Arc<dyn RoomStore> = Arc::new(SqliteRoomStore::open_in_memory().unwrap());
+    let mut ended = Room::new("ended".into(), 8);
+    ended.ends_at = Some(Utc::now().timestamp() - 1);
+    for room in [ended, Room::new("open".into(), 8)] {
+        store.save_room(&RoomRecord::from(&room)).unwrap();
+    }
+
+    let restarted: SharedState = Arc::new(Mutex::new(SignalingState::with_room_store(Arc::clone(&store))));
+    assert_eq!(rooms::restore_rooms(Arc::clone(&restarted)).await.unwrap(), 1);
+    assert!(restarted.lock().await.rooms.contains_key("open"));
+    assert_eq!(store.load_rooms().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn a_restored_room_is_handed_back_to_its_owner() {
+    let store: Arc<dyn RoomStore> = Arc::new(SqliteRoomStore::open_in_memory().unwrap());
+    let mut inner = SignalingState::with_room_store(Arc::clone(&store));
+    let (owner, _owner_rx) = add_client(&mut inner, 1);
+    sign_in(&mut inner, owner, "alice");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    handle_create_room(parsed(&signal(SignalKind::CreateRoom, json!({ "room_id": "alpha" }))), owner, Arc::clone(&state)).await.unwrap();
+    assert_eq!(store.load_rooms().unwrap()[0].owner.as_deref(), Some("webauthn:alice"));
+
+    let mut inner = SignalingState::with_room_store(Arc::clone(&store));
+    let (guest, _guest_rx) = add_client(&mut inner, 2);
+    let (owner, _owner_rx) = add_client(&mut inner, 3);
+    sign_in(&mut inner, owner, "alice");
+    let restarted: SharedState = Arc::new(Mutex::new(inner));
+    rooms::restore_rooms(Arc::clone(&restarted)).await.unwrap();
+
+    let mut restarted = restarted.lock().await;
+    restarted.join_room(guest, "alpha", 8).unwrap();
+    assert_eq!(restarted.clients[&guest].role, Role::Host);
+    restarted.join_room(owner, "alpha", 8).unwrap();
+    assert_eq!(restarted.rooms["alpha"].host, Some(owner));
+    assert_eq!(restarted.clients[&owner].role, Role::Host);
+    assert_eq!(restarted.clients[&guest].role, Role::Moderator);
+}
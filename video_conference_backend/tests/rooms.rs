@@ -0,0 +1,74 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::config;
+use video_conference_backend::signaling::{handle_join_room, handle_leave_room, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+#[tokio::test]
+async fn clients_join_and_leave_rooms() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let (b, _b_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    for addr in [a, b] {
+        handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": " alpha " }))), addr, Arc::clone(&state)).await.unwrap();
+    }
+    let joined = payloads(&mut a_rx, "room-joined");
+    assert_eq!(joined[0]["room_id"], "alpha");
+    assert_eq!(joined[0]["client_id"], "client-1");
+    assert_eq!(state.lock().await.rooms["alpha"].members, [a, b]);
+
+    handle_leave_room(a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "room-left")[0]["room_id"], "alpha");
+    handle_leave_room(b, Arc::clone(&state)).await.unwrap();
+    let state = state.lock().await;
+    assert!(state.clients[&a].room_id.is_none());
+    assert!(!state.rooms.contains_key("alpha"));
+}
+
+#[tokio::test]
+async fn blank_room_ids_are_refused() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "  " }))), a, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut a_rx, "room-joined").is_empty());
+    let state = state.lock().await;
+    assert!(state.rooms.is_empty() && state.clients[&a].room_id.is_none());
+}
+
+#[tokio::test]
+async fn full_rooms_turn_joiners_away() {
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_client(&mut inner, 1);
+    let (b, _b_rx) = add_client(&mut inner, 2);
+    let (c, mut c_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "max_participants": 2 }));
+    for addr in [a, b, c] {
+        handle_join_room(parsed(&join), addr, Arc::clone(&state)).await.unwrap();
+    }
+    let full = payloads(&mut c_rx, "room-full");
+    assert_eq!(full[0]["max_participants"], 2);
+    let state = state.lock().await;
+    assert_eq!(state.rooms["alpha"].members, [a, b]);
+    assert!(state.clients[&c].room_id.is_none());
+}
+
+#[tokio::test]
+async fn rooms_created_on_join_are_held_to_the_server_maximum() {
+    let mut inner = SignalingState::new();
+    let (addr, _rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "max_participants": usize::MAX }));
+    handle_join_room(parsed(&join), addr, Arc::clone(&state)).await.unwrap();
+    assert_eq!(state.lock().await.rooms["alpha"].max_participants, config::get_max_room_participants());
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{handle_join_room, handle_leave_room, SharedState, SignalingState};
+use common::{add_client, drain, parsed, payloads, signal};
+
+#[tokio::test]
+async fn joiners_get_the_roster_and_members_hear_arrivals_and_departures() {
+    let mut inner = SignalingState::new();
+    let (first, mut first_rx) = add_client(&mut inner, 1);
+    let (lurker, _lurker_rx) = add_client(&mut inner, 2);
+    let (second, mut second_rx) = add_client(&mut inner, 3);
+    inner.clients.get_mut(&lurker).unwrap().verified = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }));
+    for addr in [first, lurker, second] {
+        handle_join_room(parsed(&join), addr, Arc::clone(&state)).await.unwrap();
+    }
+
+    let roster = payloads(&mut second_rx, "roster").remove(0);
+    let participants = roster["participants"].as_array().unwrap();
+    assert_eq!(participants.len(), 1, "unverified members stay off the roster");
+    assert_eq!(participants[0]["client_id"], "client-1");
+    let arrivals = payloads(&mut first_rx, "peer-joined");
+    assert_eq!(arrivals.last().unwrap()["participant"]["client_id"], "client-3");
+
+    handle_leave_room(second, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut first_rx, "peer-left")[0]["client_id"], "client-3");
+}
+
+#[tokio::test]
+async fn a_rejected_join_is_not_announced() {
+    let mut inner = SignalingState::new();
+    let (first, mut first_rx) = add_client(&mut inner, 1);
+    let (second, mut second_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha", "max_participants": 1 }));
+    handle_join_room(parsed(&join), first, Arc::clone(&state)).await.unwrap();
+    drain(&mut first_rx);
+
+    handle_join_room(parsed(&join), second, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut second_rx, "roster").len(), 0);
+    assert!(drain(&mut first_rx).is_empty());
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use chrono::Utc;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::rooms::sweep_rooms;
+use video_conference_backend::signaling::{handle_create_room, handle_join_room, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+const ADMIN_TOKEN: &str = "scheduled-rooms-admin";
+
+#[tokio::test]
+async fn scheduled_rooms_need_an_admin_and_open_at_their_start() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let mut inner = SignalingState::new();
+    let (admin, mut admin_rx) = add_client(&mut inner, 1);
+    let (guest, mut guest_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let now = Utc::now().timestamp();
+
+    let schedule = |room_id: &str, admin_token: &str| signal(SignalKind::CreateRoom, json!({
+        "room_id": room_id,
+        "starts_at": now + 600,
+        "ends_at": now + 3600,
+        "admin_token": admin_token,
+    }));
+    handle_create_room(parsed(&schedule("alpha", "guess")), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "not-admin");
+    assert!(state.lock().await.rooms.is_empty());
+
+    handle_create_room(parsed(&schedule("alpha", ADMIN_TOKEN)), admin, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut admin_rx, "room-created")[0]["starts_at"], now + 600);
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "meeting-not-started")[0]["starts_at"], now + 600);
+
+    state.lock().await.rooms.get_mut("alpha").unwrap().starts_at = Some(now - 1);
+    handle_join_room(parsed(&signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }))), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "room-joined").len(), 1);
+}
+
+#[tokio::test]
+async fn schedules_must_end_in_the_future_and_after_they_start() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let mut inner = SignalingState::new();
+    let (admin, mut admin_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    let now = Utc::now().timestamp();
+
+    for (starts_at, ends_at) in [(None, now - 1), (Some(now + 60), now + 30)] {
+        let create = signal(SignalKind::CreateRoom, json!({
+            "room_id": "alpha",
+            "starts_at": starts_at,
+            "ends_at": ends_at,
+            "admin_token": ADMIN_TOKEN,
+        }));
+        handle_create_room(parsed(&create), admin, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut admin_rx, "error")[0]["code"], "invalid-schedule");
+    }
+    assert!(state.lock().await.rooms.is_empty());
+}
+
+#[tokio::test]
+async fn schedules_at_the_ends_of_the_clock_do_not_overflow() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let mut inner = SignalingState::new();
+    let (admin, mut admin_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({
+        "room_id": "alpha",
+        "starts_at": i64::MIN,
+        "ends_at": i64::MAX,
+        "admin_token": ADMIN_TOKEN,
+    }));
+    handle_create_room(parsed(&create), admin, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut admin_rx, "room-created").len(), 1);
+
+    let mut inner = state.lock().await;
+    let idle = Duration::from_secs(3600);
+    assert!(sweep_rooms(&mut inner, Instant::now(), idle, idle).is_empty());
+    assert!(inner.rooms.contains_key("alpha"));
+}
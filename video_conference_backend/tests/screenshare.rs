@@ -0,0 +1,41 @@
+mod common;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Presence, RoomConfig};
+use video_conference_backend::signaling::{screenshare, SharedState, SignalingState};
+use common::{add_client, add_member, payloads};
+
+#[tokio::test]
+async fn sharing_is_limited_per_room_and_frees_up_on_stop() {
+    let mut inner = SignalingState::new();
+    let (first, mut first_rx) = add_member(&mut inner, 1, "alpha");
+    let (second, mut second_rx) = add_member(&mut inner, 2, "alpha");
+    inner.rooms.get_mut("alpha").unwrap().config = RoomConfig { max_screen_shares: Some(1), ..RoomConfig::default() };
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    screenshare::handle_screenshare_start(first, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut second_rx, "screenshare-started")[0]["client_id"], "client-1");
+    assert_eq!(state.lock().await.clients[&first].presence, Presence::ScreenSharing);
+
+    screenshare::handle_screenshare_start(second, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut second_rx, "error")[0]["code"], "screenshare-busy");
+
+    screenshare::handle_screenshare_stop(first, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut first_rx, "screenshare-stopped").len(), 1);
+    assert_eq!(state.lock().await.clients[&first].presence, Presence::Active);
+
+    screenshare::handle_screenshare_start(second, Arc::clone(&state)).await.unwrap();
+    assert_eq!(screenshare::sharers(&*state.lock().await, "alpha"), vec!["client-2".to_string()]);
+}
+
+#[tokio::test]
+async fn sharing_needs_a_room() {
+    let mut inner = SignalingState::new();
+    let (loner, mut loner_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    screenshare::handle_screenshare_start(loner, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut loner_rx, "error")[0]["code"], "not-in-room");
+    assert_eq!(state.lock().await.clients[&loner].presence, Presence::Active);
+}
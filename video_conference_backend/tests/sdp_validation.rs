@@ -0,0 +1,236 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, SigningKey, Verifier};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::crypto::server_identity;
+use video_conference_backend::crypto::Ed25519Signer;
+use video_conference_backend::models::{Payload, SignalKind, SignalMessage};
+use video_conference_backend::rooms::CandidatePolicy;
+use video_conference_backend::signaling::sdp::{self, RewriteContext, SdpError, SdpRewriter};
+use video_conference_backend::signaling::{renegotiation, SharedState, SignalingState};
+use common::{add_member, received, signal};
+
+const OFFER: &str = "v=0\r\n\
+o=- 4611731400430051336 2 IN IP4 127.0.0.1\r\n\
+s=-\r\n\
+t=0 0\r\n\
+a=group:BUNDLE 0\r\n\
+m=audio 9 UDP/TLS/RTP/SAVPF 111 0\r\n\
+c=IN IP4 0.0.0.0\r\n\
+a=mid:0\r\n\
+a=rtpmap:111 opus/48000/2\r\n\
+a=candidate:1 1 udp 2122260223 192.168.1.20 54400 typ host\r\n\
+a=candidate:2 1 udp 1686052607 198.51.100.7 61000 typ srflx raddr 192.168.1.20 rport 54400\r\n\
+a=candidate:3 1 udp 41885439 203.0.113.9 3478 typ relay raddr 198.51.100.7 rport 61000\r\n\
+a=end-of-candidates\r\n";
+
+const VIDEO: &str = "v=0\r\n\
+o=- 1 2 IN IP4 127.0.0.1\r\n\
+s=-\r\n\
+t=0 0\r\n\
+m=video 9 UDP/TLS/RTP/SAVPF 96 97 98\r\n\
+c=IN IP4 0.0.0.0\r\n\
+b=AS:2500\r\n\
+a=extmap:3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\n\
+a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\r\n\
+a=rtpmap:96 VP8/90000\r\n\
+a=rtpmap:97 rtx/90000\r\n\
+a=rtpmap:98 VP9/90000\r\n";
+
+// Caps bandwidth at 300 kbps, or hands back something that is not SDP at all for target client-3
+struct CapBandwidth;
+
+impl SdpRewriter for CapBandwidth {
+    fn rewrite(&self, context: &RewriteContext, sdp: &str) -> Option<String> {
+        match context.target_id {
+            Some("client-3") => Some("not sdp".to_string()),
+            _ => Some(sdp::set_bandwidth(sdp, 300)),
+        }
+    }
+}
+
+fn renegotiate_offer(sdp: &str, target: &str) -> SignalMessage {
+    SignalMessage {
+        sender_id: "client-1".to_string(),
+        target_id: Some(target.to_string()),
+        ..signal(SignalKind::RenegotiateOffer, serde_json::json!({ "description": { "type": "offer", "sdp": sdp } }))
+    }
+}
+
+async fn check_description(signal: &SignalMessage, addr: SocketAddr, state: &SharedState) -> bool {
+    let kind = SignalKind::of(&signal.signal_type);
+    sdp::check_description(kind, &Payload::read(kind, signal), addr, state).await.unwrap()
+}
+
+fn malformed_line(sdp: &str) -> usize {
+    match sdp::validate(sdp, 64 * 1024) {
+        Err(SdpError::Malformed { line, .. }) => line,
+        other => panic!("expected a malformed description, got {:?}", other),
+    }
+}
+
+#[test]
+fn well_formed_descriptions_pass() {
+    assert_eq!(sdp::validate(OFFER, 64 * 1024), Ok(()));
+    assert_eq!(sdp::validate(&OFFER.replace("\r\n", "\n"), 64 * 1024), Ok(()));
+    assert_eq!(sdp::validate(OFFER, 256), Err(SdpError::TooLarge { limit: 256 }));
+}
+
+#[test]
+fn malformed_descriptions_are_refused_at_the_offending_line() {
+    assert_eq!(malformed_line("v=1\r\n"), 1);
+    assert_eq!(malformed_line(&OFFER.replace("o=- 4611731400430051336 2 IN IP4 127.0.0.1", "o=- 1 IN")), 2);
+    assert_eq!(malformed_line(&OFFER.replace("t=0 0\r\n", "t=0 0\r\n\r\n")), 5);
+    assert_eq!(malformed_line(&OFFER.replace("a=mid:0", "mid 0")), 8);
+    assert_eq!(malformed_line(&OFFER.replace("m=audio 9 UDP/TLS/RTP/SAVPF 111 0", "m=audio nine UDP/TLS/RTP/SAVPF 111")), 6);
+    assert_eq!(malformed_line(&OFFER.replace("a=mid:0", "a=mid:\u{7}")), 8);
+    assert_eq!(malformed_line(&OFFER.replace("t=0 0\r\n", "")), 5);
+}
+
+#[test]
+fn candidates_the_room_forbids_are_stripped() {
+    let relay_only = CandidatePolicy { relay_only: true, ..CandidatePolicy::default() };
+    let (stripped, removed) = sdp::strip_candidates(OFFER, &relay_only);
+
+    assert_eq!(removed, 2);
+    assert!(stripped.contains("typ relay"));
+    assert!(!stripped.contains("typ host") && !stripped.contains("typ srflx"));
+    assert!(stripped.ends_with("a=end-of-candidates\r\n"));
+    assert_eq!(sdp::validate(&stripped, 64 * 1024), Ok(()));
+
+    assert_eq!(sdp::strip_candidates(OFFER, &CandidatePolicy::default()), (OFFER.to_string(), 0));
+}
+
+#[tokio::test]
+async fn malformed_descriptions_are_answered_with_an_error() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_member(&mut inner, 1, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let offer = renegotiate_offer("v=0\r\ns=-\r\n", "client-2");
+    assert!(!check_description(&offer, a, &state).await);
+    let error = received(&mut a_rx).unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&error.payload).unwrap();
+    assert_eq!(payload["code"], "invalid-sdp");
+    assert_eq!(payload["numeric_code"], 6002);
+    assert_eq!(payload["context"]["line"], 2);
+
+    assert!(check_description(&renegotiate_offer(OFFER, "client-2"), a, &state).await);
+    assert!(a_rx.try_recv().is_err());
+
+    // Only signals that carry a description are looked into
+    let mut sealed = renegotiate_offer("v=0\r\ns=-\r\n", "client-2");
+    sealed.signal_type = SignalKind::Sealed.name().to_string();
+    sealed.payload = sealed.payload.replace("description", "offer");
+    assert!(check_description(&sealed, a, &state).await);
+    assert!(a_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn relay_only_rooms_relay_descriptions_without_their_other_candidates() {
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    inner.rooms.get_mut("alpha").unwrap().config.relay_only = Some(true);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    renegotiation::handle_renegotiate_offer(&renegotiate_offer(OFFER, "client-2"), a, Arc::clone(&state)).await.unwrap();
+
+    let relayed = received(&mut b_rx).unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&relayed.payload).unwrap();
+    assert_eq!(payload["sdp_stripped"], 2);
+    let sdp = payload["description"]["sdp"].as_str().unwrap();
+    assert_eq!(sdp.matches("a=candidate:").count(), 1);
+    assert!(sdp.contains("typ relay"));
+}
+
+#[test]
+fn rewrite_helpers_change_only_what_they_are_asked_to() {
+    let capped = sdp::set_bandwidth(VIDEO, 500);
+    assert_eq!(capped, VIDEO.replace("b=AS:2500", "b=AS:500"));
+    let uncapped = VIDEO.replace("b=AS:2500\r\n", "");
+    assert_eq!(sdp::set_bandwidth(&uncapped, 500), capped);
+
+    let vp9_first = sdp::prefer_codec(VIDEO, "video", "vp9");
+    assert!(vp9_first.contains("m=video 9 UDP/TLS/RTP/SAVPF 98 96 97\r\n"));
+    assert_eq!(sdp::prefer_codec(VIDEO, "audio", "vp9"), VIDEO);
+
+    let without = sdp::remove_extmap(VIDEO, "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time");
+    assert!(!without.contains("abs-send-time"));
+    assert!(without.contains("a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid"));
+
+    for rewritten in [capped, vp9_first, without] {
+        assert_eq!(sdp::validate(&rewritten, 64 * 1024), Ok(()));
+    }
+}
+
+#[tokio::test]
+async fn rewritten_descriptions_are_attributed_to_the_server() {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    server_identity::install(Arc::new(Ed25519Signer::new(key.clone())));
+
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let (_, mut c_rx) = add_member(&mut inner, 3, "alpha");
+    inner.sdp_rewriter = Some(Arc::new(CapBandwidth));
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    renegotiation::handle_renegotiate_offer(&renegotiate_offer(VIDEO, "client-2"), a, Arc::clone(&state)).await.unwrap();
+    let relayed = received(&mut b_rx).unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&relayed.payload).unwrap();
+    assert!(payload["description"]["sdp"].as_str().unwrap().contains("b=AS:300\r\n"));
+    assert!(payload.get("sdp_stripped").is_none());
+
+    let rewritten = &payload["rewritten"];
+    assert_eq!(rewritten["by"], "server");
+    let signature = STANDARD.decode(rewritten["signature"].as_str().unwrap()).unwrap();
+    let signature = Signature::from_slice(&signature).unwrap();
+    let message = server_identity::rewrite_signed_message(&relayed, &payload["nonce"], VIDEO, &payload["description"]);
+    key.verifying_key().verify(&message, &signature).unwrap();
+    // The attestation does not carry over to a signal for another peer
+    let elsewhere = SignalMessage { target_id: Some("client-3".to_string()), ..relayed.clone() };
+    let message = server_identity::rewrite_signed_message(&elsewhere, &payload["nonce"], VIDEO, &payload["description"]);
+    assert!(key.verifying_key().verify(&message, &signature).is_err());
+
+    // A rewrite that is not a valid description is dropped and the original relayed
+    renegotiation::handle_renegotiate_offer(&renegotiate_offer(VIDEO, "client-3"), a, Arc::clone(&state)).await.unwrap();
+    let relayed = received(&mut c_rx).unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&relayed.payload).unwrap();
+    assert_eq!(payload["description"]["sdp"], VIDEO);
+    assert!(payload.get("rewritten").is_none());
+}
+
+#[tokio::test]
+async fn senders_cannot_mark_their_own_signals_as_rewritten() {
+    let mut inner = SignalingState::new();
+    let (a, _a_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut b_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let mut offer = renegotiate_offer(VIDEO, "client-2");
+    offer.payload = serde_json::json!({
+        "description": { "type": "offer", "sdp": VIDEO },
+        "rewritten": { "by": "server" },
+        "sdp_stripped": 2,
+    })
+    .to_string();
+    renegotiation::handle_renegotiate_offer(&offer, a, Arc::clone(&state)).await.unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&received(&mut b_rx).unwrap().payload).unwrap();
+    assert_eq!(payload["description"]["sdp"], VIDEO);
+    assert!(payload.get("rewritten").is_none());
+    assert!(payload.get("sdp_stripped").is_none());
+
+    // Nor any other signal they send
+    let file_offer = SignalMessage {
+        sender_id: "client-1".to_string(),
+        target_id: Some("client-2".to_string()),
+        ..signal(SignalKind::FileOffer, serde_json::json!({ "transfer_id": "t-1", "rewritten": { "by": "server" } }))
+    };
+    let sanitized = sdp::sanitize(&file_offer, a, &*state.lock().await);
+    assert_eq!(sanitized.payload, serde_json::json!({ "transfer_id": "t-1" }).to_string());
+}
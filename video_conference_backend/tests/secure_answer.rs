@@ -0,0 +1,46 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{handle_secure_answer, ice, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, secure_offer, signal};
+
+fn candidate() -> serde_json::Value {
+    json!({ "candidate": "candidate:1 1 udp 1686052607 198.51.100.7 61000 typ srflx" })
+}
+
+#[tokio::test]
+async fn an_answerer_can_send_candidates_once_its_answer_verifies() {
+    let mut inner = SignalingState::new();
+    let (_, mut offerer_rx) = add_member(&mut inner, 1, "alpha");
+    let (answerer, mut answerer_rx) = add_member(&mut inner, 2, "alpha");
+    inner.clients.get_mut(&answerer).unwrap().verified = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut offerer_rx);
+    drain(&mut answerer_rx);
+
+    let message = signal(SignalKind::IceCandidate, candidate());
+    ice::handle_ice_candidate(&message, parsed(&message), answerer, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut answerer_rx, "error")[0]["code"], "unverified-sender");
+    assert!(drain(&mut offerer_rx).is_empty());
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let message = secure_offer(json!({ "type": "answer", "sdp": "v=0\r\n" }), public_key.as_bytes(), |message| {
+        let signature: Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    });
+    handle_secure_answer(&message, parsed(&message), answerer, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut offerer_rx, "secure-answer").len(), 1);
+    assert_eq!(state.lock().await.clients[&answerer].public_key.as_deref(), Some(public_key.as_bytes()));
+
+    let message = signal(SignalKind::IceCandidate, candidate());
+    ice::handle_ice_candidate(&message, parsed(&message), answerer, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut answerer_rx, "error").is_empty());
+    assert_eq!(payloads(&mut offerer_rx, "ice-candidate").len(), 1);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::crypto::{check_sequence, SequenceError};
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::{auth, handle_secure_offer, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, sign_message, signal, unsigned_offer};
+
+fn numbered(kind: SignalKind, payload: serde_json::Value, seq: Option<u64>) -> SignalMessage {
+    let mut message = signal(kind, payload);
+    message.seq = seq;
+    message
+}
+
+#[test]
+fn sequence_numbers_only_move_forward() {
+    assert_eq!(check_sequence(None, None, false), Ok(None));
+    assert_eq!(check_sequence(None, None, true), Err(SequenceError::Missing));
+    assert_eq!(check_sequence(None, Some(7), false), Ok(Some(7)));
+    assert_eq!(check_sequence(Some(7), Some(9), false), Ok(Some(9)));
+    assert_eq!(check_sequence(Some(7), Some(7), false), Err(SequenceError::Stale));
+    assert_eq!(check_sequence(Some(7), Some(3), false), Err(SequenceError::Stale));
+    // Having numbered once, a sender can't drop back to unnumbered messages
+    assert_eq!(check_sequence(Some(7), None, false), Err(SequenceError::Missing));
+}
+
+#[tokio::test]
+async fn repeated_or_dropped_numbers_are_refused() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_member(&mut inner, 1, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut rx);
+
+    let presence = |seq| numbered(SignalKind::Presence, json!({ "status": "away" }), seq);
+    assert!(auth::check_sequence(&presence(Some(1)), SignalKind::Presence, addr, &state).await.unwrap());
+    assert!(auth::check_sequence(&presence(Some(2)), SignalKind::Presence, addr, &state).await.unwrap());
+    assert!(!auth::check_sequence(&presence(Some(2)), SignalKind::Presence, addr, &state).await.unwrap());
+    assert!(!auth::check_sequence(&presence(None), SignalKind::Presence, addr, &state).await.unwrap());
+
+    let codes: Vec<_> = payloads(&mut rx, "error").iter().map(|error| error["code"].clone()).collect();
+    assert_eq!(codes, ["stale-seq", "missing-seq"]);
+    assert_eq!(state.lock().await.clients[&addr].last_seq, Some(2));
+}
+
+#[tokio::test]
+async fn a_replayed_offer_sequence_is_not_relayed() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let offer = |seq| {
+        let payload = unsigned_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes());
+        let mut message = numbered(SignalKind::SecureOffer, payload, Some(seq));
+        sign_message(&mut message, |message| {
+            let signature: Signature = signing_key.sign(message);
+            signature.to_bytes().to_vec()
+        });
+        message
+    };
+
+    let message = offer(5);
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut host_rx, "secure-offer").len(), 1);
+
+    let message = offer(5);
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "stale-seq");
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
+
+#[tokio::test]
+async fn verified_clients_must_number_their_messages_by_default() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_member(&mut inner, 1, "alpha");
+    let (unverified, mut unverified_rx) = add_member(&mut inner, 2, "alpha");
+    inner.clients.get_mut(&unverified).unwrap().verified = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut rx);
+    drain(&mut unverified_rx);
+
+    let presence = numbered(SignalKind::Presence, json!({ "status": "away" }), None);
+    assert!(!auth::check_sequence(&presence, SignalKind::Presence, addr, &state).await.unwrap());
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "missing-seq");
+    assert!(auth::check_sequence(&presence, SignalKind::Presence, unverified, &state).await.unwrap());
+}
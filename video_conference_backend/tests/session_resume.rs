@@ -0,0 +1,52 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::sessions;
+use video_conference_backend::signaling::{handle_resume_session, SharedState, SignalingState};
+use common::{add_client, add_member, parsed, payloads, signal};
+
+#[tokio::test]
+async fn a_reconnecting_client_resumes_its_identity_and_room_once() {
+    let mut inner = SignalingState::new();
+    let (host, _host_rx) = add_member(&mut inner, 1, "alpha");
+    let (_guest, _guest_rx) = add_member(&mut inner, 4, "alpha");
+    let token = sessions::issue_resume_token(&mut inner, host).unwrap();
+    assert!(sessions::issue_resume_token(&mut inner, host).is_none());
+
+    sessions::suspend_session(&mut inner, host, Duration::from_secs(60));
+    inner.remove_client(host);
+    let (reconnected, mut reconnected_rx) = add_client(&mut inner, 2);
+    let (thief, mut thief_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let resume = signal(SignalKind::ResumeSession, json!({ "resume_token": token }));
+    let resumed = handle_resume_session(parsed(&resume), reconnected, Arc::clone(&state)).await.unwrap();
+    assert_eq!(resumed.as_deref(), Some("client-1"));
+    let reply = payloads(&mut reconnected_rx, "session-resumed").remove(0);
+    assert_eq!(reply["room_id"], "alpha");
+    assert_ne!(reply["resume_token"], token.as_str());
+    assert!(state.lock().await.rooms["alpha"].is_host(&reconnected));
+
+    assert!(handle_resume_session(parsed(&resume), thief, Arc::clone(&state)).await.unwrap().is_none());
+    assert_eq!(payloads(&mut thief_rx, "error")[0]["code"], "resume-failed");
+}
+
+#[tokio::test]
+async fn sessions_are_not_resumable_after_the_grace_period() {
+    let mut inner = SignalingState::new();
+    let (addr, _rx) = add_member(&mut inner, 1, "alpha");
+    let token = sessions::issue_resume_token(&mut inner, addr).unwrap();
+    sessions::suspend_session(&mut inner, addr, Duration::ZERO);
+    inner.remove_client(addr);
+    let (reconnected, mut reconnected_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let resume = signal(SignalKind::ResumeSession, json!({ "resume_token": token }));
+    assert!(handle_resume_session(parsed(&resume), reconnected, Arc::clone(&state)).await.unwrap().is_none());
+    assert_eq!(payloads(&mut reconnected_rx, "error")[0]["code"], "resume-failed");
+    assert!(state.lock().await.clients[&reconnected].room_id.is_none());
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::sessions::{self, SessionTokenError};
+use video_conference_backend::signaling::{auth, handle_secure_offer, SharedState, SignalingState};
+use common::{add_client, add_member, drain, parsed, payloads, secure_offer, signal};
+
+fn chat() -> SignalMessage {
+    signal(SignalKind::Chat, json!({ "text": "hi" }))
+}
+
+#[test]
+fn tokens_are_checked_against_the_live_ones_and_renewed_past_half_life() {
+    let mut state = SignalingState::new();
+    let (addr, _rx) = add_client(&mut state, 1);
+    let ttl = Duration::from_millis(200);
+
+    let token = sessions::issue_session_token(&mut state, addr, ttl).unwrap().token;
+    let client = &state.clients[&addr];
+    assert_eq!(sessions::check_session_token(client, Some(&token), ttl), Ok(false));
+    assert_eq!(sessions::check_session_token(client, None, ttl), Err(SessionTokenError::Missing));
+    assert_eq!(sessions::check_session_token(client, Some("not-the-token"), ttl), Err(SessionTokenError::Invalid));
+
+    std::thread::sleep(Duration::from_millis(120));
+    assert_eq!(sessions::check_session_token(&state.clients[&addr], Some(&token), ttl), Ok(true));
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(sessions::check_session_token(&state.clients[&addr], Some(&token), ttl), Err(SessionTokenError::Invalid));
+}
+
+#[tokio::test]
+async fn verified_clients_must_send_their_session_token() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    inner.clients.get_mut(&guest).unwrap().verified = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let offer = secure_offer(json!({ "type": "offer", "sdp": "v=0\r\n" }), public_key.as_bytes(), |message| {
+        let signature: Signature = signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    });
+    handle_secure_offer(&offer, parsed(&offer), guest, Arc::clone(&state)).await.unwrap();
+    let issued = payloads(&mut guest_rx, "session-token");
+    let token = issued[0]["session_token"].as_str().unwrap().to_string();
+    drain(&mut host_rx);
+
+    assert!(auth::check_session_token(&chat(), SignalKind::Chat, Some(&token), guest, &state).await.unwrap());
+    assert!(!auth::check_session_token(&chat(), SignalKind::Chat, None, guest, &state).await.unwrap());
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "session-token-required");
+    assert!(!auth::check_session_token(&chat(), SignalKind::Chat, Some("forged"), guest, &state).await.unwrap());
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "session-token-invalid");
+
+    // A token close to expiry still works once, and a replacement comes back with it
+    let short = sessions::issue_session_token(&mut *state.lock().await, guest, Duration::from_millis(100)).unwrap().token;
+    assert!(auth::check_session_token(&chat(), SignalKind::Chat, Some(&short), guest, &state).await.unwrap());
+    let renewed = payloads(&mut guest_rx, "session-token");
+    assert_eq!(renewed.len(), 1);
+    assert_ne!(renewed[0]["session_token"], short.as_str());
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(!auth::check_session_token(&chat(), SignalKind::Chat, Some(&short), guest, &state).await.unwrap());
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "session-token-invalid");
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::message::{DocSnapshotPayload, DocSyncPayload, DocUpdatePayload, JoinRoomPayload};
+use video_conference_backend::models::{DocumentState, DocumentUpdate, Role, SharedDocument};
+use video_conference_backend::signaling::{documents, handle_join_room, SharedState, SignalingState};
+use common::{add_client, drain, payloads};
+
+fn join() -> JoinRoomPayload {
+    JoinRoomPayload {
+        room_id: "alpha".to_string(),
+        max_participants: None,
+        password: None,
+        invite_token: None,
+        capabilities: None,
+    }
+}
+
+fn update(doc: &str, byte: u8) -> DocUpdatePayload {
+    DocUpdatePayload { doc: doc.to_string(), update: vec![byte] }
+}
+
+fn snapshot(doc: &str, version: u64, state: &[u8]) -> DocSnapshotPayload {
+    DocSnapshotPayload { doc: doc.to_string(), version, state: state.to_vec() }
+}
+
+#[test]
+fn snapshots_replace_the_updates_they_merge() {
+    let mut document = SharedDocument::default();
+    for n in 1..=4 {
+        let update = document.append(|version| DocumentUpdate { version, client_id: "client-1".to_string(), update: vec![n as u8] });
+        assert_eq!(update.version, n);
+    }
+
+    let state = |version, state: &[u8]| DocumentState { version, state: state.to_vec() };
+    assert!(!document.compact(state(5, &[0])));
+    assert!(document.compact(state(3, &[1, 2, 3])));
+    assert!(!document.compact(state(3, &[1, 2, 3])));
+    assert_eq!(document.snapshot(), Some(&state(3, &[1, 2, 3])));
+    assert_eq!(document.entries().iter().map(|update| update.version).collect::<Vec<_>>(), [4]);
+    assert_eq!((document.seq(), document.bytes()), (4, 4));
+}
+
+#[test]
+fn updates_travel_as_base64() {
+    let payload: DocUpdatePayload = serde_json::from_value(json!({ "doc": "notes", "update": "AQID" })).unwrap();
+    assert_eq!(payload.update, [1, 2, 3]);
+    assert!(serde_json::from_value::<DocUpdatePayload>(json!({ "doc": "notes", "update": [1, 2, 3] })).is_err());
+    let update = DocumentUpdate { version: 1, client_id: "client-1".to_string(), update: vec![1, 2, 3] };
+    assert_eq!(serde_json::to_value(update).unwrap()["update"], "AQID");
+}
+
+#[tokio::test]
+async fn updates_are_relayed_and_late_joiners_get_each_document() {
+    std::env::set_var("DOCUMENT_SNAPSHOT_AFTER", "2");
+    std::env::set_var("DOCUMENT_PAGE_SIZE", "1");
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let (b, mut b_rx) = add_client(&mut inner, 2);
+    let (c, mut c_rx) = add_client(&mut inner, 3);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    handle_join_room(join(), b, Arc::clone(&state)).await.unwrap();
+    payloads(&mut a_rx, "doc-update");
+
+    for n in 1..=4 {
+        documents::handle_doc_update(update("notes", n), a, Arc::clone(&state)).await.unwrap();
+    }
+    documents::handle_doc_update(update("agenda", 9), b, Arc::clone(&state)).await.unwrap();
+    let relayed = payloads(&mut b_rx, "doc-update");
+    assert_eq!(relayed.len(), 5);
+    assert_eq!(relayed[2]["doc"], "notes");
+    assert_eq!(relayed[2]["version"], 3);
+    assert_eq!(relayed[2]["client_id"], "client-1");
+    assert_eq!(relayed[2]["update"], "Aw==");
+    assert_eq!(relayed[4]["version"], 1);
+    let requests = drain(&mut a_rx).into_iter().filter(|(kind, _)| kind == "doc-snapshot-request").collect::<Vec<_>>();
+    assert_eq!(requests.len(), 1);
+    assert_eq!((&requests[0].1["doc"], &requests[0].1["version"]), (&json!("notes"), &json!(2)));
+
+    documents::handle_doc_snapshot(snapshot("notes", 2, &[1, 2]), b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut b_rx, "error")[0]["code"], "snapshot-not-requested");
+    documents::handle_doc_snapshot(snapshot("notes", 2, &[1, 2]), a, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut a_rx, "error").is_empty());
+
+    handle_join_room(join(), c, Arc::clone(&state)).await.unwrap();
+    let pages = payloads(&mut c_rx, "doc-state");
+    let agenda: Vec<_> = pages.iter().filter(|page| page["doc"] == "agenda").collect();
+    assert_eq!(agenda.len(), 1);
+    assert!(agenda[0]["state"].is_null());
+    assert_eq!(agenda[0]["complete"], true);
+    let notes: Vec<_> = pages.iter().filter(|page| page["doc"] == "notes").collect();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0]["state_version"], 2);
+    assert_eq!(notes[0]["state"], "AQI=");
+    assert_eq!(notes[0]["updates"][0]["update"], "Aw==");
+    assert_eq!(notes[0]["complete"], false);
+    assert!(notes[1]["state"].is_null());
+    assert_eq!(notes[1]["updates"][0]["version"], 4);
+    assert_eq!(notes[1]["complete"], true);
+
+    let sync = DocSyncPayload { doc: Some("minutes".to_string()) };
+    documents::handle_doc_sync(sync, c, Arc::clone(&state)).await.unwrap();
+    let empty = payloads(&mut c_rx, "doc-state");
+    assert_eq!(empty[0]["version"], 0);
+    assert_eq!(empty[0]["updates"], json!([]));
+}
+
+#[tokio::test]
+async fn stale_snapshots_oversized_documents_and_viewer_edits_are_refused() {
+    std::env::set_var("DOCUMENT_SNAPSHOT_AFTER", "2");
+    std::env::set_var("DOCUMENT_MAX_BYTES", "8");
+    std::env::set_var("ROOM_DOCUMENTS_MAX_BYTES", "12");
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    documents::handle_doc_update(update("notes", 1), a, Arc::clone(&state)).await.unwrap();
+    documents::handle_doc_update(update("notes", 2), a, Arc::clone(&state)).await.unwrap();
+
+    documents::handle_doc_snapshot(snapshot("notes", 3, &[1]), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "invalid-snapshot");
+
+    let large = |doc: &str, len| DocUpdatePayload { doc: doc.to_string(), update: vec![7; len] };
+    documents::handle_doc_update(large("notes", 7), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "document-full");
+    documents::handle_doc_update(large("minutes", 7), a, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut a_rx, "error").is_empty());
+    documents::handle_doc_update(large("agenda", 4), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "documents-full");
+
+    state.lock().await.clients.get_mut(&a).unwrap().role = Role::Viewer;
+    documents::handle_doc_update(update("notes", 3), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "insufficient-role");
+    assert_eq!(state.lock().await.rooms["alpha"].documents["notes"].seq(), 2);
+}
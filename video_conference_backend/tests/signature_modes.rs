@@ -0,0 +1,153 @@
+use p256::ecdsa::signature::hazmat::PrehashSigner;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use video_conference_backend::crypto::{verify_p256, VerificationMode};
+
+const MESSAGE: &[u8] = br#"{"sdp":"v=0","type":"offer"}"#;
+
+// crypto.subtle.sign({ name: "ECDSA", hash: "SHA-256" }, key, MESSAGE), raw public key export
+const WEBCRYPTO_PUBLIC_KEY: &str = "04563933c51cfdfbb6d71e309fcce15120b18aeebb5c7d9785feac6815bf16687503ad2d1aaedbbb1c00bf77fef4c115b44b09e9473b8e933bfc92ee905c64dbbc";
+const WEBCRYPTO_SIGNATURE: &str = "0c991ba14777fc839ced84a0e381b71a2baccf1275393079424052cb88669d575d48ff42cfa266b11e6a8b2c8457f6f45560e8d7179a9cbb7fe9becae9336a92";
+
+// Same call, but over crypto.subtle.digest("SHA-256", MESSAGE), so SHA-256 is applied twice
+const WEBCRYPTO_PREHASHED_PUBLIC_KEY: &str = "0485dd83b3558d3b162a0e56be48d1cc3e359848aa9cecd430eb93f64d8eeb3bd9c0010abed1874d11bfa5f66c7b37668f85707169d4e6cb3d2ef430cd8d2d9877";
+const WEBCRYPTO_PREHASHED_SIGNATURE: &str = "622a2fefa55f6e9ae508fb44b3896a37349547cf058cad1f9ff4ecdfcdcbf20d41d745b5f573b058be55e41671f60f87d0733c83774b6b18bb999c143f391e67";
+
+// openssl dgst -sha256 -sign key.pem message (DER signature)
+const OPENSSL_PUBLIC_KEY: &str = "0405280cdb4fef55df060f99a51874eadcb3cf3957f3d2e67731df88ee74f273ef70c745f4280b69e5b7a82df83543a42424efc8e7efaf26b7b3738e6ed88d6a3a";
+const OPENSSL_SIGNATURE: &str = "30440220108d91457089e0eda6f885a080081864da8299b82efc0f418113750bebd035c60220648e6ec19c506f902c1146ab18be3fb945eaa30e638fbff90d6add1578d7f48b";
+
+fn hex(value: &str) -> Vec<u8> {
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn webcrypto_signature_verifies_in_raw_mode() {
+    let public_key = hex(WEBCRYPTO_PUBLIC_KEY);
+    let signature = hex(WEBCRYPTO_SIGNATURE);
+
+    assert!(verify_p256(MESSAGE, &signature, &public_key, VerificationMode::Raw).is_ok());
+}
+
+#[test]
+fn raw_and_prehashed_modes_accept_different_signatures() {
+    let public_key = hex(WEBCRYPTO_PREHASHED_PUBLIC_KEY);
+    let signature = hex(WEBCRYPTO_PREHASHED_SIGNATURE);
+    assert!(verify_p256(MESSAGE, &signature, &public_key, VerificationMode::Prehashed).is_ok());
+    assert!(verify_p256(MESSAGE, &signature, &public_key, VerificationMode::Raw).is_err());
+
+    let public_key = hex(WEBCRYPTO_PUBLIC_KEY);
+    let signature = hex(WEBCRYPTO_SIGNATURE);
+    assert!(verify_p256(MESSAGE, &signature, &public_key, VerificationMode::Raw).is_ok());
+    assert!(verify_p256(MESSAGE, &signature, &public_key, VerificationMode::Prehashed).is_err());
+}
+
+#[test]
+fn openssl_der_signature_verifies_in_raw_mode() {
+    let public_key = hex(OPENSSL_PUBLIC_KEY);
+    let signature = hex(OPENSSL_SIGNATURE);
+
+    assert!(verify_p256(MESSAGE, &signature, &public_key, VerificationMode::Raw).is_ok());
+}
+
+#[test]
+fn p256_crate_signatures_verify_in_both_encodings() {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let signature: Signature = signing_key.sign(MESSAGE);
+
+    let raw = signature.to_bytes();
+    let der = signature.to_der();
+    assert!(verify_p256(MESSAGE, &raw, public_key.as_bytes(), VerificationMode::Raw).is_ok());
+    assert!(verify_p256(MESSAGE, der.as_bytes(), public_key.as_bytes(), VerificationMode::Raw).is_ok());
+
+    let compressed = signing_key.verifying_key().to_encoded_point(true);
+    assert!(verify_p256(MESSAGE, &raw, compressed.as_bytes(), VerificationMode::Raw).is_ok());
+}
+
+#[test]
+fn digest_handed_to_raw_ecdsa_verifies_in_raw_mode() {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    let signature: Signature = signing_key.sign_prehash(&Sha256::digest(MESSAGE)).unwrap();
+
+    assert!(verify_p256(MESSAGE, &signature.to_bytes(), public_key.as_bytes(), VerificationMode::Raw).is_ok());
+    assert!(verify_p256(MESSAGE, &signature.to_bytes(), public_key.as_bytes(), VerificationMode::Prehashed).is_err());
+
+    // Signing the digest as a message hashes it a second time, which is what prehashed mode expects
+    let double_hashed: Signature = signing_key.sign(&Sha256::digest(MESSAGE));
+    assert!(verify_p256(MESSAGE, &double_hashed.to_bytes(), public_key.as_bytes(), VerificationMode::Prehashed).is_ok());
+    assert!(verify_p256(MESSAGE, &double_hashed.to_bytes(), public_key.as_bytes(), VerificationMode::Raw).is_err());
+}
+
+#[test]
+fn tampered_message_is_rejected() {
+    let public_key = hex(WEBCRYPTO_PUBLIC_KEY);
+    let signature = hex(WEBCRYPTO_SIGNATURE);
+
+    let tampered = br#"{"sdp":"v=1","type":"offer"}"#;
+    assert!(verify_p256(tampered, &signature, &public_key, VerificationMode::Raw).is_err());
+}
+
+#[test]
+fn unknown_signature_encoding_is_reported() {
+    let public_key = hex(WEBCRYPTO_PUBLIC_KEY);
+
+    let error = verify_p256(MESSAGE, &[1u8; 63], &public_key, VerificationMode::Raw).unwrap_err();
+    assert!(error.contains("raw r||s"));
+    assert!(error.contains("DER"));
+}
+
+#[test]
+fn ed25519_signatures_verify_regardless_of_mode() {
+    use ed25519_dalek::Signer as _;
+    use video_conference_backend::crypto::{DefaultVerifier, SignatureAlgorithm, SignatureVerifier};
+
+    let signing_key = ed25519_dalek::SigningKey::generate(&mut OsRng);
+    let public_key = signing_key.verifying_key().to_bytes();
+    let signature = signing_key.sign(MESSAGE).to_bytes();
+
+    for mode in [VerificationMode::Raw, VerificationMode::Prehashed] {
+        let verifier = DefaultVerifier::new(mode);
+        assert!(verifier.verify(SignatureAlgorithm::Ed25519, MESSAGE, &signature, &public_key).is_ok());
+        assert!(verifier.verify(SignatureAlgorithm::EcdsaP256, MESSAGE, &signature, &public_key).is_err());
+    }
+}
+
+#[test]
+fn verification_errors_carry_machine_readable_codes() {
+    use video_conference_backend::crypto::{check_ed25519, check_p256};
+
+    let public_key = hex(WEBCRYPTO_PUBLIC_KEY);
+
+    let error = check_p256(MESSAGE, &[1u8; 63], &public_key, VerificationMode::Raw).unwrap_err();
+    assert_eq!(error.code(), "SIG_INVALID_LENGTH");
+    assert_eq!(error.context["actual"], 63);
+
+    let error = check_p256(MESSAGE, &hex(WEBCRYPTO_SIGNATURE), &public_key[..64], VerificationMode::Raw).unwrap_err();
+    assert_eq!(error.code(), "SIG_INVALID_KEY_LENGTH");
+
+    let error = check_ed25519(MESSAGE, &[0u8; 64], &[0u8; 31]).unwrap_err();
+    assert_eq!(error.code(), "SIG_INVALID_KEY_LENGTH");
+}
+
+#[test]
+fn failed_verifications_report_what_was_checked() {
+    use video_conference_backend::crypto::check_p256;
+
+    let public_key = hex(WEBCRYPTO_PUBLIC_KEY);
+    let signature = hex(WEBCRYPTO_SIGNATURE);
+
+    let error = check_p256(b"another message", &signature, &public_key, VerificationMode::Prehashed).unwrap_err();
+    assert_eq!(error.code(), "SIG_VERIFY_FAILED");
+    assert_eq!(error.context["mode"], "prehashed");
+    assert_eq!(error.context["encoding"], "raw");
+    assert!(error.context.contains_key("message_sha256"));
+    // The other mode is not tried, so a failure says nothing about which one would verify
+    assert!(!error.context.contains_key("hint"));
+}
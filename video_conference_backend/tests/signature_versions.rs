@@ -0,0 +1,116 @@
+mod common;
+
+use chrono::Utc;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::crypto;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::{handle_secure_offer, SharedState, SignalingState};
+use common::{add_member, drain, parsed, payloads, signal};
+
+// A version 2 secure-offer from `sender_id`, signed over the whole envelope
+fn envelope_signed_offer(sender_id: &str) -> SignalMessage {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let mut fields = json!({
+        "offer": { "type": "offer", "sdp": "v=0\r\n" },
+        "timestamp": Utc::now().timestamp(),
+        "public_key": signing_key.verifying_key().to_encoded_point(false).as_bytes(),
+        "nonce": rand::random::<[u8; 16]>(),
+        "signature_version": 2,
+    });
+    let mut offer = signal(SignalKind::SecureOffer, fields.clone());
+    offer.sender_id = sender_id.to_string();
+    let envelope = json!({
+        "signal_type": "secure-offer",
+        "sender_id": sender_id,
+        "target_id": null,
+        "seq": offer.seq,
+        "payload": fields,
+    });
+    let signature: Signature = signing_key.sign(crypto::canonicalize(&envelope).as_bytes());
+    fields["signature"] = json!(signature.to_bytes().to_vec());
+    offer.payload = fields.to_string();
+    offer
+}
+
+#[tokio::test]
+async fn version_two_signatures_cover_every_envelope_field() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let message = envelope_signed_offer("client-2");
+    handle_secure_offer(&message, parsed(&message), guest, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut guest_rx, "error").is_empty());
+    assert_eq!(payloads(&mut host_rx, "secure-offer").len(), 1);
+
+    // A version 1 signature would not notice either change
+    let mut added_field = envelope_signed_offer("client-2");
+    let mut fields: serde_json::Value = serde_json::from_str(&added_field.payload).unwrap();
+    fields["e2ee"] = json!(true);
+    added_field.payload = fields.to_string();
+    let mut retargeted = envelope_signed_offer("client-2");
+    retargeted.target_id = Some("client-1".to_string());
+
+    for tampered in [added_field, retargeted] {
+        handle_secure_offer(&tampered, parsed(&tampered), guest, Arc::clone(&state)).await.unwrap();
+        assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "SIG_VERIFY_FAILED");
+    }
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
+
+#[tokio::test]
+async fn unknown_signature_versions_are_refused() {
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let mut offer = envelope_signed_offer("client-2");
+    let mut fields: serde_json::Value = serde_json::from_str(&offer.payload).unwrap();
+    fields["signature_version"] = json!(crypto::SIGNATURE_VERSION + 1);
+    offer.payload = fields.to_string();
+
+    handle_secure_offer(&offer, parsed(&offer), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "error")[0]["code"], "SIG_UNSUPPORTED_VERSION");
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
+
+// A version 1 secure-offer, whose signature covers only the offer and timestamp
+fn offer_signed_as_version_one() -> serde_json::Value {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let mut payload = json!({ "offer": { "type": "offer", "sdp": "v=0\r\n" }, "timestamp": Utc::now().timestamp() });
+    let signature: Signature = signing_key.sign(crypto::canonicalize(&payload).as_bytes());
+    payload["public_key"] = json!(signing_key.verifying_key().to_encoded_point(false).as_bytes());
+    payload["signature"] = json!(signature.to_bytes().to_vec());
+    payload["nonce"] = json!(rand::random::<[u8; 16]>());
+    payload["signature_version"] = json!(1);
+    payload
+}
+
+#[tokio::test]
+async fn version_one_is_refused_even_when_configured() {
+    std::env::set_var("MIN_SIGNATURE_VERSION", "1");
+    let mut inner = SignalingState::new();
+    let (_, mut host_rx) = add_member(&mut inner, 1, "alpha");
+    let (guest, mut guest_rx) = add_member(&mut inner, 2, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut host_rx);
+    drain(&mut guest_rx);
+
+    let offer = signal(SignalKind::SecureOffer, offer_signed_as_version_one());
+    handle_secure_offer(&offer, parsed(&offer), guest, Arc::clone(&state)).await.unwrap();
+    let error = payloads(&mut guest_rx, "error").remove(0);
+    assert_eq!(error["code"], "SIG_UNSUPPORTED_VERSION");
+    assert_eq!(error["context"]["min_version"], crypto::NONCE_SIGNATURE_VERSION);
+    assert!(payloads(&mut host_rx, "secure-offer").is_empty());
+}
@@ -0,0 +1,160 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rusqlite::Connection;
+use std::path::PathBuf;
+use std::sync::Arc;
+use video_conference_backend::abuse::{AbuseStore, Ban, BanKind, SqliteAbuseStore};
+use video_conference_backend::pinning::{KeyPinStore, SqlitePinStore};
+use video_conference_backend::storage::{RoomRecord, RoomStore, SqliteRoomStore, StorageCipher};
+
+const OLD_KEY: [u8; 32] = [1; 32];
+const NEW_KEY: [u8; 32] = [2; 32];
+const FIELD: &str = "rooms.password_hash/alpha";
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("storage-encryption-{}-{}.db", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn record(room_id: &str) -> RoomRecord {
+    RoomRecord {
+        room_id: room_id.to_string(),
+        tenant_id: None,
+        owner: None,
+        max_participants: 8,
+        password_hash: Some("$argon2id$secret-hash".to_string()),
+        lobby_enabled: true,
+        locked: false,
+        listed: false,
+        config: Default::default(),
+        starts_at: None,
+        ends_at: None,
+    }
+}
+
+// The password hash column as it sits on disk
+fn stored_password_hash(path: &PathBuf) -> String {
+    Connection::open(path)
+        .unwrap()
+        .query_row("SELECT password_hash FROM rooms WHERE room_id = 'alpha'", [], |row| row.get(0))
+        .unwrap()
+}
+
+fn ban(kind: BanKind, value: &str) -> Ban {
+    Ban { kind, value: value.to_string(), reason: Some("spam".to_string()), banned_by: None, created_at: 1 }
+}
+
+#[test]
+fn sealed_values_open_only_with_their_key_and_in_their_field() {
+    let cipher = StorageCipher::new(&OLD_KEY, &[]).unwrap();
+    let sealed = cipher.seal("hunter2", FIELD).unwrap();
+    assert!(sealed.starts_with(&format!("enc2:{}:", cipher.key_id())));
+    assert_eq!(cipher.open(&sealed, FIELD).unwrap(), "hunter2");
+    assert!(cipher.open(&sealed, "rooms.password_hash/beta").is_err());
+    assert!(cipher.open(&sealed, "rooms.config/alpha").is_err());
+
+    // Plaintext is refused once a key is set; only rekey reads it
+    assert!(cipher.open("written before encryption", FIELD).is_err());
+    assert_eq!(cipher.open_stale("written before encryption", FIELD).unwrap(), "written before encryption");
+
+    // Keys given as base64 text are the same key
+    let from_text = StorageCipher::new(STANDARD.encode(OLD_KEY).as_bytes(), &[]).unwrap();
+    assert_eq!(from_text.open(&sealed, FIELD).unwrap(), "hunter2");
+
+    let other = StorageCipher::new(&NEW_KEY, &[]).unwrap();
+    assert!(other.open(&sealed, FIELD).is_err());
+    let rotated = StorageCipher::new(&NEW_KEY, &[OLD_KEY.to_vec()]).unwrap();
+    assert_eq!(rotated.open(&sealed, FIELD).unwrap(), "hunter2");
+    assert!(rotated.needs_rekey(&sealed));
+    assert!(!rotated.needs_rekey(&rotated.seal("hunter2", FIELD).unwrap()));
+
+    assert!(StorageCipher::new(&[3; 16], &[]).is_err());
+}
+
+#[test]
+fn rekeying_moves_rooms_from_plaintext_to_the_current_key() {
+    let path = temp_path("rooms");
+    SqliteRoomStore::open(&path).unwrap().save_room(&record("alpha")).unwrap();
+    assert_eq!(stored_password_hash(&path), "$argon2id$secret-hash");
+
+    let old = Arc::new(StorageCipher::new(&OLD_KEY, &[]).unwrap());
+    let store = SqliteRoomStore::open(&path).unwrap().with_cipher(old);
+    assert_eq!(store.rekey().unwrap(), 1);
+    assert_eq!(store.rekey().unwrap(), 0);
+    let sealed = stored_password_hash(&path);
+    assert!(sealed.starts_with("enc2:"), "{}", sealed);
+    assert_eq!(store.load_rooms().unwrap()[0].password_hash.as_deref(), Some("$argon2id$secret-hash"));
+
+    // Encrypted rooms can't be read back without the key
+    assert!(SqliteRoomStore::open(&path).unwrap().load_rooms().is_err());
+
+    let rotated = Arc::new(StorageCipher::new(&NEW_KEY, &[OLD_KEY.to_vec()]).unwrap());
+    let store = SqliteRoomStore::open(&path).unwrap().with_cipher(Arc::clone(&rotated));
+    assert_eq!(store.rekey().unwrap(), 1);
+    assert!(!rotated.needs_rekey(&stored_password_hash(&path)));
+
+    // Once everything is rekeyed the retired key is no longer needed
+    let current_only = Arc::new(StorageCipher::new(&NEW_KEY, &[]).unwrap());
+    let store = SqliteRoomStore::open(&path).unwrap().with_cipher(current_only);
+    assert_eq!(store.load_rooms().unwrap()[0].room_id, "alpha");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn sealed_values_moved_to_another_row_or_written_in_the_clear_are_refused() {
+    let path = temp_path("tampered");
+    let cipher = Arc::new(StorageCipher::new(&OLD_KEY, &[]).unwrap());
+    let store = SqliteRoomStore::open(&path).unwrap().with_cipher(Arc::clone(&cipher));
+    store.save_room(&record("alpha")).unwrap();
+    store.save_room(&RoomRecord { password_hash: None, ..record("beta") }).unwrap();
+    assert_eq!(store.load_rooms().unwrap().len(), 2);
+
+    let conn = Connection::open(&path).unwrap();
+    conn.execute("UPDATE rooms SET password_hash = ?1 WHERE room_id = 'beta'", [stored_password_hash(&path)]).unwrap();
+    assert!(store.load_rooms().is_err());
+
+    conn.execute("UPDATE rooms SET password_hash = 'plaintext' WHERE room_id = 'beta'", []).unwrap();
+    assert!(store.load_rooms().is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn bans_and_pins_are_looked_up_by_a_blind_index_that_follows_the_key() {
+    let path = temp_path("bans");
+    let pins_path = temp_path("pins");
+    SqliteAbuseStore::open(&path).unwrap().ban(&ban(BanKind::Identity, "user:mallory")).unwrap();
+    SqlitePinStore::open(&pins_path).unwrap().pin("user:alice", &[4; 65]).unwrap();
+    let stored = |path: &PathBuf, query: &str| -> String {
+        Connection::open(path).unwrap().query_row(query, [], |row| row.get(0)).unwrap()
+    };
+
+    let old = Arc::new(StorageCipher::new(&OLD_KEY, &[]).unwrap());
+    let abuse = SqliteAbuseStore::open(&path).unwrap().with_cipher(Arc::clone(&old));
+    let pins = SqlitePinStore::open(&pins_path).unwrap().with_cipher(Arc::clone(&old));
+    assert_eq!((abuse.rekey().unwrap(), pins.rekey().unwrap()), (1, 1));
+    let index = stored(&path, "SELECT value FROM bans");
+    assert_ne!(index, "user:mallory");
+    assert_ne!(stored(&pins_path, "SELECT identity FROM key_pins"), "user:alice");
+    assert_eq!(abuse.find_ban(BanKind::Identity, "user:mallory").unwrap().unwrap().reason.as_deref(), Some("spam"));
+    assert_eq!(abuse.bans().unwrap()[0].value, "user:mallory");
+    assert_eq!(pins.pinned_key("user:alice").unwrap(), Some(vec![4; 65]));
+
+    // A rotated key still finds rows under the retired key's index until rekey moves them
+    let rotated = Arc::new(StorageCipher::new(&NEW_KEY, &[OLD_KEY.to_vec()]).unwrap());
+    let abuse = SqliteAbuseStore::open(&path).unwrap().with_cipher(Arc::clone(&rotated));
+    let pins = SqlitePinStore::open(&pins_path).unwrap().with_cipher(Arc::clone(&rotated));
+    assert!(abuse.find_ban(BanKind::Identity, "user:mallory").unwrap().is_some());
+    assert_eq!((abuse.rekey().unwrap(), pins.rekey().unwrap()), (1, 1));
+    assert_ne!(stored(&path, "SELECT value FROM bans"), index);
+
+    let current_only = Arc::new(StorageCipher::new(&NEW_KEY, &[]).unwrap());
+    let abuse = SqliteAbuseStore::open(&path).unwrap().with_cipher(Arc::clone(&current_only));
+    let pins = SqlitePinStore::open(&pins_path).unwrap().with_cipher(current_only);
+    assert!(abuse.find_ban(BanKind::Identity, "user:mallory").unwrap().is_some());
+    assert_eq!(pins.pinned_key("user:alice").unwrap(), Some(vec![4; 65]));
+    assert!(abuse.unban(BanKind::Identity, "user:mallory").unwrap());
+    assert!(abuse.bans().unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&pins_path);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{SignalKind, SignalMessage};
+use video_conference_backend::signaling::{relay_signal, SharedState, SignalingState};
+use common::{add_member, payloads, received, signal};
+
+fn addressed_to(target_id: &str) -> SignalMessage {
+    let mut candidate = signal(SignalKind::IceCandidate, json!({}));
+    candidate.target_id = Some(target_id.to_string());
+    candidate
+}
+
+#[tokio::test]
+async fn addressed_signals_reach_only_their_target() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (_, mut target_rx) = add_member(&mut inner, 2, "alpha");
+    let (_, mut bystander_rx) = add_member(&mut inner, 3, "alpha");
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    relay_signal(&addressed_to("client-2"), sender, Arc::clone(&state)).await.unwrap();
+
+    assert_eq!(received(&mut target_rx).unwrap().signal_type, "ice-candidate");
+    assert!(received(&mut bystander_rx).is_none());
+    assert!(received(&mut sender_rx).is_none());
+}
+
+#[tokio::test]
+async fn unknown_and_unverified_targets_are_reported_to_the_sender() {
+    let mut inner = SignalingState::new();
+    let (sender, mut sender_rx) = add_member(&mut inner, 1, "alpha");
+    let (peer, mut peer_rx) = add_member(&mut inner, 2, "alpha");
+    let (_, mut elsewhere_rx) = add_member(&mut inner, 3, "beta");
+    inner.clients.get_mut(&peer).unwrap().verified = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    relay_signal(&addressed_to("client-3"), sender, Arc::clone(&state)).await.unwrap();
+    relay_signal(&addressed_to("client-2"), sender, Arc::clone(&state)).await.unwrap();
+
+    let errors = payloads(&mut sender_rx, "error");
+    assert_eq!(errors[0]["code"], "target-unknown");
+    assert_eq!(errors[0]["target_id"], "client-3");
+    assert_eq!(errors[1]["code"], "target-unverified");
+    assert!(received(&mut peer_rx).is_none() && received(&mut elsewhere_rx).is_none());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::SignalKind;
+use video_conference_backend::signaling::{
+    handle_create_room, handle_join_room, handle_list_rooms, SharedState, SignalingState,
+};
+use video_conference_backend::tenants::{ApiKeyStore, CachedKeyStore, SqliteKeyStore, Tenant, TenantConfig};
+use common::{add_client, parsed, payloads, signal};
+
+fn tenant(tenant_id: &str, max_participants: Option<usize>) -> Tenant {
+    Tenant {
+        tenant_id: tenant_id.to_string(),
+        config: TenantConfig { max_participants, ..TenantConfig::default() },
+    }
+}
+
+#[test]
+fn keys_resolve_to_their_tenant_until_revoked() {
+    let store = SqliteKeyStore::open_in_memory().unwrap();
+    store.add_key("key-acme", &tenant("acme", Some(4))).unwrap();
+
+    assert_eq!(store.lookup("key-acme").unwrap(), Some(tenant("acme", Some(4))));
+    assert_eq!(store.lookup("key-guess").unwrap(), None);
+
+    store.revoke_key("key-acme").unwrap();
+    assert_eq!(store.lookup("key-acme").unwrap(), None);
+}
+
+#[test]
+fn the_cache_answers_from_memory_until_refreshed() {
+    let store = Arc::new(SqliteKeyStore::open_in_memory().unwrap());
+    store.add_key("key-acme", &tenant("acme", None)).unwrap();
+    let cache = CachedKeyStore::load(Arc::clone(&store)).unwrap();
+    assert_eq!(cache.lookup("key-acme").unwrap(), Some(tenant("acme", None)));
+
+    store.revoke_key("key-acme").unwrap();
+    store.add_key("key-globex", &tenant("globex", None)).unwrap();
+    assert!(cache.lookup("key-acme").unwrap().is_some());
+    cache.refresh().unwrap();
+    assert_eq!(cache.lookup("key-acme").unwrap(), None);
+    assert_eq!(cache.lookup("key-globex").unwrap(), Some(tenant("globex", None)));
+}
+
+#[tokio::test]
+async fn rooms_are_sized_by_and_scoped_to_the_creating_tenant() {
+    let mut inner = SignalingState::new();
+    let (host, _host_rx) = add_client(&mut inner, 1);
+    let (colleague, mut colleague_rx) = add_client(&mut inner, 2);
+    let (outsider, mut outsider_rx) = add_client(&mut inner, 3);
+    let (guest, mut guest_rx) = add_client(&mut inner, 4);
+    inner.clients.get_mut(&host).unwrap().tenant = Some(tenant("acme", Some(4)));
+    inner.clients.get_mut(&colleague).unwrap().tenant = Some(tenant("acme", Some(4)));
+    inner.clients.get_mut(&outsider).unwrap().tenant = Some(tenant("globex", None));
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let create = signal(SignalKind::CreateRoom, json!({ "room_id": "alpha", "max_participants": 50, "listed": true }));
+    handle_create_room(parsed(&create), host, Arc::clone(&state)).await.unwrap();
+    assert_eq!(state.lock().await.rooms["acme/alpha"].max_participants, 4);
+    assert_eq!(state.lock().await.rooms["acme/alpha"].tenant_id.as_deref(), Some("acme"));
+
+    // Each tenant's "alpha" is its own room
+    let join = signal(SignalKind::JoinRoom, json!({ "room_id": "alpha" }));
+    handle_join_room(parsed(&join), colleague, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut colleague_rx, "room-joined")[0]["room_id"], "acme/alpha");
+    handle_join_room(parsed(&join), outsider, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut outsider_rx, "room-joined")[0]["room_id"], "globex/alpha");
+    assert_eq!(state.lock().await.rooms["acme/alpha"].members.len(), 1);
+
+    let join_scoped = signal(SignalKind::JoinRoom, json!({ "room_id": "acme/alpha" }));
+    handle_join_room(parsed(&join_scoped), guest, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut guest_rx, "join-rejected")[0]["reason"], "room-not-allowed");
+
+    handle_list_rooms(parsed(&signal(SignalKind::ListRooms, json!({}))), outsider, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut outsider_rx, "room-list")[0]["rooms"].as_array().unwrap().is_empty());
+
+    // Clients of another tenant cannot be reached by id
+    let state = state.lock().await;
+    assert_eq!(state.tenant_client(colleague, "client-1").map(|client| client.address), Some(host));
+    assert!(state.tenant_client(outsider, "client-1").is_none());
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use video_conference_backend::signaling::turn::handle_turn_credentials;
+use video_conference_backend::signaling::{SharedState, SignalingState};
+use video_conference_backend::turn::issue_credentials;
+use common::{add_client, drain, payloads};
+
+#[test]
+fn credentials_follow_the_coturn_shared_secret_scheme() {
+    let uris = vec!["turn:turn.example.com:3478".to_string()];
+    let credentials = issue_credentials(b"north-secret", "alice", Duration::from_secs(3600), 1_700_000_000, uris.clone());
+
+    // base64(HMAC-SHA1("north-secret", "1700003600:alice")), as coturn computes it
+    assert_eq!(credentials.username, "1700003600:alice");
+    assert_eq!(credentials.credential, "Ji4axghh2/aljHmsPgH9mtDGVLw=");
+    assert_eq!(credentials.ttl, 3600);
+    assert_eq!(credentials.uris, uris);
+
+    let other = issue_credentials(b"south-secret", "alice", Duration::from_secs(3600), 1_700_000_000, uris);
+    assert_ne!(other.credential, credentials.credential);
+}
+
+#[tokio::test]
+async fn credentials_are_issued_per_client_only_when_a_secret_is_set() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_turn_credentials(addr, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "turn-unavailable");
+
+    state.lock().await.turn_secret = Some(b"north-secret".to_vec());
+    std::env::set_var("TURN_URIS", "turn:turn.example.com:3478,turns:turn.example.com:5349");
+    std::env::set_var("TURN_CREDENTIAL_TTL_SECS", "600");
+    handle_turn_credentials(addr, Arc::clone(&state)).await.unwrap();
+    let credentials = payloads(&mut rx, "turn-credentials").remove(0);
+    assert!(credentials["username"].as_str().unwrap().ends_with(":client-1"));
+    assert_eq!(credentials["ttl"], 600);
+    assert_eq!(credentials["uris"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn unverified_clients_get_no_credentials() {
+    let mut inner = SignalingState::new();
+    inner.turn_secret = Some(b"north-secret".to_vec());
+    let (addr, mut rx) = add_client(&mut inner, 1);
+    inner.clients.get_mut(&addr).unwrap().verified = false;
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    handle_turn_credentials(addr, Arc::clone(&state)).await.unwrap();
+    let signals = drain(&mut rx);
+    assert_eq!(signals.len(), 1);
+    assert_eq!(signals[0].1["code"], "unverified-sender");
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::{Signal, SignalKind, SignalMessage};
+use video_conference_backend::signaling::{dispatch_signal, SharedState, SignalingState};
+use common::{add_member, drain, payloads, session_token, signal};
+
+fn parse(message: &SignalMessage) -> Result<Signal, serde::de::value::Error> {
+    Signal::from_message(message)
+}
+
+#[test]
+fn payloads_parse_into_their_signal_type() {
+    let kick = parse(&signal(SignalKind::Kick, json!({ "client_id": "client-2", "reason": "spam" }))).unwrap();
+    let Signal::Kick(payload) = kick else {
+        panic!("kick parsed as {:?}", kick);
+    };
+    assert_eq!((payload.client_id.as_str(), payload.ban, payload.reason.as_deref()), ("client-2", false, Some("spam")));
+
+    // Signals that take nothing don't care what the payload holds
+    let mut raise = signal(SignalKind::RaiseHand, json!({}));
+    raise.payload = String::new();
+    assert!(matches!(parse(&raise), Ok(Signal::RaiseHand)));
+    assert!(matches!(parse(&signal(SignalKind::Unknown, json!({}))), Ok(Signal::Unknown)));
+
+    let error = parse(&signal(SignalKind::Kick, json!({ "reason": "spam" }))).unwrap_err();
+    assert!(error.to_string().contains("client_id"), "{}", error);
+
+    // A frame as it comes off the wire deserializes straight into its signal
+    let frame = r#"{"signal_type":"lower-hand","payload":"{\"client_id\":\"client-2\"}","sender_id":"","timestamp":0,"signature":null,"target_id":null}"#;
+    let lower: Signal = serde_json::from_str(frame).unwrap();
+    assert!(matches!(lower, Signal::LowerHand(payload) if payload.client_id.as_deref() == Some("client-2")));
+}
+
+#[test]
+fn every_signal_names_the_kind_its_signal_type_does() {
+    for (kind, payload) in [(SignalKind::RaiseHand, json!({})), (SignalKind::Kick, json!({ "client_id": "client-2" })), (SignalKind::Unknown, json!({}))] {
+        let message = signal(kind, payload);
+        assert_eq!(SignalKind::from(&parse(&message).unwrap()), kind);
+        assert_eq!(SignalKind::of(&message.signal_type), kind);
+    }
+    assert_eq!(SignalKind::of("ice-candidate"), SignalKind::IceCandidate);
+    assert_eq!(SignalKind::of("IceCandidate"), SignalKind::Unknown);
+}
+
+#[tokio::test]
+async fn payloads_of_the_wrong_shape_are_never_handled() {
+    let mut inner = SignalingState::new();
+    let (addr, mut rx) = add_member(&mut inner, 1, "alpha");
+    let token = session_token(&mut inner, addr);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    drain(&mut rx);
+    let mut client_id = "client-1".to_string();
+
+    let lower = signal(SignalKind::LowerHand, json!({ "client_id": 5 }));
+    dispatch_signal(&lower, Some(&token), addr, &mut client_id, Arc::clone(&state)).await.unwrap();
+    let error = payloads(&mut rx, "error").remove(0);
+    assert_eq!(error["code"], "invalid-payload");
+    assert!(error["message"].as_str().unwrap().starts_with("Invalid lower-hand payload"), "{}", error);
+
+    let unknown = signal(SignalKind::Unknown, json!({}));
+    dispatch_signal(&unknown, Some(&token), addr, &mut client_id, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut rx, "error")[0]["code"], "unknown-signal");
+}
@@ -0,0 +1,56 @@
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use std::sync::Arc;
+use video_conference_backend::crypto::{
+    AsyncVerifier, DefaultVerifier, SignatureAlgorithm, VerificationMode, VerificationPool, VerificationRequest,
+};
+
+// A request for `message`, signed by `signing_key` but claiming `public_key`
+fn request(signing_key: &SigningKey, public_key: &SigningKey, message: &[u8]) -> VerificationRequest {
+    let signature: Signature = signing_key.sign(message);
+    let public_key = public_key.verifying_key().to_encoded_point(false);
+    VerificationRequest::new(SignatureAlgorithm::EcdsaP256, message, &signature.to_bytes(), public_key.as_bytes())
+}
+
+#[tokio::test]
+async fn batched_results_come_back_in_request_order() {
+    let verifier = Arc::new(DefaultVerifier::new(VerificationMode::Raw));
+    let pool = VerificationPool::spawn(verifier, 2, 4);
+    let alice = SigningKey::random(&mut OsRng);
+    let mallory = SigningKey::random(&mut OsRng);
+
+    // More requests than a batch holds, with the forgeries scattered through them
+    let requests: Vec<_> = (0..10u8)
+        .map(|i| {
+            let signer = if i % 3 == 0 { &mallory } else { &alice };
+            request(signer, &alice, &[i; 32])
+        })
+        .collect();
+    let results = pool.verify_all(requests).await;
+
+    assert_eq!(results.len(), 10);
+    for (i, result) in results.iter().enumerate() {
+        match result {
+            Err(error) => {
+                assert_eq!(i % 3, 0, "request {} should have verified", i);
+                assert_eq!(error.code(), "SIG_VERIFY_FAILED");
+            }
+            Ok(()) => assert_ne!(i % 3, 0, "request {} should have failed", i),
+        }
+    }
+}
+
+#[tokio::test]
+async fn pooled_and_blocking_verifiers_agree() {
+    let verifier = Arc::new(DefaultVerifier::new(VerificationMode::Raw));
+    let pooled = AsyncVerifier::Pooled(VerificationPool::spawn(verifier.clone(), 1, 8));
+    let blocking = AsyncVerifier::Blocking(verifier);
+    let alice = SigningKey::random(&mut OsRng);
+    let mallory = SigningKey::random(&mut OsRng);
+
+    for verifier in [pooled, blocking] {
+        assert!(verifier.verify(request(&alice, &alice, b"hello")).await.is_ok());
+        assert!(verifier.verify(request(&mallory, &alice, b"hello")).await.is_err());
+    }
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ciborium::value::Value;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use video_conference_backend::auth::webauthn::verify_registration;
+use video_conference_backend::auth::{
+    Attestation, Ceremony, Credential, CredentialStore, PendingCeremony, RelyingParty, SqliteCredentialStore,
+};
+use video_conference_backend::crypto::SignatureAlgorithm;
+use video_conference_backend::models::{Client, SignalKind};
+use video_conference_backend::pinning;
+use video_conference_backend::signaling::{webauthn, SharedState, SignalingState};
+use common::{add_client, parsed, payloads, signal};
+
+fn relying_party() -> RelyingParty {
+    RelyingParty { rp_id: "example.com".to_string(), origin: "https://example.com".to_string() }
+}
+
+fn pending(challenge: &str) -> PendingCeremony {
+    PendingCeremony {
+        ceremony: Ceremony::Registration,
+        challenge: challenge.to_string(),
+        user_id: "linus".to_string(),
+        open: false,
+    }
+}
+
+fn client_data(challenge: &str) -> Vec<u8> {
+    json!({ "type": "webauthn.create", "challenge": challenge, "origin": "https://example.com" })
+        .to_string()
+        .into_bytes()
+}
+
+fn cbor(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(value, &mut out).unwrap();
+    out
+}
+
+// Authenticator data attesting an ES256 credential for `signing_key`
+fn auth_data(credential_id: &[u8], signing_key: &SigningKey) -> Vec<u8> {
+    let point = signing_key.verifying_key().to_encoded_point(false);
+    let cose_key = Value::Map(vec![
+        (Value::from(1), Value::from(2)),
+        (Value::from(3), Value::from(-7)),
+        (Value::from(-1), Value::from(1)),
+        (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+        (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+    ]);
+
+    let mut data = Sha256::digest(b"example.com").to_vec();
+    data.push(0x41);
+    data.extend_from_slice(&0u32.to_be_bytes());
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+    data.extend_from_slice(credential_id);
+    data.extend(cbor(&cose_key));
+    data
+}
+
+fn attestation(format: &str, statement: Vec<(Value, Value)>, auth_data: Vec<u8>) -> Vec<u8> {
+    cbor(&Value::Map(vec![
+        (Value::from("fmt"), Value::from(format)),
+        (Value::from("attStmt"), Value::Map(statement)),
+        (Value::from("authData"), Value::Bytes(auth_data)),
+    ]))
+}
+
+// Packed self attestation declaring `alg`, signed by the credential key
+fn self_attestation(signing_key: &SigningKey, alg: i64, challenge: &str) -> Vec<u8> {
+    let auth_data = auth_data(b"credential", signing_key);
+    let mut signed = auth_data.clone();
+    signed.extend_from_slice(&Sha256::digest(client_data(challenge)));
+    let signature: Signature = signing_key.sign(&signed);
+    let statement = vec![
+        (Value::from("alg"), Value::from(alg)),
+        (Value::from("sig"), Value::Bytes(signature.to_der().as_bytes().to_vec())),
+    ];
+    attestation("packed", statement, auth_data)
+}
+
+#[test]
+fn self_attestation_must_declare_the_credential_algorithm() {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let attested = self_attestation(&signing_key, -7, "challenge");
+    let credential = verify_registration(&relying_party(), &pending("challenge"), &client_data("challenge"), &attested).unwrap();
+    assert_eq!(credential.public_key, signing_key.verifying_key().to_encoded_point(false).as_bytes());
+    assert_eq!(credential.algorithm, SignatureAlgorithm::EcdsaP256);
+    assert_eq!(credential.attestation, Attestation::SelfAttested);
+
+    let mislabelled = self_attestation(&signing_key, -8, "challenge");
+    let error = verify_registration(&relying_party(), &pending("challenge"), &client_data("challenge"), &mislabelled).unwrap_err();
+    assert!(error.contains("does not match"), "{}", error);
+}
+
+#[test]
+fn an_unvalidated_x5c_chain_counts_for_no_more_than_none() {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let statement = vec![
+        (Value::from("alg"), Value::from(-7)),
+        (Value::from("sig"), Value::Bytes(vec![0; 70])),
+        (Value::from("x5c"), Value::Array(vec![Value::Bytes(b"any certificate".to_vec())])),
+    ];
+    let attested = attestation("packed", statement, auth_data(b"credential", &signing_key));
+
+    let credential = verify_registration(&relying_party(), &pending("challenge"), &client_data("challenge"), &attested).unwrap();
+    assert_eq!(credential.public_key, signing_key.verifying_key().to_encoded_point(false).as_bytes());
+    assert_eq!(credential.attestation, Attestation::None);
+
+    let store = SqliteCredentialStore::open_in_memory().unwrap();
+    store.save_credential(&credential).unwrap();
+    assert_eq!(store.credential(b"credential").unwrap().unwrap().attestation, Attestation::None);
+
+    let unknown = attestation("tpm", vec![], auth_data(b"credential", &signing_key));
+    assert!(verify_registration(&relying_party(), &pending("challenge"), &client_data("challenge"), &unknown).is_err());
+}
+
+#[tokio::test]
+async fn open_registration_only_creates_new_users() {
+    std::env::set_var("WEBAUTHN_OPEN_REGISTRATION", "true");
+    let credentials = Arc::new(SqliteCredentialStore::open_in_memory().unwrap());
+    credentials.save_credential(&Credential {
+        credential_id: vec![1, 2, 3],
+        user_id: "grace".to_string(),
+        public_key: vec![4, 5, 6],
+        algorithm: SignatureAlgorithm::EcdsaP256,
+        sign_count: 0,
+        attestation: Attestation::None,
+    }).unwrap();
+    let mut inner = SignalingState::new();
+    inner.relying_party = Some(relying_party());
+    inner.credentials = Some(credentials.clone());
+    let (first, mut first_rx) = add_client(&mut inner, 1);
+    let (second, mut second_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+
+    let begin = |user_id: &str| signal(SignalKind::WebauthnRegisterBegin, json!({ "user_id": user_id }));
+    webauthn::handle_register_begin(parsed(&begin("grace")), first, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut first_rx, "error")[0]["code"], "registration-not-allowed");
+    assert!(state.lock().await.clients[&first].webauthn.is_none());
+
+    // Both connections start claiming the same new user; only the first to finish gets it
+    webauthn::handle_register_begin(parsed(&begin("linus")), first, Arc::clone(&state)).await.unwrap();
+    webauthn::handle_register_begin(parsed(&begin("linus")), second, Arc::clone(&state)).await.unwrap();
+    let finish = |rx: &mut mpsc::Receiver<_>| {
+        let options = payloads(rx, "webauthn-register-options").remove(0);
+        let challenge = options["challenge"].as_str().unwrap().to_string();
+        signal(SignalKind::WebauthnRegister, json!({
+            "client_data_json": URL_SAFE_NO_PAD.encode(client_data(&challenge)),
+            "attestation_object": URL_SAFE_NO_PAD.encode(attestation(
+                "none",
+                vec![],
+                auth_data(&rand::random::<[u8; 16]>(), &SigningKey::random(&mut OsRng))
+            )),
+        }))
+    };
+    let first_finish = finish(&mut first_rx);
+    let second_finish = finish(&mut second_rx);
+
+    webauthn::handle_register(parsed(&first_finish), first, Arc::clone(&state)).await.unwrap();
+    let registered = payloads(&mut first_rx, "webauthn-registered").remove(0);
+    assert_eq!(registered["user_id"], "linus");
+    assert_eq!(registered["attestation"], "none");
+    webauthn::handle_register(parsed(&second_finish), second, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut second_rx, "error")[0]["code"], "registration-not-allowed");
+    assert_eq!(credentials.credentials_for("linus").unwrap().len(), 1);
+}
+
+#[test]
+fn webauthn_users_are_named_apart_from_token_subjects() {
+    let (tx, _rx) = mpsc::channel(1);
+    let mut client = Client::new(tx, "client-1".to_string(), "127.0.0.1:1".parse().unwrap());
+    client.webauthn_user = Some("grace".to_string());
+    assert_eq!(pinning::identity(&client), "webauthn:grace");
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_conference_backend::models::message::{JoinRoomPayload, WhiteboardOpPayload, WhiteboardSyncPayload};
+use video_conference_backend::models::{Role, Whiteboard, WhiteboardOp, WhiteboardSnapshot};
+use video_conference_backend::signaling::{handle_join_room, whiteboard, SharedState, SignalingState};
+use common::{add_client, drain, payloads};
+
+fn join() -> JoinRoomPayload {
+    JoinRoomPayload {
+        room_id: "alpha".to_string(),
+        max_participants: None,
+        password: None,
+        invite_token: None,
+        capabilities: None,
+    }
+}
+
+fn stroke(n: u64) -> WhiteboardOpPayload {
+    WhiteboardOpPayload { op: json!({ "kind": "stroke", "points": [n, n] }) }
+}
+
+#[test]
+fn snapshots_fold_in_the_operations_they_cover() {
+    let mut board = Whiteboard::default();
+    for n in 1..=4 {
+        let op = board.append(|seq| WhiteboardOp { seq, client_id: "client-1".to_string(), op: json!(n) });
+        assert_eq!(op.seq, n);
+    }
+
+    assert!(!board.compact(WhiteboardSnapshot { seq: 5, state: json!("ahead") }));
+    assert!(board.compact(WhiteboardSnapshot { seq: 3, state: json!("at 3") }));
+    assert!(!board.compact(WhiteboardSnapshot { seq: 2, state: json!("older") }));
+    assert_eq!(board.pending(), 1);
+
+    let (snapshot, ops) = board.since(0);
+    assert_eq!(snapshot.unwrap().state, "at 3");
+    assert_eq!(ops.iter().map(|op| op.seq).collect::<Vec<_>>(), [4]);
+    let (snapshot, ops) = board.since(3);
+    assert!(snapshot.is_none());
+    assert_eq!(ops.len(), 1);
+    assert!(board.since(4).1.is_empty());
+}
+
+#[tokio::test]
+async fn operations_are_sequenced_for_the_room_and_replayed_to_late_joiners() {
+    std::env::set_var("WHITEBOARD_SNAPSHOT_AFTER", "2");
+    std::env::set_var("WHITEBOARD_PAGE_SIZE", "2");
+    std::env::set_var("WHITEBOARD_MAX_SNAPSHOT_SIZE", "64");
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let (b, mut b_rx) = add_client(&mut inner, 2);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut a_rx, "whiteboard-state").is_empty());
+
+    for n in 1..=5 {
+        whiteboard::handle_whiteboard_op(stroke(n), a, Arc::clone(&state)).await.unwrap();
+    }
+    let received = drain(&mut a_rx);
+    let ops: Vec<_> = received.iter().filter(|(kind, _)| kind == "whiteboard-op").map(|(_, op)| op).collect();
+    assert_eq!(ops.iter().map(|op| op["seq"].as_u64().unwrap()).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+    assert_eq!(ops[0]["client_id"], "client-1");
+    let requests: Vec<_> = received.iter().filter(|(kind, _)| kind == "whiteboard-snapshot-request").collect();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].1["seq"], 2);
+
+    handle_join_room(join(), b, Arc::clone(&state)).await.unwrap();
+    let snapshot = WhiteboardSnapshot { seq: 2, state: json!({ "strokes": 2 }) };
+    whiteboard::handle_whiteboard_snapshot(snapshot.clone(), b, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut b_rx, "error")[0]["code"], "snapshot-not-requested");
+    let oversized = WhiteboardSnapshot { seq: 2, state: json!({ "strokes": "x".repeat(64) }) };
+    whiteboard::handle_whiteboard_snapshot(oversized, a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "invalid-snapshot");
+    whiteboard::handle_whiteboard_snapshot(snapshot, a, Arc::clone(&state)).await.unwrap();
+    assert!(payloads(&mut a_rx, "error").is_empty());
+
+    whiteboard::handle_whiteboard_sync(WhiteboardSyncPayload::default(), b, Arc::clone(&state)).await.unwrap();
+    let pages = payloads(&mut b_rx, "whiteboard-state");
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[0]["seq"], 5);
+    assert_eq!(pages[0]["snapshot"]["state"]["strokes"], 2);
+    assert_eq!(pages[0]["ops"][0]["op"]["points"], json!([3, 3]));
+    assert_eq!((pages[0]["ops"].as_array().unwrap().len(), &pages[0]["complete"]), (2, &json!(false)));
+    assert!(pages[1]["snapshot"].is_null());
+    assert_eq!(pages[1]["ops"][0]["seq"], 5);
+    assert_eq!(pages[1]["complete"], true);
+
+    whiteboard::handle_whiteboard_sync(WhiteboardSyncPayload { since: Some(5) }, b, Arc::clone(&state)).await.unwrap();
+    let board = payloads(&mut b_rx, "whiteboard-state");
+    assert!(board[0]["snapshot"].is_null());
+    assert_eq!(board[0]["ops"], json!([]));
+    assert_eq!(board[0]["complete"], true);
+}
+
+#[tokio::test]
+async fn viewers_watch_but_do_not_draw() {
+    let mut inner = SignalingState::new();
+    let (a, mut a_rx) = add_client(&mut inner, 1);
+    let state: SharedState = Arc::new(Mutex::new(inner));
+    handle_join_room(join(), a, Arc::clone(&state)).await.unwrap();
+    state.lock().await.clients.get_mut(&a).unwrap().role = Role::Viewer;
+
+    whiteboard::handle_whiteboard_op(stroke(1), a, Arc::clone(&state)).await.unwrap();
+    assert_eq!(payloads(&mut a_rx, "error")[0]["code"], "insufficient-role");
+    assert_eq!(state.lock().await.rooms["alpha"].whiteboard.seq(), 0);
+}
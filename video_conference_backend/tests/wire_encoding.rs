@@ -0,0 +1,135 @@
+mod common;
+
+use prost::Message as _;
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use video_conference_backend::models::proto::Envelope;
+use video_conference_backend::models::{SignalMessage, WireEncoding};
+use video_conference_backend::signaling::handlers::send_signal;
+use video_conference_backend::signaling::SignalingState;
+use common::add_client;
+
+// What a browser sends: only the fields it knows about, payload as JSON text
+const BROWSER_OFFER: &str = r#"{"signal_type":"secure-offer","payload":"{\"offer\":{\"type\":\"offer\",\"sdp\":\"v=0\\r\\n\"},\"public_key\":[4,1,2],\"signature\":[9,8,7],\"nonce\":[1]}","sender_id":"","timestamp":1700000000000,"signature":null,"target_id":"peer-2","message_id":"6f1c1b8e-3f0a-4a43-9a57-3b0f5f0c2d11"}"#;
+
+// What the server relays: every optional envelope field set
+const RELAYED_CANDIDATE: &str = r#"{"signal_type":"ice-candidate","payload":"{\"candidate\":\"candidate:1 1 udp 2122260223 192.0.2.1 54400 typ host\",\"sdpMid\":\"0\",\"sdpMLineIndex\":0}","sender_id":"client-1","timestamp":1700000001,"signature":[1,2,3,255],"target_id":null,"seq":42,"relay_seq":7,"server_signature":[0,128,255],"correlation_id":"req-9","message_id":"0e7d5a8c-58f4-4b0e-8a8f-5f4c2f1e9b20"}"#;
+
+// A server notice, with the sender id peers never use
+const SERVER_NOTICE: &str = r#"{"signal_type":"error","payload":"{\"code\":\"target-unknown\",\"numeric_code\":1007}","sender_id":"server","timestamp":1700000002,"signature":null,"target_id":null}"#;
+
+const SAMPLES: &[&str] = &[BROWSER_OFFER, RELAYED_CANDIDATE, SERVER_NOTICE];
+
+const BINARY_ENCODINGS: &[WireEncoding] = &[WireEncoding::MessagePack, WireEncoding::Cbor, WireEncoding::Protobuf];
+
+fn value(signal: &SignalMessage) -> Value {
+    serde_json::to_value(signal).unwrap()
+}
+
+#[test]
+fn binary_encodings_round_trip_every_field() {
+    for encoding in BINARY_ENCODINGS {
+        for sample in SAMPLES {
+            let signal: SignalMessage = serde_json::from_str(sample).unwrap();
+
+            let frame = encoding.encode(&signal).unwrap();
+            assert!(matches!(frame, Message::Binary(_)));
+            let decoded = encoding.decode(&frame).unwrap();
+
+            assert_eq!(value(&decoded), value(&signal));
+        }
+    }
+}
+
+#[test]
+fn binary_encodings_and_json_decode_to_the_same_message() {
+    for encoding in BINARY_ENCODINGS {
+        for sample in SAMPLES {
+            let from_json = WireEncoding::Json.decode(&Message::Text(sample.to_string())).unwrap();
+            let packed = encoding.encode(&from_json).unwrap();
+            let from_binary = encoding.decode(&packed).unwrap();
+
+            let json_again = WireEncoding::Json.encode(&from_binary).unwrap();
+            assert_eq!(json_again, WireEncoding::Json.encode(&from_json).unwrap());
+        }
+    }
+}
+
+#[tokio::test]
+async fn signals_are_queued_in_the_connections_own_encoding() {
+    let mut state = SignalingState::new();
+    let (addr, mut rx) = add_client(&mut state, 1);
+    for encoding in BINARY_ENCODINGS.iter().chain([&WireEncoding::Json]) {
+        let client = state.clients.get_mut(&addr).unwrap();
+        client.encoding = *encoding;
+        let notice: SignalMessage = serde_json::from_str(SERVER_NOTICE).unwrap();
+        send_signal(client, &notice).await.unwrap();
+
+        let queued = rx.try_recv().unwrap();
+        assert_eq!(matches!(queued, Message::Binary(_)), *encoding != WireEncoding::Json);
+        assert_eq!(value(&encoding.decode(&queued).unwrap()), value(&notice));
+    }
+}
+
+#[test]
+fn messagepack_is_smaller_than_json_for_candidates() {
+    let signal: SignalMessage = serde_json::from_str(RELAYED_CANDIDATE).unwrap();
+
+    let json = WireEncoding::Json.encode(&signal).unwrap();
+    let packed = WireEncoding::MessagePack.encode(&signal).unwrap();
+
+    assert!(packed.len() < json.len());
+}
+
+#[test]
+fn frames_of_the_wrong_kind_are_refused() {
+    let signal: SignalMessage = serde_json::from_str(SERVER_NOTICE).unwrap();
+    let text = WireEncoding::Json.encode(&signal).unwrap();
+
+    for encoding in BINARY_ENCODINGS {
+        let binary = encoding.encode(&signal).unwrap();
+        assert!(encoding.decode(&text).is_err());
+        assert!(WireEncoding::Json.decode(&binary).is_err());
+        assert!(encoding.decode(&Message::Binary(vec![0xc1, 0xff])).is_err());
+    }
+}
+
+#[test]
+fn correlation_id_is_found_in_undecodable_frames() {
+    let text = Message::Text(r#"{"signal_type":7,"correlation_id":"req-1"}"#.to_string());
+    assert_eq!(WireEncoding::Json.peek_correlation_id(&text), Some("req-1".to_string()));
+
+    let loose = serde_json::json!({ "signal_type": 7, "correlation_id": "req-2" });
+    let binary = Message::Binary(rmp_serde::to_vec_named(&loose).unwrap());
+    assert_eq!(WireEncoding::MessagePack.peek_correlation_id(&binary), Some("req-2".to_string()));
+
+    let mut cbor = Vec::new();
+    ciborium::ser::into_writer(&loose, &mut cbor).unwrap();
+    assert_eq!(WireEncoding::Cbor.peek_correlation_id(&Message::Binary(cbor)), Some("req-2".to_string()));
+
+    // Field 11 set, followed by a field 1 that is not a string
+    let proto = Message::Binary(vec![0x5a, 0x05, b'r', b'e', b'q', b'-', b'3', 0x08, 0x07]);
+    assert!(WireEncoding::Protobuf.decode(&proto).is_err());
+    assert_eq!(WireEncoding::Protobuf.peek_correlation_id(&proto), Some("req-3".to_string()));
+}
+
+#[test]
+fn protobuf_frames_match_the_published_field_numbers() {
+    let signal: SignalMessage = serde_json::from_str(RELAYED_CANDIDATE).unwrap();
+    let Message::Binary(bytes) = WireEncoding::Protobuf.encode(&signal).unwrap() else {
+        panic!("expected a binary frame");
+    };
+
+    let envelope = Envelope::decode(bytes.as_slice()).unwrap();
+    assert_eq!(envelope.signal_type, "ice-candidate");
+    assert_eq!(envelope.seq, Some(42));
+    assert_eq!(envelope.relay_seq, Some(7));
+    assert_eq!(envelope.message_id.as_deref(), Some("0e7d5a8c-58f4-4b0e-8a8f-5f4c2f1e9b20"));
+    // signal_type is field 1, a length-delimited string
+    assert_eq!(&bytes[..2], &[0x0a, 13]);
+
+    let proto = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/proto/signaling.proto")).unwrap();
+    for (field, tag) in [("signal_type", 1), ("payload", 2), ("relay_seq", 8), ("correlation_id", 11), ("message_id", 12)] {
+        assert!(proto.contains(&format!(" {} = {};", field, tag)));
+    }
+}